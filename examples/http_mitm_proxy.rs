@@ -3,8 +3,9 @@
 //! Note that this MITM proxy is not production ready, and is only meant
 //! to show you how one might start. You might want to address the following:
 //!
-//! - Load in your tls mitm cert/key pair from file or ACME
-//! - Make sure your clients trust the MITM cert
+//! - Load in your own MITM CA cert/key pair instead of generating a new one on each run
+//!   (supported by this example using the `RAMA_MITM_CA_CERT` and `RAMA_MITM_CA_KEY` env variables)
+//! - Make sure your clients trust the MITM CA cert
 //! - Do not enforce the Application protocol and instead convert requests when needed,
 //!   e.g. in this example we _always_ map the protocol between two ends,
 //!   even though it might be better to be able to map bidirectionaly between http versions
@@ -25,11 +26,13 @@
 //!
 //! # Expected output
 //!
-//! The server will start and listen on `:62017`. You can use `curl` to interact with the service:
+//! The server will start and listen on `:62017`, after writing its MITM CA cert
+//! to the temporary directory (e.g. `/tmp/rama_mitm_ca.pem`).
+//! You can use `curl` to interact with the service:
 //!
 //! ```sh
 //! curl -v -x http://127.0.0.1:62017 --proxy-user 'john:secret' http://www.example.com/
-//! curl -k -v -x http://127.0.0.1:62017 --proxy-user 'john:secret' https://www.example.com/
+//! curl --cacert /tmp/rama_mitm_ca.pem -v -x http://127.0.0.1:62017 --proxy-user 'john:secret' https://www.example.com/
 //! ```
 
 use rama::{
@@ -57,14 +60,12 @@ use rama::{
     service::service_fn,
     tcp::server::TcpListener,
     tls::{
-        dep::rcgen::{self, KeyPair},
+        mitm::CertificateAuthority,
         rustls::{
-            dep::{
-                pki_types::{CertificateDer, PrivatePkcs8KeyDer},
-                rustls::ServerConfig,
-            },
-            server::TlsAcceptorLayer,
+            dep::rustls::ServerConfig,
+            server::{MitmServerConfigProvider, TlsAcceptorLayer, TlsClientConfigHandler},
         },
+        types::ApplicationProtocol,
     },
     Layer, Service,
 };
@@ -75,6 +76,7 @@ use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, Env
 #[derive(Debug, Clone)]
 struct State {
     mitm_tls_config: Arc<ServerConfig>,
+    mitm_config_provider: MitmServerConfigProvider,
 }
 
 type Context = rama::Context<State>;
@@ -90,12 +92,22 @@ async fn main() -> Result<(), BoxError> {
         )
         .init();
 
-    let mitm_tls_config = Arc::new(
-        mitm_tls_server_credentials()
-            .map_err(OpaqueError::from_boxed)
-            .context("generate self-signed mitm tls cert")?,
-    );
-    let state = State { mitm_tls_config };
+    let mitm_authority = mitm_certificate_authority()
+        .map_err(OpaqueError::from_boxed)
+        .context("load mitm certificate authority")?;
+    let mitm_config_provider =
+        MitmServerConfigProvider::new(mitm_authority).with_alpn_protocols(vec![
+            ApplicationProtocol::HTTP_2,
+            ApplicationProtocol::HTTP_11,
+        ]);
+    // used for clients which do not indicate the server name (SNI)
+    let mitm_tls_config = mitm_config_provider
+        .server_config_for(&"localhost".parse()?)
+        .context("issue default mitm tls cert")?;
+    let state = State {
+        mitm_tls_config,
+        mitm_config_provider,
+    };
 
     let graceful = rama::graceful::Shutdown::default();
 
@@ -167,8 +179,12 @@ async fn http_connect_proxy(mut ctx: Context, upgraded: Upgraded) -> Result<(),
 
     let http_transport_service = HttpServer::auto(ctx.executor().clone()).service(http_service);
 
-    let https_service =
-        TlsAcceptorLayer::new(ctx.state().mitm_tls_config.clone()).layer(http_transport_service);
+    let https_service = TlsAcceptorLayer::with_client_config_handler(
+        ctx.state().mitm_tls_config.clone(),
+        TlsClientConfigHandler::default()
+            .server_config_provider(ctx.state().mitm_config_provider.clone()),
+    )
+    .layer(http_transport_service);
 
     https_service
         .serve(ctx, upgraded)
@@ -214,43 +230,24 @@ async fn http_mitm_proxy(ctx: Context, req: Request) -> Result<Response, Infalli
     }
 }
 
-// NOTE: for a production service you ideally use
-// an issued TLS cert (if possible via ACME). Or at the very least
-// load it in from memory/file, so that your clients can install the certificate for trust.
-fn mitm_tls_server_credentials() -> Result<ServerConfig, BoxError> {
-    // Create an issuer CA cert.
-    let alg = &rcgen::PKCS_ECDSA_P256_SHA256;
-    let ca_key_pair = KeyPair::generate_for(alg).expect("generate ca key pair");
-
-    let mut ca_params = rcgen::CertificateParams::new(Vec::new()).expect("create ca params");
-    ca_params
-        .distinguished_name
-        .push(rcgen::DnType::OrganizationName, "Rustls Server Acceptor");
-    ca_params
-        .distinguished_name
-        .push(rcgen::DnType::CommonName, "Example CA");
-    ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
-    ca_params.key_usages = vec![
-        rcgen::KeyUsagePurpose::KeyCertSign,
-        rcgen::KeyUsagePurpose::DigitalSignature,
-        rcgen::KeyUsagePurpose::CrlSign,
-    ];
-    let ca_cert = ca_params.self_signed(&ca_key_pair)?;
-
-    let server_key_pair = KeyPair::generate_for(alg)?;
-    let mut server_ee_params = rcgen::CertificateParams::new(vec!["127.0.0.1".to_string()])?;
-    server_ee_params.is_ca = rcgen::IsCa::NoCa;
-    server_ee_params.extended_key_usages = vec![rcgen::ExtendedKeyUsagePurpose::ServerAuth];
-    let server_cert = server_ee_params.signed_by(&server_key_pair, &ca_cert, &ca_key_pair)?;
-    let server_cert_der: CertificateDer = server_cert.into();
-    let server_key_der = PrivatePkcs8KeyDer::from(server_key_pair.serialize_der());
-
-    let mut tls_server_config = ServerConfig::builder()
-        .with_no_client_auth()
-        .with_single_cert(
-            vec![server_cert_der],
-            PrivatePkcs8KeyDer::from(server_key_der.secret_pkcs8_der().to_owned()).into(),
-        )?;
-    tls_server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
-    Ok(tls_server_config)
+// Load the MITM CA from the pem files defined by the `RAMA_MITM_CA_CERT` and `RAMA_MITM_CA_KEY`
+// env variables, or generate a new one, and write its cert to the temporary directory,
+// such that your clients can install the certificate for trust.
+fn mitm_certificate_authority() -> Result<CertificateAuthority, BoxError> {
+    let authority = match (
+        std::env::var("RAMA_MITM_CA_CERT"),
+        std::env::var("RAMA_MITM_CA_KEY"),
+    ) {
+        (Ok(cert_path), Ok(key_path)) => CertificateAuthority::from_pem(
+            &std::fs::read_to_string(cert_path)?,
+            &std::fs::read_to_string(key_path)?,
+        )?,
+        _ => CertificateAuthority::generate("Rama Example MITM CA")?,
+    };
+
+    let ca_cert_path = std::env::temp_dir().join("rama_mitm_ca.pem");
+    std::fs::write(&ca_cert_path, authority.cert_pem())?;
+    tracing::info!("wrote mitm CA cert to {}", ca_cert_path.display());
+
+    Ok(authority)
 }
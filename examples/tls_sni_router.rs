@@ -0,0 +1,92 @@
+//! This example demonstrates how to build an L4 SNI proxy, routing incoming TLS
+//! connections to different upstream servers based on the server name (SNI)
+//! of the TLS ClientHello, without terminating the TLS connection itself.
+//!
+//! This is similar to what you can achieve using the `ssl_preread` module of nginx.
+//!
+//! The ClientHello is peeked using the `PeekTlsClientHelloLayer`,
+//! after which the `SniMatcher` is used to select the upstream to forward the
+//! (still encrypted) stream to. The peeked bytes are forwarded as well,
+//! such that the upstream server can terminate the TLS connection as usual.
+//!
+//! # Run the example
+//!
+//! ```sh
+//! cargo run --example tls_sni_router --features=tcp,tls
+//! ```
+//!
+//! # Expected output
+//!
+//! The server will start and listen on `:62040`. Connections with the SNI `echo.localhost`
+//! (or a subdomain of it) are forwarded to an echo server listening on `:62041`,
+//! which will echo back all bytes it receives, including the TLS ClientHello:
+//!
+//! ```sh
+//! openssl s_client -connect 127.0.0.1:62040 -servername echo.localhost
+//! ```
+//!
+//! All other connections are closed by the router.
+
+use rama::{
+    error::BoxError,
+    layer::TraceErrLayer,
+    net::{
+        address::Domain,
+        stream::service::EchoService,
+        tls::server::{PeekTlsClientHelloLayer, SniMatcher},
+    },
+    service::service_fn,
+    tcp::{client::service::Forwarder, server::TcpListener},
+    Layer,
+};
+use std::time::Duration;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::registry()
+        .with(fmt::layer())
+        .with(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::DEBUG.into())
+                .from_env_lossy(),
+        )
+        .init();
+
+    let graceful = rama::graceful::Shutdown::default();
+
+    // upstream server, in a real setup this would be a tls server
+    graceful.spawn_task_fn(|guard| async {
+        TcpListener::bind("127.0.0.1:62041")
+            .await
+            .expect("bind TCP Listener: echo upstream")
+            .serve_graceful(guard, EchoService::new())
+            .await;
+    });
+
+    graceful.spawn_task_fn(|guard| async {
+        TcpListener::bind("0.0.0.0:62040")
+            .await
+            .expect("bind TCP Listener: sni router")
+            .serve_graceful(
+                guard,
+                (TraceErrLayer::new(), PeekTlsClientHelloLayer::new()).layer((
+                    (
+                        SniMatcher::sub(Domain::from_static("echo.localhost")),
+                        Forwarder::new(([127, 0, 0, 1], 62041)),
+                    ),
+                    service_fn(|_| async move {
+                        tracing::warn!("closing connection without known server name");
+                        Ok::<_, BoxError>(())
+                    }),
+                )),
+            )
+            .await;
+    });
+
+    graceful
+        .shutdown_with_limit(Duration::from_secs(30))
+        .await
+        .expect("graceful shutdown");
+}
@@ -1,13 +1,23 @@
 use super::State;
 use rama::{
     error::{BoxError, ErrorContext},
-    http::{dep::http::request::Parts, headers::Forwarded, Request},
-    net::{http::RequestContext, stream::SocketInfo},
+    http::{
+        dep::http::request::Parts, headers::Forwarded, server::fingerprint::Http2Fingerprint,
+        Request,
+    },
+    net::{
+        fingerprint::{Ja3, Ja4},
+        http::RequestContext,
+        stream::SocketInfo,
+    },
     tls::types::{
         client::{ClientHello, ClientHelloExtension},
         SecureTransport,
     },
-    ua::UserAgent,
+    ua::{
+        profile::{FingerprintRecord, Http2FingerprintRecord},
+        UserAgent,
+    },
     Context,
 };
 use serde::Serialize;
@@ -202,8 +212,83 @@ pub(super) fn get_http_info(req: &Request) -> HttpInfo {
     HttpInfo { headers }
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub(super) struct Http2DisplayInfo {
+    pub(super) akamai_fingerprint: String,
+    pub(super) settings: Vec<String>,
+    pub(super) window_update: Option<u32>,
+    pub(super) priorities: Vec<String>,
+    pub(super) pseudo_headers: Vec<String>,
+}
+
+pub(super) fn get_http2_display_info(ctx: &Context<State>) -> Option<Http2DisplayInfo> {
+    let fingerprint: &Http2Fingerprint = ctx.get()?;
+
+    Some(Http2DisplayInfo {
+        akamai_fingerprint: fingerprint.akamai_fingerprint(),
+        settings: fingerprint
+            .settings()
+            .iter()
+            .map(|(id, value)| format!("{id}: {value}"))
+            .collect(),
+        window_update: fingerprint.window_update(),
+        priorities: fingerprint
+            .priorities()
+            .iter()
+            .map(|priority| priority.to_string())
+            .collect(),
+        pseudo_headers: fingerprint
+            .pseudo_headers()
+            .iter()
+            .map(|pseudo_header| pseudo_header.as_str().to_owned())
+            .collect(),
+    })
+}
+
+/// Compose the [`FingerprintRecord`] of the request,
+/// such that it can be exported and used as emulation data.
+///
+/// The cookies and credentials of the request are not part of the record.
+///
+/// Returns `None` in case no [`UserAgent`] is known for the request.
+pub(super) fn get_fingerprint_record(
+    ctx: &Context<State>,
+    http_info: &HttpInfo,
+    request_info: &RequestInfo,
+) -> Option<FingerprintRecord> {
+    let ua: &UserAgent = ctx.get()?;
+
+    let mut record = FingerprintRecord {
+        user_agent: ua.header_str().to_owned(),
+        http_version: request_info.version.clone(),
+        fetch_mode: Some(request_info.fetch_mode.to_string()),
+        headers: http_info.headers.clone(),
+        h2: ctx
+            .get::<Http2Fingerprint>()
+            .map(|fingerprint| Http2FingerprintRecord {
+                settings: fingerprint.settings().to_vec(),
+                window_update: fingerprint.window_update(),
+                pseudo_headers: fingerprint
+                    .pseudo_headers()
+                    .iter()
+                    .map(|pseudo_header| pseudo_header.as_str().to_owned())
+                    .collect(),
+            }),
+        tls: ctx
+            .get::<SecureTransport>()
+            .and_then(|st| st.client_hello())
+            .cloned(),
+    };
+    record.strip_request_specific_headers();
+    Some(record)
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub(super) struct TlsDisplayInfo {
+    pub(super) ja3: String,
+    pub(super) ja3_hash: String,
+    pub(super) ja4: String,
+    pub(super) ja4_r: String,
     pub(super) cipher_suites: Vec<String>,
     pub(super) compression_algorithms: Vec<String>,
     pub(super) extensions: Vec<TlsDisplayInfoExtension>,
@@ -226,7 +311,14 @@ pub(super) fn get_tls_display_info(ctx: &Context<State>) -> Option<TlsDisplayInf
         .get::<SecureTransport>()
         .and_then(|st| st.client_hello())?;
 
+    let ja3 = Ja3::compute(hello);
+    let ja4 = Ja4::compute(hello);
+
     Some(TlsDisplayInfo {
+        ja3: ja3.raw().to_owned(),
+        ja3_hash: ja3.hash(),
+        ja4: ja4.to_string(),
+        ja4_r: ja4.raw(),
         cipher_suites: hello
             .cipher_suites()
             .iter()
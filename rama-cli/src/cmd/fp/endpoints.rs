@@ -2,17 +2,20 @@ use crate::cmd::fp::data::TlsDisplayInfoExtensionData;
 
 use super::{
     data::{
-        get_http_info, get_request_info, get_tls_display_info, get_user_agent_info, DataSource,
-        FetchMode, Initiator, RequestInfo, ResourceType, TlsDisplayInfo, UserAgentInfo,
+        get_fingerprint_record, get_http2_display_info, get_http_info, get_request_info,
+        get_tls_display_info, get_user_agent_info, DataSource, FetchMode, Http2DisplayInfo,
+        Initiator, RequestInfo, ResourceType, TlsDisplayInfo, UserAgentInfo,
     },
     State,
 };
 use rama::{
     http::{
+        header::{AUTHORIZATION, WWW_AUTHENTICATE},
         response::Json,
         service::web::extract::{self, FromRequestParts, Path},
-        Body, IntoResponse, Request, Response, StatusCode,
+        Body, HeaderValue, IntoResponse, Request, Response, StatusCode,
     },
+    ua::profile::FingerprintRecord,
     Context,
 };
 use serde::{Deserialize, Serialize};
@@ -93,6 +96,10 @@ pub(super) async fn get_report(mut ctx: Context<State>, req: Request) -> Result<
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response())?;
 
+    if let Some(record) = get_fingerprint_record(&ctx, &http_info, &request_info) {
+        ctx.state().records.push(record);
+    }
+
     let head = r#"<script src="/assets/script.js"></script>"#.to_owned();
 
     let mut tables = vec![
@@ -105,6 +112,10 @@ pub(super) async fn get_report(mut ctx: Context<State>, req: Request) -> Result<
         },
     ];
 
+    if let Some(http2_info) = get_http2_display_info(&ctx) {
+        tables.push(http2_info.into());
+    }
+
     let tls_info = get_tls_display_info(&ctx);
     if let Some(tls_info) = tls_info {
         let mut tls_tables = tls_info.into();
@@ -174,6 +185,11 @@ pub(super) async fn get_api_fetch_number(
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response())?;
 
+    if let Some(record) = get_fingerprint_record(&ctx, &http_info, &request_info) {
+        ctx.state().records.push(record);
+    }
+
+    let http2_info = get_http2_display_info(&ctx);
     let tls_info = get_tls_display_info(&ctx);
 
     Ok(Json(json!({
@@ -183,6 +199,7 @@ pub(super) async fn get_api_fetch_number(
             "request_info": request_info,
             "tls_info": tls_info,
             "http_info": http_info,
+            "http2_info": http2_info,
         }
     })))
 }
@@ -215,6 +232,11 @@ pub(super) async fn post_api_fetch_number(
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response())?;
 
+    if let Some(record) = get_fingerprint_record(&ctx, &http_info, &request_info) {
+        ctx.state().records.push(record);
+    }
+
+    let http2_info = get_http2_display_info(&ctx);
     let tls_info = get_tls_display_info(&ctx);
 
     Ok(Json(json!({
@@ -224,6 +246,7 @@ pub(super) async fn post_api_fetch_number(
             "request_info": request_info,
             "tls_info": tls_info,
             "http_info": http_info,
+            "http2_info": http2_info,
         }
     })))
 }
@@ -248,6 +271,10 @@ pub(super) async fn get_api_xml_http_request_number(
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response())?;
 
+    if let Some(record) = get_fingerprint_record(&ctx, &http_info, &request_info) {
+        ctx.state().records.push(record);
+    }
+
     Ok(Json(json!({
         "number": ctx.state().counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst),
         "fp": {
@@ -286,6 +313,11 @@ pub(super) async fn post_api_xml_http_request_number(
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response())?;
 
+    if let Some(record) = get_fingerprint_record(&ctx, &http_info, &request_info) {
+        ctx.state().records.push(record);
+    }
+
+    let http2_info = get_http2_display_info(&ctx);
     let tls_info = get_tls_display_info(&ctx);
 
     Ok(Json(json!({
@@ -295,10 +327,67 @@ pub(super) async fn post_api_xml_http_request_number(
             "request_info": request_info,
             "tls_info": tls_info,
             "http_info": http_info,
+            "http2_info": http2_info,
         }
     })))
 }
 
+//------------------------------------------
+// endpoints: export
+//------------------------------------------
+
+/// Only allow the export of fingerprints in case it is enabled,
+/// and the request is authorized using the configured bearer token.
+fn authorize_export(ctx: &Context<State>, req: &Request) -> Result<(), Response> {
+    let Some(expected) = ctx.state().export_authorization.as_ref() else {
+        return Err(StatusCode::NOT_FOUND.into_response());
+    };
+    // compared in constant time, such that the token cannot be guessed by timing the responses
+    let authorized = req.headers().get(AUTHORIZATION).is_some_and(|value| {
+        ring::constant_time::verify_slices_are_equal(value.as_bytes(), expected.as_bytes()).is_ok()
+    });
+    if !authorized {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            [(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"))],
+        )
+            .into_response());
+    }
+    Ok(())
+}
+
+pub(super) async fn get_api_fingerprints(
+    ctx: Context<State>,
+    req: Request,
+) -> Result<Json<Vec<FingerprintRecord>>, Response> {
+    authorize_export(&ctx, &req)?;
+    Ok(Json(ctx.state().records.to_vec()))
+}
+
+pub(super) async fn get_api_fingerprints_ndjson(
+    ctx: Context<State>,
+    req: Request,
+) -> Result<Response, Response> {
+    authorize_export(&ctx, &req)?;
+
+    let mut body = String::new();
+    for record in ctx.state().records.to_vec() {
+        match serde_json::to_string(&record) {
+            Ok(line) => {
+                body.push_str(&line);
+                body.push('\n');
+            }
+            Err(err) => tracing::error!("failed to serialize fingerprint record: {err}"),
+        }
+    }
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/x-ndjson")
+        .body(body.into())
+        .expect("build ndjson response"))
+}
+
 //------------------------------------------
 // endpoints: form
 //------------------------------------------
@@ -323,6 +412,10 @@ pub(super) async fn form(mut ctx: Context<State>, req: Request) -> Result<Html,
     .await
     .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response())?;
 
+    if let Some(record) = get_fingerprint_record(&ctx, &http_info, &request_info) {
+        ctx.state().records.push(record);
+    }
+
     let mut content = String::new();
 
     content.push_str(r##"<a href="/report" title="Back to Home">🏠 Back to Home...</a>"##);
@@ -352,6 +445,10 @@ pub(super) async fn form(mut ctx: Context<State>, req: Request) -> Result<Html,
         },
     ];
 
+    if let Some(http2_info) = get_http2_display_info(&ctx) {
+        tables.push(http2_info.into());
+    }
+
     let tls_info = get_tls_display_info(&ctx);
     if let Some(tls_info) = tls_info {
         let mut tls_tables = tls_info.into();
@@ -468,9 +565,38 @@ fn render_page(title: &'static str, head: String, content: String) -> Html {
     ))
 }
 
+impl From<Http2DisplayInfo> for Table {
+    fn from(info: Http2DisplayInfo) -> Self {
+        Self {
+            title: "🚄 HTTP/2 Fingerprint".to_owned(),
+            rows: vec![
+                ("Akamai".to_owned(), info.akamai_fingerprint),
+                ("Settings".to_owned(), info.settings.join(", ")),
+                (
+                    "Window Update".to_owned(),
+                    info.window_update
+                        .map(|increment| increment.to_string())
+                        .unwrap_or_default(),
+                ),
+                ("Priorities".to_owned(), info.priorities.join(", ")),
+                ("Pseudo Headers".to_owned(), info.pseudo_headers.join(", ")),
+            ],
+        }
+    }
+}
+
 impl From<TlsDisplayInfo> for Vec<Table> {
     fn from(info: TlsDisplayInfo) -> Self {
-        let mut vec = Vec::with_capacity(info.extensions.len() + 1);
+        let mut vec = Vec::with_capacity(info.extensions.len() + 2);
+        vec.push(Table {
+            title: "🔒 TLS Client Hello — Fingerprints".to_owned(),
+            rows: vec![
+                ("JA3".to_owned(), info.ja3),
+                ("JA3 Hash".to_owned(), info.ja3_hash),
+                ("JA4".to_owned(), info.ja4),
+                ("JA4_r".to_owned(), info.ja4_r),
+            ],
+        });
         vec.push(Table {
             title: "🔒 TLS Client Hello — Header".to_owned(),
             rows: vec![
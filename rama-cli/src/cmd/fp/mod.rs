@@ -87,6 +87,13 @@ pub struct CliCommandFingerprint {
     #[arg(long, short = 's')]
     /// run echo service in secure mode (enable TLS)
     secure: bool,
+
+    #[arg(long)]
+    /// enable the export of the collected fingerprints
+    ///
+    /// The export endpoints require the bearer token
+    /// defined by the `RAMA_FP_EXPORT_TOKEN` env variable.
+    export: bool,
 }
 
 /// run the rama FP service
@@ -150,6 +157,18 @@ pub async fn run(cfg: CliCommandFingerprint) -> Result<(), BoxError> {
         ACMEData::default()
     };
 
+    let export_authorization = match cfg.export {
+        true => {
+            let token = std::env::var("RAMA_FP_EXPORT_TOKEN")
+                .context("RAMA_FP_EXPORT_TOKEN is required to enable the export")?;
+            Some(
+                HeaderValue::from_str(&format!("Bearer {token}"))
+                    .context("create export authorization header value")?,
+            )
+        }
+        false => None,
+    };
+
     let tls_server_cfg = cfg.secure.then(|| {
         let tls_crt_pem_raw = std::env::var("RAMA_TLS_CRT").expect("RAMA_TLS_CRT");
         let tls_key_pem_raw = std::env::var("RAMA_TLS_KEY").expect("RAMA_TLS_KEY");
@@ -243,6 +262,9 @@ pub async fn run(cfg: CliCommandFingerprint) -> Result<(), BoxError> {
                     // Assets
                     HttpMatcher::get("/assets/style.css") => endpoints::get_assets_style,
                     HttpMatcher::get("/assets/script.js") => endpoints::get_assets_script,
+                    // Export
+                    HttpMatcher::get("/api/fingerprints") => endpoints::get_api_fingerprints,
+                    HttpMatcher::get("/api/fingerprints.ndjson") => endpoints::get_api_fingerprints_ndjson,
                     // Fingerprinting Endpoints
                     _ => inner_http_service,
                 })
@@ -263,7 +285,7 @@ pub async fn run(cfg: CliCommandFingerprint) -> Result<(), BoxError> {
             })
         );
 
-        let tcp_listener = TcpListener::build_with_state(State::new(acme_data))
+        let tcp_listener = TcpListener::build_with_state(State::new(acme_data, export_authorization))
             .bind(&address)
             .await
             .expect("bind TCP Listener");
@@ -1,6 +1,10 @@
-use std::{collections::HashMap, sync::atomic::AtomicUsize};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::atomic::AtomicUsize,
+};
 
-use rama::context::AsRef;
+use parking_lot::Mutex;
+use rama::{context::AsRef, http::HeaderValue, ua::profile::FingerprintRecord};
 
 use super::data::DataSource;
 
@@ -10,19 +14,49 @@ pub(super) struct State {
     pub(super) data_source: DataSource,
     pub(super) counter: AtomicUsize,
     pub(super) acme: ACMEData,
+    pub(super) records: FingerprintRecords,
+    /// The expected `Authorization` header of export requests,
+    /// `None` in case exporting is disabled.
+    pub(super) export_authorization: Option<HeaderValue>,
 }
 
 impl State {
     /// Create a new instance of [`State`].
-    pub(super) fn new(acme: ACMEData) -> Self {
+    pub(super) fn new(acme: ACMEData, export_authorization: Option<HeaderValue>) -> Self {
         State {
             data_source: DataSource::default(),
             counter: AtomicUsize::new(0),
             acme,
+            records: FingerprintRecords::default(),
+            export_authorization,
         }
     }
 }
 
+/// Maximum number of [`FingerprintRecord`]s kept in memory.
+const MAX_FINGERPRINT_RECORDS: usize = 4096;
+
+#[derive(Debug, Default)]
+/// In-memory store of the [`FingerprintRecord`]s collected by this service,
+/// dropping the oldest records once it is full.
+pub(super) struct FingerprintRecords {
+    records: Mutex<VecDeque<FingerprintRecord>>,
+}
+
+impl FingerprintRecords {
+    pub(super) fn push(&self, record: FingerprintRecord) {
+        let mut records = self.records.lock();
+        if records.len() >= MAX_FINGERPRINT_RECORDS {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    pub(super) fn to_vec(&self) -> Vec<FingerprintRecord> {
+        self.records.lock().iter().cloned().collect()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(super) struct ACMEData {
     challenges: HashMap<String, String>,
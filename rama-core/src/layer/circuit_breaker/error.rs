@@ -0,0 +1,39 @@
+//! Error type for the circuit breaker middleware.
+
+use std::{error, fmt, time::Duration};
+
+/// The request was rejected because the circuit is open.
+///
+/// Returned by the [`CircuitBreaker`] without calling the inner service,
+/// such that callers can fall back to an alternative.
+///
+/// [`CircuitBreaker`]: super::CircuitBreaker
+#[derive(Debug, Clone)]
+pub struct CircuitOpen {
+    retry_after: Duration,
+}
+
+impl CircuitOpen {
+    pub(super) const fn new(retry_after: Duration) -> Self {
+        Self { retry_after }
+    }
+
+    /// The remaining cool-down period, after which trial requests are allowed again.
+    ///
+    /// Zero in case the circuit is half-open, and the maximum amount of trial requests is in flight.
+    pub fn retry_after(&self) -> Duration {
+        self.retry_after
+    }
+}
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "request rejected by open circuit breaker (retry after {:?})",
+            self.retry_after
+        )
+    }
+}
+
+impl error::Error for CircuitOpen {}
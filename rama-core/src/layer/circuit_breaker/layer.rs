@@ -0,0 +1,148 @@
+use super::{Circuit, CircuitBreaker, CircuitConfig, CircuitState, ErrorIsFailure};
+use crate::Layer;
+use std::{fmt, sync::Arc, time::Duration};
+
+/// A [`Layer`] that produces [`CircuitBreaker`] services.
+///
+/// All services produced by the same layer (or its clones) share the same circuit.
+pub struct CircuitBreakerLayer<P> {
+    predicate: P,
+    config: CircuitConfig,
+    circuit: Arc<Circuit>,
+}
+
+impl<P: fmt::Debug> fmt::Debug for CircuitBreakerLayer<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CircuitBreakerLayer")
+            .field("predicate", &self.predicate)
+            .field("circuit", &self.circuit)
+            .finish()
+    }
+}
+
+impl<P: Clone> Clone for CircuitBreakerLayer<P> {
+    fn clone(&self) -> Self {
+        Self {
+            predicate: self.predicate.clone(),
+            config: self.config.clone(),
+            circuit: self.circuit.clone(),
+        }
+    }
+}
+
+impl CircuitBreakerLayer<ErrorIsFailure> {
+    /// Creates a new [`CircuitBreakerLayer`], considering all errors of the inner service as failures.
+    ///
+    /// By default the circuit opens after 5 consecutive failures, for a cool-down of 30 seconds,
+    /// after which a single successful trial request closes it again.
+    pub fn new() -> Self {
+        let config = CircuitConfig::default();
+        Self {
+            predicate: ErrorIsFailure,
+            circuit: Arc::new(Circuit::new(config.clone())),
+            config,
+        }
+    }
+}
+
+impl Default for CircuitBreakerLayer<ErrorIsFailure> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P> CircuitBreakerLayer<P> {
+    /// Set the [`FailurePredicate`] used to decide whether a result is a failure.
+    ///
+    /// [`FailurePredicate`]: super::FailurePredicate
+    pub fn with_failure_predicate<T>(self, predicate: T) -> CircuitBreakerLayer<T> {
+        CircuitBreakerLayer {
+            predicate,
+            config: self.config,
+            circuit: self.circuit,
+        }
+    }
+
+    /// Set the amount of consecutive failures after which the circuit opens,
+    /// with a minimum of one.
+    pub fn with_failure_threshold(mut self, threshold: u32) -> Self {
+        self.config.failure_threshold = threshold.max(1);
+        self.reset()
+    }
+
+    /// Set the amount of consecutive failures after which the circuit opens,
+    /// with a minimum of one.
+    pub fn set_failure_threshold(&mut self, threshold: u32) -> &mut Self {
+        self.config.failure_threshold = threshold.max(1);
+        self.reset_mut()
+    }
+
+    /// Set the amount of successful trial requests required to close the half-open circuit,
+    /// with a minimum of one.
+    pub fn with_success_threshold(mut self, threshold: u32) -> Self {
+        self.config.success_threshold = threshold.max(1);
+        self.reset()
+    }
+
+    /// Set the amount of successful trial requests required to close the half-open circuit,
+    /// with a minimum of one.
+    pub fn set_success_threshold(&mut self, threshold: u32) -> &mut Self {
+        self.config.success_threshold = threshold.max(1);
+        self.reset_mut()
+    }
+
+    /// Set the maximum amount of trial requests in flight while the circuit is half-open,
+    /// with a minimum of one.
+    pub fn with_half_open_max_calls(mut self, max: u32) -> Self {
+        self.config.half_open_max_calls = max.max(1);
+        self.reset()
+    }
+
+    /// Set the maximum amount of trial requests in flight while the circuit is half-open,
+    /// with a minimum of one.
+    pub fn set_half_open_max_calls(&mut self, max: u32) -> &mut Self {
+        self.config.half_open_max_calls = max.max(1);
+        self.reset_mut()
+    }
+
+    /// Set the duration the circuit stays open, before allowing trial requests.
+    pub fn with_cool_down(mut self, cool_down: Duration) -> Self {
+        self.config.cool_down = cool_down;
+        self.reset()
+    }
+
+    /// Set the duration the circuit stays open, before allowing trial requests.
+    pub fn set_cool_down(&mut self, cool_down: Duration) -> &mut Self {
+        self.config.cool_down = cool_down;
+        self.reset_mut()
+    }
+
+    /// Returns the current state of the circuit.
+    pub fn state(&self) -> CircuitState {
+        self.circuit.state()
+    }
+
+    fn reset(mut self) -> Self {
+        self.reset_mut();
+        self
+    }
+
+    /// Apply the (modified) config by creating a new circuit,
+    /// detached from the services produced so far.
+    fn reset_mut(&mut self) -> &mut Self {
+        self.circuit = Arc::new(Circuit::new(self.config.clone()));
+        self
+    }
+}
+
+impl<S, P: Clone> Layer<S> for CircuitBreakerLayer<P> {
+    type Service = CircuitBreaker<S, P>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CircuitBreaker {
+            inner,
+            predicate: self.predicate.clone(),
+            circuit: self.circuit.clone(),
+        }
+    }
+}
@@ -0,0 +1,597 @@
+//! Middleware that stops calling a failing inner service for a while,
+//! failing fast instead, known as a circuit breaker.
+//!
+//! The circuit breaker has three states:
+//!
+//! - [`CircuitState::Closed`]: requests are passed to the inner service,
+//!   while consecutive failures are counted. Once the failure threshold is reached,
+//!   the circuit opens;
+//! - [`CircuitState::Open`]: requests are rejected immediately with a [`CircuitOpen`] error,
+//!   until the cool-down period has passed, after which the circuit becomes half-open;
+//! - [`CircuitState::HalfOpen`]: a limited amount of trial requests is passed to the inner service.
+//!   The circuit closes once enough trial requests succeeded, and opens again as soon
+//!   as a single trial request fails. Other requests are rejected in the meantime.
+//!
+//! What is considered a failure is decided by a [`FailurePredicate`],
+//! by default all errors of the inner service are considered failures.
+//!
+//! All services created by the same [`CircuitBreakerLayer`] share the same circuit.
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{
+//!     error::BoxError,
+//!     layer::circuit_breaker::{CircuitBreakerLayer, CircuitOpen},
+//!     service::service_fn,
+//!     Context, Layer, Service,
+//! };
+//! use std::time::Duration;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let service = CircuitBreakerLayer::new()
+//!     .with_failure_threshold(1)
+//!     .with_cool_down(Duration::from_secs(30))
+//!     .layer(service_fn(|_, _| async {
+//!         Err::<(), _>(BoxError::from("upstream failure"))
+//!     }));
+//!
+//! // the first failure opens the circuit...
+//! let err = service.serve(Context::default(), ()).await.unwrap_err();
+//! assert!(!err.is::<CircuitOpen>());
+//!
+//! // ...after which requests fail fast
+//! let err = service.serve(Context::default(), ()).await.unwrap_err();
+//! assert!(err.is::<CircuitOpen>());
+//! # }
+//! ```
+
+use crate::error::BoxError;
+use crate::{Context, Service};
+use parking_lot::Mutex;
+use rama_utils::macros::define_inner_service_accessors;
+use std::{fmt, sync::Arc, time::Duration};
+use tokio::time::Instant;
+
+mod error;
+#[doc(inline)]
+pub use error::CircuitOpen;
+
+mod layer;
+#[doc(inline)]
+pub use layer::CircuitBreakerLayer;
+
+/// Decides whether the result of a service call is a failure,
+/// counting towards opening the circuit of a [`CircuitBreaker`].
+///
+/// It is implemented for any `Fn(&Result<Response, Error>) -> bool`.
+pub trait FailurePredicate<Response, Error>: Send + Sync + 'static {
+    /// Returns `true` in case the result is to be considered a failure.
+    fn is_failure(&self, result: &Result<Response, Error>) -> bool;
+}
+
+impl<F, Response, Error> FailurePredicate<Response, Error> for F
+where
+    F: Fn(&Result<Response, Error>) -> bool + Send + Sync + 'static,
+{
+    fn is_failure(&self, result: &Result<Response, Error>) -> bool {
+        (self)(result)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+/// The default [`FailurePredicate`], which considers all errors as failures,
+/// and all responses as successes.
+pub struct ErrorIsFailure;
+
+impl ErrorIsFailure {
+    /// Create a new [`ErrorIsFailure`] predicate.
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl<Response, Error> FailurePredicate<Response, Error> for ErrorIsFailure {
+    fn is_failure(&self, result: &Result<Response, Error>) -> bool {
+        result.is_err()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The state of the circuit of a [`CircuitBreaker`].
+pub enum CircuitState {
+    /// Requests are passed to the inner service.
+    Closed,
+    /// Requests are rejected without calling the inner service.
+    Open,
+    /// A limited amount of trial requests is passed to the inner service.
+    HalfOpen,
+}
+
+/// Middleware which stops calling a failing inner service for a while.
+///
+/// See the [module docs](self) for more information.
+pub struct CircuitBreaker<S, P> {
+    inner: S,
+    predicate: P,
+    circuit: Arc<Circuit>,
+}
+
+impl<S, P> CircuitBreaker<S, P> {
+    define_inner_service_accessors!();
+
+    /// Returns the current state of the circuit.
+    pub fn state(&self) -> CircuitState {
+        self.circuit.state()
+    }
+}
+
+impl<S> CircuitBreaker<S, ErrorIsFailure> {
+    /// Creates a new [`CircuitBreaker`] with the default configuration,
+    /// considering all errors of the inner service as failures.
+    ///
+    /// Use the [`CircuitBreakerLayer`] in order to configure the circuit.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            predicate: ErrorIsFailure,
+            circuit: Arc::new(Circuit::new(CircuitConfig::default())),
+        }
+    }
+}
+
+impl<S: fmt::Debug, P: fmt::Debug> fmt::Debug for CircuitBreaker<S, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CircuitBreaker")
+            .field("inner", &self.inner)
+            .field("predicate", &self.predicate)
+            .field("circuit", &self.circuit)
+            .finish()
+    }
+}
+
+impl<S: Clone, P: Clone> Clone for CircuitBreaker<S, P> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            predicate: self.predicate.clone(),
+            circuit: self.circuit.clone(),
+        }
+    }
+}
+
+impl<T, P, State, Request> Service<State, Request> for CircuitBreaker<T, P>
+where
+    T: Service<State, Request, Error: Into<BoxError>>,
+    P: FailurePredicate<T::Response, T::Error>,
+    State: Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Response = T::Response;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        request: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let permit = self.circuit.try_acquire()?;
+        let result = self.inner.serve(ctx, request).await;
+        permit.complete(!self.predicate.is_failure(&result));
+        result.map_err(Into::into)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CircuitConfig {
+    failure_threshold: u32,
+    success_threshold: u32,
+    half_open_max_calls: u32,
+    cool_down: Duration,
+}
+
+impl Default for CircuitConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            success_threshold: 1,
+            half_open_max_calls: 1,
+            cool_down: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum CircuitInnerState {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    HalfOpen {
+        generation: u64,
+        in_flight: u32,
+        successes: u32,
+    },
+}
+
+#[derive(Debug)]
+struct CircuitInner {
+    state: CircuitInnerState,
+    // incremented every time the circuit becomes half-open,
+    // such that results of trial requests of a previous half-open period are ignored
+    half_open_generation: u64,
+}
+
+#[derive(Debug)]
+struct Circuit {
+    config: CircuitConfig,
+    inner: Mutex<CircuitInner>,
+}
+
+impl Circuit {
+    fn new(config: CircuitConfig) -> Self {
+        Self {
+            config,
+            inner: Mutex::new(CircuitInner {
+                state: CircuitInnerState::Closed { failures: 0 },
+                half_open_generation: 0,
+            }),
+        }
+    }
+
+    fn state(&self) -> CircuitState {
+        match &self.inner.lock().state {
+            CircuitInnerState::Closed { .. } => CircuitState::Closed,
+            CircuitInnerState::Open { until } if *until > Instant::now() => CircuitState::Open,
+            CircuitInnerState::Open { .. } | CircuitInnerState::HalfOpen { .. } => {
+                CircuitState::HalfOpen
+            }
+        }
+    }
+
+    fn try_acquire(&self) -> Result<CircuitPermit<'_>, CircuitOpen> {
+        let mut inner = self.inner.lock();
+        let generation = match &mut inner.state {
+            CircuitInnerState::Closed { .. } => None,
+            CircuitInnerState::Open { until } => {
+                let now = Instant::now();
+                if *until > now {
+                    return Err(CircuitOpen::new(*until - now));
+                }
+                tracing::trace!("circuit breaker: cool-down passed, half-opening circuit");
+                inner.half_open_generation += 1;
+                let generation = inner.half_open_generation;
+                inner.state = CircuitInnerState::HalfOpen {
+                    generation,
+                    in_flight: 1,
+                    successes: 0,
+                };
+                Some(generation)
+            }
+            CircuitInnerState::HalfOpen {
+                generation,
+                in_flight,
+                ..
+            } => {
+                if *in_flight >= self.config.half_open_max_calls {
+                    return Err(CircuitOpen::new(Duration::ZERO));
+                }
+                *in_flight += 1;
+                Some(*generation)
+            }
+        };
+        Ok(CircuitPermit {
+            circuit: self,
+            generation,
+            completed: false,
+        })
+    }
+
+    fn record(&self, generation: Option<u64>, success: Option<bool>) {
+        let mut inner = self.inner.lock();
+        let state = &mut inner.state;
+        match state {
+            CircuitInnerState::Closed { failures } => match success {
+                Some(true) => *failures = 0,
+                Some(false) => {
+                    *failures += 1;
+                    if *failures >= self.config.failure_threshold {
+                        tracing::debug!(
+                            "circuit breaker: failure threshold reached, opening circuit"
+                        );
+                        *state = CircuitInnerState::Open {
+                            until: Instant::now() + self.config.cool_down,
+                        };
+                    }
+                }
+                None => (),
+            },
+            CircuitInnerState::HalfOpen {
+                generation: current,
+                in_flight,
+                successes,
+            } if generation == Some(*current) => {
+                *in_flight = in_flight.saturating_sub(1);
+                match success {
+                    Some(true) => {
+                        *successes += 1;
+                        if *successes >= self.config.success_threshold {
+                            tracing::debug!(
+                                "circuit breaker: trial requests succeeded, closing circuit"
+                            );
+                            *state = CircuitInnerState::Closed { failures: 0 };
+                        }
+                    }
+                    Some(false) => {
+                        tracing::debug!("circuit breaker: trial request failed, opening circuit");
+                        *state = CircuitInnerState::Open {
+                            until: Instant::now() + self.config.cool_down,
+                        };
+                    }
+                    None => (),
+                }
+            }
+            // results of requests started in a previous state or half-open period are ignored
+            CircuitInnerState::HalfOpen { .. } | CircuitInnerState::Open { .. } => (),
+        }
+    }
+}
+
+/// Permit to call the inner service, used to record the result of the call.
+///
+/// A permit dropped without being completed (e.g. because the request was cancelled)
+/// is neither counted as a success nor as a failure.
+struct CircuitPermit<'a> {
+    circuit: &'a Circuit,
+    // the half-open generation of a trial request, `None` for regular requests
+    generation: Option<u64>,
+    completed: bool,
+}
+
+impl CircuitPermit<'_> {
+    fn complete(mut self, success: bool) {
+        self.completed = true;
+        self.circuit.record(self.generation, Some(success));
+    }
+}
+
+impl Drop for CircuitPermit<'_> {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.circuit.record(self.generation, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{service::service_fn, Layer};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    fn flaky_service(
+        fail: Arc<AtomicBool>,
+        calls: Arc<AtomicUsize>,
+    ) -> impl Service<(), (), Response = (), Error = BoxError> {
+        service_fn(move |_ctx: Context<()>, _req: ()| {
+            let fail = fail.clone();
+            let calls = calls.clone();
+            async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                if fail.load(Ordering::SeqCst) {
+                    Err(BoxError::from("failure"))
+                } else {
+                    Ok(())
+                }
+            }
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker() {
+        let fail = Arc::new(AtomicBool::new(true));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let service = CircuitBreakerLayer::new()
+            .with_failure_threshold(2)
+            .with_cool_down(Duration::from_secs(10))
+            .layer(flaky_service(fail.clone(), calls.clone()));
+
+        assert_eq!(service.state(), CircuitState::Closed);
+        assert!(service.serve(Context::default(), ()).await.is_err());
+        assert_eq!(service.state(), CircuitState::Closed);
+        assert!(service.serve(Context::default(), ()).await.is_err());
+        assert_eq!(service.state(), CircuitState::Open);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // fail fast while open
+        let err = service.serve(Context::default(), ()).await.unwrap_err();
+        let err = err.downcast_ref::<CircuitOpen>().unwrap();
+        assert_eq!(err.retry_after(), Duration::from_secs(10));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // trial request fails: open again
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert_eq!(service.state(), CircuitState::HalfOpen);
+        let err = service.serve(Context::default(), ()).await.unwrap_err();
+        assert!(!err.is::<CircuitOpen>());
+        assert_eq!(service.state(), CircuitState::Open);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // trial request succeeds: closed
+        tokio::time::advance(Duration::from_secs(10)).await;
+        fail.store(false, Ordering::SeqCst);
+        assert!(service.serve(Context::default(), ()).await.is_ok());
+        assert_eq!(service.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker_failures_reset_on_success() {
+        let fail = Arc::new(AtomicBool::new(true));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let service = CircuitBreakerLayer::new()
+            .with_failure_threshold(2)
+            .layer(flaky_service(fail.clone(), calls.clone()));
+
+        assert!(service.serve(Context::default(), ()).await.is_err());
+        fail.store(false, Ordering::SeqCst);
+        assert!(service.serve(Context::default(), ()).await.is_ok());
+        fail.store(true, Ordering::SeqCst);
+        assert!(service.serve(Context::default(), ()).await.is_err());
+        assert_eq!(service.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker_half_open_max_calls() {
+        let service = CircuitBreakerLayer::new()
+            .with_failure_threshold(1)
+            .with_cool_down(Duration::from_secs(1))
+            .layer(service_fn(|_ctx: Context<()>, fail: bool| async move {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                if fail {
+                    Err(BoxError::from("failure"))
+                } else {
+                    Ok(())
+                }
+            }));
+
+        assert!(service.serve(Context::default(), true).await.is_err());
+        tokio::time::advance(Duration::from_secs(1)).await;
+
+        // only a single trial request is allowed at once
+        let (first, second) = tokio::join!(service.serve(Context::default(), false), async {
+            tokio::task::yield_now().await;
+            service.serve(Context::default(), false).await
+        });
+        assert!(first.is_ok());
+        assert!(second.unwrap_err().is::<CircuitOpen>());
+        assert_eq!(service.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker_predicate() {
+        let service = CircuitBreakerLayer::new()
+            .with_failure_threshold(1)
+            .with_failure_predicate(
+                |result: &Result<u16, BoxError>| !matches!(result, Ok(status) if *status < 500),
+            )
+            .layer(service_fn(|_ctx: Context<()>, status: u16| async move {
+                Ok::<_, BoxError>(status)
+            }));
+
+        assert_eq!(service.serve(Context::default(), 200).await.unwrap(), 200);
+        assert_eq!(service.state(), CircuitState::Closed);
+        assert_eq!(service.serve(Context::default(), 503).await.unwrap(), 503);
+        assert_eq!(service.state(), CircuitState::Open);
+        assert!(service
+            .serve(Context::default(), 200)
+            .await
+            .unwrap_err()
+            .is::<CircuitOpen>());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker_cancelled_trial() {
+        let service = CircuitBreakerLayer::new()
+            .with_failure_threshold(1)
+            .with_cool_down(Duration::from_secs(1))
+            .layer(service_fn(|_ctx: Context<()>, fail: bool| async move {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                if fail {
+                    Err(BoxError::from("failure"))
+                } else {
+                    Ok(())
+                }
+            }));
+
+        assert!(service.serve(Context::default(), true).await.is_err());
+        tokio::time::advance(Duration::from_secs(1)).await;
+
+        // cancelled trial request frees its slot
+        let _ = tokio::time::timeout(
+            Duration::from_millis(10),
+            service.serve(Context::default(), false),
+        )
+        .await;
+        assert_eq!(service.state(), CircuitState::HalfOpen);
+        assert!(service.serve(Context::default(), false).await.is_ok());
+        assert_eq!(service.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker_stale_trial() {
+        let service = CircuitBreakerLayer::new()
+            .with_failure_threshold(1)
+            .with_half_open_max_calls(2)
+            .with_cool_down(Duration::from_secs(1))
+            .layer(service_fn(
+                |_ctx: Context<()>, (fail, delay): (bool, u64)| async move {
+                    tokio::time::sleep(Duration::from_secs(delay)).await;
+                    if fail {
+                        Err(BoxError::from("failure"))
+                    } else {
+                        Ok(())
+                    }
+                },
+            ));
+
+        assert!(service.serve(Context::default(), (true, 0)).await.is_err());
+        tokio::time::advance(Duration::from_secs(1)).await;
+
+        // slow trial request, outliving its half-open period
+        let (stale, ()) = tokio::join!(service.serve(Context::default(), (false, 3)), async {
+            tokio::task::yield_now().await;
+            let err = service
+                .serve(Context::default(), (true, 0))
+                .await
+                .unwrap_err();
+            assert!(!err.is::<CircuitOpen>());
+            assert_eq!(service.state(), CircuitState::Open);
+
+            tokio::time::advance(Duration::from_secs(1)).await;
+            let (trial, ()) = tokio::join!(service.serve(Context::default(), (true, 5)), async {
+                tokio::task::yield_now().await;
+
+                // the result of the stale trial request is ignored
+                tokio::time::sleep(Duration::from_secs(3)).await;
+                assert_eq!(service.state(), CircuitState::HalfOpen);
+                let (second, third) =
+                    tokio::join!(service.serve(Context::default(), (false, 1)), async {
+                        tokio::task::yield_now().await;
+                        service.serve(Context::default(), (false, 0)).await
+                    });
+                assert!(second.is_ok());
+                assert!(third.unwrap_err().is::<CircuitOpen>());
+            });
+            assert!(trial.is_err());
+        });
+        assert!(stale.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker_zero_config_clamped() {
+        let service = CircuitBreakerLayer::new()
+            .with_failure_threshold(0)
+            .with_success_threshold(0)
+            .with_half_open_max_calls(0)
+            .with_cool_down(Duration::from_secs(1))
+            .layer(service_fn(|_ctx: Context<()>, fail: bool| async move {
+                if fail {
+                    Err(BoxError::from("failure"))
+                } else {
+                    Ok(())
+                }
+            }));
+
+        assert!(service.serve(Context::default(), true).await.is_err());
+        assert_eq!(service.state(), CircuitState::Open);
+        tokio::time::advance(Duration::from_secs(1)).await;
+
+        // a single trial request is allowed, and closes the circuit
+        assert!(service.serve(Context::default(), false).await.is_ok());
+        assert_eq!(service.state(), CircuitState::Closed);
+    }
+}
@@ -0,0 +1,514 @@
+//! A [`Policy`] that applies a separate budget per key.
+//!
+//! See [`KeyedPolicy`].
+//!
+//! # Examples
+//!
+//! ```
+//! use rama_core::layer::limit::{Limit, policy::{ConcurrentPolicy, KeyedPolicy}};
+//! use rama_core::service::service_fn;
+//! use rama_core::{Context, Service};
+//! # use std::convert::Infallible;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//!
+//! let service = service_fn(|_, _| async {
+//!     Ok::<_, Infallible>(())
+//! });
+//!
+//! // allow at most 2 concurrent requests per client
+//! let policy = KeyedPolicy::new(
+//!     |_: &Context<()>, client: &&'static str| Some(*client),
+//!     || ConcurrentPolicy::max(2),
+//! );
+//! let service = Limit::new(service, policy);
+//!
+//! let response = service.serve(Context::default(), "alice").await;
+//! assert!(response.is_ok());
+//! # }
+//! ```
+
+use super::{Policy, PolicyOutput, PolicyResult};
+use crate::Context;
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::{BuildHasher, Hash, RandomState},
+    marker::PhantomData,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// The amount of shards used to store the keyed policies.
+const SHARD_COUNT: usize = 16;
+
+/// The default duration after which an unused key is evicted.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Extracts the key from a request and its [`Context`],
+/// used by the [`KeyedPolicy`] to select the budget of the request.
+///
+/// It is implemented for any `Fn(&Context<State>, &Request) -> Option<Key>`.
+pub trait KeyExtractor<State, Request>: Send + Sync + 'static {
+    /// The key type used to select the budget.
+    type Key: Hash + Eq + Send + Sync + 'static;
+
+    /// Extract the key for the given request,
+    /// returning `None` in case the request should not be limited.
+    fn extract(&self, ctx: &Context<State>, request: &Request) -> Option<Self::Key>;
+}
+
+impl<State, Request, F, K> KeyExtractor<State, Request> for F
+where
+    F: Fn(&Context<State>, &Request) -> Option<K> + Send + Sync + 'static,
+    K: Hash + Eq + Send + Sync + 'static,
+{
+    type Key = K;
+
+    fn extract(&self, ctx: &Context<State>, request: &Request) -> Option<Self::Key> {
+        (self)(ctx, request)
+    }
+}
+
+/// A [`KeyExtractor`] which uses a value found in the [`Context`] as key,
+/// e.g. the `UserId` of an authenticated user or the `ProxyID` of the selected proxy.
+///
+/// Requests without such a value are not limited.
+pub struct ExtensionKey<T>(PhantomData<fn() -> T>);
+
+impl<T> ExtensionKey<T> {
+    /// Create a new [`ExtensionKey`].
+    pub const fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T> fmt::Debug for ExtensionKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ExtensionKey")
+            .field(&std::any::type_name::<T>())
+            .finish()
+    }
+}
+
+impl<T> Clone for ExtensionKey<T> {
+    fn clone(&self) -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T> Default for ExtensionKey<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<State, Request, T> KeyExtractor<State, Request> for ExtensionKey<T>
+where
+    T: Hash + Eq + Clone + Send + Sync + 'static,
+{
+    type Key = T;
+
+    fn extract(&self, ctx: &Context<State>, _request: &Request) -> Option<Self::Key> {
+        ctx.get::<T>().cloned()
+    }
+}
+
+/// A [`Policy`] which applies a separate instance of an inner [`Policy`] per key,
+/// e.g. to limit the requests per client IP, per user or per proxy.
+///
+/// The key is extracted from the request using a [`KeyExtractor`],
+/// and the inner policy of a key is created using the given factory the first time the key is seen.
+/// Requests for which no key can be extracted are not limited.
+///
+/// Keys which are no longer in use, and have not been used for the configured
+/// idle timeout (5 minutes by default), are evicted, resetting their budget.
+/// Make sure the idle timeout exceeds the period of time based policies,
+/// such as the [`RatePolicy`].
+///
+/// Clones of a [`KeyedPolicy`] share the same budgets.
+///
+/// [`RatePolicy`]: super::RatePolicy
+pub struct KeyedPolicy<E, F, K, P> {
+    extractor: E,
+    factory: F,
+    idle_timeout: Duration,
+    store: Arc<KeyedStore<K, P>>,
+}
+
+impl<E: fmt::Debug, F, K: fmt::Debug, P: fmt::Debug> fmt::Debug for KeyedPolicy<E, F, K, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyedPolicy")
+            .field("extractor", &self.extractor)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("store", &self.store)
+            .finish()
+    }
+}
+
+impl<E: Clone, F: Clone, K, P> Clone for KeyedPolicy<E, F, K, P> {
+    fn clone(&self) -> Self {
+        Self {
+            extractor: self.extractor.clone(),
+            factory: self.factory.clone(),
+            idle_timeout: self.idle_timeout,
+            store: self.store.clone(),
+        }
+    }
+}
+
+impl<E, F, K, P> KeyedPolicy<E, F, K, P> {
+    /// Create a new [`KeyedPolicy`], using the given [`KeyExtractor`]
+    /// and a factory to create the inner [`Policy`] for each key.
+    pub fn new(extractor: E, factory: F) -> Self
+    where
+        F: Fn() -> P,
+    {
+        Self {
+            extractor,
+            factory,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            store: Arc::new(KeyedStore::new()),
+        }
+    }
+
+    /// Set the duration after which a key that is no longer in use is evicted.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Set the duration after which a key that is no longer in use is evicted.
+    pub fn set_idle_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.idle_timeout = timeout;
+        self
+    }
+}
+
+impl<E, F, K, P> KeyedPolicy<E, F, K, P>
+where
+    K: Hash + Eq,
+{
+    /// Returns the amount of keys currently tracked.
+    pub fn len(&self) -> usize {
+        self.store
+            .shards
+            .iter()
+            .map(|shard| shard.lock().entries.len())
+            .sum()
+    }
+
+    /// Returns `true` in case no keys are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the inner [`Policy`] of the given key, if tracked.
+    ///
+    /// Holding on to the returned policy keeps the key from being evicted.
+    pub fn policy(&self, key: &K) -> Option<Arc<P>> {
+        self.store
+            .shard(key)
+            .lock()
+            .entries
+            .get(key)
+            .map(|entry| entry.policy.clone())
+    }
+
+    /// Returns the current usage of all tracked keys, for diagnostic purposes.
+    pub fn usage(&self) -> Vec<KeyUsage<K>>
+    where
+        K: Clone,
+    {
+        let now = Instant::now();
+        let mut usage = Vec::new();
+        for shard in self.store.shards.iter() {
+            let shard = shard.lock();
+            usage.extend(shard.entries.iter().map(|(key, entry)| KeyUsage {
+                key: key.clone(),
+                in_flight: entry.in_flight(),
+                idle: now.saturating_duration_since(entry.last_used),
+            }));
+        }
+        usage
+    }
+
+    /// Evict all keys which are no longer in use and have been idle for longer than the idle timeout.
+    ///
+    /// Keys are also evicted while checking requests, but only
+    /// for the keys stored together with the key of that request.
+    pub fn evict_idle(&self) {
+        let now = Instant::now();
+        for shard in self.store.shards.iter() {
+            shard.lock().evict_idle(now, self.idle_timeout);
+        }
+    }
+
+    fn policy_for(&self, key: K) -> Arc<P>
+    where
+        F: Fn() -> P,
+    {
+        let now = Instant::now();
+        let mut shard = self.store.shard(&key).lock();
+        if now.saturating_duration_since(shard.last_eviction) >= self.idle_timeout {
+            shard.evict_idle(now, self.idle_timeout);
+        }
+        let entry = shard.entries.entry(key).or_insert_with(|| KeyedEntry {
+            policy: Arc::new((self.factory)()),
+            last_used: now,
+        });
+        entry.last_used = now;
+        entry.policy.clone()
+    }
+}
+
+impl<E, F, K, P, State, Request> Policy<State, Request> for KeyedPolicy<E, F, K, P>
+where
+    E: KeyExtractor<State, Request, Key = K>,
+    F: Fn() -> P + Send + Sync + 'static,
+    K: Hash + Eq + Send + Sync + 'static,
+    P: Policy<State, Request>,
+    State: Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Guard = Option<KeyedGuard<P::Guard, P>>;
+    type Error = P::Error;
+
+    async fn check(
+        &self,
+        ctx: Context<State>,
+        request: Request,
+    ) -> PolicyResult<State, Request, Self::Guard, Self::Error> {
+        let Some(key) = self.extractor.extract(&ctx, &request) else {
+            return PolicyResult {
+                ctx,
+                request,
+                output: PolicyOutput::Ready(None),
+            };
+        };
+
+        let policy = self.policy_for(key);
+        let result = policy.check(ctx, request).await;
+        let output = match result.output {
+            PolicyOutput::Ready(guard) => PolicyOutput::Ready(Some(KeyedGuard {
+                guard,
+                _policy: policy,
+            })),
+            PolicyOutput::Abort(err) => PolicyOutput::Abort(err),
+            PolicyOutput::Retry => PolicyOutput::Retry,
+        };
+
+        PolicyResult {
+            ctx: result.ctx,
+            request: result.request,
+            output,
+        }
+    }
+}
+
+/// The guard of a [`KeyedPolicy`], wrapping the guard of the inner [`Policy`].
+///
+/// It keeps the key in use, preventing it from being evicted.
+pub struct KeyedGuard<G, P> {
+    guard: G,
+    _policy: Arc<P>,
+}
+
+impl<G: fmt::Debug, P> fmt::Debug for KeyedGuard<G, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyedGuard")
+            .field("guard", &self.guard)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The usage of a single key of a [`KeyedPolicy`].
+pub struct KeyUsage<K> {
+    /// The key.
+    pub key: K,
+    /// The amount of requests currently in flight for this key.
+    pub in_flight: usize,
+    /// The time since the key was last used.
+    pub idle: Duration,
+}
+
+struct KeyedStore<K, P> {
+    hasher: RandomState,
+    shards: Box<[Mutex<KeyedShard<K, P>>]>,
+}
+
+impl<K, P> KeyedStore<K, P> {
+    fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            hasher: RandomState::new(),
+            shards: (0..SHARD_COUNT)
+                .map(|_| {
+                    Mutex::new(KeyedShard {
+                        entries: HashMap::new(),
+                        last_eviction: now,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    fn shard(&self, key: &K) -> &Mutex<KeyedShard<K, P>>
+    where
+        K: Hash,
+    {
+        let index = self.hasher.hash_one(key) as usize % self.shards.len();
+        &self.shards[index]
+    }
+}
+
+impl<K: fmt::Debug, P: fmt::Debug> fmt::Debug for KeyedStore<K, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyedStore")
+            .field("shards", &self.shards)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+struct KeyedShard<K, P> {
+    entries: HashMap<K, KeyedEntry<P>>,
+    last_eviction: Instant,
+}
+
+impl<K, P> KeyedShard<K, P> {
+    fn evict_idle(&mut self, now: Instant, idle_timeout: Duration) {
+        self.entries.retain(|_, entry| {
+            entry.in_flight() > 0 || now.saturating_duration_since(entry.last_used) < idle_timeout
+        });
+        self.last_eviction = now;
+    }
+}
+
+#[derive(Debug)]
+struct KeyedEntry<P> {
+    policy: Arc<P>,
+    last_used: Instant,
+}
+
+impl<P> KeyedEntry<P> {
+    fn in_flight(&self) -> usize {
+        Arc::strong_count(&self.policy) - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::limit::policy::{ConcurrentPolicy, RatePolicy};
+
+    fn assert_ready<S, R, G, E>(result: PolicyResult<S, R, G, E>) -> G {
+        match result.output {
+            PolicyOutput::Ready(guard) => guard,
+            _ => panic!("unexpected output, expected ready"),
+        }
+    }
+
+    fn assert_abort<S, R, G, E>(result: PolicyResult<S, R, G, E>) {
+        match result.output {
+            PolicyOutput::Abort(_) => (),
+            _ => panic!("unexpected output, expected abort"),
+        }
+    }
+
+    fn client_key(_: &Context<()>, client: &&'static str) -> Option<&'static str> {
+        (!client.is_empty()).then_some(*client)
+    }
+
+    #[tokio::test]
+    async fn keyed_policy_concurrent() {
+        let policy = KeyedPolicy::new(client_key, || ConcurrentPolicy::max(1));
+
+        let guard_a = assert_ready(policy.check(Context::default(), "a").await);
+        assert!(guard_a.is_some());
+        assert_abort(policy.check(Context::default(), "a").await);
+
+        // other keys have their own budget
+        let _guard_b = assert_ready(policy.check(Context::default(), "b").await);
+
+        // requests without key are not limited
+        for _ in 0..3 {
+            let guard = assert_ready(policy.check(Context::default(), "").await);
+            assert!(guard.is_none());
+        }
+
+        drop(guard_a);
+        assert_ready(policy.check(Context::default(), "a").await);
+        assert_eq!(policy.len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keyed_policy_rate() {
+        let policy = KeyedPolicy::new(client_key, || RatePolicy::per_second(1));
+
+        assert_ready(policy.check(Context::default(), "a").await);
+        assert_ready(policy.check(Context::default(), "b").await);
+        assert_abort(policy.check(Context::default(), "a").await);
+        assert_abort(policy.check(Context::default(), "b").await);
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_ready(policy.check(Context::default(), "a").await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keyed_policy_usage_and_eviction() {
+        let policy = KeyedPolicy::new(client_key, || ConcurrentPolicy::max(2))
+            .with_idle_timeout(Duration::from_secs(10));
+
+        let guard_a = assert_ready(policy.check(Context::default(), "a").await);
+        drop(assert_ready(policy.check(Context::default(), "b").await));
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+
+        let mut usage = policy.usage();
+        usage.sort_by_key(|usage| usage.key);
+        assert_eq!(usage.len(), 2);
+        assert_eq!(usage[0].key, "a");
+        assert_eq!(usage[0].in_flight, 1);
+        assert_eq!(usage[0].idle, Duration::from_secs(5));
+        assert_eq!(usage[1].key, "b");
+        assert_eq!(usage[1].in_flight, 0);
+
+        // not idle for long enough
+        policy.evict_idle();
+        assert_eq!(policy.len(), 2);
+
+        // keys in use are never evicted
+        tokio::time::advance(Duration::from_secs(10)).await;
+        policy.evict_idle();
+        assert_eq!(policy.len(), 1);
+        assert!(policy.policy(&"a").is_some());
+        assert!(policy.policy(&"b").is_none());
+
+        drop(guard_a);
+        policy.evict_idle();
+        assert!(policy.is_empty());
+    }
+
+    #[tokio::test]
+    async fn keyed_policy_extension_key() {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        struct UserId(&'static str);
+
+        let policy = KeyedPolicy::new(ExtensionKey::<UserId>::new(), || ConcurrentPolicy::max(1));
+
+        let mut ctx = Context::default();
+        ctx.insert(UserId("alice"));
+
+        let _guard = assert_ready(policy.check(ctx.clone(), ()).await);
+        assert_abort(policy.check(ctx, ()).await);
+
+        // requests without user are not limited
+        assert_ready(policy.check(Context::default(), ()).await);
+        assert_ready(policy.check(Context::default(), ()).await);
+    }
+}
@@ -23,6 +23,12 @@
 //! external sockets or you want to rate limit specific domains/paths only for http requests.
 //! See the [`http_rate_limit.rs`] example for a use case.
 //!
+//! # Keyed Policies
+//!
+//! A [`KeyedPolicy`] applies a separate budget per key, e.g. per client IP or per user,
+//! by wrapping any other [`Policy`]. The key is extracted from the [`Context`]
+//! and request using a [`KeyExtractor`].
+//!
 //! [`Matcher`]: crate::matcher::Matcher
 //! [`Extensions`]: crate::context::Extensions
 //! [`http_listener_hello.rs`]: https://github.com/plabayo/rama/blob/main/examples/http_rate_limit.rs
@@ -35,6 +41,14 @@ mod concurrent;
 #[doc(inline)]
 pub use concurrent::{ConcurrentCounter, ConcurrentPolicy, ConcurrentTracker, LimitReached};
 
+mod rate;
+#[doc(inline)]
+pub use rate::{RateLimitReached, RatePolicy};
+
+mod keyed;
+#[doc(inline)]
+pub use keyed::{ExtensionKey, KeyExtractor, KeyUsage, KeyedGuard, KeyedPolicy};
+
 mod matcher;
 
 /// The full result of a limit policy.
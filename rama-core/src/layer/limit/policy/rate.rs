@@ -0,0 +1,318 @@
+//! A [`Policy`] that limits the rate of requests.
+//!
+//! See [`RatePolicy`].
+//!
+//! # Examples
+//!
+//! ```
+//! use rama_core::layer::limit::{Limit, policy::RatePolicy};
+//! use rama_core::service::service_fn;
+//! use rama_core::{Context, Service};
+//! # use std::convert::Infallible;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//!
+//! let service = service_fn(|_, _| async {
+//!     Ok::<_, Infallible>(())
+//! });
+//! let service = Limit::new(service, RatePolicy::per_second(1));
+//!
+//! let response = service.serve(Context::default(), ()).await;
+//! assert!(response.is_ok());
+//!
+//! let response = service.serve(Context::default(), ()).await;
+//! assert!(response.is_err());
+//! # }
+//! ```
+
+use super::{Policy, PolicyOutput, PolicyResult};
+use crate::Context;
+use parking_lot::Mutex;
+use rama_utils::backoff::Backoff;
+use std::{fmt, sync::Arc, time::Duration};
+use tokio::time::Instant;
+
+/// A [`Policy`] that limits the rate of requests,
+/// allowing bursts of requests up to a configured size.
+///
+/// It is implemented as a [GCRA] (Generic Cell Rate Algorithm),
+/// which behaves like a token bucket which is refilled at a constant rate,
+/// without requiring a background task to refill it.
+///
+/// Clones of a [`RatePolicy`] share the same budget.
+///
+/// [GCRA]: https://en.wikipedia.org/wiki/Generic_cell_rate_algorithm
+pub struct RatePolicy<B> {
+    /// The time it takes to refill a single request.
+    interval: Duration,
+    /// The amount of requests allowed at once.
+    burst: u32,
+    /// Theoretical arrival time of the next request.
+    tat: Arc<Mutex<Option<Instant>>>,
+    backoff: B,
+}
+
+impl<B: fmt::Debug> std::fmt::Debug for RatePolicy<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RatePolicy")
+            .field("interval", &self.interval)
+            .field("burst", &self.burst)
+            .field("tat", &self.tat)
+            .field("backoff", &self.backoff)
+            .finish()
+    }
+}
+
+impl<B: Clone> Clone for RatePolicy<B> {
+    fn clone(&self) -> Self {
+        RatePolicy {
+            interval: self.interval,
+            burst: self.burst,
+            tat: self.tat.clone(),
+            backoff: self.backoff.clone(),
+        }
+    }
+}
+
+impl RatePolicy<()> {
+    /// Create a new [`RatePolicy`], allowing `rate` requests per `period`.
+    ///
+    /// The burst size defaults to `rate`, meaning that all requests of a period
+    /// can be made at once. Use [`RatePolicy::with_burst`] to change it.
+    ///
+    /// # Panics
+    ///
+    /// Panics in case `rate` is zero.
+    pub fn new(rate: u32, period: Duration) -> Self {
+        assert!(rate > 0, "rate policy: rate has to be greater than zero");
+        RatePolicy {
+            interval: period / rate,
+            burst: rate,
+            tat: Arc::new(Mutex::new(None)),
+            backoff: (),
+        }
+    }
+
+    /// Create a new [`RatePolicy`], allowing `rate` requests per second.
+    ///
+    /// # Panics
+    ///
+    /// Panics in case `rate` is zero.
+    pub fn per_second(rate: u32) -> Self {
+        Self::new(rate, Duration::from_secs(1))
+    }
+
+    /// Create a new [`RatePolicy`], allowing `rate` requests per minute.
+    ///
+    /// # Panics
+    ///
+    /// Panics in case `rate` is zero.
+    pub fn per_minute(rate: u32) -> Self {
+        Self::new(rate, Duration::from_secs(60))
+    }
+}
+
+impl<B> RatePolicy<B> {
+    /// Set the maximum amount of requests that are allowed at once,
+    /// with a minimum of one.
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    /// Set the maximum amount of requests that are allowed at once,
+    /// with a minimum of one.
+    pub fn set_burst(&mut self, burst: u32) -> &mut Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    /// Use the given [`Backoff`] policy when the rate limit is reached,
+    /// after which the request is retried, instead of aborting the request immediately.
+    ///
+    /// The request is aborted once the [`Backoff`] policy gives up.
+    pub fn with_backoff<T>(self, backoff: T) -> RatePolicy<T> {
+        RatePolicy {
+            interval: self.interval,
+            burst: self.burst,
+            tat: self.tat,
+            backoff,
+        }
+    }
+
+    /// Try to consume a single request of the budget,
+    /// returning how long to wait until a request is allowed in case it is not.
+    fn try_acquire(&self) -> Result<(), Duration> {
+        let now = Instant::now();
+        // saturate, as an interval times burst can exceed what a duration can hold
+        let tolerance = self.interval.saturating_mul(self.burst - 1);
+
+        let mut tat = self.tat.lock();
+        let current = tat.map(|tat| tat.max(now)).unwrap_or(now);
+        let earliest = current.checked_sub(tolerance).unwrap_or(now);
+        if earliest > now {
+            return Err(earliest - now);
+        }
+        // an interval which cannot be represented in the future is never refilled
+        *tat = Some(current.checked_add(self.interval).ok_or(self.interval)?);
+        Ok(())
+    }
+}
+
+impl<B, State, Request> Policy<State, Request> for RatePolicy<B>
+where
+    B: Backoff,
+    State: Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Guard = ();
+    type Error = RateLimitReached;
+
+    async fn check(
+        &self,
+        ctx: Context<State>,
+        request: Request,
+    ) -> PolicyResult<State, Request, Self::Guard, Self::Error> {
+        let retry_after = match self.try_acquire() {
+            Ok(()) => {
+                return PolicyResult {
+                    ctx,
+                    request,
+                    output: PolicyOutput::Ready(()),
+                }
+            }
+            Err(retry_after) => retry_after,
+        };
+
+        let output = if !self.backoff.next_backoff().await {
+            PolicyOutput::Abort(RateLimitReached { retry_after })
+        } else {
+            PolicyOutput::Retry
+        };
+
+        PolicyResult {
+            ctx,
+            request,
+            output,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Error returned by the [`RatePolicy`] when a request
+/// is aborted because the rate limit is reached.
+pub struct RateLimitReached {
+    retry_after: Duration,
+}
+
+impl RateLimitReached {
+    /// The minimum time to wait before a new request can be made,
+    /// as it was at the moment the request was aborted.
+    ///
+    /// Can for example be used for the `Retry-After` header of an http response.
+    pub fn retry_after(&self) -> Duration {
+        self.retry_after
+    }
+}
+
+impl fmt::Display for RateLimitReached {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "request aborted due to exhausted rate limit (retry after {:?})",
+            self.retry_after
+        )
+    }
+}
+
+impl std::error::Error for RateLimitReached {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Service;
+    use rama_utils::backoff::ExponentialBackoff;
+
+    fn assert_ready<S, R, G, E>(result: PolicyResult<S, R, G, E>) -> G {
+        match result.output {
+            PolicyOutput::Ready(guard) => guard,
+            _ => panic!("unexpected output, expected ready"),
+        }
+    }
+
+    fn assert_abort<S, R, G, E>(result: PolicyResult<S, R, G, E>) -> E {
+        match result.output {
+            PolicyOutput::Abort(err) => err,
+            _ => panic!("unexpected output, expected abort"),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_policy() {
+        let policy = RatePolicy::per_second(2);
+
+        assert_ready(policy.check(Context::default(), ()).await);
+        assert_ready(policy.check(Context::default(), ()).await);
+        let err = assert_abort(policy.check(Context::default(), ()).await);
+        assert_eq!(err.retry_after(), Duration::from_millis(500));
+
+        tokio::time::advance(Duration::from_millis(500)).await;
+        assert_ready(policy.check(Context::default(), ()).await);
+        assert_abort(policy.check(Context::default(), ()).await);
+
+        // budget does not grow beyond the burst size
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert_ready(policy.check(Context::default(), ()).await);
+        assert_ready(policy.check(Context::default(), ()).await);
+        assert_abort(policy.check(Context::default(), ()).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_policy_burst() {
+        let policy = RatePolicy::per_second(10).with_burst(1);
+
+        assert_ready(policy.check(Context::default(), ()).await);
+        let err = assert_abort(policy.check(Context::default(), ()).await);
+        assert_eq!(err.retry_after(), Duration::from_millis(100));
+
+        tokio::time::advance(Duration::from_millis(100)).await;
+        assert_ready(policy.check(Context::default(), ()).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_policy_large_burst() {
+        // interval * (burst - 1) does not fit in a duration
+        let policy = RatePolicy::new(1, Duration::from_secs(u64::MAX / 8)).with_burst(u32::MAX);
+
+        assert_ready(policy.check(Context::default(), ()).await);
+        assert_ready(policy.check(Context::default(), ()).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_policy_clone() {
+        let policy = RatePolicy::per_second(1);
+        let policy_clone = policy.clone();
+
+        assert_ready(policy.check(Context::default(), ()).await);
+        assert_abort(policy_clone.check(Context::default(), ()).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_policy_backoff() {
+        let policy = RatePolicy::per_second(1).with_backoff(ExponentialBackoff::default());
+
+        assert_ready(policy.check(Context::default(), ()).await);
+        let result = policy.check(Context::default(), ()).await;
+        assert!(matches!(result.output, PolicyOutput::Retry));
+
+        // the limit service keeps retrying until the budget is refilled
+        let service = crate::layer::limit::Limit::new(
+            crate::service::service_fn(|_, _| async { Ok::<_, std::convert::Infallible>(()) }),
+            policy,
+        );
+        let start = Instant::now();
+        assert!(service.serve(Context::default(), ()).await.is_ok());
+        assert!(start.elapsed() > Duration::ZERO);
+    }
+}
@@ -0,0 +1,62 @@
+//! Error type for the queue middleware.
+
+use std::{error, fmt, time::Duration};
+
+/// The request was rejected because the service is overloaded.
+///
+/// Returned by the [`Queue`] without calling the inner service,
+/// e.g. such that an http service can respond with a `503 Service Unavailable`
+/// status code and a `Retry-After` header.
+///
+/// [`Queue`]: super::Queue
+#[derive(Debug, Clone)]
+pub struct Overloaded {
+    reason: OverloadReason,
+    retry_after: Duration,
+}
+
+/// The reason why a request was rejected by the [`Queue`].
+///
+/// [`Queue`]: super::Queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverloadReason {
+    /// All workers were busy, and the queue was full.
+    QueueFull,
+    /// The request waited longer in the queue than the maximum queue time.
+    QueueTimeout,
+}
+
+impl Overloaded {
+    pub(super) const fn new(reason: OverloadReason, retry_after: Duration) -> Self {
+        Self {
+            reason,
+            retry_after,
+        }
+    }
+
+    /// The reason why the request was rejected.
+    pub fn reason(&self) -> OverloadReason {
+        self.reason
+    }
+
+    /// The suggested duration to wait before retrying the request.
+    pub fn retry_after(&self) -> Duration {
+        self.retry_after
+    }
+}
+
+impl fmt::Display for Overloaded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.reason {
+            OverloadReason::QueueFull => "queue is full",
+            OverloadReason::QueueTimeout => "max queue time exceeded",
+        };
+        write!(
+            f,
+            "request rejected by overloaded service: {reason} (retry after {:?})",
+            self.retry_after
+        )
+    }
+}
+
+impl error::Error for Overloaded {}
@@ -0,0 +1,105 @@
+use super::{Queue, QueueConfig, QueueState};
+use crate::Layer;
+use std::{fmt, sync::Arc, time::Duration};
+
+/// A [`Layer`] that produces [`Queue`] services.
+///
+/// All services produced by the same layer (or its clones) share the same workers and queue.
+pub struct QueueLayer {
+    config: QueueConfig,
+    state: Arc<QueueState>,
+}
+
+impl fmt::Debug for QueueLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueueLayer")
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl Clone for QueueLayer {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl QueueLayer {
+    /// Creates a new [`QueueLayer`], serving requests with the given amount of workers
+    /// (with a minimum of one), queueing up to `capacity` requests while all workers are busy.
+    ///
+    /// By default requests can wait in the queue for as long as it takes,
+    /// and rejected requests suggest to retry after one second.
+    pub fn new(workers: usize, capacity: usize) -> Self {
+        let config = QueueConfig {
+            workers: workers.max(1),
+            capacity,
+            max_queue_time: None,
+            retry_after: Duration::from_secs(1),
+        };
+        Self {
+            state: Arc::new(QueueState::new(config.clone())),
+            config,
+        }
+    }
+
+    /// Set the maximum duration a request can wait in the queue,
+    /// after which it is rejected.
+    pub fn with_max_queue_time(mut self, max_queue_time: Duration) -> Self {
+        self.config.max_queue_time = Some(max_queue_time);
+        self.reset()
+    }
+
+    /// Set the maximum duration a request can wait in the queue,
+    /// after which it is rejected.
+    pub fn set_max_queue_time(&mut self, max_queue_time: Duration) -> &mut Self {
+        self.config.max_queue_time = Some(max_queue_time);
+        self.reset_mut()
+    }
+
+    /// Set the suggested duration to wait before retrying a rejected request.
+    ///
+    /// See [`Overloaded::retry_after`] for more information.
+    ///
+    /// [`Overloaded::retry_after`]: super::Overloaded::retry_after
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.config.retry_after = retry_after;
+        self.reset()
+    }
+
+    /// Set the suggested duration to wait before retrying a rejected request.
+    ///
+    /// See [`Overloaded::retry_after`] for more information.
+    ///
+    /// [`Overloaded::retry_after`]: super::Overloaded::retry_after
+    pub fn set_retry_after(&mut self, retry_after: Duration) -> &mut Self {
+        self.config.retry_after = retry_after;
+        self.reset_mut()
+    }
+
+    fn reset(mut self) -> Self {
+        self.reset_mut();
+        self
+    }
+
+    /// Apply the (modified) config by creating a new queue,
+    /// detached from the services produced so far.
+    fn reset_mut(&mut self) -> &mut Self {
+        self.state = Arc::new(QueueState::new(self.config.clone()));
+        self
+    }
+}
+
+impl<S> Layer<S> for QueueLayer {
+    type Service = Queue<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Queue {
+            inner,
+            state: self.state.clone(),
+        }
+    }
+}
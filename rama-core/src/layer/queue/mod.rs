@@ -0,0 +1,309 @@
+//! Middleware that serves requests with a fixed amount of workers,
+//! queueing the requests that arrive while all workers are busy,
+//! and shedding load when the queue is full or requests are queued for too long.
+//!
+//! Requests are served in the order they arrive (FIFO). A request is rejected
+//! with an [`Overloaded`] error, without calling the inner service, when:
+//!
+//! - all workers are busy and the queue is full ([`OverloadReason::QueueFull`]);
+//! - the request waited longer in the queue than the max queue time ([`OverloadReason::QueueTimeout`]),
+//!   such that a standing queue does not keep growing the latency of all requests.
+//!
+//! The [`Overloaded`] error carries a suggested retry delay,
+//! such that http services can respond with a `503 Service Unavailable`
+//! status code and a `Retry-After` header.
+//!
+//! In contrast to the [`Limit`] middleware with a [`ConcurrentPolicy`],
+//! requests are not retried using a backoff, but wait in a bounded queue for their turn.
+//!
+//! All services created by the same [`QueueLayer`] share the same workers and queue.
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{
+//!     layer::queue::{OverloadReason, Overloaded, QueueLayer},
+//!     service::service_fn,
+//!     Context, Layer, Service,
+//! };
+//! use std::{convert::Infallible, time::Duration};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! // a single worker, without room for queued requests
+//! let service = QueueLayer::new(1, 0)
+//!     .with_max_queue_time(Duration::from_millis(100))
+//!     .layer(service_fn(|| async {
+//!         tokio::time::sleep(Duration::from_millis(50)).await;
+//!         Ok::<_, Infallible>(())
+//!     }));
+//!
+//! let (first, second) = tokio::join!(
+//!     service.serve(Context::default(), ()),
+//!     service.serve(Context::default(), ()),
+//! );
+//! assert!(first.is_ok());
+//! let err = second.unwrap_err();
+//! let err = err.downcast_ref::<Overloaded>().unwrap();
+//! assert_eq!(err.reason(), OverloadReason::QueueFull);
+//! # }
+//! ```
+//!
+//! [`Limit`]: crate::layer::Limit
+//! [`ConcurrentPolicy`]: crate::layer::limit::policy::ConcurrentPolicy
+
+use crate::error::BoxError;
+use crate::{Context, Service};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::Semaphore;
+
+mod error;
+#[doc(inline)]
+pub use error::{OverloadReason, Overloaded};
+
+mod layer;
+#[doc(inline)]
+pub use layer::QueueLayer;
+
+/// Middleware which serves requests with a fixed amount of workers,
+/// queueing requests in a bounded queue while all workers are busy.
+///
+/// See the [module docs](self) for more information.
+pub struct Queue<S> {
+    inner: S,
+    state: Arc<QueueState>,
+}
+
+impl<S> Queue<S> {
+    define_inner_service_accessors!();
+
+    /// Returns the amount of requests waiting in the queue.
+    pub fn queued(&self) -> usize {
+        self.state.queued.load(Ordering::Acquire)
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for Queue<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Queue")
+            .field("inner", &self.inner)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for Queue<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct QueueConfig {
+    workers: usize,
+    capacity: usize,
+    max_queue_time: Option<Duration>,
+    retry_after: Duration,
+}
+
+#[derive(Debug)]
+struct QueueState {
+    config: QueueConfig,
+    workers: Semaphore,
+    queued: AtomicUsize,
+}
+
+impl QueueState {
+    fn new(config: QueueConfig) -> Self {
+        Self {
+            workers: Semaphore::new(config.workers),
+            queued: AtomicUsize::new(0),
+            config,
+        }
+    }
+
+    fn overloaded(&self, reason: OverloadReason) -> Overloaded {
+        Overloaded::new(reason, self.config.retry_after)
+    }
+}
+
+/// Tracks a request waiting in the queue, leaving the queue when dropped.
+struct QueuedGuard<'a>(&'a AtomicUsize);
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl<S, State, Request> Service<State, Request> for Queue<S>
+where
+    S: Service<State, Request, Error: Into<BoxError>>,
+    State: Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        request: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let state = &self.state;
+
+        let _permit = match state.workers.try_acquire() {
+            Ok(permit) => permit,
+            Err(_) => {
+                let capacity = state.config.capacity;
+                if state
+                    .queued
+                    .fetch_update(Ordering::AcqRel, Ordering::Acquire, |queued| {
+                        (queued < capacity).then_some(queued + 1)
+                    })
+                    .is_err()
+                {
+                    return Err(state.overloaded(OverloadReason::QueueFull).into());
+                }
+                let _queued = QueuedGuard(&state.queued);
+
+                let acquire = state.workers.acquire();
+                let permit = match state.config.max_queue_time {
+                    Some(max_queue_time) => tokio::time::timeout(max_queue_time, acquire)
+                        .await
+                        .map_err(|_| state.overloaded(OverloadReason::QueueTimeout))?,
+                    None => acquire.await,
+                };
+                // the semaphore is never closed
+                permit.expect("acquire queue worker")
+            }
+        };
+
+        self.inner.serve(ctx, request).await.map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::service_fn;
+    use crate::Layer;
+    use std::{convert::Infallible, future::Future, pin::Pin, task::Poll};
+
+    fn service(
+        layer: QueueLayer,
+    ) -> Queue<impl Service<(), Duration, Response = Duration, Error = Infallible>> {
+        layer.layer(service_fn(|duration: Duration| async move {
+            tokio::time::sleep(duration).await;
+            Ok::<_, Infallible>(duration)
+        }))
+    }
+
+    fn reason(err: BoxError) -> OverloadReason {
+        err.downcast_ref::<Overloaded>().unwrap().reason()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_queue_full() {
+        let service = service(QueueLayer::new(1, 1));
+        let d = Duration::from_secs(1);
+
+        let (a, b, c) = tokio::join!(
+            service.serve(Context::default(), d),
+            service.serve(Context::default(), d),
+            service.serve(Context::default(), d),
+        );
+        assert_eq!(a.unwrap(), d);
+        assert_eq!(b.unwrap(), d);
+        assert_eq!(reason(c.unwrap_err()), OverloadReason::QueueFull);
+
+        // queue is available again
+        service.serve(Context::default(), d).await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_queue_timeout() {
+        let service = service(
+            QueueLayer::new(1, 10)
+                .with_max_queue_time(Duration::from_millis(1500))
+                .with_retry_after(Duration::from_secs(5)),
+        );
+        let d = Duration::from_secs(1);
+
+        let (a, b, c) = tokio::join!(
+            service.serve(Context::default(), d),
+            service.serve(Context::default(), d),
+            service.serve(Context::default(), d),
+        );
+        assert!(a.is_ok());
+        // served after queueing for a second
+        assert!(b.is_ok());
+        // would have to queue for two seconds
+        let err = c.unwrap_err();
+        let err = err.downcast_ref::<Overloaded>().unwrap();
+        assert_eq!(err.reason(), OverloadReason::QueueTimeout);
+        assert_eq!(err.retry_after(), Duration::from_secs(5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_queue_workers() {
+        let layer = QueueLayer::new(2, 0);
+        let service = service(layer);
+        let d = Duration::from_secs(1);
+
+        let start = tokio::time::Instant::now();
+        let (a, b) = tokio::join!(
+            service.serve(Context::default(), d),
+            service.serve(Context::default(), d),
+        );
+        assert!(a.is_ok());
+        assert!(b.is_ok());
+        // served concurrently
+        assert_eq!(start.elapsed(), d);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_queue_fifo_and_cancel() {
+        let service = service(QueueLayer::new(1, 2));
+        let d = Duration::from_secs(1);
+
+        let mut first = Box::pin(service.serve(Context::default(), d));
+        let mut second = Box::pin(service.serve(Context::default(), d));
+        let mut third = Box::pin(service.serve(Context::default(), Duration::ZERO));
+
+        // first takes the worker, the others are queued
+        assert!(poll_once(&mut first).await.is_none());
+        assert!(poll_once(&mut second).await.is_none());
+        assert!(poll_once(&mut third).await.is_none());
+        assert_eq!(service.queued(), 2);
+
+        // cancelled requests leave the queue
+        drop(second);
+        assert_eq!(service.queued(), 1);
+
+        let start = tokio::time::Instant::now();
+        first.await.unwrap();
+        third.await.unwrap();
+        assert_eq!(start.elapsed(), d);
+        assert_eq!(service.queued(), 0);
+    }
+
+    async fn poll_once<F: Future + Unpin>(fut: &mut F) -> Option<F::Output> {
+        std::future::poll_fn(|cx| match Pin::new(&mut *fut).poll(cx) {
+            Poll::Ready(output) => Poll::Ready(Some(output)),
+            Poll::Pending => Poll::Ready(None),
+        })
+        .await
+    }
+}
@@ -66,6 +66,7 @@ pub use context::Context;
 pub use ::rama_error as error;
 
 pub mod graceful;
+pub mod reload;
 pub mod rt;
 
 pub mod service;
@@ -0,0 +1,399 @@
+//! Hot reloading of services and configuration values,
+//! without restarting the application.
+//!
+//! A [`Reloadable`] value is a shared handle to a value (e.g. a [`Service`],
+//! a routing table or a TLS configuration) which can be atomically swapped at runtime.
+//! Each [`Reloadable::load`] returns the current value, which remains valid
+//! for as long as it is held, such that in-flight requests finish on the old value,
+//! while new requests use the new value. A [`Reloadable`] [`Service`] does this for you.
+//!
+//! Reloads can be triggered using a [`ReloadTrigger`], for example
+//! when receiving a `SIGHUP` signal (see [`ReloadTrigger::trigger_on_sighup`]),
+//! or when a file is modified (see [`ReloadTrigger::trigger_on_file_change`]).
+//! [`Reloadable::reload_on`] spawns a task, tied to a graceful [`Shutdown`],
+//! which reloads the value using a loader function every time a reload is triggered.
+//! Failed reloads are logged and keep the current value in place.
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{
+//!     graceful::Shutdown,
+//!     reload::{ReloadTrigger, Reloadable},
+//!     service::service_fn,
+//!     Context, Service,
+//! };
+//! use std::{
+//!     convert::Infallible,
+//!     sync::{atomic::{AtomicUsize, Ordering}, Arc},
+//! };
+//!
+//! fn service(version: usize) -> impl Service<(), (), Response = usize, Error = Infallible> {
+//!     service_fn(move || async move { Ok(version) })
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let shutdown = Shutdown::new(std::future::pending::<()>());
+//!
+//! let version = Arc::new(AtomicUsize::new(1));
+//! let reloadable = Reloadable::new(service(1));
+//! let trigger = ReloadTrigger::new();
+//! reloadable.reload_on(shutdown.guard(), &trigger, move || {
+//!     let version = version.fetch_add(1, Ordering::SeqCst) + 1;
+//!     async move { Ok::<_, Infallible>(service(version)) }
+//! });
+//!
+//! assert_eq!(reloadable.serve(Context::default(), ()).await.unwrap(), 1);
+//!
+//! let reloaded = reloadable.reloaded();
+//! trigger.trigger();
+//! reloaded.await;
+//! assert_eq!(reloadable.serve(Context::default(), ()).await.unwrap(), 2);
+//! # }
+//! ```
+//!
+//! [`Shutdown`]: crate::graceful::Shutdown
+
+use crate::error::BoxError;
+use crate::graceful::ShutdownGuard;
+use crate::{Context, Service};
+use std::{fmt, future::Future, path::PathBuf, sync::Arc, time::Duration};
+use tokio::{sync::watch, time::MissedTickBehavior};
+
+/// A shared handle to a value which can be atomically swapped at runtime.
+///
+/// Clones of a [`Reloadable`] share the same value.
+///
+/// See the [module docs](self) for more information.
+pub struct Reloadable<T> {
+    value: Arc<watch::Sender<Arc<T>>>,
+}
+
+impl<T: fmt::Debug> fmt::Debug for Reloadable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reloadable")
+            .field("value", &self.load())
+            .finish()
+    }
+}
+
+impl<T> Clone for Reloadable<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<T> Reloadable<T> {
+    /// Create a new [`Reloadable`] with the given initial value.
+    pub fn new(value: T) -> Self {
+        let (tx, _) = watch::channel(Arc::new(value));
+        Self {
+            value: Arc::new(tx),
+        }
+    }
+
+    /// Returns the current value.
+    ///
+    /// The returned value is not affected by later reloads.
+    pub fn load(&self) -> Arc<T> {
+        self.value.borrow().clone()
+    }
+
+    /// Replace the current value, returning the previous value.
+    pub fn store(&self, value: T) -> Arc<T> {
+        self.value.send_replace(Arc::new(value))
+    }
+}
+
+impl<T: Send + Sync + 'static> Reloadable<T> {
+    /// Returns a future which resolves once the value is replaced.
+    ///
+    /// Only replacements made after this method is called are observed.
+    pub fn reloaded(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut rx = self.value.subscribe();
+        async move {
+            let _ = rx.changed().await;
+        }
+    }
+
+    /// Spawn a task which replaces the value with the output of the given loader,
+    /// every time a reload is triggered by the given [`ReloadTrigger`].
+    ///
+    /// Errors returned by the loader are logged, keeping the current value in place.
+    /// The task stops once shutdown is initiated, or all handles to the trigger are dropped.
+    pub fn reload_on<F, Fut, E>(&self, guard: ShutdownGuard, trigger: &ReloadTrigger, loader: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+        E: Into<BoxError>,
+    {
+        let reloadable = self.clone();
+        let mut rx = trigger.subscribe();
+        guard.into_spawn_task_fn(move |guard| async move {
+            loop {
+                tokio::select! {
+                    _ = guard.cancelled() => return,
+                    result = rx.changed() => if result.is_err() {
+                        tracing::trace!("reload trigger dropped: stop reload task");
+                        return;
+                    },
+                }
+                match loader().await {
+                    Ok(value) => {
+                        tracing::debug!("reloaded value");
+                        reloadable.store(value);
+                    }
+                    Err(err) => {
+                        let err = err.into();
+                        tracing::error!(error = %err, "failed to reload value: keep current value");
+                    }
+                }
+            }
+        });
+    }
+}
+
+impl<T, State, Request> Service<State, Request> for Reloadable<T>
+where
+    T: Service<State, Request>,
+    State: Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Response = T::Response;
+    type Error = T::Error;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        // hold on to the current service, such that a reload
+        // does not affect this request
+        let service = self.load();
+        service.serve(ctx, req).await
+    }
+}
+
+/// A handle used to trigger the reload of [`Reloadable`] values.
+///
+/// Clones of a [`ReloadTrigger`] trigger the same reloads.
+#[derive(Debug, Clone)]
+pub struct ReloadTrigger {
+    tx: Arc<watch::Sender<()>>,
+}
+
+impl Default for ReloadTrigger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReloadTrigger {
+    /// Create a new [`ReloadTrigger`].
+    pub fn new() -> Self {
+        let (tx, _) = watch::channel(());
+        Self { tx: Arc::new(tx) }
+    }
+
+    /// Trigger a reload of all [`Reloadable`] values which reload on this trigger.
+    ///
+    /// Triggers made while a reload is in progress are coalesced into a single reload.
+    pub fn trigger(&self) {
+        self.tx.send_replace(());
+    }
+
+    fn subscribe(&self) -> watch::Receiver<()> {
+        self.tx.subscribe()
+    }
+
+    /// Spawn a task which triggers a reload every time the process
+    /// receives a `SIGHUP` signal, until shutdown is initiated.
+    #[cfg(unix)]
+    pub fn trigger_on_sighup(&self, guard: ShutdownGuard) -> std::io::Result<()> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sighup = signal(SignalKind::hangup())?;
+        let trigger = self.clone();
+        guard.into_spawn_task_fn(move |guard| async move {
+            loop {
+                tokio::select! {
+                    _ = guard.cancelled() => return,
+                    received = sighup.recv() => match received {
+                        Some(()) => {
+                            tracing::info!("SIGHUP received: trigger reload");
+                            trigger.trigger();
+                        }
+                        None => return,
+                    },
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Spawn a task which triggers a reload every time the file
+    /// at the given path is modified, until shutdown is initiated.
+    ///
+    /// The file is polled at the given interval, comparing its modification time and size,
+    /// such that changes are noticed on any platform and file system,
+    /// within one interval. The removal or (re)creation of the file triggers a reload as well.
+    pub fn trigger_on_file_change(
+        &self,
+        guard: ShutdownGuard,
+        path: impl Into<PathBuf>,
+        interval: Duration,
+    ) {
+        let path = path.into();
+        let mut last_version = std::fs::metadata(&path).ok().map(file_version);
+        let trigger = self.clone();
+        guard.into_spawn_task_fn(move |guard| async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = guard.cancelled() => return,
+                    _ = ticker.tick() => (),
+                }
+                let version = tokio::fs::metadata(&path).await.ok().map(file_version);
+                if version != last_version {
+                    tracing::info!(path = %path.display(), "file changed: trigger reload");
+                    last_version = version;
+                    trigger.trigger();
+                }
+            }
+        });
+    }
+}
+
+/// The modification time and size of a file, used to detect that it changed.
+fn file_version(metadata: std::fs::Metadata) -> (Option<std::time::SystemTime>, u64) {
+    (metadata.modified().ok(), metadata.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graceful::Shutdown;
+    use crate::service::service_fn;
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn service(
+        version: usize,
+        delay: Duration,
+    ) -> impl Service<(), (), Response = usize, Error = Infallible> {
+        service_fn(move || async move {
+            tokio::time::sleep(delay).await;
+            Ok(version)
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reloadable_in_flight() {
+        let reloadable = Reloadable::new(service(1, Duration::from_secs(1)));
+
+        let in_flight = reloadable.serve(Context::default(), ());
+        let reload = async {
+            tokio::task::yield_now().await;
+            let previous = reloadable.store(service(2, Duration::ZERO));
+            // the in-flight request still holds the previous service
+            assert_eq!(Arc::strong_count(&previous), 2);
+        };
+        let (result, ()) = tokio::join!(in_flight, reload);
+        assert_eq!(result.unwrap(), 1);
+
+        assert_eq!(reloadable.serve(Context::default(), ()).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_reload_on_trigger() {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let shutdown = Shutdown::new(async move {
+            rx.await.unwrap();
+        });
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let reloadable = Reloadable::new(0usize);
+        let trigger = ReloadTrigger::new();
+        reloadable.reload_on(shutdown.guard(), &trigger, {
+            let calls = calls.clone();
+            move || {
+                let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                async move {
+                    if call == 2 {
+                        Err(BoxError::from("invalid config"))
+                    } else {
+                        Ok(call)
+                    }
+                }
+            }
+        });
+        assert_eq!(*reloadable.load(), 0);
+
+        let reloaded = reloadable.reloaded();
+        trigger.trigger();
+        reloaded.await;
+        assert_eq!(*reloadable.load(), 1);
+
+        // failed reloads keep the current value
+        trigger.trigger();
+        while calls.load(Ordering::SeqCst) < 2 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(*reloadable.load(), 1);
+
+        let reloaded = reloadable.reloaded();
+        trigger.trigger();
+        reloaded.await;
+        assert_eq!(*reloadable.load(), 3);
+
+        // the reload task stops on shutdown
+        tx.send(()).unwrap();
+        shutdown
+            .shutdown_with_limit(Duration::from_secs(1))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_trigger_on_file_change() {
+        let path = std::env::temp_dir().join(format!(
+            "rama-core-reload-{}-trigger-on-file-change",
+            std::process::id()
+        ));
+        std::fs::write(&path, "v1").unwrap();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let shutdown = Shutdown::new(async move {
+            rx.await.unwrap();
+        });
+        let trigger = ReloadTrigger::new();
+        let mut triggered = trigger.subscribe();
+        trigger.trigger_on_file_change(shutdown.guard(), &path, Duration::from_millis(10));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!triggered.has_changed().unwrap());
+
+        std::fs::write(&path, "version 2").unwrap();
+        tokio::time::timeout(Duration::from_secs(5), triggered.changed())
+            .await
+            .unwrap()
+            .unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), triggered.changed())
+            .await
+            .unwrap()
+            .unwrap();
+
+        tx.send(()).unwrap();
+        shutdown
+            .shutdown_with_limit(Duration::from_secs(1))
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,409 @@
+//! Spread requests over a set of equivalent backend services.
+//!
+//! The [`Balance`] service selects for each request a single backend
+//! from a [`BackendSet`], using a [`Strategy`]:
+//!
+//! - [`RoundRobin`]: select the backends in turn;
+//! - [`Random`]: select a random backend;
+//! - [`PowerOfTwoChoices`]: select the least loaded of two random backends,
+//!   based on the amount of requests in flight per backend;
+//! - [`ConsistentHash`]: select a backend based on a key derived from the request,
+//!   such that requests with the same key go to the same backend for as long as it is available.
+//!
+//! The [`BackendSet`] can be updated at runtime, e.g. when backends come and go,
+//! affecting all [`Balance`] services which share it.
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::service::{balance::{Balance, BackendSet, RoundRobin}, service_fn};
+//! use rama_core::{Context, Service};
+//! use std::convert::Infallible;
+//!
+//! fn backend(id: &'static str) -> impl Service<(), (), Response = &'static str, Error = Infallible> {
+//!     service_fn(move || async move { Ok(id) })
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let backends = BackendSet::new();
+//! backends.insert("a", backend("a"));
+//! backends.insert("b", backend("b"));
+//!
+//! let service = Balance::new(backends.clone(), RoundRobin::new());
+//! assert_eq!(service.serve(Context::default(), ()).await.unwrap(), "a");
+//! assert_eq!(service.serve(Context::default(), ()).await.unwrap(), "b");
+//!
+//! // backends can be updated at runtime
+//! backends.remove("a");
+//! assert_eq!(service.serve(Context::default(), ()).await.unwrap(), "b");
+//! # }
+//! ```
+
+use crate::error::BoxError;
+use crate::{Context, Service};
+use parking_lot::RwLock;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+mod strategy;
+#[doc(inline)]
+pub use strategy::{ConsistentHash, PowerOfTwoChoices, Random, RoundRobin, Strategy};
+
+rama_utils::macros::error::static_str_error! {
+    #[doc = "no backend available to serve the request"]
+    pub struct NoBackendAvailable;
+}
+
+/// A backend service within a [`BackendSet`].
+pub struct Backend<S> {
+    id: Arc<str>,
+    service: Arc<S>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl<S> Backend<S> {
+    /// The unique identifier of this backend.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The backend service.
+    pub fn service(&self) -> &S {
+        &self.service
+    }
+
+    /// The amount of requests currently in flight for this backend.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for Backend<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Backend")
+            .field("id", &self.id)
+            .field("service", &self.service)
+            .field("in_flight", &self.in_flight)
+            .finish()
+    }
+}
+
+impl<S> Clone for Backend<S> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id.clone(),
+            service: self.service.clone(),
+            in_flight: self.in_flight.clone(),
+        }
+    }
+}
+
+/// A set of backend services, each identified by a unique id,
+/// which can be updated at runtime.
+///
+/// Clones of a [`BackendSet`] share the same backends.
+pub struct BackendSet<S> {
+    backends: Arc<RwLock<Arc<[Backend<S>]>>>,
+}
+
+impl<S> fmt::Debug for BackendSet<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BackendSet")
+            .field(
+                "backends",
+                &self.snapshot().iter().map(Backend::id).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl<S> Clone for BackendSet<S> {
+    fn clone(&self) -> Self {
+        Self {
+            backends: self.backends.clone(),
+        }
+    }
+}
+
+impl<S> Default for BackendSet<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> BackendSet<S> {
+    /// Create a new empty [`BackendSet`].
+    pub fn new() -> Self {
+        Self {
+            backends: Arc::new(RwLock::new(Arc::from(Vec::new()))),
+        }
+    }
+
+    /// Returns the current backends, in order of insertion.
+    pub fn snapshot(&self) -> Arc<[Backend<S>]> {
+        self.backends.read().clone()
+    }
+
+    /// Returns the amount of backends.
+    pub fn len(&self) -> usize {
+        self.backends.read().len()
+    }
+
+    /// Returns `true` in case there are no backends.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Insert a backend, replacing the backend with the same id if it exists.
+    pub fn insert(&self, id: impl AsRef<str>, service: S) {
+        let id = id.as_ref();
+        let mut backends = self.backends.write();
+        let mut updated: Vec<_> = backends.iter().cloned().collect();
+        let backend = Backend {
+            id: Arc::from(id),
+            service: Arc::new(service),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+        };
+        match updated.iter_mut().find(|backend| backend.id() == id) {
+            Some(existing) => {
+                existing.service = backend.service;
+            }
+            None => updated.push(backend),
+        }
+        *backends = updated.into();
+    }
+
+    /// Remove the backend with the given id, returning `true` in case it existed.
+    ///
+    /// Requests in flight for the removed backend are not affected.
+    pub fn remove(&self, id: impl AsRef<str>) -> bool {
+        let id = id.as_ref();
+        let mut backends = self.backends.write();
+        if !backends.iter().any(|backend| backend.id() == id) {
+            return false;
+        }
+        *backends = backends
+            .iter()
+            .filter(|backend| backend.id() != id)
+            .cloned()
+            .collect();
+        true
+    }
+
+    /// Replace all backends with the given ones.
+    ///
+    /// The in flight counters of backends with an id that remains in the set are kept.
+    pub fn update<I: AsRef<str>>(&self, backends: impl IntoIterator<Item = (I, S)>) {
+        let mut current = self.backends.write();
+        let mut updated: Vec<Backend<S>> = Vec::new();
+        for (id, service) in backends {
+            let id = id.as_ref();
+            let in_flight = current
+                .iter()
+                .find(|backend| backend.id() == id)
+                .map(|backend| backend.in_flight.clone())
+                .unwrap_or_default();
+            let backend = Backend {
+                id: Arc::from(id),
+                service: Arc::new(service),
+                in_flight,
+            };
+            match updated.iter_mut().find(|existing| existing.id() == id) {
+                Some(existing) => *existing = backend,
+                None => updated.push(backend),
+            }
+        }
+        *current = updated.into();
+    }
+}
+
+impl<I: AsRef<str>, S> FromIterator<(I, S)> for BackendSet<S> {
+    fn from_iter<T: IntoIterator<Item = (I, S)>>(iter: T) -> Self {
+        let set = Self::new();
+        set.update(iter);
+        set
+    }
+}
+
+/// A [`Service`] which spreads requests over the backends of a [`BackendSet`],
+/// using a [`Strategy`] to select the backend for each request.
+///
+/// Returns a [`NoBackendAvailable`] error in case the set is empty.
+///
+/// See the [module docs](self) for more information.
+pub struct Balance<S, P> {
+    backends: BackendSet<S>,
+    strategy: P,
+}
+
+impl<S, P> Balance<S, P> {
+    /// Create a new [`Balance`] service for the given backends and strategy.
+    pub const fn new(backends: BackendSet<S>, strategy: P) -> Self {
+        Self { backends, strategy }
+    }
+
+    /// Returns the [`BackendSet`] of this [`Balance`] service,
+    /// which can be used to update the backends.
+    pub fn backends(&self) -> &BackendSet<S> {
+        &self.backends
+    }
+}
+
+impl<S, P: fmt::Debug> fmt::Debug for Balance<S, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Balance")
+            .field("backends", &self.backends)
+            .field("strategy", &self.strategy)
+            .finish()
+    }
+}
+
+impl<S, P: Clone> Clone for Balance<S, P> {
+    fn clone(&self) -> Self {
+        Self {
+            backends: self.backends.clone(),
+            strategy: self.strategy.clone(),
+        }
+    }
+}
+
+impl<S, P, State, Request> Service<State, Request> for Balance<S, P>
+where
+    S: Service<State, Request, Error: Into<BoxError>>,
+    P: Strategy<State, Request>,
+    State: Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        request: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let backends = self.backends.snapshot();
+        let backend = self
+            .strategy
+            .select(&ctx, &request, &backends)
+            .and_then(|index| backends.get(index))
+            .ok_or(NoBackendAvailable)?;
+
+        let _guard = InFlightGuard::new(backend.in_flight.clone());
+        backend
+            .service
+            .serve(ctx, request)
+            .await
+            .map_err(Into::into)
+    }
+}
+
+struct InFlightGuard(Arc<AtomicUsize>);
+
+impl InFlightGuard {
+    fn new(in_flight: Arc<AtomicUsize>) -> Self {
+        in_flight.fetch_add(1, Ordering::AcqRel);
+        Self(in_flight)
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::service_fn;
+    use std::convert::Infallible;
+
+    fn backend(
+        id: &'static str,
+    ) -> impl Service<(), (), Response = &'static str, Error = Infallible> {
+        service_fn(move || async move { Ok(id) })
+    }
+
+    #[tokio::test]
+    async fn test_balance_empty() {
+        let service = Balance::new(BackendSet::new(), RoundRobin::new());
+        let err = service.serve(Context::default(), ()).await.unwrap_err();
+        assert!(err.is::<NoBackendAvailable>());
+
+        service.backends().insert("a", backend("a"));
+        assert_eq!(service.serve(Context::default(), ()).await.unwrap(), "a");
+    }
+
+    #[tokio::test]
+    async fn test_balance_backend_set_update() {
+        let backends: BackendSet<_> = [("a", backend("a")), ("b", backend("b"))]
+            .into_iter()
+            .collect();
+        let service = Balance::new(backends.clone(), RoundRobin::new());
+        assert_eq!(backends.len(), 2);
+
+        backends.insert("a", backend("a2"));
+        assert_eq!(backends.len(), 2);
+        assert_eq!(service.serve(Context::default(), ()).await.unwrap(), "a2");
+        assert_eq!(service.serve(Context::default(), ()).await.unwrap(), "b");
+
+        assert!(backends.remove("a"));
+        assert!(!backends.remove("a"));
+        assert_eq!(service.serve(Context::default(), ()).await.unwrap(), "b");
+
+        backends.update([("c", backend("c"))]);
+        assert_eq!(
+            backends
+                .snapshot()
+                .iter()
+                .map(Backend::id)
+                .collect::<Vec<_>>(),
+            vec!["c"]
+        );
+        assert_eq!(service.serve(Context::default(), ()).await.unwrap(), "c");
+    }
+
+    fn waiting(
+        rx: Option<tokio::sync::oneshot::Receiver<()>>,
+    ) -> impl Service<(), (), Response = (), Error = Infallible> {
+        let rx = parking_lot::Mutex::new(rx);
+        service_fn(move || {
+            let rx = rx.lock().take();
+            async move {
+                if let Some(rx) = rx {
+                    let _ = rx.await;
+                }
+                Ok(())
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_balance_in_flight() {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let backends = BackendSet::new();
+        backends.insert("a", waiting(Some(rx)));
+        let service = Balance::new(backends.clone(), RoundRobin::new());
+
+        let in_flight = || backends.snapshot()[0].in_flight();
+        let request = service.serve(Context::default(), ());
+        let check = async {
+            tokio::task::yield_now().await;
+            assert_eq!(in_flight(), 1);
+            // in flight counters are kept for updated backends
+            backends.update([("a", waiting(None))]);
+            assert_eq!(in_flight(), 1);
+            tx.send(()).unwrap();
+        };
+        let (result, ()) = tokio::join!(request, check);
+        result.unwrap();
+        assert_eq!(in_flight(), 0);
+    }
+}
@@ -0,0 +1,342 @@
+use super::Backend;
+use crate::layer::limit::policy::KeyExtractor;
+use crate::Context;
+use parking_lot::Mutex;
+use rama_utils::rng::{HasherRng, Rng};
+use std::{
+    collections::hash_map::DefaultHasher,
+    fmt,
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// A strategy used by the [`Balance`] service to select a backend for a request.
+///
+/// [`Balance`]: super::Balance
+pub trait Strategy<State, Request>: Send + Sync + 'static {
+    /// Select the index of the backend to serve the given request,
+    /// returning `None` in case no backend can be selected.
+    fn select<S>(
+        &self,
+        ctx: &Context<State>,
+        request: &Request,
+        backends: &[Backend<S>],
+    ) -> Option<usize>;
+}
+
+/// A [`Strategy`] which selects the backends in turn.
+///
+/// Clones share the same position.
+#[derive(Debug, Clone, Default)]
+pub struct RoundRobin {
+    next: Arc<AtomicUsize>,
+}
+
+impl RoundRobin {
+    /// Create a new [`RoundRobin`] strategy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn next_index(&self, len: usize) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+        Some(self.next.fetch_add(1, Ordering::Relaxed) % len)
+    }
+}
+
+impl<State, Request> Strategy<State, Request> for RoundRobin {
+    fn select<S>(
+        &self,
+        _ctx: &Context<State>,
+        _request: &Request,
+        backends: &[Backend<S>],
+    ) -> Option<usize> {
+        self.next_index(backends.len())
+    }
+}
+
+/// A [`Strategy`] which selects a random backend.
+#[derive(Debug, Clone)]
+pub struct Random<R = HasherRng> {
+    rng: Arc<Mutex<R>>,
+}
+
+impl Random {
+    /// Create a new [`Random`] strategy.
+    pub fn new() -> Self {
+        Self::with_rng(HasherRng::default())
+    }
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R> Random<R> {
+    /// Create a new [`Random`] strategy using the given [`Rng`].
+    pub fn with_rng(rng: R) -> Self {
+        Self {
+            rng: Arc::new(Mutex::new(rng)),
+        }
+    }
+}
+
+impl<R: Rng> Random<R> {
+    fn next_index(&self, len: usize) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+        Some(self.rng.lock().next_range(0..len as u64) as usize)
+    }
+}
+
+impl<R: Rng, State, Request> Strategy<State, Request> for Random<R> {
+    fn select<S>(
+        &self,
+        _ctx: &Context<State>,
+        _request: &Request,
+        backends: &[Backend<S>],
+    ) -> Option<usize> {
+        self.next_index(backends.len())
+    }
+}
+
+/// A [`Strategy`] which picks two random backends,
+/// and selects the one with the least requests in flight.
+///
+/// Also known as "the power of two choices", this spreads load nearly as well
+/// as always picking the least loaded backend, without having to inspect all of them.
+#[derive(Debug, Clone)]
+pub struct PowerOfTwoChoices<R = HasherRng> {
+    random: Random<R>,
+}
+
+impl PowerOfTwoChoices {
+    /// Create a new [`PowerOfTwoChoices`] strategy.
+    pub fn new() -> Self {
+        Self {
+            random: Random::new(),
+        }
+    }
+}
+
+impl Default for PowerOfTwoChoices {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R> PowerOfTwoChoices<R> {
+    /// Create a new [`PowerOfTwoChoices`] strategy using the given [`Rng`].
+    pub fn with_rng(rng: R) -> Self {
+        Self {
+            random: Random::with_rng(rng),
+        }
+    }
+}
+
+impl<R: Rng, State, Request> Strategy<State, Request> for PowerOfTwoChoices<R> {
+    fn select<S>(
+        &self,
+        _ctx: &Context<State>,
+        _request: &Request,
+        backends: &[Backend<S>],
+    ) -> Option<usize> {
+        let len = backends.len();
+        if len < 2 {
+            return self.random.next_index(len);
+        }
+
+        let (a, b) = {
+            let mut rng = self.random.rng.lock();
+            let a = rng.next_range(0..len as u64) as usize;
+            // pick a distinct second backend
+            let b = (a + 1 + rng.next_range(0..(len - 1) as u64) as usize) % len;
+            (a, b)
+        };
+
+        if backends[b].in_flight() < backends[a].in_flight() {
+            Some(b)
+        } else {
+            Some(a)
+        }
+    }
+}
+
+/// A [`Strategy`] which selects a backend based on a key extracted from the request,
+/// such that requests with the same key are served by the same backend.
+///
+/// Backends are selected using rendezvous hashing on the key and the backend id,
+/// such that adding or removing a backend only moves the keys of that backend.
+/// Requests without a key are spread using [`RoundRobin`].
+///
+/// The key can for example be taken from the [`Context`] using an [`ExtensionKey`].
+///
+/// [`ExtensionKey`]: crate::layer::limit::policy::ExtensionKey
+pub struct ConsistentHash<E> {
+    extractor: E,
+    fallback: RoundRobin,
+}
+
+impl<E> ConsistentHash<E> {
+    /// Create a new [`ConsistentHash`] strategy using the given [`KeyExtractor`].
+    pub fn new(extractor: E) -> Self {
+        Self {
+            extractor,
+            fallback: RoundRobin::new(),
+        }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Debug for ConsistentHash<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConsistentHash")
+            .field("extractor", &self.extractor)
+            .field("fallback", &self.fallback)
+            .finish()
+    }
+}
+
+impl<E: Clone> Clone for ConsistentHash<E> {
+    fn clone(&self) -> Self {
+        Self {
+            extractor: self.extractor.clone(),
+            fallback: self.fallback.clone(),
+        }
+    }
+}
+
+impl<E, State, Request> Strategy<State, Request> for ConsistentHash<E>
+where
+    E: KeyExtractor<State, Request>,
+{
+    fn select<S>(
+        &self,
+        ctx: &Context<State>,
+        request: &Request,
+        backends: &[Backend<S>],
+    ) -> Option<usize> {
+        let Some(key) = self.extractor.extract(ctx, request) else {
+            return self.fallback.next_index(backends.len());
+        };
+
+        backends
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, backend)| {
+                let mut hasher = DefaultHasher::new();
+                key.hash(&mut hasher);
+                backend.id().hash(&mut hasher);
+                hasher.finish()
+            })
+            .map(|(index, _)| index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::limit::policy::ExtensionKey;
+    use crate::service::balance::BackendSet;
+
+    fn backends(ids: &[&str]) -> BackendSet<()> {
+        ids.iter().map(|id| (*id, ())).collect()
+    }
+
+    fn select<P: Strategy<(), ()>>(
+        strategy: &P,
+        ctx: &Context<()>,
+        backends: &BackendSet<()>,
+    ) -> Option<String> {
+        let backends = backends.snapshot();
+        strategy
+            .select(ctx, &(), &backends)
+            .map(|index| backends[index].id().to_owned())
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let set = backends(&["a", "b", "c"]);
+        let strategy = RoundRobin::new();
+        let ctx = Context::default();
+        let selected: Vec<_> = (0..6)
+            .map(|_| select(&strategy, &ctx, &set).unwrap())
+            .collect();
+        assert_eq!(selected, vec!["a", "b", "c", "a", "b", "c"]);
+
+        assert_eq!(select(&strategy, &ctx, &backends(&[])), None);
+    }
+
+    #[test]
+    fn test_random() {
+        let set = backends(&["a", "b", "c"]);
+        let strategy = Random::new();
+        let ctx = Context::default();
+        for _ in 0..100 {
+            let index = strategy.select(&ctx, &(), &set.snapshot()).unwrap();
+            assert!(index < 3);
+        }
+        assert_eq!(select(&strategy, &ctx, &backends(&[])), None);
+    }
+
+    #[test]
+    fn test_power_of_two_choices() {
+        let set = backends(&["a", "b"]);
+        set.snapshot()[0].in_flight.store(10, Ordering::Release);
+
+        let strategy = PowerOfTwoChoices::new();
+        let ctx = Context::default();
+        for _ in 0..100 {
+            assert_eq!(select(&strategy, &ctx, &set).unwrap(), "b");
+        }
+
+        let set = backends(&["a"]);
+        assert_eq!(select(&strategy, &ctx, &set).unwrap(), "a");
+    }
+
+    #[test]
+    fn test_consistent_hash() {
+        let set = backends(&["a", "b", "c", "d"]);
+        let strategy = ConsistentHash::new(ExtensionKey::<u64>::new());
+
+        let selected: Vec<_> = (0..100u64)
+            .map(|key| {
+                let mut ctx = Context::default();
+                ctx.insert(key);
+                select(&strategy, &ctx, &set).unwrap()
+            })
+            .collect();
+
+        // same key, same backend
+        for (key, expected) in selected.iter().enumerate() {
+            let mut ctx = Context::default();
+            ctx.insert(key as u64);
+            assert_eq!(&select(&strategy, &ctx, &set).unwrap(), expected);
+        }
+
+        // removing a backend only moves the keys of that backend
+        set.remove("b");
+        for (key, previous) in selected.iter().enumerate() {
+            let mut ctx = Context::default();
+            ctx.insert(key as u64);
+            let current = select(&strategy, &ctx, &set).unwrap();
+            if previous != "b" {
+                assert_eq!(&current, previous);
+            } else {
+                assert_ne!(current, "b");
+            }
+        }
+
+        // no key falls back to round robin
+        let ctx = Context::default();
+        assert_eq!(select(&strategy, &ctx, &set).unwrap(), "a");
+        assert_eq!(select(&strategy, &ctx, &set).unwrap(), "c");
+    }
+}
@@ -8,3 +8,5 @@ pub use svc::{BoxService, Service};
 
 pub mod handler;
 pub use handler::service_fn;
+
+pub mod balance;
@@ -0,0 +1,691 @@
+//! A caching [`DnsResolver`], honoring the time-to-live of records.
+//!
+//! See [`CachingDns`] for more information.
+
+use crate::{DnsLookup, DnsResolver, SrvName, SrvRecord, SvcbRecord, TxtRecord};
+use parking_lot::Mutex;
+use rama_core::error::BoxError;
+use rama_net::address::Domain;
+use rama_utils::macros::error::static_str_error;
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    net::{Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{sync::watch, time::Instant};
+
+static_str_error! {
+    #[doc = "no dns records found for domain"]
+    pub struct NoDnsRecordsErr;
+}
+
+/// A [`DnsResolver`] which caches the lookups of the inner [`DnsResolver`].
+///
+/// - Lookups are cached for the time-to-live of the records, clamped between
+///   a minimum and maximum ttl. Lookups without a ttl are cached for a default ttl.
+/// - Negative answers (the domain has no such records) are cached as well,
+///   for the negative ttl of the answer, or a default negative ttl if unknown.
+///   Errors, such as timeouts, are not cached.
+/// - Concurrent lookups of the same [`Domain`] are de-duplicated into a single lookup.
+/// - Optionally, expired lookups are still served for a limited time,
+///   while the lookup is refreshed in the background (stale-while-revalidate).
+///
+/// Only 'A' and 'AAAA' lookups are cached, other lookups are forwarded as-is.
+///
+/// Clones of a [`CachingDns`] share the same cache.
+///
+/// # Example
+///
+/// ```
+/// use rama_dns::{CachingDns, DnsResolver, InMemoryDns};
+/// use rama_net::address::Domain;
+/// use std::time::Duration;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let mut dns = InMemoryDns::new().with_ttl(Duration::from_secs(60));
+/// dns.insert(Domain::from_static("example.com"), vec![[127, 0, 0, 1].into()]);
+///
+/// let dns = CachingDns::new(dns).with_stale_while_revalidate(Duration::from_secs(30));
+/// let ips = dns.ipv4_lookup(Domain::from_static("example.com")).await.unwrap();
+/// assert_eq!(ips, vec![std::net::Ipv4Addr::new(127, 0, 0, 1)]);
+/// # }
+/// ```
+pub struct CachingDns<R> {
+    config: CacheConfig,
+    state: Arc<CacheState<R>>,
+}
+
+struct CacheState<R> {
+    resolver: R,
+    ipv4: LookupCache<Ipv4Addr>,
+    ipv6: LookupCache<Ipv6Addr>,
+}
+
+#[derive(Debug, Clone)]
+struct CacheConfig {
+    min_ttl: Duration,
+    max_ttl: Duration,
+    default_ttl: Duration,
+    negative_ttl: Duration,
+    stale_ttl: Duration,
+    max_entries: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            min_ttl: Duration::from_secs(1),
+            max_ttl: Duration::from_secs(24 * 60 * 60),
+            default_ttl: Duration::from_secs(60),
+            negative_ttl: Duration::from_secs(30),
+            stale_ttl: Duration::ZERO,
+            max_entries: 10_000,
+        }
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for CachingDns<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachingDns")
+            .field("resolver", &self.state.resolver)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl<R> Clone for CachingDns<R> {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<R> CachingDns<R> {
+    /// Create a new [`CachingDns`], caching the lookups of the given [`DnsResolver`].
+    ///
+    /// By default ttls are clamped between 1 second and 1 day, lookups without a ttl
+    /// are cached for 60 seconds, negative answers without a ttl for 30 seconds,
+    /// no stale lookups are served, and at most 10,000 lookups are cached per record type.
+    pub fn new(resolver: R) -> Self {
+        Self {
+            config: CacheConfig::default(),
+            state: Arc::new(CacheState {
+                resolver,
+                ipv4: LookupCache::default(),
+                ipv6: LookupCache::default(),
+            }),
+        }
+    }
+
+    /// Set the minimum duration a lookup is cached, regardless of its ttl.
+    pub fn with_min_ttl(mut self, ttl: Duration) -> Self {
+        self.config.min_ttl = ttl;
+        self
+    }
+
+    /// Set the minimum duration a lookup is cached, regardless of its ttl.
+    pub fn set_min_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.config.min_ttl = ttl;
+        self
+    }
+
+    /// Set the maximum duration a lookup is cached, regardless of its ttl.
+    pub fn with_max_ttl(mut self, ttl: Duration) -> Self {
+        self.config.max_ttl = ttl;
+        self
+    }
+
+    /// Set the maximum duration a lookup is cached, regardless of its ttl.
+    pub fn set_max_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.config.max_ttl = ttl;
+        self
+    }
+
+    /// Set the duration a lookup is cached, in case the resolver did not report a ttl.
+    pub fn with_default_ttl(mut self, ttl: Duration) -> Self {
+        self.config.default_ttl = ttl;
+        self
+    }
+
+    /// Set the duration a lookup is cached, in case the resolver did not report a ttl.
+    pub fn set_default_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.config.default_ttl = ttl;
+        self
+    }
+
+    /// Set the duration a negative answer is cached, in case the resolver did not report a ttl.
+    pub fn with_negative_ttl(mut self, ttl: Duration) -> Self {
+        self.config.negative_ttl = ttl;
+        self
+    }
+
+    /// Set the duration a negative answer is cached, in case the resolver did not report a ttl.
+    pub fn set_negative_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.config.negative_ttl = ttl;
+        self
+    }
+
+    /// Serve expired lookups for at most the given duration past their expiry,
+    /// while the lookup is refreshed in the background.
+    pub fn with_stale_while_revalidate(mut self, stale_ttl: Duration) -> Self {
+        self.config.stale_ttl = stale_ttl;
+        self
+    }
+
+    /// Serve expired lookups for at most the given duration past their expiry,
+    /// while the lookup is refreshed in the background.
+    pub fn set_stale_while_revalidate(&mut self, stale_ttl: Duration) -> &mut Self {
+        self.config.stale_ttl = stale_ttl;
+        self
+    }
+
+    /// Set the maximum amount of lookups cached per record type.
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.config.max_entries = max_entries;
+        self
+    }
+
+    /// Set the maximum amount of lookups cached per record type.
+    pub fn set_max_entries(&mut self, max_entries: usize) -> &mut Self {
+        self.config.max_entries = max_entries;
+        self
+    }
+
+    /// Returns a reference to the inner [`DnsResolver`].
+    pub fn resolver(&self) -> &R {
+        &self.state.resolver
+    }
+
+    /// Remove all cached lookups.
+    pub fn clear(&self) {
+        self.state.ipv4.entries.lock().clear();
+        self.state.ipv6.entries.lock().clear();
+    }
+}
+
+impl<R> DnsResolver for CachingDns<R>
+where
+    R: DnsResolver<Error: Into<BoxError>>,
+{
+    type Error = BoxError;
+
+    async fn ipv4_lookup(&self, domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+        into_addresses(self.ipv4_lookup_with_ttl(domain).await?)
+    }
+
+    async fn ipv6_lookup(&self, domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+        into_addresses(self.ipv6_lookup_with_ttl(domain).await?)
+    }
+
+    async fn ipv4_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv4Addr>, Self::Error> {
+        self.lookup(domain).await
+    }
+
+    async fn ipv6_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv6Addr>, Self::Error> {
+        self.lookup(domain).await
+    }
+
+    async fn srv_lookup(&self, name: SrvName) -> Result<Vec<SrvRecord>, Self::Error> {
+        self.state
+            .resolver
+            .srv_lookup(name)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn txt_lookup(&self, domain: Domain) -> Result<Vec<TxtRecord>, Self::Error> {
+        self.state
+            .resolver
+            .txt_lookup(domain)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn cname_lookup(&self, domain: Domain) -> Result<Vec<Domain>, Self::Error> {
+        self.state
+            .resolver
+            .cname_lookup(domain)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn https_lookup(&self, domain: Domain) -> Result<Vec<SvcbRecord>, Self::Error> {
+        self.state
+            .resolver
+            .https_lookup(domain)
+            .await
+            .map_err(Into::into)
+    }
+}
+
+fn into_addresses<T>(lookup: DnsLookup<T>) -> Result<Vec<T>, BoxError> {
+    if lookup.is_empty() {
+        Err(NoDnsRecordsErr.into())
+    } else {
+        Ok(lookup.into_addresses())
+    }
+}
+
+/// The address types that can be cached, one for each record type.
+trait CachedRecord: Clone + Send + Sync + 'static {
+    fn cache<R>(state: &CacheState<R>) -> &LookupCache<Self>;
+
+    fn resolve<R: DnsResolver>(
+        resolver: &R,
+        domain: Domain,
+    ) -> impl Future<Output = Result<DnsLookup<Self>, R::Error>> + Send + '_;
+}
+
+impl CachedRecord for Ipv4Addr {
+    fn cache<R>(state: &CacheState<R>) -> &LookupCache<Self> {
+        &state.ipv4
+    }
+
+    fn resolve<R: DnsResolver>(
+        resolver: &R,
+        domain: Domain,
+    ) -> impl Future<Output = Result<DnsLookup<Self>, R::Error>> + Send + '_ {
+        resolver.ipv4_lookup_with_ttl(domain)
+    }
+}
+
+impl CachedRecord for Ipv6Addr {
+    fn cache<R>(state: &CacheState<R>) -> &LookupCache<Self> {
+        &state.ipv6
+    }
+
+    fn resolve<R: DnsResolver>(
+        resolver: &R,
+        domain: Domain,
+    ) -> impl Future<Output = Result<DnsLookup<Self>, R::Error>> + Send + '_ {
+        resolver.ipv6_lookup_with_ttl(domain)
+    }
+}
+
+type SharedResult<T> = Result<DnsLookup<T>, SharedLookupError>;
+
+struct LookupCache<T> {
+    entries: Mutex<HashMap<Domain, CacheEntry<T>>>,
+    in_flight: Mutex<HashMap<Domain, watch::Receiver<Option<SharedResult<T>>>>>,
+}
+
+impl<T> Default for LookupCache<T> {
+    fn default() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+struct CacheEntry<T> {
+    lookup: DnsLookup<T>,
+    expires_at: Instant,
+}
+
+impl<R> CachingDns<R>
+where
+    R: DnsResolver<Error: Into<BoxError>>,
+{
+    async fn lookup<T: CachedRecord>(&self, domain: Domain) -> Result<DnsLookup<T>, BoxError> {
+        let now = Instant::now();
+        let cached = {
+            let mut entries = T::cache(&self.state).entries.lock();
+            match entries.get(&domain) {
+                Some(entry) if now < entry.expires_at + self.config.stale_ttl => {
+                    Some((entry.lookup.clone(), entry.expires_at))
+                }
+                Some(_) => {
+                    entries.remove(&domain);
+                    None
+                }
+                None => None,
+            }
+        };
+
+        match cached {
+            Some((lookup, expires_at)) => {
+                if expires_at <= now {
+                    tracing::trace!(%domain, "serve stale dns lookup: refresh in background");
+                    let dns = self.clone();
+                    tokio::spawn(async move {
+                        let _ = dns.resolve::<T>(domain, true).await;
+                    });
+                }
+                Ok(lookup.with_ttl(expires_at.saturating_duration_since(now)))
+            }
+            None => self.resolve::<T>(domain, false).await.map_err(Into::into),
+        }
+    }
+
+    /// Resolve the domain using the inner resolver and cache the result,
+    /// sharing the result with concurrent lookups of the same domain.
+    ///
+    /// A background refresh is skipped in case a lookup is already in flight.
+    async fn resolve<T: CachedRecord>(&self, domain: Domain, refresh: bool) -> SharedResult<T> {
+        let cache = T::cache(&self.state);
+        loop {
+            let tx = {
+                let mut in_flight = cache.in_flight.lock();
+                match in_flight.get(&domain) {
+                    Some(_) if refresh => {
+                        return Err(SharedLookupError(Arc::new(
+                            "dns lookup already in flight".into(),
+                        )));
+                    }
+                    Some(rx) => Err(rx.clone()),
+                    None => {
+                        let (tx, rx) = watch::channel(None);
+                        in_flight.insert(domain.clone(), rx);
+                        Ok(tx)
+                    }
+                }
+            };
+
+            let tx = match tx {
+                Ok(tx) => tx,
+                Err(mut rx) => match rx.wait_for(Option::is_some).await {
+                    Ok(result) => return result.clone().expect("shared dns lookup result"),
+                    // the shared lookup was cancelled: try again
+                    Err(_) => continue,
+                },
+            };
+
+            let _guard = InFlightGuard {
+                in_flight: &cache.in_flight,
+                domain: &domain,
+                tx: &tx,
+            };
+
+            let result = match T::resolve(&self.state.resolver, domain.clone()).await {
+                Ok(lookup) => {
+                    let ttl = self.ttl(&lookup);
+                    self.insert(cache, domain.clone(), lookup.clone(), ttl);
+                    Ok(lookup.with_ttl(ttl))
+                }
+                Err(err) => Err(SharedLookupError(Arc::new(err.into()))),
+            };
+            tx.send_replace(Some(result.clone()));
+            return result;
+        }
+    }
+
+    fn ttl<T>(&self, lookup: &DnsLookup<T>) -> Duration {
+        let ttl = lookup.ttl().unwrap_or(if lookup.is_empty() {
+            self.config.negative_ttl
+        } else {
+            self.config.default_ttl
+        });
+        ttl.clamp(
+            self.config.min_ttl,
+            self.config.max_ttl.max(self.config.min_ttl),
+        )
+    }
+
+    fn insert<T>(
+        &self,
+        cache: &LookupCache<T>,
+        domain: Domain,
+        lookup: DnsLookup<T>,
+        ttl: Duration,
+    ) {
+        let now = Instant::now();
+        let mut entries = cache.entries.lock();
+        if entries.len() >= self.config.max_entries && !entries.contains_key(&domain) {
+            // make room by evicting the lookups which can no longer be served
+            let stale_ttl = self.config.stale_ttl;
+            entries.retain(|_, entry| now < entry.expires_at + stale_ttl);
+            if entries.len() >= self.config.max_entries {
+                tracing::debug!(%domain, "dns cache full: do not cache lookup");
+                return;
+            }
+        }
+        entries.insert(
+            domain,
+            CacheEntry {
+                lookup,
+                expires_at: now + ttl,
+            },
+        );
+    }
+}
+
+/// Removes the in-flight lookup when it completes or is cancelled.
+struct InFlightGuard<'a, T> {
+    in_flight: &'a Mutex<HashMap<Domain, watch::Receiver<Option<SharedResult<T>>>>>,
+    domain: &'a Domain,
+    tx: &'a watch::Sender<Option<SharedResult<T>>>,
+}
+
+impl<T> Drop for InFlightGuard<'_, T> {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock();
+        if in_flight
+            .get(self.domain)
+            .map(|rx| rx.same_channel(&self.tx.subscribe()))
+            .unwrap_or_default()
+        {
+            in_flight.remove(self.domain);
+        }
+    }
+}
+
+/// A lookup error shared with all concurrent lookups of the same domain.
+#[derive(Debug, Clone)]
+struct SharedLookupError(Arc<BoxError>);
+
+impl fmt::Display for SharedLookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for SharedLookupError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InMemoryDns;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Resolver which counts its lookups, and returns the given lookup after a delay.
+    #[derive(Debug, Clone)]
+    struct CountingDns {
+        lookups: Arc<AtomicUsize>,
+        lookup: Arc<Mutex<Result<DnsLookup<Ipv4Addr>, &'static str>>>,
+        delay: Duration,
+    }
+
+    impl CountingDns {
+        fn new(lookup: Result<DnsLookup<Ipv4Addr>, &'static str>) -> Self {
+            Self {
+                lookups: Arc::new(AtomicUsize::new(0)),
+                lookup: Arc::new(Mutex::new(lookup)),
+                delay: Duration::from_millis(10),
+            }
+        }
+
+        fn lookups(&self) -> usize {
+            self.lookups.load(Ordering::SeqCst)
+        }
+
+        fn set(&self, lookup: Result<DnsLookup<Ipv4Addr>, &'static str>) {
+            *self.lookup.lock() = lookup;
+        }
+    }
+
+    impl DnsResolver for CountingDns {
+        type Error = BoxError;
+
+        async fn ipv4_lookup(&self, domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+            self.ipv4_lookup_with_ttl(domain)
+                .await
+                .map(DnsLookup::into_addresses)
+        }
+
+        async fn ipv6_lookup(&self, _domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+            Err(NoDnsRecordsErr.into())
+        }
+
+        async fn ipv4_lookup_with_ttl(
+            &self,
+            _domain: Domain,
+        ) -> Result<DnsLookup<Ipv4Addr>, Self::Error> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            self.lookup.lock().clone().map_err(Into::into)
+        }
+    }
+
+    fn lookup(ip: [u8; 4], ttl: u64) -> Result<DnsLookup<Ipv4Addr>, &'static str> {
+        Ok(DnsLookup::new(vec![ip.into()]).with_ttl(Duration::from_secs(ttl)))
+    }
+
+    fn domain() -> Domain {
+        Domain::from_static("example.com")
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_caching_dns_ttl() {
+        let resolver = CountingDns::new(lookup([127, 0, 0, 1], 10));
+        let dns = CachingDns::new(resolver.clone());
+
+        let ips = dns.ipv4_lookup(domain()).await.unwrap();
+        assert_eq!(ips, vec![Ipv4Addr::new(127, 0, 0, 1)]);
+        assert_eq!(resolver.lookups(), 1);
+
+        resolver.set(lookup([127, 0, 0, 2], 10));
+        tokio::time::advance(Duration::from_secs(5)).await;
+        let cached = dns.ipv4_lookup_with_ttl(domain()).await.unwrap();
+        assert_eq!(cached.addresses(), &[Ipv4Addr::new(127, 0, 0, 1)]);
+        assert!(cached.ttl().unwrap() <= Duration::from_secs(5));
+        assert_eq!(resolver.lookups(), 1);
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        let ips = dns.ipv4_lookup(domain()).await.unwrap();
+        assert_eq!(ips, vec![Ipv4Addr::new(127, 0, 0, 2)]);
+        assert_eq!(resolver.lookups(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_caching_dns_ttl_clamp() {
+        let resolver = CountingDns::new(lookup([127, 0, 0, 1], 0));
+        let dns = CachingDns::new(resolver.clone())
+            .with_min_ttl(Duration::from_secs(5))
+            .with_max_ttl(Duration::from_secs(60));
+
+        dns.ipv4_lookup(domain()).await.unwrap();
+        tokio::time::advance(Duration::from_secs(4)).await;
+        dns.ipv4_lookup(domain()).await.unwrap();
+        assert_eq!(resolver.lookups(), 1);
+
+        dns.clear();
+        resolver.set(lookup([127, 0, 0, 1], 3600));
+        dns.ipv4_lookup(domain()).await.unwrap();
+        tokio::time::advance(Duration::from_secs(61)).await;
+        dns.ipv4_lookup(domain()).await.unwrap();
+        assert_eq!(resolver.lookups(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_caching_dns_negative() {
+        let resolver = CountingDns::new(Ok(DnsLookup::empty()));
+        let dns = CachingDns::new(resolver.clone()).with_negative_ttl(Duration::from_secs(10));
+
+        let err = dns.ipv4_lookup(domain()).await.unwrap_err();
+        assert!(err.is::<NoDnsRecordsErr>());
+        dns.ipv4_lookup(domain()).await.unwrap_err();
+        assert_eq!(resolver.lookups(), 1);
+
+        // errors are not cached
+        tokio::time::advance(Duration::from_secs(10)).await;
+        resolver.set(Err("timeout"));
+        dns.ipv4_lookup(domain()).await.unwrap_err();
+        dns.ipv4_lookup(domain()).await.unwrap_err();
+        assert_eq!(resolver.lookups(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_caching_dns_in_flight_dedup() {
+        let resolver = CountingDns::new(lookup([127, 0, 0, 1], 10));
+        let dns = CachingDns::new(resolver.clone());
+
+        let (a, b, c) = tokio::join!(
+            dns.ipv4_lookup(domain()),
+            dns.ipv4_lookup(domain()),
+            dns.ipv4_lookup(domain()),
+        );
+        assert_eq!(a.unwrap(), b.unwrap());
+        assert!(c.is_ok());
+        assert_eq!(resolver.lookups(), 1);
+
+        // shared errors
+        dns.clear();
+        resolver.set(Err("timeout"));
+        let (a, b) = tokio::join!(dns.ipv4_lookup(domain()), dns.ipv4_lookup(domain()));
+        assert_eq!(a.unwrap_err().to_string(), "timeout");
+        assert_eq!(b.unwrap_err().to_string(), "timeout");
+        assert_eq!(resolver.lookups(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_caching_dns_stale_while_revalidate() {
+        let resolver = CountingDns::new(lookup([127, 0, 0, 1], 10));
+        let dns =
+            CachingDns::new(resolver.clone()).with_stale_while_revalidate(Duration::from_secs(5));
+
+        dns.ipv4_lookup(domain()).await.unwrap();
+        resolver.set(lookup([127, 0, 0, 2], 10));
+
+        // stale lookup is served, while refreshed in the background
+        tokio::time::advance(Duration::from_secs(12)).await;
+        let ips = dns.ipv4_lookup(domain()).await.unwrap();
+        assert_eq!(ips, vec![Ipv4Addr::new(127, 0, 0, 1)]);
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(resolver.lookups(), 2);
+        let ips = dns.ipv4_lookup(domain()).await.unwrap();
+        assert_eq!(ips, vec![Ipv4Addr::new(127, 0, 0, 2)]);
+
+        // too stale lookups are not served
+        tokio::time::advance(Duration::from_secs(20)).await;
+        resolver.set(lookup([127, 0, 0, 3], 10));
+        let ips = dns.ipv4_lookup(domain()).await.unwrap();
+        assert_eq!(ips, vec![Ipv4Addr::new(127, 0, 0, 3)]);
+        assert_eq!(resolver.lookups(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_caching_dns_in_memory_ttl() {
+        let mut dns = InMemoryDns::new().with_ttl(Duration::from_secs(42));
+        dns.insert(
+            domain(),
+            vec![[127, 0, 0, 1].into(), "::1".parse().unwrap()],
+        );
+
+        let lookup = dns.ipv6_lookup_with_ttl(domain()).await.unwrap();
+        assert_eq!(lookup.addresses(), &["::1".parse::<Ipv6Addr>().unwrap()]);
+        assert_eq!(lookup.ttl(), Some(Duration::from_secs(42)));
+
+        let dns = CachingDns::new(dns);
+        let lookup = dns.ipv4_lookup_with_ttl(domain()).await.unwrap();
+        assert_eq!(lookup.addresses(), &[Ipv4Addr::new(127, 0, 0, 1)]);
+        assert_eq!(lookup.ttl(), Some(Duration::from_secs(42)));
+    }
+}
@@ -0,0 +1,357 @@
+use crate::{
+    DnsLookup, DnsResolver, DomainNotMappedErr, SrvName, SrvRecord, SvcbRecord, TxtRecord,
+};
+use rama_core::error::BoxError;
+use rama_net::address::Domain;
+use std::{
+    fmt,
+    future::Future,
+    net::{Ipv4Addr, Ipv6Addr},
+    pin::Pin,
+    task::Poll,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+/// The strategy used by a [`DnsChain`] to query its resolvers.
+pub enum DnsChainStrategy {
+    #[default]
+    /// Query the resolvers one after the other, in order,
+    /// until one of them answers with records.
+    Sequential,
+    /// Query all resolvers concurrently, using the first answer with records.
+    Race,
+}
+
+/// A [`DnsResolver`] which combines multiple resolvers,
+/// querying them using a [`DnsChainStrategy`].
+///
+/// A lookup succeeds with the first answer which contains records,
+/// while resolvers which fail or answer without records fall through
+/// to the other resolvers. In case none of the resolvers answers with records,
+/// the chain answers without records if any of the resolvers did so,
+/// and fails with the error of the first failing resolver otherwise.
+///
+/// Resolvers of different types can be combined using
+/// the `Either` combinators of `rama-core`, which are [`DnsResolver`]s as well.
+pub struct DnsChain<R> {
+    resolvers: Vec<R>,
+    strategy: DnsChainStrategy,
+}
+
+impl<R: fmt::Debug> fmt::Debug for DnsChain<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DnsChain")
+            .field("resolvers", &self.resolvers)
+            .field("strategy", &self.strategy)
+            .finish()
+    }
+}
+
+impl<R: Clone> Clone for DnsChain<R> {
+    fn clone(&self) -> Self {
+        Self {
+            resolvers: self.resolvers.clone(),
+            strategy: self.strategy,
+        }
+    }
+}
+
+impl<R> DnsChain<R> {
+    /// Create a new [`DnsChain`] of the given resolvers,
+    /// queried using the [`DnsChainStrategy::Sequential`] strategy.
+    pub fn new(resolvers: impl IntoIterator<Item = R>) -> Self {
+        Self {
+            resolvers: resolvers.into_iter().collect(),
+            strategy: DnsChainStrategy::default(),
+        }
+    }
+
+    /// Set the [`DnsChainStrategy`] used to query the resolvers.
+    pub fn with_strategy(mut self, strategy: DnsChainStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Set the [`DnsChainStrategy`] used to query the resolvers.
+    pub fn set_strategy(&mut self, strategy: DnsChainStrategy) -> &mut Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// The resolvers of the [`DnsChain`], in order.
+    pub fn resolvers(&self) -> &[R] {
+        &self.resolvers
+    }
+}
+
+impl<R> DnsChain<R>
+where
+    R: DnsResolver<Error: Into<BoxError>>,
+{
+    async fn lookup<'a, T, F, Fut>(
+        &'a self,
+        lookup: F,
+        is_empty: fn(&T) -> bool,
+    ) -> Result<T, BoxError>
+    where
+        F: Fn(&'a R) -> Fut,
+        Fut: Future<Output = Result<T, R::Error>>,
+    {
+        let mut outcome = Outcome::new(is_empty);
+        match self.strategy {
+            DnsChainStrategy::Sequential => {
+                for resolver in &self.resolvers {
+                    if let Some(answer) = outcome.push(lookup(resolver).await) {
+                        return Ok(answer);
+                    }
+                }
+                outcome.finish()
+            }
+            DnsChainStrategy::Race => {
+                let mut pending: Vec<_> = self
+                    .resolvers
+                    .iter()
+                    .map(|resolver| Box::pin(lookup(resolver)))
+                    .collect();
+                std::future::poll_fn(|cx| {
+                    let mut index = 0;
+                    while index < pending.len() {
+                        match Pin::new(&mut pending[index]).poll(cx) {
+                            Poll::Pending => index += 1,
+                            Poll::Ready(result) => {
+                                pending.swap_remove(index);
+                                if let Some(answer) = outcome.push(result) {
+                                    return Poll::Ready(Ok(answer));
+                                }
+                            }
+                        }
+                    }
+                    if pending.is_empty() {
+                        Poll::Ready(outcome.take())
+                    } else {
+                        Poll::Pending
+                    }
+                })
+                .await
+            }
+        }
+    }
+}
+
+/// The outcome of the lookups of a [`DnsChain`] which did not answer with records (yet).
+struct Outcome<T> {
+    result: Option<Result<T, BoxError>>,
+    is_empty: fn(&T) -> bool,
+}
+
+impl<T> Outcome<T> {
+    fn new(is_empty: fn(&T) -> bool) -> Self {
+        Self {
+            result: None,
+            is_empty,
+        }
+    }
+
+    /// Record the result of a lookup, returning the answer in case it contains records.
+    fn push<E: Into<BoxError>>(&mut self, result: Result<T, E>) -> Option<T> {
+        match result {
+            Ok(answer) if !(self.is_empty)(&answer) => return Some(answer),
+            Ok(answer) => {
+                if !matches!(self.result, Some(Ok(_))) {
+                    self.result = Some(Ok(answer));
+                }
+            }
+            Err(err) => {
+                if self.result.is_none() {
+                    self.result = Some(Err(err.into()));
+                }
+            }
+        }
+        None
+    }
+
+    fn take(&mut self) -> Result<T, BoxError> {
+        self.result
+            .take()
+            .unwrap_or_else(|| Err(DomainNotMappedErr.into()))
+    }
+
+    fn finish(mut self) -> Result<T, BoxError> {
+        self.take()
+    }
+}
+
+impl<R> DnsResolver for DnsChain<R>
+where
+    R: DnsResolver<Error: Into<BoxError>>,
+{
+    type Error = BoxError;
+
+    async fn ipv4_lookup(&self, domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+        self.lookup(|r| r.ipv4_lookup(domain.clone()), Vec::is_empty)
+            .await
+    }
+
+    async fn ipv6_lookup(&self, domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+        self.lookup(|r| r.ipv6_lookup(domain.clone()), Vec::is_empty)
+            .await
+    }
+
+    async fn ipv4_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv4Addr>, Self::Error> {
+        self.lookup(
+            |r| r.ipv4_lookup_with_ttl(domain.clone()),
+            DnsLookup::is_empty,
+        )
+        .await
+    }
+
+    async fn ipv6_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv6Addr>, Self::Error> {
+        self.lookup(
+            |r| r.ipv6_lookup_with_ttl(domain.clone()),
+            DnsLookup::is_empty,
+        )
+        .await
+    }
+
+    async fn srv_lookup(&self, name: SrvName) -> Result<Vec<SrvRecord>, Self::Error> {
+        self.lookup(|r| r.srv_lookup(name.clone()), Vec::is_empty)
+            .await
+    }
+
+    async fn txt_lookup(&self, domain: Domain) -> Result<Vec<TxtRecord>, Self::Error> {
+        self.lookup(|r| r.txt_lookup(domain.clone()), Vec::is_empty)
+            .await
+    }
+
+    async fn cname_lookup(&self, domain: Domain) -> Result<Vec<Domain>, Self::Error> {
+        self.lookup(|r| r.cname_lookup(domain.clone()), Vec::is_empty)
+            .await
+    }
+
+    async fn https_lookup(&self, domain: Domain) -> Result<Vec<SvcbRecord>, Self::Error> {
+        self.lookup(|r| r.https_lookup(domain.clone()), Vec::is_empty)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InMemoryDns;
+    use std::{sync::Arc, time::Duration};
+
+    #[derive(Debug, Clone)]
+    struct DelayedDns {
+        dns: InMemoryDns,
+        delay: Duration,
+    }
+
+    impl DelayedDns {
+        fn new(ip: Option<Ipv4Addr>, delay: Duration) -> Self {
+            let mut dns = InMemoryDns::new();
+            if let Some(ip) = ip {
+                dns.insert(Domain::from_static("example.com"), vec![ip.into()]);
+            }
+            Self { dns, delay }
+        }
+    }
+
+    impl DnsResolver for DelayedDns {
+        type Error = DomainNotMappedErr;
+
+        async fn ipv4_lookup(&self, domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+            tokio::time::sleep(self.delay).await;
+            self.dns.ipv4_lookup(domain).await
+        }
+
+        async fn ipv6_lookup(&self, domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+            tokio::time::sleep(self.delay).await;
+            self.dns.ipv6_lookup(domain).await
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_dns_chain_sequential() {
+        let chain = DnsChain::new([
+            DelayedDns::new(None, Duration::from_secs(1)),
+            DelayedDns::new(Some(Ipv4Addr::new(10, 0, 0, 2)), Duration::from_secs(1)),
+            DelayedDns::new(Some(Ipv4Addr::new(10, 0, 0, 3)), Duration::ZERO),
+        ]);
+
+        let start = tokio::time::Instant::now();
+        let ips = chain
+            .ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        assert_eq!(ips, vec![Ipv4Addr::new(10, 0, 0, 2)]);
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+
+        let err = chain
+            .ipv6_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap_err();
+        assert!(err.is::<DomainNotMappedErr>());
+
+        let chain: DnsChain<DelayedDns> = DnsChain::new([]);
+        chain
+            .ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap_err();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_dns_chain_race() {
+        let chain = DnsChain::new([
+            DelayedDns::new(Some(Ipv4Addr::new(10, 0, 0, 1)), Duration::from_secs(3)),
+            DelayedDns::new(None, Duration::ZERO),
+            DelayedDns::new(Some(Ipv4Addr::new(10, 0, 0, 3)), Duration::from_secs(1)),
+        ])
+        .with_strategy(DnsChainStrategy::Race);
+
+        let start = tokio::time::Instant::now();
+        let ips = chain
+            .ipv4_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap();
+        assert_eq!(ips, vec![Ipv4Addr::new(10, 0, 0, 3)]);
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+
+        chain
+            .ipv6_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn test_dns_chain_empty_answer() {
+        // an empty answer falls through, but is preferred over an error
+        let mut dns = InMemoryDns::new();
+        dns.insert(
+            Domain::from_static("example.com"),
+            vec![[10, 0, 0, 1].into()],
+        );
+        let chain = DnsChain::new([
+            rama_core::combinators::Either::A(Arc::new(InMemoryDns::new())),
+            rama_core::combinators::Either::B(Arc::new(dns)),
+        ]);
+        assert!(chain
+            .txt_lookup(Domain::from_static("example.com"))
+            .await
+            .unwrap_err()
+            .is::<DomainNotMappedErr>());
+        assert_eq!(
+            chain
+                .ipv4_lookup_with_ttl(Domain::from_static("example.com"))
+                .await
+                .unwrap()
+                .addresses(),
+            [Ipv4Addr::new(10, 0, 0, 1)]
+        );
+    }
+}
@@ -0,0 +1,328 @@
+use crate::{
+    wire::{self, DnsExchange, MAX_MESSAGE_SIZE},
+    DnsLookup, DnsResolver, SrvName, SrvRecord, SvcbRecord, TxtRecord,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as ENGINE, Engine};
+use rama_core::{
+    error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
+    Context, Service,
+};
+use rama_http_types::{dep::http_body_util::BodyExt, header, Body, Method, Request, Response, Uri};
+use rama_net::address::Domain;
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+const DNS_MESSAGE_MIME: &str = "application/dns-message";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+/// The http [`Method`] used by a [`DohResolver`] to send its dns queries.
+pub enum DohMethod {
+    /// Send the dns query base64url encoded in the `dns` query parameter of a GET request,
+    /// which allows the responses to be cached by http caches.
+    Get,
+    #[default]
+    /// Send the dns query as the body of a POST request.
+    Post,
+}
+
+/// A [`DnsResolver`] which resolves domains using DNS-over-HTTPS (RFC 8484),
+/// sending the dns queries in the wire format using the given http client.
+///
+/// As the queries are sent using a regular http client [`Service`],
+/// such as the `HttpClient` of `rama-http-backend`, they go through
+/// the same proxy, tls and emulation stack as the other http requests
+/// made using that client, e.g. `DohResolver::new(HttpClient::default(), uri)`.
+///
+/// As a [`DnsResolver`] is not given the [`Context`] of the caller, the queries are
+/// served using a [`Context`] detached from the caller, meaning that any configuration
+/// the http client requires has to be part of the http client itself.
+pub struct DohResolver<S> {
+    client: S,
+    uri: Uri,
+    method: DohMethod,
+}
+
+impl<S: fmt::Debug> fmt::Debug for DohResolver<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DohResolver")
+            .field("client", &self.client)
+            .field("uri", &self.uri)
+            .field("method", &self.method)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for DohResolver<S> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            uri: self.uri.clone(),
+            method: self.method,
+        }
+    }
+}
+
+impl<S> DohResolver<S> {
+    /// Create a new [`DohResolver`], sending the dns queries
+    /// to the given DoH [`Uri`] using the given http client.
+    pub fn new(client: S, uri: Uri) -> Self {
+        Self {
+            client,
+            uri,
+            method: DohMethod::default(),
+        }
+    }
+
+    /// Set the [`DohMethod`] used to send the dns queries.
+    ///
+    /// Defaults to [`DohMethod::Post`].
+    pub fn with_method(mut self, method: DohMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Set the [`DohMethod`] used to send the dns queries.
+    ///
+    /// Defaults to [`DohMethod::Post`].
+    pub fn set_method(&mut self, method: DohMethod) -> &mut Self {
+        self.method = method;
+        self
+    }
+
+    /// The DoH [`Uri`] to which the dns queries are sent.
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    fn request(&self, query: Vec<u8>) -> Result<Request, OpaqueError> {
+        let builder = match self.method {
+            DohMethod::Get => {
+                let separator = if self.uri.query().is_some() { '&' } else { '?' };
+                let uri = format!("{}{separator}dns={}", self.uri, ENGINE.encode(&query));
+                Request::builder()
+                    .method(Method::GET)
+                    .uri(uri)
+                    .body(Body::empty())
+            }
+            DohMethod::Post => Request::builder()
+                .method(Method::POST)
+                .uri(self.uri.clone())
+                .header(header::CONTENT_TYPE, DNS_MESSAGE_MIME)
+                .body(Body::from(query)),
+        };
+        builder
+            .map(|mut req| {
+                req.headers_mut().insert(
+                    header::ACCEPT,
+                    header::HeaderValue::from_static(DNS_MESSAGE_MIME),
+                );
+                req
+            })
+            .context("create DoH request")
+    }
+}
+
+impl<S> DnsExchange for DohResolver<S>
+where
+    S: Service<(), Request, Response = Response, Error: Into<BoxError>>,
+{
+    async fn exchange(&self, query: Vec<u8>) -> Result<Vec<u8>, OpaqueError> {
+        let req = self.request(query)?;
+        let resp = self
+            .client
+            .serve(Context::default(), req)
+            .await
+            .map_err(|err| OpaqueError::from_boxed(err.into()).context("send DoH request"))?;
+
+        if !resp.status().is_success() {
+            return Err(OpaqueError::from_display(format!(
+                "DoH server replied with status {}",
+                resp.status()
+            )));
+        }
+        let body = resp
+            .into_body()
+            .limited(MAX_MESSAGE_SIZE)
+            .collect()
+            .await
+            .context("read DoH response body")?;
+        Ok(body.to_bytes().to_vec())
+    }
+}
+
+impl<S> DnsResolver for DohResolver<S>
+where
+    S: Service<(), Request, Response = Response, Error: Into<BoxError>>,
+{
+    type Error = OpaqueError;
+
+    async fn ipv4_lookup(&self, domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+        wire::ipv4_lookup(self, domain)
+            .await
+            .and_then(wire::into_addresses)
+    }
+
+    async fn ipv6_lookup(&self, domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+        wire::ipv6_lookup(self, domain)
+            .await
+            .and_then(wire::into_addresses)
+    }
+
+    async fn ipv4_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv4Addr>, Self::Error> {
+        wire::ipv4_lookup(self, domain).await
+    }
+
+    async fn ipv6_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv6Addr>, Self::Error> {
+        wire::ipv6_lookup(self, domain).await
+    }
+
+    async fn srv_lookup(&self, name: SrvName) -> Result<Vec<SrvRecord>, Self::Error> {
+        wire::srv_lookup(self, name).await
+    }
+
+    async fn txt_lookup(&self, domain: Domain) -> Result<Vec<TxtRecord>, Self::Error> {
+        wire::txt_lookup(self, domain).await
+    }
+
+    async fn cname_lookup(&self, domain: Domain) -> Result<Vec<Domain>, Self::Error> {
+        wire::cname_lookup(self, domain).await
+    }
+
+    async fn https_lookup(&self, domain: Domain) -> Result<Vec<SvcbRecord>, Self::Error> {
+        wire::https_lookup(self, domain).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        server::{proto::op::Message, DnsRequest, DnsTransport, InMemoryDnsService},
+        InMemoryDns,
+    };
+    use rama_core::service::service_fn;
+    use rama_http_types::StatusCode;
+    use std::{convert::Infallible, time::Duration};
+
+    /// A stand-in DoH server, answering the dns queries using an [`InMemoryDns`].
+    fn doh_server(
+        method: Method,
+    ) -> impl Service<(), Request, Response = Response, Error = Infallible> {
+        let mut dns = InMemoryDns::new();
+        dns.insert(
+            Domain::from_static("example.internal"),
+            vec![[10, 0, 0, 1].into()],
+        )
+        .insert_txt(
+            Domain::from_static("example.internal"),
+            vec!["v=spf1 -all".into()],
+        );
+        let dns = InMemoryDnsService::new(dns).with_ttl(Duration::from_secs(30));
+
+        service_fn(move |req: Request| {
+            let dns = dns.clone();
+            let method = method.clone();
+            async move {
+                assert_eq!(req.method(), method);
+                assert_eq!(req.uri().path(), "/dns-query");
+                assert_eq!(req.headers()[header::ACCEPT], DNS_MESSAGE_MIME);
+                let query = if req.method() == Method::GET {
+                    let query = req.uri().query().unwrap();
+                    let (_, value) = query
+                        .split('&')
+                        .filter_map(|param| param.split_once('='))
+                        .find(|(key, _)| *key == "dns")
+                        .unwrap();
+                    ENGINE.decode(value).unwrap()
+                } else {
+                    assert_eq!(req.headers()[header::CONTENT_TYPE], DNS_MESSAGE_MIME);
+                    req.into_body().collect().await.unwrap().to_bytes().to_vec()
+                };
+
+                let query = Message::from_vec(&query).unwrap();
+                assert_eq!(query.id(), 0);
+                let response = dns
+                    .serve(
+                        Context::default(),
+                        DnsRequest::new(query, DnsTransport::Tcp),
+                    )
+                    .await?;
+                Ok(Response::builder()
+                    .status(StatusCode::OK)
+                    .header(header::CONTENT_TYPE, DNS_MESSAGE_MIME)
+                    .body(Body::from(response.into_message().to_vec().unwrap()))
+                    .unwrap())
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_doh_resolver() {
+        for (method, doh_method, uri) in [
+            (
+                Method::GET,
+                DohMethod::Get,
+                "https://dns.internal/dns-query",
+            ),
+            (
+                Method::GET,
+                DohMethod::Get,
+                "https://dns.internal/dns-query?ct",
+            ),
+            (
+                Method::POST,
+                DohMethod::Post,
+                "https://dns.internal/dns-query",
+            ),
+        ] {
+            let dns =
+                DohResolver::new(doh_server(method), uri.parse().unwrap()).with_method(doh_method);
+
+            let lookup = dns
+                .ipv4_lookup_with_ttl(Domain::from_static("example.internal"))
+                .await
+                .unwrap();
+            assert_eq!(lookup.addresses(), [Ipv4Addr::new(10, 0, 0, 1)]);
+            assert_eq!(lookup.ttl(), Some(Duration::from_secs(30)));
+
+            let records = dns
+                .txt_lookup(Domain::from_static("example.internal"))
+                .await
+                .unwrap();
+            assert_eq!(records, vec![TxtRecord::from("v=spf1 -all")]);
+
+            let lookup = dns
+                .ipv4_lookup_with_ttl(Domain::from_static("unknown.internal"))
+                .await
+                .unwrap();
+            assert!(lookup.is_empty());
+            dns.ipv4_lookup(Domain::from_static("unknown.internal"))
+                .await
+                .unwrap_err();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_doh_resolver_http_error() {
+        let client = service_fn(|_req: Request| async move {
+            Ok::<_, Infallible>(
+                Response::builder()
+                    .status(StatusCode::BAD_GATEWAY)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        });
+        let dns = DohResolver::new(client, "https://dns.internal/dns-query".parse().unwrap());
+        dns.ipv4_lookup(Domain::from_static("example.internal"))
+            .await
+            .unwrap_err();
+    }
+}
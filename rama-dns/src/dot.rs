@@ -0,0 +1,485 @@
+use crate::{
+    wire::{self, DnsExchange},
+    DnsLookup, DnsResolver, SrvName, SrvRecord, SvcbRecord, TxtRecord,
+};
+use hickory_resolver::proto::op::{Message, Query};
+use parking_lot::Mutex;
+use rama_core::{
+    error::{ErrorContext, ErrorExt, OpaqueError},
+    Context,
+};
+use rama_net::{
+    address::{Authority, Domain},
+    client::{ConnectorService, EstablishedClientConnection},
+    stream::Stream,
+    transport::{TransportContext, TransportProtocol},
+};
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// The maximum amount of idle connections kept open to the DoT server.
+const MAX_IDLE_CONNECTIONS: usize = 4;
+/// How long an idle connection is reused, after which a new connection is established,
+/// as the DoT server likely closed it by then (RFC 7766).
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+/// The default time to wait for the DoT server to answer a query.
+const DEFAULT_EXCHANGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A [`DnsResolver`] which resolves domains using DNS-over-TLS (RFC 7858),
+/// sending the dns queries over a connection established using the given connector.
+///
+/// The connector is expected to establish a tls connection to the [`Authority`]
+/// of the DoT server, such as the `HttpsConnector` of `rama-tls` wrapping a tcp connector,
+/// e.g. `HttpsConnector::secure_only(TcpConnector::new())`, such that the queries
+/// go through the same proxy, tls and emulation stack as the other connections
+/// established using that connector.
+///
+/// Connections are reused for subsequent queries, and are shared between clones
+/// of the [`DotResolver`]. Concurrent queries are sent over separate connections.
+/// A connection is dropped in case the DoT server does not answer in time,
+/// or answers with a response which does not match the query.
+///
+/// As a [`DnsResolver`] is not given the [`Context`] of the caller, connections are
+/// established using a [`Context`] detached from the caller, meaning that any
+/// configuration the connector requires has to be part of the connector itself.
+pub struct DotResolver<C: ConnectorService<(), TransportContext>> {
+    connector: C,
+    authority: Authority,
+    timeout: Duration,
+    idle: Arc<Mutex<Vec<(C::Connection, Instant)>>>,
+}
+
+impl<C> fmt::Debug for DotResolver<C>
+where
+    C: ConnectorService<(), TransportContext> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DotResolver")
+            .field("connector", &self.connector)
+            .field("authority", &self.authority)
+            .field("timeout", &self.timeout)
+            .field("idle", &self.idle.lock().len())
+            .finish()
+    }
+}
+
+impl<C> Clone for DotResolver<C>
+where
+    C: ConnectorService<(), TransportContext> + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            connector: self.connector.clone(),
+            authority: self.authority.clone(),
+            timeout: self.timeout,
+            idle: self.idle.clone(),
+        }
+    }
+}
+
+impl<C: ConnectorService<(), TransportContext>> DotResolver<C> {
+    /// Create a new [`DotResolver`], sending the dns queries to the DoT server
+    /// at the given [`Authority`] over the connections established by the given connector.
+    pub fn new(connector: C, authority: Authority) -> Self {
+        Self {
+            connector,
+            authority,
+            timeout: DEFAULT_EXCHANGE_TIMEOUT,
+            idle: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// The [`Authority`] of the DoT server.
+    pub fn authority(&self) -> &Authority {
+        &self.authority
+    }
+
+    /// Set the time to wait for the DoT server to answer a query,
+    /// once the connection is established.
+    ///
+    /// Defaults to 5 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the time to wait for the DoT server to answer a query,
+    /// once the connection is established.
+    ///
+    /// Defaults to 5 seconds.
+    pub fn set_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl<C> DnsExchange for DotResolver<C>
+where
+    C: ConnectorService<(), TransportContext, Connection: Stream + Unpin>,
+{
+    async fn exchange(&self, query: Vec<u8>) -> Result<Vec<u8>, OpaqueError> {
+        let len = u16::try_from(query.len()).context("DoT query too large")?;
+        let questions = Message::from_vec(&query)
+            .context("decode DoT query")?
+            .take_queries();
+
+        if let Some(mut conn) = self.checkout() {
+            match exchange_over(&mut conn, len, &query, &questions, self.timeout).await {
+                Ok(response) => {
+                    self.checkin(conn);
+                    return Ok(response);
+                }
+                Err(err) => {
+                    // the DoT server may have closed the idle connection in the meantime
+                    tracing::trace!(err = %err, "DoT exchange over reused connection failed: retry over new connection");
+                }
+            }
+        }
+
+        let transport_ctx = TransportContext {
+            protocol: TransportProtocol::Tcp,
+            app_protocol: None,
+            http_version: None,
+            authority: self.authority.clone(),
+        };
+        let EstablishedClientConnection { mut conn, .. } = self
+            .connector
+            .connect(Context::default(), transport_ctx)
+            .await
+            .map_err(|err| OpaqueError::from_boxed(err.into()).context("connect to DoT server"))?;
+
+        let response = exchange_over(&mut conn, len, &query, &questions, self.timeout).await?;
+        self.checkin(conn);
+        Ok(response)
+    }
+}
+
+impl<C: ConnectorService<(), TransportContext>> DotResolver<C> {
+    /// Take the most recently used idle connection, if any is still fresh.
+    fn checkout(&self) -> Option<C::Connection> {
+        let mut idle = self.idle.lock();
+        idle.retain(|(_, idle_since)| idle_since.elapsed() < IDLE_TIMEOUT);
+        idle.pop().map(|(conn, _)| conn)
+    }
+
+    fn checkin(&self, conn: C::Connection) {
+        let mut idle = self.idle.lock();
+        if idle.len() < MAX_IDLE_CONNECTIONS {
+            idle.push((conn, Instant::now()));
+        }
+    }
+}
+
+/// Exchange the query over the given connection, which is to be dropped on error,
+/// as it can no longer be used for another query.
+async fn exchange_over<IO>(
+    conn: &mut IO,
+    len: u16,
+    query: &[u8],
+    questions: &[Query],
+    timeout: Duration,
+) -> Result<Vec<u8>, OpaqueError>
+where
+    IO: Stream + Unpin,
+{
+    let exchange = async {
+        // each message is prefixed with its length as a two byte integer
+        conn.write_u16(len).await.context("write DoT query")?;
+        conn.write_all(query).await.context("write DoT query")?;
+        conn.flush().await.context("write DoT query")?;
+
+        let len = conn.read_u16().await.context("read DoT response")?;
+        let mut response = vec![0; len as usize];
+        conn.read_exact(&mut response)
+            .await
+            .context("read DoT response")?;
+        Ok::<_, OpaqueError>(response)
+    };
+    let response = tokio::time::timeout(timeout, exchange)
+        .await
+        .map_err(|_| OpaqueError::from_display("DoT server did not answer in time"))??;
+
+    // a response to another query means the connection is out of sync
+    let message = Message::from_vec(&response).context("decode DoT response")?;
+    if message.queries() != questions {
+        return Err(OpaqueError::from_display(
+            "DoT server replied with a response to another query",
+        ));
+    }
+    Ok(response)
+}
+
+impl<C> DnsResolver for DotResolver<C>
+where
+    C: ConnectorService<(), TransportContext, Connection: Stream + Unpin>,
+{
+    type Error = OpaqueError;
+
+    async fn ipv4_lookup(&self, domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+        wire::ipv4_lookup(self, domain)
+            .await
+            .and_then(wire::into_addresses)
+    }
+
+    async fn ipv6_lookup(&self, domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+        wire::ipv6_lookup(self, domain)
+            .await
+            .and_then(wire::into_addresses)
+    }
+
+    async fn ipv4_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv4Addr>, Self::Error> {
+        wire::ipv4_lookup(self, domain).await
+    }
+
+    async fn ipv6_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv6Addr>, Self::Error> {
+        wire::ipv6_lookup(self, domain).await
+    }
+
+    async fn srv_lookup(&self, name: SrvName) -> Result<Vec<SrvRecord>, Self::Error> {
+        wire::srv_lookup(self, name).await
+    }
+
+    async fn txt_lookup(&self, domain: Domain) -> Result<Vec<TxtRecord>, Self::Error> {
+        wire::txt_lookup(self, domain).await
+    }
+
+    async fn cname_lookup(&self, domain: Domain) -> Result<Vec<Domain>, Self::Error> {
+        wire::cname_lookup(self, domain).await
+    }
+
+    async fn https_lookup(&self, domain: Domain) -> Result<Vec<SvcbRecord>, Self::Error> {
+        wire::https_lookup(self, domain).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        server::{DnsRequest, DnsResponse, DnsServer, InMemoryDnsService},
+        InMemoryDns,
+    };
+    use hickory_resolver::proto::rr::{Name, RecordType};
+    use rama_core::{error::BoxError, service::service_fn, Layer, Service};
+    use rama_tls::{
+        dep::rcgen,
+        rustls::{
+            client::HttpsConnector,
+            dep::{
+                pki_types::PrivatePkcs8KeyDer,
+                rustls::{server::TlsStream, ServerConfig},
+            },
+            server::TlsAcceptorLayer,
+        },
+    };
+    use std::{
+        convert::Infallible,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Spawn a stand-in DoT server on loopback, answering the dns queries using an [`InMemoryDns`].
+    async fn spawn_dot_server(dns: InMemoryDns, idle_timeout: Duration) -> Authority {
+        spawn_dot_server_with(InMemoryDnsService::new(dns), idle_timeout).await
+    }
+
+    /// Spawn a stand-in DoT server on loopback, answering the dns queries using the given service.
+    async fn spawn_dot_server_with<S>(service: S, idle_timeout: Duration) -> Authority
+    where
+        S: Service<(), DnsRequest, Response = DnsResponse, Error = Infallible>,
+    {
+        let rcgen::CertifiedKey { cert, key_pair } =
+            rcgen::generate_simple_self_signed(vec!["127.0.0.1".to_owned()]).unwrap();
+        let config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![cert.into()],
+                PrivatePkcs8KeyDer::from(key_pair.serialize_der()).into(),
+            )
+            .unwrap();
+
+        let server = DnsServer::new(service).with_idle_timeout(idle_timeout);
+        let acceptor = TlsAcceptorLayer::new(Arc::new(config)).layer(service_fn(
+            move |ctx: Context<()>, stream: TlsStream<TcpStream>| {
+                let server = server.clone();
+                async move { server.serve_stream(ctx, stream).await }
+            },
+        ));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move { acceptor.serve(Context::default(), stream).await });
+            }
+        });
+        addr.into()
+    }
+
+    /// A tcp connector counting the connections it establishes.
+    fn counting_tcp_connector(
+        connections: Arc<AtomicUsize>,
+    ) -> impl Service<
+        (),
+        TransportContext,
+        Response = EstablishedClientConnection<TcpStream, (), TransportContext>,
+        Error = BoxError,
+    > + Clone {
+        service_fn(move |ctx: Context<()>, req: TransportContext| {
+            let connections = connections.clone();
+            async move {
+                connections.fetch_add(1, Ordering::SeqCst);
+                let addr = req.authority.to_string();
+                let conn = TcpStream::connect(&addr).await?;
+                let addr = conn.peer_addr()?;
+                Ok(EstablishedClientConnection {
+                    ctx,
+                    req,
+                    conn,
+                    addr,
+                })
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_dot_resolver() {
+        let mut dns = InMemoryDns::new();
+        dns.insert(
+            Domain::from_static("example.internal"),
+            vec![[10, 0, 0, 1].into(), [10, 0, 0, 2].into()],
+        )
+        .insert_cname(
+            Domain::from_static("www.example.internal"),
+            Domain::from_static("example.internal"),
+        );
+        let authority = spawn_dot_server(dns, Duration::from_secs(10)).await;
+
+        let connections = Arc::new(AtomicUsize::new(0));
+        let tcp_connector = counting_tcp_connector(connections.clone());
+        let dns = DotResolver::new(HttpsConnector::secure_only(tcp_connector), authority);
+
+        let ips = dns
+            .ipv4_lookup(Domain::from_static("example.internal"))
+            .await
+            .unwrap();
+        assert_eq!(
+            ips,
+            vec![Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2)]
+        );
+
+        let names = dns
+            .cname_lookup(Domain::from_static("www.example.internal"))
+            .await
+            .unwrap();
+        assert_eq!(names, vec![Domain::from_static("example.internal")]);
+
+        let lookup = dns
+            .ipv6_lookup_with_ttl(Domain::from_static("unknown.internal"))
+            .await
+            .unwrap();
+        assert!(lookup.is_empty());
+
+        // the queries are sent over the same connection
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dot_resolver_closed_connection() {
+        let mut dns = InMemoryDns::new();
+        dns.insert(
+            Domain::from_static("example.internal"),
+            vec![[10, 0, 0, 1].into()],
+        );
+        let authority = spawn_dot_server(dns, Duration::from_millis(50)).await;
+
+        let connections = Arc::new(AtomicUsize::new(0));
+        let dns = DotResolver::new(
+            HttpsConnector::secure_only(counting_tcp_connector(connections.clone())),
+            authority,
+        );
+
+        for _ in 0..2 {
+            let ips = dns
+                .ipv4_lookup(Domain::from_static("example.internal"))
+                .await
+                .unwrap();
+            assert_eq!(ips, vec![Ipv4Addr::new(10, 0, 0, 1)]);
+            // the idle connection is closed by the DoT server
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_dot_resolver_timeout() {
+        let authority = spawn_dot_server_with(
+            service_fn(|req: DnsRequest| async move {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                Ok(DnsResponse::for_request(&req))
+            }),
+            Duration::from_secs(10),
+        )
+        .await;
+
+        let connections = Arc::new(AtomicUsize::new(0));
+        let dns = DotResolver::new(
+            HttpsConnector::secure_only(counting_tcp_connector(connections.clone())),
+            authority,
+        )
+        .with_timeout(Duration::from_millis(100));
+
+        for _ in 0..2 {
+            dns.ipv4_lookup(Domain::from_static("example.internal"))
+                .await
+                .unwrap_err();
+        }
+        // the connection is dropped on timeout
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_dot_resolver_question_mismatch() {
+        let authority = spawn_dot_server_with(
+            service_fn(|req: DnsRequest| async move {
+                let mut response = DnsResponse::for_request(&req);
+                let message = response.message_mut();
+                message.take_queries();
+                message.add_query(Query::query(
+                    Name::from_ascii("other.internal.").unwrap(),
+                    RecordType::A,
+                ));
+                Ok(response)
+            }),
+            Duration::from_secs(10),
+        )
+        .await;
+
+        let connections = Arc::new(AtomicUsize::new(0));
+        let dns = DotResolver::new(
+            HttpsConnector::secure_only(counting_tcp_connector(connections.clone())),
+            authority,
+        );
+
+        for _ in 0..2 {
+            dns.ipv4_lookup(Domain::from_static("example.internal"))
+                .await
+                .unwrap_err();
+        }
+        // the connection is dropped, as it is out of sync
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+}
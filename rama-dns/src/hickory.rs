@@ -1,8 +1,18 @@
 //! dns using the [`hickory_resolver`] crate
 
-use crate::DnsResolver;
+use crate::{
+    DnsLookup, DnsResolver, SrvName, SrvRecord, SrvServiceUnavailableErr, SvcbRecord, TxtRecord,
+};
 use hickory_resolver::{
-    proto::rr::rdata::{A, AAAA},
+    error::{ResolveError, ResolveErrorKind},
+    lookup::Lookup,
+    proto::rr::{
+        rdata::{
+            svcb::{SvcParamValue, SVCB},
+            A, AAAA, CNAME, HTTPS,
+        },
+        RData, RecordType,
+    },
     Name, TokioAsyncResolver,
 };
 use rama_core::error::{ErrorContext, OpaqueError};
@@ -10,6 +20,7 @@ use rama_net::address::Domain;
 use std::{
     net::{Ipv4Addr, Ipv6Addr},
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 pub use hickory_resolver::config;
@@ -122,9 +133,163 @@ impl DnsResolver for HickoryDns {
             .map(|AAAA(ip)| ip)
             .collect())
     }
+
+    async fn ipv4_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv4Addr>, Self::Error> {
+        let name = fqdn_from_domain(domain)?;
+        match self.0.ipv4_lookup(name).await {
+            Ok(lookup) => {
+                let ttl = ttl_from_lookup(lookup.as_lookup());
+                Ok(DnsLookup::new(lookup.into_iter().map(|A(ip)| ip).collect()).with_ttl(ttl))
+            }
+            Err(err) => negative_lookup(err).context("lookup IPv4 address(es)"),
+        }
+    }
+
+    async fn ipv6_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv6Addr>, Self::Error> {
+        let name = fqdn_from_domain(domain)?;
+        match self.0.ipv6_lookup(name).await {
+            Ok(lookup) => {
+                let ttl = ttl_from_lookup(lookup.as_lookup());
+                Ok(DnsLookup::new(lookup.into_iter().map(|AAAA(ip)| ip).collect()).with_ttl(ttl))
+            }
+            Err(err) => negative_lookup(err).context("lookup IPv6 address(es)"),
+        }
+    }
+
+    async fn srv_lookup(&self, name: SrvName) -> Result<Vec<SrvRecord>, Self::Error> {
+        let name =
+            Name::from_utf8(name.to_string()).context("try to consume a SrvName as a Dns Name")?;
+        let lookup = match self.0.srv_lookup(name).await {
+            Ok(lookup) => lookup,
+            Err(err) => return no_records(err).context("lookup SRV record(s)"),
+        };
+        // a target of "." indicates that the service is decidedly not offered (RFC 2782)
+        if lookup.iter().next().is_some() && lookup.iter().all(|srv| srv.target().is_root()) {
+            return Err(OpaqueError::from_std(SrvServiceUnavailableErr));
+        }
+        Ok(lookup
+            .iter()
+            .filter_map(|srv| {
+                let target = domain_from_name(srv.target())?;
+                Some(SrvRecord::new(
+                    srv.priority(),
+                    srv.weight(),
+                    srv.port(),
+                    target,
+                ))
+            })
+            .collect())
+    }
+
+    async fn txt_lookup(&self, domain: Domain) -> Result<Vec<TxtRecord>, Self::Error> {
+        let name = fqdn_from_domain(domain)?;
+        let lookup = match self.0.txt_lookup(name).await {
+            Ok(lookup) => lookup,
+            Err(err) => return no_records(err).context("lookup TXT record(s)"),
+        };
+        Ok(lookup
+            .iter()
+            .map(|txt| TxtRecord::new(txt.iter().map(|data| data.to_vec()).collect()))
+            .collect())
+    }
+
+    async fn cname_lookup(&self, domain: Domain) -> Result<Vec<Domain>, Self::Error> {
+        let name = fqdn_from_domain(domain)?;
+        let lookup = match self.0.lookup(name, RecordType::CNAME).await {
+            Ok(lookup) => lookup,
+            Err(err) => return no_records(err).context("lookup CNAME record(s)"),
+        };
+        Ok(lookup
+            .iter()
+            .filter_map(|rdata| match rdata {
+                RData::CNAME(CNAME(name)) => domain_from_name(name),
+                _ => None,
+            })
+            .collect())
+    }
+
+    async fn https_lookup(&self, domain: Domain) -> Result<Vec<SvcbRecord>, Self::Error> {
+        let name = fqdn_from_domain(domain)?;
+        let lookup = match self.0.lookup(name, RecordType::HTTPS).await {
+            Ok(lookup) => lookup,
+            Err(err) => return no_records(err).context("lookup HTTPS record(s)"),
+        };
+        Ok(lookup
+            .iter()
+            .filter_map(|rdata| match rdata {
+                RData::HTTPS(HTTPS(svcb)) => Some(svcb_record(svcb)),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+pub(crate) fn svcb_record(svcb: &SVCB) -> SvcbRecord {
+    let mut record = SvcbRecord::new(svcb.svc_priority(), domain_from_name(svcb.target_name()));
+    for (_, value) in svcb.svc_params() {
+        match value {
+            SvcParamValue::Alpn(alpn) => {
+                record.set_alpn(alpn.0.clone());
+            }
+            SvcParamValue::Port(port) => {
+                record.set_port(*port);
+            }
+            SvcParamValue::Ipv4Hint(hint) => {
+                record.set_ipv4_hints(hint.0.iter().map(|A(ip)| *ip).collect());
+            }
+            SvcParamValue::Ipv6Hint(hint) => {
+                record.set_ipv6_hints(hint.0.iter().map(|AAAA(ip)| *ip).collect());
+            }
+            SvcParamValue::EchConfig(ech_config) => {
+                record.set_ech_config(ech_config.0.clone());
+            }
+            _ => (),
+        }
+    }
+    record
+}
+
+/// Convert a dns [`Name`] into a [`Domain`],
+/// returning `None` for the root name (or any other name which is not a valid [`Domain`]).
+pub(crate) fn domain_from_name(name: &Name) -> Option<Domain> {
+    if name.is_root() {
+        return None;
+    }
+    let name = name.to_ascii();
+    Domain::try_from(name.trim_end_matches('.').to_owned()).ok()
+}
+
+/// Turn a "no records found" error into an empty list of records.
+fn no_records<T>(err: ResolveError) -> Result<Vec<T>, ResolveError> {
+    match err.kind() {
+        ResolveErrorKind::NoRecordsFound { .. } => Ok(Vec::new()),
+        _ => Err(err),
+    }
+}
+
+fn ttl_from_lookup(lookup: &Lookup) -> Duration {
+    lookup
+        .valid_until()
+        .saturating_duration_since(Instant::now())
+}
+
+/// Turn a "no records found" error into an empty lookup,
+/// using the negative ttl of the answer if known.
+fn negative_lookup<T>(err: ResolveError) -> Result<DnsLookup<T>, ResolveError> {
+    match err.kind() {
+        ResolveErrorKind::NoRecordsFound { negative_ttl, .. } => Ok(DnsLookup::empty()
+            .maybe_with_ttl(negative_ttl.map(|ttl| Duration::from_secs(ttl as u64)))),
+        _ => Err(err),
+    }
 }
 
-fn fqdn_from_domain(domain: Domain) -> Result<Name, OpaqueError> {
+pub(crate) fn fqdn_from_domain(domain: Domain) -> Result<Name, OpaqueError> {
     let mut name = Name::from_utf8(domain).context("try to consume a Domain as a Dns Name")?;
     name.set_fqdn(true);
     Ok(name)
@@ -0,0 +1,279 @@
+use crate::{DnsResolver, DomainNotMappedErr};
+use rama_core::{
+    graceful::ShutdownGuard,
+    reload::{ReloadTrigger, Reloadable},
+};
+use rama_net::address::Domain;
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+#[cfg(not(windows))]
+const SYSTEM_HOSTS_PATH: &str = "/etc/hosts";
+#[cfg(windows)]
+const SYSTEM_HOSTS_PATH: &str = r"C:\Windows\System32\drivers\etc\hosts";
+
+#[derive(Debug, Clone, Default)]
+/// The entries of a hosts file, such as `/etc/hosts`,
+/// mapping domains to the IP addresses they resolve to.
+pub struct HostsFile {
+    ipv4: HashMap<Domain, Vec<Ipv4Addr>>,
+    ipv6: HashMap<Domain, Vec<Ipv6Addr>>,
+}
+
+impl HostsFile {
+    /// Create a new empty [`HostsFile`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse the content of a hosts file.
+    ///
+    /// Each line consists of an IP address followed by one or more host names,
+    /// separated by whitespace, where everything following a `#` is a comment.
+    /// Lines with an invalid IP address, as well as invalid host names, are ignored.
+    pub fn parse(content: &str) -> Self {
+        let mut hosts = Self::new();
+        for line in content.lines() {
+            let line = line.split_once('#').map_or(line, |(line, _)| line);
+            let mut fields = line.split_whitespace();
+            let Some(Ok(ip)) = fields.next().map(str::parse::<IpAddr>) else {
+                continue;
+            };
+            for name in fields {
+                if let Ok(domain) = Domain::try_from(name.trim_end_matches('.').to_owned()) {
+                    hosts.insert(domain, ip);
+                }
+            }
+        }
+        hosts
+    }
+
+    /// Read and parse the hosts file at the given path.
+    pub async fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let content = tokio::fs::read_to_string(path).await?;
+        Ok(Self::parse(&content))
+    }
+
+    /// Read and parse the hosts file of the system,
+    /// e.g. `/etc/hosts` on unix platforms.
+    pub async fn system() -> io::Result<Self> {
+        Self::load(SYSTEM_HOSTS_PATH).await
+    }
+
+    /// Map the given [`Domain`] to the given IP address,
+    /// in addition to the addresses it is already mapped to.
+    pub fn insert(&mut self, domain: Domain, ip: IpAddr) -> &mut Self {
+        match ip {
+            IpAddr::V4(ip) => self.ipv4.entry(domain).or_default().push(ip),
+            IpAddr::V6(ip) => self.ipv6.entry(domain).or_default().push(ip),
+        }
+        self
+    }
+
+    /// The [`Ipv4Addr`]esses the given [`Domain`] is mapped to, if any.
+    pub fn ipv4_addresses(&self, domain: &Domain) -> Option<&[Ipv4Addr]> {
+        self.ipv4.get(domain).map(Vec::as_slice)
+    }
+
+    /// The [`Ipv6Addr`]esses the given [`Domain`] is mapped to, if any.
+    pub fn ipv6_addresses(&self, domain: &Domain) -> Option<&[Ipv6Addr]> {
+        self.ipv6.get(domain).map(Vec::as_slice)
+    }
+
+    /// Returns `true` in case no domain is mapped.
+    pub fn is_empty(&self) -> bool {
+        self.ipv4.is_empty() && self.ipv6.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A [`DnsResolver`] which resolves domains using the entries of a [`HostsFile`].
+///
+/// The entries can be reloaded at runtime, e.g. every time the hosts file
+/// is modified using [`HostsDns::reload_on_change`], without affecting the
+/// clones of the [`HostsDns`] in use.
+///
+/// Lookups for domains which are not mapped fail with a [`DomainNotMappedErr`],
+/// such that the [`HostsDns`] can be used as the first resolver of a [`DnsChain`].
+///
+/// [`DnsChain`]: crate::DnsChain
+pub struct HostsDns {
+    hosts: Reloadable<HostsFile>,
+}
+
+impl HostsDns {
+    /// Create a new [`HostsDns`], resolving domains using the given [`HostsFile`].
+    pub fn new(hosts: HostsFile) -> Self {
+        Self {
+            hosts: Reloadable::new(hosts),
+        }
+    }
+
+    /// Create a new [`HostsDns`], resolving domains using the hosts file at the given path.
+    pub async fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        HostsFile::load(path).await.map(Self::new)
+    }
+
+    /// Create a new [`HostsDns`], resolving domains using the hosts file of the system,
+    /// e.g. `/etc/hosts` on unix platforms.
+    pub async fn system() -> io::Result<Self> {
+        HostsFile::system().await.map(Self::new)
+    }
+
+    /// The [`HostsFile`] currently used to resolve domains.
+    pub fn hosts(&self) -> Arc<HostsFile> {
+        self.hosts.load()
+    }
+
+    /// The [`Reloadable`] [`HostsFile`] used to resolve domains,
+    /// which can be used to replace the entries, or to reload them on a custom trigger.
+    pub fn reloadable(&self) -> &Reloadable<HostsFile> {
+        &self.hosts
+    }
+
+    /// Spawn a task which reloads the entries from the hosts file at the given path
+    /// every time it is modified, polling it at the given interval, until shutdown is initiated.
+    ///
+    /// A hosts file which can no longer be read keeps the current entries in place.
+    pub fn reload_on_change(
+        &self,
+        guard: ShutdownGuard,
+        path: impl Into<PathBuf>,
+        interval: Duration,
+    ) {
+        let path = path.into();
+        let trigger = ReloadTrigger::new();
+        trigger.trigger_on_file_change(guard.clone(), path.clone(), interval);
+        self.hosts.reload_on(guard, &trigger, move || {
+            let path = path.clone();
+            async move { HostsFile::load(path).await }
+        });
+    }
+}
+
+impl DnsResolver for HostsDns {
+    type Error = DomainNotMappedErr;
+
+    async fn ipv4_lookup(&self, domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+        self.hosts
+            .load()
+            .ipv4_addresses(&domain)
+            .map(<[_]>::to_vec)
+            .ok_or(DomainNotMappedErr)
+    }
+
+    async fn ipv6_lookup(&self, domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+        self.hosts
+            .load()
+            .ipv6_addresses(&domain)
+            .map(<[_]>::to_vec)
+            .ok_or(DomainNotMappedErr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::graceful::Shutdown;
+
+    const HOSTS: &str = "
+# The following lines are desirable for IPv4 capable hosts
+127.0.0.1       localhost localhost.localdomain
+192.168.1.10    foo.internal.  foo   # trailing comment
+192.168.1.11    foo.internal
+::1             localhost ip6-localhost
+fe80::1%lo0     link.local
+not-an-ip       bar.internal
+10.0.0.1        invalid_name
+";
+
+    #[test]
+    fn test_hosts_file_parse() {
+        let hosts = HostsFile::parse(HOSTS);
+        assert_eq!(
+            hosts.ipv4_addresses(&Domain::from_static("localhost")),
+            Some(&[Ipv4Addr::LOCALHOST][..])
+        );
+        assert_eq!(
+            hosts.ipv6_addresses(&Domain::from_static("localhost")),
+            Some(&[Ipv6Addr::LOCALHOST][..])
+        );
+        assert_eq!(
+            hosts.ipv4_addresses(&Domain::from_static("FOO.internal")),
+            Some(
+                &[
+                    Ipv4Addr::new(192, 168, 1, 10),
+                    Ipv4Addr::new(192, 168, 1, 11)
+                ][..]
+            )
+        );
+        assert!(hosts.ipv4_addresses(&Domain::from_static("foo")).is_some());
+        assert!(hosts
+            .ipv6_addresses(&Domain::from_static("link.local"))
+            .is_none());
+        assert!(hosts
+            .ipv4_addresses(&Domain::from_static("bar.internal"))
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_hosts_dns_reload_on_change() {
+        let path = std::env::temp_dir().join(format!(
+            "rama-dns-hosts-{}-reload-on-change",
+            std::process::id()
+        ));
+        tokio::fs::write(&path, "10.0.0.1 example.internal\n")
+            .await
+            .unwrap();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let shutdown = Shutdown::new(async move {
+            rx.await.unwrap();
+        });
+
+        let dns = HostsDns::load(&path).await.unwrap();
+        dns.reload_on_change(shutdown.guard(), &path, Duration::from_millis(10));
+        assert_eq!(
+            dns.ipv4_lookup(Domain::from_static("example.internal"))
+                .await
+                .unwrap(),
+            vec![Ipv4Addr::new(10, 0, 0, 1)]
+        );
+        dns.ipv6_lookup(Domain::from_static("example.internal"))
+            .await
+            .unwrap_err();
+
+        let reloaded = dns.reloadable().reloaded();
+        tokio::fs::write(&path, "10.0.0.2 example.internal\n::2 example.internal\n")
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), reloaded)
+            .await
+            .unwrap();
+        assert_eq!(
+            dns.ipv4_lookup(Domain::from_static("example.internal"))
+                .await
+                .unwrap(),
+            vec![Ipv4Addr::new(10, 0, 0, 2)]
+        );
+        assert_eq!(
+            dns.ipv6_lookup(Domain::from_static("example.internal"))
+                .await
+                .unwrap(),
+            vec![Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 2)]
+        );
+
+        tokio::fs::remove_file(&path).await.unwrap();
+        tx.send(()).unwrap();
+        shutdown
+            .shutdown_with_limit(Duration::from_secs(1))
+            .await
+            .unwrap();
+    }
+}
@@ -1,10 +1,11 @@
-use crate::DnsResolver;
+use crate::{DnsLookup, DnsResolver, SrvName, SrvRecord, SvcbRecord, TxtRecord};
 use rama_net::address::Domain;
 use rama_utils::macros::{error::static_str_error, impl_deref};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
 };
 
 #[derive(Debug, Clone)]
@@ -17,6 +18,12 @@ pub struct DnsOverwrite(InMemoryDns);
 
 impl_deref! {DnsOverwrite: InMemoryDns}
 
+impl From<InMemoryDns> for DnsOverwrite {
+    fn from(dns: InMemoryDns) -> Self {
+        Self(dns)
+    }
+}
+
 impl<'de> Deserialize<'de> for DnsOverwrite {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -25,6 +32,8 @@ impl<'de> Deserialize<'de> for DnsOverwrite {
         let map = HashMap::<Domain, Vec<IpAddr>>::deserialize(deserializer)?;
         Ok(DnsOverwrite(InMemoryDns {
             map: (!map.is_empty()).then_some(map),
+            records: InMemoryRecords::default(),
+            ttl: None,
         }))
     }
 }
@@ -41,14 +50,52 @@ impl Serialize for DnsOverwrite {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 /// in-memory Dns that can be used as a simplistic cache,
 /// or wrapped in [`DnsOverwrite`] to indicate dns overwrites.
 pub struct InMemoryDns {
     map: Option<HashMap<Domain, Vec<IpAddr>>>,
+    records: InMemoryRecords,
+    ttl: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default)]
+/// The records, other than 'A' and 'AAAA' records, mapped by an [`InMemoryDns`].
+struct InMemoryRecords {
+    srv: HashMap<SrvName, Vec<SrvRecord>>,
+    txt: HashMap<Domain, Vec<TxtRecord>>,
+    cname: HashMap<Domain, Domain>,
+    https: HashMap<Domain, Vec<SvcbRecord>>,
 }
 
 impl InMemoryDns {
+    /// Create a new empty [`InMemoryDns`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the time-to-live reported for the mapped addresses,
+    /// e.g. to define how long they can be cached by a [`CachingDns`].
+    ///
+    /// No ttl is reported by default.
+    ///
+    /// [`CachingDns`]: crate::CachingDns
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Set the time-to-live reported for the mapped addresses,
+    /// e.g. to define how long they can be cached by a [`CachingDns`].
+    ///
+    /// No ttl is reported by default.
+    ///
+    /// [`CachingDns`]: crate::CachingDns
+    pub fn set_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
     /// Inserts a domain to IP address mapping to the [`InMemoryDns`].
     ///
     /// Existing mappings will be overwritten.
@@ -69,6 +116,53 @@ impl InMemoryDns {
         self.map.get_or_insert_with(HashMap::new).extend(overwrites);
         self
     }
+
+    /// Inserts the 'SRV' records of a service to the [`InMemoryDns`].
+    ///
+    /// Existing records for the service will be overwritten.
+    pub fn insert_srv(&mut self, name: SrvName, records: Vec<SrvRecord>) -> &mut Self {
+        self.records.srv.insert(name, records);
+        self
+    }
+
+    /// Inserts the 'TXT' records of a domain to the [`InMemoryDns`].
+    ///
+    /// Existing records for the domain will be overwritten.
+    pub fn insert_txt(&mut self, name: Domain, records: Vec<TxtRecord>) -> &mut Self {
+        self.records.txt.insert(name, records);
+        self
+    }
+
+    /// Inserts a 'CNAME' record, aliasing a domain to its canonical domain, to the [`InMemoryDns`].
+    ///
+    /// An existing alias for the domain will be overwritten.
+    pub fn insert_cname(&mut self, name: Domain, target: Domain) -> &mut Self {
+        self.records.cname.insert(name, target);
+        self
+    }
+
+    /// Inserts the 'HTTPS' records of a domain to the [`InMemoryDns`].
+    ///
+    /// Existing records for the domain will be overwritten.
+    pub fn insert_https(&mut self, name: Domain, records: Vec<SvcbRecord>) -> &mut Self {
+        self.records.https.insert(name, records);
+        self
+    }
+
+    /// Returns `true` in case any record is mapped for the given [`Domain`].
+    pub(crate) fn contains_domain(&self, domain: &Domain) -> bool {
+        self.map
+            .as_ref()
+            .is_some_and(|map| map.contains_key(domain))
+            || self.records.txt.contains_key(domain)
+            || self.records.cname.contains_key(domain)
+            || self.records.https.contains_key(domain)
+    }
+
+    /// Returns `true` in case 'SRV' records are mapped for the given [`SrvName`].
+    pub(crate) fn contains_srv_name(&self, name: &SrvName) -> bool {
+        self.records.srv.contains_key(name)
+    }
 }
 
 static_str_error! {
@@ -112,6 +206,54 @@ impl DnsResolver for InMemoryDns {
             })
             .ok_or(DomainNotMappedErr)
     }
+
+    async fn ipv4_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv4Addr>, Self::Error> {
+        let addresses = self.ipv4_lookup(domain).await?;
+        Ok(DnsLookup::new(addresses).maybe_with_ttl(self.ttl))
+    }
+
+    async fn ipv6_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv6Addr>, Self::Error> {
+        let addresses = self.ipv6_lookup(domain).await?;
+        Ok(DnsLookup::new(addresses).maybe_with_ttl(self.ttl))
+    }
+
+    async fn srv_lookup(&self, name: SrvName) -> Result<Vec<SrvRecord>, Self::Error> {
+        self.records
+            .srv
+            .get(&name)
+            .cloned()
+            .ok_or(DomainNotMappedErr)
+    }
+
+    async fn txt_lookup(&self, domain: Domain) -> Result<Vec<TxtRecord>, Self::Error> {
+        self.records
+            .txt
+            .get(&domain)
+            .cloned()
+            .ok_or(DomainNotMappedErr)
+    }
+
+    async fn cname_lookup(&self, domain: Domain) -> Result<Vec<Domain>, Self::Error> {
+        self.records
+            .cname
+            .get(&domain)
+            .map(|target| vec![target.clone()])
+            .ok_or(DomainNotMappedErr)
+    }
+
+    async fn https_lookup(&self, domain: Domain) -> Result<Vec<SvcbRecord>, Self::Error> {
+        self.records
+            .https
+            .get(&domain)
+            .cloned()
+            .ok_or(DomainNotMappedErr)
+    }
 }
 
 #[cfg(test)]
@@ -217,6 +359,47 @@ mod tests {
             .is_err());
     }
 
+    #[tokio::test]
+    async fn test_in_memory_dns_records() {
+        let domain = Domain::from_static("example.com");
+        let srv_name = SrvName::tcp("http", domain.clone()).unwrap();
+
+        let mut dns = InMemoryDns::new();
+        dns.insert_srv(
+            srv_name.clone(),
+            vec![SrvRecord::new(
+                10,
+                5,
+                8080,
+                Domain::from_static("a.example.com"),
+            )],
+        )
+        .insert_txt(domain.clone(), vec!["v=spf1 -all".into()])
+        .insert_cname(Domain::from_static("www.example.com"), domain.clone())
+        .insert_https(
+            domain.clone(),
+            vec![SvcbRecord::new(1, None).with_alpn(vec!["h2".to_owned()])],
+        );
+
+        let srv = dns.srv_lookup(srv_name).await.unwrap();
+        assert_eq!(srv[0].port(), 8080);
+        assert_eq!(srv[0].target(), "a.example.com");
+
+        let txt = dns.txt_lookup(domain.clone()).await.unwrap();
+        assert_eq!(txt[0].to_string_lossy(), "v=spf1 -all");
+
+        let cname = dns
+            .cname_lookup(Domain::from_static("www.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(cname, vec![domain.clone()]);
+        assert!(dns.cname_lookup(domain.clone()).await.is_err());
+
+        let https = dns.https_lookup(domain).await.unwrap();
+        assert_eq!(https[0].alpn(), &["h2".to_owned()]);
+        assert!(!https[0].is_alias());
+    }
+
     #[tokio::test]
     async fn test_dns_overwrite_deserialize_empty() {
         let dns_overwrite: DnsOverwrite = serde_html_form::from_str("").unwrap();
@@ -61,7 +61,7 @@ use std::{
     sync::Arc,
 };
 
-/// A resolver of domains into IP addresses.
+/// A resolver of domains into IP addresses and other dns records.
 pub trait DnsResolver: Send + Sync + 'static {
     /// Error returned by the [`DnsResolver`]
     type Error;
@@ -77,6 +77,80 @@ pub trait DnsResolver: Send + Sync + 'static {
         &self,
         domain: Domain,
     ) -> impl Future<Output = Result<Vec<Ipv6Addr>, Self::Error>> + Send + '_;
+
+    /// Resolve the 'A' records accessible by this resolver for the given [`Domain`],
+    /// together with the time-to-live of the records.
+    ///
+    /// An empty [`DnsLookup`] indicates that the domain has no such records,
+    /// in which case the ttl is the time-to-live of this negative answer.
+    ///
+    /// The default implementation uses [`DnsResolver::ipv4_lookup`], without a ttl.
+    fn ipv4_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<DnsLookup<Ipv4Addr>, Self::Error>> + Send + '_ {
+        async move { self.ipv4_lookup(domain).await.map(DnsLookup::new) }
+    }
+
+    /// Resolve the 'AAAA' records accessible by this resolver for the given [`Domain`],
+    /// together with the time-to-live of the records.
+    ///
+    /// An empty [`DnsLookup`] indicates that the domain has no such records,
+    /// in which case the ttl is the time-to-live of this negative answer.
+    ///
+    /// The default implementation uses [`DnsResolver::ipv6_lookup`], without a ttl.
+    fn ipv6_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<DnsLookup<Ipv6Addr>, Self::Error>> + Send + '_ {
+        async move { self.ipv6_lookup(domain).await.map(DnsLookup::new) }
+    }
+
+    /// Resolve the 'SRV' records accessible by this resolver for the given [`SrvName`].
+    ///
+    /// The default implementation returns no records.
+    fn srv_lookup(
+        &self,
+        name: SrvName,
+    ) -> impl Future<Output = Result<Vec<SrvRecord>, Self::Error>> + Send + '_ {
+        let _ = name;
+        async { Ok(Vec::new()) }
+    }
+
+    /// Resolve the 'TXT' records accessible by this resolver for the given [`Domain`].
+    ///
+    /// The default implementation returns no records.
+    fn txt_lookup(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<Vec<TxtRecord>, Self::Error>> + Send + '_ {
+        let _ = domain;
+        async { Ok(Vec::new()) }
+    }
+
+    /// Resolve the 'CNAME' records accessible by this resolver for the given [`Domain`]
+    /// into the canonical [`Domain`]s it is an alias of.
+    ///
+    /// The default implementation returns no records.
+    fn cname_lookup(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<Vec<Domain>, Self::Error>> + Send + '_ {
+        let _ = domain;
+        async { Ok(Vec::new()) }
+    }
+
+    /// Resolve the 'HTTPS' records accessible by this resolver for the given [`Domain`],
+    /// e.g. to discover the ALPN protocols and ECH configs of its endpoints.
+    ///
+    /// The default implementation returns no records.
+    fn https_lookup(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<Vec<SvcbRecord>, Self::Error>> + Send + '_ {
+        let _ = domain;
+        async { Ok(Vec::new()) }
+    }
 }
 
 impl<R: DnsResolver> DnsResolver for Arc<R> {
@@ -95,6 +169,48 @@ impl<R: DnsResolver> DnsResolver for Arc<R> {
     ) -> impl Future<Output = Result<Vec<Ipv6Addr>, Self::Error>> + Send + '_ {
         (**self).ipv6_lookup(domain)
     }
+
+    fn ipv4_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<DnsLookup<Ipv4Addr>, Self::Error>> + Send + '_ {
+        (**self).ipv4_lookup_with_ttl(domain)
+    }
+
+    fn ipv6_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<DnsLookup<Ipv6Addr>, Self::Error>> + Send + '_ {
+        (**self).ipv6_lookup_with_ttl(domain)
+    }
+
+    fn srv_lookup(
+        &self,
+        name: SrvName,
+    ) -> impl Future<Output = Result<Vec<SrvRecord>, Self::Error>> + Send + '_ {
+        (**self).srv_lookup(name)
+    }
+
+    fn txt_lookup(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<Vec<TxtRecord>, Self::Error>> + Send + '_ {
+        (**self).txt_lookup(domain)
+    }
+
+    fn cname_lookup(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<Vec<Domain>, Self::Error>> + Send + '_ {
+        (**self).cname_lookup(domain)
+    }
+
+    fn https_lookup(
+        &self,
+        domain: Domain,
+    ) -> impl Future<Output = Result<Vec<SvcbRecord>, Self::Error>> + Send + '_ {
+        (**self).https_lookup(domain)
+    }
 }
 
 impl<R: DnsResolver<Error: Into<BoxError>>> DnsResolver for Option<R> {
@@ -113,6 +229,54 @@ impl<R: DnsResolver<Error: Into<BoxError>>> DnsResolver for Option<R> {
             None => Err(DomainNotMappedErr.into()),
         }
     }
+
+    async fn ipv4_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv4Addr>, Self::Error> {
+        match self {
+            Some(d) => d.ipv4_lookup_with_ttl(domain).await.map_err(Into::into),
+            None => Err(DomainNotMappedErr.into()),
+        }
+    }
+
+    async fn ipv6_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv6Addr>, Self::Error> {
+        match self {
+            Some(d) => d.ipv6_lookup_with_ttl(domain).await.map_err(Into::into),
+            None => Err(DomainNotMappedErr.into()),
+        }
+    }
+
+    async fn srv_lookup(&self, name: SrvName) -> Result<Vec<SrvRecord>, Self::Error> {
+        match self {
+            Some(d) => d.srv_lookup(name).await.map_err(Into::into),
+            None => Err(DomainNotMappedErr.into()),
+        }
+    }
+
+    async fn txt_lookup(&self, domain: Domain) -> Result<Vec<TxtRecord>, Self::Error> {
+        match self {
+            Some(d) => d.txt_lookup(domain).await.map_err(Into::into),
+            None => Err(DomainNotMappedErr.into()),
+        }
+    }
+
+    async fn cname_lookup(&self, domain: Domain) -> Result<Vec<Domain>, Self::Error> {
+        match self {
+            Some(d) => d.cname_lookup(domain).await.map_err(Into::into),
+            None => Err(DomainNotMappedErr.into()),
+        }
+    }
+
+    async fn https_lookup(&self, domain: Domain) -> Result<Vec<SvcbRecord>, Self::Error> {
+        match self {
+            Some(d) => d.https_lookup(domain).await.map_err(Into::into),
+            None => Err(DomainNotMappedErr.into()),
+        }
+    }
 }
 
 macro_rules! impl_dns_resolver_either_either {
@@ -148,12 +312,102 @@ macro_rules! impl_dns_resolver_either_either {
                     )+
                 }
             }
+
+            async fn ipv4_lookup_with_ttl(
+                &self,
+                domain: Domain,
+            ) -> Result<DnsLookup<Ipv4Addr>, Self::Error>{
+                match self {
+                    $(
+                        ::rama_core::combinators::$id::$param(d) => d.ipv4_lookup_with_ttl(domain)
+                            .await
+                            .map_err(Into::into),
+                    )+
+                }
+            }
+
+            async fn ipv6_lookup_with_ttl(
+                &self,
+                domain: Domain,
+            ) -> Result<DnsLookup<Ipv6Addr>, Self::Error> {
+                match self {
+                    $(
+                        ::rama_core::combinators::$id::$param(d) => d.ipv6_lookup_with_ttl(domain)
+                            .await
+                            .map_err(Into::into),
+                    )+
+                }
+            }
+
+            async fn srv_lookup(
+                &self,
+                name: SrvName,
+            ) -> Result<Vec<SrvRecord>, Self::Error> {
+                match self {
+                    $(
+                        ::rama_core::combinators::$id::$param(d) => d.srv_lookup(name)
+                            .await
+                            .map_err(Into::into),
+                    )+
+                }
+            }
+
+            async fn txt_lookup(
+                &self,
+                domain: Domain,
+            ) -> Result<Vec<TxtRecord>, Self::Error> {
+                match self {
+                    $(
+                        ::rama_core::combinators::$id::$param(d) => d.txt_lookup(domain)
+                            .await
+                            .map_err(Into::into),
+                    )+
+                }
+            }
+
+            async fn cname_lookup(
+                &self,
+                domain: Domain,
+            ) -> Result<Vec<Domain>, Self::Error> {
+                match self {
+                    $(
+                        ::rama_core::combinators::$id::$param(d) => d.cname_lookup(domain)
+                            .await
+                            .map_err(Into::into),
+                    )+
+                }
+            }
+
+            async fn https_lookup(
+                &self,
+                domain: Domain,
+            ) -> Result<Vec<SvcbRecord>, Self::Error> {
+                match self {
+                    $(
+                        ::rama_core::combinators::$id::$param(d) => d.https_lookup(domain)
+                            .await
+                            .map_err(Into::into),
+                    )+
+                }
+            }
         }
     };
 }
 
 rama_core::combinators::impl_either!(impl_dns_resolver_either_either);
 
+mod lookup;
+#[doc(inline)]
+pub use lookup::DnsLookup;
+
+mod record;
+#[doc(inline)]
+pub use record::{SrvName, SrvRecord, SrvServiceUnavailableErr, SvcbRecord, TxtRecord};
+
+pub mod cache;
+#[doc(inline)]
+pub use cache::CachingDns;
+
 pub mod hickory;
 #[doc(inline)]
 pub use hickory::HickoryDns;
@@ -161,3 +415,27 @@ pub use hickory::HickoryDns;
 mod in_memory;
 #[doc(inline)]
 pub use in_memory::{DnsOverwrite, DomainNotMappedErr, InMemoryDns};
+
+mod wire;
+
+mod doh;
+#[doc(inline)]
+pub use doh::{DohMethod, DohResolver};
+
+mod dot;
+#[doc(inline)]
+pub use dot::DotResolver;
+
+mod hosts;
+#[doc(inline)]
+pub use hosts::{HostsDns, HostsFile};
+
+mod chain;
+#[doc(inline)]
+pub use chain::{DnsChain, DnsChainStrategy};
+
+mod router;
+#[doc(inline)]
+pub use router::DnsRouter;
+
+pub mod server;
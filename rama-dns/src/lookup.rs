@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The addresses resolved by a [`DnsResolver`], together with their time-to-live.
+///
+/// [`DnsResolver`]: crate::DnsResolver
+pub struct DnsLookup<T> {
+    addresses: Vec<T>,
+    ttl: Option<Duration>,
+}
+
+impl<T> DnsLookup<T> {
+    /// Create a new [`DnsLookup`] for the given addresses, without a ttl.
+    pub const fn new(addresses: Vec<T>) -> Self {
+        Self {
+            addresses,
+            ttl: None,
+        }
+    }
+
+    /// Create a new empty [`DnsLookup`], used to indicate a negative answer.
+    pub const fn empty() -> Self {
+        Self::new(Vec::new())
+    }
+
+    /// Attach the time-to-live to this [`DnsLookup`].
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Attach an optional time-to-live to this [`DnsLookup`].
+    pub fn maybe_with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set the time-to-live of this [`DnsLookup`].
+    pub fn set_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// The resolved addresses.
+    pub fn addresses(&self) -> &[T] {
+        &self.addresses
+    }
+
+    /// Consume the [`DnsLookup`] into the resolved addresses.
+    pub fn into_addresses(self) -> Vec<T> {
+        self.addresses
+    }
+
+    /// Returns `true` in case no addresses were resolved.
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    /// The time-to-live of the (negative) answer, if known.
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+}
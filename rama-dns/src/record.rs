@@ -0,0 +1,322 @@
+use rama_core::error::OpaqueError;
+use rama_net::address::Domain;
+use rama_utils::macros::error::static_str_error;
+use std::{
+    borrow::Cow,
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// The name of a service, as looked up using 'SRV' records,
+/// in the form of `_service._protocol.domain`.
+pub struct SrvName {
+    service: Cow<'static, str>,
+    protocol: Cow<'static, str>,
+    domain: Domain,
+}
+
+impl SrvName {
+    /// Create a new [`SrvName`] for the given service and protocol labels,
+    /// with or without their leading underscore, offered by the given [`Domain`].
+    pub fn new(
+        service: impl Into<Cow<'static, str>>,
+        protocol: impl Into<Cow<'static, str>>,
+        domain: Domain,
+    ) -> Result<Self, OpaqueError> {
+        Ok(Self {
+            service: srv_label(service.into())?,
+            protocol: srv_label(protocol.into())?,
+            domain,
+        })
+    }
+
+    /// Create a new [`SrvName`] for the given service offered over tcp by the given [`Domain`].
+    pub fn tcp(service: impl Into<Cow<'static, str>>, domain: Domain) -> Result<Self, OpaqueError> {
+        Self::new(service, "tcp", domain)
+    }
+
+    /// Create a new [`SrvName`] for the given service offered over udp by the given [`Domain`].
+    pub fn udp(service: impl Into<Cow<'static, str>>, domain: Domain) -> Result<Self, OpaqueError> {
+        Self::new(service, "udp", domain)
+    }
+
+    /// The service label, without its leading underscore.
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    /// The protocol label, without its leading underscore.
+    pub fn protocol(&self) -> &str {
+        &self.protocol
+    }
+
+    /// The [`Domain`] offering the service.
+    pub fn domain(&self) -> &Domain {
+        &self.domain
+    }
+}
+
+impl fmt::Display for SrvName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "_{}._{}.{}", self.service, self.protocol, self.domain)
+    }
+}
+
+fn srv_label(label: Cow<'static, str>) -> Result<Cow<'static, str>, OpaqueError> {
+    let stripped = label.strip_prefix('_').unwrap_or(&label);
+    if stripped.is_empty()
+        || stripped.len() > 62
+        || stripped.starts_with('-')
+        || stripped.ends_with('-')
+        || !stripped
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || c == b'-')
+    {
+        return Err(OpaqueError::from_display(format!(
+            "invalid srv label: {label}"
+        )));
+    }
+    Ok(Cow::Owned(stripped.to_ascii_lowercase()))
+}
+
+static_str_error! {
+    #[doc = "service decidedly not available at the domain (srv target '.')"]
+    pub struct SrvServiceUnavailableErr;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A 'SRV' record, locating a target offering a service.
+pub struct SrvRecord {
+    priority: u16,
+    weight: u16,
+    port: u16,
+    target: Domain,
+}
+
+impl SrvRecord {
+    /// Create a new [`SrvRecord`].
+    pub fn new(priority: u16, weight: u16, port: u16, target: Domain) -> Self {
+        Self {
+            priority,
+            weight,
+            port,
+            target,
+        }
+    }
+
+    /// The priority of the target, targets with a lower priority are to be tried first.
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    /// The relative weight of the target, among the targets of the same priority.
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    /// The port on which the service is offered by the target.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The [`Domain`] of the target.
+    pub fn target(&self) -> &Domain {
+        &self.target
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A 'TXT' record, made of one or more character strings.
+pub struct TxtRecord {
+    data: Vec<Vec<u8>>,
+}
+
+impl TxtRecord {
+    /// Create a new [`TxtRecord`] from the given character strings.
+    pub fn new(data: Vec<Vec<u8>>) -> Self {
+        Self { data }
+    }
+
+    /// The character strings of the record.
+    pub fn data(&self) -> &[Vec<u8>] {
+        &self.data
+    }
+
+    /// The concatenated character strings of the record,
+    /// replacing invalid utf-8 sequences with the replacement character.
+    pub fn to_string_lossy(&self) -> String {
+        self.data
+            .iter()
+            .map(|data| String::from_utf8_lossy(data))
+            .collect()
+    }
+}
+
+impl From<String> for TxtRecord {
+    fn from(value: String) -> Self {
+        Self::new(vec![value.into_bytes()])
+    }
+}
+
+impl From<&str> for TxtRecord {
+    fn from(value: &str) -> Self {
+        Self::new(vec![value.as_bytes().to_vec()])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A 'SVCB' or 'HTTPS' record, describing an endpoint of a service
+/// and the parameters to connect to it.
+pub struct SvcbRecord {
+    priority: u16,
+    target: Option<Domain>,
+    alpn: Vec<String>,
+    port: Option<u16>,
+    ipv4_hints: Vec<Ipv4Addr>,
+    ipv6_hints: Vec<Ipv6Addr>,
+    ech_config: Option<Vec<u8>>,
+}
+
+impl SvcbRecord {
+    /// Create a new [`SvcbRecord`], without any parameters.
+    ///
+    /// A target of `None` refers to the owner name of the record.
+    pub fn new(priority: u16, target: Option<Domain>) -> Self {
+        Self {
+            priority,
+            target,
+            alpn: Vec::new(),
+            port: None,
+            ipv4_hints: Vec::new(),
+            ipv6_hints: Vec::new(),
+            ech_config: None,
+        }
+    }
+
+    /// The priority of the endpoint, endpoints with a lower priority are preferred.
+    ///
+    /// A priority of `0` indicates an alias to the target.
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    /// Returns `true` in case the record is an alias to the target (AliasMode),
+    /// rather than describing an endpoint (ServiceMode).
+    pub fn is_alias(&self) -> bool {
+        self.priority == 0
+    }
+
+    /// The [`Domain`] of the target, `None` in case it is the owner name of the record.
+    pub fn target(&self) -> Option<&Domain> {
+        self.target.as_ref()
+    }
+
+    /// The ALPN protocol identifiers supported by the endpoint (e.g. `h2`).
+    pub fn alpn(&self) -> &[String] {
+        &self.alpn
+    }
+
+    /// Set the ALPN protocol identifiers supported by the endpoint.
+    pub fn with_alpn(mut self, alpn: Vec<String>) -> Self {
+        self.alpn = alpn;
+        self
+    }
+
+    /// Set the ALPN protocol identifiers supported by the endpoint.
+    pub fn set_alpn(&mut self, alpn: Vec<String>) -> &mut Self {
+        self.alpn = alpn;
+        self
+    }
+
+    /// The port of the endpoint, if it differs from the default port.
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// Set the port of the endpoint.
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Set the port of the endpoint.
+    pub fn set_port(&mut self, port: u16) -> &mut Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// The IPv4 addresses which can be used to reach the endpoint.
+    pub fn ipv4_hints(&self) -> &[Ipv4Addr] {
+        &self.ipv4_hints
+    }
+
+    /// Set the IPv4 addresses which can be used to reach the endpoint.
+    pub fn with_ipv4_hints(mut self, hints: Vec<Ipv4Addr>) -> Self {
+        self.ipv4_hints = hints;
+        self
+    }
+
+    /// Set the IPv4 addresses which can be used to reach the endpoint.
+    pub fn set_ipv4_hints(&mut self, hints: Vec<Ipv4Addr>) -> &mut Self {
+        self.ipv4_hints = hints;
+        self
+    }
+
+    /// The IPv6 addresses which can be used to reach the endpoint.
+    pub fn ipv6_hints(&self) -> &[Ipv6Addr] {
+        &self.ipv6_hints
+    }
+
+    /// Set the IPv6 addresses which can be used to reach the endpoint.
+    pub fn with_ipv6_hints(mut self, hints: Vec<Ipv6Addr>) -> Self {
+        self.ipv6_hints = hints;
+        self
+    }
+
+    /// Set the IPv6 addresses which can be used to reach the endpoint.
+    pub fn set_ipv6_hints(&mut self, hints: Vec<Ipv6Addr>) -> &mut Self {
+        self.ipv6_hints = hints;
+        self
+    }
+
+    /// The encoded Encrypted ClientHello (ECH) config list of the endpoint, if any.
+    pub fn ech_config(&self) -> Option<&[u8]> {
+        self.ech_config.as_deref()
+    }
+
+    /// Set the encoded Encrypted ClientHello (ECH) config list of the endpoint.
+    pub fn with_ech_config(mut self, ech_config: Vec<u8>) -> Self {
+        self.ech_config = Some(ech_config);
+        self
+    }
+
+    /// Set the encoded Encrypted ClientHello (ECH) config list of the endpoint.
+    pub fn set_ech_config(&mut self, ech_config: Vec<u8>) -> &mut Self {
+        self.ech_config = Some(ech_config);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srv_name() {
+        let name = SrvName::tcp("_XMPP-client", Domain::example()).unwrap();
+        assert_eq!(name.service(), "xmpp-client");
+        assert_eq!(name.protocol(), "tcp");
+        assert_eq!(name.to_string(), "_xmpp-client._tcp.example.com");
+
+        for label in ["", "_", "-http", "http-", "ht_tp", "ht.tp"] {
+            assert!(SrvName::udp(label, Domain::example()).is_err(), "{label}");
+        }
+    }
+
+    #[test]
+    fn test_txt_record_to_string_lossy() {
+        let record = TxtRecord::new(vec![b"v=spf1 ".to_vec(), b"-all".to_vec()]);
+        assert_eq!(record.to_string_lossy(), "v=spf1 -all");
+    }
+}
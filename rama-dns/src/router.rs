@@ -0,0 +1,201 @@
+use crate::{DnsLookup, DnsResolver, SrvName, SrvRecord, SvcbRecord, TxtRecord};
+use rama_core::error::BoxError;
+use rama_net::address::Domain;
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+/// A [`DnsResolver`] which routes the lookups of a domain
+/// to the resolver registered for a suffix of that domain,
+/// e.g. all subdomains of `internal` to a [`HostsDns`],
+/// using the default resolver, such as [`HickoryDns`], for all other domains.
+///
+/// In case multiple suffixes match, the most specific (longest) one is used.
+/// A suffix matches the domain itself as well as all of its subdomains.
+/// SRV lookups are routed based on the domain of the [`SrvName`].
+///
+/// Resolvers of different types can be routed to using
+/// the `Either` combinators of `rama-core`, which are [`DnsResolver`]s as well.
+///
+/// [`HostsDns`]: crate::HostsDns
+/// [`HickoryDns`]: crate::HickoryDns
+pub struct DnsRouter<R, D> {
+    routes: Vec<(Domain, R)>,
+    default: D,
+}
+
+impl<R: fmt::Debug, D: fmt::Debug> fmt::Debug for DnsRouter<R, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DnsRouter")
+            .field("routes", &self.routes)
+            .field("default", &self.default)
+            .finish()
+    }
+}
+
+impl<R: Clone, D: Clone> Clone for DnsRouter<R, D> {
+    fn clone(&self) -> Self {
+        Self {
+            routes: self.routes.clone(),
+            default: self.default.clone(),
+        }
+    }
+}
+
+impl<R, D> DnsRouter<R, D> {
+    /// Create a new [`DnsRouter`] without any routes,
+    /// resolving all domains using the given default resolver.
+    pub fn new(default: D) -> Self {
+        Self {
+            routes: Vec::new(),
+            default,
+        }
+    }
+
+    /// Route the lookups of the given domain suffix, and all of its subdomains,
+    /// to the given resolver.
+    pub fn with_route(mut self, suffix: Domain, resolver: R) -> Self {
+        self.routes.push((suffix, resolver));
+        self
+    }
+
+    /// Route the lookups of the given domain suffix, and all of its subdomains,
+    /// to the given resolver.
+    pub fn set_route(&mut self, suffix: Domain, resolver: R) -> &mut Self {
+        self.routes.push((suffix, resolver));
+        self
+    }
+
+    /// The resolver used for the domains which do not match any route.
+    pub fn default_resolver(&self) -> &D {
+        &self.default
+    }
+
+    fn route(&self, domain: &Domain) -> Option<&R> {
+        self.routes
+            .iter()
+            .filter(|(suffix, _)| domain.is_sub_of(suffix))
+            .max_by_key(|(suffix, _)| suffix.as_str().trim_matches('.').len())
+            .map(|(_, resolver)| resolver)
+    }
+}
+
+impl<R, D> DnsResolver for DnsRouter<R, D>
+where
+    R: DnsResolver<Error: Into<BoxError>>,
+    D: DnsResolver<Error: Into<BoxError>>,
+{
+    type Error = BoxError;
+
+    async fn ipv4_lookup(&self, domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+        match self.route(&domain) {
+            Some(r) => r.ipv4_lookup(domain).await.map_err(Into::into),
+            None => self.default.ipv4_lookup(domain).await.map_err(Into::into),
+        }
+    }
+
+    async fn ipv6_lookup(&self, domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+        match self.route(&domain) {
+            Some(r) => r.ipv6_lookup(domain).await.map_err(Into::into),
+            None => self.default.ipv6_lookup(domain).await.map_err(Into::into),
+        }
+    }
+
+    async fn ipv4_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv4Addr>, Self::Error> {
+        match self.route(&domain) {
+            Some(r) => r.ipv4_lookup_with_ttl(domain).await.map_err(Into::into),
+            None => self
+                .default
+                .ipv4_lookup_with_ttl(domain)
+                .await
+                .map_err(Into::into),
+        }
+    }
+
+    async fn ipv6_lookup_with_ttl(
+        &self,
+        domain: Domain,
+    ) -> Result<DnsLookup<Ipv6Addr>, Self::Error> {
+        match self.route(&domain) {
+            Some(r) => r.ipv6_lookup_with_ttl(domain).await.map_err(Into::into),
+            None => self
+                .default
+                .ipv6_lookup_with_ttl(domain)
+                .await
+                .map_err(Into::into),
+        }
+    }
+
+    async fn srv_lookup(&self, name: SrvName) -> Result<Vec<SrvRecord>, Self::Error> {
+        match self.route(name.domain()) {
+            Some(r) => r.srv_lookup(name).await.map_err(Into::into),
+            None => self.default.srv_lookup(name).await.map_err(Into::into),
+        }
+    }
+
+    async fn txt_lookup(&self, domain: Domain) -> Result<Vec<TxtRecord>, Self::Error> {
+        match self.route(&domain) {
+            Some(r) => r.txt_lookup(domain).await.map_err(Into::into),
+            None => self.default.txt_lookup(domain).await.map_err(Into::into),
+        }
+    }
+
+    async fn cname_lookup(&self, domain: Domain) -> Result<Vec<Domain>, Self::Error> {
+        match self.route(&domain) {
+            Some(r) => r.cname_lookup(domain).await.map_err(Into::into),
+            None => self.default.cname_lookup(domain).await.map_err(Into::into),
+        }
+    }
+
+    async fn https_lookup(&self, domain: Domain) -> Result<Vec<SvcbRecord>, Self::Error> {
+        match self.route(&domain) {
+            Some(r) => r.https_lookup(domain).await.map_err(Into::into),
+            None => self.default.https_lookup(domain).await.map_err(Into::into),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InMemoryDns;
+
+    fn dns(ip: Ipv4Addr) -> InMemoryDns {
+        let mut dns = InMemoryDns::new();
+        for domain in ["example.internal", "api.corp.internal", "example.com"] {
+            dns.insert(Domain::from_static(domain), vec![ip.into()]);
+        }
+        dns
+    }
+
+    #[tokio::test]
+    async fn test_dns_router() {
+        let router = DnsRouter::new(dns(Ipv4Addr::new(10, 0, 0, 1)))
+            .with_route(Domain::tld_private(), dns(Ipv4Addr::new(10, 0, 0, 2)))
+            .with_route(
+                Domain::from_static("corp.internal"),
+                dns(Ipv4Addr::new(10, 0, 0, 3)),
+            );
+
+        for (domain, ip) in [
+            ("example.com", Ipv4Addr::new(10, 0, 0, 1)),
+            ("example.internal", Ipv4Addr::new(10, 0, 0, 2)),
+            ("api.corp.internal", Ipv4Addr::new(10, 0, 0, 3)),
+        ] {
+            let ips = router
+                .ipv4_lookup(Domain::from_static(domain))
+                .await
+                .unwrap();
+            assert_eq!(ips, vec![ip], "domain: {domain}");
+        }
+
+        router
+            .ipv4_lookup(Domain::from_static("unknown.internal"))
+            .await
+            .unwrap_err();
+    }
+}
@@ -0,0 +1,168 @@
+//! Conversion of the lookups of a [`DnsResolver`] into the answers of a dns query.
+
+use crate::{
+    hickory::{domain_from_name, fqdn_from_domain},
+    DnsResolver, SrvName, SvcbRecord,
+};
+use hickory_resolver::proto::{
+    op::Query,
+    rr::{
+        rdata::{
+            svcb::{Alpn, EchConfig, IpHint, SvcParamKey, SvcParamValue, SVCB},
+            A, AAAA, CNAME, HTTPS, SRV, TXT,
+        },
+        Name, RData, Record, RecordType,
+    },
+};
+use rama_net::address::Domain;
+use std::time::Duration;
+
+/// Resolve the answers to the given query using the given [`DnsResolver`].
+///
+/// Queries for an unsupported record type, or for a name which
+/// cannot be resolved by a [`DnsResolver`], are answered without records.
+pub(super) async fn lookup_answers<R: DnsResolver>(
+    resolver: &R,
+    query: &Query,
+    default_ttl: Duration,
+) -> Result<Vec<Record>, R::Error> {
+    let name = query.name();
+    let default_ttl = ttl_secs(default_ttl);
+
+    if query.query_type() == RecordType::SRV {
+        let Some(srv_name) = srv_name_from_name(name) else {
+            return Ok(Vec::new());
+        };
+        let records = resolver.srv_lookup(srv_name).await?;
+        return Ok(records
+            .into_iter()
+            .filter_map(|record| {
+                let target = fqdn_from_domain(record.target().clone()).ok()?;
+                let srv = SRV::new(record.priority(), record.weight(), record.port(), target);
+                Some(Record::from_rdata(
+                    name.clone(),
+                    default_ttl,
+                    RData::SRV(srv),
+                ))
+            })
+            .collect());
+    }
+
+    let Some(domain) = domain_from_name(name) else {
+        return Ok(Vec::new());
+    };
+
+    let answers = match query.query_type() {
+        RecordType::A => {
+            let lookup = resolver.ipv4_lookup_with_ttl(domain).await?;
+            let ttl = lookup.ttl().map(ttl_secs).unwrap_or(default_ttl);
+            lookup
+                .into_addresses()
+                .into_iter()
+                .map(|ip| Record::from_rdata(name.clone(), ttl, RData::A(A(ip))))
+                .collect()
+        }
+        RecordType::AAAA => {
+            let lookup = resolver.ipv6_lookup_with_ttl(domain).await?;
+            let ttl = lookup.ttl().map(ttl_secs).unwrap_or(default_ttl);
+            lookup
+                .into_addresses()
+                .into_iter()
+                .map(|ip| Record::from_rdata(name.clone(), ttl, RData::AAAA(AAAA(ip))))
+                .collect()
+        }
+        RecordType::CNAME => resolver
+            .cname_lookup(domain)
+            .await?
+            .into_iter()
+            .filter_map(|target| {
+                let target = fqdn_from_domain(target).ok()?;
+                Some(Record::from_rdata(
+                    name.clone(),
+                    default_ttl,
+                    RData::CNAME(CNAME(target)),
+                ))
+            })
+            .collect(),
+        RecordType::TXT => resolver
+            .txt_lookup(domain)
+            .await?
+            .into_iter()
+            .map(|record| {
+                let txt = TXT::from_bytes(record.data().iter().map(Vec::as_slice).collect());
+                Record::from_rdata(name.clone(), default_ttl, RData::TXT(txt))
+            })
+            .collect(),
+        RecordType::HTTPS => resolver
+            .https_lookup(domain)
+            .await?
+            .into_iter()
+            .filter_map(|record| {
+                let svcb = svcb_from_record(record)?;
+                Some(Record::from_rdata(
+                    name.clone(),
+                    default_ttl,
+                    RData::HTTPS(HTTPS(svcb)),
+                ))
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    Ok(answers)
+}
+
+fn ttl_secs(ttl: Duration) -> u32 {
+    ttl.as_secs().try_into().unwrap_or(u32::MAX)
+}
+
+/// Parse a name of the form `_service._protocol.domain` into a [`SrvName`].
+pub(super) fn srv_name_from_name(name: &Name) -> Option<SrvName> {
+    let mut labels = name.iter();
+    let service = std::str::from_utf8(labels.next()?).ok()?.to_owned();
+    let protocol = std::str::from_utf8(labels.next()?).ok()?.to_owned();
+    if !service.starts_with('_') || !protocol.starts_with('_') {
+        return None;
+    }
+    let domain: Domain = domain_from_name(&Name::from_labels(labels).ok()?)?;
+    SrvName::new(service, protocol, domain).ok()
+}
+
+fn svcb_from_record(record: SvcbRecord) -> Option<SVCB> {
+    let target = match record.target() {
+        Some(target) => fqdn_from_domain(target.clone()).ok()?,
+        None => Name::root(),
+    };
+
+    let mut params = Vec::new();
+    if !record.alpn().is_empty() {
+        params.push((
+            SvcParamKey::Alpn,
+            SvcParamValue::Alpn(Alpn(record.alpn().to_vec())),
+        ));
+    }
+    if let Some(port) = record.port() {
+        params.push((SvcParamKey::Port, SvcParamValue::Port(port)));
+    }
+    if !record.ipv4_hints().is_empty() {
+        params.push((
+            SvcParamKey::Ipv4Hint,
+            SvcParamValue::Ipv4Hint(IpHint(record.ipv4_hints().iter().copied().map(A).collect())),
+        ));
+    }
+    if let Some(ech_config) = record.ech_config() {
+        params.push((
+            SvcParamKey::EchConfig,
+            SvcParamValue::EchConfig(EchConfig(ech_config.to_vec())),
+        ));
+    }
+    if !record.ipv6_hints().is_empty() {
+        params.push((
+            SvcParamKey::Ipv6Hint,
+            SvcParamValue::Ipv6Hint(IpHint(
+                record.ipv6_hints().iter().copied().map(AAAA).collect(),
+            )),
+        ));
+    }
+
+    Some(SVCB::new(record.priority(), target, params))
+}
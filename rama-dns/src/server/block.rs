@@ -0,0 +1,211 @@
+use super::{DnsRequest, DnsResponse};
+use hickory_resolver::proto::op::ResponseCode;
+use rama_core::{error::BoxError, Context, Layer, Service};
+use rama_net::address::Domain;
+use rama_utils::macros::define_inner_service_accessors;
+use std::{collections::HashSet, fmt, sync::Arc};
+
+/// A [`Layer`] producing a [`BlockList`] service,
+/// blocking queries for domains (and their subdomains) on a block list.
+pub struct BlockListLayer {
+    blocked: Arc<HashSet<String>>,
+    response_code: ResponseCode,
+}
+
+impl fmt::Debug for BlockListLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockListLayer")
+            .field("blocked", &self.blocked)
+            .field("response_code", &self.response_code)
+            .finish()
+    }
+}
+
+impl Clone for BlockListLayer {
+    fn clone(&self) -> Self {
+        Self {
+            blocked: self.blocked.clone(),
+            response_code: self.response_code,
+        }
+    }
+}
+
+impl BlockListLayer {
+    /// Create a new [`BlockListLayer`], blocking queries
+    /// for the given domains as well as for all their subdomains.
+    pub fn new(blocked: impl IntoIterator<Item = Domain>) -> Self {
+        Self {
+            blocked: Arc::new(
+                blocked
+                    .into_iter()
+                    .map(|domain| normalize(domain.as_str()))
+                    .collect(),
+            ),
+            response_code: ResponseCode::NXDomain,
+        }
+    }
+
+    /// Set the [`ResponseCode`] used to answer blocked queries.
+    ///
+    /// Defaults to [`ResponseCode::NXDomain`].
+    pub fn with_response_code(mut self, response_code: ResponseCode) -> Self {
+        self.response_code = response_code;
+        self
+    }
+
+    /// Set the [`ResponseCode`] used to answer blocked queries.
+    ///
+    /// Defaults to [`ResponseCode::NXDomain`].
+    pub fn set_response_code(&mut self, response_code: ResponseCode) -> &mut Self {
+        self.response_code = response_code;
+        self
+    }
+}
+
+impl<S> Layer<S> for BlockListLayer {
+    type Service = BlockList<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BlockList {
+            inner,
+            blocked: self.blocked.clone(),
+            response_code: self.response_code,
+        }
+    }
+}
+
+/// A dns [`Service`] answering queries for blocked domains (and their subdomains)
+/// without calling the inner [`Service`].
+///
+/// Created using a [`BlockListLayer`].
+pub struct BlockList<S> {
+    inner: S,
+    blocked: Arc<HashSet<String>>,
+    response_code: ResponseCode,
+}
+
+impl<S> BlockList<S> {
+    define_inner_service_accessors!();
+
+    /// Returns `true` in case the given [`Domain`], or one of its parents, is blocked.
+    pub fn is_blocked(&self, domain: &Domain) -> bool {
+        let domain = normalize(domain.as_str());
+        let mut suffix = domain.as_str();
+        loop {
+            if self.blocked.contains(suffix) {
+                return true;
+            }
+            match suffix.split_once('.') {
+                Some((_, parent)) => suffix = parent,
+                None => return false,
+            }
+        }
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for BlockList<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockList")
+            .field("inner", &self.inner)
+            .field("blocked", &self.blocked)
+            .field("response_code", &self.response_code)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for BlockList<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            blocked: self.blocked.clone(),
+            response_code: self.response_code,
+        }
+    }
+}
+
+impl<S, State> Service<State, DnsRequest> for BlockList<S>
+where
+    S: Service<State, DnsRequest, Response = DnsResponse, Error: Into<BoxError>>,
+    State: Send + Sync + 'static,
+{
+    type Response = DnsResponse;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        req: DnsRequest,
+    ) -> Result<Self::Response, Self::Error> {
+        if let Some(domain) = req.domain() {
+            if self.is_blocked(&domain) {
+                tracing::trace!(%domain, "dns query blocked by block list");
+                return Ok(DnsResponse::error(&req, self.response_code));
+            }
+        }
+        self.inner.serve(ctx, req).await.map_err(Into::into)
+    }
+}
+
+fn normalize(domain: &str) -> String {
+    domain.trim_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        server::{DnsTransport, InMemoryDnsService},
+        InMemoryDns,
+    };
+    use hickory_resolver::proto::{
+        op::{Message, Query},
+        rr::{Name, RecordType},
+    };
+
+    fn request(name: &str) -> DnsRequest {
+        let mut message = Message::new();
+        message.add_query(Query::query(Name::from_ascii(name).unwrap(), RecordType::A));
+        DnsRequest::new(message, DnsTransport::Udp)
+    }
+
+    #[tokio::test]
+    async fn test_block_list() {
+        let mut dns = InMemoryDns::new();
+        for domain in [
+            "example.com",
+            "ads.example.com",
+            "tracker.ads.example.com",
+            "badads.example.com",
+        ] {
+            dns.insert(
+                Domain::try_from(domain.to_owned()).unwrap(),
+                vec![[10, 0, 0, 1].into()],
+            );
+        }
+        let service = BlockListLayer::new([Domain::from_static("ADS.example.com")])
+            .layer(InMemoryDnsService::new(dns));
+
+        for (name, expected) in [
+            ("example.com.", ResponseCode::NoError),
+            ("ads.example.com.", ResponseCode::NXDomain),
+            ("tracker.ads.example.com.", ResponseCode::NXDomain),
+            ("badads.example.com.", ResponseCode::NoError),
+        ] {
+            let response = service
+                .serve(Context::default(), request(name))
+                .await
+                .unwrap();
+            assert_eq!(response.message().response_code(), expected, "{name}");
+        }
+
+        let service = BlockListLayer::new([Domain::from_static("example.com")])
+            .with_response_code(ResponseCode::Refused)
+            .layer(InMemoryDnsService::new(InMemoryDns::new()));
+        let response = service
+            .serve(Context::default(), request("www.example.com."))
+            .await
+            .unwrap();
+        assert_eq!(response.message().response_code(), ResponseCode::Refused);
+        assert!(!service.is_blocked(&Domain::from_static("example.org")));
+    }
+}
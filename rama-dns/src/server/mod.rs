@@ -0,0 +1,804 @@
+//! DNS server support, serving dns queries using a rama [`Service`].
+//!
+//! A [`DnsServer`] accepts queries over UDP and TCP, decodes them into a [`DnsRequest`],
+//! and serves them using a [`Service`] which responds with a [`DnsResponse`].
+//!
+//! The following services and layers are provided out of the box:
+//!
+//! - [`InMemoryDnsService`]: answers authoritatively using an [`InMemoryDns`];
+//! - [`ForwardDnsService`]: forwards queries to an upstream [`DnsResolver`];
+//! - [`BlockListLayer`]: blocks queries for domains (and their subdomains) on a block list.
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{Context, Layer};
+//! use rama_dns::{
+//!     server::{BlockListLayer, DnsServer, InMemoryDnsService},
+//!     InMemoryDns,
+//! };
+//! use rama_net::address::Domain;
+//! use tokio::net::UdpSocket;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let mut dns = InMemoryDns::new();
+//! dns.insert(Domain::from_static("example.internal"), vec![[10, 0, 0, 1].into()]);
+//!
+//! let service = BlockListLayer::new([Domain::from_static("ads.example.com")])
+//!     .layer(InMemoryDnsService::new(dns));
+//!
+//! let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+//! tokio::spawn(async move {
+//!     DnsServer::new(service).serve_udp(Context::default(), socket).await;
+//! });
+//! # }
+//! ```
+//!
+//! [`InMemoryDns`]: crate::InMemoryDns
+//! [`DnsResolver`]: crate::DnsResolver
+
+use hickory_resolver::proto::{
+    op::{Edns, Message, MessageType, OpCode, Query, ResponseCode},
+    rr::{Record, RecordType},
+};
+use rama_core::{error::BoxError, Context, Service};
+use rama_net::{address::Domain, stream::SocketInfo};
+use std::{fmt, io, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, UdpSocket},
+};
+
+#[doc(inline)]
+pub use hickory_resolver::proto;
+
+mod answer;
+
+mod service;
+#[doc(inline)]
+pub use service::{ForwardDnsService, InMemoryDnsService};
+
+mod block;
+#[doc(inline)]
+pub use block::{BlockList, BlockListLayer};
+
+/// The maximum size of a dns message sent over UDP,
+/// in case the client did not advertise a larger size using EDNS.
+const MIN_UDP_PAYLOAD_SIZE: usize = 512;
+
+/// The maximum size of a dns message the server accepts over UDP,
+/// advertised using EDNS to clients which support it (as recommended by DNS flag day 2020).
+const EDNS_UDP_PAYLOAD_SIZE: u16 = 1232;
+
+/// The default time a stream connection is kept open while waiting for a query (RFC 7766).
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The time to wait before receiving (or accepting) again after a socket error,
+/// such that a persistent error does not result in a busy loop.
+const SOCKET_ERROR_BACKOFF: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The transport over which a [`DnsRequest`] was received.
+pub enum DnsTransport {
+    /// The query was received over UDP.
+    Udp,
+    /// The query was received over TCP.
+    Tcp,
+}
+
+#[derive(Debug, Clone)]
+/// A dns query received by a [`DnsServer`].
+pub struct DnsRequest {
+    message: Message,
+    transport: DnsTransport,
+}
+
+impl DnsRequest {
+    /// Create a new [`DnsRequest`] for the given query message.
+    pub fn new(message: Message, transport: DnsTransport) -> Self {
+        Self { message, transport }
+    }
+
+    /// The dns message of the query.
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    /// Consume the [`DnsRequest`] into its dns message.
+    pub fn into_message(self) -> Message {
+        self.message
+    }
+
+    /// The transport over which the query was received.
+    pub fn transport(&self) -> DnsTransport {
+        self.transport
+    }
+
+    /// The (first) question of the query, if any.
+    pub fn query(&self) -> Option<&Query> {
+        self.message.queries().first()
+    }
+
+    /// The [`Domain`] the query is about, if any and if it is a valid [`Domain`].
+    pub fn domain(&self) -> Option<Domain> {
+        self.query()
+            .and_then(|query| crate::hickory::domain_from_name(query.name()))
+    }
+
+    /// The type of the records queried, if any.
+    pub fn record_type(&self) -> Option<RecordType> {
+        self.query().map(Query::query_type)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The response of a dns [`Service`] to a [`DnsRequest`].
+pub struct DnsResponse {
+    message: Message,
+}
+
+impl DnsResponse {
+    /// Create a new [`DnsResponse`] using the given response message as-is.
+    pub fn new(message: Message) -> Self {
+        Self { message }
+    }
+
+    /// Create a new [`DnsResponse`] for the given [`DnsRequest`],
+    /// without any answers and with the [`ResponseCode::NoError`] response code.
+    pub fn for_request(request: &DnsRequest) -> Self {
+        let query = request.message();
+        let mut message = Message::new();
+        message
+            .set_id(query.id())
+            .set_message_type(MessageType::Response)
+            .set_op_code(query.op_code())
+            .set_recursion_desired(query.recursion_desired())
+            .add_queries(query.queries().iter().cloned());
+        Self { message }
+    }
+
+    /// Create a new [`DnsResponse`] for the given [`DnsRequest`],
+    /// without any answers and with the given [`ResponseCode`].
+    pub fn error(request: &DnsRequest, response_code: ResponseCode) -> Self {
+        Self::for_request(request).with_response_code(response_code)
+    }
+
+    /// The dns message of the response.
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    /// A mutable reference to the dns message of the response.
+    pub fn message_mut(&mut self) -> &mut Message {
+        &mut self.message
+    }
+
+    /// Consume the [`DnsResponse`] into its dns message.
+    pub fn into_message(self) -> Message {
+        self.message
+    }
+
+    /// Set the [`ResponseCode`] of the response.
+    pub fn with_response_code(mut self, response_code: ResponseCode) -> Self {
+        self.message.set_response_code(response_code);
+        self
+    }
+
+    /// Set the [`ResponseCode`] of the response.
+    pub fn set_response_code(&mut self, response_code: ResponseCode) -> &mut Self {
+        self.message.set_response_code(response_code);
+        self
+    }
+
+    /// Set the answers of the response, replacing any existing answers.
+    pub fn with_answers(mut self, answers: impl IntoIterator<Item = Record>) -> Self {
+        self.set_answers(answers);
+        self
+    }
+
+    /// Set the answers of the response, replacing any existing answers.
+    pub fn set_answers(&mut self, answers: impl IntoIterator<Item = Record>) -> &mut Self {
+        *self.message.answers_mut() = answers.into_iter().collect();
+        self
+    }
+
+    /// Mark the response as authoritative (or not).
+    pub fn with_authoritative(mut self, authoritative: bool) -> Self {
+        self.message.set_authoritative(authoritative);
+        self
+    }
+
+    /// Mark the response as authoritative (or not).
+    pub fn set_authoritative(&mut self, authoritative: bool) -> &mut Self {
+        self.message.set_authoritative(authoritative);
+        self
+    }
+}
+
+/// A dns server, serving the queries received over UDP and TCP
+/// using the given [`Service`].
+///
+/// Queries are served concurrently, using the executor of the [`Context`],
+/// such that a graceful [`Context`] keeps the shutdown waiting for in-flight queries.
+/// The [`SocketInfo`] of the client is inserted in the [`Context`] of each query.
+///
+/// Errors returned by the [`Service`] are answered with [`ResponseCode::ServFail`],
+/// and queries using EDNS are answered with an OPT record, unless the [`Service`] already added one.
+///
+/// See the [module docs](self) for more information.
+pub struct DnsServer<S> {
+    service: Arc<S>,
+    idle_timeout: Duration,
+}
+
+impl<S: fmt::Debug> fmt::Debug for DnsServer<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DnsServer")
+            .field("service", &self.service)
+            .field("idle_timeout", &self.idle_timeout)
+            .finish()
+    }
+}
+
+impl<S> Clone for DnsServer<S> {
+    fn clone(&self) -> Self {
+        Self {
+            service: self.service.clone(),
+            idle_timeout: self.idle_timeout,
+        }
+    }
+}
+
+impl<S> DnsServer<S> {
+    /// Create a new [`DnsServer`], serving queries using the given [`Service`].
+    pub fn new(service: S) -> Self {
+        Self {
+            service: Arc::new(service),
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+        }
+    }
+
+    /// Set the time a stream connection is kept open while waiting for
+    /// (the remainder of) a query, after which the connection is closed.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Set the time a stream connection is kept open while waiting for
+    /// (the remainder of) a query, after which the connection is closed.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn set_idle_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Serve the queries received on the given [`UdpSocket`].
+    ///
+    /// Responses which do not fit in a single datagram are truncated,
+    /// such that the client can retry the query over TCP.
+    ///
+    /// Runs until the graceful shutdown of the [`Context`] is initiated, if any, or forever otherwise.
+    pub async fn serve_udp<State>(&self, ctx: Context<State>, socket: UdpSocket)
+    where
+        S: Service<State, DnsRequest, Response = DnsResponse, Error: Into<BoxError>>,
+        State: Send + Sync + 'static,
+    {
+        let socket = Arc::new(socket);
+        let local_addr = socket.local_addr().ok();
+        let mut buf = vec![0; u16::MAX as usize];
+
+        loop {
+            let (n, peer_addr) = tokio::select! {
+                _ = cancelled(&ctx) => {
+                    tracing::trace!("signal received: stop serving dns queries over udp");
+                    return;
+                }
+                result = socket.recv_from(&mut buf) => match result {
+                    Ok(tuple) => tuple,
+                    Err(err) => {
+                        tracing::debug!(error = %err, "dns server: failed to receive udp datagram");
+                        tokio::time::sleep(SOCKET_ERROR_BACKOFF).await;
+                        continue;
+                    }
+                },
+            };
+
+            let Some(request) = decode_request(&buf[..n], DnsTransport::Udp, Some(peer_addr))
+            else {
+                continue;
+            };
+
+            let service = self.service.clone();
+            let socket = socket.clone();
+            let mut request_ctx = ctx.clone();
+            request_ctx.insert(SocketInfo::new(local_addr, peer_addr));
+
+            ctx.spawn(async move {
+                let max_size = (request.message().max_payload() as usize).max(MIN_UDP_PAYLOAD_SIZE);
+                let response = serve_request(service.as_ref(), request_ctx, request).await;
+                let Some(bytes) = encode_response(response, Some(max_size)) else {
+                    return;
+                };
+                if let Err(err) = socket.send_to(&bytes, peer_addr).await {
+                    tracing::trace!(error = %err, %peer_addr, "dns server: failed to send udp response");
+                }
+            });
+        }
+    }
+
+    /// Serve the queries received on the connections accepted by the given [`TcpListener`].
+    ///
+    /// Each connection can be used to send multiple queries,
+    /// which are answered in the order they are received.
+    /// Connections are closed once idle for longer than the idle timeout,
+    /// or once the graceful shutdown of the [`Context`] is initiated.
+    ///
+    /// Runs until the graceful shutdown of the [`Context`] is initiated, if any, or forever otherwise.
+    pub async fn serve_tcp<State>(&self, ctx: Context<State>, listener: TcpListener)
+    where
+        S: Service<State, DnsRequest, Response = DnsResponse, Error: Into<BoxError>>,
+        State: Send + Sync + 'static,
+    {
+        loop {
+            let (stream, peer_addr) = tokio::select! {
+                _ = cancelled(&ctx) => {
+                    tracing::trace!("signal received: stop serving dns queries over tcp");
+                    return;
+                }
+                result = listener.accept() => match result {
+                    Ok(tuple) => tuple,
+                    Err(err) => {
+                        tracing::debug!(error = %err, "dns server: failed to accept tcp connection");
+                        tokio::time::sleep(SOCKET_ERROR_BACKOFF).await;
+                        continue;
+                    }
+                },
+            };
+
+            let service = self.service.clone();
+            let idle_timeout = self.idle_timeout;
+            let mut conn_ctx = ctx.clone();
+            conn_ctx.insert(SocketInfo::new(stream.local_addr().ok(), peer_addr));
+
+            ctx.spawn(async move {
+                if let Err(err) = serve_stream_conn(service.as_ref(), conn_ctx, stream, Some(peer_addr), idle_timeout).await {
+                    tracing::trace!(error = %err, %peer_addr, "dns server: tcp connection closed with error");
+                }
+            });
+        }
+    }
+
+    /// Serve the queries received over the given stream, until it is closed,
+    /// idle for longer than the idle timeout, or the graceful shutdown of the [`Context`] is initiated.
+    ///
+    /// Each query is expected to be prefixed with its length as a two byte integer,
+    /// as is the case for queries received over TCP. This can be used to serve queries
+    /// over a stream accepted by another transport, such as a tls stream for DNS-over-TLS.
+    pub async fn serve_stream<State, IO>(&self, ctx: Context<State>, stream: IO) -> io::Result<()>
+    where
+        S: Service<State, DnsRequest, Response = DnsResponse, Error: Into<BoxError>>,
+        State: Send + Sync + 'static,
+        IO: AsyncRead + AsyncWrite + Unpin,
+    {
+        let peer_addr = ctx.get::<SocketInfo>().map(|info| *info.peer_addr());
+        serve_stream_conn(
+            self.service.as_ref(),
+            ctx,
+            stream,
+            peer_addr,
+            self.idle_timeout,
+        )
+        .await
+    }
+}
+
+/// Resolves once the graceful shutdown of the [`Context`] is initiated,
+/// never resolving in case the [`Context`] is not graceful.
+async fn cancelled<State>(ctx: &Context<State>) {
+    match ctx.guard() {
+        Some(guard) => guard.cancelled().await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn serve_stream_conn<S, State, IO>(
+    service: &S,
+    ctx: Context<State>,
+    mut stream: IO,
+    peer_addr: Option<SocketAddr>,
+    idle_timeout: Duration,
+) -> io::Result<()>
+where
+    S: Service<State, DnsRequest, Response = DnsResponse, Error: Into<BoxError>>,
+    State: Send + Sync + 'static,
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    loop {
+        let read_message = async {
+            // each message is prefixed with its length as a two byte integer
+            let len = stream.read_u16().await?;
+            let mut buf = vec![0; len as usize];
+            stream.read_exact(&mut buf).await?;
+            Ok::<_, io::Error>(buf)
+        };
+        let buf = tokio::select! {
+            _ = cancelled(&ctx) => {
+                tracing::trace!(?peer_addr, "signal received: close dns stream connection");
+                return Ok(());
+            }
+            result = tokio::time::timeout(idle_timeout, read_message) => match result {
+                Ok(Ok(buf)) => buf,
+                Ok(Err(err)) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                Ok(Err(err)) => return Err(err),
+                Err(_) => {
+                    tracing::trace!(?peer_addr, "dns server: close idle stream connection");
+                    return Ok(());
+                }
+            },
+        };
+
+        let Some(request) = decode_request(&buf, DnsTransport::Tcp, peer_addr) else {
+            return Ok(());
+        };
+        let response = serve_request(service, ctx.clone(), request).await;
+        // the length prefix limits a message to 64 KiB, larger responses are truncated
+        let Some(bytes) = encode_response(response, Some(u16::MAX as usize)) else {
+            return Ok(());
+        };
+        let len = u16::try_from(bytes.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "dns response too large"))?;
+        stream.write_u16(len).await?;
+        stream.write_all(&bytes).await?;
+        stream.flush().await?;
+    }
+}
+
+fn decode_request(
+    buf: &[u8],
+    transport: DnsTransport,
+    peer_addr: Option<SocketAddr>,
+) -> Option<DnsRequest> {
+    match Message::from_vec(buf) {
+        Ok(message) if message.message_type() == MessageType::Query => {
+            Some(DnsRequest::new(message, transport))
+        }
+        Ok(_) => {
+            tracing::trace!(
+                ?peer_addr,
+                "dns server: ignore response message received as query"
+            );
+            None
+        }
+        Err(err) => {
+            tracing::trace!(error = %err, ?peer_addr, "dns server: failed to decode dns query");
+            None
+        }
+    }
+}
+
+async fn serve_request<S, State>(
+    service: &S,
+    ctx: Context<State>,
+    request: DnsRequest,
+) -> DnsResponse
+where
+    S: Service<State, DnsRequest, Response = DnsResponse, Error: Into<BoxError>>,
+{
+    let request_edns = request.message().extensions().is_some();
+
+    let mut response = if request.message().op_code() != OpCode::Query {
+        DnsResponse::error(&request, ResponseCode::NotImp)
+    } else {
+        let fallback = DnsResponse::error(&request, ResponseCode::ServFail);
+        match service.serve(ctx, request).await {
+            Ok(response) => response,
+            Err(err) => {
+                let err = err.into();
+                tracing::debug!(error = %err, "dns server: service failed to serve dns query");
+                fallback
+            }
+        }
+    };
+
+    // a client using EDNS expects an OPT record in the response (RFC 6891)
+    if request_edns && response.message().extensions().is_none() {
+        let mut edns = Edns::new();
+        edns.set_max_payload(EDNS_UDP_PAYLOAD_SIZE);
+        response.message_mut().set_edns(edns);
+    }
+    response
+}
+
+/// Encode the response, truncating it in case it exceeds the given max size.
+fn encode_response(response: DnsResponse, max_size: Option<usize>) -> Option<Vec<u8>> {
+    let mut message = response.into_message();
+    let bytes = match message.to_vec() {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            tracing::debug!(error = %err, "dns server: failed to encode dns response");
+            return None;
+        }
+    };
+    match max_size {
+        Some(max_size) if bytes.len() > max_size => {
+            message.take_answers();
+            message.take_name_servers();
+            message.take_additionals();
+            message.set_truncated(true);
+            message.to_vec().ok()
+        }
+        _ => Some(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DnsResolver, InMemoryDns};
+    use hickory_resolver::proto::rr::{
+        rdata::{A, TXT},
+        RData,
+    };
+    use rama_core::{graceful::Shutdown, rt::Executor, service::service_fn};
+    use std::{convert::Infallible, net::Ipv4Addr};
+    use tokio::net::TcpStream;
+
+    async fn query_udp(addr: SocketAddr, name: &str, record_type: RecordType) -> Message {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket
+            .send_to(&query(name, record_type).to_vec().unwrap(), addr)
+            .await
+            .unwrap();
+        let mut buf = vec![0; 4096];
+        let n = socket.recv(&mut buf).await.unwrap();
+        Message::from_vec(&buf[..n]).unwrap()
+    }
+
+    async fn query_tcp(stream: &mut TcpStream, name: &str, record_type: RecordType) -> Message {
+        let bytes = query(name, record_type).to_vec().unwrap();
+        stream.write_u16(bytes.len() as u16).await.unwrap();
+        stream.write_all(&bytes).await.unwrap();
+        let len = stream.read_u16().await.unwrap();
+        let mut buf = vec![0; len as usize];
+        stream.read_exact(&mut buf).await.unwrap();
+        Message::from_vec(&buf).unwrap()
+    }
+
+    fn query(name: &str, record_type: RecordType) -> Message {
+        let mut message = Message::new();
+        message
+            .set_id(42)
+            .set_recursion_desired(true)
+            .add_query(Query::query(
+                proto::rr::Name::from_ascii(name).unwrap(),
+                record_type,
+            ));
+        message
+    }
+
+    fn ipv4_answers(message: &Message) -> Vec<Ipv4Addr> {
+        message
+            .answers()
+            .iter()
+            .filter_map(|record| match record.data() {
+                Some(RData::A(A(ip))) => Some(*ip),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_dns_server_udp_and_tcp() {
+        let mut dns = InMemoryDns::new();
+        dns.insert(
+            Domain::from_static("example.internal"),
+            vec![[10, 0, 0, 1].into(), [10, 0, 0, 2].into()],
+        );
+        let server = DnsServer::new(InMemoryDnsService::new(dns));
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let udp_addr = socket.local_addr().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let tcp_addr = listener.local_addr().unwrap();
+        tokio::spawn({
+            let server = server.clone();
+            async move { server.serve_udp(Context::default(), socket).await }
+        });
+        tokio::spawn(async move { server.serve_tcp(Context::default(), listener).await });
+
+        let response = query_udp(udp_addr, "example.internal.", RecordType::A).await;
+        assert_eq!(response.id(), 42);
+        assert_eq!(response.message_type(), MessageType::Response);
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.authoritative());
+        assert_eq!(
+            ipv4_answers(&response),
+            vec![Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2)]
+        );
+
+        let response = query_udp(udp_addr, "unknown.internal.", RecordType::A).await;
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+
+        // a mapped name without records of the queried type exists
+        let response = query_udp(udp_addr, "example.internal.", RecordType::AAAA).await;
+        assert_eq!(response.response_code(), ResponseCode::NoError);
+        assert!(response.authoritative());
+        assert!(response.answers().is_empty());
+        let response = query_udp(udp_addr, "unknown.internal.", RecordType::AAAA).await;
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+
+        // multiple queries over the same tcp connection
+        let mut stream = TcpStream::connect(tcp_addr).await.unwrap();
+        let response = query_tcp(&mut stream, "example.internal.", RecordType::A).await;
+        assert_eq!(ipv4_answers(&response).len(), 2);
+        let response = query_tcp(&mut stream, "unknown.internal.", RecordType::A).await;
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+    }
+
+    #[tokio::test]
+    async fn test_dns_server_edns() {
+        let server = DnsServer::new(InMemoryDnsService::new(InMemoryDns::new()));
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move { server.serve_udp(Context::default(), socket).await });
+
+        let response = query_udp(addr, "example.internal.", RecordType::A).await;
+        assert!(response.extensions().is_none());
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut message = query("example.internal.", RecordType::A);
+        let mut edns = Edns::new();
+        edns.set_max_payload(4096);
+        message.set_edns(edns);
+        client
+            .send_to(&message.to_vec().unwrap(), addr)
+            .await
+            .unwrap();
+        let mut buf = vec![0; 4096];
+        let n = client.recv(&mut buf).await.unwrap();
+        let response = Message::from_vec(&buf[..n]).unwrap();
+        let edns = response.extensions().as_ref().unwrap();
+        assert_eq!(edns.max_payload(), EDNS_UDP_PAYLOAD_SIZE);
+        assert_eq!(edns.version(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_dns_server_truncate_udp() {
+        let server = DnsServer::new(service_fn(|req: DnsRequest| async move {
+            let name = req.query().unwrap().name().clone();
+            let answers = (0..100).map(move |i| {
+                Record::from_rdata(name.clone(), 60, RData::A(A(Ipv4Addr::new(10, 0, 0, i))))
+            });
+            Ok::<_, Infallible>(DnsResponse::for_request(&req).with_answers(answers))
+        }));
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move { server.serve_udp(Context::default(), socket).await });
+
+        let response = query_udp(addr, "example.internal.", RecordType::A).await;
+        assert!(response.truncated());
+        assert!(response.answers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dns_server_truncate_tcp() {
+        let server = DnsServer::new(service_fn(|req: DnsRequest| async move {
+            let name = req.query().unwrap().name().clone();
+            let answers = (0..300).map(move |_| {
+                let txt = TXT::from_bytes(vec![&[b'x'; 255][..]]);
+                Record::from_rdata(name.clone(), 60, RData::TXT(txt))
+            });
+            Ok::<_, Infallible>(DnsResponse::for_request(&req).with_answers(answers))
+        }));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { server.serve_tcp(Context::default(), listener).await });
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let response = query_tcp(&mut stream, "example.internal.", RecordType::TXT).await;
+        assert!(response.truncated());
+        assert!(response.answers().len() < 300);
+    }
+
+    #[tokio::test]
+    async fn test_dns_server_tcp_idle_timeout() {
+        let server = DnsServer::new(InMemoryDnsService::new(InMemoryDns::new()))
+            .with_idle_timeout(Duration::from_millis(100));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { server.serve_tcp(Context::default(), listener).await });
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut buf = [0; 1];
+        let n = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(n, 0);
+    }
+
+    #[tokio::test]
+    async fn test_dns_server_tcp_graceful_shutdown() {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let shutdown = Shutdown::new(async move {
+            rx.await.unwrap();
+        });
+        let ctx = Context::new(Arc::new(()), Executor::graceful(shutdown.guard()));
+
+        let mut dns = InMemoryDns::new();
+        dns.insert(
+            Domain::from_static("example.internal"),
+            vec![[10, 0, 0, 1].into()],
+        );
+        let server = DnsServer::new(InMemoryDnsService::new(dns));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { server.serve_tcp(ctx, listener).await });
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let response = query_tcp(&mut stream, "example.internal.", RecordType::A).await;
+        assert_eq!(ipv4_answers(&response).len(), 1);
+
+        // the open connection does not keep the shutdown waiting
+        tx.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), shutdown.shutdown())
+            .await
+            .unwrap();
+        let mut buf = [0; 1];
+        assert_eq!(stream.read(&mut buf).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_dns_server_service_error() {
+        let server = DnsServer::new(service_fn(|_req: DnsRequest| async move {
+            Err::<DnsResponse, _>(BoxError::from("upstream failure"))
+        }));
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move { server.serve_udp(Context::default(), socket).await });
+
+        let response = query_udp(addr, "example.internal.", RecordType::A).await;
+        assert_eq!(response.response_code(), ResponseCode::ServFail);
+    }
+
+    #[tokio::test]
+    async fn test_dns_server_forward_to_in_memory() {
+        // an upstream dns server, queried by a forwarding dns server,
+        // which is in its turn resolved using the upstream resolver
+        let mut dns = InMemoryDns::new();
+        dns.insert(
+            Domain::from_static("example.internal"),
+            vec![[10, 0, 0, 1].into()],
+        );
+        let forward = DnsServer::new(ForwardDnsService::new(dns));
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move { forward.serve_udp(Context::default(), socket).await });
+
+        let resolver = crate::HickoryDns::builder()
+            .with_config(crate::hickory::config::ResolverConfig::from_parts(
+                None,
+                vec![],
+                crate::hickory::config::NameServerConfigGroup::from_ips_clear(
+                    &[addr.ip()],
+                    addr.port(),
+                    true,
+                ),
+            ))
+            .build();
+        let ips = resolver
+            .ipv4_lookup(Domain::from_static("example.internal"))
+            .await
+            .unwrap();
+        assert_eq!(ips, vec![Ipv4Addr::new(10, 0, 0, 1)]);
+    }
+}
@@ -0,0 +1,149 @@
+use super::{
+    answer::{lookup_answers, srv_name_from_name},
+    DnsRequest, DnsResponse,
+};
+use crate::{hickory::domain_from_name, DnsResolver, InMemoryDns};
+use hickory_resolver::proto::{op::ResponseCode, rr::Name};
+use rama_core::{
+    error::{BoxError, OpaqueError},
+    Context, Service,
+};
+use std::{convert::Infallible, time::Duration};
+
+const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+/// A dns [`Service`] answering queries authoritatively using an [`InMemoryDns`].
+///
+/// Queries for names which are not mapped are answered with [`ResponseCode::NXDomain`],
+/// while queries for mapped names without records of the queried type
+/// are answered without records and with [`ResponseCode::NoError`].
+pub struct InMemoryDnsService {
+    dns: InMemoryDns,
+    ttl: Duration,
+}
+
+impl InMemoryDnsService {
+    /// Create a new [`InMemoryDnsService`], answering queries using the given [`InMemoryDns`].
+    pub fn new(dns: InMemoryDns) -> Self {
+        Self {
+            dns,
+            ttl: DEFAULT_TTL,
+        }
+    }
+
+    /// Set the ttl of the answers, in case the [`InMemoryDns`] has no ttl defined.
+    ///
+    /// Defaults to 60 seconds.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set the ttl of the answers, in case the [`InMemoryDns`] has no ttl defined.
+    ///
+    /// Defaults to 60 seconds.
+    pub fn set_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Returns `true` in case any record is mapped for the given [`Name`].
+    fn contains_name(&self, name: &Name) -> bool {
+        srv_name_from_name(name).is_some_and(|srv_name| self.dns.contains_srv_name(&srv_name))
+            || domain_from_name(name).is_some_and(|domain| self.dns.contains_domain(&domain))
+    }
+}
+
+impl<State> Service<State, DnsRequest> for InMemoryDnsService
+where
+    State: Send + Sync + 'static,
+{
+    type Response = DnsResponse;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        _ctx: Context<State>,
+        req: DnsRequest,
+    ) -> Result<Self::Response, Self::Error> {
+        let Some(query) = req.query() else {
+            return Ok(DnsResponse::error(&req, ResponseCode::FormErr));
+        };
+        let response = match lookup_answers(&self.dns, query, self.ttl).await {
+            Ok(answers) => DnsResponse::for_request(&req).with_answers(answers),
+            Err(_) if self.contains_name(query.name()) => DnsResponse::for_request(&req),
+            Err(_) => DnsResponse::error(&req, ResponseCode::NXDomain),
+        };
+        Ok(response.with_authoritative(true))
+    }
+}
+
+/// A dns [`Service`] forwarding queries to an upstream [`DnsResolver`].
+///
+/// Queries which fail to resolve are answered with [`ResponseCode::ServFail`].
+#[derive(Debug, Clone)]
+pub struct ForwardDnsService<R> {
+    resolver: R,
+    ttl: Duration,
+}
+
+impl<R> ForwardDnsService<R> {
+    /// Create a new [`ForwardDnsService`], forwarding queries to the given [`DnsResolver`].
+    pub fn new(resolver: R) -> Self {
+        Self {
+            resolver,
+            ttl: DEFAULT_TTL,
+        }
+    }
+
+    /// Set the ttl of the answers, in case the upstream [`DnsResolver`] does not report one.
+    ///
+    /// Defaults to 60 seconds.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set the ttl of the answers, in case the upstream [`DnsResolver`] does not report one.
+    ///
+    /// Defaults to 60 seconds.
+    pub fn set_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Returns a reference to the upstream [`DnsResolver`].
+    pub fn resolver(&self) -> &R {
+        &self.resolver
+    }
+}
+
+impl<R, State> Service<State, DnsRequest> for ForwardDnsService<R>
+where
+    R: DnsResolver<Error: Into<BoxError>>,
+    State: Send + Sync + 'static,
+{
+    type Response = DnsResponse;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        _ctx: Context<State>,
+        req: DnsRequest,
+    ) -> Result<Self::Response, Self::Error> {
+        let Some(query) = req.query() else {
+            return Ok(DnsResponse::error(&req, ResponseCode::FormErr));
+        };
+        let mut response = match lookup_answers(&self.resolver, query, self.ttl).await {
+            Ok(answers) => DnsResponse::for_request(&req).with_answers(answers),
+            Err(err) => {
+                let err = OpaqueError::from_boxed(err.into());
+                tracing::debug!(error = %err, "forward dns query: upstream lookup failed");
+                DnsResponse::error(&req, ResponseCode::ServFail)
+            }
+        };
+        response.message_mut().set_recursion_available(true);
+        Ok(response)
+    }
+}
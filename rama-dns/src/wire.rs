@@ -0,0 +1,211 @@
+//! The dns wire format, as used by the resolvers which exchange
+//! dns messages with a dns server over their own transport.
+
+use crate::{
+    cache::NoDnsRecordsErr,
+    hickory::{domain_from_name, fqdn_from_domain, svcb_record},
+    DnsLookup, SrvName, SrvRecord, SvcbRecord, TxtRecord,
+};
+use hickory_resolver::proto::{
+    op::{Message, MessageType, OpCode, Query, ResponseCode},
+    rr::{
+        rdata::{A, AAAA, CNAME, HTTPS},
+        Name, RData, Record, RecordType,
+    },
+};
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_net::address::Domain;
+use std::{
+    future::Future,
+    net::{Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+
+/// The maximum size of an encoded dns message.
+pub(crate) const MAX_MESSAGE_SIZE: usize = u16::MAX as usize;
+
+/// The transport over which an encoded dns query is exchanged for its encoded response.
+pub(crate) trait DnsExchange: Send + Sync + 'static {
+    /// Exchange the given encoded dns query for the encoded dns response.
+    fn exchange(
+        &self,
+        query: Vec<u8>,
+    ) -> impl Future<Output = Result<Vec<u8>, OpaqueError>> + Send + '_;
+}
+
+pub(crate) async fn ipv4_lookup<E: DnsExchange>(
+    exchange: &E,
+    domain: Domain,
+) -> Result<DnsLookup<Ipv4Addr>, OpaqueError> {
+    let answer = lookup(exchange, fqdn_from_domain(domain)?, RecordType::A)
+        .await
+        .context("lookup IPv4 address(es)")?;
+    Ok(answer.into_lookup(|rdata| match rdata {
+        RData::A(A(ip)) => Some(*ip),
+        _ => None,
+    }))
+}
+
+pub(crate) async fn ipv6_lookup<E: DnsExchange>(
+    exchange: &E,
+    domain: Domain,
+) -> Result<DnsLookup<Ipv6Addr>, OpaqueError> {
+    let answer = lookup(exchange, fqdn_from_domain(domain)?, RecordType::AAAA)
+        .await
+        .context("lookup IPv6 address(es)")?;
+    Ok(answer.into_lookup(|rdata| match rdata {
+        RData::AAAA(AAAA(ip)) => Some(*ip),
+        _ => None,
+    }))
+}
+
+/// Consume a [`DnsLookup`] into its addresses,
+/// failing in case no addresses were resolved.
+pub(crate) fn into_addresses<T>(lookup: DnsLookup<T>) -> Result<Vec<T>, OpaqueError> {
+    if lookup.is_empty() {
+        Err(OpaqueError::from_std(NoDnsRecordsErr))
+    } else {
+        Ok(lookup.into_addresses())
+    }
+}
+
+pub(crate) async fn srv_lookup<E: DnsExchange>(
+    exchange: &E,
+    name: SrvName,
+) -> Result<Vec<SrvRecord>, OpaqueError> {
+    let name =
+        Name::from_utf8(name.to_string()).context("try to consume a SrvName as a Dns Name")?;
+    let answer = lookup(exchange, name, RecordType::SRV)
+        .await
+        .context("lookup SRV record(s)")?;
+    Ok(answer.into_records(|rdata| match rdata {
+        RData::SRV(srv) => Some(SrvRecord::new(
+            srv.priority(),
+            srv.weight(),
+            srv.port(),
+            domain_from_name(srv.target())?,
+        )),
+        _ => None,
+    }))
+}
+
+pub(crate) async fn txt_lookup<E: DnsExchange>(
+    exchange: &E,
+    domain: Domain,
+) -> Result<Vec<TxtRecord>, OpaqueError> {
+    let answer = lookup(exchange, fqdn_from_domain(domain)?, RecordType::TXT)
+        .await
+        .context("lookup TXT record(s)")?;
+    Ok(answer.into_records(|rdata| match rdata {
+        RData::TXT(txt) => Some(TxtRecord::new(
+            txt.iter().map(|data| data.to_vec()).collect(),
+        )),
+        _ => None,
+    }))
+}
+
+pub(crate) async fn cname_lookup<E: DnsExchange>(
+    exchange: &E,
+    domain: Domain,
+) -> Result<Vec<Domain>, OpaqueError> {
+    let answer = lookup(exchange, fqdn_from_domain(domain)?, RecordType::CNAME)
+        .await
+        .context("lookup CNAME record(s)")?;
+    Ok(answer.into_records(|rdata| match rdata {
+        RData::CNAME(CNAME(name)) => domain_from_name(name),
+        _ => None,
+    }))
+}
+
+pub(crate) async fn https_lookup<E: DnsExchange>(
+    exchange: &E,
+    domain: Domain,
+) -> Result<Vec<SvcbRecord>, OpaqueError> {
+    let answer = lookup(exchange, fqdn_from_domain(domain)?, RecordType::HTTPS)
+        .await
+        .context("lookup HTTPS record(s)")?;
+    Ok(answer.into_records(|rdata| match rdata {
+        RData::HTTPS(HTTPS(svcb)) => Some(svcb_record(svcb)),
+        _ => None,
+    }))
+}
+
+/// The records answering a dns query,
+/// together with the time-to-live of a negative answer, if known.
+struct Answer {
+    records: Vec<Record>,
+    negative_ttl: Option<Duration>,
+}
+
+impl Answer {
+    fn into_records<T>(self, f: impl Fn(&RData) -> Option<T>) -> Vec<T> {
+        self.records
+            .iter()
+            .filter_map(|record| record.data().and_then(&f))
+            .collect()
+    }
+
+    fn into_lookup<T>(self, f: impl Fn(&RData) -> Option<T>) -> DnsLookup<T> {
+        let mut ttl = None;
+        let addresses: Vec<_> = self
+            .records
+            .iter()
+            .filter_map(|record| {
+                let address = record.data().and_then(&f)?;
+                ttl = Some(ttl.map_or(record.ttl(), |ttl: u32| ttl.min(record.ttl())));
+                Some(address)
+            })
+            .collect();
+        if addresses.is_empty() {
+            DnsLookup::empty().maybe_with_ttl(self.negative_ttl)
+        } else {
+            DnsLookup::new(addresses).maybe_with_ttl(ttl.map(|ttl| Duration::from_secs(ttl as u64)))
+        }
+    }
+}
+
+async fn lookup<E: DnsExchange>(
+    exchange: &E,
+    name: Name,
+    record_type: RecordType,
+) -> Result<Answer, OpaqueError> {
+    // the id is left to zero, as recommended for dns-over-https (RFC 8484 §4.1),
+    // while the other transports have a single query in flight per connection,
+    // reused connections (DoT) checking the question of the response against the query
+    let mut query = Message::new();
+    query
+        .set_id(0)
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(true)
+        .add_query(Query::query(name, record_type));
+    let query = query.to_vec().context("encode dns query")?;
+
+    let response = exchange.exchange(query).await?;
+    let mut response = Message::from_vec(&response).context("decode dns response")?;
+    if response.message_type() != MessageType::Response || response.id() != 0 {
+        return Err(OpaqueError::from_display(
+            "dns server replied with an unexpected message",
+        ));
+    }
+
+    match response.response_code() {
+        ResponseCode::NoError | ResponseCode::NXDomain => {
+            let negative_ttl = response
+                .name_servers()
+                .iter()
+                .find_map(|record| match record.data() {
+                    Some(RData::SOA(soa)) => Some(record.ttl().min(soa.minimum())),
+                    _ => None,
+                })
+                .map(|ttl| Duration::from_secs(ttl as u64));
+            Ok(Answer {
+                records: response.take_answers(),
+                negative_ttl,
+            })
+        }
+        response_code => Err(OpaqueError::from_display(format!(
+            "dns server replied with response code {response_code}"
+        ))),
+    }
+}
@@ -1,11 +1,21 @@
-use super::{svc::SendRequest, HttpClientService};
+use super::{
+    preface::{is_pseudo_header_order_supported, SettingsOrderStream, PSEUDO_HEADER_ORDER},
+    svc::SendRequest,
+    HttpClientService,
+};
 use crate::executor::HyperExecutor;
 use hyper_util::rt::TokioIo;
 use rama_core::{
     error::{BoxError, OpaqueError},
     Context, Layer, Service,
 };
-use rama_http_types::{dep::http_body, Request, Version};
+use rama_http_types::{
+    conn::{H2ClientContextParams, Http1ClientContextParams},
+    dep::http_body,
+    Request, Version,
+};
+#[cfg(feature = "tls")]
+use rama_net::tls::{client::NegotiatedTlsParameters, ApplicationProtocol};
 use rama_net::{
     client::{ConnectorService, EstablishedClientConnection},
     stream::Stream,
@@ -15,6 +25,16 @@ use std::fmt;
 use tokio::sync::Mutex;
 
 /// A [`Service`] which establishes an HTTP Connection.
+///
+/// The http version spoken over the connection is the one negotiated
+/// using ALPN for tls connections, as found in the [`NegotiatedTlsParameters`],
+/// and the version of the [`Request`] otherwise.
+/// The version of the [`Request`] is updated to match the version used.
+///
+/// The [`Http1ClientContextParams`] and [`H2ClientContextParams`] found in the [`Context`]
+/// are used to configure the connection. The order of the http/2 settings is emulated by
+/// reordering the initial `SETTINGS` frame, while a warning is logged for the
+/// parts of the [`H2ClientContextParams`] which cannot be emulated.
 pub struct HttpConnector<S> {
     inner: S,
 }
@@ -63,17 +83,47 @@ where
     ) -> Result<Self::Response, Self::Error> {
         let EstablishedClientConnection {
             ctx,
-            req,
+            mut req,
             conn,
             addr,
         } = self.inner.connect(ctx, req).await.map_err(Into::into)?;
 
-        let io = TokioIo::new(Box::pin(conn));
+        if let Some(version) = negotiated_version(&ctx) {
+            if version != req.version() {
+                tracing::trace!(
+                    "use negotiated http version {version:?} instead of the request version {:?}",
+                    req.version()
+                );
+                *req.version_mut() = version;
+            }
+        }
 
         match req.version() {
             Version::HTTP_2 => {
                 let executor = HyperExecutor(ctx.executor().clone());
-                let (sender, conn) = hyper::client::conn::http2::handshake(executor, io).await?;
+                let mut builder = hyper::client::conn::http2::Builder::new(executor);
+                let mut settings_order = None;
+                if let Some(params) = ctx.get::<H2ClientContextParams>() {
+                    builder
+                        .initial_stream_window_size(params.initial_stream_window_size)
+                        .initial_connection_window_size(params.initial_connection_window_size)
+                        .max_frame_size(params.max_frame_size);
+                    if let Some(max) = params.max_header_list_size {
+                        builder.max_header_list_size(max);
+                    }
+                    settings_order.clone_from(&params.settings_order);
+                    if let Some(order) = &params.pseudo_header_order {
+                        if !is_pseudo_header_order_supported(order) {
+                            tracing::warn!(
+                                ?order,
+                                sent = ?PSEUDO_HEADER_ORDER,
+                                "h2 pseudo header order cannot be emulated",
+                            );
+                        }
+                    }
+                }
+                let io = TokioIo::new(Box::pin(SettingsOrderStream::new(conn, settings_order)));
+                let (sender, conn) = builder.handshake(io).await?;
 
                 ctx.spawn(async move {
                     if let Err(err) = conn.await {
@@ -91,7 +141,12 @@ where
                 })
             }
             Version::HTTP_11 | Version::HTTP_10 | Version::HTTP_09 => {
-                let (sender, conn) = hyper::client::conn::http1::handshake(io).await?;
+                let mut builder = hyper::client::conn::http1::Builder::new();
+                if let Some(params) = ctx.get::<Http1ClientContextParams>() {
+                    builder.title_case_headers(params.title_header_case);
+                }
+                let io = TokioIo::new(Box::pin(conn));
+                let (sender, conn) = builder.handshake(io).await?;
 
                 ctx.spawn(async move {
                     if let Err(err) = conn.await {
@@ -117,6 +172,25 @@ where
     }
 }
 
+/// The http version negotiated using ALPN, if any.
+#[cfg(feature = "tls")]
+fn negotiated_version<State>(ctx: &Context<State>) -> Option<Version> {
+    ctx.get::<NegotiatedTlsParameters>()
+        .and_then(|params| params.application_layer_protocol.as_ref())
+        .and_then(|protocol| match protocol {
+            ApplicationProtocol::HTTP_2 => Some(Version::HTTP_2),
+            ApplicationProtocol::HTTP_11 => Some(Version::HTTP_11),
+            ApplicationProtocol::HTTP_10 => Some(Version::HTTP_10),
+            _ => None,
+        })
+}
+
+/// The http version negotiated using ALPN, if any.
+#[cfg(not(feature = "tls"))]
+fn negotiated_version<State>(_ctx: &Context<State>) -> Option<Version> {
+    None
+}
+
 /// A [`Layer`] that produces an [`HttpConnector`].
 #[derive(Debug, Clone)]
 #[non_exhaustive]
@@ -142,3 +216,64 @@ impl<S> Layer<S> for HttpConnectorLayer {
         HttpConnector { inner }
     }
 }
+
+#[cfg(all(test, feature = "rustls", not(feature = "boring")))]
+mod tests {
+    use crate::{client::HttpClient, server::HttpServer};
+    use rama_core::{rt::Executor, service::service_fn, Context, Layer, Service};
+    use rama_http_types::{dep::http_body_util::BodyExt, Body, Request, Response, Version};
+    use rama_net::{address::Host, tls::ApplicationProtocol};
+    use rama_tcp::server::TcpListener;
+    use rama_tls::{
+        mitm::CertificateAuthority,
+        rustls::{
+            dep::rustls::ClientConfig,
+            server::{MitmServerConfigProvider, TlsAcceptorLayer},
+            verify::NoServerCertVerifier,
+        },
+    };
+    use std::{convert::Infallible, net::Ipv4Addr, sync::Arc};
+
+    #[tokio::test]
+    async fn test_http_client_uses_negotiated_alpn() {
+        let server_config =
+            MitmServerConfigProvider::new(CertificateAuthority::generate("rama test ca").unwrap())
+                .with_alpn_protocols(vec![
+                    ApplicationProtocol::HTTP_2,
+                    ApplicationProtocol::HTTP_11,
+                ])
+                .server_config_for(&Host::Address(Ipv4Addr::LOCALHOST.into()))
+                .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(listener.serve(TlsAcceptorLayer::new(server_config).layer(
+            HttpServer::auto(Executor::default()).service(service_fn(
+                |_ctx: Context<()>, req: Request| async move {
+                    Ok::<_, Infallible>(Response::new(Body::from(format!("{:?}", req.version()))))
+                },
+            )),
+        )));
+
+        let mut client_config = ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoServerCertVerifier::default()))
+            .with_no_client_auth();
+        client_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        let client = HttpClient::default().with_tls_config(Arc::new(client_config));
+
+        for _ in 0..2 {
+            // the request version is overruled by the protocol negotiated using ALPN
+            let req = Request::builder()
+                .uri(format!("https://{addr}/"))
+                .version(Version::HTTP_11)
+                .body(Body::empty())
+                .unwrap();
+            let resp = client.serve(Context::default(), req).await.unwrap();
+            assert_eq!(resp.version(), Version::HTTP_2);
+            let body = resp.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body, "HTTP/2.0");
+        }
+        assert_eq!(client.connection_pool().unwrap().idle_connections(), 1);
+    }
+}
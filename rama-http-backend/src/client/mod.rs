@@ -6,14 +6,15 @@ use rama_core::{
     error::{BoxError, ErrorExt, OpaqueError},
     Context, Service,
 };
-use rama_http_types::{dep::http_body, Request, Response};
+use rama_dns::HickoryDns;
+use rama_http_types::{dep::http_body, Request, Response, Version};
 use rama_net::client::{ConnectorService, EstablishedClientConnection};
 use rama_tcp::client::service::TcpConnector;
 
-// TODO: also support client config in boring...
+#[cfg(feature = "boring")]
+use rama_tls::boring::client::ClientConfig;
 #[cfg(all(feature = "rustls", not(feature = "boring")))]
 use rama_tls::rustls::dep::rustls::ClientConfig;
-#[cfg(all(feature = "rustls", not(feature = "boring")))]
 use std::sync::Arc;
 
 #[cfg(any(feature = "rustls", feature = "boring"))]
@@ -27,9 +28,17 @@ mod conn;
 #[doc(inline)]
 pub use conn::{HttpConnector, HttpConnectorLayer};
 
+mod svcb;
+
+mod preface;
+
+mod pool;
+#[doc(inline)]
+pub use pool::{ConnectionPool, NoConnectionReuse, PoolConfig};
+
 pub mod proxy;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 #[non_exhaustive]
 /// An opiniated http client that can be used to serve HTTP requests.
 ///
@@ -38,9 +47,29 @@ pub mod proxy;
 /// passed through your "connector" setup. All this and more is possible by defining your own
 /// http client. Rama is here to empower you, the building blocks are there, go crazy
 /// with your own service fork and use the full power of Rust at your fingertips ;)
+///
+/// Established connections are reused by default using a [`ConnectionPool`],
+/// see the [`ConnectionPool`] docs for more information on how to opt-out of this behaviour.
 pub struct HttpClient {
-    #[cfg(all(feature = "rustls", not(feature = "boring")))]
+    #[cfg(any(feature = "rustls", feature = "boring"))]
     tls_config: Option<Arc<ClientConfig>>,
+    pool: Option<ConnectionPool>,
+    dns: HickoryDns,
+    svcb_alpn_hints: bool,
+    svcb_hints: Arc<svcb::SvcbHintCache>,
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self {
+            #[cfg(any(feature = "rustls", feature = "boring"))]
+            tls_config: None,
+            pool: Some(ConnectionPool::default()),
+            dns: HickoryDns::default(),
+            svcb_alpn_hints: false,
+            svcb_hints: Arc::default(),
+        }
+    }
 }
 
 impl HttpClient {
@@ -49,21 +78,98 @@ impl HttpClient {
         Self::default()
     }
 
-    #[cfg(all(feature = "rustls", not(feature = "boring")))]
+    /// Set the [`ConnectionPool`] used by this [`HttpClient`],
+    /// which can be shared between multiple clients.
+    pub fn set_connection_pool(&mut self, pool: ConnectionPool) -> &mut Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Replace this [`HttpClient`] with the [`ConnectionPool`] set,
+    /// which can be shared between multiple clients.
+    pub fn with_connection_pool(mut self, pool: ConnectionPool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Replace this [`HttpClient`] with an option of [`ConnectionPool`] set,
+    /// disabling connection reuse in case it is `None`.
+    pub fn maybe_with_connection_pool(mut self, pool: Option<ConnectionPool>) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// Disable connection reuse for this [`HttpClient`],
+    /// establishing a new connection for each request.
+    pub fn without_connection_pool(mut self) -> Self {
+        self.pool = None;
+        self
+    }
+
+    /// Returns the [`ConnectionPool`] used by this [`HttpClient`], if any.
+    pub fn connection_pool(&self) -> Option<&ConnectionPool> {
+        self.pool.as_ref()
+    }
+
+    /// Set the [`HickoryDns`] resolver used by this [`HttpClient`]
+    /// to resolve the targets of its requests.
+    pub fn set_dns(&mut self, dns: HickoryDns) -> &mut Self {
+        self.dns = dns;
+        self
+    }
+
+    /// Replace this [`HttpClient`] with the [`HickoryDns`] resolver set,
+    /// used to resolve the targets of its requests.
+    pub fn with_dns(mut self, dns: HickoryDns) -> Self {
+        self.dns = dns;
+        self
+    }
+
+    /// Use the ALPN protocols advertised by the 'HTTPS' dns records of the target
+    /// as a hint for the http version of secure requests.
+    ///
+    /// When enabled, `HTTP/1.1` requests are sent using `HTTP/2`
+    /// in case the target advertises support for `h2`.
+    /// The hints are cached per authority, unless overwritten
+    /// by a [`DnsOverwrite`] found in the [`Context`].
+    /// Disabled by default.
+    ///
+    /// [`DnsOverwrite`]: rama_dns::DnsOverwrite
+    pub fn with_svcb_alpn_hints(mut self, enabled: bool) -> Self {
+        self.svcb_alpn_hints = enabled;
+        self
+    }
+
+    /// Use the ALPN protocols advertised by the 'HTTPS' dns records of the target
+    /// as a hint for the http version of secure requests.
+    ///
+    /// When enabled, `HTTP/1.1` requests are sent using `HTTP/2`
+    /// in case the target advertises support for `h2`.
+    /// The hints are cached per authority, unless overwritten
+    /// by a [`DnsOverwrite`] found in the [`Context`].
+    /// Disabled by default.
+    ///
+    /// [`DnsOverwrite`]: rama_dns::DnsOverwrite
+    pub fn set_svcb_alpn_hints(&mut self, enabled: bool) -> &mut Self {
+        self.svcb_alpn_hints = enabled;
+        self
+    }
+
+    #[cfg(any(feature = "rustls", feature = "boring"))]
     /// Set the [`ClientConfig`] of this [`HttpClient`].
     pub fn set_tls_config(&mut self, cfg: Arc<ClientConfig>) -> &mut Self {
         self.tls_config = Some(cfg);
         self
     }
 
-    #[cfg(all(feature = "rustls", not(feature = "boring")))]
+    #[cfg(any(feature = "rustls", feature = "boring"))]
     /// Replace this [`HttpClient`] with the [`ClientConfig`] set.
     pub fn with_tls_config(mut self, cfg: Arc<ClientConfig>) -> Self {
         self.tls_config = Some(cfg);
         self
     }
 
-    #[cfg(all(feature = "rustls", not(feature = "boring")))]
+    #[cfg(any(feature = "rustls", feature = "boring"))]
     /// Replace this [`HttpClient`] with an option of [`ClientConfig`] set.
     pub fn maybe_with_tls_config(mut self, cfg: Option<Arc<ClientConfig>>) -> Self {
         self.tls_config = cfg;
@@ -81,32 +187,161 @@ where
 
     async fn serve(
         &self,
-        ctx: Context<State>,
-        req: Request<Body>,
+        mut ctx: Context<State>,
+        mut req: Request<Body>,
     ) -> Result<Self::Response, Self::Error> {
         let uri = req.uri().clone();
 
+        if self.svcb_alpn_hints && req.version() == Version::HTTP_11 {
+            if let Some(version) =
+                svcb::http_version_hint(&ctx, &self.dns, &self.svcb_hints, &uri).await
+            {
+                *req.version_mut() = version;
+            }
+        }
+
         #[cfg(all(feature = "rustls", not(feature = "boring")))]
         let connector = HttpConnector::new(
             HttpsConnector::auto(HttpProxyConnector::optional(HttpsConnector::tunnel(
-                TcpConnector::new(),
+                TcpConnector::new().with_dns(self.dns.clone()),
             )))
             .maybe_with_config(self.tls_config.clone()),
         );
         #[cfg(feature = "boring")]
-        let connector = HttpConnector::new(HttpsConnector::auto(HttpProxyConnector::optional(
-            HttpsConnector::tunnel(TcpConnector::new()),
-        )));
+        let connector = HttpConnector::new(
+            HttpsConnector::auto(HttpProxyConnector::optional(HttpsConnector::tunnel(
+                TcpConnector::new().with_dns(self.dns.clone()),
+            )))
+            .maybe_with_config(self.tls_config.clone()),
+        );
+        #[cfg(not(any(feature = "rustls", feature = "boring")))]
+        let connector = HttpConnector::new(HttpProxyConnector::optional(
+            TcpConnector::new().with_dns(self.dns.clone()),
+        ));
+
+        let pool = match &self.pool {
+            Some(pool) if !ctx.contains::<NoConnectionReuse>() => pool,
+            _ => {
+                let EstablishedClientConnection { ctx, req, conn, .. } = connector
+                    .connect(ctx, req)
+                    .await
+                    .map_err(|err| OpaqueError::from_boxed(err).with_context(|| uri.to_string()))?;
+
+                return conn
+                    .serve(ctx, req)
+                    .await
+                    .map_err(|err| OpaqueError::from_boxed(err).with_context(|| uri.to_string()));
+            }
+        };
+
+        #[cfg(any(feature = "rustls", feature = "boring"))]
+        let tls_id = self.tls_config.clone().map(pool::TlsConfigId::new);
         #[cfg(not(any(feature = "rustls", feature = "boring")))]
-        let connector = HttpConnector::new(HttpProxyConnector::optional(TcpConnector::new()));
+        let tls_id = None;
+
+        let key = pool::PoolKey::new(&mut ctx, &req, tls_id)
+            .map_err(|err| err.with_context(|| uri.to_string()))?;
+
+        let handle = match pool.checkout::<Body>(&key).await {
+            Ok(pooled) => {
+                return pooled
+                    .serve(ctx, req)
+                    .await
+                    .map_err(|err| OpaqueError::from_boxed(err).with_context(|| uri.to_string()));
+            }
+            Err(handle) => handle,
+        };
 
         let EstablishedClientConnection { ctx, req, conn, .. } = connector
             .connect(ctx, req)
             .await
             .map_err(|err| OpaqueError::from_boxed(err).with_context(|| uri.to_string()))?;
 
-        conn.serve(ctx, req)
+        conn.serve_pooled(ctx, req, pool, handle)
             .await
             .map_err(|err| OpaqueError::from_boxed(err).with_context(|| uri.to_string()))
     }
 }
+
+#[cfg(all(test, feature = "rustls", not(feature = "boring")))]
+mod tests {
+    use super::*;
+    use crate::server::HttpServer;
+    use rama_core::{rt::Executor, service::service_fn, Layer};
+    use rama_dns::{
+        server::{proto::op::Message, DnsRequest, DnsTransport, InMemoryDnsService},
+        DnsResolver, DohResolver, InMemoryDns,
+    };
+    use rama_http_types::{dep::http_body_util::BodyExt, Body, StatusCode};
+    use rama_net::address::{Domain, Host};
+    use rama_tcp::server::TcpListener;
+    use rama_tls::{
+        mitm::CertificateAuthority,
+        rustls::{
+            server::{MitmServerConfigProvider, TlsAcceptorLayer},
+            verify::NoServerCertVerifier,
+        },
+    };
+    use std::{convert::Infallible, net::Ipv4Addr};
+
+    #[tokio::test]
+    async fn test_doh_resolver_over_http_client() {
+        let server_config =
+            MitmServerConfigProvider::new(CertificateAuthority::generate("rama test ca").unwrap())
+                .server_config_for(&Host::Address(Ipv4Addr::LOCALHOST.into()))
+                .unwrap();
+
+        let mut dns = InMemoryDns::new();
+        dns.insert(
+            Domain::from_static("example.internal"),
+            vec![[10, 0, 0, 1].into()],
+        );
+        let dns = InMemoryDnsService::new(dns);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(listener.serve(TlsAcceptorLayer::new(server_config).layer(
+            HttpServer::auto(Executor::default()).service(service_fn(
+                move |ctx: Context<()>, req: Request| {
+                    let dns = dns.clone();
+                    async move {
+                        let query = req.into_body().collect().await.unwrap().to_bytes();
+                        let query = Message::from_vec(&query).unwrap();
+                        let response = dns
+                            .serve(ctx, DnsRequest::new(query, DnsTransport::Tcp))
+                            .await?;
+                        Ok::<_, Infallible>(
+                            Response::builder()
+                                .status(StatusCode::OK)
+                                .body(Body::from(response.into_message().to_vec().unwrap()))
+                                .unwrap(),
+                        )
+                    }
+                },
+            )),
+        )));
+
+        let client_config = ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoServerCertVerifier::default()))
+            .with_no_client_auth();
+        let client = HttpClient::default().with_tls_config(Arc::new(client_config));
+        let resolver = DohResolver::new(
+            client.clone(),
+            format!("https://{addr}/dns-query").parse().unwrap(),
+        );
+
+        for _ in 0..2 {
+            let ips = resolver
+                .ipv4_lookup(Domain::from_static("example.internal"))
+                .await
+                .unwrap();
+            assert_eq!(ips, vec![Ipv4Addr::new(10, 0, 0, 1)]);
+        }
+        resolver
+            .ipv4_lookup(Domain::from_static("unknown.internal"))
+            .await
+            .unwrap_err();
+        assert_eq!(client.connection_pool().unwrap().idle_connections(), 1);
+    }
+}
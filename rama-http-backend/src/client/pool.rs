@@ -0,0 +1,900 @@
+//! Connection pool used by the [`HttpClient`] to reuse established connections.
+//!
+//! Connections are pooled per combination of protocol, authority,
+//! proxy address, http version and tls configuration:
+//!
+//! - http/1 connections are reused one request at a time,
+//!   and returned to the pool once the response body is consumed;
+//! - http/2 connections are shared (multiplexed) by all requests for the same key.
+//!
+//! As the http version of a tls connection is negotiated using ALPN,
+//! connections of either version can end up pooled for the same key.
+//!
+//! Broken (closed) connections are evicted on checkout,
+//! while idle connections expire after the configured idle timeout,
+//! which for http/2 connections is counted from the last request started on them.
+//! A background task, started on first use of the pool,
+//! periodically closes the expired connections and forgets the hosts
+//! which no longer have any connection pooled.
+//!
+//! Pooling can be disabled for the entire [`HttpClient`] using
+//! [`HttpClient::without_connection_pool`], or for a single request
+//! by inserting the [`NoConnectionReuse`] marker in the [`Context`].
+//! The latter can be useful for proxy use cases where a fresh connection
+//! is required for each incoming request.
+//!
+//! [`HttpClient`]: super::HttpClient
+//! [`HttpClient::without_connection_pool`]: super::HttpClient::without_connection_pool
+
+use super::{svc::SendRequest, HttpClientService};
+use bytes::Bytes;
+use rama_core::error::{BoxError, OpaqueError};
+use rama_core::Context;
+use rama_http_types::{
+    conn::{H2ClientContextParams, Http1ClientContextParams},
+    dep::http_body,
+    Request, Response, Version,
+};
+#[cfg(feature = "tls")]
+use rama_net::tls::client::ClientHelloEmulation;
+use rama_net::{address::Authority, address::ProxyAddress, http::RequestContext, Protocol};
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, VecDeque},
+    fmt,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+    task::{ready, Poll},
+    time::{Duration, Instant},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+const DEFAULT_MAX_IDLE_PER_HOST: usize = 32;
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+#[derive(Debug, Clone)]
+/// Configuration of a [`ConnectionPool`].
+pub struct PoolConfig {
+    max_idle_per_host: usize,
+    max_per_host: Option<usize>,
+    idle_timeout: Option<Duration>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_idle_per_host: DEFAULT_MAX_IDLE_PER_HOST,
+            max_per_host: None,
+            idle_timeout: Some(DEFAULT_IDLE_TIMEOUT),
+        }
+    }
+}
+
+impl PoolConfig {
+    /// Create a new [`PoolConfig`] with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of idle http/1 connections kept per host (32 by default).
+    pub fn with_max_idle_per_host(mut self, max: usize) -> Self {
+        self.max_idle_per_host = max;
+        self
+    }
+
+    /// Set the maximum number of idle http/1 connections kept per host (32 by default).
+    pub fn set_max_idle_per_host(&mut self, max: usize) -> &mut Self {
+        self.max_idle_per_host = max;
+        self
+    }
+
+    /// Set the maximum number of http/1 connections in use per host (unlimited by default),
+    /// requests wait for a connection to become available once this limit is reached.
+    pub fn with_max_per_host(mut self, max: usize) -> Self {
+        self.max_per_host = Some(max);
+        self
+    }
+
+    /// Set the maximum number of http/1 connections in use per host (unlimited by default),
+    /// requests wait for a connection to become available once this limit is reached.
+    pub fn set_max_per_host(&mut self, max: usize) -> &mut Self {
+        self.max_per_host = Some(max);
+        self
+    }
+
+    /// Set the duration after which an idle connection is evicted (90 seconds by default).
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the duration after which an idle connection is evicted (90 seconds by default).
+    pub fn set_idle_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Keep idle connections around until they are closed or evicted due to the idle limit.
+    pub fn without_idle_timeout(mut self) -> Self {
+        self.idle_timeout = None;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+#[non_exhaustive]
+/// Marker which can be inserted in the [`Context`] to ensure
+/// a request is served over a fresh connection,
+/// bypassing the [`ConnectionPool`] of the [`HttpClient`].
+///
+/// [`HttpClient`]: super::HttpClient
+pub struct NoConnectionReuse;
+
+#[derive(Clone, Default)]
+/// Pool of established http connections, shared by all clones of an [`HttpClient`].
+///
+/// See the [module docs](self) for more information.
+///
+/// [`HttpClient`]: super::HttpClient
+pub struct ConnectionPool {
+    inner: Arc<PoolInner>,
+}
+
+#[derive(Default)]
+struct PoolInner {
+    config: PoolConfig,
+    hosts: Mutex<HashMap<PoolKey, HostEntry>>,
+    sweeper_started: AtomicBool,
+}
+
+impl fmt::Debug for ConnectionPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionPool")
+            .field("config", &self.inner.config)
+            .finish()
+    }
+}
+
+impl ConnectionPool {
+    /// Create a new [`ConnectionPool`] using the given [`PoolConfig`].
+    pub fn new(config: PoolConfig) -> Self {
+        Self {
+            inner: Arc::new(PoolInner {
+                config,
+                hosts: Mutex::new(HashMap::new()),
+                sweeper_started: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// Returns the [`PoolConfig`] of this pool.
+    pub fn config(&self) -> &PoolConfig {
+        &self.inner.config
+    }
+
+    /// Returns the total number of idle (http/1) and shared (http/2) connections in this pool.
+    pub fn idle_connections(&self) -> usize {
+        let hosts = self.inner.hosts.lock().unwrap();
+        hosts.values().map(|entry| entry.conns.len()).sum()
+    }
+
+    /// Returns the number of hosts currently tracked by this pool.
+    pub fn hosts(&self) -> usize {
+        self.inner.hosts.lock().unwrap().len()
+    }
+
+    /// Evict all closed and expired connections,
+    /// and forget the hosts which are no longer in use.
+    ///
+    /// This is done periodically by the pool itself,
+    /// there is no need to call this method other than to force an early cleanup.
+    pub fn sweep(&self) {
+        self.inner.sweep();
+    }
+
+    /// Start the background task sweeping this pool, unless it was already started.
+    ///
+    /// The task only holds a weak reference to the pool,
+    /// such that it stops once the pool (and thus all [`HttpClient`]s using it) are dropped.
+    ///
+    /// [`HttpClient`]: super::HttpClient
+    fn ensure_sweeper(&self) {
+        if self.inner.sweeper_started.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            self.inner.sweeper_started.store(false, Ordering::Release);
+            return;
+        };
+
+        let interval = self
+            .inner
+            .config
+            .idle_timeout
+            .unwrap_or(DEFAULT_IDLE_TIMEOUT);
+        let pool = Arc::downgrade(&self.inner);
+        handle.spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let Some(pool) = pool.upgrade() else {
+                    return;
+                };
+                pool.sweep();
+            }
+        });
+    }
+}
+
+impl PoolInner {
+    fn sweep(&self) {
+        let idle_timeout = self.config.idle_timeout;
+        let mut hosts = self.hosts.lock().unwrap();
+        hosts.retain(|_, entry| {
+            entry.conns.evict(idle_timeout);
+            !entry.is_unused()
+        });
+        tracing::trace!(hosts = hosts.len(), "http pool: swept idle connections");
+    }
+}
+
+/// Key used to pool connections.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) struct PoolKey {
+    body: TypeId,
+    protocol: Protocol,
+    authority: Authority,
+    http2: bool,
+    proxy: Option<ProxyAddress>,
+    tls: Option<TlsConfigId>,
+    // the emulated client hello and whether its extensions are permuted
+    tls_emulation: Option<(TlsConfigId, bool)>,
+    http1_params: Option<Http1ClientContextParams>,
+    h2_params: Option<H2ClientContextParams>,
+}
+
+/// Identity of a tls client configuration, compared by pointer.
+///
+/// The configuration is kept alive as part of the key,
+/// such that its address cannot be reused by another configuration.
+#[derive(Clone)]
+pub(super) struct TlsConfigId(Arc<dyn Any + Send + Sync>);
+
+impl TlsConfigId {
+    #[cfg(feature = "tls")]
+    pub(super) fn new<T: Send + Sync + 'static>(config: Arc<T>) -> Self {
+        Self(config)
+    }
+}
+
+impl fmt::Debug for TlsConfigId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TlsConfigId")
+            .field(&Arc::as_ptr(&self.0).cast::<()>())
+            .finish()
+    }
+}
+
+impl PartialEq for TlsConfigId {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(Arc::as_ptr(&self.0), Arc::as_ptr(&other.0))
+    }
+}
+
+impl Eq for TlsConfigId {}
+
+impl std::hash::Hash for TlsConfigId {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).cast::<()>().hash(state)
+    }
+}
+
+impl PoolKey {
+    /// Compute the [`PoolKey`] for the given request,
+    /// where `tls` identifies the tls configuration used by the client (if any).
+    ///
+    /// Connection parameters found in the [`Context`] are part of the key,
+    /// as connections established using different parameters cannot be shared.
+    pub(super) fn new<State, Body>(
+        ctx: &mut Context<State>,
+        req: &Request<Body>,
+        tls: Option<TlsConfigId>,
+    ) -> Result<Self, OpaqueError>
+    where
+        State: Send + Sync + 'static,
+        Body: 'static,
+    {
+        let request_ctx: &RequestContext =
+            ctx.get_or_try_insert_with_ctx(|ctx| (ctx, req).try_into())?;
+        let protocol = request_ctx.protocol.clone();
+        let authority = request_ctx.authority.clone();
+        Ok(Self {
+            body: TypeId::of::<Body>(),
+            protocol,
+            authority,
+            http2: req.version() == Version::HTTP_2,
+            proxy: ctx.get::<ProxyAddress>().cloned(),
+            tls,
+            #[cfg(feature = "tls")]
+            tls_emulation: ctx.get::<ClientHelloEmulation>().map(|emulation| {
+                (
+                    TlsConfigId::new(emulation.shared_client_hello().clone()),
+                    emulation.permute_extensions(),
+                )
+            }),
+            #[cfg(not(feature = "tls"))]
+            tls_emulation: None,
+            http1_params: ctx.get::<Http1ClientContextParams>().cloned(),
+            h2_params: ctx.get::<H2ClientContextParams>().cloned(),
+        })
+    }
+}
+
+struct HostEntry {
+    // type erased `PooledConns<Body>`, the body type is part of the key
+    conns: Box<dyn ErasedConns>,
+    semaphore: Option<Arc<Semaphore>>,
+}
+
+impl Default for HostEntry {
+    fn default() -> Self {
+        Self {
+            conns: Box::new(NoConns),
+            semaphore: None,
+        }
+    }
+}
+
+trait ErasedConns: Send {
+    fn len(&self) -> usize;
+    /// Drop all closed connections, as well as the idle ones which expired.
+    fn evict(&mut self, idle_timeout: Option<Duration>);
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+struct NoConns;
+
+impl ErasedConns for NoConns {
+    fn len(&self) -> usize {
+        0
+    }
+
+    fn evict(&mut self, _idle_timeout: Option<Duration>) {}
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+struct PooledConns<Body> {
+    http1: VecDeque<IdleConn<hyper::client::conn::http1::SendRequest<Body>>>,
+    http2: Option<IdleConn<hyper::client::conn::http2::SendRequest<Body>>>,
+}
+
+struct IdleConn<T> {
+    sender: T,
+    idle_since: Instant,
+}
+
+impl<Body: Send + 'static> ErasedConns for PooledConns<Body> {
+    fn len(&self) -> usize {
+        self.http1.len() + self.http2.iter().len()
+    }
+
+    fn evict(&mut self, idle_timeout: Option<Duration>) {
+        self.http1.retain(|idle| {
+            !idle.sender.is_closed()
+                && idle_timeout
+                    .map(|timeout| idle.idle_since.elapsed() < timeout)
+                    .unwrap_or(true)
+        });
+        // the pool only drops its own handle, requests still in flight keep the connection open
+        if self.http2.as_ref().is_some_and(|idle| {
+            idle.sender.is_closed()
+                || idle_timeout
+                    .map(|timeout| idle.idle_since.elapsed() >= timeout)
+                    .unwrap_or_default()
+        }) {
+            self.http2 = None;
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl HostEntry {
+    /// An entry is unused if it has no pooled connections,
+    /// and no connections are checked out under its max-per-host limit.
+    fn is_unused(&self) -> bool {
+        self.conns.len() == 0
+            && self
+                .semaphore
+                .as_ref()
+                .map(|semaphore| Arc::strong_count(semaphore) == 1)
+                .unwrap_or(true)
+    }
+
+    fn conns_mut<Body: Send + 'static>(&mut self) -> &mut PooledConns<Body> {
+        if self
+            .conns
+            .as_any_mut()
+            .downcast_mut::<PooledConns<Body>>()
+            .is_none()
+        {
+            self.conns = Box::new(PooledConns::<Body> {
+                http1: VecDeque::new(),
+                http2: None,
+            });
+        }
+        self.conns
+            .as_any_mut()
+            .downcast_mut()
+            .expect("pooled conns of body type to exist")
+    }
+}
+
+/// A connection checked out of the [`ConnectionPool`].
+pub(super) enum Pooled<Body> {
+    Http1 {
+        sender: hyper::client::conn::http1::SendRequest<Body>,
+        handle: ReturnHandle,
+    },
+    Http2 {
+        sender: hyper::client::conn::http2::SendRequest<Body>,
+    },
+}
+
+/// Handle used to return an http/1 connection to the pool,
+/// keeping the max-per-host permit for as long as the connection is in use.
+pub(super) struct ReturnHandle {
+    pool: Weak<PoolInner>,
+    key: Arc<PoolKey>,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl ConnectionPool {
+    /// Checkout a pooled connection for the given key,
+    /// or a [`ReturnHandle`] that can be used to pool a new connection instead.
+    pub(super) async fn checkout<Body>(&self, key: &PoolKey) -> Result<Pooled<Body>, ReturnHandle>
+    where
+        Body: Send + 'static,
+    {
+        self.ensure_sweeper();
+
+        let semaphore = {
+            let mut hosts = self.inner.hosts.lock().unwrap();
+            let entry = hosts.entry(key.clone()).or_default();
+
+            // the protocol of a tls connection is negotiated using ALPN,
+            // such that an h2 connection can be pooled for an http/1.1 request and vice versa
+            let conns = entry.conns_mut::<Body>();
+            match conns.http2.as_mut() {
+                Some(idle) if !idle.sender.is_closed() => {
+                    tracing::trace!(authority = %key.authority, "http pool: reuse h2 connection");
+                    idle.idle_since = Instant::now();
+                    return Ok(Pooled::Http2 {
+                        sender: idle.sender.clone(),
+                    });
+                }
+                Some(_) => {
+                    tracing::trace!(authority = %key.authority, "http pool: evict closed h2 connection");
+                    conns.http2 = None;
+                }
+                None => (),
+            }
+
+            match self.inner.config.max_per_host {
+                Some(max) if !key.http2 => Some(
+                    entry
+                        .semaphore
+                        .get_or_insert_with(|| Arc::new(Semaphore::new(max)))
+                        .clone(),
+                ),
+                _ => None,
+            }
+        };
+
+        let permit = match semaphore {
+            Some(semaphore) => semaphore.acquire_owned().await.ok(),
+            None => None,
+        };
+        let handle = ReturnHandle {
+            pool: Arc::downgrade(&self.inner),
+            key: Arc::new(key.clone()),
+            _permit: permit,
+        };
+
+        loop {
+            let idle = {
+                let mut hosts = self.inner.hosts.lock().unwrap();
+                let entry = hosts.entry(key.clone()).or_default();
+                let idle = entry.conns_mut::<Body>().http1.pop_back();
+                if idle.is_none() && entry.is_unused() {
+                    hosts.remove(key);
+                }
+                idle
+            };
+            let Some(IdleConn {
+                mut sender,
+                idle_since,
+            }) = idle
+            else {
+                return Err(handle);
+            };
+
+            if sender.is_closed() || self.is_expired(idle_since) {
+                tracing::trace!(authority = %key.authority, "http pool: evict closed or expired h1 connection");
+                continue;
+            }
+            if sender.ready().await.is_err() {
+                tracing::trace!(authority = %key.authority, "http pool: evict broken h1 connection");
+                continue;
+            }
+
+            tracing::trace!(authority = %key.authority, "http pool: reuse h1 connection");
+            return Ok(Pooled::Http1 { sender, handle });
+        }
+    }
+
+    /// Add a newly established h2 connection to the pool,
+    /// unless another open connection was already pooled for the same key.
+    pub(super) fn insert_http2<Body>(
+        &self,
+        key: &PoolKey,
+        sender: &hyper::client::conn::http2::SendRequest<Body>,
+    ) where
+        Body: Send + 'static,
+    {
+        let mut hosts = self.inner.hosts.lock().unwrap();
+        let conns = hosts.entry(key.clone()).or_default().conns_mut::<Body>();
+        if conns
+            .http2
+            .as_ref()
+            .map(|idle| idle.sender.is_closed())
+            .unwrap_or(true)
+        {
+            conns.http2 = Some(IdleConn {
+                sender: sender.clone(),
+                idle_since: Instant::now(),
+            });
+        }
+    }
+
+    fn is_expired(&self, idle_since: Instant) -> bool {
+        self.inner
+            .config
+            .idle_timeout
+            .map(|timeout| idle_since.elapsed() >= timeout)
+            .unwrap_or_default()
+    }
+}
+
+impl ReturnHandle {
+    fn put_back<Body: Send + 'static>(self, sender: hyper::client::conn::http1::SendRequest<Body>) {
+        let Some(pool) = self.pool.upgrade() else {
+            return;
+        };
+        if sender.is_closed() {
+            return;
+        }
+
+        let mut hosts = pool.hosts.lock().unwrap();
+        let conns = hosts
+            .entry(PoolKey::clone(&self.key))
+            .or_default()
+            .conns_mut::<Body>();
+
+        if let Some(timeout) = pool.config.idle_timeout {
+            conns
+                .http1
+                .retain(|idle| idle.idle_since.elapsed() < timeout && !idle.sender.is_closed());
+        }
+        while !conns.http1.is_empty() && conns.http1.len() >= pool.config.max_idle_per_host {
+            conns.http1.pop_front();
+        }
+        if pool.config.max_idle_per_host > 0 {
+            tracing::trace!(authority = %self.key.authority, "http pool: return idle h1 connection");
+            conns.http1.push_back(IdleConn {
+                sender,
+                idle_since: Instant::now(),
+            });
+        }
+    }
+}
+
+impl<Body> Pooled<Body>
+where
+    Body: http_body::Body<Data: Send + 'static, Error: Into<BoxError>> + Unpin + Send + 'static,
+{
+    /// Serve the request over the pooled connection.
+    pub(super) async fn serve<State>(
+        self,
+        ctx: Context<State>,
+        req: Request<Body>,
+    ) -> Result<Response, BoxError>
+    where
+        State: Send + Sync + 'static,
+    {
+        match self {
+            Self::Http1 { sender, handle } => serve_http1(ctx, req, sender, handle).await,
+            Self::Http2 { sender } => {
+                let svc = HttpClientService(SendRequest::Http2(tokio::sync::Mutex::new(sender)));
+                rama_core::Service::serve(&svc, ctx, req).await
+            }
+        }
+    }
+}
+
+impl<Body> HttpClientService<Body>
+where
+    Body: http_body::Body<Data: Send + 'static, Error: Into<BoxError>> + Unpin + Send + 'static,
+{
+    /// Serve the request over this newly established connection,
+    /// and add the connection to the pool afterwards.
+    pub(super) async fn serve_pooled<State>(
+        self,
+        ctx: Context<State>,
+        req: Request<Body>,
+        pool: &ConnectionPool,
+        handle: ReturnHandle,
+    ) -> Result<Response, BoxError>
+    where
+        State: Send + Sync + 'static,
+    {
+        match self.0 {
+            SendRequest::Http1(sender) => serve_http1(ctx, req, sender.into_inner(), handle).await,
+            SendRequest::Http2(sender) => {
+                let sender = sender.into_inner();
+                pool.insert_http2(&handle.key, &sender);
+                Pooled::Http2 { sender }.serve(ctx, req).await
+            }
+        }
+    }
+}
+
+async fn serve_http1<State, Body>(
+    ctx: Context<State>,
+    req: Request<Body>,
+    sender: hyper::client::conn::http1::SendRequest<Body>,
+    handle: ReturnHandle,
+) -> Result<Response, BoxError>
+where
+    State: Send + Sync + 'static,
+    Body: http_body::Body<Data: Send + 'static, Error: Into<BoxError>> + Unpin + Send + 'static,
+{
+    let svc = HttpClientService(SendRequest::Http1(tokio::sync::Mutex::new(sender)));
+    let resp = rama_core::Service::serve(&svc, ctx, req).await?;
+
+    let SendRequest::Http1(sender) = svc.0 else {
+        unreachable!("http1 client service")
+    };
+    let sender = sender.into_inner();
+
+    let (parts, body) = resp.into_parts();
+    let body = PooledBody::new(body, sender, handle);
+    Ok(Response::from_parts(
+        parts,
+        rama_http_types::Body::new(body),
+    ))
+}
+
+/// Body which returns the http/1 connection to the pool
+/// once the response body has been consumed.
+struct PooledBody<Body> {
+    inner: rama_http_types::Body,
+    conn: Option<(hyper::client::conn::http1::SendRequest<Body>, ReturnHandle)>,
+}
+
+impl<Body: Send + 'static> PooledBody<Body> {
+    fn new(
+        inner: rama_http_types::Body,
+        sender: hyper::client::conn::http1::SendRequest<Body>,
+        handle: ReturnHandle,
+    ) -> Self {
+        let mut body = Self {
+            inner,
+            conn: Some((sender, handle)),
+        };
+        if http_body::Body::is_end_stream(&body.inner) {
+            body.release();
+        }
+        body
+    }
+
+    fn release(&mut self) {
+        if let Some((sender, handle)) = self.conn.take() {
+            handle.put_back(sender);
+        }
+    }
+}
+
+impl<Body: Send + 'static> http_body::Body for PooledBody<Body> {
+    type Data = Bytes;
+    type Error = OpaqueError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        let result = ready!(Pin::new(&mut self.inner).poll_frame(cx));
+        match &result {
+            None => self.release(),
+            Some(Ok(_)) if http_body::Body::is_end_stream(&self.inner) => self.release(),
+            Some(Err(_)) => {
+                // do not reuse a connection which failed mid-body
+                self.conn = None;
+            }
+            Some(Ok(_)) => (),
+        }
+        Poll::Ready(result)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::HttpClient, server::HttpServer};
+    use rama_core::{rt::Executor, service::service_fn};
+    use rama_http_types::dep::http_body_util::BodyExt;
+    use rama_net::stream::SocketInfo;
+    use rama_tcp::server::TcpListener;
+    use std::{collections::HashSet, convert::Infallible, net::SocketAddr};
+
+    type Peers = Arc<Mutex<HashSet<SocketAddr>>>;
+
+    async fn spawn_server() -> (SocketAddr, Peers) {
+        let peers = Peers::default();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server_peers = peers.clone();
+        tokio::spawn(
+            listener.serve(HttpServer::auto(Executor::default()).service(service_fn(
+                move |ctx: Context<()>, _req: Request| {
+                    let peers = server_peers.clone();
+                    async move {
+                        let peer = *ctx.get::<SocketInfo>().unwrap().peer_addr();
+                        peers.lock().unwrap().insert(peer);
+                        Ok::<_, Infallible>(Response::new(rama_http_types::Body::from("hello")))
+                    }
+                },
+            ))),
+        );
+        (addr, peers)
+    }
+
+    async fn send_requests(client: &HttpClient, addr: SocketAddr, version: Version, n: usize) {
+        for _ in 0..n {
+            let req = Request::builder()
+                .uri(format!("http://{addr}/"))
+                .version(version)
+                .body(rama_http_types::Body::empty())
+                .unwrap();
+            let resp = rama_core::Service::serve(client, Context::default(), req)
+                .await
+                .unwrap();
+            let body = resp.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body, "hello");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http1_connection_reuse() {
+        let (addr, peers) = spawn_server().await;
+        let client = HttpClient::default();
+
+        send_requests(&client, addr, Version::HTTP_11, 5).await;
+        assert_eq!(peers.lock().unwrap().len(), 1);
+        assert_eq!(client.connection_pool().unwrap().idle_connections(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_http2_connection_reuse() {
+        let (addr, peers) = spawn_server().await;
+        let client = HttpClient::default();
+
+        send_requests(&client, addr, Version::HTTP_2, 5).await;
+        assert_eq!(peers.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_http1_without_connection_pool() {
+        let (addr, peers) = spawn_server().await;
+        let client = HttpClient::default().without_connection_pool();
+
+        send_requests(&client, addr, Version::HTTP_11, 3).await;
+        assert_eq!(peers.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_http1_no_connection_reuse_marker() {
+        let (addr, peers) = spawn_server().await;
+        let client = HttpClient::default();
+
+        for _ in 0..3 {
+            let mut ctx = Context::default();
+            ctx.insert(NoConnectionReuse);
+            let req = Request::builder()
+                .uri(format!("http://{addr}/"))
+                .body(rama_http_types::Body::empty())
+                .unwrap();
+            let resp = rama_core::Service::serve(&client, ctx, req).await.unwrap();
+            let _ = resp.into_body().collect().await.unwrap();
+        }
+        assert_eq!(peers.lock().unwrap().len(), 3);
+        assert_eq!(client.connection_pool().unwrap().idle_connections(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_http1_unconsumed_body_is_not_reused() {
+        let (addr, peers) = spawn_server().await;
+        let client = HttpClient::default();
+
+        for _ in 0..2 {
+            let req = Request::builder()
+                .uri(format!("http://{addr}/"))
+                .body(rama_http_types::Body::empty())
+                .unwrap();
+            let resp = rama_core::Service::serve(&client, Context::default(), req)
+                .await
+                .unwrap();
+            drop(resp);
+        }
+        assert_eq!(peers.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_http1_idle_timeout() {
+        let (addr, peers) = spawn_server().await;
+        let client = HttpClient::default().with_connection_pool(ConnectionPool::new(
+            PoolConfig::new().with_idle_timeout(Duration::from_millis(10)),
+        ));
+
+        send_requests(&client, addr, Version::HTTP_11, 1).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        send_requests(&client, addr, Version::HTTP_11, 1).await;
+        assert_eq!(peers.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_http1_max_idle_per_host() {
+        let (addr, _) = spawn_server().await;
+        let pool = ConnectionPool::new(PoolConfig::new().with_max_idle_per_host(0));
+        let client = HttpClient::default().with_connection_pool(pool.clone());
+
+        send_requests(&client, addr, Version::HTTP_11, 2).await;
+        assert_eq!(pool.idle_connections(), 0);
+
+        pool.sweep();
+        assert_eq!(pool.hosts(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_idle_connections_are_swept() {
+        let (addr, _) = spawn_server().await;
+        let pool =
+            ConnectionPool::new(PoolConfig::new().with_idle_timeout(Duration::from_millis(20)));
+        let client = HttpClient::default().with_connection_pool(pool.clone());
+
+        send_requests(&client, addr, Version::HTTP_11, 1).await;
+        send_requests(&client, addr, Version::HTTP_2, 1).await;
+        assert_eq!(pool.idle_connections(), 2);
+        assert_eq!(pool.hosts(), 2);
+
+        // expired connections are closed and their hosts forgotten without further use of the pool
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(pool.idle_connections(), 0);
+        assert_eq!(pool.hosts(), 0);
+    }
+}
@@ -0,0 +1,339 @@
+//! Emulation of the http/2 client connection preface.
+//!
+//! The http/2 implementation writes its initial `SETTINGS` frame in a fixed order,
+//! which is reordered by the [`SettingsOrderStream`] to emulate the order of another client.
+
+use pin_project_lite::pin_project;
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+const FRAME_HEADER_LEN: usize = 9;
+const FRAME_TYPE_SETTINGS: u8 = 0x4;
+const SETTING_LEN: usize = 6;
+
+/// The pseudo headers in the order they are sent by the http/2 implementation.
+pub(super) const PSEUDO_HEADER_ORDER: [&str; 4] = [":method", ":scheme", ":authority", ":path"];
+
+/// Returns `true` in case the given pseudo header order is the one
+/// of the http/2 implementation, ignoring the pseudo headers not part of the given order.
+pub(super) fn is_pseudo_header_order_supported(order: &[String]) -> bool {
+    order.iter().map(String::as_str).eq(PSEUDO_HEADER_ORDER
+        .iter()
+        .copied()
+        .filter(|name| order.iter().any(|ordered| ordered == name)))
+}
+
+pin_project! {
+    /// A stream which reorders the settings of the initial `SETTINGS` frame,
+    /// written by the http/2 client as part of its connection preface.
+    ///
+    /// All other bytes are passed through as-is.
+    pub(super) struct SettingsOrderStream<S> {
+        #[pin]
+        inner: S,
+        order: Vec<u16>,
+        // bytes of the connection preface, collected until its SETTINGS frame is complete
+        preface: Option<Vec<u8>>,
+        // bytes accepted from the writer, not yet written to the inner stream
+        pending: Vec<u8>,
+        pending_pos: usize,
+    }
+}
+
+impl<S> SettingsOrderStream<S> {
+    /// Create a new [`SettingsOrderStream`], reordering the settings
+    /// according to the given order, or passing everything through if `None`.
+    pub(super) fn new(inner: S, order: Option<Vec<u16>>) -> Self {
+        Self {
+            inner,
+            preface: order
+                .as_ref()
+                .map(|_| Vec::with_capacity(PREFACE.len() + 64)),
+            order: order.unwrap_or_default(),
+            pending: Vec::new(),
+            pending_pos: 0,
+        }
+    }
+}
+
+impl<S: AsyncRead> AsyncRead for SettingsOrderStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.project().inner.poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite> AsyncWrite for SettingsOrderStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut this = self.project();
+        ready!(poll_write_pending(
+            this.inner.as_mut(),
+            this.pending,
+            this.pending_pos,
+            cx
+        ))?;
+
+        let Some(preface) = this.preface.as_mut() else {
+            return this.inner.poll_write(cx, buf);
+        };
+        preface.extend_from_slice(buf);
+        match settings_frame_end(preface) {
+            PrefaceState::Incomplete => (),
+            PrefaceState::Complete(end) => {
+                let payload = &preface[PREFACE.len() + FRAME_HEADER_LEN..end];
+                let payload = reorder_settings(payload, this.order);
+                let mut rewritten = Vec::with_capacity(preface.len());
+                rewritten.extend_from_slice(&preface[..PREFACE.len()]);
+                rewritten.extend_from_slice(&(payload.len() as u32).to_be_bytes()[1..]);
+                rewritten.extend_from_slice(
+                    &preface[PREFACE.len() + 3..PREFACE.len() + FRAME_HEADER_LEN],
+                );
+                rewritten.extend_from_slice(&payload);
+                rewritten.extend_from_slice(&preface[end..]);
+                *this.pending = rewritten;
+                *this.preface = None;
+            }
+            PrefaceState::Invalid => {
+                tracing::debug!(
+                    "h2 connection preface not recognised: settings order not emulated"
+                );
+                *this.pending = this.preface.take().unwrap_or_default();
+            }
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut this = self.project();
+        if let Some(preface) = this.preface.take() {
+            // flushed before the SETTINGS frame is complete, give up on reordering it
+            this.pending.extend_from_slice(&preface);
+        }
+        ready!(poll_write_pending(
+            this.inner.as_mut(),
+            this.pending,
+            this.pending_pos,
+            cx
+        ))?;
+        this.inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        self.project().inner.poll_shutdown(cx)
+    }
+}
+
+fn poll_write_pending<S: AsyncWrite>(
+    mut inner: Pin<&mut S>,
+    pending: &mut Vec<u8>,
+    pending_pos: &mut usize,
+    cx: &mut Context<'_>,
+) -> Poll<io::Result<()>> {
+    while *pending_pos < pending.len() {
+        let n = ready!(inner.as_mut().poll_write(cx, &pending[*pending_pos..]))?;
+        if n == 0 {
+            return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+        }
+        *pending_pos += n;
+    }
+    pending.clear();
+    *pending_pos = 0;
+    Poll::Ready(Ok(()))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum PrefaceState {
+    Incomplete,
+    /// The end offset of the SETTINGS frame.
+    Complete(usize),
+    Invalid,
+}
+
+fn settings_frame_end(buf: &[u8]) -> PrefaceState {
+    if buf.len() < PREFACE.len() {
+        return if PREFACE.starts_with(buf) {
+            PrefaceState::Incomplete
+        } else {
+            PrefaceState::Invalid
+        };
+    }
+    if !buf.starts_with(PREFACE) {
+        return PrefaceState::Invalid;
+    }
+    let Some(header) = buf.get(PREFACE.len()..PREFACE.len() + FRAME_HEADER_LEN) else {
+        return PrefaceState::Incomplete;
+    };
+    let payload_len = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+    if header[3] != FRAME_TYPE_SETTINGS || payload_len % SETTING_LEN != 0 {
+        return PrefaceState::Invalid;
+    }
+    let end = PREFACE.len() + FRAME_HEADER_LEN + payload_len;
+    if buf.len() < end {
+        PrefaceState::Incomplete
+    } else {
+        PrefaceState::Complete(end)
+    }
+}
+
+/// The value of a setting as defined by RFC 9113, for the settings which have one.
+fn default_setting_value(id: u16) -> Option<u32> {
+    match id {
+        0x1 => Some(4096),
+        0x2 => Some(1),
+        0x4 => Some(65_535),
+        0x5 => Some(16_384),
+        0x8 => Some(0),
+        _ => None,
+    }
+}
+
+/// Reorder the settings of the given SETTINGS frame payload.
+///
+/// Settings not part of the order are omitted if equal to their default value,
+/// and are sent last otherwise.
+fn reorder_settings(payload: &[u8], order: &[u16]) -> Vec<u8> {
+    let mut settings: Vec<(u16, u32)> = payload
+        .chunks_exact(SETTING_LEN)
+        .map(|setting| {
+            (
+                u16::from_be_bytes([setting[0], setting[1]]),
+                u32::from_be_bytes([setting[2], setting[3], setting[4], setting[5]]),
+            )
+        })
+        .collect();
+
+    let missing: Vec<_> = order
+        .iter()
+        .filter(|id| !settings.iter().any(|(sent, _)| sent == *id))
+        .collect();
+    if !missing.is_empty() {
+        tracing::warn!(
+            ?missing,
+            "h2 SETTINGS not sent by the http implementation cannot be emulated"
+        );
+    }
+
+    settings.retain(|(id, value)| order.contains(id) || default_setting_value(*id) != Some(*value));
+    let unordered: Vec<_> = settings
+        .iter()
+        .filter(|(id, _)| !order.contains(id))
+        .map(|(id, _)| id)
+        .collect();
+    if !unordered.is_empty() {
+        tracing::warn!(
+            ?unordered,
+            "h2 SETTINGS sent by the http implementation are not part of the emulated order: sent last"
+        );
+    }
+    settings.sort_by_key(|(id, _)| {
+        order
+            .iter()
+            .position(|ordered| ordered == id)
+            .unwrap_or(usize::MAX)
+    });
+
+    settings
+        .into_iter()
+        .flat_map(|(id, value)| {
+            let mut setting = [0; SETTING_LEN];
+            setting[..2].copy_from_slice(&id.to_be_bytes());
+            setting[2..].copy_from_slice(&value.to_be_bytes());
+            setting
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    fn settings_frame(settings: &[(u16, u32)]) -> Vec<u8> {
+        let mut frame = ((settings.len() * SETTING_LEN) as u32).to_be_bytes()[1..].to_vec();
+        frame.extend_from_slice(&[FRAME_TYPE_SETTINGS, 0, 0, 0, 0, 0]);
+        for (id, value) in settings {
+            frame.extend_from_slice(&id.to_be_bytes());
+            frame.extend_from_slice(&value.to_be_bytes());
+        }
+        frame
+    }
+
+    #[test]
+    fn test_pseudo_header_order_supported() {
+        let order = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| (*name).to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert!(is_pseudo_header_order_supported(&order(&[
+            ":method",
+            ":scheme",
+            ":authority",
+            ":path"
+        ])));
+        assert!(is_pseudo_header_order_supported(&order(&[
+            ":method", ":path"
+        ])));
+        assert!(!is_pseudo_header_order_supported(&order(&[
+            ":method",
+            ":authority",
+            ":scheme",
+            ":path"
+        ])));
+        assert!(!is_pseudo_header_order_supported(&order(&[":protocol"])));
+    }
+
+    #[tokio::test]
+    async fn test_settings_order_stream() {
+        let sent = settings_frame(&[(2, 0), (4, 6291456), (5, 16384), (6, 262144)]);
+        let window_update = [0, 0, 4, 8, 0, 0, 0, 0, 0, 0, 0xee, 0, 1];
+
+        let mut stream = SettingsOrderStream::new(Vec::new(), Some(vec![1, 6, 2, 4]));
+        // written in pieces, followed by the WINDOW_UPDATE frame
+        stream.write_all(&PREFACE[..10]).await.unwrap();
+        stream.write_all(&PREFACE[10..]).await.unwrap();
+        stream.write_all(&sent[..12]).await.unwrap();
+        let mut rest = sent[12..].to_vec();
+        rest.extend_from_slice(&window_update);
+        stream.write_all(&rest).await.unwrap();
+        stream.write_all(b"next").await.unwrap();
+        stream.flush().await.unwrap();
+
+        let mut expected = PREFACE.to_vec();
+        expected.extend(settings_frame(&[(6, 262144), (2, 0), (4, 6291456)]));
+        expected.extend_from_slice(&window_update);
+        expected.extend_from_slice(b"next");
+        assert_eq!(stream.inner, expected);
+    }
+
+    #[tokio::test]
+    async fn test_settings_order_stream_passthrough() {
+        let mut data = PREFACE.to_vec();
+        data.extend(settings_frame(&[(2, 0), (4, 65535)]));
+
+        let mut stream = SettingsOrderStream::new(Vec::new(), None);
+        stream.write_all(&data).await.unwrap();
+        stream.flush().await.unwrap();
+        assert_eq!(stream.inner, data);
+
+        // not an h2 connection preface
+        let mut stream = SettingsOrderStream::new(Vec::new(), Some(vec![4, 2]));
+        stream.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        stream.flush().await.unwrap();
+        assert_eq!(stream.inner, b"GET / HTTP/1.1\r\n\r\n");
+    }
+}
@@ -32,8 +32,18 @@ where
     async fn serve(
         &self,
         mut ctx: Context<State>,
-        req: Request<Body>,
+        mut req: Request<Body>,
     ) -> Result<Self::Response, Self::Error> {
+        // the version of the request has to match the protocol of the connection,
+        // which can differ from the requested one, e.g. due to the tls (ALPN) negotiation
+        match &self.0 {
+            SendRequest::Http1(_) if req.version() == Version::HTTP_2 => {
+                *req.version_mut() = Version::HTTP_11;
+            }
+            SendRequest::Http2(_) => *req.version_mut() = Version::HTTP_2,
+            SendRequest::Http1(_) => (),
+        }
+
         // sanitize subject line request uri
         // because Hyper (http) writes the URI as-is
         //
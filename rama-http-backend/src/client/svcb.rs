@@ -0,0 +1,243 @@
+use rama_core::{
+    error::{BoxError, OpaqueError},
+    Context,
+};
+use rama_dns::{DnsOverwrite, DnsResolver, SvcbRecord};
+use rama_http_types::{Uri, Version};
+use rama_net::address::{Authority, Domain, Host};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// How long a resolved hint is remembered.
+const HINT_TTL: Duration = Duration::from_secs(300);
+/// Maximum amount of authorities for which a hint is remembered.
+const MAX_HINTS: usize = 1024;
+
+#[derive(Debug, Default)]
+/// Cache of the http [`Version`] hints, per [`Authority`].
+pub(super) struct SvcbHintCache {
+    hints: Mutex<HashMap<Authority, Hint>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Hint {
+    version: Option<Version>,
+    resolved_at: Instant,
+}
+
+impl Hint {
+    fn is_expired(&self) -> bool {
+        self.resolved_at.elapsed() >= HINT_TTL
+    }
+}
+
+impl SvcbHintCache {
+    fn get(&self, authority: &Authority) -> Option<Hint> {
+        let hints = self.hints.lock().unwrap();
+        hints
+            .get(authority)
+            .filter(|hint| !hint.is_expired())
+            .copied()
+    }
+
+    fn insert(&self, authority: Authority, version: Option<Version>) {
+        let mut hints = self.hints.lock().unwrap();
+        if hints.len() >= MAX_HINTS {
+            hints.retain(|_, hint| !hint.is_expired());
+            if hints.len() >= MAX_HINTS {
+                hints.clear();
+            }
+        }
+        let resolved_at = Instant::now();
+        hints.insert(
+            authority,
+            Hint {
+                version,
+                resolved_at,
+            },
+        );
+    }
+}
+
+/// Returns the http [`Version`] hinted by the ALPN protocols
+/// of the 'HTTPS' records of the host of the given [`Uri`], if any.
+///
+/// Only secure requests to a domain are hinted, as the ALPN protocols
+/// are negotiated as part of the tls handshake.
+///
+/// The records of the [`DnsOverwrite`] found in the [`Context`] take precedence
+/// over those resolved using the given [`DnsResolver`], of which the hints are cached.
+pub(super) async fn http_version_hint<State, Dns>(
+    ctx: &Context<State>,
+    dns: &Dns,
+    cache: &SvcbHintCache,
+    uri: &Uri,
+) -> Option<Version>
+where
+    Dns: DnsResolver<Error: Into<BoxError>>,
+{
+    if uri.scheme_str() != Some("https") {
+        return None;
+    }
+    let domain = match uri.host()?.parse::<Host>().ok()? {
+        Host::Name(domain) => domain,
+        Host::Address(_) => return None,
+    };
+
+    if let Some(dns_overwrite) = ctx.get::<DnsOverwrite>() {
+        if let Ok(records) = dns_overwrite.https_lookup(domain.clone()).await {
+            return alpn_hint(&domain, records);
+        }
+    }
+
+    let authority = Authority::new(domain.clone().into(), uri.port_u16().unwrap_or(443));
+    if let Some(hint) = cache.get(&authority) {
+        return hint.version;
+    }
+
+    let version = match dns.https_lookup(domain.clone()).await {
+        Ok(records) => alpn_hint(&domain, records),
+        Err(err) => {
+            let err = OpaqueError::from_boxed(err.into());
+            tracing::trace!(err = %err, "failed to resolve https records for {domain}");
+            None
+        }
+    };
+    cache.insert(authority, version);
+    version
+}
+
+fn alpn_hint(domain: &Domain, mut records: Vec<SvcbRecord>) -> Option<Version> {
+    records.retain(|record| !record.is_alias());
+    let record = records.iter().min_by_key(|record| record.priority())?;
+
+    let version = record
+        .alpn()
+        .iter()
+        .any(|alpn| alpn == "h2")
+        .then_some(Version::HTTP_2);
+    tracing::trace!(
+        "https record of {domain} advertises alpn {:?}: use http version {version:?}",
+        record.alpn()
+    );
+    version
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_dns::InMemoryDns;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    fn record(priority: u16, alpn: &[&str]) -> SvcbRecord {
+        SvcbRecord::new(priority, None).with_alpn(alpn.iter().map(|s| (*s).to_owned()).collect())
+    }
+
+    #[tokio::test]
+    async fn test_http_version_hint() {
+        let mut dns = InMemoryDns::new();
+        dns.insert_https(
+            Domain::from_static("h2.example.com"),
+            vec![record(2, &["http/1.1"]), record(1, &["h2", "http/1.1"])],
+        )
+        .insert_https(
+            Domain::from_static("h1.example.com"),
+            vec![record(1, &["http/1.1"])],
+        )
+        .insert_https(
+            Domain::from_static("alias.example.com"),
+            vec![record(0, &["h2"])],
+        );
+
+        let ctx = Context::default();
+        let cache = SvcbHintCache::default();
+        for (uri, expected) in [
+            ("https://h2.example.com/", Some(Version::HTTP_2)),
+            ("http://h2.example.com/", None),
+            ("https://h1.example.com/", None),
+            ("https://alias.example.com/", None),
+            ("https://unknown.example.com/", None),
+            ("https://127.0.0.1/", None),
+        ] {
+            let uri: Uri = uri.parse().unwrap();
+            assert_eq!(
+                http_version_hint(&ctx, &dns, &cache, &uri).await,
+                expected,
+                "{uri}"
+            );
+        }
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct CountingDns {
+        dns: InMemoryDns,
+        lookups: Arc<AtomicUsize>,
+    }
+
+    impl DnsResolver for CountingDns {
+        type Error = <InMemoryDns as DnsResolver>::Error;
+
+        async fn ipv4_lookup(
+            &self,
+            domain: Domain,
+        ) -> Result<Vec<std::net::Ipv4Addr>, Self::Error> {
+            self.dns.ipv4_lookup(domain).await
+        }
+
+        async fn ipv6_lookup(
+            &self,
+            domain: Domain,
+        ) -> Result<Vec<std::net::Ipv6Addr>, Self::Error> {
+            self.dns.ipv6_lookup(domain).await
+        }
+
+        async fn https_lookup(&self, domain: Domain) -> Result<Vec<SvcbRecord>, Self::Error> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            self.dns.https_lookup(domain).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_version_hint_cached() {
+        let mut dns = CountingDns::default();
+        dns.dns
+            .insert_https(Domain::from_static("example.com"), vec![record(1, &["h2"])]);
+
+        let cache = SvcbHintCache::default();
+        let uri: Uri = "https://example.com/".parse().unwrap();
+        for _ in 0..3 {
+            assert_eq!(
+                http_version_hint(&Context::default(), &dns, &cache, &uri).await,
+                Some(Version::HTTP_2)
+            );
+        }
+        assert_eq!(dns.lookups.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_http_version_hint_dns_overwrite() {
+        let mut dns = CountingDns::default();
+        dns.dns
+            .insert_https(Domain::from_static("example.com"), vec![record(1, &["h2"])]);
+
+        let mut overwrite = InMemoryDns::new();
+        overwrite.insert_https(
+            Domain::from_static("example.com"),
+            vec![record(1, &["http/1.1"])],
+        );
+        let overwrite: DnsOverwrite = overwrite.into();
+        let mut ctx = Context::default();
+        ctx.insert(overwrite);
+
+        let cache = SvcbHintCache::default();
+        let uri: Uri = "https://example.com/".parse().unwrap();
+        assert_eq!(http_version_hint(&ctx, &dns, &cache, &uri).await, None);
+        assert_eq!(dns.lookups.load(Ordering::SeqCst), 0);
+    }
+}
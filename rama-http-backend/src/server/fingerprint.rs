@@ -0,0 +1,725 @@
+//! HTTP/2 fingerprinting of incoming server connections.
+//!
+//! The [`HttpServer`] records the connection preface of each HTTP/2 client,
+//! and makes it available as an [`Http2Fingerprint`] in the [`Context`] of
+//! every request served on that connection.
+//!
+//! [`HttpServer`]: crate::server::HttpServer
+//! [`Context`]: rama_core::Context
+
+use pin_project_lite::pin_project;
+use std::{
+    fmt::{self, Write},
+    io::IoSlice,
+    pin::Pin,
+    sync::{Arc, OnceLock},
+    task::{Context, Poll},
+};
+use tokio::io::{self, AsyncRead, AsyncWrite, ReadBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The HTTP/2 connection preface as sent by a client,
+/// recorded up to and including its first HEADERS frame.
+///
+/// Use [`Http2Fingerprint::akamai_fingerprint`] to render it
+/// as an [Akamai HTTP/2 fingerprint].
+///
+/// [Akamai HTTP/2 fingerprint]: https://www.blackhat.com/docs/eu-17/materials/eu-17-Shuster-Passive-Fingerprinting-Of-HTTP2-Clients-wp.pdf
+pub struct Http2Fingerprint {
+    settings: Vec<(u16, u32)>,
+    window_update: Option<u32>,
+    priorities: Vec<Http2Priority>,
+    headers_priority: Option<Http2Priority>,
+    pseudo_headers: Vec<PseudoHeader>,
+}
+
+impl Http2Fingerprint {
+    /// The (identifier, value) pairs of the first SETTINGS frame,
+    /// in the order as sent by the client.
+    pub fn settings(&self) -> &[(u16, u32)] {
+        &self.settings
+    }
+
+    /// The increment of the first connection-level WINDOW_UPDATE frame, if any.
+    pub fn window_update(&self) -> Option<u32> {
+        self.window_update
+    }
+
+    /// The PRIORITY frames sent prior to the first HEADERS frame.
+    pub fn priorities(&self) -> &[Http2Priority] {
+        &self.priorities
+    }
+
+    /// The priority information embedded in the first HEADERS frame, if any.
+    pub fn headers_priority(&self) -> Option<&Http2Priority> {
+        self.headers_priority.as_ref()
+    }
+
+    /// The pseudo headers of the first request, in the order as sent by the client.
+    pub fn pseudo_headers(&self) -> &[PseudoHeader] {
+        &self.pseudo_headers
+    }
+
+    /// Render the [Akamai HTTP/2 fingerprint], formatted as `S[;]|WU|P[,]|PS[,]`,
+    /// e.g. `1:65536;2:0;4:6291456;6:262144|15663105|0|m,a,s,p`.
+    ///
+    /// [Akamai HTTP/2 fingerprint]: https://www.blackhat.com/docs/eu-17/materials/eu-17-Shuster-Passive-Fingerprinting-Of-HTTP2-Clients-wp.pdf
+    pub fn akamai_fingerprint(&self) -> String {
+        let mut out = String::new();
+
+        for (i, (id, value)) in self.settings.iter().enumerate() {
+            if i > 0 {
+                out.push(';');
+            }
+            let _ = write!(out, "{id}:{value}");
+        }
+
+        match self.window_update {
+            Some(increment) => {
+                let _ = write!(out, "|{increment}|");
+            }
+            None => out.push_str("|00|"),
+        }
+
+        if self.priorities.is_empty() {
+            out.push('0');
+        }
+        for (i, priority) in self.priorities.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{priority}");
+        }
+
+        out.push('|');
+        for (i, pseudo_header) in self.pseudo_headers.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{pseudo_header}");
+        }
+
+        out
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Priority information of an HTTP/2 stream, as found in PRIORITY and HEADERS frames.
+pub struct Http2Priority {
+    /// The stream the priority applies to.
+    pub stream_id: u32,
+    /// Whether or not the stream dependency is exclusive.
+    pub exclusive: bool,
+    /// The stream this stream depends on.
+    pub dependency: u32,
+    /// The priority weight of the stream, in the range `1..=256`.
+    pub weight: u16,
+}
+
+impl fmt::Display for Http2Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}",
+            self.stream_id,
+            u8::from(self.exclusive),
+            self.dependency,
+            self.weight
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// An HTTP/2 request pseudo header.
+pub enum PseudoHeader {
+    /// The `:method` pseudo header.
+    Method,
+    /// The `:scheme` pseudo header.
+    Scheme,
+    /// The `:authority` pseudo header.
+    Authority,
+    /// The `:path` pseudo header.
+    Path,
+    /// The `:protocol` pseudo header, used for extended CONNECT requests.
+    Protocol,
+}
+
+impl PseudoHeader {
+    fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b":method" => Some(Self::Method),
+            b":scheme" => Some(Self::Scheme),
+            b":authority" => Some(Self::Authority),
+            b":path" => Some(Self::Path),
+            b":protocol" => Some(Self::Protocol),
+            _ => None,
+        }
+    }
+
+    /// Name of the pseudo header, including its `:` prefix.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Method => ":method",
+            Self::Scheme => ":scheme",
+            Self::Authority => ":authority",
+            Self::Path => ":path",
+            Self::Protocol => ":protocol",
+        }
+    }
+}
+
+impl fmt::Display for PseudoHeader {
+    /// Renders the single letter used in the Akamai fingerprint.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Method => "m",
+            Self::Scheme => "s",
+            Self::Authority => "a",
+            Self::Path => "p",
+            Self::Protocol => "r",
+        })
+    }
+}
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// Maximum amount of frame data buffered while looking for the first HEADERS frame.
+const MAX_BUFFER_SIZE: usize = 64 * 1024;
+
+const FRAME_HEADER_SIZE: usize = 9;
+
+const FRAME_TYPE_HEADERS: u8 = 0x1;
+const FRAME_TYPE_PRIORITY: u8 = 0x2;
+const FRAME_TYPE_SETTINGS: u8 = 0x4;
+const FRAME_TYPE_WINDOW_UPDATE: u8 = 0x8;
+
+const FLAG_ACK: u8 = 0x1;
+const FLAG_PADDED: u8 = 0x8;
+const FLAG_PRIORITY: u8 = 0x20;
+
+#[derive(Debug, Default)]
+/// Incremental parser of the client side of an HTTP/2 connection preface.
+struct PrefaceParser {
+    preface_matched: usize,
+    buffer: Vec<u8>,
+    settings: Option<Vec<(u16, u32)>>,
+    window_update: Option<u32>,
+    priorities: Vec<Http2Priority>,
+}
+
+enum ParseResult {
+    Pending,
+    Done(Http2Fingerprint),
+    Abort,
+}
+
+impl PrefaceParser {
+    fn feed(&mut self, mut data: &[u8]) -> ParseResult {
+        if self.preface_matched < PREFACE.len() {
+            let n = data.len().min(PREFACE.len() - self.preface_matched);
+            if data[..n] != PREFACE[self.preface_matched..self.preface_matched + n] {
+                return ParseResult::Abort;
+            }
+            self.preface_matched += n;
+            data = &data[n..];
+        }
+
+        self.buffer.extend_from_slice(data);
+
+        let mut offset = 0;
+        while let Some(header) = self.buffer.get(offset..offset + FRAME_HEADER_SIZE) {
+            let length = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+            let kind = header[3];
+            let flags = header[4];
+            let stream_id =
+                u32::from_be_bytes([header[5], header[6], header[7], header[8]]) & 0x7fff_ffff;
+
+            let start = offset + FRAME_HEADER_SIZE;
+            if start + length > MAX_BUFFER_SIZE {
+                return ParseResult::Abort;
+            }
+            let Some(payload) = self.buffer.get(start..start + length) else {
+                break;
+            };
+
+            match kind {
+                FRAME_TYPE_SETTINGS
+                    if stream_id == 0 && flags & FLAG_ACK == 0 && self.settings.is_none() =>
+                {
+                    self.settings = Some(
+                        payload
+                            .chunks_exact(6)
+                            .map(|chunk| {
+                                (
+                                    u16::from_be_bytes([chunk[0], chunk[1]]),
+                                    u32::from_be_bytes([chunk[2], chunk[3], chunk[4], chunk[5]]),
+                                )
+                            })
+                            .collect(),
+                    );
+                }
+                FRAME_TYPE_WINDOW_UPDATE
+                    if stream_id == 0 && payload.len() == 4 && self.window_update.is_none() =>
+                {
+                    self.window_update = Some(
+                        u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]])
+                            & 0x7fff_ffff,
+                    );
+                }
+                FRAME_TYPE_PRIORITY => {
+                    if let Some(priority) = parse_priority(stream_id, payload) {
+                        self.priorities.push(priority);
+                    }
+                }
+                FRAME_TYPE_HEADERS => {
+                    let (headers_priority, pseudo_headers) =
+                        parse_headers(stream_id, flags, payload);
+                    return ParseResult::Done(Http2Fingerprint {
+                        settings: self.settings.take().unwrap_or_default(),
+                        window_update: self.window_update,
+                        priorities: std::mem::take(&mut self.priorities),
+                        headers_priority,
+                        pseudo_headers,
+                    });
+                }
+                _ => (),
+            }
+
+            offset = start + length;
+        }
+
+        self.buffer.drain(..offset);
+        ParseResult::Pending
+    }
+}
+
+fn parse_priority(stream_id: u32, payload: &[u8]) -> Option<Http2Priority> {
+    let payload = payload.get(..5)?;
+    let dependency = u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]);
+    Some(Http2Priority {
+        stream_id,
+        exclusive: dependency & 0x8000_0000 != 0,
+        dependency: dependency & 0x7fff_ffff,
+        weight: payload[4] as u16 + 1,
+    })
+}
+
+fn parse_headers(
+    stream_id: u32,
+    flags: u8,
+    mut payload: &[u8],
+) -> (Option<Http2Priority>, Vec<PseudoHeader>) {
+    if flags & FLAG_PADDED != 0 {
+        let Some((&pad_length, rest)) = payload.split_first() else {
+            return (None, Vec::new());
+        };
+        payload = &rest[..rest.len().saturating_sub(pad_length as usize)];
+    }
+
+    let mut priority = None;
+    if flags & FLAG_PRIORITY != 0 {
+        priority = parse_priority(stream_id, payload);
+        payload = payload.get(5..).unwrap_or_default();
+    }
+
+    (priority, decode_pseudo_headers(payload))
+}
+
+/// Decode the pseudo headers at the start of an HPACK encoded header block.
+///
+/// Only header names are decoded, and decoding stops at the first regular
+/// header or any representation we cannot (or do not need to) decode,
+/// such as huffman encoded header names.
+fn decode_pseudo_headers(mut block: &[u8]) -> Vec<PseudoHeader> {
+    // names of the entries inserted in the dynamic table, oldest first
+    let mut dynamic_names: Vec<Option<PseudoHeader>> = Vec::new();
+    let mut pseudo_headers = Vec::new();
+
+    while let Some(&first) = block.first() {
+        let (name, indexed) = if first & 0x80 != 0 {
+            // indexed header field
+            let Some((index, _)) = decode_integer(&mut block, 7) else {
+                break;
+            };
+            (lookup_name(index, &dynamic_names), false)
+        } else if first & 0xe0 == 0x20 {
+            // dynamic table size update
+            if decode_integer(&mut block, 5).is_none() {
+                break;
+            }
+            continue;
+        } else {
+            // literal header field, with (0x40) or without indexing
+            let indexed = first & 0xc0 == 0x40;
+            let prefix = if indexed { 6 } else { 4 };
+            let Some((index, _)) = decode_integer(&mut block, prefix) else {
+                break;
+            };
+            let name = if index == 0 {
+                match decode_string(&mut block) {
+                    Some(name) => PseudoHeader::from_name(name),
+                    None => break,
+                }
+            } else {
+                lookup_name(index, &dynamic_names)
+            };
+            if decode_string(&mut block).is_none() {
+                break;
+            }
+            (name, indexed)
+        };
+
+        let Some(name) = name else {
+            break;
+        };
+        if indexed {
+            dynamic_names.push(Some(name));
+        }
+        pseudo_headers.push(name);
+    }
+
+    pseudo_headers
+}
+
+/// Lookup the name of the header at the given index,
+/// returning `None` in case it is not a known pseudo header.
+fn lookup_name(index: usize, dynamic_names: &[Option<PseudoHeader>]) -> Option<PseudoHeader> {
+    match index {
+        1 => Some(PseudoHeader::Authority),
+        2 | 3 => Some(PseudoHeader::Method),
+        4 | 5 => Some(PseudoHeader::Path),
+        6 | 7 => Some(PseudoHeader::Scheme),
+        62.. => dynamic_names
+            .len()
+            .checked_sub(index - 61)
+            .and_then(|i| dynamic_names[i]),
+        _ => None,
+    }
+}
+
+/// Decode an HPACK integer with the given prefix size (in bits),
+/// returning the integer and the flags of the first octet.
+fn decode_integer(block: &mut &[u8], prefix: u8) -> Option<(usize, u8)> {
+    let (&first, mut rest) = block.split_first()?;
+    let mask = (1u8 << prefix) - 1;
+    let mut value = (first & mask) as usize;
+    if value == mask as usize {
+        let mut shift = 0;
+        loop {
+            let (&byte, tail) = rest.split_first()?;
+            rest = tail;
+            value = value.checked_add(((byte & 0x7f) as usize).checked_shl(shift)?)?;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            if shift > 28 {
+                return None;
+            }
+        }
+    }
+    *block = rest;
+    Some((value, first & !mask))
+}
+
+/// Decode an HPACK string literal.
+///
+/// Huffman encoded strings are returned as empty,
+/// as these are not used for the header names we care about.
+fn decode_string<'a>(block: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let (length, flags) = decode_integer(block, 7)?;
+    let value = block.get(..length)?;
+    *block = &block[length..];
+    if flags & 0x80 != 0 {
+        return Some(b"");
+    }
+    Some(value)
+}
+
+pin_project! {
+    /// A stream that records the HTTP/2 connection preface of the client,
+    /// storing the resulting [`Http2Fingerprint`] once the first HEADERS frame is read.
+    ///
+    /// Streams which do not start with the HTTP/2 preface are left untouched.
+    pub(crate) struct Http2FingerprintStream<S> {
+        #[pin]
+        inner: S,
+        parser: Option<Box<PrefaceParser>>,
+        fingerprint: Arc<OnceLock<Http2Fingerprint>>,
+    }
+}
+
+impl<S> Http2FingerprintStream<S> {
+    /// Wrap the given stream, storing its fingerprint in the given slot.
+    pub(crate) fn new(inner: S, fingerprint: Arc<OnceLock<Http2Fingerprint>>) -> Self {
+        Self {
+            inner,
+            parser: Some(Box::default()),
+            fingerprint,
+        }
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for Http2FingerprintStream<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Http2FingerprintStream")
+            .field("inner", &self.inner)
+            .field("fingerprint", &self.fingerprint)
+            .finish()
+    }
+}
+
+impl<S: AsyncRead> AsyncRead for Http2FingerprintStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.project();
+        let filled = buf.filled().len();
+        let result = this.inner.poll_read(cx, buf);
+
+        if let (Poll::Ready(Ok(())), Some(parser)) = (&result, this.parser.as_mut()) {
+            let data = &buf.filled()[filled..];
+            if data.is_empty() {
+                *this.parser = None;
+            } else {
+                match parser.feed(data) {
+                    ParseResult::Pending => (),
+                    ParseResult::Done(fingerprint) => {
+                        let _ = this.fingerprint.set(fingerprint);
+                        *this.parser = None;
+                    }
+                    ParseResult::Abort => {
+                        *this.parser = None;
+                    }
+                }
+            }
+        }
+
+        result
+    }
+}
+
+impl<S: AsyncWrite> AsyncWrite for Http2FingerprintStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.project().inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.project().inner.poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    fn frame(kind: u8, flags: u8, stream_id: u32, payload: &[u8]) -> Vec<u8> {
+        let mut out = (payload.len() as u32).to_be_bytes()[1..].to_vec();
+        out.push(kind);
+        out.push(flags);
+        out.extend_from_slice(&stream_id.to_be_bytes());
+        out.extend_from_slice(payload);
+        out
+    }
+
+    /// A connection preface as sent by Chrome.
+    fn chrome_preface() -> Vec<u8> {
+        let mut data = PREFACE.to_vec();
+
+        let mut settings = Vec::new();
+        for (id, value) in [(1u16, 65536u32), (2, 0), (4, 6291456), (6, 262144)] {
+            settings.extend_from_slice(&id.to_be_bytes());
+            settings.extend_from_slice(&value.to_be_bytes());
+        }
+        data.extend(frame(FRAME_TYPE_SETTINGS, 0, 0, &settings));
+        data.extend(frame(
+            FRAME_TYPE_WINDOW_UPDATE,
+            0,
+            0,
+            &15663105u32.to_be_bytes(),
+        ));
+
+        let mut headers = vec![0x80, 0, 0, 0, 255];
+        headers.extend_from_slice(&[
+            0x82, // :method GET
+            0x41, 0x0b, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'c', b'o',
+            b'm', // :authority (incremental indexing)
+            0x87, // :scheme https
+            0x84, // :path /
+            0x40, 0x0a, b'u', b's', b'e', b'r', b'-', b'a', b'g', b'e', b'n', b't', 0x01,
+            b'x', // user-agent: x
+        ]);
+        data.extend(frame(
+            FRAME_TYPE_HEADERS,
+            FLAG_PRIORITY | 0x4 | 0x1,
+            1,
+            &headers,
+        ));
+
+        data
+    }
+
+    #[test]
+    fn test_akamai_fingerprint_chrome() {
+        let mut parser = PrefaceParser::default();
+        let ParseResult::Done(fingerprint) = parser.feed(&chrome_preface()) else {
+            panic!("expected fingerprint");
+        };
+
+        assert_eq!(
+            fingerprint.akamai_fingerprint(),
+            "1:65536;2:0;4:6291456;6:262144|15663105|0|m,a,s,p"
+        );
+        assert_eq!(
+            fingerprint.headers_priority(),
+            Some(&Http2Priority {
+                stream_id: 1,
+                exclusive: true,
+                dependency: 0,
+                weight: 256,
+            })
+        );
+    }
+
+    #[test]
+    fn test_akamai_fingerprint_byte_by_byte() {
+        let mut parser = PrefaceParser::default();
+        let data = chrome_preface();
+        let (last, data) = data.split_last().unwrap();
+        for byte in data {
+            assert!(matches!(
+                parser.feed(std::slice::from_ref(byte)),
+                ParseResult::Pending
+            ));
+        }
+        let ParseResult::Done(fingerprint) = parser.feed(&[*last]) else {
+            panic!("expected fingerprint");
+        };
+        assert_eq!(
+            fingerprint.akamai_fingerprint(),
+            "1:65536;2:0;4:6291456;6:262144|15663105|0|m,a,s,p"
+        );
+    }
+
+    #[test]
+    fn test_akamai_fingerprint_priorities_and_dynamic_table() {
+        let mut data = PREFACE.to_vec();
+        data.extend(frame(FRAME_TYPE_SETTINGS, 0, 0, &[0, 3, 0, 0, 0, 100]));
+        data.extend(frame(FRAME_TYPE_SETTINGS, FLAG_ACK, 0, &[]));
+        data.extend(frame(FRAME_TYPE_PRIORITY, 0, 3, &[0, 0, 0, 0, 200]));
+        data.extend(frame(FRAME_TYPE_PRIORITY, 0, 5, &[0x80, 0, 0, 3, 100]));
+        data.extend(frame(
+            FRAME_TYPE_HEADERS,
+            0x4,
+            1,
+            &[
+                0x20, // dynamic table size update
+                0x44, 0x01, b'/', // :path (incremental indexing)
+                0x00, 0x07, b':', b'm', b'e', b't', b'h', b'o', b'd', 0x03, b'G', b'E',
+                b'T', // :method (literal name, without indexing)
+                0xbe, // :path (dynamic table)
+                0x87, // :scheme https
+            ],
+        ));
+
+        let mut parser = PrefaceParser::default();
+        let ParseResult::Done(fingerprint) = parser.feed(&data) else {
+            panic!("expected fingerprint");
+        };
+        assert_eq!(
+            fingerprint.akamai_fingerprint(),
+            "3:100|00|3:0:0:201,5:1:3:101|p,m,p,s"
+        );
+        assert!(fingerprint.headers_priority().is_none());
+    }
+
+    #[test]
+    fn test_http1_is_ignored() {
+        let mut parser = PrefaceParser::default();
+        assert!(matches!(
+            parser.feed(b"GET / HTTP/1.1\r\n\r\n"),
+            ParseResult::Abort
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fingerprint_stream() {
+        let data = chrome_preface();
+        let slot = Arc::new(OnceLock::new());
+        let mut stream =
+            Http2FingerprintStream::new((&data[..20]).chain(&data[20..]), slot.clone());
+
+        let mut output = Vec::new();
+        stream.read_to_end(&mut output).await.unwrap();
+        assert_eq!(output, data);
+        assert_eq!(
+            slot.get().unwrap().akamai_fingerprint(),
+            "1:65536;2:0;4:6291456;6:262144|15663105|0|m,a,s,p"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_server_inserts_fingerprint() {
+        use crate::{client::HttpClient, server::HttpServer};
+        use rama_core::{rt::Executor, service::service_fn, Service};
+        use rama_http_types::{dep::http_body_util::BodyExt, Body, Request, Response, Version};
+        use rama_tcp::server::TcpListener;
+        use std::convert::Infallible;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            listener.serve(HttpServer::auto(Executor::default()).service(service_fn(
+                |ctx: rama_core::Context<()>, _req: Request| async move {
+                    let fingerprint = ctx
+                        .get::<Http2Fingerprint>()
+                        .map(Http2Fingerprint::akamai_fingerprint)
+                        .unwrap_or_default();
+                    Ok::<_, Infallible>(Response::new(Body::from(fingerprint)))
+                },
+            ))),
+        );
+
+        let client = HttpClient::default();
+        for (version, expected_h2) in [(Version::HTTP_2, true), (Version::HTTP_11, false)] {
+            let req = Request::builder()
+                .uri(format!("http://{addr}/"))
+                .version(version)
+                .body(Body::empty())
+                .unwrap();
+            let resp = client
+                .serve(rama_core::Context::default(), req)
+                .await
+                .unwrap();
+            let body = resp.into_body().collect().await.unwrap().to_bytes();
+            if expected_h2 {
+                assert!(body.ends_with(b"|m,s,a,p"), "{body:?}");
+            } else {
+                assert!(body.is_empty(), "{body:?}");
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use super::{svc_hyper::HyperService, HttpServeResult};
+use super::{fingerprint::Http2FingerprintStream, svc_hyper::HyperService, HttpServeResult};
 use crate::executor::HyperExecutor;
 use hyper::server::conn::http1::Builder as Http1Builder;
 use hyper::server::conn::http2::Builder as Http2Builder;
@@ -11,6 +11,7 @@ use rama_utils::future::Fuse;
 use std::convert::Infallible;
 use std::error::Error;
 use std::pin::pin;
+use std::sync::{Arc, OnceLock};
 use tokio::select;
 
 /// A utility trait to allow any of the hyper server builders to be used
@@ -84,9 +85,13 @@ impl HyperConnServer for Http2Builder<HyperExecutor> {
         S: Service<State, Request, Response = Response, Error = Infallible>,
         Response: IntoResponse + Send + 'static,
     {
-        let stream = TokioIo::new(Box::pin(io));
+        let fingerprint = Arc::new(OnceLock::new());
+        let stream = TokioIo::new(Box::pin(Http2FingerprintStream::new(
+            io,
+            fingerprint.clone(),
+        )));
         let guard = ctx.guard().cloned();
-        let service = HyperService::new(ctx, service);
+        let service = HyperService::new(ctx, service).with_http2_fingerprint(fingerprint);
 
         let mut conn = pin!(self.serve_connection(stream, service));
 
@@ -125,9 +130,13 @@ impl HyperConnServer for AutoBuilder<HyperExecutor> {
         S: Service<State, Request, Response = Response, Error = Infallible>,
         Response: IntoResponse + Send + 'static,
     {
-        let stream = TokioIo::new(Box::pin(io));
+        let fingerprint = Arc::new(OnceLock::new());
+        let stream = TokioIo::new(Box::pin(Http2FingerprintStream::new(
+            io,
+            fingerprint.clone(),
+        )));
         let guard = ctx.guard().cloned();
-        let service = HyperService::new(ctx, service);
+        let service = HyperService::new(ctx, service).with_http2_fingerprint(fingerprint);
 
         let mut conn = pin!(self.serve_connection_with_upgrades(stream, service));
 
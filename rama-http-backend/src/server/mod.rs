@@ -7,6 +7,8 @@ pub type HttpServeResult = Result<(), rama_core::error::BoxError>;
 pub mod service;
 pub use service::HttpServer;
 
+pub mod fingerprint;
+
 mod hyper_conn;
 mod svc_hyper;
 
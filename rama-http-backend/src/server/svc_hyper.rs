@@ -1,6 +1,13 @@
+use super::fingerprint::Http2Fingerprint;
 use rama_core::{Context, Service};
 use rama_http_types::{BodyLimit, IntoResponse, Request};
-use std::{convert::Infallible, fmt, future::Future, pin::Pin, sync::Arc};
+use std::{
+    convert::Infallible,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, OnceLock},
+};
 
 /// Wrapper service that implements [`hyper::service::Service`].
 ///
@@ -12,6 +19,7 @@ use std::{convert::Infallible, fmt, future::Future, pin::Pin, sync::Arc};
 pub(crate) struct HyperService<S, T> {
     ctx: Context<S>,
     inner: Arc<T>,
+    http2_fingerprint: Option<Arc<OnceLock<Http2Fingerprint>>>,
 }
 
 impl<S, T> HyperService<S, T> {
@@ -20,8 +28,19 @@ impl<S, T> HyperService<S, T> {
         Self {
             ctx,
             inner: Arc::new(inner),
+            http2_fingerprint: None,
         }
     }
+
+    /// Insert the [`Http2Fingerprint`], once recorded in the given slot,
+    /// into the [`Context`] of each request.
+    pub(crate) fn with_http2_fingerprint(
+        mut self,
+        fingerprint: Arc<OnceLock<Http2Fingerprint>>,
+    ) -> Self {
+        self.http2_fingerprint = Some(fingerprint);
+        self
+    }
 }
 
 impl<S, T, Response> hyper::service::Service<HyperRequest> for HyperService<S, T>
@@ -36,9 +55,17 @@ where
         Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
 
     fn call(&self, req: hyper::Request<hyper::body::Incoming>) -> Self::Future {
-        let ctx = self.ctx.clone();
+        let mut ctx = self.ctx.clone();
         let inner = self.inner.clone();
 
+        if let Some(fingerprint) = self
+            .http2_fingerprint
+            .as_ref()
+            .and_then(|fingerprint| fingerprint.get())
+        {
+            ctx.insert(fingerprint.clone());
+        }
+
         let body_limit = ctx.get::<BodyLimit>().cloned();
 
         let req = match body_limit.and_then(|limit| limit.request()) {
@@ -66,6 +93,7 @@ where
         f.debug_struct("HyperService")
             .field("ctx", &self.ctx)
             .field("inner", &self.inner)
+            .field("http2_fingerprint", &self.http2_fingerprint)
             .finish()
     }
 }
@@ -79,6 +107,7 @@ where
         Self {
             ctx: self.ctx.clone(),
             inner: self.inner.clone(),
+            http2_fingerprint: self.http2_fingerprint.clone(),
         }
     }
 }
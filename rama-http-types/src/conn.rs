@@ -0,0 +1,42 @@
+//! Connection-level parameters for http clients.
+//!
+//! These can be added to the (rama) `Context` of a request in order to configure
+//! the http connection established by a client for that request,
+//! as far as the underlying http implementation allows it.
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+/// Parameters used to establish an HTTP/1.1 client connection.
+pub struct Http1ClientContextParams {
+    /// Write the names of the request headers in Title-Case (e.g. `User-Agent`),
+    /// instead of lowercase.
+    pub title_header_case: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+/// Parameters used to establish an HTTP/2 client connection.
+///
+/// Values which are `None` are left to the defaults of the http implementation.
+pub struct H2ClientContextParams {
+    /// The `SETTINGS_INITIAL_WINDOW_SIZE` value, used as the flow control window of each stream.
+    pub initial_stream_window_size: Option<u32>,
+    /// The flow control window of the connection.
+    ///
+    /// This is advertised using a `WINDOW_UPDATE` frame,
+    /// incrementing the default window of `65_535` bytes.
+    pub initial_connection_window_size: Option<u32>,
+    /// The `SETTINGS_MAX_FRAME_SIZE` value.
+    pub max_frame_size: Option<u32>,
+    /// The `SETTINGS_MAX_HEADER_LIST_SIZE` value.
+    pub max_header_list_size: Option<u32>,
+    /// The order of the settings of the initial `SETTINGS` frame, by id, e.g. `[1, 2, 4, 6]`.
+    ///
+    /// The settings sent by the http implementation are reordered accordingly,
+    /// omitting the settings not part of the order which are equal to their default value.
+    /// Settings which are not sent by the http implementation cannot be emulated.
+    pub settings_order: Option<Vec<u16>>,
+    /// The order of the pseudo headers, e.g. `[":method", ":authority", ":scheme", ":path"]`.
+    ///
+    /// The http implementation sends the pseudo headers in the order
+    /// `:method`, `:scheme`, `:authority`, `:path`, which cannot be changed.
+    pub pseudo_header_order: Option<Vec<String>>,
+}
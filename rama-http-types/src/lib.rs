@@ -71,6 +71,8 @@ pub use response::{IntoResponse, IntoResponseParts, Response};
 
 pub mod headers;
 
+pub mod conn;
+
 pub mod dep {
     //! Dependencies for rama http modules.
     //!
@@ -0,0 +1,58 @@
+use super::{ClassifiedResponse, ClassifyResponse};
+use crate::Response;
+use rama_core::layer::circuit_breaker::FailurePredicate;
+
+/// A [`FailurePredicate`] which uses a [`ClassifyResponse`] to decide
+/// whether a response is a failure, e.g. to be used by a [`CircuitBreaker`].
+///
+/// All errors are considered failures. Responses which can only be classified
+/// at the end of their stream (e.g. gRPC responses) are considered successes,
+/// as the predicate is only given access to the response head.
+///
+/// [`CircuitBreaker`]: rama_core::layer::circuit_breaker::CircuitBreaker
+#[derive(Debug, Clone, Default)]
+pub struct ClassifyResponseFailure<C> {
+    classifier: C,
+}
+
+impl<C> ClassifyResponseFailure<C> {
+    /// Create a new [`ClassifyResponseFailure`] using the given [`ClassifyResponse`].
+    pub const fn new(classifier: C) -> Self {
+        Self { classifier }
+    }
+}
+
+impl<C, B, E> FailurePredicate<Response<B>, E> for ClassifyResponseFailure<C>
+where
+    C: ClassifyResponse + Clone,
+{
+    fn is_failure(&self, result: &Result<Response<B>, E>) -> bool {
+        match result {
+            Ok(response) => matches!(
+                self.classifier.clone().classify_response(response),
+                ClassifiedResponse::Ready(Err(_))
+            ),
+            Err(_) => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::classify::ServerErrorsAsFailures;
+    use crate::StatusCode;
+
+    #[test]
+    fn test_classify_response_failure() {
+        let predicate = ClassifyResponseFailure::new(ServerErrorsAsFailures::new());
+
+        let response =
+            |status: StatusCode| Ok::<_, ()>(Response::builder().status(status).body(()).unwrap());
+
+        assert!(!predicate.is_failure(&response(StatusCode::OK)));
+        assert!(!predicate.is_failure(&response(StatusCode::NOT_FOUND)));
+        assert!(predicate.is_failure(&response(StatusCode::BAD_GATEWAY)));
+        assert!(predicate.is_failure(&Err::<Response<()>, _>(())));
+    }
+}
@@ -3,11 +3,13 @@
 use crate::{HeaderMap, Request, Response, StatusCode};
 use std::{convert::Infallible, fmt, marker::PhantomData};
 
+mod failure_predicate;
 pub(crate) mod grpc_errors_as_failures;
 mod map_failure_class;
 mod status_in_range_is_error;
 
 pub use self::{
+    failure_predicate::ClassifyResponseFailure,
     grpc_errors_as_failures::{
         GrpcCode, GrpcEosErrorsAsFailures, GrpcErrorsAsFailures, GrpcFailureClass,
     },
@@ -0,0 +1,161 @@
+use super::{Hedge, HedgeBudget, HedgeConfig};
+use rama_core::Layer;
+use rama_utils::latency::LatencyHistogram;
+use std::{fmt, sync::Arc, time::Duration};
+
+const DEFAULT_ROTATION_PERIOD: Duration = Duration::from_secs(10);
+const DEFAULT_MAX_HEDGE_RATIO: f64 = 0.1;
+const DEFAULT_MAX_HEDGE_BURST: u32 = 10;
+
+/// A [`Layer`] that produces [`Hedge`] services.
+///
+/// All services produced by the same layer (or its clones)
+/// share the same latency histogram and hedge budget.
+///
+/// See the [module docs](super) for more information.
+pub struct HedgeLayer<C> {
+    clone: C,
+    config: HedgeConfig,
+    rotation_period: Duration,
+    max_hedge_ratio: f64,
+    max_hedge_burst: u32,
+    histogram: Arc<LatencyHistogram>,
+    budget: Arc<HedgeBudget>,
+}
+
+impl<C: fmt::Debug> fmt::Debug for HedgeLayer<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HedgeLayer")
+            .field("clone", &self.clone)
+            .field("config", &self.config)
+            .field("rotation_period", &self.rotation_period)
+            .field("max_hedge_ratio", &self.max_hedge_ratio)
+            .field("max_hedge_burst", &self.max_hedge_burst)
+            .field("histogram", &self.histogram)
+            .field("budget", &self.budget)
+            .finish()
+    }
+}
+
+impl<C: Clone> Clone for HedgeLayer<C> {
+    fn clone(&self) -> Self {
+        Self {
+            clone: self.clone.clone(),
+            config: self.config.clone(),
+            rotation_period: self.rotation_period,
+            max_hedge_ratio: self.max_hedge_ratio,
+            max_hedge_burst: self.max_hedge_burst,
+            histogram: self.histogram.clone(),
+            budget: self.budget.clone(),
+        }
+    }
+}
+
+impl<C> HedgeLayer<C> {
+    /// Creates a new [`HedgeLayer`], using the given [`CloneInput`] strategy
+    /// to clone the requests which can be hedged.
+    ///
+    /// By default requests are hedged once they take longer than the 95th percentile latency
+    /// of the previous 10 seconds, with at most 10% of the requests hedged.
+    ///
+    /// [`CloneInput`]: crate::layer::retry::managed::CloneInput
+    pub fn new(clone: C) -> Self {
+        Self {
+            clone,
+            config: HedgeConfig::default(),
+            rotation_period: DEFAULT_ROTATION_PERIOD,
+            max_hedge_ratio: DEFAULT_MAX_HEDGE_RATIO,
+            max_hedge_burst: DEFAULT_MAX_HEDGE_BURST,
+            histogram: Arc::new(LatencyHistogram::new(DEFAULT_ROTATION_PERIOD)),
+            budget: Arc::new(HedgeBudget::new(
+                DEFAULT_MAX_HEDGE_RATIO,
+                DEFAULT_MAX_HEDGE_BURST,
+            )),
+        }
+    }
+
+    /// Set the latency percentile (between `0.0` and `1.0`)
+    /// after which a request is hedged.
+    pub fn with_percentile(mut self, percentile: f64) -> Self {
+        self.config.percentile = percentile.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the latency percentile (between `0.0` and `1.0`)
+    /// after which a request is hedged.
+    pub fn set_percentile(&mut self, percentile: f64) -> &mut Self {
+        self.config.percentile = percentile.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the minimum amount of latencies recorded in the previous rotation period,
+    /// before requests are hedged.
+    pub fn with_min_data_points(mut self, min: u64) -> Self {
+        self.config.min_data_points = min;
+        self
+    }
+
+    /// Set the minimum amount of latencies recorded in the previous rotation period,
+    /// before requests are hedged.
+    pub fn set_min_data_points(&mut self, min: u64) -> &mut Self {
+        self.config.min_data_points = min;
+        self
+    }
+
+    /// Set the period over which latencies are recorded,
+    /// the latencies of the previous period are used to compute the percentile.
+    pub fn with_rotation_period(mut self, period: Duration) -> Self {
+        self.set_rotation_period(period);
+        self
+    }
+
+    /// Set the period over which latencies are recorded,
+    /// the latencies of the previous period are used to compute the percentile.
+    pub fn set_rotation_period(&mut self, period: Duration) -> &mut Self {
+        self.rotation_period = period;
+        self.histogram = Arc::new(LatencyHistogram::new(period));
+        self
+    }
+
+    /// Set the maximum ratio (between `0.0` and `1.0`) of requests which can be hedged.
+    pub fn with_max_hedge_ratio(mut self, ratio: f64) -> Self {
+        self.set_max_hedge_ratio(ratio);
+        self
+    }
+
+    /// Set the maximum ratio (between `0.0` and `1.0`) of requests which can be hedged.
+    pub fn set_max_hedge_ratio(&mut self, ratio: f64) -> &mut Self {
+        self.max_hedge_ratio = ratio;
+        self.budget = Arc::new(HedgeBudget::new(ratio, self.max_hedge_burst));
+        self
+    }
+
+    /// Set the maximum amount of hedged requests which can be saved up in the budget,
+    /// and thus be sent in a burst, with a minimum of one.
+    pub fn with_max_hedge_burst(mut self, burst: u32) -> Self {
+        self.set_max_hedge_burst(burst);
+        self
+    }
+
+    /// Set the maximum amount of hedged requests which can be saved up in the budget,
+    /// and thus be sent in a burst, with a minimum of one.
+    pub fn set_max_hedge_burst(&mut self, burst: u32) -> &mut Self {
+        self.max_hedge_burst = burst;
+        self.budget = Arc::new(HedgeBudget::new(self.max_hedge_ratio, burst));
+        self
+    }
+}
+
+impl<S, C: Clone> Layer<S> for HedgeLayer<C> {
+    type Service = Hedge<S, C>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Hedge {
+            inner,
+            clone: self.clone.clone(),
+            config: self.config.clone(),
+            histogram: self.histogram.clone(),
+            budget: self.budget.clone(),
+        }
+    }
+}
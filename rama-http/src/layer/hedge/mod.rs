@@ -0,0 +1,452 @@
+//! Middleware for hedging slow requests, to reduce tail latency.
+//!
+//! The [`Hedge`] middleware sends a second (hedged) attempt of a request in case the first attempt
+//! did not complete within a latency percentile of previous requests (by default the 95th percentile),
+//! and returns the response of whichever attempt completes first, cancelling the other attempt.
+//!
+//! Hedging is only safe for idempotent requests, which is why a [`CloneInput`] strategy
+//! is required to clone the request, which can refuse to clone (and thus hedge)
+//! a request by returning `None`. The amount of hedged attempts is capped by a budget,
+//! by default to 10% of the requests, such that hedging does not overload a struggling service.
+//!
+//! No requests are hedged until enough latencies are recorded, see
+//! [`HedgeLayer::with_min_data_points`] and [`HedgeLayer::with_rotation_period`].
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{service::service_fn, Context, Layer, Service};
+//! use rama_http::layer::{hedge::HedgeLayer, retry::RetryBody};
+//! use rama_http::{Method, Request, Response};
+//! use std::convert::Infallible;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let service = HedgeLayer::new(|ctx: &Context<()>, req: &Request<RetryBody>| {
+//!     // only hedge idempotent requests
+//!     req.method()
+//!         .is_idempotent()
+//!         .then(|| (ctx.clone(), req.clone()))
+//! })
+//! .with_percentile(0.9)
+//! .layer(service_fn(|_req: Request<RetryBody>| async {
+//!     Ok::<_, Infallible>(Response::new(rama_http::Body::empty()))
+//! }));
+//!
+//! let req = Request::builder().method(Method::GET).body(rama_http::Body::empty()).unwrap();
+//! let resp = service.serve(Context::default(), req).await.unwrap();
+//! assert!(resp.status().is_success());
+//! # }
+//! ```
+//!
+//! [`CloneInput`]: crate::layer::retry::managed::CloneInput
+
+use crate::dep::http_body::Body as HttpBody;
+use crate::dep::http_body_util::BodyExt;
+use crate::layer::retry::{managed::CloneInput, RetryBody};
+use crate::Request;
+use rama_core::error::BoxError;
+use rama_core::{Context, Service};
+use rama_utils::latency::LatencyHistogram;
+use rama_utils::macros::define_inner_service_accessors;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::{fmt, time::Duration};
+use tokio::time::Instant;
+
+mod layer;
+#[doc(inline)]
+pub use layer::HedgeLayer;
+
+/// Middleware which sends a hedged attempt of slow requests,
+/// returning the response of whichever attempt completes first.
+///
+/// See the [module docs](self) for more information.
+pub struct Hedge<S, C> {
+    inner: S,
+    clone: C,
+    config: HedgeConfig,
+    histogram: Arc<LatencyHistogram>,
+    budget: Arc<HedgeBudget>,
+}
+
+impl<S: fmt::Debug, C: fmt::Debug> fmt::Debug for Hedge<S, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hedge")
+            .field("inner", &self.inner)
+            .field("clone", &self.clone)
+            .field("config", &self.config)
+            .field("histogram", &self.histogram)
+            .field("budget", &self.budget)
+            .finish()
+    }
+}
+
+impl<S: Clone, C: Clone> Clone for Hedge<S, C> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            clone: self.clone.clone(),
+            config: self.config.clone(),
+            histogram: self.histogram.clone(),
+            budget: self.budget.clone(),
+        }
+    }
+}
+
+impl<S, C> Hedge<S, C> {
+    define_inner_service_accessors!();
+}
+
+#[derive(Debug, Clone)]
+struct HedgeConfig {
+    percentile: f64,
+    min_data_points: u64,
+}
+
+impl Default for HedgeConfig {
+    fn default() -> Self {
+        Self {
+            percentile: 0.95,
+            min_data_points: 10,
+        }
+    }
+}
+
+/// Budget of hedged attempts, filled by every request.
+///
+/// Tokens are tracked in thousandths, to allow for fractional deposits.
+#[derive(Debug)]
+struct HedgeBudget {
+    deposit: u64,
+    max_balance: u64,
+    balance: AtomicU64,
+}
+
+impl HedgeBudget {
+    const TOKEN: u64 = 1000;
+
+    fn new(ratio: f64, burst: u32) -> Self {
+        Self {
+            deposit: (ratio.clamp(0.0, 1.0) * Self::TOKEN as f64) as u64,
+            max_balance: (burst.max(1) as u64) * Self::TOKEN,
+            balance: AtomicU64::new(0),
+        }
+    }
+
+    fn deposit(&self) {
+        let _ = self
+            .balance
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |balance| {
+                Some((balance + self.deposit).min(self.max_balance))
+            });
+    }
+
+    fn withdraw(&self) -> bool {
+        self.balance
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |balance| {
+                balance.checked_sub(Self::TOKEN)
+            })
+            .is_ok()
+    }
+}
+
+#[derive(Debug)]
+/// Error type for [`Hedge`]
+pub struct HedgeError {
+    kind: HedgeErrorKind,
+    inner: BoxError,
+}
+
+#[derive(Debug)]
+enum HedgeErrorKind {
+    BodyConsume,
+    Service,
+}
+
+impl fmt::Display for HedgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            HedgeErrorKind::BodyConsume => write!(f, "failed to consume body: {}", self.inner),
+            HedgeErrorKind::Service => write!(f, "service error: {}", self.inner),
+        }
+    }
+}
+
+impl std::error::Error for HedgeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.inner.as_ref())
+    }
+}
+
+impl<S, C> Hedge<S, C> {
+    async fn attempt<State, Body>(
+        &self,
+        ctx: Context<State>,
+        request: Request<Body>,
+    ) -> Result<S::Response, S::Error>
+    where
+        S: Service<State, Request<Body>>,
+        State: Send + Sync + 'static,
+        Body: Send + 'static,
+    {
+        let start = Instant::now();
+        let result = self.inner.serve(ctx, request).await;
+        if result.is_ok() {
+            self.histogram.record(start.elapsed());
+        }
+        result
+    }
+
+    fn hedge_delay(&self) -> Option<Duration> {
+        if self.histogram.samples() < self.config.min_data_points {
+            return None;
+        }
+        self.histogram.percentile(self.config.percentile)
+    }
+}
+
+impl<S, C, State, Body> Service<State, Request<Body>> for Hedge<S, C>
+where
+    S: Service<State, Request<RetryBody>, Error: Into<BoxError>>,
+    C: CloneInput<State>,
+    State: Send + Sync + 'static,
+    Body: HttpBody<Data: Send + 'static, Error: Into<BoxError>> + Send + 'static,
+{
+    type Response = S::Response;
+    type Error = HedgeError;
+
+    async fn serve(
+        &self,
+        ctx: Context<State>,
+        request: Request<Body>,
+    ) -> Result<Self::Response, Self::Error> {
+        // consume body so we can clone the request if desired
+        let (parts, body) = request.into_parts();
+        let body = body.collect().await.map_err(|e| HedgeError {
+            kind: HedgeErrorKind::BodyConsume,
+            inner: e.into(),
+        })?;
+        let request = Request::from_parts(parts, RetryBody::new(body.to_bytes()));
+
+        self.budget.deposit();
+        let hedge = self
+            .hedge_delay()
+            .and_then(|delay| Some((delay, self.clone.clone_input(&ctx, &request)?)));
+
+        let primary = self.attempt(ctx, request);
+        let result = match hedge {
+            None => primary.await,
+            Some((delay, (hedge_ctx, hedge_request))) => {
+                tokio::pin!(primary);
+                tokio::select! {
+                    result = &mut primary => result,
+                    _ = tokio::time::sleep(delay) => {
+                        if self.budget.withdraw() {
+                            tracing::trace!("hedge request after {delay:?}");
+                            let hedge = self.attempt(hedge_ctx, hedge_request);
+                            tokio::pin!(hedge);
+                            // the first successful attempt wins, dropping (and thus cancelling) the other
+                            tokio::select! {
+                                result = &mut primary => match result {
+                                    Ok(response) => Ok(response),
+                                    Err(_) => hedge.await,
+                                },
+                                result = &mut hedge => match result {
+                                    Ok(response) => Ok(response),
+                                    Err(_) => primary.await,
+                                },
+                            }
+                        } else {
+                            tracing::trace!("hedge budget exhausted: wait for original request");
+                            primary.await
+                        }
+                    }
+                }
+            }
+        };
+
+        result.map_err(|e| HedgeError {
+            kind: HedgeErrorKind::Service,
+            inner: e.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Body, BodyExtractExt, Response};
+    use rama_core::{service::service_fn, Layer};
+    use std::convert::Infallible;
+    use std::sync::atomic::AtomicUsize;
+
+    fn clone_input(
+        ctx: &Context<()>,
+        req: &Request<RetryBody>,
+    ) -> Option<(Context<()>, Request<RetryBody>)> {
+        Some((ctx.clone(), req.clone()))
+    }
+
+    fn request(s: &'static str) -> Request {
+        Request::builder().body(Body::from(s)).unwrap()
+    }
+
+    struct CancelCounter {
+        cancelled: Arc<AtomicUsize>,
+        completed: bool,
+    }
+
+    impl Drop for CancelCounter {
+        fn drop(&mut self) {
+            if !self.completed {
+                self.cancelled.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    #[derive(Clone)]
+    struct Counters {
+        calls: Arc<AtomicUsize>,
+        cancelled: Arc<AtomicUsize>,
+    }
+
+    /// Service which sleeps the amount of millis given in the body,
+    /// unless it is a hedged attempt of a "slow" request.
+    fn service(
+        counters: Counters,
+    ) -> impl Service<(), Request<RetryBody>, Response = Response, Error = Infallible> {
+        service_fn(move |req: Request<RetryBody>| {
+            let counters = counters.clone();
+            async move {
+                let attempt = counters.calls.fetch_add(1, Ordering::SeqCst);
+                let body = req.try_into_string().await.unwrap();
+                let (millis, output) = match body.as_str() {
+                    "slow" if attempt % 2 == 0 => (1000, "original"),
+                    "slow" => (10, "hedged"),
+                    millis => (millis.parse().unwrap(), "fast"),
+                };
+                let mut guard = CancelCounter {
+                    cancelled: counters.cancelled.clone(),
+                    completed: false,
+                };
+                tokio::time::sleep(Duration::from_millis(millis)).await;
+                guard.completed = true;
+                Ok(Response::new(Body::from(output)))
+            }
+        })
+    }
+
+    fn counters() -> Counters {
+        Counters {
+            calls: Arc::new(AtomicUsize::new(0)),
+            cancelled: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    async fn warm_up(
+        service: &impl Service<(), Request, Response = Response, Error = HedgeError>,
+        counters: &Counters,
+    ) {
+        for _ in 0..20 {
+            let resp = service
+                .serve(Context::default(), request("10"))
+                .await
+                .unwrap();
+            assert_eq!(resp.try_into_string().await.unwrap(), "fast");
+        }
+        // latencies of the previous rotation period are used
+        tokio::time::advance(Duration::from_secs(10)).await;
+        counters.calls.store(0, Ordering::SeqCst);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hedge_slow_request() {
+        let counters = counters();
+        let service = HedgeLayer::new(clone_input)
+            .with_max_hedge_ratio(1.0)
+            .layer(service(counters.clone()));
+
+        warm_up(&service, &counters).await;
+
+        let start = Instant::now();
+        let resp = service
+            .serve(Context::default(), request("slow"))
+            .await
+            .unwrap();
+        assert_eq!(resp.try_into_string().await.unwrap(), "hedged");
+        assert!(start.elapsed() < Duration::from_millis(100));
+        assert_eq!(counters.calls.load(Ordering::SeqCst), 2);
+        // the original attempt was cancelled
+        assert_eq!(counters.cancelled.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hedge_requires_data_points() {
+        let counters = counters();
+        let service = HedgeLayer::new(clone_input)
+            .with_max_hedge_ratio(1.0)
+            .layer(service(counters.clone()));
+
+        let resp = service
+            .serve(Context::default(), request("slow"))
+            .await
+            .unwrap();
+        assert_eq!(resp.try_into_string().await.unwrap(), "original");
+        assert_eq!(counters.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hedge_budget() {
+        let counters = counters();
+        let service = HedgeLayer::new(clone_input)
+            .with_max_hedge_ratio(0.0)
+            .layer(service(counters.clone()));
+
+        warm_up(&service, &counters).await;
+
+        let resp = service
+            .serve(Context::default(), request("slow"))
+            .await
+            .unwrap();
+        assert_eq!(resp.try_into_string().await.unwrap(), "original");
+        assert_eq!(counters.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hedge_not_cloned() {
+        let counters = counters();
+        let service = HedgeLayer::new(|_: &Context<()>, _: &Request<RetryBody>| None)
+            .with_max_hedge_ratio(1.0)
+            .layer(service(counters.clone()));
+
+        warm_up(&service, &counters).await;
+
+        let resp = service
+            .serve(Context::default(), request("slow"))
+            .await
+            .unwrap();
+        assert_eq!(resp.try_into_string().await.unwrap(), "original");
+        assert_eq!(counters.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_hedge_budget_tokens() {
+        let budget = HedgeBudget::new(0.5, 1);
+        assert!(!budget.withdraw());
+        budget.deposit();
+        assert!(!budget.withdraw());
+        budget.deposit();
+        assert!(budget.withdraw());
+        assert!(!budget.withdraw());
+
+        // balance is capped by the burst
+        for _ in 0..10 {
+            budget.deposit();
+        }
+        assert!(budget.withdraw());
+        assert!(!budget.withdraw());
+    }
+}
@@ -28,6 +28,7 @@ pub mod follow_redirect;
 pub mod forwarded;
 pub mod header_config;
 pub mod header_option_value;
+pub mod hedge;
 pub mod map_request_body;
 pub mod map_response_body;
 pub mod normalize_path;
@@ -0,0 +1,425 @@
+use crate::{
+    conn::{H2ClientContextParams, Http1ClientContextParams},
+    header::{HOST, USER_AGENT},
+    HeaderMap, HeaderName, HeaderValue, Request, Version,
+};
+use rama_core::{Context, Layer, Service};
+use rama_net::tls::client::ClientHelloEmulation;
+use rama_ua::{
+    profile::{user_agent_kind_for_http_agent, HttpProfile},
+    TlsAgent, UserAgent, UserAgentDatabase,
+};
+use rama_utils::macros::define_inner_service_accessors;
+use std::{
+    fmt::{self, Debug},
+    sync::Arc,
+};
+
+/// The http/2 default initial window size, as defined by RFC 9113.
+const H2_DEFAULT_WINDOW_SIZE: u32 = 65_535;
+
+/// A [`Service`] that emulates the [`UserAgent`] found in the [`Context`]
+/// for outgoing [`Request`]s.
+///
+/// The [`UserAgentProfile`] to emulate is looked up in the [`UserAgentDatabase`],
+/// after which, unless the [`HttpAgent`] or [`TlsAgent`] is to be preserved:
+///
+/// - the headers of the [`Request`] are ordered as the profile does,
+///   adding the default headers of the profile which are not yet defined;
+/// - the [`Http1ClientContextParams`] and [`H2ClientContextParams`] are added to the [`Context`],
+///   to configure the header casing and the http/2 settings of the connection,
+///   including the order of the SETTINGS and pseudo headers;
+/// - the [`ClientHelloEmulation`] is added to the [`Context`],
+///   to configure the tls connection, including whether its extensions are permuted.
+///
+/// The http connector warns for the orders which cannot be emulated by its http implementation,
+/// such as the pseudo header order, and so does the tls connector for the order of the tls extensions.
+///
+/// [`Request`]s are passed through as-is in case no [`UserAgent`] is found in the [`Context`].
+///
+/// [`UserAgentProfile`]: rama_ua::UserAgentProfile
+pub struct UserAgentEmulateService<S> {
+    inner: S,
+    database: Arc<UserAgentDatabase>,
+}
+
+impl<S> UserAgentEmulateService<S> {
+    /// Create a new [`UserAgentEmulateService`].
+    pub const fn new(inner: S, database: Arc<UserAgentDatabase>) -> Self {
+        Self { inner, database }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S> Debug for UserAgentEmulateService<S>
+where
+    S: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UserAgentEmulateService")
+            .field("inner", &self.inner)
+            .field("database", &self.database)
+            .finish()
+    }
+}
+
+impl<S> Clone for UserAgentEmulateService<S>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            database: self.database.clone(),
+        }
+    }
+}
+
+impl<S, State, Body> Service<State, Request<Body>> for UserAgentEmulateService<S>
+where
+    S: Service<State, Request<Body>>,
+    State: Send + Sync + 'static,
+    Body: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        mut req: Request<Body>,
+    ) -> Result<Self::Response, Self::Error> {
+        let Some(ua) = ctx.get::<UserAgent>().cloned() else {
+            return self.inner.serve(ctx, req).await;
+        };
+        let Some(profile) = self.database.get(&ua) else {
+            tracing::trace!(ua = %ua, "no user agent profile found to emulate");
+            return self.inner.serve(ctx, req).await;
+        };
+
+        let http_agent = ua.http_agent();
+        if let Some(kind) = user_agent_kind_for_http_agent(&http_agent) {
+            // the http agent can be overwritten to differ from the profile found
+            let http_profile = match UserAgent::new(profile.ua_str.as_str()).info() {
+                Some(info) if info.kind != kind => self
+                    .database
+                    .find(kind, ua.platform(), None)
+                    .or_else(|| self.database.find(kind, None, None))
+                    .map(|profile| &profile.http)
+                    .unwrap_or(&profile.http),
+                _ => &profile.http,
+            };
+            let ua_header = if ua.preserve_ua_header() {
+                ua.header_str()
+            } else {
+                profile.ua_str.as_str()
+            };
+            emulate_http(&mut ctx, &mut req, http_profile, ua_header);
+        }
+
+        if ua.tls_agent() != TlsAgent::Preserve {
+            if let Some(hello) = &profile.tls {
+                ctx.insert(
+                    ClientHelloEmulation::new(hello.clone())
+                        .with_permute_extensions(profile.tls_permute_extensions),
+                );
+            }
+        }
+
+        self.inner.serve(ctx, req).await
+    }
+}
+
+fn emulate_http<State, Body>(
+    ctx: &mut Context<State>,
+    req: &mut Request<Body>,
+    profile: &HttpProfile,
+    ua_header: &str,
+) {
+    let profile_headers = if req.version() == Version::HTTP_2 {
+        &profile.h2.headers
+    } else {
+        &profile.h1.headers
+    };
+
+    let host = req
+        .uri()
+        .authority()
+        .and_then(|authority| HeaderValue::from_str(authority.as_str()).ok());
+    let ua_header = HeaderValue::from_str(ua_header).ok();
+    let headers = std::mem::take(req.headers_mut());
+    *req.headers_mut() = emulate_headers(headers, profile_headers, host, ua_header);
+
+    ctx.insert(Http1ClientContextParams {
+        title_header_case: profile.h1.title_case_headers(),
+    });
+    ctx.insert(H2ClientContextParams {
+        initial_stream_window_size: profile.h2.setting(4),
+        initial_connection_window_size: profile
+            .h2
+            .window_update
+            .map(|increment| H2_DEFAULT_WINDOW_SIZE.saturating_add(increment)),
+        max_frame_size: profile.h2.setting(5),
+        max_header_list_size: profile.h2.setting(6),
+        settings_order: (!profile.h2.settings.is_empty())
+            .then(|| profile.h2.settings.iter().map(|(id, _)| *id).collect()),
+        pseudo_header_order: (!profile.h2.pseudo_headers.is_empty())
+            .then(|| profile.h2.pseudo_headers.clone()),
+    });
+}
+
+/// Order the headers as defined by the profile, keeping the values already defined,
+/// with the headers unknown to the profile appended in their original order.
+fn emulate_headers(
+    mut headers: HeaderMap,
+    profile_headers: &[(String, String)],
+    host: Option<HeaderValue>,
+    ua_header: Option<HeaderValue>,
+) -> HeaderMap {
+    let mut emulated = HeaderMap::with_capacity(headers.len() + profile_headers.len());
+
+    for (name, value) in profile_headers {
+        let Ok(name) = HeaderName::from_bytes(name.as_bytes()) else {
+            continue;
+        };
+        if name == USER_AGENT {
+            if let Some(ua_header) = &ua_header {
+                headers.remove(&name);
+                emulated.insert(name, ua_header.clone());
+                continue;
+            }
+        }
+        if headers.contains_key(&name) {
+            for value in headers.get_all(&name) {
+                emulated.append(name.clone(), value.clone());
+            }
+            headers.remove(&name);
+        } else if name == HOST {
+            if let Some(host) = &host {
+                emulated.insert(name, host.clone());
+            }
+        } else if !value.is_empty() {
+            if let Ok(value) = HeaderValue::from_str(value) {
+                emulated.insert(name, value);
+            }
+        }
+    }
+
+    let mut last_name = None;
+    for (name, value) in headers {
+        if let Some(name) = name {
+            last_name = Some(name);
+        }
+        if let Some(name) = &last_name {
+            emulated.append(name.clone(), value);
+        }
+    }
+
+    emulated
+}
+
+#[derive(Debug, Clone)]
+/// A [`Layer`] that wraps a [`Service`] with a [`UserAgentEmulateService`].
+///
+/// This [`Layer`] is used to emulate the [`UserAgent`] of outgoing [`Request`]s.
+pub struct UserAgentEmulateLayer {
+    database: Arc<UserAgentDatabase>,
+}
+
+impl UserAgentEmulateLayer {
+    /// Create a new [`UserAgentEmulateLayer`], using the given [`UserAgentDatabase`].
+    pub fn new(database: Arc<UserAgentDatabase>) -> Self {
+        Self { database }
+    }
+}
+
+impl Default for UserAgentEmulateLayer {
+    fn default() -> Self {
+        Self::new(Arc::new(UserAgentDatabase::embedded()))
+    }
+}
+
+impl<S> Layer<S> for UserAgentEmulateLayer {
+    type Service = UserAgentEmulateService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        UserAgentEmulateService::new(inner, self.database.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IntoResponse, Response, StatusCode};
+    use rama_core::service::service_fn;
+    use rama_ua::HttpAgent;
+    use std::convert::Infallible;
+
+    const CHROME_UA: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36";
+
+    async fn serve_emulated(ctx: Context<()>, req: Request) -> (Context<()>, Request) {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let service = UserAgentEmulateLayer::default().layer(service_fn(
+            move |ctx: Context<()>, req: Request| {
+                let tx = tx.clone();
+                async move {
+                    tx.send((ctx, req)).unwrap();
+                    Ok::<Response, Infallible>(StatusCode::OK.into_response())
+                }
+            },
+        ));
+        service.serve(ctx, req).await.unwrap();
+        rx.recv().unwrap()
+    }
+
+    fn header_names(req: &Request) -> Vec<&str> {
+        req.headers().keys().map(|name| name.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_emulate_no_user_agent() {
+        let req = Request::builder()
+            .uri("http://example.com")
+            .header("x-foo", "bar")
+            .body(crate::Body::empty())
+            .unwrap();
+        let (ctx, req) = serve_emulated(Context::default(), req).await;
+        assert_eq!(header_names(&req), ["x-foo"]);
+        assert!(ctx.get::<ClientHelloEmulation>().is_none());
+        assert!(ctx.get::<H2ClientContextParams>().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_emulate_chrome_h1() {
+        let mut ctx = Context::default();
+        ctx.insert(UserAgent::new(CHROME_UA));
+        let req = Request::builder()
+            .uri("http://example.com/foo")
+            .header("x-foo", "bar")
+            .header("accept", "application/json")
+            .header("user-agent", "curl/8.0")
+            .body(crate::Body::empty())
+            .unwrap();
+
+        let (ctx, req) = serve_emulated(ctx, req).await;
+
+        let names = header_names(&req);
+        assert_eq!(names[..3], ["host", "connection", "sec-ch-ua"]);
+        assert_eq!(names.last(), Some(&"x-foo"));
+        assert_eq!(req.headers()["host"], "example.com");
+        assert_eq!(req.headers()["accept"], "application/json");
+        assert_eq!(req.headers()["user-agent"], CHROME_UA);
+        assert_eq!(req.headers()["sec-ch-ua-platform"], "\"Windows\"");
+
+        assert!(
+            ctx.get::<Http1ClientContextParams>()
+                .unwrap()
+                .title_header_case
+        );
+        assert_eq!(
+            ctx.get::<H2ClientContextParams>(),
+            Some(&H2ClientContextParams {
+                initial_stream_window_size: Some(6291456),
+                initial_connection_window_size: Some(15663105 + 65535),
+                max_frame_size: None,
+                max_header_list_size: Some(262144),
+                settings_order: Some(vec![1, 2, 4, 6]),
+                pseudo_header_order: Some(
+                    [":method", ":authority", ":scheme", ":path"]
+                        .map(String::from)
+                        .to_vec()
+                ),
+            })
+        );
+
+        let emulation = ctx.get::<ClientHelloEmulation>().unwrap();
+        assert!(emulation.permute_extensions());
+        let hello = emulation.client_hello();
+        assert!(hello.cipher_suites().iter().any(|cs| cs.is_grease()));
+    }
+
+    #[tokio::test]
+    async fn test_emulate_h2_preserve_ua_header() {
+        let mut ua = UserAgent::new(
+            "Mozilla/5.0 (X11; Linux x86_64; rv:120.0) Gecko/20100101 Firefox/120.0",
+        );
+        ua.with_preserve_ua_header(true);
+        let mut ctx = Context::default();
+        ctx.insert(ua);
+        let req = Request::builder()
+            .uri("https://example.com")
+            .version(Version::HTTP_2)
+            .body(crate::Body::empty())
+            .unwrap();
+
+        let (ctx, req) = serve_emulated(ctx, req).await;
+
+        let names = header_names(&req);
+        assert_eq!(names[..2], ["user-agent", "accept"]);
+        assert!(!names.contains(&"host"));
+        assert_eq!(
+            req.headers()["user-agent"],
+            "Mozilla/5.0 (X11; Linux x86_64; rv:120.0) Gecko/20100101 Firefox/120.0"
+        );
+        assert_eq!(req.headers()["te"], "trailers");
+        assert!(
+            ctx.get::<Http1ClientContextParams>()
+                .unwrap()
+                .title_header_case
+        );
+        assert_eq!(
+            ctx.get::<H2ClientContextParams>().unwrap().max_frame_size,
+            Some(16384)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_emulate_preserve_agents() {
+        let mut ua = UserAgent::new(CHROME_UA);
+        ua.with_http_agent(HttpAgent::Preserve);
+        ua.with_tls_agent(TlsAgent::Preserve);
+        let mut ctx = Context::default();
+        ctx.insert(ua);
+        let req = Request::builder()
+            .uri("http://example.com")
+            .header("x-foo", "bar")
+            .body(crate::Body::empty())
+            .unwrap();
+
+        let (ctx, req) = serve_emulated(ctx, req).await;
+
+        assert_eq!(header_names(&req), ["x-foo"]);
+        assert!(ctx.get::<ClientHelloEmulation>().is_none());
+        assert!(ctx.get::<Http1ClientContextParams>().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_emulate_http_agent_overwrite() {
+        let mut ua = UserAgent::new(CHROME_UA);
+        ua.with_http_agent(HttpAgent::Safari);
+        let mut ctx = Context::default();
+        ctx.insert(ua);
+        let req = Request::builder()
+            .uri("http://example.com")
+            .body(crate::Body::empty())
+            .unwrap();
+
+        let (ctx, req) = serve_emulated(ctx, req).await;
+
+        // safari http headers, but the chrome user agent and tls
+        assert_eq!(
+            header_names(&req)[..3],
+            ["host", "accept", "sec-fetch-site"]
+        );
+        assert_eq!(req.headers()["user-agent"], CHROME_UA);
+        assert_eq!(
+            ctx.get::<H2ClientContextParams>()
+                .unwrap()
+                .initial_stream_window_size,
+            Some(4194304)
+        );
+        let hello = ctx.get::<ClientHelloEmulation>().unwrap().client_hello();
+        assert_eq!(hello.cipher_suites().len(), 16);
+    }
+}
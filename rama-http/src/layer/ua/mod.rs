@@ -1,5 +1,8 @@
 //! User-Agent (see also `rama-ua`) http layer support
 //!
+//! The [`UserAgentClassifierLayer`] classifies the [`UserAgent`] of incoming requests,
+//! while the [`UserAgentEmulateLayer`] emulates the [`UserAgent`] for outgoing requests.
+//!
 //! # Example
 //!
 //! ```
@@ -47,10 +50,14 @@ use std::{
 };
 
 pub use rama_ua::{
-    DeviceKind, HttpAgent, PlatformKind, TlsAgent, UserAgent, UserAgentInfo, UserAgentKind,
-    UserAgentOverwrites,
+    DeviceKind, HttpAgent, PlatformKind, TlsAgent, UserAgent, UserAgentDatabase, UserAgentInfo,
+    UserAgentKind, UserAgentOverwrites, UserAgentProfile,
 };
 
+mod emulate;
+#[doc(inline)]
+pub use emulate::{UserAgentEmulateLayer, UserAgentEmulateService};
+
 /// A [`Service`] that classifies the [`UserAgent`] of incoming [`Request`]s.
 ///
 /// The [`Extensions`] of the [`Context`] is updated with the [`UserAgent`]
@@ -55,7 +55,7 @@
 
 #[doc(inline)]
 pub use ::rama_http_types::{
-    header,
+    conn, header,
     response::{self, IntoResponse, Response},
     Body, BodyDataStream, BodyExtractExt, BodyLimit, HeaderMap, HeaderName, HeaderValue, Method,
     Request, Scheme, StatusCode, Uri, Version,
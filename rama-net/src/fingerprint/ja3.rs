@@ -0,0 +1,104 @@
+use crate::tls::client::{ClientHello, ClientHelloExtension};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// The [JA3] fingerprint of a TLS [`ClientHello`].
+///
+/// It is computed from the (legacy) protocol version, cipher suites,
+/// extensions, supported groups (elliptic curves) and EC point formats
+/// found in the [`ClientHello`], in the order as sent by the client.
+/// GREASE values are ignored, as defined by [RFC 8701].
+///
+/// Its [`Display`] implementation renders the MD5 hash of the fingerprint,
+/// while [`Ja3::raw`] gives access to the full fingerprint string.
+///
+/// Note that the extension order is only accurate in case the [`ClientHello`]
+/// was parsed from the raw message (e.g. when using boring),
+/// as rustls only exposes some of the extensions.
+///
+/// [JA3]: https://github.com/salesforce/ja3
+/// [RFC 8701]: https://datatracker.ietf.org/doc/html/rfc8701
+/// [`Display`]: std::fmt::Display
+pub struct Ja3 {
+    raw: String,
+}
+
+impl Ja3 {
+    /// Compute the [`Ja3`] fingerprint for the given [`ClientHello`].
+    pub fn compute(hello: &ClientHello) -> Self {
+        let version = u16::from(hello.protocol_version());
+
+        let ciphers = hello
+            .cipher_suites()
+            .iter()
+            .filter(|cs| !cs.is_grease())
+            .map(|cs| u16::from(*cs).to_string())
+            .collect::<Vec<_>>()
+            .join("-");
+
+        let extensions = hello
+            .extensions()
+            .iter()
+            .map(ClientHelloExtension::id)
+            .filter(|id| !id.is_grease())
+            .map(|id| u16::from(id).to_string())
+            .collect::<Vec<_>>()
+            .join("-");
+
+        let curves = hello
+            .ext_supported_groups()
+            .unwrap_or_default()
+            .iter()
+            .filter(|group| !group.is_grease())
+            .map(|group| u16::from(*group).to_string())
+            .collect::<Vec<_>>()
+            .join("-");
+
+        let point_formats = hello
+            .ext_ec_point_formats()
+            .unwrap_or_default()
+            .iter()
+            .map(|format| u8::from(*format).to_string())
+            .collect::<Vec<_>>()
+            .join("-");
+
+        Self {
+            raw: format!("{version},{ciphers},{extensions},{curves},{point_formats}"),
+        }
+    }
+
+    /// Return the full (unhashed) fingerprint string,
+    /// e.g. `771,4865-4866-4867,0-23-65281-10-11,29-23-24,0`.
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    /// Return the MD5 hash of the fingerprint, formatted as lowercase hex string.
+    pub fn hash(&self) -> String {
+        format!("{:x}", md5::compute(self.raw.as_bytes()))
+    }
+}
+
+impl fmt::Display for Ja3 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:x}", md5::compute(self.raw.as_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fingerprint::ja4::tests::chrome_client_hello;
+
+    #[test]
+    fn test_ja3_compute() {
+        let ja3 = Ja3::compute(&chrome_client_hello());
+        assert_eq!(
+            ja3.raw(),
+            "771,4865-4866-4867-49195-49199-49196-49200-52393-52392-49171-49172-156-157-47-53,\
+             0-23-65281-10-11-35-16-5-13-18-51-45-43-27-17513-21,29-23-24,0"
+        );
+        assert_eq!(ja3.hash(), "cd08e31494f9531f560d64c695473da9");
+        assert_eq!(ja3.to_string(), ja3.hash());
+    }
+}
@@ -0,0 +1,312 @@
+use crate::tls::{
+    client::{ClientHello, ClientHelloExtension},
+    ExtensionId, ProtocolVersion,
+};
+use sha2::{Digest, Sha256};
+use std::fmt::{self, Write};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// The [JA4] fingerprint of a TLS [`ClientHello`].
+///
+/// GREASE values are ignored, as defined by [RFC 8701].
+///
+/// Its [`Display`] implementation renders the default (hashed and sorted) fingerprint,
+/// e.g. `t13d1516h2_8daaf6152771_e5627efa2ab1`, while the other variants
+/// are available via [`Ja4::raw`] (`ja4_r`), [`Ja4::original`] (`ja4_o`)
+/// and [`Ja4::original_raw`] (`ja4_ro`).
+///
+/// Only TLS over TCP (`t`) and DTLS (`d`) are detected,
+/// as QUIC (`q`) cannot be derived from the [`ClientHello`] alone.
+///
+/// [JA4]: https://github.com/FoxIO-LLC/ja4/blob/main/technical_details/JA4.md
+/// [RFC 8701]: https://datatracker.ietf.org/doc/html/rfc8701
+/// [`Display`]: std::fmt::Display
+pub struct Ja4 {
+    protocol: char,
+    version: &'static str,
+    has_server_name: bool,
+    alpn: String,
+    cipher_suites: Vec<u16>,
+    extensions: Vec<u16>,
+    signature_algorithms: Vec<u16>,
+}
+
+impl Ja4 {
+    /// Compute the [`Ja4`] fingerprint for the given [`ClientHello`].
+    pub fn compute(hello: &ClientHello) -> Self {
+        let (protocol, version) = hello
+            .supported_versions()
+            .unwrap_or_default()
+            .iter()
+            .filter(|version| !version.is_grease())
+            .filter_map(|version| ja4_version(*version))
+            .max_by_key(|(rank, ..)| *rank)
+            .or_else(|| ja4_version(hello.protocol_version()))
+            .map(|(_, protocol, version)| (protocol, version))
+            .unwrap_or(('t', "00"));
+
+        let alpn = hello
+            .ext_alpn()
+            .and_then(|alpns| alpns.first())
+            .map(|alpn| ja4_alpn(alpn.as_bytes()))
+            .unwrap_or_else(|| "00".to_owned());
+
+        Self {
+            protocol,
+            version,
+            has_server_name: hello
+                .extensions()
+                .iter()
+                .any(|ext| matches!(ext, ClientHelloExtension::ServerName(_))),
+            alpn,
+            cipher_suites: hello
+                .cipher_suites()
+                .iter()
+                .filter(|cs| !cs.is_grease())
+                .map(|cs| u16::from(*cs))
+                .collect(),
+            extensions: hello
+                .extensions()
+                .iter()
+                .map(ClientHelloExtension::id)
+                .filter(|id| !id.is_grease())
+                .map(u16::from)
+                .collect(),
+            signature_algorithms: hello
+                .ext_signature_algorithms()
+                .unwrap_or_default()
+                .iter()
+                .filter(|scheme| !scheme.is_grease())
+                .map(|scheme| u16::from(*scheme))
+                .collect(),
+        }
+    }
+
+    /// Return the raw fingerprint (`ja4_r`), with sorted cipher suites and extensions.
+    pub fn raw(&self) -> String {
+        self.format(true, false)
+    }
+
+    /// Return the hashed fingerprint (`ja4_o`),
+    /// with cipher suites and extensions in their original order.
+    pub fn original(&self) -> String {
+        self.format(false, true)
+    }
+
+    /// Return the raw fingerprint (`ja4_ro`),
+    /// with cipher suites and extensions in their original order.
+    pub fn original_raw(&self) -> String {
+        self.format(true, true)
+    }
+
+    fn format(&self, raw: bool, original: bool) -> String {
+        let mut cipher_suites = self.cipher_suites.clone();
+        let mut extensions = self.extensions.clone();
+        if !original {
+            cipher_suites.sort_unstable();
+            extensions.retain(|id| {
+                *id != u16::from(ExtensionId::SERVER_NAME)
+                    && *id != u16::from(ExtensionId::APPLICATION_LAYER_PROTOCOL_NEGOTIATION)
+            });
+            extensions.sort_unstable();
+        }
+
+        let mut out = format!(
+            "{}{}{}{:02}{:02}{}_",
+            self.protocol,
+            self.version,
+            if self.has_server_name { 'd' } else { 'i' },
+            self.cipher_suites.len().min(99),
+            self.extensions.len().min(99),
+            self.alpn,
+        );
+
+        let cipher_suites = join_hex(&cipher_suites);
+        let mut extensions_and_algorithms = join_hex(&extensions);
+        if !self.signature_algorithms.is_empty() {
+            extensions_and_algorithms.push('_');
+            extensions_and_algorithms.push_str(&join_hex(&self.signature_algorithms));
+        }
+
+        if raw {
+            let _ = write!(out, "{cipher_suites}_{extensions_and_algorithms}");
+        } else {
+            let _ = write!(
+                out,
+                "{}_{}",
+                hash12(&cipher_suites),
+                if extensions.is_empty() {
+                    hash12("")
+                } else {
+                    hash12(&extensions_and_algorithms)
+                }
+            );
+        }
+        out
+    }
+}
+
+impl fmt::Display for Ja4 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.format(false, false))
+    }
+}
+
+/// Map a [`ProtocolVersion`] to its rank, protocol and JA4 representation.
+fn ja4_version(version: ProtocolVersion) -> Option<(u8, char, &'static str)> {
+    Some(match version {
+        ProtocolVersion::SSLv2 => (1, 't', "s2"),
+        ProtocolVersion::SSLv3 => (2, 't', "s3"),
+        ProtocolVersion::TLSv1_0 => (3, 't', "10"),
+        ProtocolVersion::TLSv1_1 => (4, 't', "11"),
+        ProtocolVersion::TLSv1_2 => (5, 't', "12"),
+        ProtocolVersion::TLSv1_3 => (6, 't', "13"),
+        ProtocolVersion::DTLSv1_0 => (3, 'd', "d1"),
+        ProtocolVersion::DTLSv1_2 => (5, 'd', "d2"),
+        ProtocolVersion::DTLSv1_3 => (6, 'd', "d3"),
+        _ => return None,
+    })
+}
+
+/// First and last character of the ALPN value,
+/// or of its hex representation in case these are not alphanumeric.
+fn ja4_alpn(alpn: &[u8]) -> String {
+    match (alpn.first(), alpn.last()) {
+        (Some(first), Some(last)) => {
+            if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+                format!("{}{}", *first as char, *last as char)
+            } else {
+                let hex = hex::encode(alpn);
+                let mut chars = hex.chars();
+                let first = chars.next().unwrap_or('0');
+                let last = chars.next_back().unwrap_or('0');
+                format!("{first}{last}")
+            }
+        }
+        _ => "00".to_owned(),
+    }
+}
+
+fn join_hex(values: &[u16]) -> String {
+    values
+        .iter()
+        .map(|value| format!("{value:04x}"))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// First 12 characters of the hex encoded SHA256 hash,
+/// or all zeros in case the input is empty.
+fn hash12(value: &str) -> String {
+    if value.is_empty() {
+        return "000000000000".to_owned();
+    }
+    hex::encode(&Sha256::digest(value.as_bytes())[..6])
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::tls::{
+        ApplicationProtocol, CipherSuite, CompressionAlgorithm, ECPointFormat, SignatureScheme,
+        SupportedGroup,
+    };
+
+    /// A Chrome-like [`ClientHello`], including GREASE values.
+    pub(crate) fn chrome_client_hello() -> ClientHello {
+        let opaque = |id: u16| ClientHelloExtension::Opaque {
+            id: ExtensionId::from(id),
+            data: vec![],
+        };
+        ClientHello {
+            protocol_version: ProtocolVersion::TLSv1_2,
+            cipher_suites: [
+                0x0a0a, 0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f, 0xc02c, 0xc030, 0xcca9, 0xcca8,
+                0xc013, 0xc014, 0x009c, 0x009d, 0x002f, 0x0035,
+            ]
+            .into_iter()
+            .map(CipherSuite::from)
+            .collect(),
+            compression_algorithms: vec![CompressionAlgorithm::Null],
+            extensions: vec![
+                opaque(0x1a1a),
+                ClientHelloExtension::ServerName(Some("example.com".parse().unwrap())),
+                opaque(0x0017),
+                opaque(0xff01),
+                ClientHelloExtension::SupportedGroups(
+                    [0x2a2a, 0x001d, 0x0017, 0x0018]
+                        .into_iter()
+                        .map(SupportedGroup::from)
+                        .collect(),
+                ),
+                ClientHelloExtension::ECPointFormats(vec![ECPointFormat::Uncompressed]),
+                opaque(0x0023),
+                ClientHelloExtension::ApplicationLayerProtocolNegotiation(vec![
+                    ApplicationProtocol::HTTP_2,
+                    ApplicationProtocol::HTTP_11,
+                ]),
+                opaque(0x0005),
+                ClientHelloExtension::SignatureAlgorithms(
+                    [
+                        0x0403, 0x0804, 0x0401, 0x0503, 0x0805, 0x0501, 0x0806, 0x0601,
+                    ]
+                    .into_iter()
+                    .map(SignatureScheme::from)
+                    .collect(),
+                ),
+                opaque(0x0012),
+                opaque(0x0033),
+                opaque(0x002d),
+                ClientHelloExtension::SupportedVersions(vec![
+                    ProtocolVersion::from(0x3a3a),
+                    ProtocolVersion::TLSv1_3,
+                    ProtocolVersion::TLSv1_2,
+                ]),
+                opaque(0x001b),
+                opaque(0x4469),
+                opaque(0x4a4a),
+                opaque(0x0015),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_ja4_compute() {
+        let ja4 = Ja4::compute(&chrome_client_hello());
+        assert_eq!(ja4.to_string(), "t13d1516h2_8daaf6152771_e5627efa2ab1");
+        assert_eq!(
+            ja4.raw(),
+            "t13d1516h2_002f,0035,009c,009d,1301,1302,1303,c013,c014,c02b,c02c,c02f,c030,cca8,cca9_\
+             0005,000a,000b,000d,0012,0015,0017,001b,0023,002b,002d,0033,4469,ff01_\
+             0403,0804,0401,0503,0805,0501,0806,0601"
+        );
+        assert_eq!(ja4.original(), "t13d1516h2_acb858a92679_2d79a7d73c2f");
+        assert_eq!(
+            ja4.original_raw(),
+            "t13d1516h2_1301,1302,1303,c02b,c02f,c02c,c030,cca9,cca8,c013,c014,009c,009d,002f,0035_\
+             0000,0017,ff01,000a,000b,0023,0010,0005,000d,0012,0033,002d,002b,001b,4469,0015_\
+             0403,0804,0401,0503,0805,0501,0806,0601"
+        );
+    }
+
+    #[test]
+    fn test_ja4_minimal_client_hello() {
+        let ja4 = Ja4::compute(&ClientHello {
+            protocol_version: ProtocolVersion::TLSv1_2,
+            cipher_suites: vec![],
+            compression_algorithms: vec![],
+            extensions: vec![],
+        });
+        assert_eq!(ja4.to_string(), "t12i000000_000000000000_000000000000");
+        assert_eq!(ja4.raw(), "t12i000000__");
+    }
+
+    #[test]
+    fn test_ja4_alpn() {
+        assert_eq!(ja4_alpn(b"h2"), "h2");
+        assert_eq!(ja4_alpn(b"http/1.1"), "h1");
+        assert_eq!(ja4_alpn(b"h"), "hh");
+        assert_eq!(ja4_alpn(&[0xab, 0xcd]), "ad");
+        assert_eq!(ja4_alpn(b""), "00");
+    }
+}
@@ -0,0 +1,20 @@
+//! Fingerprint implementations used to identify clients.
+//!
+//! Fingerprints are computed from information stored in the [`Context`],
+//! and can be used as (part of) a key for bot detection,
+//! analytics or to emulate clients.
+//!
+//! Currently supported:
+//!
+//! - [`Ja3`]: the original TLS fingerprint by Salesforce;
+//! - [`Ja4`]: the TLS fingerprint of the [JA4+ suite by FoxIO](https://github.com/FoxIO-LLC/ja4).
+//!
+//! [`Context`]: rama_core::Context
+
+mod ja3;
+#[doc(inline)]
+pub use ja3::Ja3;
+
+mod ja4;
+#[doc(inline)]
+pub use ja4::Ja4;
@@ -56,6 +56,7 @@ pub mod address;
 pub mod asn;
 pub mod client;
 pub mod forwarded;
+pub mod proxy;
 pub mod stream;
 pub mod user;
 
@@ -71,3 +72,6 @@ pub mod http;
 
 #[cfg(feature = "tls")]
 pub mod tls;
+
+#[cfg(feature = "tls")]
+pub mod fingerprint;
@@ -0,0 +1,96 @@
+use super::ProxyRequest;
+use crate::stream::Stream;
+use rama_core::{
+    error::{BoxError, ErrorExt},
+    Context, Service,
+};
+use std::io;
+
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+/// A [`Service`] which forwards the data of a [`ProxyRequest`]
+/// bidirectionally between the source and target streams,
+/// until either side closes the connection.
+///
+/// Connection errors (e.g. a reset by the peer) are treated
+/// as a regular end of the forwarding and are not returned as an error.
+pub struct StreamForwardService;
+
+impl StreamForwardService {
+    /// Create a new [`StreamForwardService`].
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl<T, S, U> Service<T, ProxyRequest<S, U>> for StreamForwardService
+where
+    T: Send + Sync + 'static,
+    S: Stream + Unpin,
+    U: Stream + Unpin,
+{
+    type Response = ();
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        _ctx: Context<T>,
+        ProxyRequest {
+            mut source,
+            mut target,
+        }: ProxyRequest<S, U>,
+    ) -> Result<Self::Response, Self::Error> {
+        match tokio::io::copy_bidirectional(&mut source, &mut target).await {
+            Ok((bytes_copied_north, bytes_copied_south)) => {
+                tracing::trace!(
+                    "(proxy) I/O stream forwarder finished: bytes north: {}; bytes south: {}",
+                    bytes_copied_north,
+                    bytes_copied_south,
+                );
+                Ok(())
+            }
+            Err(err) => {
+                if is_connection_error(&err) {
+                    Ok(())
+                } else {
+                    Err(err.context("(proxy) I/O stream forwarder").into())
+                }
+            }
+        }
+    }
+}
+
+fn is_connection_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::Interrupted
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stream_forward_service() {
+        let source = tokio_test::io::Builder::new()
+            .read(b"ping")
+            .write(b"pong")
+            .build();
+        let target = tokio_test::io::Builder::new()
+            .write(b"ping")
+            .read(b"pong")
+            .build();
+
+        StreamForwardService::new()
+            .serve(Context::default(), ProxyRequest { source, target })
+            .await
+            .unwrap();
+    }
+}
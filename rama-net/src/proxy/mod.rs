@@ -0,0 +1,9 @@
+//! Generic proxy utilities, shared by the different proxy protocols.
+
+mod request;
+#[doc(inline)]
+pub use request::ProxyRequest;
+
+mod forward;
+#[doc(inline)]
+pub use forward::StreamForwardService;
@@ -0,0 +1,11 @@
+#[derive(Debug, Clone)]
+/// A request to be proxied between a source and a target stream.
+///
+/// Used by proxy services (e.g. socks5) once the proxy
+/// handshake is complete, to relay the data between the two streams.
+pub struct ProxyRequest<S, T> {
+    /// The source stream, usually the stream of the (proxy) client.
+    pub source: S,
+    /// The target stream, usually the stream to the destination server.
+    pub target: T,
+}
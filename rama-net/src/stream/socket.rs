@@ -1,5 +1,5 @@
 use std::io::Result;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 
 /// Common information exposed by a Socket-like construct.
 ///
@@ -87,3 +87,49 @@ impl SocketInfo {
         &self.peer_addr
     }
 }
+
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+/// A [`KeyExtractor`] which uses the ip address of the peer,
+/// as found in the [`SocketInfo`] of the [`Context`], as key.
+///
+/// Use it in combination with a [`KeyedPolicy`] to limit requests per client ip.
+///
+/// [`KeyExtractor`]: rama_core::layer::limit::policy::KeyExtractor
+/// [`KeyedPolicy`]: rama_core::layer::limit::policy::KeyedPolicy
+/// [`Context`]: rama_core::Context
+pub struct PeerIpKey;
+
+impl PeerIpKey {
+    /// Create a new [`PeerIpKey`].
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl<State, Request> rama_core::layer::limit::policy::KeyExtractor<State, Request> for PeerIpKey {
+    type Key = IpAddr;
+
+    fn extract(&self, ctx: &rama_core::Context<State>, _request: &Request) -> Option<Self::Key> {
+        ctx.get::<SocketInfo>().map(|info| info.peer_addr().ip())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::layer::limit::policy::KeyExtractor;
+    use rama_core::Context;
+
+    #[test]
+    fn test_peer_ip_key() {
+        let mut ctx = Context::default();
+        assert_eq!(PeerIpKey::new().extract(&ctx, &()), None);
+
+        ctx.insert(SocketInfo::new(None, ([127, 0, 0, 1], 8080).into()));
+        assert_eq!(
+            PeerIpKey::new().extract(&ctx, &()),
+            Some(IpAddr::from([127, 0, 0, 1]))
+        );
+    }
+}
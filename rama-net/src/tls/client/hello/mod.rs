@@ -3,6 +3,7 @@ use crate::tls::{
     enums::CompressionAlgorithm, ApplicationProtocol, CipherSuite, ECPointFormat, ExtensionId,
     ProtocolVersion, SignatureScheme, SupportedGroup,
 };
+use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "rustls")]
 mod rustls;
@@ -10,7 +11,7 @@ mod rustls;
 #[cfg(feature = "boring")]
 mod boring;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 /// When a client first connects to a server, it is required to send
 /// the ClientHello as its first message.
 ///
@@ -20,12 +21,37 @@ mod boring;
 /// For Rama however we only focus on the parts which
 /// a user might want to inspect and/or set.
 pub struct ClientHello {
-    pub(super) cipher_suites: Vec<CipherSuite>,
-    pub(super) compression_algorithms: Vec<CompressionAlgorithm>,
-    pub(super) extensions: Vec<ClientHelloExtension>,
+    pub(crate) protocol_version: ProtocolVersion,
+    pub(crate) cipher_suites: Vec<CipherSuite>,
+    pub(crate) compression_algorithms: Vec<CompressionAlgorithm>,
+    pub(crate) extensions: Vec<ClientHelloExtension>,
 }
 
 impl ClientHello {
+    /// Create a new [`ClientHello`].
+    pub fn new(
+        protocol_version: ProtocolVersion,
+        cipher_suites: Vec<CipherSuite>,
+        compression_algorithms: Vec<CompressionAlgorithm>,
+        extensions: Vec<ClientHelloExtension>,
+    ) -> Self {
+        Self {
+            protocol_version,
+            cipher_suites,
+            compression_algorithms,
+            extensions,
+        }
+    }
+
+    /// Return the (legacy) [`ProtocolVersion`] defined in this [`ClientHello`].
+    ///
+    /// Note that since TLS 1.3 the versions supported by the client
+    /// are instead defined in the supported versions extension,
+    /// see [`ClientHello::supported_versions`].
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
     /// Return all [`CipherSuite`]s defined in this [`ClientHello`].
     pub fn cipher_suites(&self) -> &[CipherSuite] {
         &self.cipher_suites[..]
@@ -119,7 +145,7 @@ impl ClientHello {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 /// Extensions that can be set in a [`ClientHello`] message by a TLS client.
 ///
 /// While its name may infer that an extension is by definition optional,
@@ -1,5 +1,5 @@
 use super::ClientHelloExtension;
-use crate::tls::{ApplicationProtocol, CipherSuite, SignatureScheme};
+use crate::tls::{ApplicationProtocol, CipherSuite, ProtocolVersion, SignatureScheme};
 
 impl<'a> From<rustls::server::ClientHello<'a>> for super::ClientHello {
     fn from(value: rustls::server::ClientHello<'a>) -> Self {
@@ -30,6 +30,9 @@ impl<'a> From<rustls::server::ClientHello<'a>> for super::ClientHello {
         }
 
         Self {
+            // not exposed by rustls, but it only supports clients
+            // which advertise TLS 1.2 as their (legacy) version
+            protocol_version: ProtocolVersion::TLSv1_2,
             cipher_suites,
             compression_algorithms: vec![],
             extensions,
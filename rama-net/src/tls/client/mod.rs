@@ -7,10 +7,100 @@
 //! By being implementation agnostic we have the advantage to be able to bridge
 //! easily between different implementations. Making it possible to run for example
 //! a Rustls proxy service but establish connections using BoringSSL.
+//!
+//! The same type is used to describe the [`ClientHello`] a client should send,
+//! by adding a [`ClientHelloEmulation`] to the [`Context`] of a request.
+//!
+//! Tls client connectors add the [`NegotiatedTlsParameters`] to the [`Context`]
+//! of the connections they establish.
+//!
+//! [`Context`]: rama_core::Context
+
+use super::ApplicationProtocol;
+use std::sync::Arc;
 
 mod hello;
 #[doc(inline)]
 pub use hello::{ClientHello, ClientHelloExtension};
 
-#[cfg(feature = "boring")]
 mod parser;
+#[doc(inline)]
+pub use parser::parse_client_hello;
+
+#[derive(Debug, Clone)]
+/// A [`ClientHello`] which tls client connectors should emulate
+/// for the connections they establish.
+///
+/// Add it to the [`Context`] of the request in order to have
+/// the tls client connectors configure themselves accordingly,
+/// as far as their tls implementation allows it.
+///
+/// Clients such as Chromium randomly permute the order of their extensions
+/// for every connection, which is emulated instead of the extension order
+/// of the [`ClientHello`] in case [`ClientHelloEmulation::permute_extensions`] is enabled.
+///
+/// [`Context`]: rama_core::Context
+pub struct ClientHelloEmulation {
+    hello: Arc<ClientHello>,
+    permute_extensions: bool,
+}
+
+impl ClientHelloEmulation {
+    /// Create a new [`ClientHelloEmulation`] for the given [`ClientHello`].
+    pub fn new(hello: impl Into<Arc<ClientHello>>) -> Self {
+        Self {
+            hello: hello.into(),
+            permute_extensions: false,
+        }
+    }
+
+    /// Return the [`ClientHello`] to be emulated.
+    pub fn client_hello(&self) -> &ClientHello {
+        &self.hello
+    }
+
+    /// Return the shared reference to the [`ClientHello`] to be emulated,
+    /// which can be used to identify the emulation by pointer.
+    pub fn shared_client_hello(&self) -> &Arc<ClientHello> {
+        &self.hello
+    }
+
+    /// Randomly permute the order of the extensions for every connection,
+    /// instead of sending them in the order of the [`ClientHello`].
+    pub fn with_permute_extensions(mut self, permute: bool) -> Self {
+        self.permute_extensions = permute;
+        self
+    }
+
+    /// Randomly permute the order of the extensions for every connection,
+    /// instead of sending them in the order of the [`ClientHello`].
+    pub fn set_permute_extensions(&mut self, permute: bool) -> &mut Self {
+        self.permute_extensions = permute;
+        self
+    }
+
+    /// Returns `true` in case the order of the extensions is to be
+    /// randomly permuted for every connection.
+    pub fn permute_extensions(&self) -> bool {
+        self.permute_extensions
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// The parameters negotiated during the handshake of a tls client connection,
+/// added by the tls client connectors to the [`Context`] of the connection.
+///
+/// Http connectors use the negotiated application layer protocol
+/// to decide which http version to speak over the connection.
+///
+/// [`Context`]: rama_core::Context
+pub struct NegotiatedTlsParameters {
+    /// The application layer protocol negotiated using ALPN, if any.
+    pub application_layer_protocol: Option<ApplicationProtocol>,
+}
+
+impl From<ClientHello> for ClientHelloEmulation {
+    fn from(hello: ClientHello) -> Self {
+        Self::new(hello)
+    }
+}
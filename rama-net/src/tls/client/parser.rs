@@ -19,7 +19,11 @@ use nom::{
 use rama_core::error::OpaqueError;
 
 #[inline]
-pub(crate) fn parse_client_hello(i: &[u8]) -> Result<ClientHello, OpaqueError> {
+/// Parse a [`ClientHello`] from the body of a (plaintext) ClientHello handshake message.
+///
+/// The input is expected to contain neither the TLS record header
+/// nor the handshake message header (type and length).
+pub fn parse_client_hello(i: &[u8]) -> Result<ClientHello, OpaqueError> {
     match parse_client_hello_inner(i) {
         Err(err) => Err(OpaqueError::from_display(format!(
             "parse client hello handshake message: {err:?}"
@@ -37,7 +41,7 @@ pub(crate) fn parse_client_hello(i: &[u8]) -> Result<ClientHello, OpaqueError> {
 }
 
 fn parse_client_hello_inner(i: &[u8]) -> IResult<&[u8], ClientHello> {
-    let (i, _version) = be_u16(i)?;
+    let (i, version) = be_u16(i)?;
     let (i, _random) = take(32usize)(i)?;
     let (i, sidlen) = verify(be_u8, |&n| n <= 32)(i)?;
     let (i, _sid) = cond(sidlen > 0, take(sidlen as usize))(i)?;
@@ -59,6 +63,7 @@ fn parse_client_hello_inner(i: &[u8]) -> IResult<&[u8], ClientHello> {
     Ok((
         i,
         ClientHello {
+            protocol_version: ProtocolVersion::from(version),
             cipher_suites,
             compression_algorithms,
             extensions,
@@ -324,6 +329,7 @@ mod tests {
             0x00, 0x00, 0x00, 0x00,
         ])
         .expect("to parse");
+        assert_eq!(client_hello.protocol_version(), ProtocolVersion::TLSv1_2);
         assert_eq!(
             client_hello.cipher_suites(),
             &[
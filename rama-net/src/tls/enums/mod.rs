@@ -48,6 +48,23 @@ macro_rules! enum_builder {
                 }
             }
         }
+        impl ::serde::Serialize for $enum_name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: ::serde::Serializer,
+            {
+                serializer.serialize_u8(u8::from(*self))
+            }
+        }
+
+        impl<'de> ::serde::Deserialize<'de> for $enum_name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: ::serde::Deserializer<'de>,
+            {
+                <u8 as ::serde::Deserialize>::deserialize(deserializer).map(Into::into)
+            }
+        }
     };
     (
         $(#[$comment:meta])*
@@ -81,6 +98,28 @@ macro_rules! enum_builder {
             }
         }
 
+        impl $enum_name {
+            /// Returns `true` in case this value is a GREASE value,
+            /// as reserved by [RFC 8701](https://datatracker.ietf.org/doc/html/rfc8701).
+            pub fn is_grease(&self) -> bool {
+                match self {
+                    $enum_name::Unknown(x) => x & 0x0f0f == 0x0a0a && x >> 8 == x & 0xff,
+                    _ => false,
+                }
+            }
+
+            /// Returns the name of this value, as used in its [`Display`] implementation,
+            /// or `None` in case the value is unknown.
+            ///
+            /// [`Display`]: std::fmt::Display
+            pub fn name(&self) -> Option<&'static str> {
+                match self {
+                    $( $enum_name::$enum_var => Some(stringify!($enum_var))),*
+                    ,$enum_name::Unknown(_) => None,
+                }
+            }
+        }
+
         impl ::std::fmt::Display for $enum_name {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 match self {
@@ -93,6 +132,23 @@ macro_rules! enum_builder {
                 }
             }
         }
+        impl ::serde::Serialize for $enum_name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: ::serde::Serializer,
+            {
+                serializer.serialize_u16(u16::from(*self))
+            }
+        }
+
+        impl<'de> ::serde::Deserialize<'de> for $enum_name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: ::serde::Deserializer<'de>,
+            {
+                <u16 as ::serde::Deserialize>::deserialize(deserializer).map(Into::into)
+            }
+        }
     };
     (
         $(#[$comment:meta])*
@@ -216,6 +272,36 @@ macro_rules! enum_builder {
                 }
             }
         }
+        impl ::serde::Serialize for $enum_name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: ::serde::Serializer,
+            {
+                match self.try_as_str() {
+                    Some(s) => serializer.serialize_str(s),
+                    None => serializer.serialize_bytes(self.as_bytes()),
+                }
+            }
+        }
+
+        impl<'de> ::serde::Deserialize<'de> for $enum_name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: ::serde::Deserializer<'de>,
+            {
+                #[derive(::serde::Deserialize)]
+                #[serde(untagged)]
+                enum Repr {
+                    Str(String),
+                    Bytes(Vec<u8>),
+                }
+
+                Ok(match <Repr as ::serde::Deserialize>::deserialize(deserializer)? {
+                    Repr::Str(s) => s.into(),
+                    Repr::Bytes(b) => b.into(),
+                })
+            }
+        }
     };
 }
 
@@ -910,6 +996,58 @@ mod tests {
         assert_eq!("GREASE (0xdada)", SupportedGroup::from(0xdada).to_string());
     }
 
+    #[test]
+    fn test_enum_uint_is_grease() {
+        assert!(SupportedGroup::from(0xdada).is_grease());
+        assert!(CipherSuite::from(0x0a0a).is_grease());
+        assert!(!CipherSuite::from(0x0a1a).is_grease());
+        assert!(!SupportedGroup::X25519.is_grease());
+        assert!(!SupportedGroup::from(0xffff).is_grease());
+    }
+
+    #[test]
+    fn test_enum_uint_name() {
+        assert_eq!(Some("X25519"), SupportedGroup::X25519.name());
+        assert_eq!(
+            Some("TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256"),
+            CipherSuite::from(0xc02b).name()
+        );
+        assert_eq!(None, SupportedGroup::from(0xdada).name());
+    }
+
+    #[test]
+    fn test_enum_serde() {
+        assert_eq!(
+            "4865",
+            serde_json::to_string(&CipherSuite::from(0x1301)).unwrap()
+        );
+        assert_eq!(
+            CipherSuite::from(0x1301),
+            serde_json::from_str::<CipherSuite>("4865").unwrap()
+        );
+        assert_eq!(
+            ExtensionId::from(0x0a0a),
+            serde_json::from_str::<ExtensionId>("2570").unwrap()
+        );
+        assert_eq!(
+            "0",
+            serde_json::to_string(&ECPointFormat::Uncompressed).unwrap()
+        );
+
+        assert_eq!(
+            r#""h2""#,
+            serde_json::to_string(&ApplicationProtocol::HTTP_2).unwrap()
+        );
+        assert_eq!(
+            ApplicationProtocol::HTTP_11,
+            serde_json::from_str::<ApplicationProtocol>(r#""http/1.1""#).unwrap()
+        );
+        assert_eq!(
+            ApplicationProtocol::from(&[0xff, 0x00]),
+            serde_json::from_str::<ApplicationProtocol>("[255,0]").unwrap()
+        );
+    }
+
     #[test]
     fn test_enum_bytes_display() {
         assert_eq!("http/1.1", ApplicationProtocol::HTTP_11.to_string());
@@ -0,0 +1,153 @@
+use crate::address::Domain;
+use crate::tls::{client::ClientHello, ApplicationProtocol, SecureTransport};
+use rama_core::{context::Extensions, matcher::Matcher, Context};
+
+/// Get the [`ClientHello`] from the [`Context`],
+/// as inserted by the [`PeekTlsClientHelloService`] or a tls acceptor.
+///
+/// [`PeekTlsClientHelloService`]: super::PeekTlsClientHelloService
+fn client_hello<State>(ctx: &Context<State>) -> Option<&ClientHello> {
+    ctx.get::<ClientHello>().or_else(|| {
+        ctx.get::<SecureTransport>()
+            .and_then(SecureTransport::client_hello)
+    })
+}
+
+#[derive(Debug, Clone)]
+/// Matcher based on the server name (SNI) of the [`ClientHello`] found in the [`Context`].
+///
+/// It will not match in case no [`ClientHello`] is found,
+/// or in case the [`ClientHello`] has no server name.
+pub struct SniMatcher {
+    domain: Domain,
+    sub: bool,
+}
+
+impl SniMatcher {
+    /// create a new SNI matcher to match on an exact server name match.
+    pub fn exact(domain: Domain) -> Self {
+        Self { domain, sub: false }
+    }
+
+    /// create a new SNI matcher to match on a subdomain of the server name.
+    ///
+    /// Note that a domain is also a subdomain of itself, so this will also
+    /// include all matches that [`Self::exact`] would capture.
+    pub fn sub(domain: Domain) -> Self {
+        Self { domain, sub: true }
+    }
+}
+
+impl<State, Request> Matcher<State, Request> for SniMatcher
+where
+    State: Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    fn matches(&self, _ext: Option<&mut Extensions>, ctx: &Context<State>, _req: &Request) -> bool {
+        match client_hello(ctx).and_then(ClientHello::ext_server_name) {
+            Some(domain) if self.sub => self.domain.is_parent_of(domain),
+            Some(domain) => self.domain == *domain,
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Matcher based on the ALPN protocols offered by the [`ClientHello`] found in the [`Context`].
+///
+/// It matches in case the given protocol is one of the offered protocols,
+/// and will not match in case no [`ClientHello`] is found.
+pub struct AlpnMatcher {
+    protocol: ApplicationProtocol,
+}
+
+impl AlpnMatcher {
+    /// create a new ALPN matcher to match on the given protocol being offered.
+    pub const fn new(protocol: ApplicationProtocol) -> Self {
+        Self { protocol }
+    }
+}
+
+impl<State, Request> Matcher<State, Request> for AlpnMatcher
+where
+    State: Send + Sync + 'static,
+    Request: Send + 'static,
+{
+    fn matches(&self, _ext: Option<&mut Extensions>, ctx: &Context<State>, _req: &Request) -> bool {
+        client_hello(ctx)
+            .and_then(ClientHello::ext_alpn)
+            .map(|protocols| protocols.contains(&self.protocol))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::{client::ClientHelloExtension, CipherSuite, ProtocolVersion};
+
+    fn client_hello(
+        server_name: Option<&'static str>,
+        alpn: &[ApplicationProtocol],
+    ) -> ClientHello {
+        ClientHello::new(
+            ProtocolVersion::TLSv1_2,
+            vec![CipherSuite::TLS13_AES_128_GCM_SHA256],
+            vec![],
+            vec![
+                ClientHelloExtension::ServerName(server_name.map(Domain::from_static)),
+                ClientHelloExtension::ApplicationLayerProtocolNegotiation(alpn.to_vec()),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_sni_matcher() {
+        let exact = SniMatcher::exact(Domain::from_static("example.com"));
+        let sub = SniMatcher::sub(Domain::from_static("example.com"));
+
+        let mut ctx = Context::default();
+        assert!(!exact.matches(None, &ctx, &()));
+        assert!(!sub.matches(None, &ctx, &()));
+
+        ctx.insert(client_hello(None, &[]));
+        assert!(!exact.matches(None, &ctx, &()));
+        assert!(!sub.matches(None, &ctx, &()));
+
+        ctx.insert(client_hello(Some("example.com"), &[]));
+        assert!(exact.matches(None, &ctx, &()));
+        assert!(sub.matches(None, &ctx, &()));
+
+        ctx.insert(client_hello(Some("www.example.com"), &[]));
+        assert!(!exact.matches(None, &ctx, &()));
+        assert!(sub.matches(None, &ctx, &()));
+
+        ctx.insert(client_hello(Some("example.org"), &[]));
+        assert!(!exact.matches(None, &ctx, &()));
+        assert!(!sub.matches(None, &ctx, &()));
+
+        let mut ctx = Context::default();
+        ctx.insert(SecureTransport::with_client_hello(client_hello(
+            Some("example.com"),
+            &[],
+        )));
+        assert!(exact.matches(None, &ctx, &()));
+    }
+
+    #[test]
+    fn test_alpn_matcher() {
+        let matcher = AlpnMatcher::new(ApplicationProtocol::HTTP_2);
+
+        let mut ctx = Context::default();
+        assert!(!matcher.matches(None, &ctx, &()));
+
+        ctx.insert(client_hello(None, &[ApplicationProtocol::HTTP_11]));
+        assert!(!matcher.matches(None, &ctx, &()));
+
+        ctx.insert(client_hello(
+            None,
+            &[ApplicationProtocol::HTTP_2, ApplicationProtocol::HTTP_11],
+        ));
+        assert!(matcher.matches(None, &ctx, &()));
+    }
+}
@@ -0,0 +1,56 @@
+//! TLS implementation agnostic server types
+//!
+//! [`PeekTlsClientHelloLayer`] peeks the [`ClientHello`] of an incoming TLS connection,
+//! without consuming it and without terminating the TLS connection. The parsed
+//! [`ClientHello`] is added to the [`Context`], such that matchers such as the
+//! [`SniMatcher`] and [`AlpnMatcher`] can be used to route the (still encrypted) stream,
+//! e.g. to different upstream TCP forwarders. This allows you to build an L4 SNI proxy,
+//! similar to the `ssl_preread` module of nginx.
+//!
+//! # Example
+//!
+//! ```
+//! use rama_core::{
+//!     error::BoxError,
+//!     service::service_fn,
+//!     Context, Layer, Service,
+//! };
+//! use rama_net::{
+//!     address::Domain,
+//!     tls::server::{PeekTlsClientHelloLayer, PeekTlsClientHelloStream, SniMatcher},
+//! };
+//! use tokio::io::DuplexStream;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let service = PeekTlsClientHelloLayer::new().layer((
+//!     (
+//!         SniMatcher::sub(Domain::from_static("example.com")),
+//!         service_fn(|_stream: PeekTlsClientHelloStream<DuplexStream>| async move {
+//!             // forward the stream to the upstream of example.com
+//!             Ok::<_, BoxError>("example.com")
+//!         }),
+//!     ),
+//!     service_fn(|_stream: PeekTlsClientHelloStream<DuplexStream>| async move {
+//!         // forward the stream to a default upstream
+//!         Ok::<_, BoxError>("default")
+//!     }),
+//! ));
+//!
+//! let (_client, server) = tokio::io::duplex(1024);
+//! # drop(_client);
+//! let upstream = service.serve(Context::default(), server).await.unwrap();
+//! assert_eq!(upstream, "default");
+//! # }
+//! ```
+//!
+//! [`ClientHello`]: crate::tls::client::ClientHello
+//! [`Context`]: rama_core::Context
+
+mod peek;
+#[doc(inline)]
+pub use peek::{PeekTlsClientHelloLayer, PeekTlsClientHelloService, PeekTlsClientHelloStream};
+
+mod matcher;
+#[doc(inline)]
+pub use matcher::{AlpnMatcher, SniMatcher};
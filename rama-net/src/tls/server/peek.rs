@@ -0,0 +1,353 @@
+use crate::{
+    stream::{ChainReader, HeapReader, Stream},
+    tls::client::{parse_client_hello, ClientHello},
+};
+use rama_core::{
+    error::{BoxError, OpaqueError},
+    Context, Layer, Service,
+};
+use rama_utils::macros::define_inner_service_accessors;
+use std::fmt;
+use tokio::io::AsyncReadExt;
+
+/// The stream passed by the [`PeekTlsClientHelloService`] to its inner service.
+///
+/// It replays all bytes peeked from the original stream, prior
+/// to continuing reading from the original stream itself.
+pub type PeekTlsClientHelloStream<IO> =
+    tokio::io::Join<ChainReader<HeapReader, tokio::io::ReadHalf<IO>>, tokio::io::WriteHalf<IO>>;
+
+/// The maximum amount of bytes peeked in order to find a complete [`ClientHello`].
+const MAX_PEEK_SIZE: usize = 64 * 1024;
+
+/// TLS record content type of a handshake message.
+const CONTENT_TYPE_HANDSHAKE: u8 = 0x16;
+/// TLS handshake message type of a client hello.
+const HANDSHAKE_TYPE_CLIENT_HELLO: u8 = 0x01;
+/// The maximum length of a (plaintext) TLS record fragment.
+const MAX_FRAGMENT_LEN: usize = 1 << 14;
+
+/// A [`Layer`] which produces a [`PeekTlsClientHelloService`].
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct PeekTlsClientHelloLayer;
+
+impl PeekTlsClientHelloLayer {
+    /// Create a new [`PeekTlsClientHelloLayer`].
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for PeekTlsClientHelloLayer {
+    type Service = PeekTlsClientHelloService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        PeekTlsClientHelloService { inner }
+    }
+}
+
+/// Service which peeks the [`ClientHello`] of an incoming TLS stream,
+/// without consuming it and without terminating the TLS connection.
+///
+/// The parsed [`ClientHello`] is inserted into the [`Context`],
+/// after which the stream is passed, with all peeked bytes intact,
+/// to the inner service. Streams which do not start with a (valid)
+/// TLS ClientHello are passed as-is, without a [`ClientHello`] being inserted.
+pub struct PeekTlsClientHelloService<S> {
+    inner: S,
+}
+
+impl<S> PeekTlsClientHelloService<S> {
+    /// Create a new [`PeekTlsClientHelloService`] with the given inner service.
+    pub const fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S: fmt::Debug> fmt::Debug for PeekTlsClientHelloService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PeekTlsClientHelloService")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for PeekTlsClientHelloService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<State, S, IO> Service<State, IO> for PeekTlsClientHelloService<S>
+where
+    State: Send + Sync + 'static,
+    S: Service<State, PeekTlsClientHelloStream<IO>, Error: Into<BoxError>>,
+    IO: Stream + Unpin,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        mut stream: IO,
+    ) -> Result<Self::Response, Self::Error> {
+        let mut buffer = Vec::with_capacity(1024);
+        loop {
+            match peek_client_hello(&buffer) {
+                PeekResult::Incomplete => {
+                    if buffer.len() >= MAX_PEEK_SIZE {
+                        tracing::debug!(
+                            "peek tls client hello: no complete client hello found in first {} bytes",
+                            buffer.len()
+                        );
+                        break;
+                    }
+                }
+                PeekResult::NotTls => {
+                    tracing::trace!("peek tls client hello: stream is not a tls stream");
+                    break;
+                }
+                PeekResult::Invalid(err) => {
+                    tracing::debug!(error = %err, "peek tls client hello: invalid client hello");
+                    break;
+                }
+                PeekResult::ClientHello(hello) => {
+                    tracing::trace!("peek tls client hello: client hello found");
+                    ctx.insert(hello);
+                    break;
+                }
+            }
+
+            buffer.reserve(2048);
+            if stream.read_buf(&mut buffer).await? == 0 {
+                tracing::trace!(
+                    "peek tls client hello: eof reached after {} bytes",
+                    buffer.len()
+                );
+                break;
+            }
+        }
+
+        // put back the data that is peeked
+        let (r, w) = tokio::io::split(stream);
+        let r = ChainReader::new(HeapReader::from(buffer), r);
+        let stream = tokio::io::join(r, w);
+
+        self.inner.serve(ctx, stream).await.map_err(Into::into)
+    }
+}
+
+#[derive(Debug)]
+enum PeekResult {
+    Incomplete,
+    NotTls,
+    Invalid(OpaqueError),
+    ClientHello(ClientHello),
+}
+
+/// Try to parse a [`ClientHello`] from the (start) of the given TLS stream data,
+/// reassembling the handshake message in case it is fragmented over multiple records.
+fn peek_client_hello(buf: &[u8]) -> PeekResult {
+    match buf {
+        [] => return PeekResult::Incomplete,
+        [content_type, ..] if *content_type != CONTENT_TYPE_HANDSHAKE => return PeekResult::NotTls,
+        [_, major_version, ..] if *major_version != 0x03 => return PeekResult::NotTls,
+        _ => (),
+    }
+
+    let mut handshake = Vec::new();
+    let mut records = buf;
+    loop {
+        if records.len() < 5 {
+            return PeekResult::Incomplete;
+        }
+        if records[0] != CONTENT_TYPE_HANDSHAKE {
+            return PeekResult::Invalid(OpaqueError::from_display(
+                "unexpected record content type prior to complete client hello",
+            ));
+        }
+        let fragment_len = u16::from_be_bytes([records[3], records[4]]) as usize;
+        if fragment_len == 0 || fragment_len > MAX_FRAGMENT_LEN {
+            return PeekResult::Invalid(OpaqueError::from_display(format!(
+                "invalid record fragment length: {fragment_len}"
+            )));
+        }
+        let Some(fragment) = records.get(5..5 + fragment_len) else {
+            return PeekResult::Incomplete;
+        };
+        handshake.extend_from_slice(fragment);
+        records = &records[5 + fragment_len..];
+
+        if handshake.len() < 4 {
+            continue;
+        }
+        if handshake[0] != HANDSHAKE_TYPE_CLIENT_HELLO {
+            return PeekResult::Invalid(OpaqueError::from_display(format!(
+                "unexpected handshake message type: {}",
+                handshake[0]
+            )));
+        }
+        let message_len =
+            u32::from_be_bytes([0, handshake[1], handshake[2], handshake[3]]) as usize;
+        if let Some(message) = handshake.get(4..4 + message_len) {
+            return match parse_client_hello(message) {
+                Ok(hello) => PeekResult::ClientHello(hello),
+                Err(err) => PeekResult::Invalid(err),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::Domain;
+    use crate::tls::ApplicationProtocol;
+    use rama_core::service::service_fn;
+    use tokio::io::AsyncWriteExt;
+
+    /// Client hello handshake message body with SNI `example.com` and ALPN `h2`.
+    fn client_hello_body() -> Vec<u8> {
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0x42; 32]); // random
+        body.push(0x00); // session id
+        body.extend_from_slice(&[0x00, 0x02, 0x13, 0x01]); // cipher suites
+        body.extend_from_slice(&[0x01, 0x00]); // compression algorithms
+
+        let name = b"example.com";
+        let mut extensions = vec![0x00, 0x00];
+        extensions.extend_from_slice(&(name.len() as u16 + 5).to_be_bytes());
+        extensions.extend_from_slice(&(name.len() as u16 + 3).to_be_bytes());
+        extensions.push(0x00);
+        extensions.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        extensions.extend_from_slice(name);
+        extensions.extend_from_slice(&[0x00, 0x10, 0x00, 0x05, 0x00, 0x03, 0x02, b'h', b'2']);
+
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend_from_slice(&extensions);
+        body
+    }
+
+    /// Wrap the client hello body in a handshake message,
+    /// split over records of at most `fragment_len` bytes.
+    fn client_hello_records(fragment_len: usize) -> Vec<u8> {
+        let body = client_hello_body();
+        let mut handshake = vec![HANDSHAKE_TYPE_CLIENT_HELLO];
+        handshake.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        handshake.extend_from_slice(&body);
+
+        let mut records = Vec::new();
+        for fragment in handshake.chunks(fragment_len) {
+            records.extend_from_slice(&[CONTENT_TYPE_HANDSHAKE, 0x03, 0x01]);
+            records.extend_from_slice(&(fragment.len() as u16).to_be_bytes());
+            records.extend_from_slice(fragment);
+        }
+        records
+    }
+
+    fn assert_client_hello(hello: &ClientHello) {
+        assert_eq!(
+            hello.ext_server_name(),
+            Some(&Domain::from_static("example.com"))
+        );
+        assert_eq!(hello.ext_alpn(), Some(&[ApplicationProtocol::HTTP_2][..]));
+    }
+
+    #[test]
+    fn test_peek_client_hello() {
+        for fragment_len in [3, 16, 1024] {
+            let records = client_hello_records(fragment_len);
+            for n in 0..records.len() {
+                assert!(
+                    matches!(peek_client_hello(&records[..n]), PeekResult::Incomplete),
+                    "fragment_len = {fragment_len}, n = {n}"
+                );
+            }
+            match peek_client_hello(&records) {
+                PeekResult::ClientHello(hello) => assert_client_hello(&hello),
+                result => panic!("unexpected result: {result:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_peek_client_hello_not_tls() {
+        assert!(matches!(
+            peek_client_hello(b"GET / HTTP/1.1\r\n"),
+            PeekResult::NotTls
+        ));
+        assert!(matches!(
+            peek_client_hello(&[CONTENT_TYPE_HANDSHAKE, 0x01]),
+            PeekResult::NotTls
+        ));
+    }
+
+    #[test]
+    fn test_peek_client_hello_invalid() {
+        // server hello
+        assert!(matches!(
+            peek_client_hello(&[
+                CONTENT_TYPE_HANDSHAKE,
+                0x03,
+                0x03,
+                0x00,
+                0x04,
+                0x02,
+                0,
+                0,
+                0
+            ]),
+            PeekResult::Invalid(_)
+        ));
+        // alert record in between handshake fragments
+        let mut records = client_hello_records(3);
+        records[5 + 3] = 0x15;
+        assert!(matches!(
+            peek_client_hello(&records),
+            PeekResult::Invalid(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_peek_tls_client_hello_service() {
+        for (input, expect_hello) in [
+            (client_hello_records(16), true),
+            (b"GET / HTTP/1.1\r\n\r\n".to_vec(), false),
+        ] {
+            let mut data = input.clone();
+            data.extend_from_slice(b"application data");
+
+            let (mut client, server) = tokio::io::duplex(16);
+            let writer = {
+                let data = data.clone();
+                tokio::spawn(async move {
+                    client.write_all(&data).await.unwrap();
+                })
+            };
+
+            let service = PeekTlsClientHelloLayer::new().layer(service_fn(
+                |ctx: Context<()>, mut stream: PeekTlsClientHelloStream<_>| async move {
+                    let hello = ctx.get::<ClientHello>().cloned();
+                    let mut received = Vec::new();
+                    stream.read_to_end(&mut received).await?;
+                    Ok::<_, BoxError>((hello, received))
+                },
+            ));
+
+            let (hello, received) = service.serve(Context::default(), server).await.unwrap();
+            writer.await.unwrap();
+
+            assert_eq!(received, data);
+            assert_eq!(hello.is_some(), expect_hello);
+            if let Some(hello) = hello {
+                assert_client_hello(&hello);
+            }
+        }
+    }
+}
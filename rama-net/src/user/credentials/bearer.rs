@@ -4,7 +4,7 @@ use std::borrow::Cow;
 #[cfg(feature = "http")]
 use rama_http_types::{headers::authorization, HeaderValue};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 /// Bearer credentials.
 pub struct Bearer(Cow<'static, str>);
 
@@ -5,7 +5,7 @@ use std::borrow::Cow;
 #[cfg(feature = "http")]
 use rama_http_types::HeaderValue;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 /// Proxy credentials.
 pub enum ProxyCredential {
     /// [`Basic`]` credentials.
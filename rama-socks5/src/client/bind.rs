@@ -0,0 +1,119 @@
+use super::{HandshakeError, Socks5Client};
+use crate::proto::Command;
+use rama_net::{address::Authority, stream::Stream};
+
+impl Socks5Client {
+    /// Perform the socks5 handshake for a [`Command::Bind`] request,
+    /// over an established connection with the proxy server.
+    ///
+    /// The destination is the address of the peer which is expected
+    /// to connect to the address bound by the proxy server. Use the returned
+    /// [`Binding`] to get this bound address and to wait for the inbound connection.
+    pub async fn handshake_bind<S: Stream + Unpin>(
+        &self,
+        mut stream: S,
+        destination: &Authority,
+    ) -> Result<Binding<S>, HandshakeError> {
+        let bind_address = self
+            .handshake_request(&mut stream, Command::Bind, destination)
+            .await?;
+        Ok(Binding {
+            stream,
+            bind_address,
+        })
+    }
+}
+
+#[derive(Debug)]
+/// A socks5 [`Command::Bind`] binding,
+/// established using [`Socks5Client::handshake_bind`].
+pub struct Binding<S> {
+    stream: S,
+    bind_address: Authority,
+}
+
+impl<S: Stream + Unpin> Binding<S> {
+    /// The address bound by the proxy server,
+    /// to which the peer is expected to connect.
+    pub fn bind_address(&self) -> &Authority {
+        &self.bind_address
+    }
+
+    /// Wait for the proxy server to accept the inbound connection of the peer.
+    ///
+    /// The stream, which can be used to communicate with the peer,
+    /// is returned together with the address of the peer.
+    pub async fn accept(mut self) -> Result<(S, Authority), HandshakeError> {
+        let reply = Socks5Client::read_reply(&mut self.stream)
+            .await
+            .map_err(|err| err.with_context("read server reply: bind accept"))?;
+
+        tracing::trace!(
+            bind_address = %self.bind_address,
+            peer_address = %reply.bind_address,
+            "socks5 client: bind: inbound connection accepted",
+        );
+
+        Ok((self.stream, reply.bind_address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{server::DefaultBinder, Socks5Acceptor};
+    use rama_tcp::server::TcpListener;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    #[tokio::test]
+    async fn test_socks5_bind_e2e() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        tokio::spawn(listener.serve(Socks5Acceptor::new().with_binder(DefaultBinder::default())));
+
+        let stream = TcpStream::connect(proxy_addr).await.unwrap();
+        let binding = Socks5Client::new()
+            .handshake_bind(stream, &([127, 0, 0, 1], 0).into())
+            .await
+            .unwrap();
+
+        let bind_address = binding.bind_address().to_string();
+        let peer = tokio::spawn(async move {
+            let mut peer = TcpStream::connect(bind_address).await.unwrap();
+            peer.write_all(b"hello from peer").await.unwrap();
+            let mut buf = [0u8; 5];
+            peer.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+            peer.local_addr().unwrap()
+        });
+
+        let (mut stream, peer_address) = binding.accept().await.unwrap();
+        let mut buf = [0u8; 15];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello from peer");
+        stream.write_all(b"hello").await.unwrap();
+
+        let peer_addr = peer.await.unwrap();
+        assert_eq!(peer_address, peer_addr.into());
+    }
+
+    #[tokio::test]
+    async fn test_socks5_bind_not_supported() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        tokio::spawn(listener.serve(Socks5Acceptor::new()));
+
+        let stream = TcpStream::connect(proxy_addr).await.unwrap();
+        let err = Socks5Client::new()
+            .handshake_bind(stream, &([127, 0, 0, 1], 0).into())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.reply(),
+            Some(crate::proto::ReplyKind::CommandNotSupported)
+        );
+    }
+}
@@ -0,0 +1,337 @@
+use super::Socks5Client;
+use crate::dns::resolve_authority;
+use rama_core::{
+    error::{BoxError, ErrorExt, OpaqueError},
+    Context, Layer, Service,
+};
+use rama_dns::{DnsResolver, HickoryDns};
+use rama_net::{
+    address::{Authority, ProxyAddress},
+    client::{ConnectorService, EstablishedClientConnection},
+    stream::Stream,
+    transport::TryRefIntoTransportContext,
+    user::ProxyCredential,
+};
+use rama_utils::macros::define_inner_service_accessors;
+use std::fmt;
+
+#[derive(Debug, Clone, Default)]
+/// A [`Layer`] which wraps the given service with a [`Socks5ProxyConnector`].
+///
+/// See [`Socks5ProxyConnector`] for more information.
+pub struct Socks5ProxyConnectorLayer<Dns = HickoryDns> {
+    required: bool,
+    dns: Dns,
+}
+
+impl Socks5ProxyConnectorLayer {
+    /// Create a new [`Socks5ProxyConnectorLayer`] which creates a [`Socks5ProxyConnector`]
+    /// which will only connect via a socks5 proxy in case a socks5 [`ProxyAddress`] is available
+    /// in the [`Context`].
+    pub fn optional() -> Self {
+        Self {
+            required: false,
+            dns: HickoryDns::default(),
+        }
+    }
+
+    /// Create a new [`Socks5ProxyConnectorLayer`] which creates a [`Socks5ProxyConnector`]
+    /// which will always connect via a socks5 proxy, but fail in case the [`ProxyAddress`] is
+    /// not available in the [`Context`].
+    pub fn required() -> Self {
+        Self {
+            required: true,
+            dns: HickoryDns::default(),
+        }
+    }
+}
+
+impl<Dns> Socks5ProxyConnectorLayer<Dns> {
+    /// Attach the given [`DnsResolver`], used to resolve the destination
+    /// in case the proxy protocol is `socks5` (and not `socks5h`).
+    pub fn with_dns<T>(self, dns: T) -> Socks5ProxyConnectorLayer<T> {
+        Socks5ProxyConnectorLayer {
+            required: self.required,
+            dns,
+        }
+    }
+}
+
+impl<S, Dns: Clone> Layer<S> for Socks5ProxyConnectorLayer<Dns> {
+    type Service = Socks5ProxyConnector<S, Dns>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Socks5ProxyConnector {
+            inner,
+            required: self.required,
+            dns: self.dns.clone(),
+        }
+    }
+}
+
+/// A connector which can be used to establish a connection over a socks5 proxy.
+///
+/// This behaviour is optional and only triggered in case there
+/// is a [`ProxyAddress`] found in the [`Context`], with a `socks5` or `socks5h` protocol.
+/// The inner connector is expected to establish the connection to the proxy.
+///
+/// In case of the `socks5` protocol, a destination domain is resolved locally
+/// using the [`DnsResolver`], while for `socks5h` the domain is resolved by the proxy.
+pub struct Socks5ProxyConnector<S, Dns = HickoryDns> {
+    inner: S,
+    required: bool,
+    dns: Dns,
+}
+
+impl<S: fmt::Debug, Dns: fmt::Debug> fmt::Debug for Socks5ProxyConnector<S, Dns> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Socks5ProxyConnector")
+            .field("inner", &self.inner)
+            .field("required", &self.required)
+            .field("dns", &self.dns)
+            .finish()
+    }
+}
+
+impl<S: Clone, Dns: Clone> Clone for Socks5ProxyConnector<S, Dns> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            required: self.required,
+            dns: self.dns.clone(),
+        }
+    }
+}
+
+impl<S> Socks5ProxyConnector<S> {
+    /// Create a new [`Socks5ProxyConnector`]
+    /// which will only connect via a socks5 proxy in case a socks5 [`ProxyAddress`] is available
+    /// in the [`Context`].
+    pub fn optional(inner: S) -> Self {
+        Self {
+            inner,
+            required: false,
+            dns: HickoryDns::default(),
+        }
+    }
+
+    /// Create a new [`Socks5ProxyConnector`]
+    /// which will always connect via a socks5 proxy, but fail in case the [`ProxyAddress`] is
+    /// not available in the [`Context`].
+    pub fn required(inner: S) -> Self {
+        Self {
+            inner,
+            required: true,
+            dns: HickoryDns::default(),
+        }
+    }
+}
+
+impl<S, Dns> Socks5ProxyConnector<S, Dns> {
+    /// Attach the given [`DnsResolver`], used to resolve the destination
+    /// in case the proxy protocol is `socks5` (and not `socks5h`).
+    pub fn with_dns<T>(self, dns: T) -> Socks5ProxyConnector<S, T> {
+        Socks5ProxyConnector {
+            inner: self.inner,
+            required: self.required,
+            dns,
+        }
+    }
+
+    define_inner_service_accessors!();
+}
+
+impl<S, Dns, State, Request> Service<State, Request> for Socks5ProxyConnector<S, Dns>
+where
+    S: ConnectorService<State, Request, Connection: Stream + Unpin, Error: Into<BoxError>>,
+    Dns: DnsResolver<Error: Into<BoxError>>,
+    State: Send + Sync + 'static,
+    Request: TryRefIntoTransportContext<State, Error: Into<BoxError> + Send + Sync + 'static>
+        + Send
+        + 'static,
+{
+    type Response = EstablishedClientConnection<S::Connection, State, Request>;
+    type Error = BoxError;
+
+    async fn serve(
+        &self,
+        mut ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let address = ctx.get::<ProxyAddress>().cloned().filter(|address| {
+            match address.protocol.as_ref() {
+                Some(protocol) => protocol.is_socks5() || protocol.is_socks5h(),
+                // assume socks5 in case no protocol is defined but a proxy is required
+                None => self.required,
+            }
+        });
+
+        let address = match address {
+            Some(address) => address,
+            None => {
+                return if self.required {
+                    Err("socks5 proxy required but none is defined".into())
+                } else {
+                    tracing::trace!("socks5 proxy connector: no proxy required or set: proceed with direct connection");
+                    self.inner.connect(ctx, req).await.map_err(Into::into)
+                };
+            }
+        };
+
+        let transport_ctx = ctx
+            .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
+            .map_err(|err| {
+                OpaqueError::from_boxed(err.into())
+                    .context("socks5 proxy connector: get transport context")
+            })?
+            .clone();
+
+        let EstablishedClientConnection {
+            ctx,
+            req,
+            mut conn,
+            addr,
+        } = self.inner.connect(ctx, req).await.map_err(|err| {
+            OpaqueError::from_boxed(err.into())
+                .context(format!("establish connection to socks5 proxy {}", address))
+        })?;
+
+        tracing::trace!(
+            authority = %transport_ctx.authority,
+            proxy_addr = %addr,
+            "socks5 proxy connector: connected to proxy",
+        );
+
+        let remote_dns = address
+            .protocol
+            .as_ref()
+            .map(|p| p.is_socks5h())
+            .unwrap_or_default();
+        let destination = if remote_dns {
+            transport_ctx.authority.clone()
+        } else {
+            resolve_authority(&self.dns, transport_ctx.authority.clone())
+                .await
+                .map(Authority::from)
+                .map_err(|err| {
+                    OpaqueError::from_boxed(err)
+                        .context("socks5 proxy connector: resolve destination")
+                })?
+        };
+
+        let mut client = Socks5Client::new();
+        match address.credential {
+            Some(ProxyCredential::Basic(basic)) => {
+                client.set_auth(basic);
+            }
+            Some(ProxyCredential::Bearer(_)) => {
+                return Err(OpaqueError::from_display(
+                    "socks5 proxy connector: bearer credentials are not supported",
+                )
+                .into());
+            }
+            None => (),
+        }
+
+        client
+            .handshake_connect(&mut conn, &destination)
+            .await
+            .map_err(|err| OpaqueError::from_std(err).context("socks5 proxy handshake"))?;
+
+        tracing::trace!(
+            authority = %transport_ctx.authority,
+            proxy_addr = %addr,
+            "socks5 proxy connector: connected to destination via proxy",
+        );
+
+        Ok(EstablishedClientConnection {
+            ctx,
+            req,
+            conn,
+            addr,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Socks5Acceptor;
+    use rama_net::user::Basic;
+    use rama_tcp::{
+        client::{service::TcpConnector, Request as TcpRequest},
+        server::TcpListener,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    async fn spawn_echo_server() -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let (mut r, mut w) = stream.split();
+                    let _ = tokio::io::copy(&mut r, &mut w).await;
+                });
+            }
+        });
+        addr
+    }
+
+    async fn spawn_proxy_server() -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            listener.serve(Socks5Acceptor::new().with_authorizer(Basic::new("john", "secret"))),
+        );
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_socks5_proxy_connector_e2e() {
+        let echo_addr = spawn_echo_server().await;
+        let proxy_addr = spawn_proxy_server().await;
+
+        for (scheme, credential, ok) in [
+            ("socks5", "john:secret", true),
+            ("socks5h", "john:secret", true),
+            ("socks5", "john:wrong", false),
+        ] {
+            let mut ctx = Context::default();
+            ctx.insert(
+                ProxyAddress::try_from(format!("{scheme}://{credential}@{proxy_addr}")).unwrap(),
+            );
+
+            let connector = Socks5ProxyConnector::required(TcpConnector::new());
+            let result = connector
+                .connect(ctx, TcpRequest::new(echo_addr.into()))
+                .await;
+
+            if !ok {
+                assert!(result.is_err(), "{scheme}://{credential}");
+                continue;
+            }
+
+            let EstablishedClientConnection { mut conn, addr, .. } = result.unwrap();
+            assert_eq!(addr, proxy_addr);
+
+            conn.write_all(b"hello socks5").await.unwrap();
+            let mut buf = [0u8; 12];
+            conn.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello socks5");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_socks5_proxy_connector_required_without_proxy() {
+        let connector = Socks5ProxyConnector::required(TcpConnector::new());
+        assert!(connector
+            .connect(
+                Context::default(),
+                TcpRequest::new(([127, 0, 0, 1], 80).into())
+            )
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::proto::{
+    client::{Header, Request, UsernamePasswordRequest},
+    server::{Header as ServerHeader, Reply, UsernamePasswordResponse},
+    Command, ProtocolError, ReplyKind, SocksMethod,
+};
+use rama_net::{address::Authority, stream::Stream, user::Basic};
+use std::fmt;
+
+#[derive(Debug, Clone, Default)]
+/// Socks5 client implementation of [RFC 1928].
+///
+/// Used to perform the socks5 handshake over an established
+/// connection to a socks5 proxy server. Username-password authentication,
+/// as defined in [RFC 1929], is offered in case credentials are set.
+///
+/// See [`Socks5ProxyConnector`] for a connector which can be used
+/// to establish connections via a socks5 proxy.
+///
+/// [RFC 1928]: https://datatracker.ietf.org/doc/html/rfc1928
+/// [RFC 1929]: https://datatracker.ietf.org/doc/html/rfc1929
+/// [`Socks5ProxyConnector`]: super::Socks5ProxyConnector
+pub struct Socks5Client {
+    auth: Option<Basic>,
+}
+
+impl Socks5Client {
+    /// Create a new [`Socks5Client`] without credentials.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the [`Basic`] credentials used to authenticate with the proxy server.
+    pub fn with_auth(mut self, auth: Basic) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Set the [`Basic`] credentials used to authenticate with the proxy server.
+    pub fn set_auth(&mut self, auth: Basic) -> &mut Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Perform the socks5 handshake for a [`Command::Connect`] request
+    /// to the given destination, over an established connection with
+    /// the proxy server.
+    ///
+    /// The address bound by the proxy server is returned on success,
+    /// after which the stream can be used to communicate with the destination.
+    pub async fn handshake_connect<S: Stream + Unpin>(
+        &self,
+        stream: &mut S,
+        destination: &Authority,
+    ) -> Result<Authority, HandshakeError> {
+        self.handshake_request(stream, Command::Connect, destination)
+            .await
+    }
+
+    pub(crate) async fn handshake_request<S: Stream + Unpin>(
+        &self,
+        stream: &mut S,
+        command: Command,
+        destination: &Authority,
+    ) -> Result<Authority, HandshakeError> {
+        self.handshake_auth(stream).await?;
+
+        Request::new(command, destination.clone())
+            .write_to(stream)
+            .await
+            .map_err(|err| HandshakeError::protocol(err).with_context("write client request"))?;
+
+        let reply = Self::read_reply(stream)
+            .await
+            .map_err(|err| err.with_context("read server reply"))?;
+
+        tracing::trace!(
+            %command,
+            %destination,
+            bind_address = %reply.bind_address,
+            "socks5 client: handshake complete",
+        );
+
+        Ok(reply.bind_address)
+    }
+
+    /// Read a server reply, failing in case it is not successful.
+    pub(crate) async fn read_reply<S: Stream + Unpin>(
+        stream: &mut S,
+    ) -> Result<Reply, HandshakeError> {
+        let reply = Reply::read_from(stream)
+            .await
+            .map_err(HandshakeError::protocol)?;
+        if reply.reply != ReplyKind::Succeeded {
+            return Err(HandshakeError::reply_kind(reply.reply));
+        }
+        Ok(reply)
+    }
+
+    async fn handshake_auth<S: Stream + Unpin>(
+        &self,
+        stream: &mut S,
+    ) -> Result<(), HandshakeError> {
+        let methods = if self.auth.is_some() {
+            vec![
+                SocksMethod::NoAuthenticationRequired,
+                SocksMethod::UsernamePassword,
+            ]
+        } else {
+            vec![SocksMethod::NoAuthenticationRequired]
+        };
+
+        Header::new(methods)
+            .write_to(stream)
+            .await
+            .map_err(|err| HandshakeError::protocol(err).with_context("write client header"))?;
+
+        let header = ServerHeader::read_from(stream)
+            .await
+            .map_err(|err| HandshakeError::protocol(err).with_context("read server header"))?;
+
+        match (header.method, self.auth.as_ref()) {
+            (SocksMethod::NoAuthenticationRequired, _) => Ok(()),
+            (SocksMethod::UsernamePassword, Some(auth)) => {
+                UsernamePasswordRequest::new(auth.clone())
+                    .write_to(stream)
+                    .await
+                    .map_err(|err| {
+                        HandshakeError::protocol(err)
+                            .with_context("write username-password request")
+                    })?;
+
+                let response =
+                    UsernamePasswordResponse::read_from(stream)
+                        .await
+                        .map_err(|err| {
+                            HandshakeError::protocol(err)
+                                .with_context("read username-password response")
+                        })?;
+
+                if response.success() {
+                    Ok(())
+                } else {
+                    Err(HandshakeError::unauthorized(response.status))
+                }
+            }
+            (method, _) => Err(HandshakeError::method_mismatch(method)),
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Error returned by the [`Socks5Client`] in case the handshake failed.
+pub struct HandshakeError {
+    kind: HandshakeErrorKind,
+    context: Option<&'static str>,
+}
+
+#[derive(Debug)]
+enum HandshakeErrorKind {
+    Protocol(ProtocolError),
+    MethodMismatch(SocksMethod),
+    Unauthorized(u8),
+    Reply(ReplyKind),
+}
+
+impl HandshakeError {
+    pub(crate) fn protocol(error: ProtocolError) -> Self {
+        Self {
+            kind: HandshakeErrorKind::Protocol(error),
+            context: None,
+        }
+    }
+
+    fn method_mismatch(method: SocksMethod) -> Self {
+        Self {
+            kind: HandshakeErrorKind::MethodMismatch(method),
+            context: None,
+        }
+    }
+
+    fn unauthorized(status: u8) -> Self {
+        Self {
+            kind: HandshakeErrorKind::Unauthorized(status),
+            context: None,
+        }
+    }
+
+    fn reply_kind(kind: ReplyKind) -> Self {
+        Self {
+            kind: HandshakeErrorKind::Reply(kind),
+            context: None,
+        }
+    }
+
+    pub(crate) fn with_context(mut self, context: &'static str) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Returns the [`ReplyKind`] in case the handshake failed
+    /// due to an unsuccessful reply of the server.
+    pub fn reply(&self) -> Option<ReplyKind> {
+        match self.kind {
+            HandshakeErrorKind::Reply(kind) => Some(kind),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` in case the server rejected the credentials of the client.
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self.kind, HandshakeErrorKind::Unauthorized(_))
+    }
+}
+
+impl fmt::Display for HandshakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let context = self.context.unwrap_or("no context");
+        match &self.kind {
+            HandshakeErrorKind::Protocol(error) => {
+                write!(f, "socks5 client: protocol error ({context}): {error}")
+            }
+            HandshakeErrorKind::MethodMismatch(method) => write!(
+                f,
+                "socks5 client: unexpected method selected by server ({context}): {method}"
+            ),
+            HandshakeErrorKind::Unauthorized(status) => write!(
+                f,
+                "socks5 client: unauthorized ({context}): status {status:#04x}"
+            ),
+            HandshakeErrorKind::Reply(kind) => {
+                write!(
+                    f,
+                    "socks5 client: server replied with error ({context}): {kind}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for HandshakeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            HandshakeErrorKind::Protocol(err) => Some(err),
+            HandshakeErrorKind::MethodMismatch(_)
+            | HandshakeErrorKind::Unauthorized(_)
+            | HandshakeErrorKind::Reply(_) => None,
+        }
+    }
+}
@@ -0,0 +1,25 @@
+//! Socks5 client support.
+//!
+//! Use the [`Socks5ProxyConnector`] (or its [`Socks5ProxyConnectorLayer`])
+//! to establish connections via a socks5 proxy, or the [`Socks5Client`]
+//! directly in case you are managing the connection to the proxy yourself,
+//! which is also required for the [`Command::Bind`] and [`Command::UdpAssociate`] commands.
+//!
+//! [`Command::Bind`]: crate::proto::Command::Bind
+//! [`Command::UdpAssociate`]: crate::proto::Command::UdpAssociate
+
+mod core;
+#[doc(inline)]
+pub use core::{HandshakeError, Socks5Client};
+
+mod bind;
+#[doc(inline)]
+pub use bind::Binding;
+
+mod udp;
+#[doc(inline)]
+pub use udp::UdpAssociation;
+
+mod connector;
+#[doc(inline)]
+pub use connector::{Socks5ProxyConnector, Socks5ProxyConnectorLayer};
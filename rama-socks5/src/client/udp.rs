@@ -0,0 +1,275 @@
+use super::{HandshakeError, Socks5Client};
+use crate::proto::{udp::UdpHeader, Command, ProtocolError};
+use rama_net::{
+    address::Authority,
+    stream::{Socket, Stream},
+};
+use rama_udp::UdpSocket;
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr},
+};
+
+impl Socks5Client {
+    /// Perform the socks5 handshake for a [`Command::UdpAssociate`] request,
+    /// over an established connection with the proxy server.
+    ///
+    /// The given [`UdpSocket`] is used to exchange datagrams with the
+    /// udp relay of the proxy server. The association remains valid for as long
+    /// as the returned [`UdpAssociation`] (which owns the control connection) is alive.
+    pub async fn handshake_udp_associate<S: Stream + Socket + Unpin>(
+        &self,
+        mut stream: S,
+        socket: UdpSocket,
+    ) -> Result<UdpAssociation<S>, HandshakeError> {
+        let local_addr = socket
+            .local_addr()
+            .unwrap_or_else(|_| SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0));
+
+        let relay_address = self
+            .handshake_request(&mut stream, Command::UdpAssociate, &local_addr.into())
+            .await?;
+
+        let (relay_host, relay_port) = relay_address.into_parts();
+        let relay_ip = match relay_host {
+            rama_net::address::Host::Address(ip) if !ip.is_unspecified() => ip,
+            // proxy servers are allowed to reply with an unspecified address,
+            // in which case the relay is reachable via the proxy address
+            _ => stream
+                .peer_addr()
+                .map_err(|err| {
+                    HandshakeError::protocol(ProtocolError::IO(err))
+                        .with_context("get proxy address for unspecified udp relay")
+                })?
+                .ip(),
+        };
+        let relay_address = SocketAddr::new(relay_ip, relay_port);
+
+        socket.connect(relay_address).await.map_err(|err| {
+            HandshakeError::protocol(ProtocolError::IO(err)).with_context("connect to udp relay")
+        })?;
+
+        tracing::trace!(
+            %local_addr,
+            %relay_address,
+            "socks5 client: udp associate: association established",
+        );
+
+        Ok(UdpAssociation {
+            stream,
+            socket,
+            relay_address,
+        })
+    }
+}
+
+#[derive(Debug)]
+/// A socks5 [`Command::UdpAssociate`] association,
+/// established using [`Socks5Client::handshake_udp_associate`].
+///
+/// Datagrams sent and received via this association
+/// are (un)wrapped with a [`UdpHeader`].
+pub struct UdpAssociation<S> {
+    stream: S,
+    socket: UdpSocket,
+    relay_address: SocketAddr,
+}
+
+impl<S> UdpAssociation<S> {
+    /// The address of the udp relay of the proxy server.
+    pub fn relay_address(&self) -> SocketAddr {
+        self.relay_address
+    }
+
+    /// The local address of the [`UdpSocket`] used for this association.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Send the data as a single datagram to the given destination via the udp relay.
+    pub async fn send_to(
+        &self,
+        data: &[u8],
+        destination: impl Into<Authority>,
+    ) -> io::Result<usize> {
+        let mut datagram = Vec::with_capacity(data.len() + 22);
+        UdpHeader::new(destination)
+            .write_to(&mut datagram)
+            .await
+            .map_err(into_io_error)?;
+        datagram.extend_from_slice(data);
+
+        self.socket.send(&datagram).await?;
+        Ok(data.len())
+    }
+
+    /// Receive a single datagram via the udp relay,
+    /// returning the number of bytes read and the origin address.
+    ///
+    /// The buffer should be large enough to contain the [`UdpHeader`] as well,
+    /// fragmented datagrams are dropped.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, Authority)> {
+        loop {
+            let n = self.socket.recv(buf).await?;
+
+            let mut data = &buf[..n];
+            let header = UdpHeader::read_from(&mut data)
+                .await
+                .map_err(into_io_error)?;
+            if header.fragment_number != 0 {
+                tracing::debug!(
+                    fragment_number = header.fragment_number,
+                    "socks5 client: udp associate: drop fragmented datagram",
+                );
+                continue;
+            }
+
+            let offset = n - data.len();
+            buf.copy_within(offset..n, 0);
+            return Ok((n - offset, header.destination));
+        }
+    }
+
+    /// Consume the association, returning the control connection and [`UdpSocket`].
+    ///
+    /// Dropping the control connection terminates the association.
+    pub fn into_parts(self) -> (S, UdpSocket) {
+        (self.stream, self.socket)
+    }
+}
+
+fn into_io_error(err: ProtocolError) -> io::Error {
+    match err {
+        ProtocolError::IO(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidData, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        server::{DefaultUdpRelay, UdpRelay},
+        Socks5Acceptor,
+    };
+    use rama_dns::DnsResolver;
+    use rama_net::address::{Domain, Host};
+    use rama_tcp::server::TcpListener;
+    use std::{
+        convert::Infallible,
+        net::{Ipv4Addr, Ipv6Addr},
+        time::Duration,
+    };
+    use tokio::net::TcpStream;
+
+    #[derive(Debug, Clone)]
+    /// A [`DnsResolver`] which never answers.
+    struct StalledDns;
+
+    impl DnsResolver for StalledDns {
+        type Error = Infallible;
+
+        async fn ipv4_lookup(&self, _domain: Domain) -> Result<Vec<Ipv4Addr>, Self::Error> {
+            std::future::pending().await
+        }
+
+        async fn ipv6_lookup(&self, _domain: Domain) -> Result<Vec<Ipv6Addr>, Self::Error> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_socks5_udp_associate_e2e() {
+        let echo = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let echo_addr = echo.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            loop {
+                let (n, src) = echo.recv_from(&mut buf).await.unwrap();
+                echo.send_to(&buf[..n], src).await.unwrap();
+            }
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            listener.serve(Socks5Acceptor::new().with_udp_associator(DefaultUdpRelay::default())),
+        );
+
+        let stream = TcpStream::connect(proxy_addr).await.unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let association = Socks5Client::new()
+            .handshake_udp_associate(stream, socket)
+            .await
+            .unwrap();
+        assert_eq!(association.relay_address().ip(), proxy_addr.ip());
+
+        for msg in [&b"ping"[..], b"hello udp"] {
+            let n = association.send_to(msg, echo_addr).await.unwrap();
+            assert_eq!(n, msg.len());
+
+            let mut buf = [0u8; 1024];
+            let (n, src) = association.recv_from(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], msg);
+            assert_eq!(src, echo_addr.into());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_socks5_udp_associate_slow_dns() {
+        let echo = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let echo_addr = echo.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            loop {
+                let (n, src) = echo.recv_from(&mut buf).await.unwrap();
+                echo.send_to(&buf[..n], src).await.unwrap();
+            }
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        tokio::spawn(listener.serve(
+            Socks5Acceptor::new().with_udp_associator(UdpRelay::new().with_dns(StalledDns)),
+        ));
+
+        let stream = TcpStream::connect(proxy_addr).await.unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let association = Socks5Client::new()
+            .handshake_udp_associate(stream, socket)
+            .await
+            .unwrap();
+
+        // a datagram which cannot be resolved does not hold up the next one
+        let stalled = Authority::new(Host::Name(Domain::from_static("stalled.internal")), 53);
+        association.send_to(b"stalled", stalled).await.unwrap();
+        association.send_to(b"ping", echo_addr).await.unwrap();
+
+        let mut buf = [0u8; 1024];
+        let (n, src) =
+            tokio::time::timeout(Duration::from_secs(1), association.recv_from(&mut buf))
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(&buf[..n], b"ping");
+        assert_eq!(src, echo_addr.into());
+    }
+
+    #[tokio::test]
+    async fn test_socks5_udp_associate_not_supported() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        tokio::spawn(listener.serve(Socks5Acceptor::new()));
+
+        let stream = TcpStream::connect(proxy_addr).await.unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let err = Socks5Client::new()
+            .handshake_udp_associate(stream, socket)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.reply(),
+            Some(crate::proto::ReplyKind::CommandNotSupported)
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use rama_core::error::{BoxError, OpaqueError};
+use rama_dns::DnsResolver;
+use rama_net::address::{Authority, Host};
+use std::net::{IpAddr, SocketAddr};
+
+/// Resolve the given [`Authority`] into a [`SocketAddr`],
+/// preferring an IPv4 address over an IPv6 address
+/// in case the host is a domain.
+pub(crate) async fn resolve_authority<Dns>(
+    dns: &Dns,
+    authority: Authority,
+) -> Result<SocketAddr, BoxError>
+where
+    Dns: DnsResolver<Error: Into<BoxError>>,
+{
+    let (host, port) = authority.into_parts();
+    let domain = match host {
+        Host::Address(ip) => return Ok((ip, port).into()),
+        Host::Name(domain) => domain,
+    };
+
+    match dns.ipv4_lookup(domain.clone()).await.map_err(Into::into) {
+        Ok(ips) => {
+            if let Some(ip) = ips.first() {
+                return Ok((*ip, port).into());
+            }
+        }
+        Err(err) => {
+            let err: BoxError = err;
+            tracing::trace!(
+                error = %err,
+                %domain,
+                "socks5: ipv4 lookup failed",
+            );
+        }
+    }
+
+    let ips = dns
+        .ipv6_lookup(domain.clone())
+        .await
+        .map_err(Into::<BoxError>::into)?;
+    let ip: IpAddr = (*ips.first().ok_or_else(|| {
+        OpaqueError::from_display(format!("no ip address found for domain {domain}"))
+    })?)
+    .into();
+
+    Ok((ip, port).into())
+}
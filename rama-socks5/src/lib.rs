@@ -1,5 +1,15 @@
 //! SOCKS5 support for Rama.
 //!
+//! Implements the socks5 protocol as defined in [RFC 1928],
+//! including username-password authentication as defined in [RFC 1929].
+//!
+//! - use the [`Socks5Acceptor`] to serve socks5 clients as a proxy server;
+//! - use the [`Socks5ProxyConnector`] to connect via a socks5 proxy as a client;
+//! - use the [`Socks5Client`] to bind or udp associate via a socks5 proxy as a client.
+//!
+//! [RFC 1928]: https://datatracker.ietf.org/doc/html/rfc1928
+//! [RFC 1929]: https://datatracker.ietf.org/doc/html/rfc1929
+//!
 //! # Rama
 //!
 //! Crate used by the end-user `rama` crate and `rama` crate authors alike.
@@ -53,5 +63,14 @@
 #![cfg_attr(test, allow(clippy::float_cmp))]
 #![cfg_attr(not(test), warn(clippy::print_stdout, clippy::dbg_macro))]
 
-/// TODO
-pub const PLACEHOLDER: bool = true;
+pub mod proto;
+
+mod dns;
+
+pub mod client;
+#[doc(inline)]
+pub use client::{Socks5Client, Socks5ProxyConnector, Socks5ProxyConnectorLayer};
+
+pub mod server;
+#[doc(inline)]
+pub use server::Socks5Acceptor;
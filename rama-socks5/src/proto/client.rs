@@ -0,0 +1,314 @@
+//! Client-side socks5 protocol messages,
+//! sent from client to server.
+
+use super::{
+    common::{read_authority, read_version, write_authority},
+    Command, ProtocolError, ProtocolVersion, SocksMethod, UsernamePasswordSubnegotiationVersion,
+};
+use rama_net::{address::Authority, user::Basic};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Client header, sent as the first message
+/// to negotiate the authentication method.
+///
+/// ```plain
+/// +----+----------+----------+
+/// |VER | NMETHODS | METHODS  |
+/// +----+----------+----------+
+/// | 1  |    1     | 1 to 255 |
+/// +----+----------+----------+
+/// ```
+///
+/// See <https://datatracker.ietf.org/doc/html/rfc1928#section-3>.
+pub struct Header {
+    /// Protocol version, always [`ProtocolVersion::Socks5`].
+    pub version: ProtocolVersion,
+    /// Authentication methods supported by the client.
+    pub methods: Vec<SocksMethod>,
+}
+
+impl Header {
+    /// Create a new socks5 client [`Header`] for the given methods.
+    pub fn new(methods: Vec<SocksMethod>) -> Self {
+        Self {
+            version: ProtocolVersion::Socks5,
+            methods,
+        }
+    }
+
+    /// Read the client [`Header`] from the given reader.
+    pub async fn read_from<R: AsyncRead + Unpin>(r: &mut R) -> Result<Self, ProtocolError> {
+        let version = read_version(r).await?;
+
+        let n = r.read_u8().await?;
+        if n == 0 {
+            return Err(ProtocolError::InvalidData("no methods offered"));
+        }
+        let mut raw = vec![0u8; n as usize];
+        r.read_exact(&mut raw).await?;
+
+        Ok(Self {
+            version,
+            methods: raw.into_iter().map(SocksMethod::from).collect(),
+        })
+    }
+
+    /// Write the client [`Header`] to the given writer.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<(), ProtocolError> {
+        let n: u8 = self
+            .methods
+            .len()
+            .try_into()
+            .map_err(|_| ProtocolError::InvalidData("too many methods"))?;
+        if n == 0 {
+            return Err(ProtocolError::InvalidData("no methods offered"));
+        }
+
+        let mut buf = Vec::with_capacity(2 + n as usize);
+        buf.push(self.version.into());
+        buf.push(n);
+        buf.extend(self.methods.iter().copied().map(u8::from));
+
+        w.write_all(&buf).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Username-password request, sent by the client
+/// in case [`SocksMethod::UsernamePassword`] was selected by the server.
+///
+/// ```plain
+/// +----+------+----------+------+----------+
+/// |VER | ULEN |  UNAME   | PLEN |  PASSWD  |
+/// +----+------+----------+------+----------+
+/// | 1  |  1   | 1 to 255 |  1   | 1 to 255 |
+/// +----+------+----------+------+----------+
+/// ```
+///
+/// See <https://datatracker.ietf.org/doc/html/rfc1929>.
+pub struct UsernamePasswordRequest {
+    /// Sub-negotiation version.
+    pub version: UsernamePasswordSubnegotiationVersion,
+    /// The credentials of the client.
+    pub basic: Basic,
+}
+
+impl UsernamePasswordRequest {
+    /// Create a new [`UsernamePasswordRequest`] for the given [`Basic`] credentials.
+    pub fn new(basic: Basic) -> Self {
+        Self {
+            version: UsernamePasswordSubnegotiationVersion::One,
+            basic,
+        }
+    }
+
+    /// Read the [`UsernamePasswordRequest`] from the given reader.
+    pub async fn read_from<R: AsyncRead + Unpin>(r: &mut R) -> Result<Self, ProtocolError> {
+        let byte = r.read_u8().await?;
+        let version = UsernamePasswordSubnegotiationVersion::from(byte);
+        if !matches!(version, UsernamePasswordSubnegotiationVersion::One) {
+            return Err(ProtocolError::unexpected_byte(0, byte));
+        }
+
+        let username = read_short_str(r, "username is not valid utf-8").await?;
+        let password = read_short_str(r, "password is not valid utf-8").await?;
+        if username.is_empty() {
+            return Err(ProtocolError::InvalidData("empty username"));
+        }
+
+        Ok(Self {
+            version,
+            basic: Basic::new(username, password),
+        })
+    }
+
+    /// Write the [`UsernamePasswordRequest`] to the given writer.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<(), ProtocolError> {
+        let username = self.basic.username().as_bytes();
+        let password = self.basic.password().as_bytes();
+
+        let mut buf = Vec::with_capacity(3 + username.len() + password.len());
+        buf.push(self.version.into());
+        write_short_bytes(&mut buf, username, "username too long")?;
+        write_short_bytes(&mut buf, password, "password too long")?;
+
+        w.write_all(&buf).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Client request, sent after the authentication (if any) was successful.
+///
+/// ```plain
+/// +----+-----+-------+------+----------+----------+
+/// |VER | CMD |  RSV  | ATYP | DST.ADDR | DST.PORT |
+/// +----+-----+-------+------+----------+----------+
+/// | 1  |  1  | X'00' |  1   | Variable |    2     |
+/// +----+-----+-------+------+----------+----------+
+/// ```
+///
+/// See <https://datatracker.ietf.org/doc/html/rfc1928#section-4>.
+pub struct Request {
+    /// Protocol version, always [`ProtocolVersion::Socks5`].
+    pub version: ProtocolVersion,
+    /// The requested [`Command`].
+    pub command: Command,
+    /// The desired destination address.
+    pub destination: Authority,
+}
+
+impl Request {
+    /// Create a new socks5 [`Request`] for the given [`Command`] and destination.
+    pub fn new(command: Command, destination: Authority) -> Self {
+        Self {
+            version: ProtocolVersion::Socks5,
+            command,
+            destination,
+        }
+    }
+
+    /// Read the client [`Request`] from the given reader.
+    pub async fn read_from<R: AsyncRead + Unpin>(r: &mut R) -> Result<Self, ProtocolError> {
+        let version = read_version(r).await?;
+        let command = Command::from(r.read_u8().await?);
+
+        let rsv = r.read_u8().await?;
+        if rsv != 0 {
+            return Err(ProtocolError::unexpected_byte(2, rsv));
+        }
+
+        let destination = read_authority(r, 3).await?;
+
+        Ok(Self {
+            version,
+            command,
+            destination,
+        })
+    }
+
+    /// Write the client [`Request`] to the given writer.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<(), ProtocolError> {
+        let mut buf = Vec::with_capacity(22);
+        buf.push(self.version.into());
+        buf.push(self.command.into());
+        buf.push(0);
+        write_authority(&mut buf, &self.destination)?;
+
+        w.write_all(&buf).await?;
+        Ok(())
+    }
+}
+
+async fn read_short_str<R: AsyncRead + Unpin>(
+    r: &mut R,
+    invalid_msg: &'static str,
+) -> Result<String, ProtocolError> {
+    let n = r.read_u8().await? as usize;
+    let mut raw = vec![0u8; n];
+    r.read_exact(&mut raw).await?;
+    String::from_utf8(raw).map_err(|_| ProtocolError::InvalidData(invalid_msg))
+}
+
+fn write_short_bytes(
+    buf: &mut Vec<u8>,
+    data: &[u8],
+    too_long_msg: &'static str,
+) -> Result<(), ProtocolError> {
+    let n: u8 = data
+        .len()
+        .try_into()
+        .map_err(|_| ProtocolError::InvalidData(too_long_msg))?;
+    buf.push(n);
+    buf.extend_from_slice(data);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::test_write_read_eq;
+    use rama_net::address::Domain;
+    use std::net::Ipv6Addr;
+
+    #[tokio::test]
+    async fn test_header_write_read_eq() {
+        test_write_read_eq!(
+            Header::new(vec![SocksMethod::NoAuthenticationRequired]),
+            Header
+        );
+        test_write_read_eq!(
+            Header::new(vec![
+                SocksMethod::NoAuthenticationRequired,
+                SocksMethod::UsernamePassword,
+                SocksMethod::Unknown(0x42),
+            ]),
+            Header
+        );
+    }
+
+    #[tokio::test]
+    async fn test_header_read_no_methods() {
+        let mut r: &[u8] = &[0x05, 0x00];
+        assert!(Header::read_from(&mut r).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_header_read_wrong_version() {
+        let mut r: &[u8] = &[0x04, 0x01, 0x00];
+        assert!(matches!(
+            Header::read_from(&mut r).await,
+            Err(ProtocolError::UnexpectedByte { pos: 0, byte: 0x04 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_username_password_request_write_read_eq() {
+        test_write_read_eq!(
+            UsernamePasswordRequest::new(Basic::new("john", "secret")),
+            UsernamePasswordRequest
+        );
+        test_write_read_eq!(
+            UsernamePasswordRequest::new(Basic::unprotected("john")),
+            UsernamePasswordRequest
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_write_read_eq() {
+        test_write_read_eq!(
+            Request::new(Command::Connect, ([127, 0, 0, 1], 8080).into()),
+            Request
+        );
+        test_write_read_eq!(
+            Request::new(Command::Connect, (Ipv6Addr::LOCALHOST, 443).into()),
+            Request
+        );
+        test_write_read_eq!(
+            Request::new(Command::UdpAssociate, (Domain::example(), 53).into()),
+            Request
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_read_domain() {
+        let mut r: &[u8] = &[
+            0x05, 0x01, 0x00, 0x03, 0x0b, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'c',
+            b'o', b'm', 0x01, 0xbb,
+        ];
+        let request = Request::read_from(&mut r).await.unwrap();
+        assert_eq!(request.command, Command::Connect);
+        assert_eq!(request.destination.to_string(), "example.com:443");
+    }
+
+    #[tokio::test]
+    async fn test_request_read_invalid_rsv() {
+        let mut r: &[u8] = &[0x05, 0x01, 0x01, 0x01, 127, 0, 0, 1, 0x00, 0x50];
+        assert!(matches!(
+            Request::read_from(&mut r).await,
+            Err(ProtocolError::UnexpectedByte { pos: 2, byte: 0x01 })
+        ));
+    }
+}
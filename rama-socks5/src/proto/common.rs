@@ -0,0 +1,82 @@
+use super::{AddressType, ProtocolError, ProtocolVersion};
+use rama_net::address::{Authority, Domain, Host};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Read an [`Authority`] (`ATYP | ADDR | PORT`) from the given reader.
+///
+/// `pos` is the position of the `ATYP` byte within the message,
+/// and is only used for error reporting.
+pub(crate) async fn read_authority<R: AsyncRead + Unpin>(
+    r: &mut R,
+    pos: usize,
+) -> Result<Authority, ProtocolError> {
+    let byte = r.read_u8().await?;
+    let host = match AddressType::from(byte) {
+        AddressType::IpV4 => {
+            let mut octets = [0u8; 4];
+            r.read_exact(&mut octets).await?;
+            Host::Address(IpAddr::V4(Ipv4Addr::from(octets)))
+        }
+        AddressType::IpV6 => {
+            let mut octets = [0u8; 16];
+            r.read_exact(&mut octets).await?;
+            Host::Address(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        AddressType::DomainName => {
+            let len = r.read_u8().await? as usize;
+            if len == 0 {
+                return Err(ProtocolError::InvalidData("empty domain name"));
+            }
+            let mut raw = vec![0u8; len];
+            r.read_exact(&mut raw).await?;
+            let domain = Domain::try_from(raw)
+                .map_err(|_| ProtocolError::InvalidData("invalid domain name"))?;
+            Host::Name(domain)
+        }
+        AddressType::Unknown(_) => return Err(ProtocolError::unexpected_byte(pos, byte)),
+    };
+    let port = r.read_u16().await?;
+    Ok(Authority::new(host, port))
+}
+
+/// Write an [`Authority`] (`ATYP | ADDR | PORT`) into the given buffer.
+pub(crate) fn write_authority(
+    buf: &mut Vec<u8>,
+    authority: &Authority,
+) -> Result<(), ProtocolError> {
+    match authority.host() {
+        Host::Address(IpAddr::V4(ip)) => {
+            buf.push(AddressType::IpV4.into());
+            buf.extend_from_slice(&ip.octets());
+        }
+        Host::Address(IpAddr::V6(ip)) => {
+            buf.push(AddressType::IpV6.into());
+            buf.extend_from_slice(&ip.octets());
+        }
+        Host::Name(domain) => {
+            let raw = domain.as_str().as_bytes();
+            let len: u8 = raw
+                .len()
+                .try_into()
+                .map_err(|_| ProtocolError::InvalidData("domain name too long"))?;
+            buf.push(AddressType::DomainName.into());
+            buf.push(len);
+            buf.extend_from_slice(raw);
+        }
+    }
+    buf.extend_from_slice(&authority.port().to_be_bytes());
+    Ok(())
+}
+
+/// Read the socks [`ProtocolVersion`] (`VER`) from the given reader,
+/// failing for any version other than [`ProtocolVersion::Socks5`].
+pub(crate) async fn read_version<R: AsyncRead + Unpin>(
+    r: &mut R,
+) -> Result<ProtocolVersion, ProtocolError> {
+    let byte = r.read_u8().await?;
+    match ProtocolVersion::from(byte) {
+        version @ ProtocolVersion::Socks5 => Ok(version),
+        ProtocolVersion::Unknown(_) => Err(ProtocolError::unexpected_byte(0, byte)),
+    }
+}
@@ -0,0 +1,137 @@
+#![allow(missing_docs)]
+
+/// A macro which defines a single-byte enum type.
+macro_rules! enum_builder {
+    (
+        $(#[$comment:meta])*
+        @U8
+        $enum_vis:vis enum $enum_name:ident
+        { $( $(#[$var_comment:meta])* $enum_var: ident => $enum_val: expr ),* $(,)? }
+    ) => {
+        $(#[$comment])*
+        #[non_exhaustive]
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+        $enum_vis enum $enum_name {
+            $( $(#[$var_comment])* $enum_var),*
+            ,Unknown(u8)
+        }
+
+        impl From<u8> for $enum_name {
+            fn from(x: u8) -> Self {
+                match x {
+                    $($enum_val => $enum_name::$enum_var),*
+                    , x => $enum_name::Unknown(x),
+                }
+            }
+        }
+
+        impl From<$enum_name> for u8 {
+            fn from(value: $enum_name) -> Self {
+                match value {
+                    $( $enum_name::$enum_var => $enum_val),*
+                    ,$enum_name::Unknown(x) => x
+                }
+            }
+        }
+
+        impl ::std::fmt::Display for $enum_name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    $( $enum_name::$enum_var => write!(f, concat!(stringify!($enum_var), " ({:#04x})"), $enum_val)),*
+                    ,$enum_name::Unknown(x) => write!(f, "Unknown ({x:#04x})"),
+                }
+            }
+        }
+    };
+}
+
+enum_builder! {
+    /// The SOCKS protocol version.
+    ///
+    /// Only version 5 is supported by rama.
+    @U8
+    pub enum ProtocolVersion {
+        Socks5 => 0x05,
+    }
+}
+
+enum_builder! {
+    /// Authentication methods which can be negotiated
+    /// between client and server.
+    ///
+    /// See <https://datatracker.ietf.org/doc/html/rfc1928#section-3>
+    /// and <https://www.iana.org/assignments/socks-methods/socks-methods.xhtml>.
+    @U8
+    pub enum SocksMethod {
+        /// No authentication required.
+        NoAuthenticationRequired => 0x00,
+        /// GSSAPI, as defined in RFC 1961.
+        GSSAPI => 0x01,
+        /// Username/Password, as defined in RFC 1929.
+        UsernamePassword => 0x02,
+        ChallengeHandshakeAuthenticationProtocol => 0x03,
+        ChallengeResponseAuthenticationMethod => 0x05,
+        SecureSocketsLayer => 0x06,
+        NDSAuthentication => 0x07,
+        MultiAuthenticationFramework => 0x08,
+        JSONParameterBlock => 0x09,
+        /// No acceptable methods, returned by the server
+        /// in case none of the client methods are supported.
+        NoAcceptableMethods => 0xff,
+    }
+}
+
+enum_builder! {
+    /// Command requested by the client.
+    ///
+    /// See <https://datatracker.ietf.org/doc/html/rfc1928#section-4>.
+    @U8
+    pub enum Command {
+        /// Establish a TCP/IP stream connection.
+        Connect => 0x01,
+        /// Establish a TCP/IP port binding.
+        Bind => 0x02,
+        /// Associate a UDP port.
+        UdpAssociate => 0x03,
+    }
+}
+
+enum_builder! {
+    /// Type of address used in a request or reply.
+    ///
+    /// See <https://datatracker.ietf.org/doc/html/rfc1928#section-5>.
+    @U8
+    pub enum AddressType {
+        IpV4 => 0x01,
+        DomainName => 0x03,
+        IpV6 => 0x04,
+    }
+}
+
+enum_builder! {
+    /// Reply status sent by the server in response to a client request.
+    ///
+    /// See <https://datatracker.ietf.org/doc/html/rfc1928#section-6>.
+    @U8
+    pub enum ReplyKind {
+        Succeeded => 0x00,
+        GeneralServerFailure => 0x01,
+        ConnectionNotAllowed => 0x02,
+        NetworkUnreachable => 0x03,
+        HostUnreachable => 0x04,
+        ConnectionRefused => 0x05,
+        TtlExpired => 0x06,
+        CommandNotSupported => 0x07,
+        AddressTypeNotSupported => 0x08,
+    }
+}
+
+enum_builder! {
+    /// Version of the username-password sub-negotiation.
+    ///
+    /// See <https://datatracker.ietf.org/doc/html/rfc1929>.
+    @U8
+    pub enum UsernamePasswordSubnegotiationVersion {
+        One => 0x01,
+    }
+}
@@ -0,0 +1,54 @@
+use std::fmt;
+
+#[derive(Debug)]
+/// Error that can occur while reading or writing
+/// a socks5 protocol message.
+pub enum ProtocolError {
+    /// I/O error that occurred while reading or writing.
+    IO(std::io::Error),
+    /// A byte was read which is not valid at the given position.
+    UnexpectedByte {
+        /// Position within the message of the unexpected byte.
+        pos: usize,
+        /// The byte value that was found.
+        byte: u8,
+    },
+    /// A message contained data which could not be interpreted.
+    InvalidData(&'static str),
+}
+
+impl ProtocolError {
+    pub(crate) fn unexpected_byte(pos: usize, byte: u8) -> Self {
+        Self::UnexpectedByte { pos, byte }
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IO(err) => write!(f, "socks5 protocol: i/o error: {err}"),
+            Self::UnexpectedByte { pos, byte } => {
+                write!(
+                    f,
+                    "socks5 protocol: unexpected byte {byte:#04x} at pos {pos}"
+                )
+            }
+            Self::InvalidData(reason) => write!(f, "socks5 protocol: invalid data: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IO(err) => Some(err),
+            Self::UnexpectedByte { .. } | Self::InvalidData(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ProtocolError {
+    fn from(err: std::io::Error) -> Self {
+        Self::IO(err)
+    }
+}
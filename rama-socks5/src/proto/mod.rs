@@ -0,0 +1,39 @@
+//! Socks5 protocol messages and their (de)serialization.
+//!
+//! As defined in [RFC 1928] and (for username-password authentication) [RFC 1929].
+//!
+//! [RFC 1928]: https://datatracker.ietf.org/doc/html/rfc1928
+//! [RFC 1929]: https://datatracker.ietf.org/doc/html/rfc1929
+
+mod enums;
+#[doc(inline)]
+pub use enums::{
+    AddressType, Command, ProtocolVersion, ReplyKind, SocksMethod,
+    UsernamePasswordSubnegotiationVersion,
+};
+
+mod error;
+#[doc(inline)]
+pub use error::ProtocolError;
+
+mod common;
+
+pub mod client;
+pub mod server;
+pub mod udp;
+
+#[cfg(test)]
+macro_rules! test_write_read_eq {
+    ($value:expr, $ty:ty) => {{
+        let value = $value;
+        let mut buf = Vec::new();
+        value.write_to(&mut buf).await.unwrap();
+        let mut r: &[u8] = &buf;
+        let read_value = <$ty>::read_from(&mut r).await.unwrap();
+        assert_eq!(value, read_value);
+        assert!(r.is_empty(), "all bytes should be consumed");
+    }};
+}
+
+#[cfg(test)]
+pub(crate) use test_write_read_eq;
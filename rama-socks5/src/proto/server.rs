@@ -0,0 +1,230 @@
+//! Server-side socks5 protocol messages,
+//! sent from server to client.
+
+use super::{
+    common::{read_authority, read_version, write_authority},
+    ProtocolError, ProtocolVersion, ReplyKind, SocksMethod, UsernamePasswordSubnegotiationVersion,
+};
+use rama_net::address::Authority;
+use std::net::{Ipv4Addr, SocketAddr};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Server header, sent in response to the client header
+/// to communicate the selected authentication method.
+///
+/// ```plain
+/// +----+--------+
+/// |VER | METHOD |
+/// +----+--------+
+/// | 1  |   1    |
+/// +----+--------+
+/// ```
+///
+/// See <https://datatracker.ietf.org/doc/html/rfc1928#section-3>.
+pub struct Header {
+    /// Protocol version, always [`ProtocolVersion::Socks5`].
+    pub version: ProtocolVersion,
+    /// Authentication method selected by the server.
+    pub method: SocksMethod,
+}
+
+impl Header {
+    /// Create a new socks5 server [`Header`] for the selected method.
+    pub fn new(method: SocksMethod) -> Self {
+        Self {
+            version: ProtocolVersion::Socks5,
+            method,
+        }
+    }
+
+    /// Read the server [`Header`] from the given reader.
+    pub async fn read_from<R: AsyncRead + Unpin>(r: &mut R) -> Result<Self, ProtocolError> {
+        let version = read_version(r).await?;
+        let method = SocksMethod::from(r.read_u8().await?);
+        Ok(Self { version, method })
+    }
+
+    /// Write the server [`Header`] to the given writer.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<(), ProtocolError> {
+        w.write_all(&[self.version.into(), self.method.into()])
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Username-password response, sent by the server
+/// to communicate the result of the sub-negotiation.
+///
+/// ```plain
+/// +----+--------+
+/// |VER | STATUS |
+/// +----+--------+
+/// | 1  |   1    |
+/// +----+--------+
+/// ```
+///
+/// See <https://datatracker.ietf.org/doc/html/rfc1929>.
+pub struct UsernamePasswordResponse {
+    /// Sub-negotiation version.
+    pub version: UsernamePasswordSubnegotiationVersion,
+    /// Status of the authentication, `0` meaning success.
+    pub status: u8,
+}
+
+impl UsernamePasswordResponse {
+    /// Create a new successful [`UsernamePasswordResponse`].
+    pub fn new_success() -> Self {
+        Self {
+            version: UsernamePasswordSubnegotiationVersion::One,
+            status: 0,
+        }
+    }
+
+    /// Create a new failed [`UsernamePasswordResponse`].
+    pub fn new_invalid_credentials() -> Self {
+        Self {
+            version: UsernamePasswordSubnegotiationVersion::One,
+            status: 1,
+        }
+    }
+
+    /// Returns `true` if this response indicates a successful authentication.
+    pub fn success(&self) -> bool {
+        self.status == 0
+    }
+
+    /// Read the [`UsernamePasswordResponse`] from the given reader.
+    pub async fn read_from<R: AsyncRead + Unpin>(r: &mut R) -> Result<Self, ProtocolError> {
+        let byte = r.read_u8().await?;
+        let version = UsernamePasswordSubnegotiationVersion::from(byte);
+        if !matches!(version, UsernamePasswordSubnegotiationVersion::One) {
+            return Err(ProtocolError::unexpected_byte(0, byte));
+        }
+        let status = r.read_u8().await?;
+        Ok(Self { version, status })
+    }
+
+    /// Write the [`UsernamePasswordResponse`] to the given writer.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<(), ProtocolError> {
+        w.write_all(&[self.version.into(), self.status]).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Server reply, sent in response to a client request.
+///
+/// ```plain
+/// +----+-----+-------+------+----------+----------+
+/// |VER | REP |  RSV  | ATYP | BND.ADDR | BND.PORT |
+/// +----+-----+-------+------+----------+----------+
+/// | 1  |  1  | X'00' |  1   | Variable |    2     |
+/// +----+-----+-------+------+----------+----------+
+/// ```
+///
+/// See <https://datatracker.ietf.org/doc/html/rfc1928#section-6>.
+pub struct Reply {
+    /// Protocol version, always [`ProtocolVersion::Socks5`].
+    pub version: ProtocolVersion,
+    /// The status of the reply.
+    pub reply: ReplyKind,
+    /// The address bound by the server.
+    pub bind_address: Authority,
+}
+
+impl Reply {
+    /// Create a new successful [`Reply`] for the given bind address.
+    pub fn new(bind_address: impl Into<Authority>) -> Self {
+        Self {
+            version: ProtocolVersion::Socks5,
+            reply: ReplyKind::Succeeded,
+            bind_address: bind_address.into(),
+        }
+    }
+
+    /// Create a new [`Reply`] which indicates an error.
+    ///
+    /// The bind address is set to the unspecified IPv4 address,
+    /// as it has no meaning in this context.
+    pub fn error_reply(kind: ReplyKind) -> Self {
+        Self {
+            version: ProtocolVersion::Socks5,
+            reply: kind,
+            bind_address: SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0).into(),
+        }
+    }
+
+    /// Read the server [`Reply`] from the given reader.
+    pub async fn read_from<R: AsyncRead + Unpin>(r: &mut R) -> Result<Self, ProtocolError> {
+        let version = read_version(r).await?;
+        let reply = ReplyKind::from(r.read_u8().await?);
+
+        let rsv = r.read_u8().await?;
+        if rsv != 0 {
+            return Err(ProtocolError::unexpected_byte(2, rsv));
+        }
+
+        let bind_address = read_authority(r, 3).await?;
+
+        Ok(Self {
+            version,
+            reply,
+            bind_address,
+        })
+    }
+
+    /// Write the server [`Reply`] to the given writer.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<(), ProtocolError> {
+        let mut buf = Vec::with_capacity(22);
+        buf.push(self.version.into());
+        buf.push(self.reply.into());
+        buf.push(0);
+        write_authority(&mut buf, &self.bind_address)?;
+
+        w.write_all(&buf).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::test_write_read_eq;
+    use std::net::Ipv6Addr;
+
+    #[tokio::test]
+    async fn test_header_write_read_eq() {
+        test_write_read_eq!(Header::new(SocksMethod::NoAuthenticationRequired), Header);
+        test_write_read_eq!(Header::new(SocksMethod::UsernamePassword), Header);
+        test_write_read_eq!(Header::new(SocksMethod::NoAcceptableMethods), Header);
+    }
+
+    #[tokio::test]
+    async fn test_username_password_response_write_read_eq() {
+        test_write_read_eq!(
+            UsernamePasswordResponse::new_success(),
+            UsernamePasswordResponse
+        );
+        test_write_read_eq!(
+            UsernamePasswordResponse::new_invalid_credentials(),
+            UsernamePasswordResponse
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reply_write_read_eq() {
+        test_write_read_eq!(Reply::new(([127, 0, 0, 1], 1080)), Reply);
+        test_write_read_eq!(Reply::new((Ipv6Addr::LOCALHOST, 1080)), Reply);
+        test_write_read_eq!(Reply::error_reply(ReplyKind::HostUnreachable), Reply);
+    }
+
+    #[tokio::test]
+    async fn test_reply_read_wire() {
+        let mut r: &[u8] = &[0x05, 0x00, 0x00, 0x01, 10, 0, 0, 1, 0x04, 0x38];
+        let reply = Reply::read_from(&mut r).await.unwrap();
+        assert_eq!(reply.reply, ReplyKind::Succeeded);
+        assert_eq!(reply.bind_address.to_string(), "10.0.0.1:1080");
+    }
+}
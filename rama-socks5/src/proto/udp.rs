@@ -0,0 +1,100 @@
+//! Socks5 UDP datagram header,
+//! prefixed to each datagram relayed via a [`Command::UdpAssociate`] association.
+//!
+//! [`Command::UdpAssociate`]: super::Command::UdpAssociate
+
+use super::{
+    common::{read_authority, write_authority},
+    ProtocolError,
+};
+use rama_net::address::Authority;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Header of a UDP datagram, exchanged between client and UDP relay server.
+///
+/// ```plain
+/// +----+------+------+----------+----------+----------+
+/// |RSV | FRAG | ATYP | DST.ADDR | DST.PORT |   DATA   |
+/// +----+------+------+----------+----------+----------+
+/// | 2  |  1   |  1   | Variable |    2     | Variable |
+/// +----+------+------+----------+----------+----------+
+/// ```
+///
+/// Fragmentation is optional and not supported by rama,
+/// datagrams with a non-zero fragment number are expected to be dropped.
+pub struct UdpHeader {
+    /// Current fragment number, `0` for a standalone datagram.
+    pub fragment_number: u8,
+    /// Destination address (from client) or source address (to client) of the datagram.
+    pub destination: Authority,
+}
+
+impl UdpHeader {
+    /// Create a new (unfragmented) [`UdpHeader`] for the given destination.
+    pub fn new(destination: impl Into<Authority>) -> Self {
+        Self {
+            fragment_number: 0,
+            destination: destination.into(),
+        }
+    }
+
+    /// Read the [`UdpHeader`] from the given reader,
+    /// leaving the reader at the start of the data.
+    ///
+    /// A datagram can be parsed by reading from a `&[u8]` slice,
+    /// after which the slice only contains the data.
+    pub async fn read_from<R: AsyncRead + Unpin>(r: &mut R) -> Result<Self, ProtocolError> {
+        let rsv = r.read_u16().await?;
+        if rsv != 0 {
+            return Err(ProtocolError::unexpected_byte(0, (rsv >> 8) as u8));
+        }
+        let fragment_number = r.read_u8().await?;
+        let destination = read_authority(r, 3).await?;
+        Ok(Self {
+            fragment_number,
+            destination,
+        })
+    }
+
+    /// Write the [`UdpHeader`] to the given writer.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<(), ProtocolError> {
+        let mut buf = Vec::with_capacity(22);
+        buf.extend_from_slice(&[0, 0, self.fragment_number]);
+        write_authority(&mut buf, &self.destination)?;
+
+        w.write_all(&buf).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::test_write_read_eq;
+    use rama_net::address::Domain;
+    use std::net::Ipv6Addr;
+
+    #[tokio::test]
+    async fn test_udp_header_write_read_eq() {
+        test_write_read_eq!(UdpHeader::new(([127, 0, 0, 1], 53)), UdpHeader);
+        test_write_read_eq!(UdpHeader::new((Ipv6Addr::LOCALHOST, 53)), UdpHeader);
+        test_write_read_eq!(UdpHeader::new((Domain::example(), 443)), UdpHeader);
+    }
+
+    #[tokio::test]
+    async fn test_udp_header_read_datagram() {
+        let datagram: &[u8] = b"\x00\x00\x00\x01\x08\x08\x08\x08\x00\x35hello";
+        let mut r = datagram;
+        let header = UdpHeader::read_from(&mut r).await.unwrap();
+        assert_eq!(header.fragment_number, 0);
+        assert_eq!(header.destination.to_string(), "8.8.8.8:53");
+        assert_eq!(r, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_udp_header_read_invalid_reserved() {
+        let mut r: &[u8] = b"\x01\x00\x00\x01\x08\x08\x08\x08\x00\x35";
+        assert!(UdpHeader::read_from(&mut r).await.is_err());
+    }
+}
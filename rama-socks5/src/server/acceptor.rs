@@ -0,0 +1,506 @@
+use super::{connect::DefaultConnector, Error, Socks5Binder, Socks5Connector, Socks5UdpAssociator};
+use crate::proto::{
+    client::{Header, Request, UsernamePasswordRequest},
+    server::{Header as ServerHeader, Reply, UsernamePasswordResponse},
+    Command, ProtocolError, ReplyKind, SocksMethod,
+};
+use rama_core::{Context, Service};
+use rama_net::{
+    stream::Stream,
+    user::{auth::Authority, Basic},
+};
+use std::{fmt, marker::PhantomData};
+
+/// Socks5 server implementation of [RFC 1928].
+///
+/// Serves a single client stream, negotiating the authentication method
+/// (none or username-password as defined in [RFC 1929]) and handling
+/// the requested command.
+///
+/// The [`Command::Connect`] command is supported by default,
+/// while the [`Command::Bind`] and [`Command::UdpAssociate`] commands
+/// have to be enabled using [`Socks5Acceptor::with_binder`] and
+/// [`Socks5Acceptor::with_udp_associator`] respectively,
+/// e.g. using the [`DefaultBinder`] and [`DefaultUdpRelay`].
+///
+/// In case an authorizer is set using [`Socks5Acceptor::with_authorizer`],
+/// clients are required to authenticate themselves using username-password credentials,
+/// unless authentication is made optional using [`Socks5Acceptor::with_auth_optional`].
+/// The [`Extensions`] returned by a successful authorization (e.g. the [`UserId`])
+/// are added to the [`Context`].
+///
+/// [RFC 1928]: https://datatracker.ietf.org/doc/html/rfc1928
+/// [RFC 1929]: https://datatracker.ietf.org/doc/html/rfc1929
+/// [`Extensions`]: rama_core::context::Extensions
+/// [`UserId`]: rama_net::user::UserId
+/// [`DefaultBinder`]: super::DefaultBinder
+/// [`DefaultUdpRelay`]: super::DefaultUdpRelay
+pub struct Socks5Acceptor<C = DefaultConnector, B = (), U = (), A = Basic, L = ()> {
+    connector: C,
+    binder: B,
+    udp_associator: U,
+    auth: Option<A>,
+    auth_opt: bool,
+    _phantom: PhantomData<fn(L) -> ()>,
+}
+
+impl<C: fmt::Debug, B: fmt::Debug, U: fmt::Debug, A: fmt::Debug, L> fmt::Debug
+    for Socks5Acceptor<C, B, U, A, L>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Socks5Acceptor")
+            .field("connector", &self.connector)
+            .field("binder", &self.binder)
+            .field("udp_associator", &self.udp_associator)
+            .field("auth", &self.auth)
+            .field("auth_opt", &self.auth_opt)
+            .field(
+                "_phantom",
+                &format_args!("{}", std::any::type_name::<fn(L) -> ()>()),
+            )
+            .finish()
+    }
+}
+
+impl<C: Clone, B: Clone, U: Clone, A: Clone, L> Clone for Socks5Acceptor<C, B, U, A, L> {
+    fn clone(&self) -> Self {
+        Self {
+            connector: self.connector.clone(),
+            binder: self.binder.clone(),
+            udp_associator: self.udp_associator.clone(),
+            auth: self.auth.clone(),
+            auth_opt: self.auth_opt,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl Socks5Acceptor {
+    /// Create a new [`Socks5Acceptor`], which requires no authentication
+    /// and serves [`Command::Connect`] requests using the [`DefaultConnector`].
+    pub fn new() -> Self {
+        Self {
+            connector: DefaultConnector::default(),
+            binder: (),
+            udp_associator: (),
+            auth: None,
+            auth_opt: false,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl Default for Socks5Acceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C, B, U, A, L> Socks5Acceptor<C, B, U, A, L> {
+    /// Define the authorizer used to authorize the username-password
+    /// credentials of the client, making authentication required.
+    ///
+    /// Any [`Authority`] for [`Basic`] credentials can be used,
+    /// e.g. a single [`Basic`] credential or a `Vec` of them.
+    pub fn with_authorizer<T>(self, auth: T) -> Socks5Acceptor<C, B, U, T, L> {
+        Socks5Acceptor {
+            connector: self.connector,
+            binder: self.binder,
+            udp_associator: self.udp_associator,
+            auth: Some(auth),
+            auth_opt: self.auth_opt,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Overwrite the Labels extract type
+    ///
+    /// This is used if the username contains labels that you need to extract out.
+    /// Example implementation is the [`UsernameOpaqueLabelParser`].
+    ///
+    /// You can provide your own extractor by implementing the [`UsernameLabelParser`] trait.
+    ///
+    /// [`UsernameOpaqueLabelParser`]: rama_core::username::UsernameOpaqueLabelParser
+    /// [`UsernameLabelParser`]: rama_core::username::UsernameLabelParser
+    pub fn with_labels<T>(self) -> Socks5Acceptor<C, B, U, A, T> {
+        Socks5Acceptor {
+            connector: self.connector,
+            binder: self.binder,
+            udp_associator: self.udp_associator,
+            auth: self.auth,
+            auth_opt: self.auth_opt,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Make the authentication optional,
+    /// allowing clients to connect without credentials
+    /// even when an authorizer is defined.
+    pub fn with_auth_optional(mut self, optional: bool) -> Self {
+        self.auth_opt = optional;
+        self
+    }
+
+    /// Make the authentication optional,
+    /// allowing clients to connect without credentials
+    /// even when an authorizer is defined.
+    pub fn set_auth_optional(&mut self, optional: bool) -> &mut Self {
+        self.auth_opt = optional;
+        self
+    }
+
+    /// Define the [`Socks5Connector`] used to serve [`Command::Connect`] requests.
+    ///
+    /// Use `()` to disable support for the connect command.
+    pub fn with_connector<T>(self, connector: T) -> Socks5Acceptor<T, B, U, A, L> {
+        Socks5Acceptor {
+            connector,
+            binder: self.binder,
+            udp_associator: self.udp_associator,
+            auth: self.auth,
+            auth_opt: self.auth_opt,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Define the [`Socks5Binder`] used to serve [`Command::Bind`] requests.
+    ///
+    /// By default (`()`) the bind command is not supported.
+    pub fn with_binder<T>(self, binder: T) -> Socks5Acceptor<C, T, U, A, L> {
+        Socks5Acceptor {
+            connector: self.connector,
+            binder,
+            udp_associator: self.udp_associator,
+            auth: self.auth,
+            auth_opt: self.auth_opt,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Define the [`Socks5UdpAssociator`] used to serve [`Command::UdpAssociate`] requests.
+    ///
+    /// By default (`()`) the udp associate command is not supported.
+    pub fn with_udp_associator<T>(self, udp_associator: T) -> Socks5Acceptor<C, B, T, A, L> {
+        Socks5Acceptor {
+            connector: self.connector,
+            binder: self.binder,
+            udp_associator,
+            auth: self.auth,
+            auth_opt: self.auth_opt,
+            _phantom: PhantomData,
+        }
+    }
+
+    fn select_method(&self, methods: &[SocksMethod]) -> SocksMethod {
+        let offers = |method| methods.contains(&method);
+        if self.auth.is_some() {
+            if offers(SocksMethod::UsernamePassword) {
+                return SocksMethod::UsernamePassword;
+            }
+            if !self.auth_opt {
+                return SocksMethod::NoAcceptableMethods;
+            }
+        }
+        if offers(SocksMethod::NoAuthenticationRequired) {
+            SocksMethod::NoAuthenticationRequired
+        } else {
+            SocksMethod::NoAcceptableMethods
+        }
+    }
+}
+
+impl<C, B, U, A, L> Socks5Acceptor<C, B, U, A, L>
+where
+    A: Authority<Basic, L>,
+    L: 'static,
+{
+    /// Negotiate the authentication method with the client,
+    /// and authenticate the client in case username-password was selected.
+    async fn handshake<State, S>(
+        &self,
+        ctx: &mut Context<State>,
+        stream: &mut S,
+    ) -> Result<(), Error>
+    where
+        State: Send + Sync + 'static,
+        S: Stream + Unpin,
+    {
+        let header = Header::read_from(stream)
+            .await
+            .map_err(|err| Error::protocol(err).with_context("read client header"))?;
+
+        let method = self.select_method(&header.methods);
+        tracing::trace!(
+            methods = ?header.methods,
+            %method,
+            "socks5 server: client header received: method selected",
+        );
+
+        ServerHeader::new(method)
+            .write_to(stream)
+            .await
+            .map_err(|err| Error::protocol(err).with_context("write server header"))?;
+
+        match method {
+            SocksMethod::NoAuthenticationRequired => Ok(()),
+            SocksMethod::UsernamePassword => {
+                let request = UsernamePasswordRequest::read_from(stream)
+                    .await
+                    .map_err(|err| {
+                        Error::protocol(err).with_context("read username-password request")
+                    })?;
+
+                let authorized = match self.auth.as_ref() {
+                    Some(auth) => auth.authorized(request.basic).await,
+                    None => None,
+                };
+
+                match authorized {
+                    Some(ext) => {
+                        UsernamePasswordResponse::new_success()
+                            .write_to(stream)
+                            .await
+                            .map_err(|err| {
+                                Error::protocol(err)
+                                    .with_context("write username-password response: success")
+                            })?;
+                        ctx.extend(ext);
+                        Ok(())
+                    }
+                    None => {
+                        UsernamePasswordResponse::new_invalid_credentials()
+                            .write_to(stream)
+                            .await
+                            .map_err(|err| {
+                                Error::protocol(err)
+                                    .with_context("write username-password response: failure")
+                            })?;
+                        Err(Error::aborted("unauthorized"))
+                    }
+                }
+            }
+            _ => Err(Error::aborted("no acceptable methods")),
+        }
+    }
+}
+
+impl<State, S, C, B, U, A, L> Service<State, S> for Socks5Acceptor<C, B, U, A, L>
+where
+    State: Send + Sync + 'static,
+    S: Stream + Unpin,
+    C: Socks5Connector<State, S>,
+    B: Socks5Binder<State, S>,
+    U: Socks5UdpAssociator<State, S>,
+    A: Authority<Basic, L>,
+    L: 'static,
+{
+    type Response = ();
+    type Error = Error;
+
+    async fn serve(&self, mut ctx: Context<State>, mut stream: S) -> Result<(), Self::Error> {
+        self.handshake(&mut ctx, &mut stream).await?;
+
+        let request = match Request::read_from(&mut stream).await {
+            Ok(request) => request,
+            Err(err) => {
+                if let ProtocolError::UnexpectedByte { pos: 3, .. } = err {
+                    Reply::error_reply(ReplyKind::AddressTypeNotSupported)
+                        .write_to(&mut stream)
+                        .await
+                        .map_err(|err| {
+                            Error::protocol(err).with_context("write server reply: bad request")
+                        })?;
+                }
+                return Err(Error::protocol(err).with_context("read client request"));
+            }
+        };
+
+        tracing::trace!(
+            command = %request.command,
+            destination = %request.destination,
+            "socks5 server: client request received",
+        );
+
+        match request.command {
+            Command::Connect => {
+                self.connector
+                    .accept_connect(ctx, stream, request.destination)
+                    .await
+            }
+            Command::Bind => {
+                self.binder
+                    .accept_bind(ctx, stream, request.destination)
+                    .await
+            }
+            Command::UdpAssociate => {
+                self.udp_associator
+                    .accept_udp_associate(ctx, stream, request.destination)
+                    .await
+            }
+            Command::Unknown(_) => {
+                tracing::debug!(
+                    command = %request.command,
+                    "socks5 server: abort: command not supported",
+                );
+                Reply::error_reply(ReplyKind::CommandNotSupported)
+                    .write_to(&mut stream)
+                    .await
+                    .map_err(|err| {
+                        Error::protocol(err).with_context("write server reply: unsupported")
+                    })?;
+                Err(Error::aborted("command not supported"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_net::user::UserId;
+
+    #[tokio::test]
+    async fn test_acceptor_no_auth_connect_not_supported() {
+        let stream = tokio_test::io::Builder::new()
+            .read(b"\x05\x01\x00")
+            .write(b"\x05\x00")
+            .read(b"\x05\x01\x00\x01\x7f\x00\x00\x01\x00\x50")
+            .write(b"\x05\x07\x00\x01\x00\x00\x00\x00\x00\x00")
+            .build();
+
+        let err = Socks5Acceptor::new()
+            .with_connector(())
+            .serve(Context::default(), stream)
+            .await
+            .unwrap_err();
+        assert!(err.is_aborted());
+    }
+
+    #[tokio::test]
+    async fn test_acceptor_unsupported_command() {
+        let stream = tokio_test::io::Builder::new()
+            .read(b"\x05\x01\x00")
+            .write(b"\x05\x00")
+            .read(b"\x05\x02\x00\x01\x7f\x00\x00\x01\x00\x50")
+            .write(b"\x05\x07\x00\x01\x00\x00\x00\x00\x00\x00")
+            .build();
+
+        let err = Socks5Acceptor::new()
+            .serve(Context::default(), stream)
+            .await
+            .unwrap_err();
+        assert!(err.is_aborted());
+    }
+
+    #[tokio::test]
+    async fn test_acceptor_address_type_not_supported() {
+        let stream = tokio_test::io::Builder::new()
+            .read(b"\x05\x01\x00")
+            .write(b"\x05\x00")
+            .read(b"\x05\x01\x00\x02")
+            .write(b"\x05\x08\x00\x01\x00\x00\x00\x00\x00\x00")
+            .build();
+
+        let err = Socks5Acceptor::new()
+            .serve(Context::default(), stream)
+            .await
+            .unwrap_err();
+        assert!(!err.is_aborted());
+    }
+
+    #[tokio::test]
+    async fn test_acceptor_auth_required_no_acceptable_methods() {
+        let stream = tokio_test::io::Builder::new()
+            .read(b"\x05\x01\x00")
+            .write(b"\x05\xff")
+            .build();
+
+        let err = Socks5Acceptor::new()
+            .with_authorizer(Basic::new("john", "secret"))
+            .serve(Context::default(), stream)
+            .await
+            .unwrap_err();
+        assert!(err.is_aborted());
+    }
+
+    #[tokio::test]
+    async fn test_acceptor_auth_optional_no_auth() {
+        let stream = tokio_test::io::Builder::new()
+            .read(b"\x05\x01\x00")
+            .write(b"\x05\x00")
+            .read(b"\x05\x01\x00\x01\x7f\x00\x00\x01\x00\x50")
+            .write(b"\x05\x07\x00\x01\x00\x00\x00\x00\x00\x00")
+            .build();
+
+        let err = Socks5Acceptor::new()
+            .with_connector(())
+            .with_authorizer(Basic::new("john", "secret"))
+            .with_auth_optional(true)
+            .serve(Context::default(), stream)
+            .await
+            .unwrap_err();
+        assert!(err.is_aborted());
+    }
+
+    #[tokio::test]
+    async fn test_acceptor_auth_invalid_credentials() {
+        let stream = tokio_test::io::Builder::new()
+            .read(b"\x05\x02\x00\x02")
+            .write(b"\x05\x02")
+            .read(b"\x01\x04john\x05wrong")
+            .write(b"\x01\x01")
+            .build();
+
+        let err = Socks5Acceptor::new()
+            .with_authorizer(Basic::new("john", "secret"))
+            .serve(Context::default(), stream)
+            .await
+            .unwrap_err();
+        assert!(err.is_aborted());
+    }
+
+    #[derive(Debug, Clone)]
+    struct AssertUserConnector;
+
+    impl<State, S> Socks5Connector<State, S> for AssertUserConnector
+    where
+        State: Send + Sync + 'static,
+        S: Stream + Unpin,
+    {
+        async fn accept_connect(
+            &self,
+            ctx: Context<State>,
+            mut stream: S,
+            destination: rama_net::address::Authority,
+        ) -> Result<(), Error> {
+            assert_eq!(
+                ctx.get::<UserId>(),
+                Some(&UserId::Username("john".to_owned()))
+            );
+            assert_eq!(destination.to_string(), "example.com:443");
+            Reply::new(([127, 0, 0, 1], 42))
+                .write_to(&mut stream)
+                .await
+                .map_err(Error::protocol)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_acceptor_auth_success() {
+        let stream = tokio_test::io::Builder::new()
+            .read(b"\x05\x02\x00\x02")
+            .write(b"\x05\x02")
+            .read(b"\x01\x04john\x06secret")
+            .write(b"\x01\x00")
+            .read(b"\x05\x01\x00\x03\x0bexample.com\x01\xbb")
+            .write(b"\x05\x00\x00\x01\x7f\x00\x00\x01\x00\x2a")
+            .build();
+
+        Socks5Acceptor::new()
+            .with_connector(AssertUserConnector)
+            .with_authorizer(Basic::new("john", "secret"))
+            .serve(Context::default(), stream)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,279 @@
+//! Socks5 server support for the [`Command::Bind`] command.
+//!
+//! [`Command::Bind`]: crate::proto::Command::Bind
+
+use super::{connect::reply_kind_from_error, Error};
+use crate::proto::{server::Reply, ReplyKind};
+use rama_core::{error::BoxError, Context, Service};
+use rama_net::{
+    address::{Authority, Host},
+    proxy::{ProxyRequest, StreamForwardService},
+    stream::SocketInfo,
+};
+use std::{
+    future::Future,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Types which can be used as socks5 [`Command::Bind`] drivers
+/// for the [`Socks5Acceptor`].
+///
+/// Implementors are responsible for replying to the client,
+/// both in case of success and failure.
+///
+/// [`Command::Bind`]: crate::proto::Command::Bind
+/// [`Socks5Acceptor`]: super::Socks5Acceptor
+pub trait Socks5Binder<S, Stream>: Send + Sync + 'static {
+    /// Accept a bind request for the given (expected) destination,
+    /// and proxy the data between the client and the inbound connection.
+    fn accept_bind(
+        &self,
+        ctx: Context<S>,
+        stream: Stream,
+        destination: Authority,
+    ) -> impl Future<Output = Result<(), Error>> + Send + '_;
+}
+
+impl<S, Stream> Socks5Binder<S, Stream> for ()
+where
+    S: Send + Sync + 'static,
+    Stream: rama_net::stream::Stream + Unpin,
+{
+    async fn accept_bind(
+        &self,
+        _ctx: Context<S>,
+        mut stream: Stream,
+        destination: Authority,
+    ) -> Result<(), Error> {
+        tracing::debug!(
+            %destination,
+            "socks5 server: abort: command not supported: bind",
+        );
+
+        Reply::error_reply(ReplyKind::CommandNotSupported)
+            .write_to(&mut stream)
+            .await
+            .map_err(|err| Error::protocol(err).with_context("write server reply: bind"))?;
+        Err(Error::aborted("command not supported: bind"))
+    }
+}
+
+/// The default [`Socks5Binder`] which can be used by the [`Socks5Acceptor`],
+/// forwarding the data between client and inbound connection as-is.
+///
+/// [`Socks5Acceptor`]: super::Socks5Acceptor
+pub type DefaultBinder = Binder<StreamForwardService>;
+
+/// A [`Socks5Binder`] which binds a TCP listener, accepting a single
+/// inbound connection, and serves the established [`ProxyRequest`]
+/// using the given [`Service`].
+///
+/// The listener is bound on the local interface of the client connection,
+/// unless a bind interface is defined. In case the destination of the
+/// bind request is an IP address (other than the unspecified address),
+/// only an inbound connection from that IP address is accepted.
+pub struct Binder<S> {
+    bind_interface: Option<IpAddr>,
+    accept_timeout: Option<Duration>,
+    service: S,
+}
+
+impl<S: std::fmt::Debug> std::fmt::Debug for Binder<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Binder")
+            .field("bind_interface", &self.bind_interface)
+            .field("accept_timeout", &self.accept_timeout)
+            .field("service", &self.service)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for Binder<S> {
+    fn clone(&self) -> Self {
+        Self {
+            bind_interface: self.bind_interface,
+            accept_timeout: self.accept_timeout,
+            service: self.service.clone(),
+        }
+    }
+}
+
+impl<S> Binder<S> {
+    /// Create a new [`Binder`] for the given [`Service`].
+    pub const fn new(service: S) -> Self {
+        Self {
+            bind_interface: None,
+            accept_timeout: None,
+            service,
+        }
+    }
+
+    /// Define the IP address of the interface to bind the listener on.
+    pub fn with_bind_interface(mut self, ip: impl Into<IpAddr>) -> Self {
+        self.bind_interface = Some(ip.into());
+        self
+    }
+
+    /// Define the IP address of the interface to bind the listener on.
+    pub fn set_bind_interface(&mut self, ip: impl Into<IpAddr>) -> &mut Self {
+        self.bind_interface = Some(ip.into());
+        self
+    }
+
+    /// Define the maximum duration to wait for an inbound connection.
+    pub fn with_accept_timeout(mut self, timeout: Duration) -> Self {
+        self.accept_timeout = Some(timeout);
+        self
+    }
+
+    /// Define the maximum duration to wait for an inbound connection.
+    pub fn set_accept_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.accept_timeout = Some(timeout);
+        self
+    }
+
+    /// Replace the [`Service`] used to serve the established [`ProxyRequest`].
+    pub fn with_service<T>(self, service: T) -> Binder<T> {
+        Binder {
+            bind_interface: self.bind_interface,
+            accept_timeout: self.accept_timeout,
+            service,
+        }
+    }
+}
+
+impl Default for DefaultBinder {
+    fn default() -> Self {
+        Self::new(StreamForwardService::new())
+    }
+}
+
+impl<State, Stream, S> Socks5Binder<State, Stream> for Binder<S>
+where
+    State: Send + Sync + 'static,
+    Stream: rama_net::stream::Stream + Unpin,
+    S: Service<State, ProxyRequest<Stream, TcpStream>, Response = (), Error: Into<BoxError>>,
+{
+    async fn accept_bind(
+        &self,
+        ctx: Context<State>,
+        mut stream: Stream,
+        destination: Authority,
+    ) -> Result<(), Error> {
+        let bind_ip = self
+            .bind_interface
+            .or_else(|| {
+                ctx.get::<SocketInfo>()
+                    .and_then(|info| info.local_addr())
+                    .map(|addr| addr.ip())
+            })
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+        tracing::trace!(%destination, %bind_ip, "socks5 server: bind: try to bind listener");
+
+        let listener = match TcpListener::bind((bind_ip, 0)).await {
+            Ok(listener) => listener,
+            Err(err) => {
+                write_error_reply(&mut stream, ReplyKind::GeneralServerFailure).await?;
+                return Err(Error::io(err).with_context("bind listener"));
+            }
+        };
+        let bind_address = listener
+            .local_addr()
+            .map_err(|err| Error::io(err).with_context("get local address of bound listener"))?;
+
+        Reply::new(bind_address)
+            .write_to(&mut stream)
+            .await
+            .map_err(|err| Error::protocol(err).with_context("write server reply: bind"))?;
+
+        let accept = listener.accept();
+        let result = match self.accept_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, accept)
+                .await
+                .unwrap_or_else(|_| Err(io::Error::from(io::ErrorKind::TimedOut))),
+            None => accept.await,
+        };
+        let (target, peer_addr) = match result {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                let err: BoxError = err.into();
+                write_error_reply(&mut stream, reply_kind_from_error(&err)).await?;
+                return Err(Error::service(err).with_context("accept inbound connection"));
+            }
+        };
+
+        if !is_expected_peer(&destination, peer_addr) {
+            tracing::debug!(
+                %destination,
+                %peer_addr,
+                "socks5 server: bind: abort: unexpected inbound connection",
+            );
+            write_error_reply(&mut stream, ReplyKind::ConnectionNotAllowed).await?;
+            return Err(Error::aborted("unexpected inbound connection"));
+        }
+
+        Reply::new(peer_addr)
+            .write_to(&mut stream)
+            .await
+            .map_err(|err| Error::protocol(err).with_context("write server reply: bind accept"))?;
+
+        tracing::trace!(
+            %destination,
+            %bind_address,
+            %peer_addr,
+            "socks5 server: bind: inbound connection accepted, serve proxy request",
+        );
+
+        self.service
+            .serve(
+                ctx,
+                ProxyRequest {
+                    source: stream,
+                    target,
+                },
+            )
+            .await
+            .map_err(|err| Error::service(err).with_context("serve bind proxy request"))
+    }
+}
+
+async fn write_error_reply<S: rama_net::stream::Stream + Unpin>(
+    stream: &mut S,
+    kind: ReplyKind,
+) -> Result<(), Error> {
+    Reply::error_reply(kind)
+        .write_to(stream)
+        .await
+        .map_err(|err| Error::protocol(err).with_context("write server reply: bind failed"))
+}
+
+fn is_expected_peer(destination: &Authority, peer_addr: SocketAddr) -> bool {
+    match destination.host() {
+        Host::Address(ip) if !ip.is_unspecified() => {
+            ip.to_canonical() == peer_addr.ip().to_canonical()
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_expected_peer() {
+        let peer: SocketAddr = ([127, 0, 0, 1], 4000).into();
+        assert!(is_expected_peer(&([0, 0, 0, 0], 0).into(), peer));
+        assert!(is_expected_peer(&([127, 0, 0, 1], 0).into(), peer));
+        assert!(is_expected_peer(&([127, 0, 0, 1], 1234).into(), peer));
+        assert!(!is_expected_peer(&([127, 0, 0, 2], 0).into(), peer));
+        assert!(is_expected_peer(
+            &(rama_net::address::Domain::example(), 80).into(),
+            peer
+        ));
+    }
+}
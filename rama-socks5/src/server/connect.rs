@@ -0,0 +1,217 @@
+//! Socks5 server support for the [`Command::Connect`] command.
+//!
+//! [`Command::Connect`]: crate::proto::Command::Connect
+
+use super::Error;
+use crate::proto::{server::Reply, ReplyKind};
+use rama_core::{error::BoxError, Context, Service};
+use rama_net::{
+    address::Authority,
+    client::{ConnectorService, EstablishedClientConnection},
+    proxy::{ProxyRequest, StreamForwardService},
+    stream::Socket,
+};
+use rama_tcp::client::{service::TcpConnector, Request as TcpRequest};
+use std::{
+    future::Future,
+    io,
+    net::{Ipv4Addr, SocketAddr},
+};
+
+/// Types which can be used as socks5 [`Command::Connect`] drivers
+/// for the [`Socks5Acceptor`].
+///
+/// Implementors are responsible for replying to the client,
+/// both in case of success and failure.
+///
+/// [`Command::Connect`]: crate::proto::Command::Connect
+/// [`Socks5Acceptor`]: super::Socks5Acceptor
+pub trait Socks5Connector<S, Stream>: Send + Sync + 'static {
+    /// Accept a connect request for the given destination,
+    /// and proxy the data between the client and the destination.
+    fn accept_connect(
+        &self,
+        ctx: Context<S>,
+        stream: Stream,
+        destination: Authority,
+    ) -> impl Future<Output = Result<(), Error>> + Send + '_;
+}
+
+impl<S, Stream> Socks5Connector<S, Stream> for ()
+where
+    S: Send + Sync + 'static,
+    Stream: rama_net::stream::Stream + Unpin,
+{
+    async fn accept_connect(
+        &self,
+        _ctx: Context<S>,
+        mut stream: Stream,
+        destination: Authority,
+    ) -> Result<(), Error> {
+        tracing::debug!(
+            %destination,
+            "socks5 server: abort: command not supported: connect",
+        );
+
+        Reply::error_reply(ReplyKind::CommandNotSupported)
+            .write_to(&mut stream)
+            .await
+            .map_err(|err| Error::protocol(err).with_context("write server reply: connect"))?;
+        Err(Error::aborted("command not supported: connect"))
+    }
+}
+
+/// The default [`Socks5Connector`] used by the [`Socks5Acceptor`],
+/// establishing a TCP connection to the destination and
+/// forwarding the data between client and destination as-is.
+///
+/// [`Socks5Acceptor`]: super::Socks5Acceptor
+pub type DefaultConnector = Connector<TcpConnector, StreamForwardService>;
+
+/// A [`Socks5Connector`] which establishes a connection using the given
+/// [`ConnectorService`] and serves the established [`ProxyRequest`]
+/// using the given [`Service`].
+pub struct Connector<C, S> {
+    connector: C,
+    service: S,
+}
+
+impl<C: std::fmt::Debug, S: std::fmt::Debug> std::fmt::Debug for Connector<C, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Connector")
+            .field("connector", &self.connector)
+            .field("service", &self.service)
+            .finish()
+    }
+}
+
+impl<C: Clone, S: Clone> Clone for Connector<C, S> {
+    fn clone(&self) -> Self {
+        Self {
+            connector: self.connector.clone(),
+            service: self.service.clone(),
+        }
+    }
+}
+
+impl<C, S> Connector<C, S> {
+    /// Create a new [`Connector`] for the given [`ConnectorService`] and [`Service`].
+    pub const fn new(connector: C, service: S) -> Self {
+        Self { connector, service }
+    }
+
+    /// Replace the [`ConnectorService`] used to connect to the destination.
+    pub fn with_connector<T>(self, connector: T) -> Connector<T, S> {
+        Connector {
+            connector,
+            service: self.service,
+        }
+    }
+
+    /// Replace the [`Service`] used to serve the established [`ProxyRequest`].
+    pub fn with_service<T>(self, service: T) -> Connector<C, T> {
+        Connector {
+            connector: self.connector,
+            service,
+        }
+    }
+}
+
+impl Default for DefaultConnector {
+    fn default() -> Self {
+        Self::new(TcpConnector::new(), StreamForwardService::new())
+    }
+}
+
+impl<State, Stream, C, S> Socks5Connector<State, Stream> for Connector<C, S>
+where
+    State: Send + Sync + 'static,
+    Stream: rama_net::stream::Stream + Unpin,
+    C: ConnectorService<
+        State,
+        TcpRequest,
+        Connection: rama_net::stream::Stream + Socket + Unpin,
+        Error: Into<BoxError>,
+    >,
+    S: Service<State, ProxyRequest<Stream, C::Connection>, Response = (), Error: Into<BoxError>>,
+{
+    async fn accept_connect(
+        &self,
+        ctx: Context<State>,
+        mut stream: Stream,
+        destination: Authority,
+    ) -> Result<(), Error> {
+        tracing::trace!(%destination, "socks5 server: connect: try to establish connection");
+
+        let result = self
+            .connector
+            .connect(ctx, TcpRequest::new(destination.clone()))
+            .await
+            .map_err(Into::<BoxError>::into);
+
+        let EstablishedClientConnection { ctx, conn, .. } = match result {
+            Ok(established) => established,
+            Err(err) => {
+                tracing::debug!(
+                    %destination,
+                    error = %err,
+                    "socks5 server: connect: failed to establish connection",
+                );
+
+                Reply::error_reply(reply_kind_from_error(&err))
+                    .write_to(&mut stream)
+                    .await
+                    .map_err(|err| {
+                        Error::protocol(err).with_context("write server reply: connect failed")
+                    })?;
+                return Err(Error::service(err).with_context("connect to destination"));
+            }
+        };
+
+        let bind_address = conn
+            .local_addr()
+            .unwrap_or_else(|_| SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0));
+        Reply::new(bind_address)
+            .write_to(&mut stream)
+            .await
+            .map_err(|err| Error::protocol(err).with_context("write server reply: connect"))?;
+
+        tracing::trace!(
+            %destination,
+            %bind_address,
+            "socks5 server: connect: connection established, serve proxy request",
+        );
+
+        self.service
+            .serve(
+                ctx,
+                ProxyRequest {
+                    source: stream,
+                    target: conn,
+                },
+            )
+            .await
+            .map_err(|err| Error::service(err).with_context("serve connect proxy request"))
+    }
+}
+
+/// Map an error, which occurred while connecting to the destination,
+/// to the most appropriate [`ReplyKind`].
+pub(crate) fn reply_kind_from_error(error: &BoxError) -> ReplyKind {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(error.as_ref());
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<io::Error>() {
+            return match err.kind() {
+                io::ErrorKind::ConnectionRefused => ReplyKind::ConnectionRefused,
+                io::ErrorKind::PermissionDenied => ReplyKind::ConnectionNotAllowed,
+                io::ErrorKind::TimedOut => ReplyKind::TtlExpired,
+                io::ErrorKind::AddrNotAvailable | io::ErrorKind::NotFound => {
+                    ReplyKind::HostUnreachable
+                }
+                _ => ReplyKind::GeneralServerFailure,
+            };
+        }
+        source = err.source();
+    }
+    ReplyKind::HostUnreachable
+}
@@ -0,0 +1,94 @@
+use crate::proto::ProtocolError;
+use rama_core::error::BoxError;
+use std::fmt;
+
+#[derive(Debug)]
+/// Error returned by the socks5 server
+/// in case the client could not be served.
+pub struct Error {
+    kind: ErrorKind,
+    context: Option<&'static str>,
+}
+
+#[derive(Debug)]
+enum ErrorKind {
+    IO(std::io::Error),
+    Protocol(ProtocolError),
+    Aborted(&'static str),
+    Service(BoxError),
+}
+
+impl Error {
+    pub(crate) fn io(error: std::io::Error) -> Self {
+        Self {
+            kind: ErrorKind::IO(error),
+            context: None,
+        }
+    }
+
+    pub(crate) fn protocol(error: ProtocolError) -> Self {
+        Self {
+            kind: ErrorKind::Protocol(error),
+            context: None,
+        }
+    }
+
+    pub(crate) fn aborted(reason: &'static str) -> Self {
+        Self {
+            kind: ErrorKind::Aborted(reason),
+            context: None,
+        }
+    }
+
+    pub(crate) fn service(error: impl Into<BoxError>) -> Self {
+        Self {
+            kind: ErrorKind::Service(error.into()),
+            context: None,
+        }
+    }
+
+    pub(crate) fn with_context(mut self, context: &'static str) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Returns `true` in case the serving was aborted
+    /// due to an unsupported or unauthorized client request,
+    /// rather than an I/O or protocol error.
+    pub fn is_aborted(&self) -> bool {
+        matches!(self.kind, ErrorKind::Aborted(_))
+    }
+}
+
+impl From<ProtocolError> for Error {
+    fn from(error: ProtocolError) -> Self {
+        Self::protocol(error)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let context = self.context.unwrap_or("no context");
+        match &self.kind {
+            ErrorKind::IO(error) => write!(f, "socks5 server: i/o error ({context}): {error}"),
+            ErrorKind::Protocol(error) => {
+                write!(f, "socks5 server: protocol error ({context}): {error}")
+            }
+            ErrorKind::Aborted(reason) => write!(f, "socks5 server: aborted ({context}): {reason}"),
+            ErrorKind::Service(error) => {
+                write!(f, "socks5 server: service error ({context}): {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ErrorKind::IO(err) => Some(err),
+            ErrorKind::Protocol(err) => Some(err),
+            ErrorKind::Aborted(_) => None,
+            ErrorKind::Service(err) => Some(err.as_ref()),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+//! Socks5 server support.
+//!
+//! Use the [`Socks5Acceptor`] to serve socks5 clients,
+//! e.g. on top of a [`TcpListener`].
+//!
+//! [`TcpListener`]: rama_tcp::server::TcpListener
+
+mod error;
+#[doc(inline)]
+pub use error::Error;
+
+pub mod connect;
+#[doc(inline)]
+pub use connect::{Connector, DefaultConnector, Socks5Connector};
+
+pub mod bind;
+#[doc(inline)]
+pub use bind::{Binder, DefaultBinder, Socks5Binder};
+
+pub mod udp;
+#[doc(inline)]
+pub use udp::{DefaultUdpRelay, Socks5UdpAssociator, UdpRelay};
+
+mod acceptor;
+#[doc(inline)]
+pub use acceptor::Socks5Acceptor;
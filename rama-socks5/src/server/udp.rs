@@ -0,0 +1,439 @@
+//! Socks5 server support for the [`Command::UdpAssociate`] command.
+//!
+//! [`Command::UdpAssociate`]: crate::proto::Command::UdpAssociate
+
+use super::Error;
+use crate::{
+    dns::resolve_authority,
+    proto::{server::Reply, udp::UdpHeader, ReplyKind},
+};
+use rama_core::{error::BoxError, Context};
+use rama_dns::{DnsResolver, HickoryDns};
+use rama_net::{
+    address::{Authority, Host},
+    stream::SocketInfo,
+};
+use rama_udp::UdpSocket;
+use std::{
+    future::Future,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{io::AsyncReadExt, task::JoinSet};
+
+/// Types which can be used as socks5 [`Command::UdpAssociate`] drivers
+/// for the [`Socks5Acceptor`].
+///
+/// Implementors are responsible for replying to the client,
+/// both in case of success and failure. The association is expected
+/// to be terminated as soon as the (tcp) control connection is closed.
+///
+/// [`Command::UdpAssociate`]: crate::proto::Command::UdpAssociate
+/// [`Socks5Acceptor`]: super::Socks5Acceptor
+pub trait Socks5UdpAssociator<S, Stream>: Send + Sync + 'static {
+    /// Accept a udp associate request, where the destination is the address
+    /// the client expects to send its datagrams from (possibly unspecified),
+    /// and relay the datagrams between the client and its destinations.
+    fn accept_udp_associate(
+        &self,
+        ctx: Context<S>,
+        stream: Stream,
+        destination: Authority,
+    ) -> impl Future<Output = Result<(), Error>> + Send + '_;
+}
+
+impl<S, Stream> Socks5UdpAssociator<S, Stream> for ()
+where
+    S: Send + Sync + 'static,
+    Stream: rama_net::stream::Stream + Unpin,
+{
+    async fn accept_udp_associate(
+        &self,
+        _ctx: Context<S>,
+        mut stream: Stream,
+        destination: Authority,
+    ) -> Result<(), Error> {
+        tracing::debug!(
+            %destination,
+            "socks5 server: abort: command not supported: udp associate",
+        );
+
+        Reply::error_reply(ReplyKind::CommandNotSupported)
+            .write_to(&mut stream)
+            .await
+            .map_err(|err| {
+                Error::protocol(err).with_context("write server reply: udp associate")
+            })?;
+        Err(Error::aborted("command not supported: udp associate"))
+    }
+}
+
+/// The default [`Socks5UdpAssociator`] which can be used by the [`Socks5Acceptor`].
+///
+/// [`Socks5Acceptor`]: super::Socks5Acceptor
+pub type DefaultUdpRelay = UdpRelay<HickoryDns>;
+
+/// A [`Socks5UdpAssociator`] which binds a [`UdpSocket`] and relays
+/// datagrams between the client and its destinations for as long
+/// as the control connection remains open.
+///
+/// The socket is bound on the local interface of the client connection,
+/// unless a bind interface is defined. Datagrams from the client are
+/// only accepted from the IP address of the control connection
+/// (or the address requested by the client), while fragmented
+/// datagrams are dropped as fragmentation is not supported.
+///
+/// Domain destinations are resolved in the background, such that
+/// a slow lookup does not hold up the other datagrams of the association.
+pub struct UdpRelay<Dns = HickoryDns> {
+    bind_interface: Option<IpAddr>,
+    buffer_size: usize,
+    dns: Dns,
+}
+
+const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+/// Time allowed to resolve the destination of a client datagram, before it is dropped.
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Maximum number of client datagrams waiting for their destination to be resolved,
+/// additional datagrams with a domain destination are dropped.
+const MAX_PENDING_RESOLVES: usize = 64;
+
+impl<Dns: std::fmt::Debug> std::fmt::Debug for UdpRelay<Dns> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UdpRelay")
+            .field("bind_interface", &self.bind_interface)
+            .field("buffer_size", &self.buffer_size)
+            .field("dns", &self.dns)
+            .finish()
+    }
+}
+
+impl<Dns: Clone> Clone for UdpRelay<Dns> {
+    fn clone(&self) -> Self {
+        Self {
+            bind_interface: self.bind_interface,
+            buffer_size: self.buffer_size,
+            dns: self.dns.clone(),
+        }
+    }
+}
+
+impl UdpRelay {
+    /// Create a new [`UdpRelay`], using the default [`HickoryDns`]
+    /// to resolve domain destinations.
+    pub fn new() -> Self {
+        Self {
+            bind_interface: None,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            dns: HickoryDns::default(),
+        }
+    }
+}
+
+impl Default for UdpRelay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Dns> UdpRelay<Dns> {
+    /// Define the IP address of the interface to bind the relay socket on.
+    pub fn with_bind_interface(mut self, ip: impl Into<IpAddr>) -> Self {
+        self.bind_interface = Some(ip.into());
+        self
+    }
+
+    /// Define the IP address of the interface to bind the relay socket on.
+    pub fn set_bind_interface(&mut self, ip: impl Into<IpAddr>) -> &mut Self {
+        self.bind_interface = Some(ip.into());
+        self
+    }
+
+    /// Define the size of the buffer used to receive datagrams,
+    /// larger datagrams are truncated.
+    pub fn with_buffer_size(mut self, size: usize) -> Self {
+        self.buffer_size = size;
+        self
+    }
+
+    /// Define the size of the buffer used to receive datagrams,
+    /// larger datagrams are truncated.
+    pub fn set_buffer_size(&mut self, size: usize) -> &mut Self {
+        self.buffer_size = size;
+        self
+    }
+
+    /// Attach the given [`DnsResolver`], used to resolve domain destinations.
+    pub fn with_dns<T>(self, dns: T) -> UdpRelay<T> {
+        UdpRelay {
+            bind_interface: self.bind_interface,
+            buffer_size: self.buffer_size,
+            dns,
+        }
+    }
+}
+
+impl<State, Stream, Dns> Socks5UdpAssociator<State, Stream> for UdpRelay<Dns>
+where
+    State: Send + Sync + 'static,
+    Stream: rama_net::stream::Stream + Unpin,
+    Dns: DnsResolver<Error: Into<BoxError>> + Clone,
+{
+    async fn accept_udp_associate(
+        &self,
+        ctx: Context<State>,
+        mut stream: Stream,
+        destination: Authority,
+    ) -> Result<(), Error> {
+        let socket_info = ctx.get::<SocketInfo>();
+        let bind_ip = self
+            .bind_interface
+            .or_else(|| {
+                socket_info
+                    .and_then(|info| info.local_addr())
+                    .map(|addr| addr.ip())
+            })
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+        let socket = match UdpSocket::bind((bind_ip, 0)).await {
+            Ok(socket) => socket,
+            Err(err) => {
+                Reply::error_reply(ReplyKind::GeneralServerFailure)
+                    .write_to(&mut stream)
+                    .await
+                    .map_err(|err| {
+                        Error::protocol(err)
+                            .with_context("write server reply: udp associate failed")
+                    })?;
+                return Err(Error::io(err).with_context("bind udp relay socket"));
+            }
+        };
+        let socket = Arc::new(socket);
+        let bind_address = socket
+            .local_addr()
+            .map_err(|err| Error::io(err).with_context("get local address of udp relay socket"))?;
+
+        Reply::new(bind_address)
+            .write_to(&mut stream)
+            .await
+            .map_err(|err| {
+                Error::protocol(err).with_context("write server reply: udp associate")
+            })?;
+
+        tracing::trace!(
+            %destination,
+            %bind_address,
+            "socks5 server: udp associate: relay socket bound",
+        );
+
+        let mut client = ClientFilter::new(&destination, socket_info.map(|info| *info.peer_addr()));
+        let mut buf = vec![0u8; self.buffer_size];
+        let mut control_buf = [0u8; 64];
+        // dropping the set aborts the lookups still in flight once the association ends
+        let mut pending_resolves = JoinSet::new();
+
+        loop {
+            tokio::select! {
+                result = stream.read(&mut control_buf) => {
+                    match result {
+                        Ok(0) => {
+                            tracing::trace!(%bind_address, "socks5 server: udp associate: control connection closed");
+                            return Ok(());
+                        }
+                        Ok(_) => (),
+                        Err(err) => return Err(Error::io(err).with_context("read control connection")),
+                    }
+                }
+                result = socket.recv_from(&mut buf) => {
+                    let (n, src) = result.map_err(|err| Error::io(err).with_context("receive datagram"))?;
+                    if client.matches(src) {
+                        self.relay_to_destination(&socket, &mut pending_resolves, &buf[..n]).await;
+                    } else if let Some(client_addr) = client.addr {
+                        relay_to_client(&socket, client_addr, src, &buf[..n]).await;
+                    } else {
+                        tracing::trace!(%src, "socks5 server: udp associate: drop datagram: client address unknown");
+                    }
+                }
+                Some(_) = pending_resolves.join_next(), if !pending_resolves.is_empty() => (),
+            }
+        }
+    }
+}
+
+impl<Dns> UdpRelay<Dns>
+where
+    Dns: DnsResolver<Error: Into<BoxError>> + Clone,
+{
+    async fn relay_to_destination(
+        &self,
+        socket: &Arc<UdpSocket>,
+        pending_resolves: &mut JoinSet<()>,
+        datagram: &[u8],
+    ) {
+        let mut data = datagram;
+        let header = match UdpHeader::read_from(&mut data).await {
+            Ok(header) => header,
+            Err(err) => {
+                tracing::debug!(error = %err, "socks5 server: udp associate: drop invalid client datagram");
+                return;
+            }
+        };
+        if header.fragment_number != 0 {
+            tracing::debug!(
+                fragment_number = header.fragment_number,
+                "socks5 server: udp associate: drop fragmented client datagram",
+            );
+            return;
+        }
+
+        let destination = header.destination;
+        if let Host::Address(ip) = destination.host() {
+            send_to_destination(socket, data, (*ip, destination.port()).into()).await;
+            return;
+        }
+
+        if pending_resolves.len() >= MAX_PENDING_RESOLVES {
+            tracing::debug!(
+                %destination,
+                "socks5 server: udp associate: drop client datagram: too many pending resolves",
+            );
+            return;
+        }
+        let dns = self.dns.clone();
+        let socket = socket.clone();
+        let data = data.to_vec();
+        pending_resolves.spawn(async move {
+            let resolved =
+                tokio::time::timeout(RESOLVE_TIMEOUT, resolve_authority(&dns, destination.clone()))
+                    .await;
+            match resolved {
+                Ok(Ok(target)) => send_to_destination(&socket, &data, target).await,
+                Ok(Err(err)) => tracing::debug!(
+                    error = %err,
+                    %destination,
+                    "socks5 server: udp associate: drop client datagram: resolve destination",
+                ),
+                Err(_) => tracing::debug!(
+                    %destination,
+                    "socks5 server: udp associate: drop client datagram: resolve destination timed out",
+                ),
+            }
+        });
+    }
+}
+
+async fn send_to_destination(socket: &UdpSocket, data: &[u8], target: SocketAddr) {
+    if let Err(err) = socket.send_to(data, target).await {
+        tracing::debug!(
+            error = %err,
+            %target,
+            "socks5 server: udp associate: failed to relay datagram to destination",
+        );
+    }
+}
+
+async fn relay_to_client(socket: &UdpSocket, client: SocketAddr, src: SocketAddr, data: &[u8]) {
+    let mut datagram = Vec::with_capacity(data.len() + 22);
+    if let Err(err) = UdpHeader::new(src).write_to(&mut datagram).await {
+        tracing::debug!(error = %err, "socks5 server: udp associate: failed to write datagram header");
+        return;
+    }
+    datagram.extend_from_slice(data);
+
+    if let Err(err) = socket.send_to(&datagram, client).await {
+        tracing::debug!(
+            error = %err,
+            %client,
+            "socks5 server: udp associate: failed to relay datagram to client",
+        );
+    }
+}
+
+/// Filter used to recognise the datagrams send by the client,
+/// locking onto the first matching source address.
+#[derive(Debug)]
+struct ClientFilter {
+    addr: Option<SocketAddr>,
+    ip: Option<IpAddr>,
+    port: Option<u16>,
+}
+
+impl ClientFilter {
+    fn new(destination: &Authority, peer_addr: Option<SocketAddr>) -> Self {
+        let ip = match destination.host() {
+            Host::Address(ip) if !ip.is_unspecified() => Some(*ip),
+            _ => peer_addr.map(|addr| addr.ip()),
+        };
+        let port = Some(destination.port()).filter(|port| *port != 0);
+        let addr = match (ip, port) {
+            (Some(ip), Some(port)) => Some((ip, port).into()),
+            _ => None,
+        };
+        Self { addr, ip, port }
+    }
+
+    fn matches(&mut self, src: SocketAddr) -> bool {
+        if let Some(addr) = self.addr {
+            return addr.ip().to_canonical() == src.ip().to_canonical()
+                && addr.port() == src.port();
+        }
+        let ip_matches = self
+            .ip
+            .map(|ip| ip.to_canonical() == src.ip().to_canonical())
+            .unwrap_or(true);
+        let port_matches = self.port.map(|port| port == src.port()).unwrap_or(true);
+        if ip_matches && port_matches {
+            self.addr = Some(src);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_filter_locks_on_first_match() {
+        let mut filter = ClientFilter::new(
+            &([0, 0, 0, 0], 0).into(),
+            Some(([127, 0, 0, 1], 50000).into()),
+        );
+        assert!(!filter.matches(([10, 0, 0, 1], 1234).into()));
+        assert!(filter.matches(([127, 0, 0, 1], 1234).into()));
+        assert!(!filter.matches(([127, 0, 0, 1], 4321).into()));
+        assert!(filter.matches(([127, 0, 0, 1], 1234).into()));
+    }
+
+    #[test]
+    fn test_client_filter_requested_address() {
+        let mut filter = ClientFilter::new(
+            &([10, 0, 0, 2], 5353).into(),
+            Some(([127, 0, 0, 1], 50000).into()),
+        );
+        assert!(!filter.matches(([127, 0, 0, 1], 5353).into()));
+        assert!(filter.matches(([10, 0, 0, 2], 5353).into()));
+    }
+
+    #[test]
+    fn test_client_filter_v4_mapped() {
+        let mut filter = ClientFilter::new(
+            &([127, 0, 0, 1], 5353).into(),
+            Some(([127, 0, 0, 1], 50000).into()),
+        );
+        let mapped = std::net::Ipv4Addr::new(127, 0, 0, 1).to_ipv6_mapped();
+        assert!(filter.matches((mapped, 5353).into()));
+        assert!(filter.matches(([127, 0, 0, 1], 5353).into()));
+        assert!(!filter.matches((mapped, 5354).into()));
+    }
+
+    #[test]
+    fn test_client_filter_unknown_peer() {
+        let mut filter = ClientFilter::new(&([0, 0, 0, 0], 0).into(), None);
+        assert!(filter.matches(([192, 168, 1, 1], 1234).into()));
+        assert!(!filter.matches(([192, 168, 1, 1], 1235).into()));
+    }
+}
@@ -1,11 +1,13 @@
 use rama_core::{
     combinators::Either,
-    error::{BoxError, ErrorContext, OpaqueError},
+    error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
     Context,
 };
-use rama_dns::{DnsOverwrite, DnsResolver};
+use rama_dns::{DnsOverwrite, DnsResolver, SrvName, SrvRecord, SrvServiceUnavailableErr};
 use rama_net::address::{Authority, Domain, Host};
+use rama_utils::rng::{HasherRng, Rng};
 use std::{
+    error::Error,
     future::Future,
     net::{IpAddr, SocketAddr},
     ops::Deref,
@@ -150,6 +152,117 @@ where
     tcp_connect_inner(ctx, domain, port, dns, connector).await
 }
 
+/// Establish a [`TcpStream`] connection for the given [`Authority`],
+/// connecting to the targets of the 'SRV' records of the given service (e.g. `xmpp-client`)
+/// offered by its domain.
+///
+/// Targets are tried in order of priority, and within the same priority,
+/// in a random order weighted by their weight (RFC 2782). In case the service has no 'SRV' records,
+/// or the authority is not a domain, the connection is established
+/// to the authority itself using [`tcp_connect`]. An error is returned without falling back
+/// in case the service is decidedly not available at the domain ([`SrvServiceUnavailableErr`]).
+pub async fn tcp_connect_srv<State, Dns, Connector>(
+    ctx: &Context<State>,
+    service: &str,
+    authority: Authority,
+    allow_overwrites: bool,
+    dns: Dns,
+    connector: Connector,
+) -> Result<(TcpStream, SocketAddr), OpaqueError>
+where
+    State: Send + Sync + 'static,
+    Dns: DnsResolver<Error: Into<BoxError>> + Clone,
+    Connector: TcpStreamConnector<Error: Into<BoxError> + Send + 'static> + Clone,
+{
+    let srv_name = match authority.host() {
+        Host::Name(domain) => {
+            SrvName::tcp(service.to_owned(), domain.clone()).context("create srv name")?
+        }
+        Host::Address(_) => {
+            return tcp_connect(ctx, authority, allow_overwrites, dns, connector).await;
+        }
+    };
+
+    let records = match dns.srv_lookup(srv_name.clone()).await {
+        Ok(records) => records,
+        Err(err) => {
+            let err = OpaqueError::from_boxed(err.into());
+            if is_srv_service_unavailable(&err) {
+                return Err(err.context(format!("service {srv_name} not available")));
+            }
+            tracing::trace!(err = %err, "failed to resolve srv records for {srv_name}");
+            Vec::new()
+        }
+    };
+    if records.is_empty() {
+        tracing::trace!("no srv records found for {srv_name}: connect to {authority}");
+        return tcp_connect(ctx, authority, allow_overwrites, dns, connector).await;
+    }
+
+    for record in order_srv_records(records, &mut HasherRng::default()) {
+        let target = Authority::new(record.target().clone().into_host(), record.port());
+        match tcp_connect(
+            ctx,
+            target.clone(),
+            allow_overwrites,
+            dns.clone(),
+            connector.clone(),
+        )
+        .await
+        {
+            Ok(tuple) => return Ok(tuple),
+            Err(err) => {
+                tracing::trace!(err = %err, "failed to connect to srv target {target} of {srv_name}");
+            }
+        }
+    }
+
+    Err(OpaqueError::from_display(format!(
+        "failed to connect to any srv target of {srv_name}"
+    )))
+}
+
+fn is_srv_service_unavailable(err: &OpaqueError) -> bool {
+    let mut source: Option<&(dyn Error + 'static)> = Some(err);
+    while let Some(err) = source {
+        if err.is::<SrvServiceUnavailableErr>() {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Order the 'SRV' records by priority, and within the same priority
+/// using the weighted random selection defined in RFC 2782.
+fn order_srv_records(mut records: Vec<SrvRecord>, rng: &mut impl Rng) -> Vec<SrvRecord> {
+    // records with weight zero come first, such that they have a very small chance to be selected
+    records.sort_by_key(|record| (record.priority(), record.weight() != 0));
+
+    let mut ordered = Vec::with_capacity(records.len());
+    while let Some(priority) = records.first().map(SrvRecord::priority) {
+        let end = records
+            .iter()
+            .position(|record| record.priority() != priority)
+            .unwrap_or(records.len());
+        let mut group: Vec<_> = records.drain(..end).collect();
+        while !group.is_empty() {
+            let total: u64 = group.iter().map(|record| record.weight() as u64).sum();
+            let selected = rng.next_range(0..total + 1);
+            let mut running_sum = 0;
+            let index = group
+                .iter()
+                .position(|record| {
+                    running_sum += record.weight() as u64;
+                    running_sum >= selected
+                })
+                .unwrap_or_default();
+            ordered.push(group.remove(index));
+        }
+    }
+    ordered
+}
+
 async fn tcp_connect_inner<State, Dns, Connector>(
     ctx: &Context<State>,
     domain: Domain,
@@ -296,3 +409,52 @@ async fn tcp_connect_inner_branch<Dns, Connector>(
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(priority: u16, weight: u16, target: &'static str) -> SrvRecord {
+        SrvRecord::new(priority, weight, 443, Domain::from_static(target))
+    }
+
+    fn targets(records: &[SrvRecord]) -> Vec<&str> {
+        records
+            .iter()
+            .map(|record| record.target().as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_order_srv_records() {
+        let mut rng = HasherRng::default();
+        let mut heavy_first = 0;
+        for _ in 0..1000 {
+            let ordered = order_srv_records(
+                vec![
+                    record(2, 0, "backup.example.com"),
+                    record(1, 1, "light.example.com"),
+                    record(1, 99, "heavy.example.com"),
+                ],
+                &mut rng,
+            );
+            let ordered = targets(&ordered);
+            assert_eq!(ordered[2], "backup.example.com");
+            if ordered[0] == "heavy.example.com" {
+                heavy_first += 1;
+            }
+        }
+        assert!(heavy_first > 900, "heavy first: {heavy_first}");
+        assert!(heavy_first < 1000, "heavy first: {heavy_first}");
+    }
+
+    #[test]
+    fn test_srv_service_unavailable() {
+        let err = OpaqueError::from_std(SrvServiceUnavailableErr::new());
+        let err = OpaqueError::from_boxed(err.into()).context("lookup srv");
+        assert!(is_srv_service_unavailable(&err));
+        assert!(!is_srv_service_unavailable(&OpaqueError::from_display(
+            "timeout"
+        )));
+    }
+}
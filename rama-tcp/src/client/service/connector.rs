@@ -8,6 +8,7 @@ use rama_net::{
     client::EstablishedClientConnection,
     transport::{TransportProtocol, TryRefIntoTransportContext},
 };
+use std::borrow::Cow;
 use tokio::net::TcpStream;
 
 use crate::client::connect::TcpStreamConnector;
@@ -18,6 +19,7 @@ use crate::client::connect::TcpStreamConnector;
 pub struct TcpConnector<Dns = HickoryDns, Connector = ()> {
     dns: Dns,
     connector: Connector,
+    srv_service: Option<Cow<'static, str>>,
 }
 
 impl<Dns, Connector> TcpConnector<Dns, Connector> {}
@@ -31,6 +33,7 @@ impl TcpConnector {
         Self {
             dns: HickoryDns::default(),
             connector: (),
+            srv_service: None,
         }
     }
 }
@@ -44,8 +47,29 @@ impl<Dns, Connector> TcpConnector<Dns, Connector> {
         TcpConnector {
             dns,
             connector: self.connector,
+            srv_service: self.srv_service,
         }
     }
+
+    /// Connect to the targets of the 'SRV' records of the given service (e.g. `xmpp-client`),
+    /// offered over tcp by the domain of the target authority.
+    ///
+    /// The target authority itself is used in case the service has no 'SRV' records.
+    /// Connections to a proxy do not use 'SRV' records.
+    pub fn with_srv_service(mut self, service: impl Into<Cow<'static, str>>) -> Self {
+        self.srv_service = Some(service.into());
+        self
+    }
+
+    /// Connect to the targets of the 'SRV' records of the given service (e.g. `xmpp-client`),
+    /// offered over tcp by the domain of the target authority.
+    ///
+    /// The target authority itself is used in case the service has no 'SRV' records.
+    /// Connections to a proxy do not use 'SRV' records.
+    pub fn set_srv_service(&mut self, service: impl Into<Cow<'static, str>>) -> &mut Self {
+        self.srv_service = Some(service.into());
+        self
+    }
 }
 
 impl<Dns> TcpConnector<Dns, ()> {
@@ -57,6 +81,7 @@ impl<Dns> TcpConnector<Dns, ()> {
         TcpConnector {
             dns: self.dns,
             connector,
+            srv_service: self.srv_service,
         }
     }
 }
@@ -120,14 +145,29 @@ where
         }
 
         let authority = transport_ctx.authority.clone();
-        let (conn, addr) = crate::client::tcp_connect(
-            &ctx,
-            authority,
-            false,
-            self.dns.clone(),
-            self.connector.clone(),
-        )
-        .await
+        let (conn, addr) = match self.srv_service.as_deref() {
+            Some(service) => {
+                crate::client::tcp_connect_srv(
+                    &ctx,
+                    service,
+                    authority,
+                    false,
+                    self.dns.clone(),
+                    self.connector.clone(),
+                )
+                .await
+            }
+            None => {
+                crate::client::tcp_connect(
+                    &ctx,
+                    authority,
+                    false,
+                    self.dns.clone(),
+                    self.connector.clone(),
+                )
+                .await
+            }
+        }
         .context("tcp connector: connect to server")?;
 
         Ok(EstablishedClientConnection {
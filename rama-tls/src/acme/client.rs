@@ -0,0 +1,1223 @@
+use super::{
+    jws::AccountKey,
+    proto::{Authorization, Directory, Identifier, Order, Problem, Status},
+    AcmeCertResolver, AcmeChallengeType, Http01ChallengeService,
+};
+use crate::mitm::san_covers;
+use crate::rustls::{
+    dep::{
+        pki_types::{CertificateDer, PrivatePkcs8KeyDer},
+        rustls::sign::CertifiedKey,
+    },
+    server::certified_key,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as ENGINE, Engine};
+use rama_core::{
+    error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
+    graceful::ShutdownGuard,
+    Context, Service,
+};
+use rama_http_types::{
+    dep::http_body_util::BodyExt, header, Body, HeaderMap, Method, Request, Response, StatusCode,
+    Uri,
+};
+use rama_net::address::Domain;
+use rcgen::{CertificateParams, CustomExtension, KeyPair, SanType, PKCS_ECDSA_P256_SHA256};
+use ring::digest;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::time::MissedTickBehavior;
+
+const JOSE_MIME: &str = "application/jose+json";
+const REPLAY_NONCE: &str = "replay-nonce";
+const ACCOUNT_KEY_FILE: &str = "account.key.pem";
+const MAX_RESPONSE_SIZE: usize = 1024 * 1024;
+const MAX_NONCE_ATTEMPTS: usize = 3;
+const MAX_POLL_ATTEMPTS: usize = 30;
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_RENEW_BEFORE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// An ACME (RFC 8555) client which obtains and renews the certificate
+/// for a set of domains, using the given http client to talk to the ACME server.
+///
+/// As the requests are sent using a regular http client [`Service`],
+/// such as the `HttpClient` of `rama-http-backend`, they go through
+/// the same proxy and tls stack as the other http requests made using that client,
+/// e.g. `AcmeClient::new(HttpClient::default(), directory_url, domains, cache_dir)`.
+///
+/// The obtained certificate is served by the [`AcmeCertResolver`] of this client,
+/// while the challenges are answered by the [`Http01ChallengeService`]
+/// or [`AcmeCertResolver`] depending on the [`AcmeChallengeType`].
+///
+/// The account key and certificate are stored in the cache directory:
+///
+/// - `account.key.pem`: the private key of the ACME account;
+/// - `<domain>.crt.pem`: the certificate chain, named after the first domain;
+/// - `<domain>.key.pem`: the private key of the certificate.
+pub struct AcmeClient<S> {
+    client: S,
+    directory_url: Uri,
+    domains: Vec<Domain>,
+    cache_dir: PathBuf,
+    contact: Vec<String>,
+    challenge_type: AcmeChallengeType,
+    renew_before: Duration,
+    resolver: AcmeCertResolver,
+    http01: Http01ChallengeService,
+}
+
+impl<S: fmt::Debug> fmt::Debug for AcmeClient<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AcmeClient")
+            .field("client", &self.client)
+            .field("directory_url", &self.directory_url)
+            .field("domains", &self.domains)
+            .field("cache_dir", &self.cache_dir)
+            .field("contact", &self.contact)
+            .field("challenge_type", &self.challenge_type)
+            .field("renew_before", &self.renew_before)
+            .field("resolver", &self.resolver)
+            .field("http01", &self.http01)
+            .finish()
+    }
+}
+
+impl<S: Clone> Clone for AcmeClient<S> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            directory_url: self.directory_url.clone(),
+            domains: self.domains.clone(),
+            cache_dir: self.cache_dir.clone(),
+            contact: self.contact.clone(),
+            challenge_type: self.challenge_type,
+            renew_before: self.renew_before,
+            resolver: self.resolver.clone(),
+            http01: self.http01.clone(),
+        }
+    }
+}
+
+impl<S> AcmeClient<S> {
+    /// Create a new [`AcmeClient`], ordering a certificate for the given domains
+    /// from the ACME server at the given directory [`Uri`],
+    /// e.g. [`LETS_ENCRYPT_DIRECTORY`], using the given http client.
+    ///
+    /// [`LETS_ENCRYPT_DIRECTORY`]: super::LETS_ENCRYPT_DIRECTORY
+    pub fn new(
+        client: S,
+        directory_url: Uri,
+        domains: Vec<Domain>,
+        cache_dir: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            client,
+            directory_url,
+            domains,
+            cache_dir: cache_dir.into(),
+            contact: Vec::new(),
+            challenge_type: AcmeChallengeType::default(),
+            renew_before: DEFAULT_RENEW_BEFORE,
+            resolver: AcmeCertResolver::default(),
+            http01: Http01ChallengeService::default(),
+        }
+    }
+
+    /// Set the contact urls of the ACME account, e.g. `mailto:admin@example.com`.
+    pub fn with_contact(mut self, contact: Vec<String>) -> Self {
+        self.contact = contact;
+        self
+    }
+
+    /// Set the contact urls of the ACME account, e.g. `mailto:admin@example.com`.
+    pub fn set_contact(&mut self, contact: Vec<String>) -> &mut Self {
+        self.contact = contact;
+        self
+    }
+
+    /// Set the [`AcmeChallengeType`] used to prove the control over the domains.
+    ///
+    /// Defaults to [`AcmeChallengeType::Http01`].
+    pub fn with_challenge_type(mut self, challenge_type: AcmeChallengeType) -> Self {
+        self.challenge_type = challenge_type;
+        self
+    }
+
+    /// Set the [`AcmeChallengeType`] used to prove the control over the domains.
+    ///
+    /// Defaults to [`AcmeChallengeType::Http01`].
+    pub fn set_challenge_type(&mut self, challenge_type: AcmeChallengeType) -> &mut Self {
+        self.challenge_type = challenge_type;
+        self
+    }
+
+    /// Set how long before its expiry the certificate is renewed.
+    ///
+    /// Defaults to 30 days.
+    pub fn with_renew_before(mut self, renew_before: Duration) -> Self {
+        self.renew_before = renew_before;
+        self
+    }
+
+    /// Set how long before its expiry the certificate is renewed.
+    ///
+    /// Defaults to 30 days.
+    pub fn set_renew_before(&mut self, renew_before: Duration) -> &mut Self {
+        self.renew_before = renew_before;
+        self
+    }
+
+    /// The [`AcmeCertResolver`] serving the obtained certificate
+    /// and answering the tls-alpn-01 challenges.
+    pub fn resolver(&self) -> &AcmeCertResolver {
+        &self.resolver
+    }
+
+    /// The [`Http01ChallengeService`] answering the http-01 challenges.
+    pub fn http01_service(&self) -> &Http01ChallengeService {
+        &self.http01
+    }
+
+    fn cert_path(&self) -> Result<(PathBuf, PathBuf), OpaqueError> {
+        let domain = self
+            .domains
+            .first()
+            .context("ACME client requires at least one domain")?;
+        Ok((
+            self.cache_dir.join(format!("{domain}.crt.pem")),
+            self.cache_dir.join(format!("{domain}.key.pem")),
+        ))
+    }
+
+    /// Load the certificate stored in the cache directory, if any.
+    ///
+    /// A stored certificate which cannot be used, e.g. because its private key
+    /// does not match or because it does not cover all configured domains,
+    /// is ignored such that a new certificate is ordered.
+    async fn load_certificate(&self) -> Result<Option<StoredCertificate>, OpaqueError> {
+        let (cert_path, key_path) = self.cert_path()?;
+        let cert_pem = match read_optional(&cert_path).await? {
+            Some(cert_pem) => cert_pem,
+            None => return Ok(None),
+        };
+        let key_pem = match read_optional(&key_path).await? {
+            Some(key_pem) => key_pem,
+            None => return Ok(None),
+        };
+        let certificate = match StoredCertificate::from_pem(&cert_pem, &key_pem) {
+            Ok(certificate) => certificate,
+            Err(err) => {
+                tracing::warn!(
+                    path = %cert_path.display(),
+                    error = %err,
+                    "ACME: ignore invalid stored certificate",
+                );
+                return Ok(None);
+            }
+        };
+        if !certificate.covers(&self.domains) {
+            tracing::info!(
+                domains = ?self.domains,
+                path = %cert_path.display(),
+                "ACME: stored certificate does not cover all domains",
+            );
+            return Ok(None);
+        }
+        Ok(Some(certificate))
+    }
+}
+
+impl<S> AcmeClient<S>
+where
+    S: Service<(), Request, Response = Response, Error: Into<BoxError>>,
+{
+    /// Ensure that a certificate is served by the [`AcmeCertResolver`]
+    /// which does not expire within the renewal window.
+    ///
+    /// The certificate stored in the cache directory is used if it is still valid,
+    /// otherwise a new certificate is ordered from the ACME server and stored.
+    pub async fn ensure_certificate(&self) -> Result<(), OpaqueError> {
+        let renew_at = SystemTime::now() + self.renew_before;
+        let certificate = match self.load_certificate().await? {
+            Some(certificate) if certificate.expires_at > renew_at => certificate,
+            _ => self.order_certificate().await?,
+        };
+        self.resolver.set_certificate(certificate.certified_key);
+        Ok(())
+    }
+
+    /// Order a new certificate from the ACME server, storing it in the cache directory.
+    ///
+    /// The ordered certificate is not served yet by the [`AcmeCertResolver`],
+    /// use [`AcmeClient::ensure_certificate`] for that instead.
+    async fn order_certificate(&self) -> Result<StoredCertificate, OpaqueError> {
+        tracing::info!(domains = ?self.domains, "ACME: order certificate");
+        let mut session = self.session().await?;
+
+        let identifiers: Vec<_> = self
+            .domains
+            .iter()
+            .map(|domain| Identifier::dns(domain.as_str()))
+            .collect();
+        let new_order = session.directory.new_order.clone();
+        let resp = self
+            .post(
+                &mut session,
+                &new_order,
+                Some(&json!({ "identifiers": identifiers })),
+            )
+            .await
+            .context("create ACME order")?;
+        let order_url = resp.location()?;
+        let order: Order = resp.json()?;
+
+        for authorization_url in &order.authorizations {
+            self.authorize(&mut session, authorization_url).await?;
+        }
+
+        let key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).context("generate key pair")?;
+        let domains: Vec<_> = self.domains.iter().map(|d| d.as_str().to_owned()).collect();
+        let csr = CertificateParams::new(domains)
+            .context("create certificate signing request params")?
+            .serialize_request(&key)
+            .context("create certificate signing request")?;
+
+        let order = self
+            .poll(&mut session, &order_url, |order: &Order| {
+                order.status != Status::Pending
+            })
+            .await?;
+        if order.status != Status::Ready {
+            return Err(order_error(
+                &order,
+                "ACME order is not ready to be finalized",
+            ));
+        }
+        self.post(
+            &mut session,
+            &order.finalize,
+            Some(&json!({ "csr": ENGINE.encode(csr.der()) })),
+        )
+        .await
+        .context("finalize ACME order")?;
+
+        let order = self
+            .poll(&mut session, &order_url, |order: &Order| {
+                !matches!(order.status, Status::Ready | Status::Processing)
+            })
+            .await?;
+        let certificate_url = match (order.status, &order.certificate) {
+            (Status::Valid, Some(certificate_url)) => certificate_url,
+            _ => return Err(order_error(&order, "ACME order failed")),
+        };
+        let resp = self
+            .post(&mut session, certificate_url, None)
+            .await
+            .context("download ACME certificate")?;
+
+        let key_pem = key.serialize_pem();
+        let certificate = StoredCertificate::from_pem(&resp.body, key_pem.as_bytes())?;
+        let (cert_path, key_path) = self.cert_path()?;
+        write_file(&cert_path, &resp.body, false).await?;
+        write_file(&key_path, key_pem.as_bytes(), true).await?;
+        tracing::info!(
+            domains = ?self.domains,
+            path = %cert_path.display(),
+            "ACME: certificate obtained",
+        );
+        Ok(certificate)
+    }
+
+    /// Spawn a task which ensures, at the given interval, that a valid certificate
+    /// is served by the [`AcmeCertResolver`], until shutdown is initiated.
+    ///
+    /// The first check happens immediately. A failure to renew the certificate
+    /// keeps the current certificate in place, retrying it at the next interval.
+    pub fn spawn_renewal(self, guard: ShutdownGuard, interval: Duration)
+    where
+        S: Send + Sync + 'static,
+    {
+        guard.into_spawn_task_fn(move |guard| async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = guard.cancelled() => return,
+                    _ = ticker.tick() => (),
+                }
+                if let Err(err) = self.ensure_certificate().await {
+                    tracing::error!(domains = ?self.domains, error = %err, "ACME: failed to renew certificate");
+                }
+            }
+        });
+    }
+
+    async fn session(&self) -> Result<Session, OpaqueError> {
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(self.directory_url.clone())
+            .body(Body::empty())
+            .context("create ACME directory request")?;
+        let resp = self.send(req).await.context("fetch ACME directory")?;
+        if !resp.status.is_success() {
+            return Err(OpaqueError::from_display(format!(
+                "ACME directory replied with status {}",
+                resp.status
+            )));
+        }
+        let directory: Directory = resp.json()?;
+
+        let mut session = Session {
+            directory,
+            key: self.account_key().await?,
+            kid: None,
+            nonce: None,
+        };
+        let new_account = session.directory.new_account.clone();
+        let resp = self
+            .post(
+                &mut session,
+                &new_account,
+                Some(&json!({
+                    "termsOfServiceAgreed": true,
+                    "contact": self.contact,
+                })),
+            )
+            .await
+            .context("register ACME account")?;
+        session.kid = Some(resp.location()?);
+        Ok(session)
+    }
+
+    /// Load the account key from the cache directory, generating and storing it if missing.
+    async fn account_key(&self) -> Result<AccountKey, OpaqueError> {
+        let path = self.cache_dir.join(ACCOUNT_KEY_FILE);
+        if let Some(key_pem) = read_optional(&path).await? {
+            let key = pem::parse(key_pem).context("parse ACME account key pem")?;
+            return AccountKey::from_pkcs8_der(key.into_contents());
+        }
+        let key = AccountKey::generate()?;
+        let key_pem = pem::encode(&pem::Pem::new("PRIVATE KEY", key.pkcs8_der()));
+        write_file(&path, key_pem.as_bytes(), true).await?;
+        Ok(key)
+    }
+
+    /// Complete the given authorization, unless it is valid already.
+    async fn authorize(&self, session: &mut Session, url: &str) -> Result<(), OpaqueError> {
+        let resp = self
+            .post(session, url, None)
+            .await
+            .context("fetch ACME authorization")?;
+        let authorization: Authorization = resp.json()?;
+        if authorization.status == Status::Valid {
+            return Ok(());
+        }
+
+        let challenge_type = self.challenge_type.as_str();
+        let challenge = authorization
+            .challenges
+            .iter()
+            .find(|challenge| challenge.kind == challenge_type)
+            .with_context(|| {
+                format!(
+                    "ACME server does not offer the {challenge_type} challenge for {}",
+                    authorization.identifier.value
+                )
+            })?;
+        let domain = authorization.identifier.value.as_str();
+        let key_authorization = session.key.key_authorization(&challenge.token);
+        match self.challenge_type {
+            AcmeChallengeType::Http01 => self
+                .http01
+                .insert(challenge.token.clone(), key_authorization),
+            AcmeChallengeType::TlsAlpn01 => self.resolver.insert_challenge(
+                domain.to_owned(),
+                tls_alpn01_certificate(domain, &key_authorization)?,
+            ),
+        }
+
+        let result = self.complete_challenge(session, url, &challenge.url).await;
+        match self.challenge_type {
+            AcmeChallengeType::Http01 => self.http01.remove(&challenge.token),
+            AcmeChallengeType::TlsAlpn01 => self.resolver.remove_challenge(domain),
+        }
+        result.with_context(|| format!("authorize {domain} using {challenge_type}"))
+    }
+
+    async fn complete_challenge(
+        &self,
+        session: &mut Session,
+        authorization_url: &str,
+        challenge_url: &str,
+    ) -> Result<(), OpaqueError> {
+        self.post(session, challenge_url, Some(&json!({})))
+            .await
+            .context("respond to ACME challenge")?;
+        let authorization = self
+            .poll(
+                session,
+                authorization_url,
+                |authorization: &Authorization| authorization.status != Status::Pending,
+            )
+            .await?;
+        if authorization.status == Status::Valid {
+            return Ok(());
+        }
+        let problem = authorization
+            .challenges
+            .iter()
+            .find_map(|challenge| challenge.error.as_ref());
+        Err(match problem {
+            Some(problem) => OpaqueError::from_display(format!("ACME challenge failed: {problem}")),
+            None => OpaqueError::from_display(format!(
+                "ACME authorization is {:?}",
+                authorization.status
+            )),
+        })
+    }
+
+    /// Fetch the resource at the given url until it is done,
+    /// waiting in between attempts.
+    async fn poll<T, F>(&self, session: &mut Session, url: &str, done: F) -> Result<T, OpaqueError>
+    where
+        T: DeserializeOwned,
+        F: Fn(&T) -> bool,
+    {
+        for attempt in 0..MAX_POLL_ATTEMPTS {
+            if attempt > 0 {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            let resource: T = self
+                .post(session, url, None)
+                .await
+                .context("poll ACME resource")?
+                .json()?;
+            if done(&resource) {
+                return Ok(resource);
+            }
+        }
+        Err(OpaqueError::from_display(format!(
+            "ACME resource {url} did not complete in time"
+        )))
+    }
+
+    /// Send a signed POST request, or POST-as-GET request in case no payload is given,
+    /// retrying it with a fresh nonce in case the nonce is rejected by the server.
+    async fn post(
+        &self,
+        session: &mut Session,
+        url: &str,
+        payload: Option<&Value>,
+    ) -> Result<AcmeResponse, OpaqueError> {
+        for _ in 0..MAX_NONCE_ATTEMPTS {
+            let nonce = match session.nonce.take() {
+                Some(nonce) => nonce,
+                None => self.new_nonce(&session.directory).await?,
+            };
+            let body = session
+                .key
+                .sign(url, &nonce, session.kid.as_deref(), payload)?;
+            let req = Request::builder()
+                .method(Method::POST)
+                .uri(url)
+                .header(header::CONTENT_TYPE, JOSE_MIME)
+                .body(Body::from(body))
+                .context("create ACME request")?;
+
+            let resp = self.send(req).await?;
+            session.nonce = resp.nonce();
+            if resp.status.is_success() {
+                return Ok(resp);
+            }
+            match serde_json::from_slice::<Problem>(&resp.body) {
+                Ok(problem) if problem.is_bad_nonce() => {
+                    tracing::debug!(url, "ACME: bad nonce, retry request");
+                }
+                Ok(problem) => {
+                    return Err(OpaqueError::from_display(format!(
+                        "ACME server replied with status {}: {problem}",
+                        resp.status
+                    )))
+                }
+                Err(_) => {
+                    return Err(OpaqueError::from_display(format!(
+                        "ACME server replied with status {}",
+                        resp.status
+                    )))
+                }
+            }
+        }
+        Err(OpaqueError::from_display(
+            "ACME server rejected too many nonces",
+        ))
+    }
+
+    async fn new_nonce(&self, directory: &Directory) -> Result<String, OpaqueError> {
+        let req = Request::builder()
+            .method(Method::HEAD)
+            .uri(directory.new_nonce.as_str())
+            .body(Body::empty())
+            .context("create ACME nonce request")?;
+        self.send(req)
+            .await
+            .context("fetch ACME nonce")?
+            .nonce()
+            .context("ACME server did not provide a nonce")
+    }
+
+    async fn send(&self, req: Request) -> Result<AcmeResponse, OpaqueError> {
+        let resp = self
+            .client
+            .serve(Context::default(), req)
+            .await
+            .map_err(|err| OpaqueError::from_boxed(err.into()).context("send ACME request"))?;
+        let (parts, body) = resp.into_parts();
+        let body = body
+            .limited(MAX_RESPONSE_SIZE)
+            .collect()
+            .await
+            .context("read ACME response body")?;
+        Ok(AcmeResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body: body.to_bytes().to_vec(),
+        })
+    }
+}
+
+/// The state of an ACME account for the duration of a single order.
+#[derive(Debug)]
+struct Session {
+    directory: Directory,
+    key: AccountKey,
+    kid: Option<String>,
+    nonce: Option<String>,
+}
+
+#[derive(Debug)]
+struct AcmeResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl AcmeResponse {
+    fn nonce(&self) -> Option<String> {
+        self.headers
+            .get(REPLAY_NONCE)
+            .and_then(|value| value.to_str().ok())
+            .map(ToOwned::to_owned)
+    }
+
+    fn location(&self) -> Result<String, OpaqueError> {
+        self.headers
+            .get(header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .map(ToOwned::to_owned)
+            .context("ACME server did not provide a location")
+    }
+
+    fn json<T: DeserializeOwned>(&self) -> Result<T, OpaqueError> {
+        serde_json::from_slice(&self.body).context("decode ACME response")
+    }
+}
+
+struct StoredCertificate {
+    certified_key: Arc<CertifiedKey>,
+    subject_alt_names: Vec<SanType>,
+    expires_at: SystemTime,
+}
+
+impl StoredCertificate {
+    fn from_pem(cert_pem: &[u8], key_pem: &[u8]) -> Result<Self, OpaqueError> {
+        let cert_chain: Vec<_> = pem::parse_many(cert_pem)
+            .context("parse certificate chain pem")?
+            .into_iter()
+            .map(|cert| CertificateDer::from(cert.into_contents()))
+            .collect();
+        let leaf = cert_chain.first().context("empty certificate chain")?;
+        let params = CertificateParams::from_ca_cert_der(leaf).context("parse certificate")?;
+        let key = pem::parse(key_pem).context("parse certificate key pem")?;
+        let certified_key = certified_key(
+            cert_chain,
+            PrivatePkcs8KeyDer::from(key.into_contents()).into(),
+        )?;
+        certified_key
+            .keys_match()
+            .context("private key does not match certificate")?;
+        Ok(Self {
+            certified_key,
+            subject_alt_names: params.subject_alt_names,
+            expires_at: params.not_after.into(),
+        })
+    }
+
+    /// Whether the subject alternative names of the certificate cover all given domains.
+    fn covers(&self, domains: &[Domain]) -> bool {
+        domains.iter().all(|domain| {
+            domain
+                .as_str()
+                .trim_end_matches('.')
+                .try_into()
+                .is_ok_and(|name| san_covers(&self.subject_alt_names, &SanType::DnsName(name)))
+        })
+    }
+}
+
+/// Create the self-signed certificate answering a tls-alpn-01 challenge (RFC 8737 §3).
+fn tls_alpn01_certificate(
+    domain: &str,
+    key_authorization: &str,
+) -> Result<Arc<CertifiedKey>, OpaqueError> {
+    let key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).context("generate key pair")?;
+    let mut params = CertificateParams::new(vec![domain.to_owned()])
+        .context("create tls-alpn-01 certificate params")?;
+    let digest = digest::digest(&digest::SHA256, key_authorization.as_bytes());
+    params.custom_extensions = vec![CustomExtension::new_acme_identifier(digest.as_ref())];
+    let cert = params
+        .self_signed(&key)
+        .context("create tls-alpn-01 certificate")?;
+    certified_key(
+        vec![cert.der().clone()],
+        PrivatePkcs8KeyDer::from(key.serialize_der()).into(),
+    )
+}
+
+fn order_error(order: &Order, msg: &'static str) -> OpaqueError {
+    match &order.error {
+        Some(problem) => OpaqueError::from_display(format!("{msg}: {problem}")),
+        None => OpaqueError::from_display(format!("{msg}: status {:?}", order.status)),
+    }
+}
+
+async fn read_optional(path: &Path) -> Result<Option<Vec<u8>>, OpaqueError> {
+    match tokio::fs::read(path).await {
+        Ok(content) => Ok(Some(content)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.context(format!("read {}", path.display()))),
+    }
+}
+
+/// Write the file atomically, creating the cache directory if needed,
+/// by writing to a temporary file in the same directory which is renamed once written,
+/// such that a crash never leaves a partial file behind, only allowing the owner
+/// to read private files on unix. The temporary file is removed in case writing fails.
+async fn write_file(path: &Path, content: &[u8], private: bool) -> Result<(), OpaqueError> {
+    let dir = path
+        .parent()
+        .context("file path without parent directory")?;
+    tokio::fs::create_dir_all(dir)
+        .await
+        .with_context(|| format!("create directory {}", dir.display()))?;
+    let file_name = path
+        .file_name()
+        .context("file path without file name")?
+        .to_string_lossy();
+    let tmp_path = dir.join(format!(".{file_name}.tmp"));
+
+    let write = async {
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        if private {
+            options.mode(0o600);
+        }
+        #[cfg(not(unix))]
+        let _ = private;
+        let mut file = options
+            .open(&tmp_path)
+            .await
+            .with_context(|| format!("open {}", tmp_path.display()))?;
+        tokio::io::AsyncWriteExt::write_all(&mut file, content)
+            .await
+            .with_context(|| format!("write {}", tmp_path.display()))?;
+        file.sync_all()
+            .await
+            .with_context(|| format!("sync {}", tmp_path.display()))?;
+        drop(file);
+        tokio::fs::rename(&tmp_path, path)
+            .await
+            .with_context(|| format!("rename {} to {}", tmp_path.display(), path.display()))
+    };
+    let result = write.await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rustls::{
+        dep::{
+            pki_types::ServerName,
+            rustls::ClientConfig,
+            tokio_rustls::{TlsAcceptor, TlsConnector},
+        },
+        server::crypto_provider,
+        verify::NoServerCertVerifier,
+    };
+    use parking_lot::Mutex;
+    use rama_core::service::service_fn;
+    use rcgen::{BasicConstraints, Certificate, CertificateSigningRequestParams, IsCa};
+    use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_FIXED};
+    use std::{collections::HashSet, convert::Infallible};
+
+    const BASE_URL: &str = "http://acme.test";
+
+    enum Validator {
+        Http01(Http01ChallengeService),
+        TlsAlpn01(AcmeCertResolver),
+    }
+
+    #[derive(Default)]
+    struct State {
+        nonce: u64,
+        rejected_nonce: bool,
+        jwk: Option<Value>,
+        orders: usize,
+        authorized: HashSet<String>,
+        certificate: Option<String>,
+        validator: Option<Arc<Validator>>,
+    }
+
+    enum Reply {
+        Done(Response),
+        Validate {
+            nonce: String,
+            domain: String,
+            key_authorization: String,
+            validator: Arc<Validator>,
+        },
+    }
+
+    /// A minimal stand-in for an ACME server such as Pebble,
+    /// validating the challenges by calling the services of the client directly.
+    struct StandIn {
+        ca_cert: Certificate,
+        ca_key: KeyPair,
+        state: Mutex<State>,
+    }
+
+    impl StandIn {
+        fn new() -> Arc<Self> {
+            let ca_key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).unwrap();
+            let mut params = CertificateParams::new(Vec::new()).unwrap();
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            let ca_cert = params.self_signed(&ca_key).unwrap();
+            Arc::new(Self {
+                ca_cert,
+                ca_key,
+                state: Mutex::new(State::default()),
+            })
+        }
+
+        async fn serve(&self, req: Request) -> Response {
+            let path = req.uri().path().to_owned();
+            let url = req.uri().to_string();
+            let body = req.into_body().collect().await.unwrap().to_bytes();
+
+            let reply = self.reply(&path, &url, &body);
+            match reply {
+                Reply::Done(resp) => resp,
+                Reply::Validate {
+                    nonce,
+                    domain,
+                    key_authorization,
+                    validator,
+                } => {
+                    validate(&validator, &domain, &key_authorization).await;
+                    self.state.lock().authorized.insert(domain);
+                    json_response(
+                        StatusCode::OK,
+                        json!({ "status": "valid" }),
+                        Some(nonce),
+                        None,
+                    )
+                }
+            }
+        }
+
+        fn reply(&self, path: &str, url: &str, body: &[u8]) -> Reply {
+            if path == "/directory" {
+                return Reply::Done(json_response(
+                    StatusCode::OK,
+                    json!({
+                        "newNonce": format!("{BASE_URL}/new-nonce"),
+                        "newAccount": format!("{BASE_URL}/new-account"),
+                        "newOrder": format!("{BASE_URL}/new-order"),
+                    }),
+                    None,
+                    None,
+                ));
+            }
+
+            let mut state = self.state.lock();
+            state.nonce += 1;
+            let nonce = format!("nonce-{}", state.nonce);
+            if path == "/new-nonce" {
+                return Reply::Done(
+                    Response::builder()
+                        .header(REPLAY_NONCE, nonce)
+                        .body(Body::empty())
+                        .unwrap(),
+                );
+            }
+
+            let payload = verify_jws(&mut state, url, body);
+            if !state.rejected_nonce {
+                state.rejected_nonce = true;
+                return Reply::Done(json_response(
+                    StatusCode::BAD_REQUEST,
+                    json!({ "type": "urn:ietf:params:acme:error:badNonce" }),
+                    Some(nonce),
+                    None,
+                ));
+            }
+
+            let (status, body, location) = match path {
+                "/new-account" => (
+                    StatusCode::CREATED,
+                    json!({ "status": "valid" }),
+                    Some(format!("{BASE_URL}/account/1")),
+                ),
+                "/new-order" => {
+                    state.orders += 1;
+                    state.certificate = None;
+                    let identifiers = payload.unwrap()["identifiers"].clone();
+                    assert_eq!(
+                        identifiers,
+                        json!([
+                            { "type": "dns", "value": "example.com" },
+                            { "type": "dns", "value": "www.example.com" },
+                        ])
+                    );
+                    (
+                        StatusCode::CREATED,
+                        order(&state),
+                        Some(format!("{BASE_URL}/order/1")),
+                    )
+                }
+                "/order/1" => (StatusCode::OK, order(&state), None),
+                "/finalize" => {
+                    let csr = payload.unwrap()["csr"].as_str().unwrap().to_owned();
+                    let mut csr = CertificateSigningRequestParams::from_der(
+                        &ENGINE.decode(csr).unwrap().into(),
+                    )
+                    .unwrap();
+                    csr.params.not_after =
+                        (SystemTime::now() + Duration::from_secs(90 * 24 * 60 * 60)).into();
+                    let cert = csr.signed_by(&self.ca_cert, &self.ca_key).unwrap();
+                    state.certificate = Some(cert.pem() + &self.ca_cert.pem());
+                    (StatusCode::OK, order(&state), None)
+                }
+                "/certificate" => {
+                    return Reply::Done(
+                        Response::builder()
+                            .header(REPLAY_NONCE, nonce)
+                            .body(Body::from(state.certificate.clone().unwrap()))
+                            .unwrap(),
+                    );
+                }
+                path => {
+                    if let Some(domain) = path.strip_prefix("/authz/") {
+                        (StatusCode::OK, authorization(&state, domain), None)
+                    } else if let Some(domain) = path.strip_prefix("/challenge/") {
+                        let jwk = state.jwk.as_ref().unwrap();
+                        return Reply::Validate {
+                            nonce,
+                            domain: domain.to_owned(),
+                            key_authorization: format!("token-{domain}.{}", thumbprint(jwk)),
+                            validator: state.validator.clone().unwrap(),
+                        };
+                    } else {
+                        panic!("unexpected request to {path}");
+                    }
+                }
+            };
+            Reply::Done(json_response(status, body, Some(nonce), location))
+        }
+    }
+
+    /// Verify the JWS request, returning its payload.
+    fn verify_jws(state: &mut State, url: &str, body: &[u8]) -> Option<Value> {
+        let jws: Value = serde_json::from_slice(body).unwrap();
+        let protected = jws["protected"].as_str().unwrap();
+        let payload = jws["payload"].as_str().unwrap();
+        let header: Value = serde_json::from_slice(&ENGINE.decode(protected).unwrap()).unwrap();
+        assert_eq!(header["alg"], "ES256");
+        assert_eq!(header["url"], url);
+
+        let jwk = match header.get("jwk") {
+            Some(jwk) => {
+                // the account is registered once and then reused
+                let registered = state.jwk.get_or_insert_with(|| jwk.clone());
+                assert_eq!(registered, jwk);
+                jwk.clone()
+            }
+            None => {
+                assert_eq!(header["kid"], format!("{BASE_URL}/account/1"));
+                state.jwk.clone().unwrap()
+            }
+        };
+        let mut public_key = vec![4];
+        public_key.extend(ENGINE.decode(jwk["x"].as_str().unwrap()).unwrap());
+        public_key.extend(ENGINE.decode(jwk["y"].as_str().unwrap()).unwrap());
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, public_key)
+            .verify(
+                format!("{protected}.{payload}").as_bytes(),
+                &ENGINE.decode(jws["signature"].as_str().unwrap()).unwrap(),
+            )
+            .unwrap();
+
+        (!payload.is_empty())
+            .then(|| serde_json::from_slice(&ENGINE.decode(payload).unwrap()).unwrap())
+    }
+
+    fn thumbprint(jwk: &Value) -> String {
+        let jwk = format!(
+            r#"{{"crv":"P-256","kty":"EC","x":{},"y":{}}}"#,
+            jwk["x"], jwk["y"]
+        );
+        ENGINE.encode(digest::digest(&digest::SHA256, jwk.as_bytes()))
+    }
+
+    fn order(state: &State) -> Value {
+        let authorized = ["example.com", "www.example.com"]
+            .iter()
+            .all(|domain| state.authorized.contains(*domain));
+        let status = match (&state.certificate, authorized) {
+            (Some(_), _) => "valid",
+            (None, true) => "ready",
+            (None, false) => "pending",
+        };
+        json!({
+            "status": status,
+            "authorizations": [
+                format!("{BASE_URL}/authz/example.com"),
+                format!("{BASE_URL}/authz/www.example.com"),
+            ],
+            "finalize": format!("{BASE_URL}/finalize"),
+            "certificate": state.certificate.as_ref().map(|_| format!("{BASE_URL}/certificate")),
+        })
+    }
+
+    fn authorization(state: &State, domain: &str) -> Value {
+        let status = if state.authorized.contains(domain) {
+            "valid"
+        } else {
+            "pending"
+        };
+        let challenges: Vec<_> = ["http-01", "tls-alpn-01"]
+            .iter()
+            .map(|kind| {
+                json!({
+                    "type": kind,
+                    "url": format!("{BASE_URL}/challenge/{domain}"),
+                    "token": format!("token-{domain}"),
+                    "status": status,
+                })
+            })
+            .collect();
+        json!({
+            "identifier": { "type": "dns", "value": domain },
+            "status": status,
+            "challenges": challenges,
+        })
+    }
+
+    async fn validate(validator: &Validator, domain: &str, key_authorization: &str) {
+        match validator {
+            Validator::Http01(service) => {
+                let req = Request::builder()
+                    .uri(format!(
+                        "http://{domain}/.well-known/acme-challenge/token-{domain}"
+                    ))
+                    .body(Body::empty())
+                    .unwrap();
+                let resp = service.serve(Context::default(), req).await.unwrap();
+                assert_eq!(resp.status(), StatusCode::OK);
+                let body = resp.into_body().collect().await.unwrap().to_bytes();
+                assert_eq!(body, key_authorization.as_bytes());
+            }
+            Validator::TlsAlpn01(resolver) => {
+                let acceptor = TlsAcceptor::from(resolver.server_config(&[]));
+                let mut client_config = ClientConfig::builder_with_provider(crypto_provider())
+                    .with_safe_default_protocol_versions()
+                    .unwrap()
+                    .dangerous()
+                    .with_custom_certificate_verifier(Arc::new(NoServerCertVerifier::default()))
+                    .with_no_client_auth();
+                client_config.alpn_protocols = vec![b"acme-tls/1".to_vec()];
+                let connector = TlsConnector::from(Arc::new(client_config));
+
+                let (client, server) = tokio::io::duplex(16 * 1024);
+                let server_name = ServerName::try_from(domain.to_owned()).unwrap();
+                let (server, client) = tokio::join!(
+                    acceptor.accept(server),
+                    connector.connect(server_name, client)
+                );
+                server.unwrap();
+                let client = client.unwrap();
+                let cert = &client.get_ref().1.peer_certificates().unwrap()[0];
+                let digest = digest::digest(&digest::SHA256, key_authorization.as_bytes());
+                assert!(cert
+                    .as_ref()
+                    .windows(digest.as_ref().len())
+                    .any(|window| window == digest.as_ref()));
+            }
+        }
+    }
+
+    fn json_response(
+        status: StatusCode,
+        body: Value,
+        nonce: Option<String>,
+        location: Option<String>,
+    ) -> Response {
+        let mut resp = Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(nonce) = nonce {
+            resp = resp.header(REPLAY_NONCE, nonce);
+        }
+        if let Some(location) = location {
+            resp = resp.header(header::LOCATION, location);
+        }
+        resp.body(Body::from(body.to_string())).unwrap()
+    }
+
+    async fn test_acme_client(challenge_type: AcmeChallengeType) {
+        let cache_dir = std::env::temp_dir().join(format!(
+            "rama-tls-acme-{}-{}",
+            std::process::id(),
+            challenge_type.as_str()
+        ));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+
+        let stand_in = StandIn::new();
+        let client = service_fn({
+            let stand_in = stand_in.clone();
+            move |req: Request| {
+                let stand_in = stand_in.clone();
+                async move { Ok::<_, Infallible>(stand_in.serve(req).await) }
+            }
+        });
+        let acme = AcmeClient::new(
+            client,
+            Uri::from_static("http://acme.test/directory"),
+            vec![
+                Domain::from_static("example.com"),
+                Domain::from_static("www.example.com"),
+            ],
+            &cache_dir,
+        )
+        .with_contact(vec!["mailto:admin@example.com".to_owned()])
+        .with_challenge_type(challenge_type);
+        stand_in.state.lock().validator = Some(Arc::new(match challenge_type {
+            AcmeChallengeType::Http01 => Validator::Http01(acme.http01_service().clone()),
+            AcmeChallengeType::TlsAlpn01 => Validator::TlsAlpn01(acme.resolver().clone()),
+        }));
+
+        assert!(acme.resolver().certificate().is_none());
+        acme.ensure_certificate().await.unwrap();
+        let certificate = acme.resolver().certificate().unwrap();
+        assert_eq!(certificate.cert.len(), 2);
+        assert_eq!(stand_in.state.lock().orders, 1);
+        assert!(acme.http01_service().get("token-example.com").is_none());
+        for file in [
+            "account.key.pem",
+            "example.com.crt.pem",
+            "example.com.key.pem",
+        ] {
+            assert!(cache_dir.join(file).exists(), "file: {file}");
+        }
+
+        // the stored certificate is reused as long as it is not about to expire
+        let acme = acme.clone();
+        acme.ensure_certificate().await.unwrap();
+        assert_eq!(stand_in.state.lock().orders, 1);
+        assert_eq!(
+            acme.resolver().certificate().unwrap().cert,
+            certificate.cert
+        );
+
+        // within the renewal window a new certificate is ordered and swapped in
+        let acme = acme.with_renew_before(Duration::from_secs(365 * 24 * 60 * 60));
+        acme.ensure_certificate().await.unwrap();
+        assert_eq!(stand_in.state.lock().orders, 2);
+        assert_ne!(
+            acme.resolver().certificate().unwrap().cert,
+            certificate.cert
+        );
+        assert!(!cache_dir.join(".example.com.crt.pem.tmp").exists());
+
+        // a stored key which does not match the certificate results in a new order
+        let acme = acme.with_renew_before(Duration::from_secs(24 * 60 * 60));
+        let other_key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).unwrap();
+        std::fs::write(
+            cache_dir.join("example.com.key.pem"),
+            other_key.serialize_pem(),
+        )
+        .unwrap();
+        acme.ensure_certificate().await.unwrap();
+        assert_eq!(stand_in.state.lock().orders, 3);
+        acme.ensure_certificate().await.unwrap();
+        assert_eq!(stand_in.state.lock().orders, 3);
+
+        // a stored certificate which does not cover all domains results in a new order
+        let partial_key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).unwrap();
+        let partial_cert = CertificateParams::new(vec!["example.com".to_owned()])
+            .unwrap()
+            .self_signed(&partial_key)
+            .unwrap();
+        std::fs::write(cache_dir.join("example.com.crt.pem"), partial_cert.pem()).unwrap();
+        std::fs::write(
+            cache_dir.join("example.com.key.pem"),
+            partial_key.serialize_pem(),
+        )
+        .unwrap();
+        acme.ensure_certificate().await.unwrap();
+        assert_eq!(stand_in.state.lock().orders, 4);
+        acme.ensure_certificate().await.unwrap();
+        assert_eq!(stand_in.state.lock().orders, 4);
+
+        std::fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_file_removes_tmp_file_on_failure() {
+        let dir =
+            std::env::temp_dir().join(format!("rama-tls-acme-write-file-{}", std::process::id()));
+        // renaming the written file onto a directory fails
+        let path = dir.join("cert.pem");
+        tokio::fs::create_dir_all(&path).await.unwrap();
+
+        write_file(&path, b"cert", false).await.unwrap_err();
+        assert!(!dir.join(".cert.pem.tmp").exists());
+
+        tokio::fs::remove_dir(&path).await.unwrap();
+        write_file(&path, b"cert", false).await.unwrap();
+        assert_eq!(tokio::fs::read(&path).await.unwrap(), b"cert");
+        assert!(!dir.join(".cert.pem.tmp").exists());
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_acme_client_http01() {
+        test_acme_client(AcmeChallengeType::Http01).await;
+    }
+
+    #[tokio::test]
+    async fn test_acme_client_tls_alpn01() {
+        test_acme_client(AcmeChallengeType::TlsAlpn01).await;
+    }
+}
@@ -0,0 +1,65 @@
+use parking_lot::RwLock;
+use rama_core::{Context, Service};
+use rama_http_types::{header, Body, Request, Response, StatusCode};
+use std::{collections::HashMap, convert::Infallible, sync::Arc};
+
+const CHALLENGE_PATH_PREFIX: &str = "/.well-known/acme-challenge/";
+
+#[derive(Debug, Clone, Default)]
+/// A web [`Service`] answering the http-01 challenges (RFC 8555 §8.3)
+/// provisioned by an [`AcmeClient`].
+///
+/// It is meant to be served on port 80 for all domains ordered by the [`AcmeClient`],
+/// for example as the `/.well-known/acme-challenge/:token` endpoint of a `WebService`.
+/// Requests for unknown tokens, or for any other path, are answered with a 404.
+///
+/// [`AcmeClient`]: super::AcmeClient
+pub struct Http01ChallengeService {
+    challenges: Arc<RwLock<HashMap<String, String>>>,
+}
+
+impl Http01ChallengeService {
+    pub(super) fn insert(&self, token: String, key_authorization: String) {
+        self.challenges.write().insert(token, key_authorization);
+    }
+
+    pub(super) fn remove(&self, token: &str) {
+        self.challenges.write().remove(token);
+    }
+
+    /// The key authorization of the challenge with the given token, if any.
+    pub fn get(&self, token: &str) -> Option<String> {
+        self.challenges.read().get(token).cloned()
+    }
+}
+
+impl<State> Service<State, Request> for Http01ChallengeService
+where
+    State: Send + Sync + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+
+    async fn serve(
+        &self,
+        _ctx: Context<State>,
+        req: Request,
+    ) -> Result<Self::Response, Self::Error> {
+        let key_authorization = req
+            .uri()
+            .path()
+            .strip_prefix(CHALLENGE_PATH_PREFIX)
+            .and_then(|token| self.get(token));
+
+        let resp = match key_authorization {
+            Some(key_authorization) => Response::builder()
+                .status(StatusCode::OK)
+                .header(header::CONTENT_TYPE, "application/octet-stream")
+                .body(Body::from(key_authorization)),
+            None => Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::empty()),
+        };
+        Ok(resp.expect("build acme challenge response"))
+    }
+}
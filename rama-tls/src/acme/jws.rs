@@ -0,0 +1,123 @@
+//! JSON Web Signatures (RFC 7515) as used to authenticate ACME requests.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as ENGINE, Engine};
+use rama_core::error::{ErrorContext, OpaqueError};
+use ring::{
+    digest,
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+};
+use serde_json::{json, Value};
+use std::fmt;
+
+/// The ECDSA P-256 key pair identifying an ACME account.
+pub(super) struct AccountKey {
+    key_pair: EcdsaKeyPair,
+    pkcs8_der: Vec<u8>,
+    rng: SystemRandom,
+}
+
+impl fmt::Debug for AccountKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccountKey")
+            .field("public_key", &self.key_pair.public_key())
+            .finish()
+    }
+}
+
+impl AccountKey {
+    pub(super) fn generate() -> Result<Self, OpaqueError> {
+        let rng = SystemRandom::new();
+        let pkcs8_der = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+            .map_err(|_| OpaqueError::from_display("generate ACME account key"))?;
+        Self::from_pkcs8_der(pkcs8_der.as_ref().to_vec())
+    }
+
+    pub(super) fn from_pkcs8_der(pkcs8_der: Vec<u8>) -> Result<Self, OpaqueError> {
+        let rng = SystemRandom::new();
+        let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &pkcs8_der, &rng)
+            .map_err(|err| OpaqueError::from_display(format!("load ACME account key: {err}")))?;
+        Ok(Self {
+            key_pair,
+            pkcs8_der,
+            rng,
+        })
+    }
+
+    pub(super) fn pkcs8_der(&self) -> &[u8] {
+        &self.pkcs8_der
+    }
+
+    /// The base64url encoded affine coordinates of the public key.
+    fn coordinates(&self) -> (String, String) {
+        // the public key is encoded as an uncompressed point: 0x04 || x || y
+        let public_key = self.key_pair.public_key().as_ref();
+        (
+            ENGINE.encode(&public_key[1..33]),
+            ENGINE.encode(&public_key[33..]),
+        )
+    }
+
+    /// The public key as JSON Web Key (RFC 7517).
+    pub(super) fn jwk(&self) -> Value {
+        let (x, y) = self.coordinates();
+        json!({
+            "crv": "P-256",
+            "kty": "EC",
+            "x": x,
+            "y": y,
+        })
+    }
+
+    /// The JSON Web Key thumbprint (RFC 7638) of the public key.
+    pub(super) fn thumbprint(&self) -> String {
+        // the required members, in lexicographic order and without whitespace
+        let (x, y) = self.coordinates();
+        let jwk = format!(r#"{{"crv":"P-256","kty":"EC","x":"{x}","y":"{y}"}}"#);
+        ENGINE.encode(digest::digest(&digest::SHA256, jwk.as_bytes()))
+    }
+
+    /// The key authorization of a challenge token (RFC 8555 §8.1).
+    pub(super) fn key_authorization(&self, token: &str) -> String {
+        format!("{token}.{}", self.thumbprint())
+    }
+
+    /// Sign the payload for the given url, identifying the account using its `kid`,
+    /// or the public key in case the account is not yet registered.
+    ///
+    /// A missing payload results in a POST-as-GET request (RFC 8555 §6.3).
+    pub(super) fn sign(
+        &self,
+        url: &str,
+        nonce: &str,
+        kid: Option<&str>,
+        payload: Option<&Value>,
+    ) -> Result<Vec<u8>, OpaqueError> {
+        let mut protected = json!({
+            "alg": "ES256",
+            "nonce": nonce,
+            "url": url,
+        });
+        match kid {
+            Some(kid) => protected["kid"] = kid.into(),
+            None => protected["jwk"] = self.jwk(),
+        }
+        let protected = ENGINE.encode(protected.to_string());
+        let payload = match payload {
+            Some(payload) => ENGINE.encode(payload.to_string()),
+            None => String::new(),
+        };
+
+        let signature = self
+            .key_pair
+            .sign(&self.rng, format!("{protected}.{payload}").as_bytes())
+            .map_err(|_| OpaqueError::from_display("sign ACME request"))?;
+
+        serde_json::to_vec(&json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": ENGINE.encode(signature.as_ref()),
+        }))
+        .context("encode ACME request")
+    }
+}
@@ -0,0 +1,63 @@
+//! ACME (RFC 8555) certificate provisioning,
+//! e.g. to obtain and renew certificates from [Let's Encrypt].
+//!
+//! The [`AcmeClient`] orders certificates for a set of domains,
+//! proving the control over those domains using one of the [`AcmeChallengeType`]s:
+//!
+//! - [`AcmeChallengeType::Http01`]: answered by the [`Http01ChallengeService`],
+//!   which is to be served on port 80 at `/.well-known/acme-challenge/:token`,
+//!   e.g. as an endpoint of a `WebService`;
+//! - [`AcmeChallengeType::TlsAlpn01`] (RFC 8737): answered by the [`AcmeCertResolver`],
+//!   which is to be used as the certificate resolver of the tls acceptor on port 443,
+//!   for example using the [`ServerConfig`] created by [`AcmeCertResolver::server_config`].
+//!
+//! The account key and obtained certificates are stored in a cache directory,
+//! such that they can be reused across restarts. The certificate served
+//! by the [`AcmeCertResolver`] is swapped once it is renewed, which happens
+//! a configurable duration before it expires, see [`AcmeClient::spawn_renewal`].
+//!
+//! [Let's Encrypt]: https://letsencrypt.org
+//! [`ServerConfig`]: crate::rustls::dep::rustls::ServerConfig
+
+mod client;
+mod http01;
+mod jws;
+mod proto;
+mod resolver;
+
+#[doc(inline)]
+pub use client::AcmeClient;
+#[doc(inline)]
+pub use http01::Http01ChallengeService;
+#[doc(inline)]
+pub use resolver::AcmeCertResolver;
+
+/// The production directory of [Let's Encrypt](https://letsencrypt.org).
+pub const LETS_ENCRYPT_DIRECTORY: &str = "https://acme-v02.api.letsencrypt.org/directory";
+
+/// The staging directory of [Let's Encrypt](https://letsencrypt.org),
+/// which has far higher rate limits but issues untrusted certificates.
+pub const LETS_ENCRYPT_STAGING_DIRECTORY: &str =
+    "https://acme-staging-v02.api.letsencrypt.org/directory";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+/// The challenge used by the [`AcmeClient`] to prove the control over a domain.
+pub enum AcmeChallengeType {
+    #[default]
+    /// Serve the key authorization over plain http on port 80,
+    /// using the [`Http01ChallengeService`].
+    Http01,
+    /// Serve a self-signed certificate containing the key authorization
+    /// over tls on port 443 (RFC 8737), using the [`AcmeCertResolver`].
+    TlsAlpn01,
+}
+
+impl AcmeChallengeType {
+    /// The challenge type as identified within the ACME protocol.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Http01 => "http-01",
+            Self::TlsAlpn01 => "tls-alpn-01",
+        }
+    }
+}
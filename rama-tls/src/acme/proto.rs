@@ -0,0 +1,92 @@
+//! The ACME resources (RFC 8555 §7.1) used by the [`AcmeClient`].
+//!
+//! [`AcmeClient`]: super::AcmeClient
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct Directory {
+    pub(super) new_nonce: String,
+    pub(super) new_account: String,
+    pub(super) new_order: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum Status {
+    Pending,
+    Ready,
+    Processing,
+    Valid,
+    Invalid,
+    Expired,
+    Deactivated,
+    Revoked,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct Identifier {
+    #[serde(rename = "type")]
+    pub(super) kind: String,
+    pub(super) value: String,
+}
+
+impl Identifier {
+    pub(super) fn dns(value: impl Into<String>) -> Self {
+        Self {
+            kind: "dns".to_owned(),
+            value: value.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(super) struct Order {
+    pub(super) status: Status,
+    pub(super) authorizations: Vec<String>,
+    pub(super) finalize: String,
+    pub(super) certificate: Option<String>,
+    pub(super) error: Option<Problem>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(super) struct Authorization {
+    pub(super) identifier: Identifier,
+    pub(super) status: Status,
+    pub(super) challenges: Vec<Challenge>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(super) struct Challenge {
+    #[serde(rename = "type")]
+    pub(super) kind: String,
+    pub(super) url: String,
+    #[serde(default)]
+    pub(super) token: String,
+    pub(super) error: Option<Problem>,
+}
+
+/// An error returned by the ACME server (RFC 7807).
+#[derive(Debug, Clone, Deserialize)]
+pub(super) struct Problem {
+    #[serde(rename = "type", default)]
+    pub(super) kind: String,
+    pub(super) detail: Option<String>,
+}
+
+impl Problem {
+    pub(super) fn is_bad_nonce(&self) -> bool {
+        self.kind == "urn:ietf:params:acme:error:badNonce"
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.detail {
+            Some(detail) => write!(f, "{}: {detail}", self.kind),
+            None => self.kind.fmt(f),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{
+    rustls::{
+        dep::rustls::{
+            server::{ClientHello, ResolvesServerCert},
+            sign::CertifiedKey,
+            ServerConfig,
+        },
+        server::crypto_provider,
+    },
+    types::ApplicationProtocol,
+};
+use parking_lot::RwLock;
+use std::{collections::HashMap, sync::Arc};
+
+#[derive(Debug, Clone, Default)]
+/// A rustls certificate resolver serving the certificate obtained by an [`AcmeClient`],
+/// which is swapped for the renewed certificate without interrupting the acceptor.
+///
+/// It also answers the tls-alpn-01 challenges (RFC 8737) provisioned by the [`AcmeClient`],
+/// which is why it is to be used for all tls connections accepted on port 443.
+///
+/// [`AcmeClient`]: super::AcmeClient
+pub struct AcmeCertResolver {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    certificate: RwLock<Option<Arc<CertifiedKey>>>,
+    challenges: RwLock<HashMap<String, Arc<CertifiedKey>>>,
+}
+
+impl AcmeCertResolver {
+    /// The certificate currently served, if any was obtained yet.
+    pub fn certificate(&self) -> Option<Arc<CertifiedKey>> {
+        self.inner.certificate.read().clone()
+    }
+
+    pub(super) fn set_certificate(&self, certificate: Arc<CertifiedKey>) {
+        *self.inner.certificate.write() = Some(certificate);
+    }
+
+    pub(super) fn insert_challenge(&self, domain: String, certificate: Arc<CertifiedKey>) {
+        self.inner.challenges.write().insert(domain, certificate);
+    }
+
+    pub(super) fn remove_challenge(&self, domain: &str) {
+        self.inner.challenges.write().remove(domain);
+    }
+
+    /// Create a [`ServerConfig`] resolving its certificates using this [`AcmeCertResolver`],
+    /// negotiating the given ALPN protocols, as well as the one of the tls-alpn-01 challenge.
+    pub fn server_config(&self, alpn_protocols: &[ApplicationProtocol]) -> Arc<ServerConfig> {
+        // as done by `ServerConfig::builder`, for the provider used explicitly instead
+        let mut config = ServerConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()
+            .expect("crypto provider supports the safe default protocol versions")
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(self.clone()));
+        config.alpn_protocols = alpn_protocols
+            .iter()
+            .chain(std::iter::once(&ApplicationProtocol::ACME_TLS))
+            .map(|protocol| protocol.as_bytes().to_vec())
+            .collect();
+        Arc::new(config)
+    }
+}
+
+impl ResolvesServerCert for AcmeCertResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let is_challenge = client_hello.alpn().is_some_and(|mut protocols| {
+            protocols.any(|protocol| protocol == ApplicationProtocol::ACME_TLS.as_bytes())
+        });
+        if is_challenge {
+            let domain = client_hello.server_name()?;
+            return self.inner.challenges.read().get(domain).cloned();
+        }
+        self.certificate()
+    }
+}
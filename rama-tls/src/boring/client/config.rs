@@ -0,0 +1,482 @@
+use crate::boring::dep::boring::{
+    pkey::{PKey, Private},
+    ssl::{
+        SslConnector, SslConnectorBuilder, SslCurve, SslMethod, SslSignatureAlgorithm,
+        SslVerifyMode, SslVersion,
+    },
+    x509::{store::X509StoreBuilder, X509},
+};
+use crate::types::{
+    client::{ClientHello, ClientHelloEmulation, ClientHelloExtension},
+    ApplicationProtocol, ProtocolVersion, SignatureScheme, SupportedGroup,
+};
+use parking_lot::Mutex;
+use rama_core::error::{ErrorContext, OpaqueError};
+use std::{collections::HashMap, fmt, sync::Arc};
+
+/// Maximum number of emulated connectors kept by a [`ConnectorCache`].
+const MAX_EMULATED_CONNECTORS: usize = 32;
+
+#[derive(Clone, Debug, Default)]
+/// Common configuration for a set of client sessions.
+///
+/// All fields are optional, boring's defaults are used for whatever is not defined.
+pub struct ClientConfig {
+    /// Set the ALPN protocols supported by the client,
+    /// no ALPN extension is sent in case none are defined.
+    pub alpn_protocols: Vec<ApplicationProtocol>,
+    /// Cipher list, in the OpenSSL cipher list format, e.g. `ECDHE+AESGCM:ECDHE+CHACHA20`.
+    pub cipher_list: Option<String>,
+    /// The (elliptic curve) groups supported by the client, in order of preference.
+    pub curves: Option<Vec<SupportedGroup>>,
+    /// Minimum TLS version supported by the client.
+    pub min_ssl_version: Option<ProtocolVersion>,
+    /// Maximum TLS version supported by the client.
+    pub max_ssl_version: Option<ProtocolVersion>,
+    /// The signature algorithms the client accepts, in order of preference.
+    pub verify_algorithm_prefs: Option<Vec<SignatureScheme>>,
+    /// Enable GREASE (RFC 8701) in the client hello.
+    pub grease_enabled: bool,
+    /// Randomly permute the order of the extensions in the client hello for every connection.
+    pub permute_extensions: bool,
+    /// How to verify the certificate of the server.
+    pub server_verify_mode: ServerVerifyMode,
+    /// Root CA certificates used to verify the server certificate,
+    /// the system default paths are used in case none are defined.
+    pub root_ca_certs: Vec<X509>,
+    /// Client certificate and private key, used for mutual TLS (mTLS).
+    pub client_auth: Option<ClientAuth>,
+    /// Write logging information to facilitate tls interception.
+    pub keylog_filename: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// Mode used to verify the certificate of the server.
+pub enum ServerVerifyMode {
+    #[default]
+    /// Verify the certificate chain of the server using the (default) root CA certificates,
+    /// as well as the hostname it was issued for.
+    Auto,
+    /// Do not verify the certificate of the server at all.
+    ///
+    /// Useful for proxies and (testing) tools, but otherwise not recommended.
+    Disable,
+}
+
+#[derive(Clone, Debug)]
+/// Client certificate (chain) and private key used for mutual TLS (mTLS).
+pub struct ClientAuth {
+    /// Certificate chain of the client, starting with the leaf certificate.
+    pub cert_chain: Vec<X509>,
+    /// Private key of the client.
+    pub private_key: PKey<Private>,
+}
+
+impl ClientAuth {
+    /// Create a new [`ClientAuth`].
+    pub const fn new(cert_chain: Vec<X509>, private_key: PKey<Private>) -> Self {
+        Self {
+            cert_chain,
+            private_key,
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Create a new [`ClientConfig`] with all defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overwrite the tls settings of this config with the ones
+    /// found in the given [`ClientHello`], in order to emulate it.
+    ///
+    /// The cipher suites (TLS 1.3 suites are not configurable in boring),
+    /// supported groups, supported versions, signature algorithms,
+    /// ALPN protocols and the use of GREASE are applied, limited to what boring supports.
+    /// The order of the extensions cannot be emulated,
+    /// use [`ClientConfig::apply_emulation`] to permute them instead.
+    pub fn apply_client_hello(&mut self, hello: &ClientHello) {
+        let cipher_list: Vec<_> = hello
+            .cipher_suites()
+            .iter()
+            .filter(|cs| u16::from(**cs) >> 8 != 0x13)
+            .filter_map(|cs| cs.name())
+            .collect();
+        self.cipher_list = (!cipher_list.is_empty()).then(|| cipher_list.join(":"));
+
+        self.curves = hello.ext_supported_groups().map(|groups| {
+            groups
+                .iter()
+                .filter(|group| ssl_curve_from_group(**group).is_some())
+                .copied()
+                .collect()
+        });
+
+        let versions = hello
+            .supported_versions()
+            .unwrap_or_default()
+            .iter()
+            .filter(|version| ssl_version(**version).is_ok());
+        self.min_ssl_version = versions.clone().min_by_key(|v| u16::from(**v)).copied();
+        self.max_ssl_version = versions.max_by_key(|v| u16::from(**v)).copied();
+
+        self.verify_algorithm_prefs = hello.ext_signature_algorithms().map(|schemes| {
+            schemes
+                .iter()
+                .filter(|scheme| !scheme.is_grease())
+                .copied()
+                .collect()
+        });
+
+        self.alpn_protocols = hello.ext_alpn().map(<[_]>::to_vec).unwrap_or_default();
+
+        self.grease_enabled = hello.cipher_suites().iter().any(|cs| cs.is_grease())
+            || hello.extensions().iter().any(
+                |ext| matches!(ext, ClientHelloExtension::Opaque { id, .. } if id.is_grease()),
+            );
+    }
+
+    /// Overwrite the tls settings of this config with the ones
+    /// of the given [`ClientHelloEmulation`], in order to emulate it.
+    ///
+    /// See [`ClientConfig::apply_client_hello`] for the settings applied,
+    /// in addition to which the extensions are permuted if the emulation asks for it.
+    /// A warning is logged otherwise, as boring cannot send the extensions
+    /// in the exact order of the [`ClientHello`].
+    pub fn apply_emulation(&mut self, emulation: &ClientHelloEmulation) {
+        self.apply_client_hello(emulation.client_hello());
+        self.permute_extensions = emulation.permute_extensions();
+        if !self.permute_extensions {
+            tracing::warn!(
+                "boring tls client cannot emulate the extension order of the client hello: using boring's order"
+            );
+        }
+    }
+
+    /// Build a [`SslConnector`] using this config.
+    pub(super) fn build_connector(&self) -> Result<SslConnector, OpaqueError> {
+        let mut builder = SslConnector::builder(SslMethod::tls_client())
+            .context("build boring ssl connector: create builder")?;
+        self.configure(&mut builder)?;
+        Ok(builder.build())
+    }
+
+    /// Apply this config to the given [`SslConnectorBuilder`].
+    fn configure(&self, builder: &mut SslConnectorBuilder) -> Result<(), OpaqueError> {
+        match self.server_verify_mode {
+            ServerVerifyMode::Auto => {
+                if !self.root_ca_certs.is_empty() {
+                    let mut store = X509StoreBuilder::new()
+                        .context("build boring ssl connector: create x509 store")?;
+                    for cert in &self.root_ca_certs {
+                        store
+                            .add_cert(cert.clone())
+                            .context("build boring ssl connector: add root CA certificate")?;
+                    }
+                    builder.set_cert_store(store.build());
+                }
+                builder.set_verify(SslVerifyMode::PEER);
+            }
+            ServerVerifyMode::Disable => {
+                builder.set_custom_verify_callback(SslVerifyMode::NONE, |_| Ok(()));
+                builder.set_verify(SslVerifyMode::NONE);
+            }
+        }
+
+        if let Some(cipher_list) = &self.cipher_list {
+            builder
+                .set_cipher_list(cipher_list)
+                .context("build boring ssl connector: set cipher list")?;
+        }
+
+        if let Some(curves) = &self.curves {
+            let curves = curves
+                .iter()
+                .map(|group| {
+                    ssl_curve_from_group(*group).ok_or_else(|| {
+                        OpaqueError::from_display(format!(
+                            "build boring ssl connector: unsupported curve: {group}"
+                        ))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            builder
+                .set_curves(&curves)
+                .context("build boring ssl connector: set curves")?;
+        }
+
+        builder
+            .set_min_proto_version(self.min_ssl_version.map(ssl_version).transpose()?)
+            .context("build boring ssl connector: set min ssl version")?;
+        builder
+            .set_max_proto_version(self.max_ssl_version.map(ssl_version).transpose()?)
+            .context("build boring ssl connector: set max ssl version")?;
+
+        if let Some(schemes) = &self.verify_algorithm_prefs {
+            let prefs: Vec<_> = schemes
+                .iter()
+                .map(|scheme| SslSignatureAlgorithm::from(u16::from(*scheme)))
+                .collect();
+            builder
+                .set_verify_algorithm_prefs(&prefs)
+                .context("build boring ssl connector: set verify algorithm prefs")?;
+        }
+
+        builder.set_grease_enabled(self.grease_enabled);
+        builder.set_permute_extensions(self.permute_extensions);
+
+        if !self.alpn_protocols.is_empty() {
+            let mut buf = vec![];
+            for alpn in &self.alpn_protocols {
+                alpn.encode_wire_format(&mut buf)
+                    .context("build boring ssl connector: encode alpn")?;
+            }
+            builder
+                .set_alpn_protos(&buf[..])
+                .context("build boring ssl connector: set alpn")?;
+        }
+
+        if let Some(auth) = &self.client_auth {
+            for (i, cert) in auth.cert_chain.iter().enumerate() {
+                if i == 0 {
+                    builder
+                        .set_certificate(cert.as_ref())
+                        .context("build boring ssl connector: set client certificate (x509)")?;
+                } else {
+                    builder.add_extra_chain_cert(cert.clone()).context(
+                        "build boring ssl connector: add extra chain certificate (x509)",
+                    )?;
+                }
+            }
+            builder
+                .set_private_key(auth.private_key.as_ref())
+                .context("build boring ssl connector: set client private key")?;
+            builder
+                .check_private_key()
+                .context("build boring ssl connector: check client private key")?;
+        }
+
+        if let Some(keylog_filename) = &self.keylog_filename {
+            // open file in append mode and write keylog to it with callback
+            let file = std::fs::OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(keylog_filename)
+                .context("build boring ssl connector: set keylog: open file")?;
+            builder.set_keylog_callback(move |_, line| {
+                use std::io::Write;
+                let line = format!("{}\n", line);
+                let mut file = &file;
+                let _ = file.write_all(line.as_bytes());
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+/// Cache of the [`SslConnector`]s built from a [`ClientConfig`],
+/// such that the certificate store is not rebuilt,
+/// nor the keylog file reopened, for every handshake.
+///
+/// Connectors emulating a [`ClientHello`] are cached per (shared) client hello
+/// and extension permutation, and the cache is cleared once it holds too many of them.
+pub(super) struct ConnectorCache {
+    connector: Mutex<Option<SslConnector>>,
+    emulated: Mutex<HashMap<(usize, bool), (Arc<ClientHello>, SslConnector)>>,
+}
+
+impl fmt::Debug for ConnectorCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectorCache")
+            .field("connector", &self.connector.lock().is_some())
+            .field("emulated", &self.emulated.lock().len())
+            .finish()
+    }
+}
+
+impl ConnectorCache {
+    /// Get the [`SslConnector`] for the given config,
+    /// optionally emulating the given [`ClientHelloEmulation`], building it if not yet cached.
+    pub(super) fn connector(
+        &self,
+        config: &ClientConfig,
+        emulation: Option<&ClientHelloEmulation>,
+    ) -> Result<SslConnector, OpaqueError> {
+        let Some(emulation) = emulation else {
+            let mut connector = self.connector.lock();
+            if let Some(connector) = connector.as_ref() {
+                return Ok(connector.clone());
+            }
+            let built = config.build_connector()?;
+            *connector = Some(built.clone());
+            return Ok(built);
+        };
+
+        // the client hello is kept alive as part of the entry,
+        // such that its address cannot be reused by another client hello
+        let hello = emulation.shared_client_hello();
+        let key = (Arc::as_ptr(hello) as usize, emulation.permute_extensions());
+        let mut emulated = self.emulated.lock();
+        if let Some((_, connector)) = emulated.get(&key) {
+            return Ok(connector.clone());
+        }
+
+        let mut config = config.clone();
+        config.apply_emulation(emulation);
+        let built = config.build_connector()?;
+        if emulated.len() >= MAX_EMULATED_CONNECTORS {
+            emulated.clear();
+        }
+        emulated.insert(key, (hello.clone(), built.clone()));
+        Ok(built)
+    }
+
+    /// Returns the number of connectors in this cache.
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.connector.lock().iter().len() + self.emulated.lock().len()
+    }
+}
+
+fn ssl_version(version: ProtocolVersion) -> Result<SslVersion, OpaqueError> {
+    match version {
+        ProtocolVersion::SSLv3 => Ok(SslVersion::SSL3),
+        ProtocolVersion::TLSv1_0 => Ok(SslVersion::TLS1),
+        ProtocolVersion::TLSv1_1 => Ok(SslVersion::TLS1_1),
+        ProtocolVersion::TLSv1_2 => Ok(SslVersion::TLS1_2),
+        ProtocolVersion::TLSv1_3 => Ok(SslVersion::TLS1_3),
+        version => Err(OpaqueError::from_display(format!(
+            "build boring ssl connector: unsupported ssl version: {version}"
+        ))),
+    }
+}
+
+fn ssl_curve_from_group(group: SupportedGroup) -> Option<SslCurve> {
+    Some(match group {
+        SupportedGroup::SECP224R1 => SslCurve::SECP224R1,
+        SupportedGroup::SECP256R1 => SslCurve::SECP256R1,
+        SupportedGroup::SECP384R1 => SslCurve::SECP384R1,
+        SupportedGroup::SECP521R1 => SslCurve::SECP521R1,
+        SupportedGroup::X25519 => SslCurve::X25519,
+        SupportedGroup::X25519KYBER768DRAFT00 => SslCurve::X25519_KYBER768_DRAFT00,
+        SupportedGroup::SECP256R1KYBER768DRAFT00 => SslCurve::P256_KYBER768_DRAFT00,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boring::dep::boring::ssl::{select_next_proto, AlpnError, SslAcceptor};
+    use rcgen::{CertificateParams, KeyPair, PKCS_ECDSA_P256_SHA256};
+
+    fn acceptor() -> (SslAcceptor, X509) {
+        let key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).unwrap();
+        let cert = CertificateParams::new(vec!["example.com".to_owned()])
+            .unwrap()
+            .self_signed(&key)
+            .unwrap();
+        let cert = X509::from_der(cert.der()).unwrap();
+
+        let mut builder = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        builder.set_certificate(&cert).unwrap();
+        builder
+            .set_private_key(&PKey::private_key_from_der(key.serialized_der()).unwrap())
+            .unwrap();
+        builder.set_alpn_select_callback(|_, client| {
+            select_next_proto(b"\x02h2\x08http/1.1", client).ok_or(AlpnError::NOACK)
+        });
+        (builder.build(), cert)
+    }
+
+    /// Handshake using the given connector, returning the negotiated ALPN protocol.
+    async fn handshake(
+        connector: &SslConnector,
+        acceptor: &SslAcceptor,
+    ) -> Result<Option<Vec<u8>>, OpaqueError> {
+        let (client, server) = tokio::io::duplex(16 * 1024);
+        let config = connector.configure().unwrap();
+        let (server, client) = tokio::join!(
+            tokio_boring::accept(acceptor, server),
+            tokio_boring::connect(config, "example.com", client)
+        );
+        let client = client.map_err(|err| OpaqueError::from_display(format!("{err:?}")))?;
+        server.map_err(|err| OpaqueError::from_display(format!("{err:?}")))?;
+        Ok(client.ssl().selected_alpn_protocol().map(<[u8]>::to_vec))
+    }
+
+    #[tokio::test]
+    async fn test_alpn_only_when_configured() {
+        let (acceptor, _) = acceptor();
+
+        let config = ClientConfig {
+            server_verify_mode: ServerVerifyMode::Disable,
+            ..Default::default()
+        };
+        let alpn = handshake(&config.build_connector().unwrap(), &acceptor)
+            .await
+            .unwrap();
+        assert_eq!(alpn, None);
+
+        let config = ClientConfig {
+            server_verify_mode: ServerVerifyMode::Disable,
+            alpn_protocols: vec![ApplicationProtocol::HTTP_2],
+            ..Default::default()
+        };
+        let alpn = handshake(&config.build_connector().unwrap(), &acceptor)
+            .await
+            .unwrap();
+        assert_eq!(alpn.as_deref(), Some(&b"h2"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_root_ca_certs() {
+        let (acceptor, cert) = acceptor();
+
+        let config = ClientConfig::default();
+        handshake(&config.build_connector().unwrap(), &acceptor)
+            .await
+            .unwrap_err();
+
+        let config = ClientConfig {
+            root_ca_certs: vec![cert],
+            ..Default::default()
+        };
+        handshake(&config.build_connector().unwrap(), &acceptor)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_connector_cache() {
+        let (acceptor, _) = acceptor();
+        let keylog = std::env::temp_dir().join(format!(
+            "rama-tls-boring-client-keylog-{}.txt",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&keylog);
+
+        let config = ClientConfig {
+            server_verify_mode: ServerVerifyMode::Disable,
+            keylog_filename: Some(keylog.to_str().unwrap().to_owned()),
+            ..Default::default()
+        };
+        let cache = ConnectorCache::default();
+
+        let first = cache.connector(&config, None).unwrap();
+        let second = cache.connector(&config, None).unwrap();
+        assert!(std::ptr::eq(first.context(), second.context()));
+        assert_eq!(cache.len(), 1);
+
+        // the keylog file is opened once, and written to for every handshake
+        handshake(&first, &acceptor).await.unwrap();
+        handshake(&second, &acceptor).await.unwrap();
+        let lines = std::fs::read_to_string(&keylog).unwrap();
+        assert!(lines.lines().count() >= 2);
+
+        std::fs::remove_file(&keylog).unwrap();
+    }
+}
@@ -1,4 +1,9 @@
-use crate::types::HttpsTunnel;
+use super::config::ConnectorCache;
+use super::{ClientConfig, ServerVerifyMode};
+use crate::types::{
+    client::{ClientHelloEmulation, NegotiatedTlsParameters},
+    ApplicationProtocol, HttpsTunnel,
+};
 use boring::ssl::SslVerifyMode;
 use pin_project_lite::pin_project;
 use private::{ConnectorKindAuto, ConnectorKindSecure, ConnectorKindTunnel};
@@ -7,7 +12,7 @@ use rama_core::{Context, Layer, Service};
 use rama_net::client::{ConnectorService, EstablishedClientConnection};
 use rama_net::stream::Stream;
 use rama_net::transport::TryRefIntoTransportContext;
-use std::fmt;
+use std::{fmt, sync::Arc};
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_boring::SslStream;
 
@@ -16,12 +21,38 @@ use tokio_boring::SslStream;
 /// See [`HttpsConnector`] for more information.
 #[derive(Clone)]
 pub struct HttpsConnectorLayer<K = ConnectorKindAuto> {
+    config: Option<Arc<ClientConfig>>,
     _kind: std::marker::PhantomData<K>,
 }
 
 impl<K> std::fmt::Debug for HttpsConnectorLayer<K> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("HttpsConnectorLayer").finish()
+        f.debug_struct("HttpsConnectorLayer")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl<K> HttpsConnectorLayer<K> {
+    /// Attach a client config to this [`HttpsConnectorLayer`],
+    /// to be used instead of the default client config.
+    pub fn with_config(mut self, config: Arc<ClientConfig>) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Maybe attach a client config to this [`HttpsConnectorLayer`],
+    /// to be used instead of the default client config.
+    pub fn maybe_with_config(mut self, config: Option<Arc<ClientConfig>>) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Attach a client config to this [`HttpsConnectorLayer`],
+    /// to be used instead of the default client config.
+    pub fn set_config(&mut self, config: Arc<ClientConfig>) -> &mut Self {
+        self.config = Some(config);
+        self
     }
 }
 
@@ -31,6 +62,7 @@ impl HttpsConnectorLayer<ConnectorKindAuto> {
     /// otherwise it will forward the pre-established inner connection.
     pub fn auto() -> Self {
         Self {
+            config: None,
             _kind: std::marker::PhantomData,
         }
     }
@@ -41,6 +73,7 @@ impl HttpsConnectorLayer<ConnectorKindSecure> {
     /// establish a secure connection regardless of the request it is for.
     pub fn secure_only() -> Self {
         Self {
+            config: None,
             _kind: std::marker::PhantomData,
         }
     }
@@ -51,6 +84,7 @@ impl HttpsConnectorLayer<ConnectorKindTunnel> {
     /// a secure connection if the request is to be tunneled.
     pub fn tunnel() -> Self {
         Self {
+            config: None,
             _kind: std::marker::PhantomData,
         }
     }
@@ -60,7 +94,7 @@ impl<K, S> Layer<S> for HttpsConnectorLayer<K> {
     type Service = HttpsConnector<S, K>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        HttpsConnector::new(inner)
+        HttpsConnector::new(inner).maybe_with_config(self.config.clone())
     }
 }
 
@@ -79,6 +113,8 @@ impl Default for HttpsConnectorLayer<ConnectorKindAuto> {
 /// establish a secure connection.
 pub struct HttpsConnector<S, K = ConnectorKindAuto> {
     inner: S,
+    config: Option<Arc<ClientConfig>>,
+    connectors: Option<Arc<ConnectorCache>>,
     _kind: std::marker::PhantomData<K>,
 }
 
@@ -86,6 +122,7 @@ impl<S: fmt::Debug, K> fmt::Debug for HttpsConnector<S, K> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("HttpsConnector")
             .field("inner", &self.inner)
+            .field("config", &self.config)
             .finish()
     }
 }
@@ -94,6 +131,8 @@ impl<S: Clone, K> Clone for HttpsConnector<S, K> {
     fn clone(&self) -> Self {
         Self {
             inner: self.inner.clone(),
+            config: self.config.clone(),
+            connectors: self.connectors.clone(),
             _kind: std::marker::PhantomData,
         }
     }
@@ -104,9 +143,34 @@ impl<S, K> HttpsConnector<S, K> {
     pub const fn new(inner: S) -> Self {
         Self {
             inner,
+            config: None,
+            connectors: None,
             _kind: std::marker::PhantomData,
         }
     }
+
+    /// Attach a client config to this [`HttpsConnector`],
+    /// to be used instead of the default client config.
+    pub fn with_config(mut self, config: Arc<ClientConfig>) -> Self {
+        self.set_config(config);
+        self
+    }
+
+    /// Maybe attach a client config to this [`HttpsConnector`],
+    /// to be used instead of the default client config.
+    pub fn maybe_with_config(mut self, config: Option<Arc<ClientConfig>>) -> Self {
+        self.connectors = config.is_some().then(Default::default);
+        self.config = config;
+        self
+    }
+
+    /// Attach a client config to this [`HttpsConnector`],
+    /// to be used instead of the default client config.
+    pub fn set_config(&mut self, config: Arc<ClientConfig>) -> &mut Self {
+        self.config = Some(config);
+        self.connectors = Some(Default::default());
+        self
+    }
 }
 
 impl<S> HttpsConnector<S, ConnectorKindAuto> {
@@ -134,7 +198,7 @@ impl<S> HttpsConnector<S, ConnectorKindTunnel> {
     }
 }
 
-/// this way we do not need a hacky macro... however is there a way to do this without needing to hacK?!?!
+// this way we do not need a hacky macro... however is there a way to do this without needing to hacK?!?!
 
 impl<S, State, Request> Service<State, Request> for HttpsConnector<S, ConnectorKindAuto>
 where
@@ -159,6 +223,7 @@ where
             addr,
         } = self.inner.connect(ctx, req).await.map_err(Into::into)?;
 
+        let emulation = ctx.get::<ClientHelloEmulation>().cloned();
         let transport_ctx = ctx
             .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
             .map_err(|err| {
@@ -188,12 +253,13 @@ where
 
         let host = transport_ctx.authority.host().to_string();
 
-        let stream = self.handshake(host, conn).await?;
+        let stream = self.handshake(host, emulation, conn).await?;
 
         tracing::trace!(
             authority = %transport_ctx.authority,
             "HttpsConnector(auto): protocol secure, established tls connection",
         );
+        ctx.insert(negotiated_tls_parameters(&stream));
         Ok(EstablishedClientConnection {
             ctx,
             req,
@@ -228,6 +294,7 @@ where
             addr,
         } = self.inner.connect(ctx, req).await.map_err(Into::into)?;
 
+        let emulation = ctx.get::<ClientHelloEmulation>().cloned();
         let transport_ctx = ctx
             .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
             .map_err(|err| {
@@ -241,7 +308,8 @@ where
 
         let host = transport_ctx.authority.host().to_string();
 
-        let conn = self.handshake(host, conn).await?;
+        let conn = self.handshake(host, emulation, conn).await?;
+        ctx.insert(negotiated_tls_parameters(&conn));
 
         Ok(EstablishedClientConnection {
             ctx,
@@ -267,7 +335,7 @@ where
         req: Request,
     ) -> Result<Self::Response, Self::Error> {
         let EstablishedClientConnection {
-            ctx,
+            mut ctx,
             req,
             conn,
             addr,
@@ -290,7 +358,9 @@ where
             }
         };
 
-        let stream = self.handshake(host, conn).await?;
+        let emulation = ctx.get::<ClientHelloEmulation>().cloned();
+        let stream = self.handshake(host, emulation, conn).await?;
+        ctx.insert(negotiated_tls_parameters(&stream));
 
         tracing::trace!("HttpsConnector(tunnel): connection secured");
         Ok(EstablishedClientConnection {
@@ -305,38 +375,70 @@ where
 }
 
 impl<S, K> HttpsConnector<S, K> {
-    async fn handshake<T>(&self, target_host: String, stream: T) -> Result<SslStream<T>, BoxError>
+    async fn handshake<T>(
+        &self,
+        target_host: String,
+        emulation: Option<ClientHelloEmulation>,
+        stream: T,
+    ) -> Result<SslStream<T>, BoxError>
     where
         T: Stream + Unpin,
     {
-        // TODO: make this configurable from "outside""...
-        // - for emulation
-        // - for obvious stuff like ALPN
-        // - for client preferences/options
-        // TODO: make configurable via ClientConfig
-        let mut cfg_builder =
-            boring::ssl::SslConnector::builder(boring::ssl::SslMethod::tls_client())
-                .context("create ssl connector builder")?;
-        cfg_builder.set_custom_verify_callback(SslVerifyMode::NONE, |_| Ok(()));
-        cfg_builder.set_verify(SslVerifyMode::NONE);
-
-        let cfg = cfg_builder
-            .build()
+        let emulation = emulation.as_ref();
+
+        let (connector, verify_hostname) = match (&self.config, &self.connectors) {
+            (Some(config), Some(connectors)) => (
+                connectors.connector(config, emulation)?,
+                config.server_verify_mode == ServerVerifyMode::Auto,
+            ),
+            _ => match emulation {
+                Some(emulation) => {
+                    // no config defined: keep the permissive defaults of this connector
+                    let mut config = ClientConfig {
+                        server_verify_mode: ServerVerifyMode::Disable,
+                        ..Default::default()
+                    };
+                    config.apply_emulation(emulation);
+                    (config.build_connector()?, false)
+                }
+                None => {
+                    // no config defined: keep the permissive defaults of this connector
+                    let mut cfg_builder =
+                        boring::ssl::SslConnector::builder(boring::ssl::SslMethod::tls_client())
+                            .context("create ssl connector builder")?;
+                    cfg_builder.set_custom_verify_callback(SslVerifyMode::NONE, |_| Ok(()));
+                    cfg_builder.set_verify(SslVerifyMode::NONE);
+                    (cfg_builder.build(), false)
+                }
+            },
+        };
+
+        let cfg = connector
             .configure()
             .context("create ssl connector configuration")?
             .use_server_name_indication(true)
-            .verify_hostname(false);
+            .verify_hostname(verify_hostname);
         tokio_boring::connect(cfg, target_host.as_str(), stream)
             .await
             .map_err(|err| match err.as_io_error() {
                 Some(err) => OpaqueError::from_display(err.to_string())
-                    .context("boring ssl acceptor: accept")
+                    .context("boring ssl connector: connect")
                     .into_boxed(),
-                None => OpaqueError::from_display("boring ssl acceptor: accept").into_boxed(),
+                None => OpaqueError::from_display("boring ssl connector: connect").into_boxed(),
             })
     }
 }
 
+/// Returns the [`NegotiatedTlsParameters`] of the given (established) tls connection.
+fn negotiated_tls_parameters<T>(stream: &SslStream<T>) -> NegotiatedTlsParameters {
+    NegotiatedTlsParameters {
+        application_layer_protocol: stream
+            .ssl()
+            .selected_alpn_protocol()
+            .map(ApplicationProtocol::from),
+    }
+}
+
 pin_project! {
     /// A stream which can be either a secure or a plain stream.
     pub struct AutoTlsStream<S> {
@@ -1,5 +1,9 @@
 //! TLS client support for Rama.
 
+mod config;
+#[doc(inline)]
+pub use config::{ClientAuth, ClientConfig, ServerVerifyMode};
+
 mod http;
 #[doc(inline)]
 pub use http::{AutoTlsStream, HttpsConnector, HttpsConnectorLayer};
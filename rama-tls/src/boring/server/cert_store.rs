@@ -0,0 +1,44 @@
+use crate::{
+    boring::dep::boring::{
+        pkey::{PKey, Private},
+        x509::X509,
+    },
+    cert_store::{LoadCertificate, PemCertificate},
+};
+use rama_core::error::{ErrorContext, OpaqueError};
+
+#[derive(Clone, Debug)]
+/// A certificate chain and private key loaded by a [`CertificateStore`],
+/// to be served by the boring acceptor using the `cert_store` of its [`ServerConfig`].
+///
+/// [`CertificateStore`]: crate::cert_store::CertificateStore
+/// [`ServerConfig`]: super::ServerConfig
+pub struct ServerCertificate {
+    /// Private Key of the leaf certificate
+    pub private_key: PKey<Private>,
+    /// Cert Chain, starting with the leaf certificate
+    pub cert_chain: Vec<X509>,
+}
+
+impl LoadCertificate for ServerCertificate {
+    fn load(cert: &PemCertificate) -> Result<Self, OpaqueError> {
+        let cert_chain =
+            X509::stack_from_pem(cert.cert_pem()).context("parse certificate chain pem")?;
+        let leaf = cert_chain.first().context("empty certificate chain")?;
+        let private_key =
+            PKey::private_key_from_pem(cert.key_pem()).context("parse private key pem")?;
+
+        let public_key = leaf
+            .public_key()
+            .context("read public key of certificate")?;
+        if !public_key.public_eq(&private_key) {
+            return Err(OpaqueError::from_display(
+                "private key does not match certificate",
+            ));
+        }
+        Ok(Self {
+            private_key,
+            cert_chain,
+        })
+    }
+}
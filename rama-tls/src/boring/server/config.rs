@@ -1,8 +1,9 @@
+use super::ServerCertificate;
 use crate::boring::dep::boring::{
     pkey::{PKey, Private},
     x509::X509,
 };
-use crate::types::ApplicationProtocol;
+use crate::{cert_store::CertificateStore, mitm::CertificateAuthority, types::ApplicationProtocol};
 
 #[derive(Clone, Debug)]
 /// Common configuration for a set of server sessions.
@@ -15,6 +16,20 @@ pub struct ServerConfig {
     pub alpn_protocols: Vec<ApplicationProtocol>,
     /// Write logging information to facilitate tls interception.
     pub keylog_filename: Option<String>,
+    /// Issue a leaf certificate for the server name (SNI) requested by the client
+    /// using this [`CertificateAuthority`], instead of using the private key and
+    /// cert chain of the server, which are still used for clients without SNI.
+    ///
+    /// The leaf certificate mirrors the [`UpstreamCertificate`] found
+    /// in the `Context` of the acceptor, if any.
+    ///
+    /// [`UpstreamCertificate`]: crate::mitm::UpstreamCertificate
+    pub mitm_authority: Option<CertificateAuthority>,
+    /// Serve the certificate of this [`CertificateStore`] for the server name (SNI)
+    /// requested by the client, such that reloaded certificates are served to new handshakes.
+    /// The private key and cert chain of the server are still used
+    /// in case the store has no certificate for the requested server name.
+    pub cert_store: Option<CertificateStore<ServerCertificate>>,
 }
 
 impl ServerConfig {
@@ -25,6 +40,8 @@ impl ServerConfig {
             ca_cert_chain,
             alpn_protocols: vec![],
             keylog_filename: None,
+            mitm_authority: None,
+            cert_store: None,
         }
     }
 }
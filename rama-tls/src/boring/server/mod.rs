@@ -14,6 +14,10 @@ mod config;
 #[doc(inline)]
 pub use config::ServerConfig;
 
+mod cert_store;
+#[doc(inline)]
+pub use cert_store::ServerCertificate;
+
 mod service;
 #[doc(inline)]
 pub use service::TlsAcceptorService;
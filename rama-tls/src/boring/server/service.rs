@@ -1,9 +1,14 @@
-use super::ServerConfig;
+use super::{ServerCertificate, ServerConfig};
 use crate::{
     boring::dep::{
-        boring::ssl::{SslAcceptor, SslMethod},
+        boring::{
+            pkey::PKey,
+            ssl::{NameType, SelectCertError, SslAcceptor, SslMethod, SslRef},
+            x509::X509,
+        },
         tokio_boring::SslStream,
     },
+    mitm::{CertificateAuthority, UpstreamCertificate},
     types::client::ClientHello,
     types::SecureTransport,
 };
@@ -12,7 +17,7 @@ use rama_core::{
     error::{BoxError, ErrorContext, ErrorExt, OpaqueError},
     Context, Service,
 };
-use rama_net::stream::Stream;
+use rama_net::{address::Host, stream::Stream};
 use rama_utils::macros::define_inner_service_accessors;
 use std::sync::Arc;
 
@@ -80,11 +85,23 @@ where
             .set_default_verify_paths()
             .context("build boring ssl acceptor: set default verify paths")?;
 
+        let mitm_authority = self.config.mitm_authority.clone();
+        let cert_store = self.config.cert_store.clone();
+
+        // the chain certificates added to a connection are appended to the chain
+        // inherited from the acceptor, which cannot be cleared, so in case the certificate
+        // is selected per connection the default chain is only added to the connection
+        // once it is known that the default certificate is served
+        let select_certificate = mitm_authority.is_some() || cert_store.is_some();
+        let mut default_chain = Vec::new();
+
         for (i, ca_cert) in self.config.ca_cert_chain.iter().enumerate() {
             if i == 0 {
                 acceptor_builder
                     .set_certificate(ca_cert.as_ref())
                     .context("build boring ssl acceptor: set Leaf CA certificate (x509)")?;
+            } else if select_certificate {
+                default_chain.push(ca_cert.clone());
             } else {
                 acceptor_builder
                     .add_extra_chain_cert(ca_cert.clone())
@@ -98,24 +115,73 @@ where
             .check_private_key()
             .context("build boring ssl acceptor: check private key")?;
 
-        let mut maybe_client_hello = if self.store_client_hello {
-            let maybe_client_hello = Arc::new(Mutex::new(None));
+        let maybe_client_hello = if self.store_client_hello {
+            Some(Arc::new(Mutex::new(None)))
+        } else {
+            None
+        };
+        let upstream_cert = ctx.get::<UpstreamCertificate>().cloned();
+
+        if maybe_client_hello.is_some() || mitm_authority.is_some() || cert_store.is_some() {
             let cb_maybe_client_hello = maybe_client_hello.clone();
-            acceptor_builder.set_select_certificate_callback(move |boring_client_hello| {
-                let maybe_client_hello = match ClientHello::try_from(boring_client_hello) {
-                    Ok(ch) => Some(ch),
-                    Err(err) => {
-                        tracing::warn!(err = %err, "failed to extract boringssl client hello");
-                        None
+            acceptor_builder.set_select_certificate_callback(move |mut boring_client_hello| {
+                let server_name = boring_client_hello
+                    .servername(NameType::HOST_NAME)
+                    .map(str::to_owned);
+
+                let stored_cert = cert_store
+                    .as_ref()
+                    .and_then(|store| store.get(server_name.as_deref()));
+                if let Some(cert) = stored_cert {
+                    let ssl = boring_client_hello.ssl_mut();
+                    if let Err(err) = set_server_certificate(ssl, &cert) {
+                        tracing::warn!(
+                            err = %err,
+                            ?server_name,
+                            "failed to set boringssl stored certificate"
+                        );
+                        return Err(SelectCertError::ERROR);
+                    }
+                } else if let (Some(authority), Some(server_name)) = (&mitm_authority, &server_name)
+                {
+                    let ssl = boring_client_hello.ssl_mut();
+                    if let Err(err) =
+                        set_mitm_certificate(ssl, authority, server_name, upstream_cert.as_ref())
+                    {
+                        tracing::warn!(
+                            err = %err,
+                            %server_name,
+                            "failed to set boringssl mitm certificate"
+                        );
+                        return Err(SelectCertError::ERROR);
+                    }
+                } else {
+                    let ssl = boring_client_hello.ssl_mut();
+                    for chain_cert in &default_chain {
+                        if let Err(err) = ssl.add_chain_cert(chain_cert) {
+                            tracing::warn!(
+                                err = %err,
+                                ?server_name,
+                                "failed to add boringssl default chain certificate"
+                            );
+                            return Err(SelectCertError::ERROR);
+                        }
                     }
-                };
-                *cb_maybe_client_hello.lock() = maybe_client_hello;
+                }
+
+                if let Some(cb_maybe_client_hello) = &cb_maybe_client_hello {
+                    let maybe_client_hello = match ClientHello::try_from(boring_client_hello) {
+                        Ok(ch) => Some(ch),
+                        Err(err) => {
+                            tracing::warn!(err = %err, "failed to extract boringssl client hello");
+                            None
+                        }
+                    };
+                    *cb_maybe_client_hello.lock() = maybe_client_hello;
+                }
                 Ok(())
             });
-            Some(maybe_client_hello)
-        } else {
-            None
-        };
+        }
 
         if !self.config.alpn_protocols.is_empty() {
             let mut buf = vec![];
@@ -154,7 +220,6 @@ where
             })?;
 
         let secure_transport = maybe_client_hello
-            .take()
             .and_then(|maybe_client_hello| maybe_client_hello.lock().take())
             .map(SecureTransport::with_client_hello)
             .unwrap_or_default();
@@ -167,3 +232,211 @@ where
         })
     }
 }
+
+/// Set the certificate loaded by the [`CertificateStore`]
+/// on the ssl connection being accepted.
+///
+/// [`CertificateStore`]: crate::cert_store::CertificateStore
+fn set_server_certificate(ssl: &mut SslRef, cert: &ServerCertificate) -> Result<(), OpaqueError> {
+    let (leaf, chain) = cert
+        .cert_chain
+        .split_first()
+        .context("empty stored certificate chain")?;
+    ssl.set_certificate(leaf)
+        .context("set stored leaf certificate (x509)")?;
+    for chain_cert in chain {
+        ssl.add_chain_cert(chain_cert)
+            .context("add stored chain certificate (x509)")?;
+    }
+    ssl.set_private_key(&cert.private_key)
+        .context("set stored private key")?;
+    Ok(())
+}
+
+/// Set the leaf certificate issued by the [`CertificateAuthority`]
+/// for the given server name on the ssl connection being accepted,
+/// mirroring the [`UpstreamCertificate`] if one is given.
+fn set_mitm_certificate(
+    ssl: &mut SslRef,
+    authority: &CertificateAuthority,
+    server_name: &str,
+    upstream_cert: Option<&UpstreamCertificate>,
+) -> Result<(), OpaqueError> {
+    let host = Host::try_from(server_name)?;
+    let cert = match upstream_cert {
+        Some(upstream_cert) => authority.issue_mirrored(&host, upstream_cert.cert_der())?,
+        None => authority.issue(&host)?,
+    };
+
+    let leaf = X509::from_der(cert.cert_der()).context("decode mitm leaf certificate")?;
+    let ca = X509::from_der(authority.cert_der()).context("decode mitm CA certificate")?;
+    let key = PKey::private_key_from_pkcs8(cert.key_der()).context("decode mitm private key")?;
+
+    ssl.set_certificate(&leaf)
+        .context("set mitm leaf certificate (x509)")?;
+    ssl.add_chain_cert(&ca)
+        .context("add mitm CA certificate (x509)")?;
+    ssl.set_private_key(&key).context("set mitm private key")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        boring::dep::boring::ssl::{SslConnector, SslVerifyMode},
+        cert_store::{CertificateFiles, CertificateStore},
+    };
+    use rama_core::service::service_fn;
+    use rcgen::{
+        BasicConstraints, Certificate, CertificateParams, DnType, IsCa, KeyPair,
+        PKCS_ECDSA_P256_SHA256,
+    };
+    use std::convert::Infallible;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    /// Generate a leaf certificate for the given name, signed by a freshly generated
+    /// intermediate certificate, returning the chain and the private key of the leaf.
+    fn generate_chain(name: &str) -> (Vec<Certificate>, KeyPair) {
+        let ca_key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).unwrap();
+        let mut ca_params = CertificateParams::default();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        ca_params
+            .distinguished_name
+            .push(DnType::CommonName, format!("{name} intermediate"));
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).unwrap();
+        let leaf = CertificateParams::new(vec![name.to_owned()])
+            .unwrap()
+            .signed_by(&key, &ca, &ca_key)
+            .unwrap();
+        (vec![leaf, ca], key)
+    }
+
+    fn server_config(chain: &[Certificate], key: &KeyPair) -> ServerConfig {
+        ServerConfig::new(
+            PKey::private_key_from_der(key.serialized_der()).unwrap(),
+            chain
+                .iter()
+                .map(|cert| X509::from_der(cert.der()).unwrap())
+                .collect(),
+        )
+    }
+
+    /// Handshake with the acceptor, returning the DER encoded certificate chain it served.
+    async fn served_chain(
+        config: ServerConfig,
+        ctx: Context<()>,
+        server_name: Option<&str>,
+    ) -> Vec<Vec<u8>> {
+        let acceptor = TlsAcceptorService::new(
+            Arc::new(config),
+            service_fn(|mut stream: SslStream<DuplexStream>| async move {
+                stream.shutdown().await.unwrap();
+                Ok::<_, Infallible>(())
+            }),
+            false,
+        );
+
+        let mut builder = SslConnector::builder(SslMethod::tls()).unwrap();
+        builder.set_verify(SslVerifyMode::NONE);
+        let mut connect_config = builder.build().configure().unwrap();
+        connect_config.set_use_server_name_indication(server_name.is_some());
+        connect_config.set_verify_hostname(false);
+
+        let (client, server) = tokio::io::duplex(16 * 1024);
+        let server = tokio::spawn(async move { acceptor.serve(ctx, server).await });
+        let mut stream =
+            tokio_boring::connect(connect_config, server_name.unwrap_or("localhost"), client)
+                .await
+                .unwrap();
+        let chain = stream
+            .ssl()
+            .peer_cert_chain()
+            .unwrap()
+            .iter()
+            .map(|cert| cert.to_der().unwrap())
+            .collect();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        server.await.unwrap().unwrap();
+        chain
+    }
+
+    fn ders(chain: &[Certificate]) -> Vec<Vec<u8>> {
+        chain.iter().map(|cert| cert.der().to_vec()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_cert_store_chain() {
+        let dir =
+            std::env::temp_dir().join(format!("rama-tls-boring-cert-store-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let (stored_chain, stored_key) = generate_chain("example.com");
+        std::fs::write(
+            dir.join("example.com.crt.pem"),
+            stored_chain
+                .iter()
+                .map(Certificate::pem)
+                .collect::<String>(),
+        )
+        .unwrap();
+        std::fs::write(dir.join("example.com.key.pem"), stored_key.serialize_pem()).unwrap();
+        let store = CertificateStore::<ServerCertificate>::load(CertificateFiles::directory(&dir))
+            .await
+            .unwrap();
+
+        let (default_chain, default_key) = generate_chain("localhost");
+        let mut config = server_config(&default_chain, &default_key);
+        config.cert_store = Some(store);
+
+        // the stored chain is served without the chain of the default certificate
+        let chain = served_chain(config.clone(), Context::default(), Some("example.com")).await;
+        assert_eq!(chain, ders(&stored_chain));
+
+        // the default chain is still served for unknown server names and clients without SNI
+        let chain = served_chain(config.clone(), Context::default(), Some("other.internal")).await;
+        assert_eq!(chain, ders(&default_chain));
+        let chain = served_chain(config, Context::default(), None).await;
+        assert_eq!(chain, ders(&default_chain));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mitm_chain() {
+        let authority = CertificateAuthority::generate("rama test CA").unwrap();
+        let (default_chain, default_key) = generate_chain("localhost");
+        let mut config = server_config(&default_chain, &default_key);
+        config.mitm_authority = Some(authority.clone());
+
+        // the issued leaf is served together with the CA, without the default chain
+        let chain = served_chain(config.clone(), Context::default(), Some("example.com")).await;
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[1], authority.cert_der());
+
+        // the issued leaf mirrors the upstream certificate found in the context
+        let upstream_key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).unwrap();
+        let mut upstream_params = CertificateParams::new(vec!["example.com".to_owned()]).unwrap();
+        upstream_params
+            .distinguished_name
+            .push(DnType::OrganizationName, "Example Inc.");
+        let upstream = upstream_params.self_signed(&upstream_key).unwrap();
+        let mut ctx = Context::default();
+        ctx.insert(UpstreamCertificate::new(upstream.der().to_vec()));
+        let chain = served_chain(config.clone(), ctx, Some("example.com")).await;
+        assert_eq!(chain.len(), 2);
+        let params = CertificateParams::from_ca_cert_der(&chain[0].as_slice().into()).unwrap();
+        assert!(matches!(
+            params.distinguished_name.get(&DnType::OrganizationName),
+            Some(rcgen::DnValue::Utf8String(org)) if org == "Example Inc."
+        ));
+
+        // clients without SNI are served the default chain
+        let chain = served_chain(config, Context::default(), None).await;
+        assert_eq!(chain, ders(&default_chain));
+    }
+}
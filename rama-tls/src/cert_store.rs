@@ -0,0 +1,443 @@
+//! Server certificates loaded from PEM files, which can be reloaded
+//! without restarting the tls acceptors serving them.
+//!
+//! A [`CertificateStore`] loads its certificates from [`CertificateFiles`], either:
+//!
+//! - a single certificate chain and private key, served for all server names;
+//! - or a directory with a `<server name>.crt.pem` certificate chain
+//!   and `<server name>.key.pem` private key per server name (SNI),
+//!   e.g. `example.com.crt.pem` and `example.com.key.pem`,
+//!   which is also the layout of the cache directory of the ACME client.
+//!   Wildcard certificates can be stored as `*.example.com.crt.pem`,
+//!   and the `default.crt.pem` certificate, if any, is served to all other clients.
+//!
+//! Certificates are reloaded on demand (see [`CertificateStore::reload`]),
+//! when triggered by a [`ReloadTrigger`], or when the files change
+//! (see [`CertificateStore::reload_on_change`]). A private key which does not match
+//! its certificate fails the reload, keeping the current certificates in place.
+//! Only new handshakes are served using the reloaded certificates.
+//!
+//! The [`CertificateStore`] can be used with the rustls acceptor
+//! as the certificate resolver of its `ServerConfig`,
+//! and with the boring acceptor using the `cert_store` of its `ServerConfig`.
+
+use rama_core::{
+    error::{ErrorContext, ErrorExt, OpaqueError},
+    graceful::ShutdownGuard,
+    reload::{ReloadTrigger, Reloadable},
+};
+use std::{
+    collections::HashMap,
+    fmt, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tokio::time::MissedTickBehavior;
+
+const CERT_SUFFIX: &str = ".crt.pem";
+const KEY_SUFFIX: &str = ".key.pem";
+const DEFAULT_NAME: &str = "default";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The PEM files from which the certificates of a [`CertificateStore`] are loaded.
+///
+/// See [the module level documentation](crate::cert_store) for more information.
+pub struct CertificateFiles {
+    source: Source,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Source {
+    Single {
+        cert_path: PathBuf,
+        key_path: PathBuf,
+    },
+    Directory(PathBuf),
+}
+
+impl CertificateFiles {
+    /// A single certificate chain and private key, served for all server names.
+    pub fn new(cert_path: impl Into<PathBuf>, key_path: impl Into<PathBuf>) -> Self {
+        Self {
+            source: Source::Single {
+                cert_path: cert_path.into(),
+                key_path: key_path.into(),
+            },
+        }
+    }
+
+    /// A directory containing a certificate chain and private key per server name.
+    pub fn directory(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            source: Source::Directory(dir.into()),
+        }
+    }
+
+    /// Read the PEM files, returning the certificates by server name,
+    /// using the [`DEFAULT_NAME`] for the certificate served to all other clients.
+    async fn read(&self) -> Result<HashMap<String, PemCertificate>, OpaqueError> {
+        match &self.source {
+            Source::Single {
+                cert_path,
+                key_path,
+            } => {
+                let cert = PemCertificate::read(cert_path, key_path).await?;
+                Ok([(DEFAULT_NAME.to_owned(), cert)].into())
+            }
+            Source::Directory(dir) => {
+                let mut certs = HashMap::new();
+                for name in list_cert_names(dir).await? {
+                    let cert = PemCertificate::read(
+                        &dir.join(format!("{name}{CERT_SUFFIX}")),
+                        &dir.join(format!("{name}{KEY_SUFFIX}")),
+                    )
+                    .await?;
+                    certs.insert(name.to_ascii_lowercase(), cert);
+                }
+                Ok(certs)
+            }
+        }
+    }
+
+    /// The modification time and size of the PEM files, used to detect that they changed.
+    async fn version(&self) -> Vec<(PathBuf, Option<SystemTime>, u64)> {
+        let paths = match &self.source {
+            Source::Single {
+                cert_path,
+                key_path,
+            } => vec![cert_path.clone(), key_path.clone()],
+            Source::Directory(dir) => {
+                let mut paths = Vec::new();
+                if let Ok(names) = list_cert_names(dir).await {
+                    for name in names {
+                        paths.push(dir.join(format!("{name}{CERT_SUFFIX}")));
+                        paths.push(dir.join(format!("{name}{KEY_SUFFIX}")));
+                    }
+                }
+                paths
+            }
+        };
+
+        let mut version = Vec::with_capacity(paths.len());
+        for path in paths {
+            let metadata = tokio::fs::metadata(&path).await.ok();
+            let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+            let len = metadata.map(|m| m.len()).unwrap_or_default();
+            version.push((path, modified, len));
+        }
+        version
+    }
+}
+
+/// The sorted server names of the certificate chains stored in the given directory.
+async fn list_cert_names(dir: &Path) -> Result<Vec<String>, OpaqueError> {
+    let mut entries = tokio::fs::read_dir(dir)
+        .await
+        .with_context(|| format!("read certificate directory {}", dir.display()))?;
+    let mut names = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .with_context(|| format!("read certificate directory {}", dir.display()))?
+    {
+        if let Some(name) = entry
+            .file_name()
+            .to_str()
+            .and_then(|file_name| file_name.strip_suffix(CERT_SUFFIX))
+        {
+            names.push(name.to_owned());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+#[derive(Clone)]
+/// A PEM encoded certificate chain and private key, as read from disk.
+pub struct PemCertificate {
+    cert_pem: Vec<u8>,
+    key_pem: Vec<u8>,
+}
+
+impl fmt::Debug for PemCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PemCertificate")
+            .field("cert_pem", &String::from_utf8_lossy(&self.cert_pem))
+            .finish()
+    }
+}
+
+impl PemCertificate {
+    async fn read(cert_path: &Path, key_path: &Path) -> Result<Self, OpaqueError> {
+        Ok(Self {
+            cert_pem: read_file(cert_path).await?,
+            key_pem: read_file(key_path).await?,
+        })
+    }
+
+    /// The PEM encoded certificate chain, starting with the leaf certificate.
+    pub fn cert_pem(&self) -> &[u8] {
+        &self.cert_pem
+    }
+
+    /// The PEM encoded private key of the leaf certificate.
+    pub fn key_pem(&self) -> &[u8] {
+        &self.key_pem
+    }
+}
+
+async fn read_file(path: &Path) -> Result<Vec<u8>, OpaqueError> {
+    tokio::fs::read(path)
+        .await
+        .map_err(|err: io::Error| err.context(format!("read {}", path.display())))
+}
+
+/// A certificate chain and private key in the format of a tls backend,
+/// loaded from a [`PemCertificate`] by a [`CertificateStore`].
+pub trait LoadCertificate: Sized + Send + Sync + 'static {
+    /// Load the certificate chain and private key,
+    /// failing in case the private key does not match the leaf certificate.
+    fn load(cert: &PemCertificate) -> Result<Self, OpaqueError>;
+}
+
+#[derive(Debug)]
+struct Certificates<C> {
+    by_name: HashMap<String, C>,
+    version: Vec<(PathBuf, Option<SystemTime>, u64)>,
+}
+
+impl<C: LoadCertificate> Certificates<C> {
+    async fn load(files: &CertificateFiles) -> Result<Self, OpaqueError> {
+        // read the version first, such that changes made while loading trigger another reload
+        let version = files.version().await;
+        let mut by_name = HashMap::new();
+        for (name, pem) in files.read().await? {
+            let cert = C::load(&pem).with_context(|| format!("load certificate for {name}"))?;
+            by_name.insert(name, cert);
+        }
+        Ok(Self { by_name, version })
+    }
+}
+
+/// A store of server certificates loaded from [`CertificateFiles`],
+/// which can be reloaded without restarting the tls acceptors serving them.
+///
+/// Clones of a [`CertificateStore`] share the same certificates.
+///
+/// See [the module level documentation](crate::cert_store) for more information.
+pub struct CertificateStore<C> {
+    files: CertificateFiles,
+    certificates: Reloadable<Certificates<C>>,
+}
+
+impl<C: fmt::Debug> fmt::Debug for CertificateStore<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CertificateStore")
+            .field("files", &self.files)
+            .field("certificates", &self.certificates)
+            .finish()
+    }
+}
+
+impl<C> Clone for CertificateStore<C> {
+    fn clone(&self) -> Self {
+        Self {
+            files: self.files.clone(),
+            certificates: self.certificates.clone(),
+        }
+    }
+}
+
+impl<C> CertificateStore<C> {
+    /// The [`CertificateFiles`] from which the certificates are loaded.
+    pub fn files(&self) -> &CertificateFiles {
+        &self.files
+    }
+
+    /// Returns the certificate for the given server name (SNI),
+    /// falling back to a matching wildcard certificate and then the default certificate.
+    pub fn get(&self, server_name: Option<&str>) -> Option<C>
+    where
+        C: Clone,
+    {
+        let certificates = self.certificates.load();
+        let by_name = &certificates.by_name;
+        server_name
+            .and_then(|server_name| {
+                let server_name = server_name.trim_end_matches('.').to_ascii_lowercase();
+                by_name.get(&server_name).or_else(|| {
+                    let (_, parent) = server_name.split_once('.')?;
+                    by_name.get(&format!("*.{parent}"))
+                })
+            })
+            .or_else(|| by_name.get(DEFAULT_NAME))
+            .cloned()
+    }
+}
+
+impl<C: LoadCertificate> CertificateStore<C> {
+    /// Create a new [`CertificateStore`], loading its certificates from the given files.
+    pub async fn load(files: CertificateFiles) -> Result<Self, OpaqueError> {
+        let certificates = Certificates::load(&files).await?;
+        Ok(Self {
+            files,
+            certificates: Reloadable::new(certificates),
+        })
+    }
+
+    /// Reload the certificates from the files, replacing the current certificates
+    /// only in case all of them could be loaded.
+    pub async fn reload(&self) -> Result<(), OpaqueError> {
+        let certificates = Certificates::load(&self.files).await?;
+        self.certificates.store(certificates);
+        Ok(())
+    }
+
+    /// Spawn a task which reloads the certificates every time a reload
+    /// is triggered by the given [`ReloadTrigger`], until shutdown is initiated.
+    ///
+    /// Failed reloads are logged, keeping the current certificates in place.
+    pub fn reload_on(&self, guard: ShutdownGuard, trigger: &ReloadTrigger) {
+        let files = self.files.clone();
+        self.certificates.reload_on(guard, trigger, move || {
+            let files = files.clone();
+            async move { Certificates::load(&files).await }
+        });
+    }
+
+    /// Spawn a task which reloads the certificates every time
+    /// the files are modified, added or removed, until shutdown is initiated.
+    ///
+    /// The files are polled at the given interval, comparing their modification time and size.
+    pub fn reload_on_change(&self, guard: ShutdownGuard, interval: Duration) {
+        let trigger = ReloadTrigger::new();
+        self.reload_on(guard.clone(), &trigger);
+
+        let files = self.files.clone();
+        let mut last_version = self.certificates.load().version.clone();
+        guard.into_spawn_task_fn(move |guard| async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = guard.cancelled() => return,
+                    _ = ticker.tick() => (),
+                }
+                let version = files.version().await;
+                if version != last_version {
+                    tracing::info!(files = ?files, "certificate files changed: trigger reload");
+                    last_version = version;
+                    trigger.trigger();
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::graceful::Shutdown;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct TestCertificate(String);
+
+    impl LoadCertificate for TestCertificate {
+        fn load(cert: &PemCertificate) -> Result<Self, OpaqueError> {
+            let cert_pem = String::from_utf8_lossy(cert.cert_pem()).into_owned();
+            if cert.key_pem() != format!("key of {cert_pem}").as_bytes() {
+                return Err(OpaqueError::from_display("key mismatch"));
+            }
+            Ok(Self(cert_pem))
+        }
+    }
+
+    fn write_cert(dir: &Path, name: &str, key_of: &str) {
+        std::fs::write(dir.join(format!("{name}{CERT_SUFFIX}")), name).unwrap();
+        std::fs::write(
+            dir.join(format!("{name}{KEY_SUFFIX}")),
+            format!("key of {key_of}"),
+        )
+        .unwrap();
+    }
+
+    fn get(store: &CertificateStore<TestCertificate>, server_name: &str) -> Option<String> {
+        store.get(Some(server_name)).map(|cert| cert.0)
+    }
+
+    #[tokio::test]
+    async fn test_certificate_store_directory() {
+        let dir = std::env::temp_dir().join(format!(
+            "rama-tls-cert-store-{}-directory",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        write_cert(&dir, "example.com", "example.com");
+        write_cert(&dir, "*.example.com", "*.example.com");
+
+        let store = CertificateStore::<TestCertificate>::load(CertificateFiles::directory(&dir))
+            .await
+            .unwrap();
+        assert_eq!(get(&store, "Example.COM."), Some("example.com".to_owned()));
+        assert_eq!(
+            get(&store, "www.example.com"),
+            Some("*.example.com".to_owned())
+        );
+        assert_eq!(get(&store, "example.org"), None);
+        assert_eq!(store.get(None), None);
+
+        // a key which does not match keeps the current certificates in place
+        write_cert(&dir, "default", "example.org");
+        store.reload().await.unwrap_err();
+        assert_eq!(get(&store, "example.org"), None);
+
+        write_cert(&dir, "default", "default");
+        store.reload().await.unwrap();
+        assert_eq!(get(&store, "example.org"), Some("default".to_owned()));
+        assert_eq!(store.get(None), Some(TestCertificate("default".to_owned())));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_certificate_store_reload_on_change() {
+        let dir = std::env::temp_dir().join(format!(
+            "rama-tls-cert-store-{}-reload-on-change",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        write_cert(&dir, "server", "server");
+
+        let files = CertificateFiles::new(
+            dir.join(format!("server{CERT_SUFFIX}")),
+            dir.join(format!("server{KEY_SUFFIX}")),
+        );
+        let store = CertificateStore::<TestCertificate>::load(files)
+            .await
+            .unwrap();
+        assert_eq!(get(&store, "example.com"), Some("server".to_owned()));
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let shutdown = Shutdown::new(async move {
+            rx.await.unwrap();
+        });
+        store.reload_on_change(shutdown.guard(), Duration::from_millis(10));
+
+        let reloaded = store.certificates.reloaded();
+        std::fs::write(dir.join(format!("server{CERT_SUFFIX}")), "renewed").unwrap();
+        std::fs::write(dir.join(format!("server{KEY_SUFFIX}")), "key of renewed").unwrap();
+        tokio::time::timeout(Duration::from_secs(5), reloaded)
+            .await
+            .unwrap();
+        assert_eq!(get(&store, "example.com"), Some("renewed".to_owned()));
+
+        tx.send(()).unwrap();
+        shutdown
+            .shutdown_with_limit(Duration::from_secs(1))
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
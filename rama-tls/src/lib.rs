@@ -65,6 +65,13 @@ pub use rustls as std;
 #[cfg(feature = "boring")]
 pub use boring as std;
 
+#[cfg(feature = "rustls")]
+pub mod acme;
+
+pub mod cert_store;
+
+pub mod mitm;
+
 pub mod types {
     //! common tls types
     #[doc(inline)]
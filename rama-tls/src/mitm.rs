@@ -0,0 +1,538 @@
+//! On-the-fly certificate issuance for tls interception (MITM) proxies.
+//!
+//! A [`CertificateAuthority`] loads (or generates) a CA certificate and key pair,
+//! and issues a leaf certificate for each server name (SNI) or authority
+//! a client connects to, such that clients which trust the CA accept
+//! the intercepted connections. Issued certificates are cached,
+//! evicting the least recently used ones once the cache is full.
+//!
+//! The [`CertificateAuthority`] can be used with the rustls acceptor
+//! using the `MitmServerConfigProvider` of the `rustls` module,
+//! and with the boring acceptor using the `mitm_authority` of its `ServerConfig`.
+//!
+//! A MITM proxy which connected to the upstream server prior to accepting
+//! the intercepted connection can insert the [`UpstreamCertificate`] in the
+//! [`Context`] of the acceptor, such that the issued leaf certificate mirrors it.
+//!
+//! [`Context`]: rama_core::Context
+
+use parking_lot::Mutex;
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_net::address::Host;
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType,
+    ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose, SanType, SerialNumber,
+    PKCS_ECDSA_P256_SHA256,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::{BuildHasher, Hash, Hasher, RandomState},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+#[cfg(feature = "rustls")]
+use std::sync::OnceLock;
+
+const DEFAULT_CACHE_CAPACITY: usize = 1024;
+const DEFAULT_LEAF_VALIDITY: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+/// Margin applied to the validity of issued certificates, to allow for clock skew.
+const CLOCK_SKEW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A certificate authority issuing leaf certificates on the fly,
+/// for each server name (SNI) or authority intercepted by a MITM proxy.
+///
+/// All leaf certificates share a single key pair, generated when the
+/// [`CertificateAuthority`] is created, such that issuing a certificate
+/// only requires it to be signed. Issued certificates are cached
+/// in an LRU cache shared by all clones of the [`CertificateAuthority`].
+///
+/// See [the module level documentation](crate::mitm) for more information.
+pub struct CertificateAuthority {
+    issuer: Arc<Issuer>,
+    leaf_validity: Duration,
+    cache: Arc<Mutex<LeafCache>>,
+}
+
+struct Issuer {
+    cert: Certificate,
+    cert_der: Vec<u8>,
+    key: KeyPair,
+    leaf_key: KeyPair,
+    leaf_key_der: Arc<[u8]>,
+    serial_hasher: RandomState,
+    serial_counter: AtomicU64,
+}
+
+impl fmt::Debug for CertificateAuthority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CertificateAuthority")
+            .field("issuer", &self.issuer.cert.params().distinguished_name)
+            .field("leaf_validity", &self.leaf_validity)
+            .field("cache_capacity", &self.cache.lock().capacity)
+            .finish()
+    }
+}
+
+impl Clone for CertificateAuthority {
+    fn clone(&self) -> Self {
+        Self {
+            issuer: self.issuer.clone(),
+            leaf_validity: self.leaf_validity,
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+impl CertificateAuthority {
+    /// Load a [`CertificateAuthority`] from its PEM encoded certificate
+    /// and PEM encoded (PKCS#8) private key.
+    pub fn from_pem(cert_pem: &str, key_pem: &str) -> Result<Self, OpaqueError> {
+        let cert = pem::parse(cert_pem).context("parse CA certificate pem")?;
+        let key = KeyPair::from_pem(key_pem).context("parse CA private key pem")?;
+        Self::from_parts(cert.into_contents(), key)
+    }
+
+    /// Load a [`CertificateAuthority`] from its DER encoded certificate
+    /// and DER encoded PKCS#8 private key.
+    pub fn from_der(cert_der: &[u8], key_der: &[u8]) -> Result<Self, OpaqueError> {
+        let key = KeyPair::try_from(key_der).context("parse CA private key der")?;
+        Self::from_parts(cert_der.to_vec(), key)
+    }
+
+    /// Generate a new self-signed [`CertificateAuthority`] with the given common name,
+    /// valid for ten years.
+    ///
+    /// Use [`CertificateAuthority::cert_pem`] to export its certificate,
+    /// such that it can be installed as trusted on the intercepted clients.
+    pub fn generate(common_name: &str) -> Result<Self, OpaqueError> {
+        let key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).context("generate CA key pair")?;
+
+        let mut params = CertificateParams::default();
+        params.distinguished_name = DistinguishedName::new();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, common_name);
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.key_usages = vec![
+            KeyUsagePurpose::KeyCertSign,
+            KeyUsagePurpose::DigitalSignature,
+            KeyUsagePurpose::CrlSign,
+        ];
+        let now = SystemTime::now();
+        params.not_before = (now - CLOCK_SKEW).into();
+        params.not_after = (now + 10 * DEFAULT_LEAF_VALIDITY).into();
+
+        let cert = params
+            .self_signed(&key)
+            .context("self-sign CA certificate")?;
+        Self::from_parts(cert.der().to_vec(), key)
+    }
+
+    fn from_parts(cert_der: Vec<u8>, key: KeyPair) -> Result<Self, OpaqueError> {
+        let (_, parsed) =
+            x509_parser::parse_x509_certificate(&cert_der).context("parse CA certificate")?;
+        if parsed.public_key().subject_public_key.data.as_ref() != key.public_key_raw() {
+            return Err(OpaqueError::from_display(
+                "CA private key does not match the public key of the CA certificate",
+            ));
+        }
+
+        let params = CertificateParams::from_ca_cert_der(&cert_der.as_slice().into())
+            .context("parse CA certificate")?;
+        // the certificate is only used as issuer of the leaf certificates,
+        // for which its distinguished name and key identifier are preserved
+        let cert = params
+            .self_signed(&key)
+            .context("create CA issuer certificate")?;
+
+        let leaf_key =
+            KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).context("generate leaf key pair")?;
+        let leaf_key_der = leaf_key.serialize_der().into();
+
+        Ok(Self {
+            issuer: Arc::new(Issuer {
+                cert,
+                cert_der,
+                key,
+                leaf_key,
+                leaf_key_der,
+                serial_hasher: RandomState::new(),
+                serial_counter: AtomicU64::new(0),
+            }),
+            leaf_validity: DEFAULT_LEAF_VALIDITY,
+            cache: Arc::new(Mutex::new(LeafCache::new(DEFAULT_CACHE_CAPACITY))),
+        })
+    }
+
+    /// Set the maximum amount of issued certificates to cache,
+    /// replacing the cache of this [`CertificateAuthority`] with a new one.
+    ///
+    /// Defaults to `1024`.
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache = Arc::new(Mutex::new(LeafCache::new(capacity)));
+        self
+    }
+
+    /// Set the maximum amount of issued certificates to cache,
+    /// replacing the cache of this [`CertificateAuthority`] with a new one.
+    ///
+    /// Defaults to `1024`.
+    pub fn set_cache_capacity(&mut self, capacity: usize) -> &mut Self {
+        self.cache = Arc::new(Mutex::new(LeafCache::new(capacity)));
+        self
+    }
+
+    /// Set the duration for which issued certificates are valid.
+    ///
+    /// Defaults to one year.
+    pub fn with_leaf_validity(mut self, validity: Duration) -> Self {
+        self.leaf_validity = validity;
+        self
+    }
+
+    /// Set the duration for which issued certificates are valid.
+    ///
+    /// Defaults to one year.
+    pub fn set_leaf_validity(&mut self, validity: Duration) -> &mut Self {
+        self.leaf_validity = validity;
+        self
+    }
+
+    /// The DER encoded certificate of this [`CertificateAuthority`].
+    pub fn cert_der(&self) -> &[u8] {
+        &self.issuer.cert_der
+    }
+
+    /// The PEM encoded certificate of this [`CertificateAuthority`].
+    pub fn cert_pem(&self) -> String {
+        pem::encode(&pem::Pem::new("CERTIFICATE", self.issuer.cert_der.clone()))
+    }
+
+    /// Issue a leaf certificate for the given [`Host`],
+    /// or return the cached one issued earlier.
+    pub fn issue(&self, host: &Host) -> Result<Arc<IssuedCertificate>, OpaqueError> {
+        let key = CacheKey {
+            host: host.to_string(),
+            upstream: None,
+        };
+        self.issue_cached(key, || self.leaf_params(host))
+    }
+
+    /// Issue a leaf certificate for the given [`Host`] which mirrors
+    /// the subject and subject alternative names of the given DER encoded
+    /// certificate of the upstream server, or return the cached one issued earlier.
+    ///
+    /// The [`Host`] is added to the subject alternative names in case
+    /// it is not covered by the upstream certificate.
+    ///
+    /// Used by the acceptors in case an [`UpstreamCertificate`] is found in their `Context`.
+    pub fn issue_mirrored(
+        &self,
+        host: &Host,
+        upstream_cert_der: &[u8],
+    ) -> Result<Arc<IssuedCertificate>, OpaqueError> {
+        let mut hasher = std::hash::DefaultHasher::new();
+        upstream_cert_der.hash(&mut hasher);
+        let key = CacheKey {
+            host: host.to_string(),
+            upstream: Some(hasher.finish()),
+        };
+        self.issue_cached(key, || {
+            let upstream = CertificateParams::from_ca_cert_der(&upstream_cert_der.into())
+                .context("parse upstream certificate")?;
+            let mut params = self.leaf_params(host)?;
+            let host_san = params.subject_alt_names.pop();
+            params.distinguished_name = upstream.distinguished_name;
+            params.subject_alt_names = upstream.subject_alt_names;
+            if let Some(host_san) = host_san {
+                if !san_covers(&params.subject_alt_names, &host_san) {
+                    params.subject_alt_names.push(host_san);
+                }
+            }
+            Ok(params)
+        })
+    }
+
+    fn issue_cached(
+        &self,
+        key: CacheKey,
+        params: impl FnOnce() -> Result<CertificateParams, OpaqueError>,
+    ) -> Result<Arc<IssuedCertificate>, OpaqueError> {
+        let now = SystemTime::now();
+        if let Some(cert) = self.cache.lock().get(&key, now) {
+            return Ok(cert);
+        }
+
+        let params = params()?;
+        let expires_at = SystemTime::from(params.not_after);
+        let cert = params
+            .signed_by(&self.issuer.leaf_key, &self.issuer.cert, &self.issuer.key)
+            .context("sign leaf certificate")?;
+        let cert = Arc::new(IssuedCertificate {
+            cert_der: cert.der().to_vec(),
+            key_der: self.issuer.leaf_key_der.clone(),
+            expires_at,
+            #[cfg(feature = "rustls")]
+            rustls_server_config: OnceLock::new(),
+        });
+        tracing::trace!(host = %key.host, "issued mitm leaf certificate");
+
+        self.cache.lock().insert(key, cert.clone());
+        Ok(cert)
+    }
+
+    fn leaf_params(&self, host: &Host) -> Result<CertificateParams, OpaqueError> {
+        let san = match host {
+            Host::Name(domain) => SanType::DnsName(
+                domain
+                    .as_str()
+                    .trim_end_matches('.')
+                    .try_into()
+                    .context("encode domain as subject alternative name")?,
+            ),
+            Host::Address(ip) => SanType::IpAddress(*ip),
+        };
+
+        let mut params = CertificateParams::default();
+        params.distinguished_name = DistinguishedName::new();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, host.to_string());
+        params.subject_alt_names = vec![san];
+        params.serial_number = Some(self.next_serial_number(host));
+        params.is_ca = IsCa::ExplicitNoCa;
+        // the leaf key pair is an ECDSA key, which is only used to sign
+        params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        params.use_authority_key_identifier_extension = true;
+        let now = SystemTime::now();
+        params.not_before = (now - CLOCK_SKEW).into();
+        params.not_after = (now + self.leaf_validity).into();
+        Ok(params)
+    }
+
+    /// Generate a unique serial number, as all leaf certificates share the same key pair,
+    /// from which the serial number would be derived otherwise.
+    fn next_serial_number(&self, host: &Host) -> SerialNumber {
+        let mut hasher = self.issuer.serial_hasher.build_hasher();
+        host.hash(&mut hasher);
+        self.issuer
+            .serial_counter
+            .fetch_add(1, Ordering::Relaxed)
+            .hash(&mut hasher);
+        SystemTime::now().hash(&mut hasher);
+        // a positive integer, as required by RFC 5280
+        SerialNumber::from(hasher.finish() >> 1)
+    }
+}
+
+pub(crate) fn san_covers(sans: &[SanType], host_san: &SanType) -> bool {
+    sans.iter().any(|san| match (san, host_san) {
+        (SanType::DnsName(name), SanType::DnsName(host)) => {
+            let (name, host) = (name.as_str(), host.as_str());
+            name.eq_ignore_ascii_case(host)
+                || name.strip_prefix("*.").is_some_and(|parent| {
+                    host.split_once('.')
+                        .is_some_and(|(_, host_parent)| host_parent.eq_ignore_ascii_case(parent))
+                })
+        }
+        (SanType::IpAddress(ip), SanType::IpAddress(host)) => ip == host,
+        _ => false,
+    })
+}
+
+#[derive(Debug, Clone)]
+/// The DER encoded certificate of the upstream server of an intercepted connection.
+///
+/// Insert it in the `Context` of the tls acceptor, such that the leaf certificate
+/// issued by the [`CertificateAuthority`] mirrors it, see [`CertificateAuthority::issue_mirrored`].
+pub struct UpstreamCertificate(Arc<[u8]>);
+
+impl UpstreamCertificate {
+    /// Create a new [`UpstreamCertificate`] from the DER encoded certificate of the upstream server.
+    pub fn new(cert_der: impl Into<Arc<[u8]>>) -> Self {
+        Self(cert_der.into())
+    }
+
+    /// The DER encoded certificate of the upstream server.
+    pub fn cert_der(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[derive(Clone)]
+/// A leaf certificate issued by a [`CertificateAuthority`], together with its private key.
+pub struct IssuedCertificate {
+    cert_der: Vec<u8>,
+    key_der: Arc<[u8]>,
+    expires_at: SystemTime,
+    /// The rustls server config serving this certificate, cached (and evicted) together with it.
+    #[cfg(feature = "rustls")]
+    pub(crate) rustls_server_config: OnceLock<Arc<crate::rustls::dep::rustls::ServerConfig>>,
+}
+
+impl fmt::Debug for IssuedCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IssuedCertificate")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+impl IssuedCertificate {
+    /// The DER encoded leaf certificate.
+    pub fn cert_der(&self) -> &[u8] {
+        &self.cert_der
+    }
+
+    /// The DER encoded PKCS#8 private key of the leaf certificate.
+    pub fn key_der(&self) -> &[u8] {
+        &self.key_der
+    }
+
+    /// The time at which the leaf certificate expires.
+    pub fn expires_at(&self) -> SystemTime {
+        self.expires_at
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    host: String,
+    upstream: Option<u64>,
+}
+
+/// A least recently used cache of issued certificates.
+struct LeafCache {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<CacheKey, (u64, Arc<IssuedCertificate>)>,
+}
+
+impl LeafCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &CacheKey, now: SystemTime) -> Option<Arc<IssuedCertificate>> {
+        self.tick += 1;
+        let (last_used, cert) = self.entries.get_mut(key)?;
+        if cert.expires_at <= now + CLOCK_SKEW {
+            self.entries.remove(key);
+            return None;
+        }
+        *last_used = self.tick;
+        Some(cert.clone())
+    }
+
+    fn insert(&mut self, key: CacheKey, cert: Arc<IssuedCertificate>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let lru = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (last_used, _))| *last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(lru) = lru {
+                self.entries.remove(&lru);
+            }
+        }
+        self.tick += 1;
+        self.entries.insert(key, (self.tick, cert));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_net::address::Domain;
+
+    fn host(name: &'static str) -> Host {
+        Host::Name(Domain::from_static(name))
+    }
+
+    #[test]
+    fn test_issue_cached() {
+        let ca = CertificateAuthority::generate("rama test CA")
+            .unwrap()
+            .with_cache_capacity(2);
+
+        let a = ca.issue(&host("a.example.com")).unwrap();
+        assert!(Arc::ptr_eq(&a, &ca.issue(&host("a.example.com")).unwrap()));
+        let b = ca.issue(&host("b.example.com")).unwrap();
+        assert_ne!(a.cert_der(), b.cert_der());
+        assert_eq!(a.key_der(), b.key_der());
+
+        // a is used more recently than b, so b is evicted
+        ca.issue(&host("a.example.com")).unwrap();
+        ca.issue(&host("c.example.com")).unwrap();
+        assert!(Arc::ptr_eq(&a, &ca.issue(&host("a.example.com")).unwrap()));
+        assert!(!Arc::ptr_eq(&b, &ca.issue(&host("b.example.com")).unwrap()));
+
+        let ip = ca.issue(&Host::Address([127, 0, 0, 1].into())).unwrap();
+        let params = CertificateParams::from_ca_cert_der(&ip.cert_der().into()).unwrap();
+        assert!(matches!(
+            params.subject_alt_names.as_slice(),
+            [SanType::IpAddress(ip)] if *ip == std::net::IpAddr::from([127, 0, 0, 1])
+        ));
+        assert_eq!(params.key_usages, vec![KeyUsagePurpose::DigitalSignature]);
+    }
+
+    #[test]
+    fn test_issue_mirrored() {
+        let ca = CertificateAuthority::generate("rama test CA").unwrap();
+
+        let upstream_key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).unwrap();
+        let mut upstream_params =
+            CertificateParams::new(vec!["*.example.com".to_owned(), "example.com".to_owned()])
+                .unwrap();
+        upstream_params
+            .distinguished_name
+            .push(DnType::OrganizationName, "Example Inc.");
+        let upstream = upstream_params.self_signed(&upstream_key).unwrap();
+
+        let cert = ca
+            .issue_mirrored(&host("www.example.com"), upstream.der())
+            .unwrap();
+        let params = CertificateParams::from_ca_cert_der(&cert.cert_der().into()).unwrap();
+        assert!(matches!(
+            params.distinguished_name.get(&DnType::OrganizationName),
+            Some(rcgen::DnValue::Utf8String(org)) if org == "Example Inc."
+        ));
+        assert_eq!(params.subject_alt_names.len(), 2);
+        assert_eq!(params.is_ca, IsCa::ExplicitNoCa);
+
+        let cert = ca
+            .issue_mirrored(&host("other.internal"), upstream.der())
+            .unwrap();
+        let params = CertificateParams::from_ca_cert_der(&cert.cert_der().into()).unwrap();
+        assert_eq!(params.subject_alt_names.len(), 3);
+    }
+
+    #[test]
+    fn test_from_pem_key_mismatch() {
+        let ca = CertificateAuthority::generate("rama test CA").unwrap();
+        let other = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).unwrap();
+        CertificateAuthority::from_pem(&ca.cert_pem(), &other.serialize_pem()).unwrap_err();
+    }
+
+    #[test]
+    fn test_from_pem() {
+        let ca = CertificateAuthority::generate("rama test CA").unwrap();
+        let key_pem = pem::encode(&pem::Pem::new("PRIVATE KEY", ca.issuer.key.serialize_der()));
+        let loaded = CertificateAuthority::from_pem(&ca.cert_pem(), &key_pem).unwrap();
+        assert_eq!(loaded.cert_der(), ca.cert_der());
+        loaded.issue(&host("example.com")).unwrap();
+    }
+}
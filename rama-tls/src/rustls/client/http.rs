@@ -1,12 +1,14 @@
 use crate::rustls::dep::pki_types::ServerName;
-use crate::rustls::dep::rustls::ClientConfig;
-use crate::rustls::dep::rustls::RootCertStore;
+use crate::rustls::dep::rustls::{self, crypto::CryptoProvider, ClientConfig, RootCertStore};
 use crate::rustls::dep::tokio_rustls::{client::TlsStream, TlsConnector};
 use crate::rustls::verify::NoServerCertVerifier;
-use crate::types::HttpsTunnel;
+use crate::types::{
+    client::{ClientHelloEmulation, NegotiatedTlsParameters},
+    ApplicationProtocol, HttpsTunnel, ProtocolVersion,
+};
 use pin_project_lite::pin_project;
 use private::{ConnectorKindAuto, ConnectorKindSecure, ConnectorKindTunnel};
-use rama_core::error::{BoxError, ErrorExt, OpaqueError};
+use rama_core::error::{BoxError, ErrorContext, ErrorExt, OpaqueError};
 use rama_core::{Context, Layer, Service};
 use rama_http_types::Version;
 use rama_net::client::{ConnectorService, EstablishedClientConnection};
@@ -194,7 +196,7 @@ impl<S> HttpsConnector<S, ConnectorKindTunnel> {
     }
 }
 
-/// this way we do not need a hacky macro... however is there a way to do this without needing to hacK?!?!
+// this way we do not need a hacky macro... however is there a way to do this without needing to hacK?!?!
 
 impl<S, State, Request> Service<State, Request> for HttpsConnector<S, ConnectorKindAuto>
 where
@@ -219,6 +221,7 @@ where
             addr,
         } = self.inner.connect(ctx, req).await.map_err(Into::into)?;
 
+        let emulation = ctx.get::<ClientHelloEmulation>().cloned();
         let transport_ctx = ctx
             .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
             .map_err(|err| {
@@ -259,7 +262,7 @@ where
         );
 
         let stream = self
-            .handshake(domain, transport_ctx.http_version, conn)
+            .handshake(domain, transport_ctx.http_version, emulation, conn)
             .await?;
 
         tracing::trace!(
@@ -268,6 +271,7 @@ where
             http_version = ?transport_ctx.http_version,
             "HttpsConnector(auto): protocol secure, established tls connection",
         );
+        ctx.insert(negotiated_tls_parameters(&stream));
         Ok(EstablishedClientConnection {
             ctx,
             req,
@@ -302,6 +306,7 @@ where
             addr,
         } = self.inner.connect(ctx, req).await.map_err(Into::into)?;
 
+        let emulation = ctx.get::<ClientHelloEmulation>().cloned();
         let transport_ctx = ctx
             .get_or_try_insert_with_ctx(|ctx| req.try_ref_into_transport_ctx(ctx))
             .map_err(|err| {
@@ -321,8 +326,9 @@ where
             .to_owned();
 
         let conn = self
-            .handshake(domain, transport_ctx.http_version, conn)
+            .handshake(domain, transport_ctx.http_version, emulation, conn)
             .await?;
+        ctx.insert(negotiated_tls_parameters(&conn));
 
         Ok(EstablishedClientConnection {
             ctx,
@@ -348,7 +354,7 @@ where
         req: Request,
     ) -> Result<Self::Response, Self::Error> {
         let EstablishedClientConnection {
-            ctx,
+            mut ctx,
             req,
             conn,
             addr,
@@ -373,7 +379,9 @@ where
             }
         };
 
-        let conn = self.handshake(domain, None, conn).await?;
+        let emulation = ctx.get::<ClientHelloEmulation>().cloned();
+        let conn = self.handshake(domain, None, emulation, conn).await?;
+        ctx.insert(negotiated_tls_parameters(&conn));
 
         tracing::trace!("HttpsConnector(tunnel): connection secured");
         Ok(EstablishedClientConnection {
@@ -392,15 +400,21 @@ impl<S, K> HttpsConnector<S, K> {
         &self,
         server_name: ServerName<'static>,
         http_version: Option<Version>,
+        emulation: Option<ClientHelloEmulation>,
         stream: T,
     ) -> Result<TlsStream<T>, BoxError>
     where
         T: Stream + Unpin,
     {
-        let config = self
-            .config
-            .clone()
-            .unwrap_or_else(|| new_tls_client_config(http_version));
+        let config = match emulation {
+            Some(emulation) => {
+                emulate_tls_client_config(self.config.as_ref(), &emulation, http_version)?
+            }
+            None => self
+                .config
+                .clone()
+                .unwrap_or_else(|| new_tls_client_config(http_version)),
+        };
         let connector = TlsConnector::from(config);
 
         connector
@@ -410,6 +424,17 @@ impl<S, K> HttpsConnector<S, K> {
     }
 }
 
+/// Returns the [`NegotiatedTlsParameters`] of the given (established) tls connection.
+fn negotiated_tls_parameters<T>(stream: &TlsStream<T>) -> NegotiatedTlsParameters {
+    NegotiatedTlsParameters {
+        application_layer_protocol: stream
+            .get_ref()
+            .1
+            .alpn_protocol()
+            .map(ApplicationProtocol::from),
+    }
+}
+
 pin_project! {
     /// A stream which can be either a secure or a plain stream.
     pub struct AutoTlsStream<S> {
@@ -498,32 +523,132 @@ where
     }
 }
 
-fn new_tls_client_config(http_version: Option<Version>) -> Arc<ClientConfig> {
+fn root_certs() -> Arc<RootCertStore> {
     static ROOT_CERTS: OnceLock<Arc<RootCertStore>> = OnceLock::new();
-    let root_certs = ROOT_CERTS
+    ROOT_CERTS
         .get_or_init(|| {
             let mut root_storage = RootCertStore::empty();
             root_storage.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
             Arc::new(root_storage)
         })
-        .clone();
+        .clone()
+}
 
+fn alpn_protocols_for_version(http_version: Option<Version>) -> Vec<Vec<u8>> {
+    match http_version {
+        Some(Version::HTTP_11) => vec![b"http/1.1".to_vec()],
+        Some(Version::HTTP_2) => vec![b"h2".to_vec()],
+        Some(Version::HTTP_3) => vec![b"h3".to_vec()],
+        _ => vec![],
+    }
+}
+
+fn new_tls_client_config(http_version: Option<Version>) -> Arc<ClientConfig> {
     let mut config = ClientConfig::builder()
-        .with_root_certificates(root_certs)
+        .with_root_certificates(root_certs())
         .with_no_client_auth();
     config
         .dangerous()
         .set_certificate_verifier(Arc::new(NoServerCertVerifier::default()));
-    config.alpn_protocols = match http_version {
-        Some(Version::HTTP_11) => vec![b"http/1.1".to_vec()],
-        Some(Version::HTTP_2) => vec![b"h2".to_vec()],
-        Some(Version::HTTP_3) => vec![b"h3".to_vec()],
-        _ => vec![],
-    };
+    config.alpn_protocols = alpn_protocols_for_version(http_version);
 
     Arc::new(config)
 }
 
+/// Create a [`ClientConfig`] which emulates the given [`ClientHello`],
+/// as far as rustls allows it.
+///
+/// The order of the cipher suites and key exchange groups is respected,
+/// limited to the ones supported by the crypto provider, as well as the
+/// supported versions and ALPN protocols. GREASE, the order (or permutation)
+/// of the extensions and extensions not implemented by rustls cannot be emulated,
+/// for which a warning is logged.
+///
+/// In case a custom `config` is defined only the ALPN protocols are applied,
+/// as a rustls config cannot be derived using another crypto provider.
+fn emulate_tls_client_config(
+    config: Option<&Arc<ClientConfig>>,
+    emulation: &ClientHelloEmulation,
+    http_version: Option<Version>,
+) -> Result<Arc<ClientConfig>, OpaqueError> {
+    let hello = emulation.client_hello();
+    tracing::warn!(
+        permute_extensions = emulation.permute_extensions(),
+        "rustls tls client cannot emulate the extension order nor GREASE of the client hello"
+    );
+
+    let alpn_protocols = match hello.ext_alpn() {
+        Some(alpns) if !alpns.is_empty() => {
+            alpns.iter().map(|alpn| alpn.as_bytes().to_vec()).collect()
+        }
+        _ => alpn_protocols_for_version(http_version),
+    };
+
+    if let Some(config) = config {
+        let mut config = ClientConfig::clone(config);
+        config.alpn_protocols = alpn_protocols;
+        return Ok(Arc::new(config));
+    }
+
+    let default_config = new_tls_client_config(http_version);
+    let mut provider = CryptoProvider::clone(default_config.crypto_provider());
+
+    let cipher_suites: Vec<_> = hello
+        .cipher_suites()
+        .iter()
+        .filter_map(|cs| {
+            provider
+                .cipher_suites
+                .iter()
+                .find(|suite| u16::from(suite.suite()) == u16::from(*cs))
+                .copied()
+        })
+        .collect();
+    if !cipher_suites.is_empty() {
+        provider.cipher_suites = cipher_suites;
+    }
+
+    let kx_groups: Vec<_> = hello
+        .ext_supported_groups()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|group| {
+            provider
+                .kx_groups
+                .iter()
+                .find(|kx| u16::from(kx.name()) == u16::from(*group))
+                .copied()
+        })
+        .collect();
+    if !kx_groups.is_empty() {
+        provider.kx_groups = kx_groups;
+    }
+
+    let mut versions = Vec::with_capacity(2);
+    for version in hello.supported_versions().unwrap_or_default() {
+        match version {
+            ProtocolVersion::TLSv1_3 => versions.push(&rustls::version::TLS13),
+            ProtocolVersion::TLSv1_2 => versions.push(&rustls::version::TLS12),
+            _ => (),
+        }
+    }
+    if versions.is_empty() {
+        versions = rustls::DEFAULT_VERSIONS.to_vec();
+    }
+
+    let mut config = ClientConfig::builder_with_provider(Arc::new(provider))
+        .with_protocol_versions(&versions)
+        .context("create rustls client config: set protocol versions")?
+        .with_root_certificates(root_certs())
+        .with_no_client_auth();
+    config
+        .dangerous()
+        .set_certificate_verifier(Arc::new(NoServerCertVerifier::default()));
+    config.alpn_protocols = alpn_protocols;
+
+    Ok(Arc::new(config))
+}
+
 mod private {
     #[derive(Debug)]
     /// A connector which can be used to establish a connection to a server
@@ -0,0 +1,191 @@
+use crate::{
+    cert_store::{CertificateStore, LoadCertificate, PemCertificate},
+    rustls::dep::{
+        pemfile,
+        pki_types::{CertificateDer, PrivateKeyDer},
+        rustls::{
+            crypto::{aws_lc_rs, CryptoProvider},
+            server::{ClientHello, ResolvesServerCert},
+            sign::CertifiedKey,
+            ServerConfig,
+        },
+    },
+    types::ApplicationProtocol,
+};
+use rama_core::error::{ErrorContext, OpaqueError};
+use std::sync::Arc;
+
+impl LoadCertificate for Arc<CertifiedKey> {
+    fn load(cert: &PemCertificate) -> Result<Self, OpaqueError> {
+        let cert_chain = pemfile::certs(&mut cert.cert_pem())
+            .collect::<Result<Vec<_>, _>>()
+            .context("parse certificate chain pem")?;
+        if cert_chain.is_empty() {
+            return Err(OpaqueError::from_display("empty certificate chain"));
+        }
+        let key = pemfile::private_key(&mut cert.key_pem())
+            .context("parse private key pem")?
+            .context("missing private key")?;
+
+        let certified_key = certified_key(cert_chain, key)?;
+        certified_key
+            .keys_match()
+            .context("private key does not match certificate")?;
+        Ok(certified_key)
+    }
+}
+
+/// The process-wide default crypto provider,
+/// or the aws-lc-rs crypto provider in case no default provider is installed.
+///
+/// Used to create server configs, as the rustls builder panics in case
+/// multiple crypto providers are enabled while none is installed as default.
+pub(crate) fn crypto_provider() -> Arc<CryptoProvider> {
+    CryptoProvider::get_default()
+        .cloned()
+        .unwrap_or_else(|| Arc::new(aws_lc_rs::default_provider()))
+}
+
+/// Create a [`CertifiedKey`] using the [`crypto_provider`].
+pub(crate) fn certified_key(
+    cert_chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+) -> Result<Arc<CertifiedKey>, OpaqueError> {
+    let key = crypto_provider()
+        .key_provider
+        .load_private_key(key)
+        .context("load private key")?;
+    Ok(Arc::new(CertifiedKey::new(cert_chain, key)))
+}
+
+impl CertificateStore<Arc<CertifiedKey>> {
+    /// Create a [`ServerConfig`] resolving its certificates using this [`CertificateStore`],
+    /// such that new handshakes are served using the reloaded certificates.
+    pub fn server_config(&self, alpn_protocols: &[ApplicationProtocol]) -> Arc<ServerConfig> {
+        // as done by `ServerConfig::builder`, for the provider used explicitly instead
+        let mut config = ServerConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()
+            .expect("crypto provider supports the safe default protocol versions")
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(self.clone()));
+        config.alpn_protocols = alpn_protocols
+            .iter()
+            .map(|protocol| protocol.as_bytes().to_vec())
+            .collect();
+        Arc::new(config)
+    }
+}
+
+impl ResolvesServerCert for CertificateStore<Arc<CertifiedKey>> {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        self.get(client_hello.server_name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cert_store::CertificateFiles,
+        rustls::dep::{
+            pki_types::ServerName,
+            rustls::{ClientConfig, RootCertStore},
+            tokio_rustls::{TlsAcceptor, TlsConnector},
+        },
+    };
+    use rcgen::{CertificateParams, KeyPair, PKCS_ECDSA_P256_SHA256};
+    use std::path::Path;
+
+    fn generate(name: &str) -> (String, String) {
+        let key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).unwrap();
+        let cert = CertificateParams::new(vec![name.to_owned()])
+            .unwrap()
+            .self_signed(&key)
+            .unwrap();
+        (cert.pem(), key.serialize_pem())
+    }
+
+    async fn handshake(acceptor: &TlsAcceptor, cert_pem: &str) -> Result<(), std::io::Error> {
+        let mut roots = RootCertStore::empty();
+        for cert in pemfile::certs(&mut cert_pem.as_bytes()) {
+            roots.add(cert.unwrap()).unwrap();
+        }
+        let client_config = ClientConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let connector = TlsConnector::from(Arc::new(client_config));
+
+        let (client, server) = tokio::io::duplex(16 * 1024);
+        let server_name = ServerName::try_from("example.com").unwrap();
+        let (server, client) = tokio::join!(
+            acceptor.accept(server),
+            connector.connect(server_name, client)
+        );
+        server?;
+        client.map(|_| ())
+    }
+
+    fn write(path: &Path, content: &str) {
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_certificate_store_server_config() {
+        let dir =
+            std::env::temp_dir().join(format!("rama-tls-rustls-cert-store-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let cert_path = dir.join("example.com.crt.pem");
+        let key_path = dir.join("example.com.key.pem");
+
+        let (first_cert, first_key) = generate("example.com");
+        write(&cert_path, &first_cert);
+        write(&key_path, &first_key);
+        let store = CertificateStore::<Arc<CertifiedKey>>::load(CertificateFiles::directory(&dir))
+            .await
+            .unwrap();
+        let acceptor = TlsAcceptor::from(store.server_config(&[]));
+        handshake(&acceptor, &first_cert).await.unwrap();
+
+        // a key which does not match its certificate is refused
+        let (second_cert, second_key) = generate("example.com");
+        write(&cert_path, &second_cert);
+        store.reload().await.unwrap_err();
+        handshake(&acceptor, &first_cert).await.unwrap();
+
+        // once both are replaced the new certificate is served by the same acceptor
+        write(&key_path, &second_key);
+        store.reload().await.unwrap();
+        handshake(&acceptor, &second_cert).await.unwrap();
+        handshake(&acceptor, &first_cert).await.unwrap_err();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "rustls-ring")]
+    #[tokio::test]
+    async fn test_certificate_store_server_config_multiple_providers() {
+        // both the aws-lc-rs and ring crypto providers are enabled, without a default installed
+        assert!(CryptoProvider::get_default().is_none());
+
+        let dir = std::env::temp_dir().join(format!(
+            "rama-tls-rustls-cert-store-providers-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let (cert, key) = generate("example.com");
+        write(&dir.join("example.com.crt.pem"), &cert);
+        write(&dir.join("example.com.key.pem"), &key);
+
+        let store = CertificateStore::<Arc<CertifiedKey>>::load(CertificateFiles::directory(&dir))
+            .await
+            .unwrap();
+        let acceptor = TlsAcceptor::from(store.server_config(&[]));
+        handshake(&acceptor, &cert).await.unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
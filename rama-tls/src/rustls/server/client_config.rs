@@ -1,4 +1,6 @@
-use crate::{rustls::dep::rustls::ServerConfig, types::client::ClientHello};
+use crate::{
+    mitm::UpstreamCertificate, rustls::dep::rustls::ServerConfig, types::client::ClientHello,
+};
 use std::{fmt, future::Future, sync::Arc};
 
 /// A handler that allows you to define what to do with the client config,
@@ -55,6 +57,19 @@ pub trait ServerConfigProvider: Send + Sync + 'static {
         &self,
         client_hello: ClientHello,
     ) -> impl Future<Output = Result<Option<Arc<ServerConfig>>, std::io::Error>> + Send + '_;
+
+    /// Same as [`ServerConfigProvider::get_server_config`], but used instead
+    /// in case an [`UpstreamCertificate`] is found in the `Context` of the acceptor.
+    ///
+    /// By default the [`UpstreamCertificate`] is ignored.
+    fn get_server_config_for_upstream(
+        &self,
+        client_hello: ClientHello,
+        upstream: UpstreamCertificate,
+    ) -> impl Future<Output = Result<Option<Arc<ServerConfig>>, std::io::Error>> + Send + '_ {
+        let _ = upstream;
+        self.get_server_config(client_hello)
+    }
 }
 
 impl<F, Fut> ServerConfigProvider for F
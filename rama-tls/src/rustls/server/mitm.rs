@@ -0,0 +1,278 @@
+use super::{crypto_provider, ServerConfigProvider};
+use crate::{
+    mitm::{CertificateAuthority, IssuedCertificate, UpstreamCertificate},
+    rustls::dep::{
+        pki_types::{CertificateDer, PrivatePkcs8KeyDer},
+        rustls::ServerConfig,
+    },
+    types::{client::ClientHello, ApplicationProtocol},
+};
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_net::address::Host;
+use std::{future::Future, io, sync::Arc};
+
+#[derive(Debug, Clone)]
+/// A [`ServerConfigProvider`] which provides a [`ServerConfig`]
+/// with a leaf certificate issued by a [`CertificateAuthority`]
+/// for the server name (SNI) requested by the client.
+///
+/// In case an [`UpstreamCertificate`] is found in the `Context` of the acceptor,
+/// the issued leaf certificate mirrors it, see [`CertificateAuthority::issue_mirrored`].
+///
+/// Clients which do not request a server name are served
+/// using the default [`ServerConfig`] of the acceptor,
+/// which can be created using [`MitmServerConfigProvider::server_config_for`].
+pub struct MitmServerConfigProvider {
+    authority: CertificateAuthority,
+    alpn_protocols: Vec<ApplicationProtocol>,
+}
+
+impl MitmServerConfigProvider {
+    /// Create a new [`MitmServerConfigProvider`],
+    /// issuing the leaf certificates using the given [`CertificateAuthority`].
+    pub fn new(authority: CertificateAuthority) -> Self {
+        Self {
+            authority,
+            alpn_protocols: Vec::new(),
+        }
+    }
+
+    /// Set the ALPN protocols supported by the service's inner application service.
+    pub fn with_alpn_protocols(mut self, protocols: Vec<ApplicationProtocol>) -> Self {
+        self.alpn_protocols = protocols;
+        self
+    }
+
+    /// Set the ALPN protocols supported by the service's inner application service.
+    pub fn set_alpn_protocols(&mut self, protocols: Vec<ApplicationProtocol>) -> &mut Self {
+        self.alpn_protocols = protocols;
+        self
+    }
+
+    /// The [`CertificateAuthority`] issuing the leaf certificates.
+    pub fn authority(&self) -> &CertificateAuthority {
+        &self.authority
+    }
+
+    /// Create a [`ServerConfig`] serving the given [`IssuedCertificate`],
+    /// e.g. one which mirrors the certificate of the upstream server.
+    ///
+    /// The [`ServerConfig`] is cached together with the [`IssuedCertificate`],
+    /// such that it is only created once for each certificate issued.
+    pub fn server_config(
+        &self,
+        cert: &IssuedCertificate,
+    ) -> Result<Arc<ServerConfig>, OpaqueError> {
+        let alpn_protocols: Vec<_> = self
+            .alpn_protocols
+            .iter()
+            .map(|protocol| protocol.as_bytes().to_vec())
+            .collect();
+        if let Some(config) = cert.rustls_server_config.get() {
+            // providers sharing the authority can support other ALPN protocols
+            if config.alpn_protocols == alpn_protocols {
+                return Ok(config.clone());
+            }
+        }
+
+        let cert_chain = vec![
+            CertificateDer::from(cert.cert_der().to_vec()),
+            CertificateDer::from(self.authority.cert_der().to_vec()),
+        ];
+        let key_der = PrivatePkcs8KeyDer::from(cert.key_der().to_vec());
+
+        let mut config = ServerConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()
+            .context("create mitm rustls server config: protocol versions")?
+            .with_no_client_auth()
+            .with_single_cert(cert_chain, key_der.into())
+            .context("create mitm rustls server config")?;
+        config.alpn_protocols = alpn_protocols;
+        let config = Arc::new(config);
+        let _ = cert.rustls_server_config.set(config.clone());
+        Ok(config)
+    }
+
+    /// Create a [`ServerConfig`] serving a leaf certificate issued for the given [`Host`].
+    pub fn server_config_for(&self, host: &Host) -> Result<Arc<ServerConfig>, OpaqueError> {
+        let cert = self.authority.issue(host)?;
+        self.server_config(&cert)
+    }
+}
+
+impl ServerConfigProvider for MitmServerConfigProvider {
+    fn get_server_config(
+        &self,
+        client_hello: ClientHello,
+    ) -> impl Future<Output = Result<Option<Arc<ServerConfig>>, io::Error>> + Send + '_ {
+        let result = match client_hello.ext_server_name() {
+            Some(domain) => self
+                .server_config_for(&Host::Name(domain.clone()))
+                .map(Some)
+                .map_err(io::Error::other),
+            None => Ok(None),
+        };
+        std::future::ready(result)
+    }
+
+    fn get_server_config_for_upstream(
+        &self,
+        client_hello: ClientHello,
+        upstream: UpstreamCertificate,
+    ) -> impl Future<Output = Result<Option<Arc<ServerConfig>>, io::Error>> + Send + '_ {
+        let result = match client_hello.ext_server_name() {
+            Some(domain) => self
+                .authority
+                .issue_mirrored(&Host::Name(domain.clone()), upstream.cert_der())
+                .and_then(|cert| self.server_config(&cert))
+                .map(Some)
+                .map_err(io::Error::other),
+            None => Ok(None),
+        };
+        std::future::ready(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rustls::{
+        dep::{
+            pki_types::ServerName,
+            rustls::{ClientConfig, RootCertStore},
+            tokio_rustls::{server::TlsStream, TlsConnector},
+        },
+        server::{TlsAcceptorLayer, TlsClientConfigHandler},
+    };
+    use rama_core::{service::service_fn, Context, Layer, Service};
+    use rcgen::{CertificateParams, DnType, KeyPair, PKCS_ECDSA_P256_SHA256};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    #[tokio::test]
+    async fn test_mitm_server_config_provider() {
+        let authority = CertificateAuthority::generate("rama test CA").unwrap();
+        let provider = MitmServerConfigProvider::new(authority.clone())
+            .with_alpn_protocols(vec![ApplicationProtocol::HTTP_11]);
+        let default_config = provider
+            .server_config_for(&Host::Address([127, 0, 0, 1].into()))
+            .unwrap();
+        let acceptor = TlsAcceptorLayer::with_client_config_handler(
+            default_config,
+            TlsClientConfigHandler::default().server_config_provider(provider),
+        )
+        .layer(service_fn(
+            |mut stream: TlsStream<DuplexStream>| async move {
+                stream.write_all(b"hello").await?;
+                stream.shutdown().await?;
+                Ok::<_, io::Error>(())
+            },
+        ));
+
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from(authority.cert_der().to_vec()))
+            .unwrap();
+        let mut client_config = ClientConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        client_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        let connector = TlsConnector::from(Arc::new(client_config));
+
+        for server_name in ["example.com", "www.example.internal", "127.0.0.1"] {
+            let (client, server) = tokio::io::duplex(16 * 1024);
+            let server = tokio::spawn({
+                let acceptor = acceptor.clone();
+                async move { acceptor.serve(Context::default(), server).await }
+            });
+
+            let mut stream = connector
+                .connect(ServerName::try_from(server_name).unwrap(), client)
+                .await
+                .unwrap();
+            assert_eq!(stream.get_ref().1.alpn_protocol(), Some(&b"http/1.1"[..]));
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).await.unwrap();
+            assert_eq!(buf, b"hello");
+            server.await.unwrap().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_mitm_server_config_cached() {
+        let authority = CertificateAuthority::generate("rama test CA").unwrap();
+        let provider = MitmServerConfigProvider::new(authority.clone())
+            .with_alpn_protocols(vec![ApplicationProtocol::HTTP_11]);
+        let host = Host::Address([127, 0, 0, 1].into());
+
+        let config = provider.server_config_for(&host).unwrap();
+        assert!(Arc::ptr_eq(
+            &config,
+            &provider.server_config_for(&host).unwrap()
+        ));
+
+        // a provider supporting other ALPN protocols does not use the cached config
+        let other = MitmServerConfigProvider::new(authority)
+            .with_alpn_protocols(vec![ApplicationProtocol::HTTP_2]);
+        let other_config = other.server_config_for(&host).unwrap();
+        assert_eq!(other_config.alpn_protocols, vec![b"h2".to_vec()]);
+        assert_eq!(config.alpn_protocols, vec![b"http/1.1".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn test_mitm_server_config_provider_upstream() {
+        let authority = CertificateAuthority::generate("rama test CA").unwrap();
+        let provider = MitmServerConfigProvider::new(authority.clone());
+        let default_config = provider
+            .server_config_for(&Host::Address([127, 0, 0, 1].into()))
+            .unwrap();
+        let acceptor = TlsAcceptorLayer::with_client_config_handler(
+            default_config,
+            TlsClientConfigHandler::default().server_config_provider(provider),
+        )
+        .layer(service_fn(
+            |mut stream: TlsStream<DuplexStream>| async move {
+                stream.shutdown().await?;
+                Ok::<_, io::Error>(())
+            },
+        ));
+
+        let upstream_key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).unwrap();
+        let mut upstream_params = CertificateParams::new(vec!["example.com".to_owned()]).unwrap();
+        upstream_params
+            .distinguished_name
+            .push(DnType::OrganizationName, "Example Inc.");
+        let upstream = upstream_params.self_signed(&upstream_key).unwrap();
+
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from(authority.cert_der().to_vec()))
+            .unwrap();
+        let client_config = ClientConfig::builder_with_provider(crypto_provider())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let connector = TlsConnector::from(Arc::new(client_config));
+
+        let (client, server) = tokio::io::duplex(16 * 1024);
+        let mut ctx = Context::default();
+        ctx.insert(UpstreamCertificate::new(upstream.der().to_vec()));
+        let server = tokio::spawn(async move { acceptor.serve(ctx, server).await });
+
+        let mut stream = connector
+            .connect(ServerName::try_from("example.com").unwrap(), client)
+            .await
+            .unwrap();
+        let leaf = &stream.get_ref().1.peer_certificates().unwrap()[0];
+        let params = CertificateParams::from_ca_cert_der(leaf).unwrap();
+        assert!(matches!(
+            params.distinguished_name.get(&DnType::OrganizationName),
+            Some(rcgen::DnValue::Utf8String(org)) if org == "Example Inc."
+        ));
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
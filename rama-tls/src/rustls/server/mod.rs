@@ -24,3 +24,10 @@ pub use client_config::{ServerConfigProvider, TlsClientConfigHandler};
 mod layer;
 #[doc(inline)]
 pub use layer::TlsAcceptorLayer;
+
+mod mitm;
+#[doc(inline)]
+pub use mitm::MitmServerConfigProvider;
+
+mod cert_store;
+pub(crate) use cert_store::{certified_key, crypto_provider};
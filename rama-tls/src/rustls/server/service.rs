@@ -1,4 +1,5 @@
 use crate::{
+    mitm::UpstreamCertificate,
     rustls::dep::{
         rustls::{server::Acceptor, ServerConfig},
         tokio_rustls::{server::TlsStream, LazyConfigAcceptor, TlsAcceptor},
@@ -138,12 +139,16 @@ where
             SecureTransport::default()
         };
 
-        let config = self
-            .client_config_handler
-            .server_config_provider
-            .get_server_config(accepted_client_hello)
-            .await?
-            .unwrap_or_else(|| self.config.clone());
+        let provider = &self.client_config_handler.server_config_provider;
+        let config = match ctx.get::<UpstreamCertificate>().cloned() {
+            Some(upstream) => {
+                provider
+                    .get_server_config_for_upstream(accepted_client_hello, upstream)
+                    .await?
+            }
+            None => provider.get_server_config(accepted_client_hello).await?,
+        }
+        .unwrap_or_else(|| self.config.clone());
 
         let stream = start.into_stream(config).await?;
 
@@ -43,6 +43,11 @@
 //! 3. otherwise match the [`DeviceKind`] using [`UserAgent::device`].
 //! 4. final fallback is to find emulation data for [`DeviceKind::Desktop`].
 //!
+//! The [`UserAgentDatabase`] implements this lookup order for the [`UserAgentProfile`]s it contains,
+//! with [`UserAgentDatabase::embedded`] providing profiles for the latest Chromium, Firefox and Safari
+//! releases on the platforms they run on. These profiles are used by the `UserAgentEmulateLayer`
+//! (see `rama-http`) to emulate the http and tls behaviour of a [`UserAgent`].
+//!
 //! Please open an [issue](https://github.com/plabayo/rama/issues) in case you need support for more User Agents,
 //! and have a good case to make for it. For example we might also support the default user agents used by mobile
 //! application SDKs. This makes however only sense if we can provide Http and Tls emulation for it.
@@ -101,6 +106,10 @@ pub use info::{
 mod parse;
 use parse::parse_http_user_agent_header;
 
+pub mod profile;
+#[doc(inline)]
+pub use profile::{FingerprintRecord, UserAgentDatabase, UserAgentProfile};
+
 /// Information that can be used to overwrite the [`UserAgent`] of an http request.
 ///
 /// Used by the `UserAgentClassifier` (see `rama-http`) to overwrite the specified
@@ -0,0 +1,347 @@
+//! Profiles embedded in rama, captured from the stable browser releases
+//! for the platforms they are available on.
+
+use super::{Http1Profile, Http2Profile, HttpProfile, UserAgentProfile};
+use rama_net::tls::{
+    client::{ClientHello, ClientHelloExtension},
+    ApplicationProtocol, CipherSuite, CompressionAlgorithm, ECPointFormat, ExtensionId,
+    ProtocolVersion, SignatureScheme, SupportedGroup,
+};
+use std::sync::Arc;
+
+/// Returns all embedded [`UserAgentProfile`]s.
+pub(super) fn profiles() -> Vec<UserAgentProfile> {
+    let chromium_tls = Arc::new(chromium_client_hello());
+    let firefox_tls = Arc::new(firefox_client_hello());
+    let safari_tls = Arc::new(safari_client_hello());
+
+    let mut profiles = Vec::new();
+
+    for (platform, os, mobile) in [
+        ("\"Windows\"", "Windows NT 10.0; Win64; x64", false),
+        ("\"macOS\"", "Macintosh; Intel Mac OS X 10_15_7", false),
+        ("\"Linux\"", "X11; Linux x86_64", false),
+        ("\"Android\"", "Linux; Android 10; K", true),
+    ] {
+        let ua_str = format!(
+            "Mozilla/5.0 ({os}) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 {}Safari/537.36",
+            if mobile { "Mobile " } else { "" },
+        );
+        profiles.push(UserAgentProfile {
+            http: chromium_http_profile(&ua_str, platform, mobile),
+            tls: Some(chromium_tls.clone()),
+            tls_permute_extensions: true,
+            ua_str,
+        });
+    }
+
+    for os in [
+        "Windows NT 10.0; Win64; x64; rv:125.0",
+        "Macintosh; Intel Mac OS X 10.15; rv:125.0",
+        "X11; Linux x86_64; rv:125.0",
+    ] {
+        let ua_str = format!("Mozilla/5.0 ({os}) Gecko/20100101 Firefox/125.0");
+        profiles.push(UserAgentProfile {
+            http: firefox_http_profile(&ua_str),
+            tls: Some(firefox_tls.clone()),
+            tls_permute_extensions: false,
+            ua_str,
+        });
+    }
+    let ua_str = "Mozilla/5.0 (Android 14; Mobile; rv:125.0) Gecko/125.0 Firefox/125.0".to_owned();
+    profiles.push(UserAgentProfile {
+        http: firefox_http_profile(&ua_str),
+        tls: Some(firefox_tls),
+        tls_permute_extensions: false,
+        ua_str,
+    });
+
+    for ua_str in [
+        "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Safari/605.1.15",
+        "Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Mobile/15E148 Safari/604.1",
+    ] {
+        profiles.push(UserAgentProfile {
+            http: safari_http_profile(ua_str),
+            tls: Some(safari_tls.clone()),
+            tls_permute_extensions: false,
+            ua_str: ua_str.to_owned(),
+        });
+    }
+
+    profiles
+}
+
+const GREASE: u16 = 0x0a0a;
+
+fn chromium_client_hello() -> ClientHello {
+    client_hello(
+        &[
+            GREASE, 0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f, 0xc02c, 0xc030, 0xcca9, 0xcca8, 0xc013,
+            0xc014, 0x009c, 0x009d, 0x002f, 0x0035,
+        ],
+        vec![
+            opaque(GREASE),
+            ClientHelloExtension::ServerName(None),
+            opaque(0x0017),
+            opaque(0xff01),
+            supported_groups(&[GREASE, 0x6399, 0x001d, 0x0017, 0x0018]),
+            ClientHelloExtension::ECPointFormats(vec![ECPointFormat::Uncompressed]),
+            opaque(0x0023),
+            alpn(),
+            opaque(0x0005),
+            signature_algorithms(&[
+                0x0403, 0x0804, 0x0401, 0x0503, 0x0805, 0x0501, 0x0806, 0x0601,
+            ]),
+            opaque(0x0012),
+            opaque(0x0033),
+            opaque(0x002d),
+            supported_versions(&[GREASE, 0x0304, 0x0303]),
+            opaque(0x001b),
+            opaque(0x4469),
+            opaque(0xfe0d),
+            opaque(GREASE),
+            opaque(0x0015),
+        ],
+    )
+}
+
+fn firefox_client_hello() -> ClientHello {
+    client_hello(
+        &[
+            0x1301, 0x1303, 0x1302, 0xc02b, 0xc02f, 0xcca9, 0xcca8, 0xc02c, 0xc030, 0xc00a, 0xc009,
+            0xc013, 0xc014, 0x009c, 0x009d, 0x002f, 0x0035,
+        ],
+        vec![
+            ClientHelloExtension::ServerName(None),
+            opaque(0x0017),
+            opaque(0xff01),
+            supported_groups(&[0x001d, 0x0017, 0x0018, 0x0019, 0x0100, 0x0101]),
+            ClientHelloExtension::ECPointFormats(vec![ECPointFormat::Uncompressed]),
+            opaque(0x0023),
+            alpn(),
+            opaque(0x0005),
+            opaque(0x0022),
+            opaque(0x0033),
+            supported_versions(&[0x0304, 0x0303]),
+            signature_algorithms(&[
+                0x0403, 0x0503, 0x0603, 0x0804, 0x0805, 0x0806, 0x0401, 0x0501, 0x0601, 0x0203,
+                0x0201,
+            ]),
+            opaque(0x001c),
+            opaque(0x001b),
+            opaque(0xfe0d),
+        ],
+    )
+}
+
+fn safari_client_hello() -> ClientHello {
+    client_hello(
+        &[
+            GREASE, 0x1301, 0x1302, 0x1303, 0xc02c, 0xc02b, 0xcca9, 0xc030, 0xc02f, 0xcca8, 0xc00a,
+            0xc009, 0xc014, 0xc013, 0x009d, 0x009c, 0x0035, 0x002f, 0xc008, 0xc012, 0x000a,
+        ],
+        vec![
+            opaque(GREASE),
+            ClientHelloExtension::ServerName(None),
+            opaque(0x0017),
+            opaque(0xff01),
+            supported_groups(&[GREASE, 0x001d, 0x0017, 0x0018, 0x0019]),
+            ClientHelloExtension::ECPointFormats(vec![ECPointFormat::Uncompressed]),
+            alpn(),
+            opaque(0x0005),
+            signature_algorithms(&[
+                0x0403, 0x0804, 0x0401, 0x0503, 0x0203, 0x0805, 0x0501, 0x0806, 0x0601, 0x0201,
+            ]),
+            opaque(0x0012),
+            opaque(0x0033),
+            opaque(0x002d),
+            supported_versions(&[GREASE, 0x0304, 0x0303, 0x0302, 0x0301]),
+            opaque(0x001b),
+            opaque(GREASE),
+            opaque(0x0015),
+        ],
+    )
+}
+
+fn chromium_http_profile(ua_str: &str, platform: &str, mobile: bool) -> HttpProfile {
+    let sec_ch_ua = r#""Chromium";v="124", "Google Chrome";v="124", "Not-A.Brand";v="99""#;
+    let mobile = if mobile { "?1" } else { "?0" };
+    let accept = "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/apng,*/*;q=0.8,application/signed-exchange;v=b3;q=0.7";
+
+    HttpProfile {
+        h1: Http1Profile {
+            headers: headers(&[
+                ("Host", ""),
+                ("Connection", "keep-alive"),
+                ("sec-ch-ua", sec_ch_ua),
+                ("sec-ch-ua-mobile", mobile),
+                ("sec-ch-ua-platform", platform),
+                ("Upgrade-Insecure-Requests", "1"),
+                ("User-Agent", ua_str),
+                ("Accept", accept),
+                ("Sec-Fetch-Site", "none"),
+                ("Sec-Fetch-Mode", "navigate"),
+                ("Sec-Fetch-User", "?1"),
+                ("Sec-Fetch-Dest", "document"),
+                ("Accept-Encoding", "gzip, deflate, br, zstd"),
+                ("Accept-Language", "en-US,en;q=0.9"),
+            ]),
+        },
+        h2: Http2Profile {
+            headers: headers(&[
+                ("sec-ch-ua", sec_ch_ua),
+                ("sec-ch-ua-mobile", mobile),
+                ("sec-ch-ua-platform", platform),
+                ("upgrade-insecure-requests", "1"),
+                ("user-agent", ua_str),
+                ("accept", accept),
+                ("sec-fetch-site", "none"),
+                ("sec-fetch-mode", "navigate"),
+                ("sec-fetch-user", "?1"),
+                ("sec-fetch-dest", "document"),
+                ("accept-encoding", "gzip, deflate, br, zstd"),
+                ("accept-language", "en-US,en;q=0.9"),
+                ("priority", "u=0, i"),
+            ]),
+            settings: vec![(1, 65536), (2, 0), (4, 6291456), (6, 262144)],
+            window_update: Some(15663105),
+            pseudo_headers: pseudo_headers(&[":method", ":authority", ":scheme", ":path"]),
+        },
+    }
+}
+
+fn firefox_http_profile(ua_str: &str) -> HttpProfile {
+    let accept =
+        "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8";
+
+    HttpProfile {
+        h1: Http1Profile {
+            headers: headers(&[
+                ("Host", ""),
+                ("User-Agent", ua_str),
+                ("Accept", accept),
+                ("Accept-Language", "en-US,en;q=0.5"),
+                ("Accept-Encoding", "gzip, deflate, br"),
+                ("Connection", "keep-alive"),
+                ("Upgrade-Insecure-Requests", "1"),
+                ("Sec-Fetch-Dest", "document"),
+                ("Sec-Fetch-Mode", "navigate"),
+                ("Sec-Fetch-Site", "none"),
+                ("Sec-Fetch-User", "?1"),
+            ]),
+        },
+        h2: Http2Profile {
+            headers: headers(&[
+                ("user-agent", ua_str),
+                ("accept", accept),
+                ("accept-language", "en-US,en;q=0.5"),
+                ("accept-encoding", "gzip, deflate, br"),
+                ("upgrade-insecure-requests", "1"),
+                ("sec-fetch-dest", "document"),
+                ("sec-fetch-mode", "navigate"),
+                ("sec-fetch-site", "none"),
+                ("sec-fetch-user", "?1"),
+                ("priority", "u=0, i"),
+                ("te", "trailers"),
+            ]),
+            settings: vec![(1, 65536), (4, 131072), (5, 16384)],
+            window_update: Some(12517377),
+            pseudo_headers: pseudo_headers(&[":method", ":path", ":authority", ":scheme"]),
+        },
+    }
+}
+
+fn safari_http_profile(ua_str: &str) -> HttpProfile {
+    let accept = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+
+    HttpProfile {
+        h1: Http1Profile {
+            headers: headers(&[
+                ("Host", ""),
+                ("Accept", accept),
+                ("Sec-Fetch-Site", "none"),
+                ("Accept-Encoding", "gzip, deflate, br"),
+                ("Sec-Fetch-Mode", "navigate"),
+                ("User-Agent", ua_str),
+                ("Accept-Language", "en-US,en;q=0.9"),
+                ("Sec-Fetch-Dest", "document"),
+                ("Connection", "keep-alive"),
+            ]),
+        },
+        h2: Http2Profile {
+            headers: headers(&[
+                ("accept", accept),
+                ("sec-fetch-site", "none"),
+                ("accept-encoding", "gzip, deflate, br"),
+                ("sec-fetch-mode", "navigate"),
+                ("user-agent", ua_str),
+                ("accept-language", "en-US,en;q=0.9"),
+                ("sec-fetch-dest", "document"),
+                ("priority", "u=0, i"),
+            ]),
+            settings: vec![(2, 0), (4, 4194304), (3, 100)],
+            window_update: Some(10485760),
+            pseudo_headers: pseudo_headers(&[":method", ":scheme", ":path", ":authority"]),
+        },
+    }
+}
+
+fn client_hello(cipher_suites: &[u16], extensions: Vec<ClientHelloExtension>) -> ClientHello {
+    ClientHello::new(
+        ProtocolVersion::TLSv1_2,
+        cipher_suites
+            .iter()
+            .copied()
+            .map(CipherSuite::from)
+            .collect(),
+        vec![CompressionAlgorithm::Null],
+        extensions,
+    )
+}
+
+fn opaque(id: u16) -> ClientHelloExtension {
+    ClientHelloExtension::Opaque {
+        id: ExtensionId::from(id),
+        data: vec![],
+    }
+}
+
+fn alpn() -> ClientHelloExtension {
+    ClientHelloExtension::ApplicationLayerProtocolNegotiation(vec![
+        ApplicationProtocol::HTTP_2,
+        ApplicationProtocol::HTTP_11,
+    ])
+}
+
+fn supported_groups(groups: &[u16]) -> ClientHelloExtension {
+    ClientHelloExtension::SupportedGroups(
+        groups.iter().copied().map(SupportedGroup::from).collect(),
+    )
+}
+
+fn signature_algorithms(schemes: &[u16]) -> ClientHelloExtension {
+    ClientHelloExtension::SignatureAlgorithms(
+        schemes.iter().copied().map(SignatureScheme::from).collect(),
+    )
+}
+
+fn supported_versions(versions: &[u16]) -> ClientHelloExtension {
+    ClientHelloExtension::SupportedVersions(
+        versions
+            .iter()
+            .copied()
+            .map(ProtocolVersion::from)
+            .collect(),
+    )
+}
+
+fn headers(headers: &[(&str, &str)]) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| ((*name).to_owned(), (*value).to_owned()))
+        .collect()
+}
+
+fn pseudo_headers(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| (*name).to_owned()).collect()
+}
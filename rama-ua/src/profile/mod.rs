@@ -0,0 +1,401 @@
+//! User Agent emulation profiles.
+//!
+//! A [`UserAgentProfile`] describes how a specific [`UserAgent`] behaves on the wire:
+//! the tls [`ClientHello`] it sends, the http/2 settings it advertises
+//! and the default headers (in order and casing) it attaches to its requests.
+//!
+//! Profiles are stored in a [`UserAgentDatabase`], which can be used
+//! to find the best matching profile for a [`UserAgent`],
+//! following the lookup order documented in [the crate level documentation](crate).
+//!
+//! Besides the embedded profiles, a database can also be built from
+//! [`FingerprintRecord`]s, as collected and exported by the `rama fp` service.
+
+use crate::{DeviceKind, HttpAgent, PlatformKind, UserAgent, UserAgentInfo, UserAgentKind};
+use rama_net::tls::client::ClientHello;
+use std::{collections::HashMap, sync::Arc};
+
+mod embedded;
+
+mod record;
+#[doc(inline)]
+pub use record::{parse_records, FingerprintRecord, Http2FingerprintRecord};
+
+#[derive(Debug, Clone)]
+/// The emulation profile of a [`UserAgent`],
+/// describing both its http and tls behaviour.
+pub struct UserAgentProfile {
+    /// The `User-Agent` header value of the profile.
+    pub ua_str: String,
+    /// The http behaviour of the [`UserAgent`].
+    pub http: HttpProfile,
+    /// The tls [`ClientHello`] sent by the [`UserAgent`], if known.
+    ///
+    /// Shared so that connections established for the same profile
+    /// can be identified (and thus reused) as such.
+    pub tls: Option<Arc<ClientHello>>,
+    /// Whether the [`UserAgent`] randomly permutes the order of its tls extensions
+    /// for every connection, as Chromium does, instead of using the order of its [`ClientHello`].
+    pub tls_permute_extensions: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+/// The http behaviour of a [`UserAgent`], per http version.
+pub struct HttpProfile {
+    /// The behaviour of the [`UserAgent`] for http/1.1 requests.
+    pub h1: Http1Profile,
+    /// The behaviour of the [`UserAgent`] for http/2 requests.
+    pub h2: Http2Profile,
+}
+
+#[derive(Debug, Clone, Default)]
+/// The http/1.1 behaviour of a [`UserAgent`].
+pub struct Http1Profile {
+    /// The default headers sent by the [`UserAgent`],
+    /// in order and using their original casing.
+    ///
+    /// Headers with an empty value only define the position of that header,
+    /// e.g. for the `Host` header as its value depends on the request.
+    pub headers: Vec<(String, String)>,
+}
+
+impl Http1Profile {
+    /// Returns `true` in case the [`UserAgent`] sends its headers in Title-Case,
+    /// as opposed to lowercase.
+    pub fn title_case_headers(&self) -> bool {
+        self.headers
+            .iter()
+            .any(|(name, _)| name.starts_with(|c: char| c.is_ascii_uppercase()))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// The http/2 behaviour of a [`UserAgent`].
+pub struct Http2Profile {
+    /// The default headers sent by the [`UserAgent`], in order.
+    ///
+    /// Headers with an empty value only define the position of that header.
+    pub headers: Vec<(String, String)>,
+    /// The (id, value) pairs of the initial SETTINGS frame, in order.
+    pub settings: Vec<(u16, u32)>,
+    /// The window size increment of the connection level WINDOW_UPDATE frame,
+    /// sent right after the initial SETTINGS frame, if any.
+    pub window_update: Option<u32>,
+    /// The order of the pseudo headers, e.g. `[":method", ":authority", ":scheme", ":path"]`.
+    pub pseudo_headers: Vec<String>,
+}
+
+impl Http2Profile {
+    /// Returns the value of the setting with the given id, if defined.
+    pub fn setting(&self, id: u16) -> Option<u32> {
+        self.settings
+            .iter()
+            .find_map(|(setting_id, value)| (*setting_id == id).then_some(*value))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// A database of [`UserAgentProfile`]s, used to find
+/// the profile to emulate for a given [`UserAgent`].
+///
+/// Use [`UserAgentDatabase::embedded`] for the profiles shipped with rama,
+/// covering the latest Chromium, Firefox and Safari releases for the platforms they run on.
+pub struct UserAgentDatabase {
+    entries: Vec<Entry>,
+    by_ua_str: HashMap<String, usize>,
+    by_info: HashMap<UserAgentInfo, Vec<usize>>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    profile: Arc<UserAgentProfile>,
+    kind: Option<UserAgentKind>,
+    version: Option<usize>,
+    platform: Option<PlatformKind>,
+    device: DeviceKind,
+}
+
+impl UserAgentDatabase {
+    /// Create a new empty [`UserAgentDatabase`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new [`UserAgentDatabase`] containing the profiles embedded in rama.
+    pub fn embedded() -> Self {
+        let mut db = Self::new();
+        for profile in embedded::profiles() {
+            db.insert(profile);
+        }
+        db
+    }
+
+    /// Create a new [`UserAgentDatabase`] from the given [`FingerprintRecord`]s.
+    ///
+    /// Records are merged into a single [`UserAgentProfile`] per `User-Agent` header value.
+    pub fn from_records(records: impl IntoIterator<Item = FingerprintRecord>) -> Self {
+        let mut db = Self::new();
+        db.insert_records(records);
+        db
+    }
+
+    /// Insert the profiles built from the given [`FingerprintRecord`]s in the database,
+    /// replacing the existing profiles with the same `User-Agent` header value, if any.
+    pub fn insert_records(&mut self, records: impl IntoIterator<Item = FingerprintRecord>) {
+        for profile in record::profiles_from_records(records) {
+            self.insert(profile);
+        }
+    }
+
+    /// Insert a [`UserAgentProfile`] in the database,
+    /// replacing the existing profile with the same `User-Agent` header value, if any.
+    pub fn insert(&mut self, profile: UserAgentProfile) {
+        let ua = UserAgent::new(profile.ua_str.clone());
+        let info = ua.info();
+        let entry = Entry {
+            kind: info.as_ref().map(|info| info.kind),
+            version: info.as_ref().and_then(|info| info.version),
+            platform: ua.platform(),
+            device: ua.device(),
+            profile: Arc::new(profile),
+        };
+        match self.by_ua_str.get(ua.header_str()) {
+            Some(index) => self.entries[*index] = entry,
+            None => {
+                let index = self.entries.len();
+                self.by_ua_str.insert(ua.header_str().to_owned(), index);
+                if let Some(info) = info {
+                    self.by_info.entry(info).or_default().push(index);
+                }
+                self.entries.push(entry);
+            }
+        }
+    }
+
+    /// Returns the number of profiles in the database.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` in case the database contains no profiles.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over all profiles in the database, in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<UserAgentProfile>> {
+        self.entries.iter().map(|entry| &entry.profile)
+    }
+
+    /// Iterate over all profiles matching the given [`UserAgentInfo`] exactly.
+    pub fn get_by_info(
+        &self,
+        info: &UserAgentInfo,
+    ) -> impl Iterator<Item = &Arc<UserAgentProfile>> {
+        self.by_info
+            .get(info)
+            .into_iter()
+            .flatten()
+            .map(|index| &self.entries[*index].profile)
+    }
+
+    /// Find the [`UserAgentProfile`] to emulate for the given [`UserAgent`].
+    ///
+    /// The lookup order is:
+    ///
+    /// 1. the profile with the exact same [`UserAgent::header_str`];
+    /// 2. the profile with the same [`UserAgentKind`] and [`PlatformKind`],
+    ///    with the version closest to the one of the [`UserAgent`];
+    /// 3. a profile for the same [`DeviceKind`],
+    ///    preferring the one matching the [`HttpAgent`] of the [`UserAgent`];
+    /// 4. a profile for [`DeviceKind::Desktop`].
+    pub fn get(&self, ua: &UserAgent) -> Option<&Arc<UserAgentProfile>> {
+        if let Some(index) = self.by_ua_str.get(ua.header_str()) {
+            return Some(&self.entries[*index].profile);
+        }
+
+        if let Some(info) = ua.info() {
+            if let Some(profile) = self.find(info.kind, ua.platform(), info.version) {
+                return Some(profile);
+            }
+        }
+
+        let device = ua.device();
+        let kind = user_agent_kind_for_http_agent(&ua.http_agent());
+        self.entries
+            .iter()
+            .find(|entry| entry.device == device && kind.is_some() && entry.kind == kind)
+            .or_else(|| self.entries.iter().find(|entry| entry.device == device))
+            .or_else(|| {
+                self.entries
+                    .iter()
+                    .find(|entry| entry.device == DeviceKind::Desktop)
+            })
+            .map(|entry| &entry.profile)
+    }
+
+    /// Find the [`UserAgentProfile`] for the given [`UserAgentKind`],
+    /// on the given [`PlatformKind`] (if any), with the version closest
+    /// to the given version, or the latest version in case none is given.
+    pub fn find(
+        &self,
+        kind: UserAgentKind,
+        platform: Option<PlatformKind>,
+        version: Option<usize>,
+    ) -> Option<&Arc<UserAgentProfile>> {
+        if let Some(indices) = version.and_then(|version| {
+            self.by_info.get(&UserAgentInfo {
+                kind,
+                version: Some(version),
+            })
+        }) {
+            if let Some(entry) = indices
+                .iter()
+                .map(|index| &self.entries[*index])
+                .find(|entry| platform.is_none() || entry.platform == platform)
+            {
+                return Some(&entry.profile);
+            }
+        }
+
+        self.entries
+            .iter()
+            .filter(|entry| entry.kind == Some(kind))
+            .filter(|entry| platform.is_none() || entry.platform == platform)
+            .min_by_key(|entry| {
+                let distance = match (version, entry.version) {
+                    (Some(version), Some(entry_version)) => version.abs_diff(entry_version),
+                    _ => usize::MAX,
+                };
+                (distance, std::cmp::Reverse(entry.version))
+            })
+            .map(|entry| &entry.profile)
+    }
+}
+
+/// Returns the [`UserAgentKind`] implementing the given [`HttpAgent`],
+/// or `None` in case the http agent is to be preserved.
+pub fn user_agent_kind_for_http_agent(agent: &HttpAgent) -> Option<UserAgentKind> {
+    match agent {
+        HttpAgent::Chromium => Some(UserAgentKind::Chromium),
+        HttpAgent::Firefox => Some(UserAgentKind::Firefox),
+        HttpAgent::Safari => Some(UserAgentKind::Safari),
+        HttpAgent::Preserve => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_profiles_are_classified() {
+        let db = UserAgentDatabase::embedded();
+        assert!(!db.is_empty());
+        for entry in &db.entries {
+            assert!(entry.kind.is_some(), "{}", entry.profile.ua_str);
+            assert!(entry.version.is_some(), "{}", entry.profile.ua_str);
+            assert!(entry.platform.is_some(), "{}", entry.profile.ua_str);
+            assert!(entry.profile.tls.is_some(), "{}", entry.profile.ua_str);
+            assert!(!entry.profile.http.h1.headers.is_empty());
+            assert!(!entry.profile.http.h2.headers.is_empty());
+            assert_eq!(entry.profile.http.h2.pseudo_headers.len(), 4);
+        }
+    }
+
+    #[test]
+    fn test_get_exact_match() {
+        let db = UserAgentDatabase::embedded();
+        let profile = db.iter().nth(2).unwrap().clone();
+        let found = db.get(&UserAgent::new(profile.ua_str.clone())).unwrap();
+        assert!(Arc::ptr_eq(&profile, found));
+    }
+
+    #[test]
+    fn test_get_by_kind_and_platform() {
+        let db = UserAgentDatabase::embedded();
+
+        let ua = UserAgent::new(
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:110.0) Gecko/20100101 Firefox/110.0",
+        );
+        let found = UserAgent::new(db.get(&ua).unwrap().ua_str.clone());
+        assert_eq!(found.info().unwrap().kind, UserAgentKind::Firefox);
+        assert_eq!(found.platform(), Some(PlatformKind::Windows));
+
+        let ua = UserAgent::new("Mozilla/5.0 (iPhone; CPU iPhone OS 16_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.0 Mobile/15E148 Safari/604.1");
+        let found = UserAgent::new(db.get(&ua).unwrap().ua_str.clone());
+        assert_eq!(found.info().unwrap().kind, UserAgentKind::Safari);
+        assert_eq!(found.platform(), Some(PlatformKind::IOS));
+    }
+
+    #[test]
+    fn test_get_closest_version() {
+        let mut db = UserAgentDatabase::new();
+        for version in [100, 120, 124] {
+            db.insert(UserAgentProfile {
+                ua_str: format!("Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/{version}.0.0.0 Safari/537.36"),
+                http: HttpProfile::default(),
+                tls: None,
+                tls_permute_extensions: false,
+            });
+        }
+        assert_eq!(db.len(), 3);
+
+        for (version, expected) in [(99, 100), (118, 120), (123, 124), (130, 124)] {
+            let ua = UserAgent::new(format!("Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/{version}.0.0.0 Safari/537.36"));
+            let found = UserAgent::new(db.get(&ua).unwrap().ua_str.clone());
+            assert_eq!(found.info().unwrap().version, Some(expected), "{version}");
+        }
+
+        let found = db.find(UserAgentKind::Chromium, None, None).unwrap();
+        assert!(found.ua_str.contains("Chrome/124"));
+    }
+
+    #[test]
+    fn test_get_device_fallback() {
+        let db = UserAgentDatabase::embedded();
+
+        let mut ua = UserAgent::new("Mozilla/5.0 (Mobile) Unknown");
+        ua.with_http_agent(HttpAgent::Firefox);
+        let found = UserAgent::new(db.get(&ua).unwrap().ua_str.clone());
+        assert_eq!(found.device(), DeviceKind::Mobile);
+        assert_eq!(found.info().unwrap().kind, UserAgentKind::Firefox);
+
+        let found = UserAgent::new(db.get(&UserAgent::new("curl/8.0")).unwrap().ua_str.clone());
+        assert_eq!(found.device(), DeviceKind::Desktop);
+
+        assert!(UserAgentDatabase::new()
+            .get(&UserAgent::new("curl/8.0"))
+            .is_none());
+    }
+
+    #[test]
+    fn test_insert_replaces_same_ua() {
+        let mut db = UserAgentDatabase::new();
+        for _ in 0..2 {
+            db.insert(UserAgentProfile {
+                ua_str: "curl/8.0".to_owned(),
+                http: HttpProfile::default(),
+                tls: None,
+                tls_permute_extensions: false,
+            });
+        }
+        assert_eq!(db.len(), 1);
+    }
+
+    #[test]
+    fn test_http_profile_helpers() {
+        let h1 = Http1Profile {
+            headers: vec![("Host".to_owned(), String::new())],
+        };
+        assert!(h1.title_case_headers());
+        assert!(!Http1Profile::default().title_case_headers());
+
+        let h2 = Http2Profile {
+            settings: vec![(1, 65536), (4, 6291456)],
+            ..Default::default()
+        };
+        assert_eq!(h2.setting(4), Some(6291456));
+        assert_eq!(h2.setting(5), None);
+    }
+}
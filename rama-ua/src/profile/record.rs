@@ -0,0 +1,339 @@
+use super::{Http1Profile, HttpProfile, UserAgentProfile};
+use crate::{UserAgent, UserAgentKind};
+use rama_core::error::{ErrorContext, OpaqueError};
+use rama_net::tls::client::ClientHello;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A fingerprint of a single request made by a [`UserAgent`],
+/// as collected by the `rama fp` service.
+///
+/// Records can be exported as JSON (array) or as line-delimited JSON (NDJSON),
+/// and loaded again using [`parse_records`] and [`UserAgentDatabase::from_records`],
+/// in order to use them as emulation data.
+///
+/// [`UserAgent`]: crate::UserAgent
+/// [`UserAgentDatabase::from_records`]: super::UserAgentDatabase::from_records
+pub struct FingerprintRecord {
+    /// The `User-Agent` header value of the request.
+    pub user_agent: String,
+    /// The http version of the request, e.g. `HTTP/1.1` or `HTTP/2.0`.
+    pub http_version: String,
+    /// The fetch mode of the request (e.g. `navigate` or `cors`), if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetch_mode: Option<String>,
+    /// The headers of the request, in the order as received.
+    pub headers: Vec<(String, String)>,
+    /// The http/2 fingerprint of the connection, for http/2 requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub h2: Option<Http2FingerprintRecord>,
+    /// The tls [`ClientHello`] of the connection, for requests received over tls.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<ClientHello>,
+}
+
+impl FingerprintRecord {
+    /// Remove the headers which are specific to the recorded request,
+    /// such as cookies and credentials, which are not to be stored nor emulated.
+    pub fn strip_request_specific_headers(&mut self) -> &mut Self {
+        self.headers
+            .retain(|(name, _)| !is_request_specific_header(name));
+        self
+    }
+
+    /// Returns `true` in case the record is of an http/2 request.
+    pub fn is_h2(&self) -> bool {
+        self.http_version.starts_with("HTTP/2")
+    }
+
+    fn is_navigation(&self) -> bool {
+        self.fetch_mode
+            .as_deref()
+            .map_or(true, |mode| mode.eq_ignore_ascii_case("navigate"))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/// The http/2 connection fingerprint of a [`FingerprintRecord`].
+pub struct Http2FingerprintRecord {
+    /// The (id, value) pairs of the initial SETTINGS frame, in order.
+    pub settings: Vec<(u16, u32)>,
+    /// The window size increment of the connection level WINDOW_UPDATE frame, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_update: Option<u32>,
+    /// The order of the pseudo headers, e.g. `[":method", ":authority", ":scheme", ":path"]`.
+    pub pseudo_headers: Vec<String>,
+}
+
+/// Parse [`FingerprintRecord`]s, either formatted as a JSON array
+/// or as line-delimited JSON (NDJSON), one record per line.
+pub fn parse_records(input: &str) -> Result<Vec<FingerprintRecord>, OpaqueError> {
+    let input = input.trim_start();
+    if input.starts_with('[') {
+        return serde_json::from_str(input).context("parse fingerprint records as json array");
+    }
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("parse fingerprint record at line {}", index + 1))
+        })
+        .collect()
+}
+
+/// Headers which are specific to the request they were recorded for,
+/// and should therefore not be emulated.
+const REQUEST_SPECIFIC_HEADERS: &[&str] = &[
+    "cookie",
+    "authorization",
+    "proxy-authorization",
+    "referer",
+    "origin",
+    "content-length",
+    "content-type",
+];
+
+/// Merge the given records into [`UserAgentProfile`]s, one per `User-Agent`,
+/// in order of first appearance.
+///
+/// Records of navigation requests are preferred for the default headers,
+/// as these are the most complete.
+pub(super) fn profiles_from_records(
+    records: impl IntoIterator<Item = FingerprintRecord>,
+) -> Vec<UserAgentProfile> {
+    let mut builders: Vec<ProfileBuilder> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+
+    for record in records {
+        let builder = match index.get(&record.user_agent) {
+            Some(i) => &mut builders[*i],
+            None => {
+                index.insert(record.user_agent.clone(), builders.len());
+                builders.push(ProfileBuilder::new(record.user_agent.clone()));
+                builders.last_mut().expect("just pushed")
+            }
+        };
+        builder.add(record);
+    }
+
+    builders.into_iter().map(ProfileBuilder::build).collect()
+}
+
+#[derive(Debug)]
+struct ProfileBuilder {
+    profile: UserAgentProfile,
+    h1_navigation: bool,
+    h2_navigation: bool,
+}
+
+impl ProfileBuilder {
+    fn new(ua_str: String) -> Self {
+        Self {
+            profile: UserAgentProfile {
+                tls_permute_extensions: UserAgent::new(ua_str.as_str())
+                    .info()
+                    .is_some_and(|info| info.kind == UserAgentKind::Chromium),
+                ua_str,
+                http: HttpProfile::default(),
+                tls: None,
+            },
+            h1_navigation: false,
+            h2_navigation: false,
+        }
+    }
+
+    fn add(&mut self, record: FingerprintRecord) {
+        let navigation = record.is_navigation();
+        let headers = sanitize_headers(&record.headers);
+
+        if record.is_h2() {
+            let h2 = &mut self.profile.http.h2;
+            if h2.headers.is_empty() || (navigation && !self.h2_navigation) {
+                h2.headers = headers;
+                self.h2_navigation = navigation;
+            }
+            if let Some(fingerprint) = record.h2 {
+                h2.settings = fingerprint.settings;
+                h2.window_update = fingerprint.window_update;
+                h2.pseudo_headers = fingerprint.pseudo_headers;
+            }
+        } else {
+            let h1 = &mut self.profile.http.h1;
+            if h1.headers.is_empty() || (navigation && !self.h1_navigation) {
+                *h1 = Http1Profile { headers };
+                self.h1_navigation = navigation;
+            }
+        }
+
+        if self.profile.tls.is_none() {
+            self.profile.tls = record.tls.map(Arc::new);
+        }
+    }
+
+    fn build(self) -> UserAgentProfile {
+        self.profile
+    }
+}
+
+fn sanitize_headers(headers: &[(String, String)]) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter(|(name, _)| !is_request_specific_header(name))
+        .map(|(name, value)| {
+            if name.eq_ignore_ascii_case("host") {
+                (name.clone(), String::new())
+            } else {
+                (name.clone(), value.clone())
+            }
+        })
+        .collect()
+}
+
+fn is_request_specific_header(name: &str) -> bool {
+    REQUEST_SPECIFIC_HEADERS
+        .iter()
+        .any(|header| name.eq_ignore_ascii_case(header))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{UserAgent, UserAgentDatabase, UserAgentInfo, UserAgentKind};
+    use rama_net::tls::{client::ClientHelloExtension, CipherSuite, ProtocolVersion};
+
+    const UA: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:126.0) Gecko/20100101 Firefox/126.0";
+
+    fn record(http_version: &str, fetch_mode: &str, headers: &[(&str, &str)]) -> FingerprintRecord {
+        FingerprintRecord {
+            user_agent: UA.to_owned(),
+            http_version: http_version.to_owned(),
+            fetch_mode: Some(fetch_mode.to_owned()),
+            headers: headers
+                .iter()
+                .map(|(name, value)| ((*name).to_owned(), (*value).to_owned()))
+                .collect(),
+            h2: None,
+            tls: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_records_json_and_ndjson() {
+        let mut h2 = record("HTTP/2.0", "navigate", &[("user-agent", UA)]);
+        h2.h2 = Some(Http2FingerprintRecord {
+            settings: vec![(1, 65536), (4, 131072)],
+            window_update: Some(12517377),
+            pseudo_headers: vec![":method".to_owned(), ":path".to_owned()],
+        });
+        h2.tls = Some(ClientHello::new(
+            ProtocolVersion::TLSv1_2,
+            vec![CipherSuite::from(0x1301)],
+            vec![],
+            vec![ClientHelloExtension::ServerName(None)],
+        ));
+        let records = vec![record("HTTP/1.1", "navigate", &[("Host", "a")]), h2];
+
+        let json = serde_json::to_string(&records).unwrap();
+        let ndjson = records
+            .iter()
+            .map(|record| serde_json::to_string(record).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        for input in [json, ndjson] {
+            let parsed = parse_records(&input).unwrap();
+            assert_eq!(parsed.len(), 2);
+            assert!(!parsed[0].is_h2());
+            assert!(parsed[1].is_h2());
+            assert_eq!(parsed[1].h2.as_ref().unwrap().window_update, Some(12517377));
+            let tls = parsed[1].tls.as_ref().unwrap();
+            assert_eq!(
+                tls.cipher_suites(),
+                &[CipherSuite::TLS13_AES_128_GCM_SHA256]
+            );
+        }
+
+        assert!(parse_records("{\"foo\": 1}").is_err());
+        assert!(parse_records("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_database_from_records() {
+        let mut h2 = record(
+            "HTTP/2.0",
+            "navigate",
+            &[("user-agent", UA), ("cookie", "a=b"), ("accept", "*/*")],
+        );
+        h2.h2 = Some(Http2FingerprintRecord {
+            settings: vec![(4, 131072)],
+            window_update: None,
+            pseudo_headers: vec![],
+        });
+        let records = vec![
+            record(
+                "HTTP/1.1",
+                "cors",
+                &[("Host", "example.com"), ("Accept", "*/*")],
+            ),
+            record(
+                "HTTP/1.1",
+                "navigate",
+                &[("Host", "example.com"), ("User-Agent", UA)],
+            ),
+            record("HTTP/1.1", "cors", &[("Accept", "application/json")]),
+            h2,
+        ];
+
+        let db = UserAgentDatabase::from_records(records);
+        assert_eq!(db.len(), 1);
+
+        let profile = db.get(&UserAgent::new(UA)).unwrap();
+        assert_eq!(
+            profile.http.h1.headers,
+            vec![
+                ("Host".to_owned(), String::new()),
+                ("User-Agent".to_owned(), UA.to_owned())
+            ]
+        );
+        assert_eq!(
+            profile.http.h2.headers,
+            vec![
+                ("user-agent".to_owned(), UA.to_owned()),
+                ("accept".to_owned(), "*/*".to_owned())
+            ]
+        );
+        assert_eq!(profile.http.h2.setting(4), Some(131072));
+
+        let info = UserAgentInfo {
+            kind: UserAgentKind::Firefox,
+            version: Some(126),
+        };
+        assert_eq!(db.get_by_info(&info).count(), 1);
+    }
+
+    #[test]
+    fn test_strip_request_specific_headers() {
+        let mut record = record(
+            "HTTP/1.1",
+            "navigate",
+            &[
+                ("Host", "example.com"),
+                ("Cookie", "session=secret"),
+                ("Authorization", "Bearer secret"),
+                ("Proxy-Authorization", "Basic secret"),
+                ("Accept", "*/*"),
+            ],
+        );
+        record.strip_request_specific_headers();
+        assert_eq!(
+            record.headers,
+            vec![
+                ("Host".to_owned(), "example.com".to_owned()),
+                ("Accept".to_owned(), "*/*".to_owned())
+            ]
+        );
+    }
+}
@@ -53,5 +53,8 @@
 #![cfg_attr(test, allow(clippy::float_cmp))]
 #![cfg_attr(not(test), warn(clippy::print_stdout, clippy::dbg_macro))]
 
-/// TODO
-pub const PLACEHOLDER: bool = true;
+mod socket;
+#[doc(inline)]
+pub use socket::{UdpSocket, UdpSocketBuilder};
+
+pub mod server;
@@ -0,0 +1,115 @@
+use bytes::Bytes;
+use rama_net::stream::Socket;
+use std::{
+    fmt, io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+use tokio::sync::mpsc;
+
+use crate::UdpSocket;
+
+/// A datagram flow between a single peer and the local [`UdpSocket`],
+/// served by the [`UdpListener`].
+///
+/// The flow ends (and [`UdpFlow::recv`] returns `None`) once the flow
+/// has been idle for too long or the listener is shut down.
+///
+/// [`UdpListener`]: super::UdpListener
+pub struct UdpFlow {
+    socket: Arc<UdpSocket>,
+    peer_addr: SocketAddr,
+    rx: mpsc::Receiver<Bytes>,
+    activity: Arc<FlowActivity>,
+}
+
+impl fmt::Debug for UdpFlow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpFlow")
+            .field("socket", &self.socket)
+            .field("peer_addr", &self.peer_addr)
+            .finish()
+    }
+}
+
+impl UdpFlow {
+    pub(super) fn new(
+        socket: Arc<UdpSocket>,
+        peer_addr: SocketAddr,
+        rx: mpsc::Receiver<Bytes>,
+        activity: Arc<FlowActivity>,
+    ) -> Self {
+        Self {
+            socket,
+            peer_addr,
+            rx,
+            activity,
+        }
+    }
+
+    /// Returns the address of the peer of this flow.
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
+    /// Returns the local address of the socket this flow is served on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Receive the next datagram sent by the peer,
+    /// returning `None` in case the flow has ended.
+    pub async fn recv(&mut self) -> Option<Bytes> {
+        self.rx.recv().await
+    }
+
+    /// Send a datagram to the peer,
+    /// returning the number of bytes written.
+    pub async fn send(&self, data: &[u8]) -> io::Result<usize> {
+        let n = self.socket.send_to(data, self.peer_addr).await?;
+        self.activity.touch();
+        Ok(n)
+    }
+}
+
+impl Socket for UdpFlow {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.peer_addr)
+    }
+}
+
+/// Tracks the last activity of a [`UdpFlow`],
+/// as the number of milliseconds since the start of the listener.
+#[derive(Debug)]
+pub(super) struct FlowActivity {
+    epoch: Instant,
+    last: AtomicU64,
+}
+
+impl FlowActivity {
+    pub(super) fn new(epoch: Instant) -> Self {
+        let activity = Self {
+            epoch,
+            last: AtomicU64::new(0),
+        };
+        activity.touch();
+        activity
+    }
+
+    pub(super) fn touch(&self) {
+        self.last
+            .store(self.epoch.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub(super) fn idle_millis(&self) -> u64 {
+        (self.epoch.elapsed().as_millis() as u64).saturating_sub(self.last.load(Ordering::Relaxed))
+    }
+}
@@ -0,0 +1,600 @@
+use super::flow::{FlowActivity, UdpFlow};
+use crate::{UdpSocket, UdpSocketBuilder};
+use bytes::Bytes;
+use rama_core::graceful::ShutdownGuard;
+use rama_core::rt::Executor;
+use rama_core::service::handler::{Factory, FromContextRequest};
+use rama_core::Context;
+use rama_core::Service;
+use rama_net::stream::SocketInfo;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{io, net::SocketAddr};
+use tokio::net::ToSocketAddrs;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_FLOW_CAPACITY: usize = 64;
+const DEFAULT_MAX_DATAGRAM_SIZE: usize = 64 * 1024;
+const DEFAULT_MAX_FLOWS: usize = 4096;
+
+/// Builder for `UdpListener`.
+pub struct UdpListenerBuilder<S> {
+    socket: UdpSocketBuilder,
+    idle_timeout: Duration,
+    flow_capacity: usize,
+    max_datagram_size: usize,
+    max_flows: usize,
+    state: Arc<S>,
+}
+
+impl<S> fmt::Debug for UdpListenerBuilder<S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpListenerBuilder")
+            .field("socket", &self.socket)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("flow_capacity", &self.flow_capacity)
+            .field("max_datagram_size", &self.max_datagram_size)
+            .field("max_flows", &self.max_flows)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl UdpListenerBuilder<()> {
+    /// Create a new `UdpListenerBuilder` without a state.
+    pub fn new() -> Self {
+        Self::with_state(())
+    }
+}
+
+impl Default for UdpListenerBuilder<()> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Clone for UdpListenerBuilder<S> {
+    fn clone(&self) -> Self {
+        Self {
+            socket: self.socket.clone(),
+            idle_timeout: self.idle_timeout,
+            flow_capacity: self.flow_capacity,
+            max_datagram_size: self.max_datagram_size,
+            max_flows: self.max_flows,
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<S> UdpListenerBuilder<S> {
+    /// Sets the value for the `IP_TTL` option on this socket.
+    ///
+    /// This value sets the time-to-live field that is used in every packet sent
+    /// from this socket.
+    pub fn ttl(&mut self, ttl: u32) -> &mut Self {
+        self.socket.ttl(ttl);
+        self
+    }
+
+    /// Sets the value of the `SO_BROADCAST` option for this socket.
+    pub fn broadcast(&mut self, on: bool) -> &mut Self {
+        self.socket.broadcast(on);
+        self
+    }
+
+    /// Sets the value of the `SO_REUSEADDR` option for this socket.
+    pub fn reuse_address(&mut self, reuse: bool) -> &mut Self {
+        self.socket.reuse_address(reuse);
+        self
+    }
+
+    /// Sets the value of the `SO_REUSEPORT` option for this socket (unix only).
+    pub fn reuse_port(&mut self, reuse: bool) -> &mut Self {
+        self.socket.reuse_port(reuse);
+        self
+    }
+
+    /// Sets the duration after which a flow without any activity expires.
+    ///
+    /// Defaults to 60 seconds.
+    pub fn idle_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum number of datagrams buffered per flow,
+    /// datagrams received while the buffer of its flow is full are dropped.
+    ///
+    /// Defaults to 64 datagrams.
+    pub fn flow_capacity(&mut self, capacity: usize) -> &mut Self {
+        self.flow_capacity = capacity.max(1);
+        self
+    }
+
+    /// Sets the maximum size of a received datagram,
+    /// larger datagrams are truncated.
+    ///
+    /// Defaults to 64 KiB.
+    pub fn max_datagram_size(&mut self, size: usize) -> &mut Self {
+        self.max_datagram_size = size;
+        self
+    }
+
+    /// Sets the maximum number of concurrent flows,
+    /// datagrams of new peers received while this limit is reached are dropped.
+    ///
+    /// Defaults to 4096 flows.
+    pub fn max_flows(&mut self, max: usize) -> &mut Self {
+        self.max_flows = max.max(1);
+        self
+    }
+}
+
+impl<S> UdpListenerBuilder<S>
+where
+    S: Send + Sync + 'static,
+{
+    /// Create a new `UdpListenerBuilder` with the given state.
+    pub fn with_state(state: S) -> Self {
+        Self {
+            socket: UdpSocketBuilder::new(),
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            flow_capacity: DEFAULT_FLOW_CAPACITY,
+            max_datagram_size: DEFAULT_MAX_DATAGRAM_SIZE,
+            max_flows: DEFAULT_MAX_FLOWS,
+            state: Arc::new(state),
+        }
+    }
+
+    /// Creates a new UdpListener, which will be bound to the specified address.
+    ///
+    /// Binding with a port number of 0 will request that the OS assigns a port
+    /// to this listener. The port allocated can be queried via the `local_addr`
+    /// method.
+    pub async fn bind<A: ToSocketAddrs>(&self, addr: A) -> io::Result<UdpListener<S>> {
+        let socket = self.socket.bind(addr).await?;
+        Ok(self.from_socket(socket))
+    }
+
+    /// Creates a new UdpListener, using the given (already bound) [`UdpSocket`].
+    ///
+    /// The socket options defined on this builder are not applied to the socket.
+    pub fn from_socket(&self, socket: UdpSocket) -> UdpListener<S> {
+        UdpListener {
+            socket: Arc::new(socket),
+            idle_timeout: self.idle_timeout,
+            flow_capacity: self.flow_capacity,
+            max_datagram_size: self.max_datagram_size,
+            max_flows: self.max_flows,
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// A UDP socket server, serving each peer as its own [`UdpFlow`]
+/// once served using one of the `serve` methods such as [`UdpListener::serve`].
+pub struct UdpListener<S> {
+    socket: Arc<UdpSocket>,
+    idle_timeout: Duration,
+    flow_capacity: usize,
+    max_datagram_size: usize,
+    max_flows: usize,
+    state: Arc<S>,
+}
+
+impl<S> fmt::Debug for UdpListener<S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpListener")
+            .field("socket", &self.socket)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("flow_capacity", &self.flow_capacity)
+            .field("max_datagram_size", &self.max_datagram_size)
+            .field("max_flows", &self.max_flows)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl UdpListener<()> {
+    /// Create a new `UdpListenerBuilder` without a state,
+    /// which can be used to configure a `UdpListener`.
+    pub fn build() -> UdpListenerBuilder<()> {
+        UdpListenerBuilder::new()
+    }
+
+    /// Create a new `UdpListenerBuilder` with the given state,
+    /// which can be used to configure a `UdpListener`.
+    pub fn build_with_state<S>(state: S) -> UdpListenerBuilder<S>
+    where
+        S: Send + Sync + 'static,
+    {
+        UdpListenerBuilder::with_state(state)
+    }
+
+    /// Creates a new UdpListener, which will be bound to the specified address.
+    ///
+    /// See [`UdpListenerBuilder::bind`] for more details.
+    pub async fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        UdpListenerBuilder::new().bind(addr).await
+    }
+}
+
+impl<S> UdpListener<S> {
+    /// Returns the local address that this listener is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Gets a reference to the listener's state.
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+}
+
+struct FlowEntry {
+    tx: mpsc::Sender<Bytes>,
+    activity: Arc<FlowActivity>,
+}
+
+impl<State> UdpListener<State>
+where
+    State: Send + Sync + 'static,
+{
+    /// Serve the flows of this listener with the given service.
+    ///
+    /// A new flow is created for each peer that sends a datagram while it has no
+    /// active flow, and is served within its own task. The [`Context`] of each flow
+    /// contains the [`SocketInfo`] of the peer. A flow ends once it has been idle
+    /// for longer than the idle timeout, or once its service returns.
+    ///
+    /// Datagrams of new peers are dropped while the maximum number of flows is active.
+    pub async fn serve<S>(self, service: S)
+    where
+        S: Service<State, UdpFlow>,
+    {
+        let ctx = Context::new(self.state.clone(), Executor::new());
+        self.serve_flows(ctx, service, std::future::pending::<()>())
+            .await
+    }
+
+    /// Serve the flows of this listener with the given service function.
+    ///
+    /// See [`Self::serve`] for more details.
+    pub async fn serve_fn<F, T, R, O, E>(self, f: F)
+    where
+        F: Factory<T, R, O, E>,
+        R: Future<Output = Result<O, E>> + Send + 'static,
+        O: Send + Sync + 'static,
+        E: Send + Sync + 'static,
+        T: FromContextRequest<State, UdpFlow>,
+    {
+        let service = rama_core::service::service_fn(f);
+        self.serve(service).await
+    }
+
+    /// Serve gracefully the flows of this listener with the given service.
+    ///
+    /// This method does the same as [`Self::serve`] but it
+    /// will respect the given [`rama_core::graceful::ShutdownGuard`], and also pass
+    /// it to the service. All active flows are ended once the shutdown is initiated.
+    pub async fn serve_graceful<S>(self, guard: ShutdownGuard, service: S)
+    where
+        S: Service<State, UdpFlow>,
+    {
+        let ctx = Context::new(self.state.clone(), Executor::graceful(guard.clone()));
+        self.serve_flows(ctx, service, guard.cancelled()).await
+    }
+
+    /// Serve gracefully the flows of this listener with the given service function.
+    ///
+    /// See [`Self::serve_graceful`] for more details.
+    pub async fn serve_fn_graceful<F, T, R, O, E>(self, guard: ShutdownGuard, service: F)
+    where
+        F: Factory<T, R, O, E>,
+        R: Future<Output = Result<O, E>> + Send + 'static,
+        O: Send + Sync + 'static,
+        E: Send + Sync + 'static,
+        T: FromContextRequest<State, UdpFlow>,
+    {
+        let service = rama_core::service::service_fn(service);
+        self.serve_graceful(guard, service).await
+    }
+
+    async fn serve_flows<S>(
+        self,
+        ctx: Context<State>,
+        service: S,
+        cancelled: impl Future<Output = ()>,
+    ) where
+        S: Service<State, UdpFlow>,
+    {
+        let service = Arc::new(service);
+        let mut cancelled = pin!(cancelled);
+
+        let local_addr = self.socket.local_addr().ok();
+        let epoch = Instant::now();
+        let idle_millis = self.idle_timeout.as_millis() as u64;
+
+        let mut flows: HashMap<SocketAddr, FlowEntry> = HashMap::new();
+        let is_active = |peer_addr: &SocketAddr, entry: &mut FlowEntry| {
+            let keep = !entry.tx.is_closed() && entry.activity.idle_millis() < idle_millis;
+            if !keep {
+                tracing::trace!(%peer_addr, "UDP flow expired or ended");
+            }
+            keep
+        };
+        let mut buf = vec![0u8; self.max_datagram_size];
+
+        let mut sweep =
+            tokio::time::interval((self.idle_timeout / 2).max(Duration::from_millis(1)));
+        sweep.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = cancelled.as_mut() => {
+                    tracing::trace!("signal received: initiate graceful shutdown");
+                    break;
+                }
+                _ = sweep.tick() => {
+                    flows.retain(is_active);
+                }
+                result = self.socket.recv_from(&mut buf) => {
+                    let (n, peer_addr) = match result {
+                        Ok(received) => received,
+                        Err(err) => {
+                            handle_recv_err(err).await;
+                            continue;
+                        }
+                    };
+
+                    let mut datagram = Bytes::copy_from_slice(&buf[..n]);
+                    if let Some(entry) = flows.get(&peer_addr) {
+                        entry.activity.touch();
+                        match entry.tx.try_send(datagram) {
+                            Ok(()) => continue,
+                            Err(TrySendError::Full(_)) => {
+                                tracing::trace!(%peer_addr, "UDP flow buffer full: drop datagram");
+                                continue;
+                            }
+                            Err(TrySendError::Closed(returned)) => {
+                                // flow ended by its service, start a new flow
+                                flows.remove(&peer_addr);
+                                datagram = returned;
+                            }
+                        }
+                    }
+
+                    if flows.len() >= self.max_flows {
+                        flows.retain(is_active);
+                        if flows.len() >= self.max_flows {
+                            tracing::trace!(%peer_addr, "UDP max flows reached: drop datagram");
+                            continue;
+                        }
+                    }
+
+                    let (tx, rx) = mpsc::channel(self.flow_capacity);
+                    let _ = tx.try_send(datagram);
+                    let activity = Arc::new(FlowActivity::new(epoch));
+                    flows.insert(peer_addr, FlowEntry { tx, activity: activity.clone() });
+
+                    let flow = UdpFlow::new(self.socket.clone(), peer_addr, rx, activity);
+                    let service = service.clone();
+                    let mut ctx = ctx.clone();
+                    ctx.insert(SocketInfo::new(local_addr, peer_addr));
+
+                    ctx.clone().spawn(async move {
+                        let _ = service.serve(ctx, flow).await;
+                    });
+                }
+            }
+        }
+    }
+}
+
+async fn handle_recv_err(err: io::Error) {
+    match err.kind() {
+        // ICMP errors of previously sent datagrams can be reported
+        // on the next receive call on some platforms, these are not fatal
+        io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionRefused => {
+            tracing::trace!(
+                error = &err as &dyn std::error::Error,
+                "UDP receive error: connect error"
+            );
+        }
+        _ => {
+            tracing::error!(error = &err as &dyn std::error::Error, "UDP receive error");
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rama_core::graceful::Shutdown;
+    use std::convert::Infallible;
+
+    async fn spawn_echo_listener(builder: UdpListenerBuilder<()>) -> SocketAddr {
+        let listener = builder.bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(listener.serve_fn(|mut flow: UdpFlow| async move {
+            while let Some(datagram) = flow.recv().await {
+                flow.send(&datagram).await.unwrap();
+            }
+            Ok::<_, Infallible>(())
+        }));
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_udp_listener_flows_per_peer() {
+        let listener = UdpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            listener.serve_fn(|ctx: Context<()>, mut flow: UdpFlow| async move {
+                let info = ctx.get::<SocketInfo>().unwrap();
+                assert_eq!(*info.peer_addr(), flow.peer_addr());
+                let mut count = 0;
+                while let Some(datagram) = flow.recv().await {
+                    count += 1;
+                    let reply = format!("{}:{count}", String::from_utf8_lossy(&datagram));
+                    flow.send(reply.as_bytes()).await.unwrap();
+                }
+                Ok::<_, Infallible>(())
+            }),
+        );
+
+        let a = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let b = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut buf = [0u8; 64];
+
+        for (socket, msg, expected) in [
+            (&a, "a", "a:1"),
+            (&b, "b", "b:1"),
+            (&a, "a", "a:2"),
+            (&a, "a", "a:3"),
+            (&b, "b", "b:2"),
+        ] {
+            socket.send_to(msg.as_bytes(), addr).await.unwrap();
+            let (n, src) = socket.recv_from(&mut buf).await.unwrap();
+            assert_eq!(src, addr);
+            assert_eq!(&buf[..n], expected.as_bytes());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_udp_listener_echo() {
+        let addr = spawn_echo_listener(UdpListener::build()).await;
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.connect(addr).await.unwrap();
+        let mut buf = [0u8; 64];
+        for msg in [&b"hello"[..], b"world"] {
+            socket.send(msg).await.unwrap();
+            let n = socket.recv(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], msg);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_udp_listener_max_flows() {
+        let addr = spawn_echo_listener(
+            UdpListener::build()
+                .max_flows(1)
+                .idle_timeout(Duration::from_millis(200))
+                .clone(),
+        )
+        .await;
+
+        let a = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let b = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut buf = [0u8; 64];
+
+        a.send_to(b"a", addr).await.unwrap();
+        let (n, _) = a.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"a");
+
+        // the datagram of a new peer is dropped while the flow of the first peer is active
+        b.send_to(b"b", addr).await.unwrap();
+        tokio::time::timeout(Duration::from_millis(100), b.recv_from(&mut buf))
+            .await
+            .unwrap_err();
+        a.send_to(b"a", addr).await.unwrap();
+        let (n, _) = a.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"a");
+
+        // once the first flow expired a new peer can start a flow
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        b.send_to(b"b", addr).await.unwrap();
+        let (n, _) = tokio::time::timeout(Duration::from_secs(5), b.recv_from(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf[..n], b"b");
+    }
+
+    #[tokio::test]
+    async fn test_udp_listener_idle_flow_expires() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let listener = UdpListener::build()
+            .idle_timeout(Duration::from_millis(50))
+            .bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(listener.serve_fn(move |mut flow: UdpFlow| {
+            let tx = tx.clone();
+            async move {
+                let mut count = 0;
+                while flow.recv().await.is_some() {
+                    count += 1;
+                }
+                tx.send(count).unwrap();
+                Ok::<_, Infallible>(())
+            }
+        }));
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.send_to(b"1", addr).await.unwrap();
+        socket.send_to(b"2", addr).await.unwrap();
+
+        let count = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(count, 2);
+
+        // a new datagram after expiry starts a new flow
+        socket.send_to(b"3", addr).await.unwrap();
+        let count = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_udp_listener_serve_graceful() {
+        let (signal_tx, signal_rx) = tokio::sync::oneshot::channel::<()>();
+        let shutdown = Shutdown::new(async move {
+            let _ = signal_rx.await;
+        });
+
+        let listener = UdpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        shutdown.spawn_task_fn(|guard| async move {
+            listener
+                .serve_fn_graceful(guard, |mut flow: UdpFlow| async move {
+                    while let Some(datagram) = flow.recv().await {
+                        flow.send(&datagram).await.unwrap();
+                    }
+                    Ok::<_, Infallible>(())
+                })
+                .await
+        });
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.send_to(b"ping", addr).await.unwrap();
+        let mut buf = [0u8; 64];
+        let (n, _) = socket.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"ping");
+
+        signal_tx.send(()).unwrap();
+        shutdown
+            .shutdown_with_limit(Duration::from_secs(5))
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,35 @@
+//! UDP server module for Rama.
+//!
+//! The [`UdpListener`] receives datagrams on a bound [`UdpSocket`],
+//! and serves each peer as its own [`UdpFlow`], similar to how
+//! a `TcpListener` serves each accepted connection.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rama_udp::server::{UdpFlow, UdpListener};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     UdpListener::bind("127.0.0.1:9000")
+//!         .await
+//!         .expect("bind UDP Listener")
+//!         .serve_fn(|mut flow: UdpFlow| async move {
+//!             while let Some(datagram) = flow.recv().await {
+//!                 flow.send(&datagram).await?;
+//!             }
+//!             Ok::<_, std::io::Error>(())
+//!         })
+//!         .await;
+//! }
+//! ```
+//!
+//! [`UdpSocket`]: crate::UdpSocket
+
+mod flow;
+#[doc(inline)]
+pub use flow::UdpFlow;
+
+mod listener;
+#[doc(inline)]
+pub use listener::{UdpListener, UdpListenerBuilder};
@@ -0,0 +1,257 @@
+use rama_net::stream::Socket;
+use socket2::{Domain, Protocol, Socket as RawSocket, Type};
+use std::{io, net::SocketAddr};
+use tokio::net::{lookup_host, ToSocketAddrs, UdpSocket as TokioUdpSocket};
+
+/// Builder for [`UdpSocket`], used to configure the socket before it is bound.
+#[derive(Debug, Clone, Default)]
+pub struct UdpSocketBuilder {
+    ttl: Option<u32>,
+    broadcast: Option<bool>,
+    reuse_address: bool,
+    reuse_port: bool,
+}
+
+impl UdpSocketBuilder {
+    /// Create a new [`UdpSocketBuilder`] with the default (OS) socket options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the value for the `IP_TTL` option on this socket.
+    ///
+    /// This value sets the time-to-live field that is used in every packet sent
+    /// from this socket.
+    pub fn ttl(&mut self, ttl: u32) -> &mut Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Sets the value of the `SO_BROADCAST` option for this socket.
+    ///
+    /// When enabled, this socket is allowed to send packets to a broadcast address.
+    pub fn broadcast(&mut self, on: bool) -> &mut Self {
+        self.broadcast = Some(on);
+        self
+    }
+
+    /// Sets the value of the `SO_REUSEADDR` option for this socket.
+    ///
+    /// Allows the socket to bind to an address which is already in use.
+    pub fn reuse_address(&mut self, reuse: bool) -> &mut Self {
+        self.reuse_address = reuse;
+        self
+    }
+
+    /// Sets the value of the `SO_REUSEPORT` option for this socket.
+    ///
+    /// Allows multiple sockets to bind to the same port, load balancing
+    /// the incoming datagrams between them. Only supported on unix platforms,
+    /// and ignored on other platforms.
+    pub fn reuse_port(&mut self, reuse: bool) -> &mut Self {
+        self.reuse_port = reuse;
+        self
+    }
+
+    /// Creates a new [`UdpSocket`], which will be bound to the specified address.
+    ///
+    /// In case the address resolves to multiple addresses,
+    /// the first address the socket can be bound to is used.
+    pub async fn bind<A: ToSocketAddrs>(&self, addr: A) -> io::Result<UdpSocket> {
+        let mut last_err = None;
+        for addr in lookup_host(addr).await? {
+            match self.bind_addr(addr) {
+                Ok(socket) => return Ok(socket),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any address",
+            )
+        }))
+    }
+
+    fn bind_addr(&self, addr: SocketAddr) -> io::Result<UdpSocket> {
+        let socket = RawSocket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+
+        if self.reuse_address {
+            socket.set_reuse_address(true)?;
+        }
+        #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+        if self.reuse_port {
+            socket.set_reuse_port(true)?;
+        }
+        if let Some(broadcast) = self.broadcast {
+            socket.set_broadcast(broadcast)?;
+        }
+        if let Some(ttl) = self.ttl {
+            socket.set_ttl(ttl)?;
+        }
+
+        socket.set_nonblocking(true)?;
+        socket.bind(&addr.into())?;
+
+        let inner = TokioUdpSocket::from_std(socket.into())?;
+        Ok(UdpSocket { inner })
+    }
+}
+
+/// A UDP socket, used to send and receive datagrams.
+///
+/// Thin wrapper around the [`tokio::net::UdpSocket`],
+/// which can be used with or without a connected peer.
+pub struct UdpSocket {
+    inner: TokioUdpSocket,
+}
+
+impl std::fmt::Debug for UdpSocket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UdpSocket")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl UdpSocket {
+    /// Create a new [`UdpSocketBuilder`], to configure the socket before binding it.
+    pub fn build() -> UdpSocketBuilder {
+        UdpSocketBuilder::new()
+    }
+
+    /// Creates a new [`UdpSocket`], which will be bound to the specified address.
+    ///
+    /// Binding with a port number of 0 will request that the OS assigns a port
+    /// to this socket. The port allocated can be queried via the `local_addr`
+    /// method.
+    pub async fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let inner = TokioUdpSocket::bind(addr).await?;
+        Ok(Self { inner })
+    }
+
+    /// Returns the local address that this socket is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    /// Returns the address of the peer this socket is connected to.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    /// Connects the socket to the given remote address,
+    /// limiting [`Self::send`] and [`Self::recv`] to that peer.
+    pub async fn connect<A: ToSocketAddrs>(&self, addr: A) -> io::Result<()> {
+        self.inner.connect(addr).await
+    }
+
+    /// Gets the value of the `IP_TTL` option for this socket.
+    pub fn ttl(&self) -> io::Result<u32> {
+        self.inner.ttl()
+    }
+
+    /// Gets the value of the `SO_BROADCAST` option for this socket.
+    pub fn broadcast(&self) -> io::Result<bool> {
+        self.inner.broadcast()
+    }
+
+    /// Sends data on the socket to the connected peer,
+    /// returning the number of bytes written.
+    pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.send(buf).await
+    }
+
+    /// Receives a single datagram from the connected peer,
+    /// returning the number of bytes read.
+    pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.recv(buf).await
+    }
+
+    /// Sends data on the socket to the given address,
+    /// returning the number of bytes written.
+    pub async fn send_to(&self, buf: &[u8], target: SocketAddr) -> io::Result<usize> {
+        self.inner.send_to(buf, target).await
+    }
+
+    /// Receives a single datagram on the socket,
+    /// returning the number of bytes read and the origin address.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.inner.recv_from(buf).await
+    }
+
+    /// Returns a reference to the underlying [`tokio::net::UdpSocket`].
+    pub fn get_ref(&self) -> &TokioUdpSocket {
+        &self.inner
+    }
+
+    /// Consumes this [`UdpSocket`], returning the underlying [`tokio::net::UdpSocket`].
+    pub fn into_inner(self) -> TokioUdpSocket {
+        self.inner
+    }
+}
+
+impl From<TokioUdpSocket> for UdpSocket {
+    fn from(inner: TokioUdpSocket) -> Self {
+        Self { inner }
+    }
+}
+
+impl Socket for UdpSocket {
+    #[inline]
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    #[inline]
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_udp_socket_send_recv() {
+        let a = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let b = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        a.send_to(b"ping", b.local_addr().unwrap()).await.unwrap();
+        let mut buf = [0u8; 16];
+        let (n, addr) = b.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"ping");
+        assert_eq!(addr, a.local_addr().unwrap());
+
+        b.connect(addr).await.unwrap();
+        b.send(b"pong").await.unwrap();
+        let (n, addr) = a.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"pong");
+        assert_eq!(addr, b.local_addr().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_udp_socket_builder() {
+        let socket = UdpSocket::build()
+            .ttl(42)
+            .broadcast(true)
+            .reuse_address(true)
+            .bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        assert_eq!(socket.ttl().unwrap(), 42);
+        assert!(socket.broadcast().unwrap());
+    }
+
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    #[tokio::test]
+    async fn test_udp_socket_builder_reuse_port() {
+        let mut builder = UdpSocket::build();
+        builder.reuse_port(true);
+
+        let a = builder.bind("127.0.0.1:0").await.unwrap();
+        let b = builder.bind(a.local_addr().unwrap()).await.unwrap();
+        assert_eq!(a.local_addr().unwrap(), b.local_addr().unwrap());
+    }
+}
@@ -1,5 +1,9 @@
 //! latency utilities and common types
 
+use parking_lot::Mutex;
+use std::{fmt, time::Duration};
+use tokio::time::Instant;
+
 /// The latency unit used to report latencies by various parts of the Rama codebase.
 #[derive(Copy, Clone, Debug)]
 pub enum LatencyUnit {
@@ -12,3 +16,173 @@ pub enum LatencyUnit {
     /// Use nanoseconds.
     Nanos,
 }
+
+/// A histogram of recorded latencies, used to estimate latency percentiles,
+/// e.g. to decide when a request is slow compared to its peers.
+///
+/// Latencies are recorded with a precision of 25%, in microseconds,
+/// and the recorded latencies are rotated every period:
+/// percentiles are computed over the latencies recorded in the previous (complete) period,
+/// such that the estimate adapts over time and is not skewed by a partial period.
+pub struct LatencyHistogram {
+    period: Duration,
+    state: Mutex<LatencyHistogramState>,
+}
+
+struct LatencyHistogramState {
+    rotated_at: Instant,
+    read: Buckets,
+    write: Buckets,
+}
+
+struct Buckets {
+    counts: [u64; BUCKET_COUNT],
+    total: u64,
+}
+
+const BUCKET_COUNT: usize = 252;
+
+impl Buckets {
+    const fn new() -> Self {
+        Self {
+            counts: [0; BUCKET_COUNT],
+            total: 0,
+        }
+    }
+
+    /// Index of the bucket, using 4 sub buckets per power of two.
+    fn index(micros: u64) -> usize {
+        if micros < 4 {
+            return micros as usize;
+        }
+        let exp = 63 - micros.leading_zeros() as usize;
+        let sub = ((micros >> (exp - 2)) & 3) as usize;
+        (exp - 1) * 4 + sub
+    }
+
+    /// Highest value (in microseconds) recorded in the bucket at the given index.
+    fn upper_bound(index: usize) -> u64 {
+        if index < 4 {
+            return index as u64;
+        }
+        let exp = index / 4 + 1;
+        let sub = (index % 4) as u64;
+        let lower = (4 + sub) << (exp - 2);
+        lower + ((1 << (exp - 2)) - 1)
+    }
+
+    fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.total == 0 {
+            return None;
+        }
+        let target = ((percentile.clamp(0.0, 1.0) * self.total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return Some(Duration::from_micros(Self::upper_bound(index)));
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Debug for LatencyHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LatencyHistogram")
+            .field("period", &self.period)
+            .field("samples", &self.samples())
+            .finish()
+    }
+}
+
+impl LatencyHistogram {
+    /// Create a new [`LatencyHistogram`], rotating the recorded latencies every period.
+    pub fn new(period: Duration) -> Self {
+        Self {
+            period,
+            state: Mutex::new(LatencyHistogramState {
+                rotated_at: Instant::now(),
+                read: Buckets::new(),
+                write: Buckets::new(),
+            }),
+        }
+    }
+
+    /// Record a latency.
+    pub fn record(&self, latency: Duration) {
+        let micros = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        let mut state = self.lock();
+        state.write.counts[Buckets::index(micros)] += 1;
+        state.write.total += 1;
+    }
+
+    /// Returns the amount of latencies in the previous period,
+    /// on which the percentiles are based.
+    pub fn samples(&self) -> u64 {
+        self.lock().read.total
+    }
+
+    /// Returns the latency at the given percentile (between `0.0` and `1.0`)
+    /// of the previous period, or `None` in case no latencies were recorded in that period.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        self.lock().read.percentile(percentile)
+    }
+
+    fn lock(&self) -> parking_lot::MutexGuard<'_, LatencyHistogramState> {
+        let mut state = self.state.lock();
+        let elapsed = state.rotated_at.elapsed();
+        if elapsed >= self.period {
+            state.read = if elapsed >= self.period * 2 {
+                // nothing recorded during the last complete period
+                Buckets::new()
+            } else {
+                std::mem::replace(&mut state.write, Buckets::new())
+            };
+            state.write = Buckets::new();
+            state.rotated_at = Instant::now();
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets_index_upper_bound() {
+        for micros in [0, 1, 3, 4, 5, 7, 8, 9, 100, 1_000, 123_456, u64::MAX] {
+            let index = Buckets::index(micros);
+            assert!(index < BUCKET_COUNT);
+            let upper = Buckets::upper_bound(index);
+            assert!(upper >= micros, "{micros}");
+            // precision of 25%
+            assert!(upper - micros <= micros / 4, "{micros}");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_latency_histogram() {
+        let histogram = LatencyHistogram::new(Duration::from_secs(1));
+        for millis in 1..=100 {
+            histogram.record(Duration::from_millis(millis));
+        }
+        // percentiles are only available for the previous period
+        assert_eq!(histogram.samples(), 0);
+        assert_eq!(histogram.percentile(0.9), None);
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(histogram.samples(), 100);
+        let p90 = histogram.percentile(0.9).unwrap();
+        assert!(p90 >= Duration::from_millis(90), "{p90:?}");
+        assert!(p90 <= Duration::from_millis(113), "{p90:?}");
+        let p50 = histogram.percentile(0.5).unwrap();
+        assert!(p50 >= Duration::from_millis(50), "{p50:?}");
+        assert!(p50 <= Duration::from_millis(63), "{p50:?}");
+
+        // stale latencies are forgotten
+        tokio::time::advance(Duration::from_secs(2)).await;
+        assert_eq!(histogram.samples(), 0);
+    }
+}
@@ -15,13 +15,13 @@
 //!
 //! | category | support list |
 //! |-|-|
-//! | ✅ [transports](crate::net::stream) | ✅ [tcp] ⸱ 🏗️ udp <sup>(1)</sup> ⸱ ✅ [middleware](crate::net::stream::layer) |
+//! | ✅ [transports](crate::net::stream) | ✅ [tcp] ⸱ ✅ [udp] ⸱ ✅ [middleware](crate::net::stream::layer) |
 //! | ✅ [http] | ✅ [auto](crate::http::server::service::HttpServer::auto) ⸱ ✅ [http/1.1](crate::http::server::service::HttpServer::http1) ⸱ ✅ [h2](crate::http::server::service::HttpServer::h2) ⸱ 🏗️ h3 <sup>(1)</sup> ⸱ ✅ [middleware](crate::http::layer) |
 //! | ✅ web server | ✅ [fs](crate::http::service::fs) ⸱ ✅ [redirect](crate::http::service::redirect::Redirect) ⸱ ✅ [dyn router](crate::http::service::web::WebService) ⸱ ✅ [static router](crate::http::service::web::match_service) ⸱ ✅ [handler extractors](crate::http::service::web::extract) ⸱ ✅ [k8s healthcheck](crate::http::service::web::k8s) |
 //! | ✅ [http client](crate::http::client) | ✅ [client](crate::http::client::HttpClient) ⸱ ✅ [high level API](crate::http::service::client::HttpClientExt) ⸱ ✅ [Proxy Connect](crate::http::client::proxy::layer::HttpProxyConnector) ⸱ ❌ [Chromium Http](https://github.com/plabayo/rama/issues/189) <sup>(3)</sup> |
 //! | ✅ [tls] | ✅ [Rustls](crate::tls::rustls) ⸱ ✅ [BoringSSL](crate::tls::boring) ⸱ ❌ NSS <sup>(3)</sup> |
 //! | ✅ [dns] | ✅ [DNS Resolver][crate::dns::DnsResolver] |
-//! | ✅ [proxy] protocols | ✅ [PROXY protocol](crate::proxy::haproxy) ⸱ ✅ [http proxy](https://github.com/plabayo/rama/blob/main/examples/http_connect_proxy.rs) ⸱ ✅ [https proxy](https://github.com/plabayo/rama/blob/main/examples/https_connect_proxy.rs) ⸱ 🏗️ SOCKS5 <sup>(1)</sup> ⸱ 🏗️ SOCKS5H <sup>(1)</sup> |
+//! | ✅ [proxy] protocols | ✅ [PROXY protocol](crate::proxy::haproxy) ⸱ ✅ [http proxy](https://github.com/plabayo/rama/blob/main/examples/http_connect_proxy.rs) ⸱ ✅ [https proxy](https://github.com/plabayo/rama/blob/main/examples/https_connect_proxy.rs) ⸱ ✅ [SOCKS5](crate::proxy::socks5) ⸱ ✅ [SOCKS5H](crate::proxy::socks5) |
 //! | 🏗️ web protocols | 🏗️ Web Sockets (WS) <sup>(2)</sup> ⸱ 🏗️ WSS <sup>(2)</sup> ⸱ ❌ Web Transport <sup>(3)</sup> ⸱ ❌ gRPC <sup>(3)</sup> |
 //! | ✅ [async-method trait](https://blog.rust-lang.org/inside-rust/2023/05/03/stabilizing-async-fn-in-trait.html) services | ✅ [Service] ⸱ ✅ [Layer] ⸱ ✅ [context] ⸱ ✅ [dyn dispatch](crate::service::BoxService) ⸱ ✅ [middleware](crate::layer) |
 //! | ✅ [telemetry] | ✅ [tracing](https://tracing.rs/tracing/) ⸱ ✅ [opentelemetry][telemetry::opentelemetry] ⸱ ✅ [http metrics](crate::http::layer::opentelemetry) ⸱ ✅ [transport metrics](crate::net::stream::layer::opentelemetry) |
@@ -46,7 +46,7 @@
 //! - 🚦 [Reverse proxies](https://ramaproxy.org/book/proxies/reverse);
 //! - 🔓 [TLS Termination proxies](https://ramaproxy.org/book/proxies/tls);
 //! - 🌐 [HTTP(S) proxies](https://ramaproxy.org/book/proxies/http);
-//! - 🧦 [SOCKS5 proxies](https://ramaproxy.org/book/proxies/socks5) (will be implemented in `v0.3`);
+//! - 🧦 [SOCKS5 proxies](https://ramaproxy.org/book/proxies/socks5);
 //! - 🔎 [MITM proxies](https://ramaproxy.org/book/proxies/mitm);
 //! - 🕵️‍♀️ [Distortion proxies](https://ramaproxy.org/book/proxies/distort).
 //!
@@ -144,9 +144,11 @@
 //! - [`rama-net`](https://crates.io/crates/rama-net): rama network types and utilities
 //! - [`rama-dns`](https://crates.io/crates/rama-dns): DNS support for rama
 //! - [`rama-tcp`](https://crates.io/crates/rama-tcp): TCP support for rama
+//! - [`rama-udp`](https://crates.io/crates/rama-udp): UDP support for rama
 //! - [`rama-tls`](https://crates.io/crates/rama-tls): TLS support for rama (types, `rustls` and `boring`)
 //! - [`rama-proxy`](https://crates.io/crates/rama-proxy): proxy types and utilities for rama
 //! - [`rama-haproxy`](https://crates.io/crates/rama-haproxy): rama HaProxy support
+//! - [`rama-socks5`](https://crates.io/crates/rama-socks5): SOCKS5 support for rama
 //! - [`rama-ua`](https://crates.io/crates/rama-ua): User-Agent (UA) support for `rama`
 //! - [`rama-http-types`](https://crates.io/crates/rama-http-types): http types and utilities
 //! - [`rama-http`](https://crates.io/crates/rama-http): rama http services, layers and utilities
@@ -320,14 +322,18 @@
 
 #[doc(inline)]
 pub use ::rama_core::{
-    combinators, context, error, graceful, layer, matcher, rt, service, username, Context, Layer,
-    Service,
+    combinators, context, error, graceful, layer, matcher, reload, rt, service, username, Context,
+    Layer, Service,
 };
 
 #[cfg(feature = "tcp")]
 #[doc(inline)]
 pub use ::rama_tcp as tcp;
 
+#[cfg(feature = "udp")]
+#[doc(inline)]
+pub use ::rama_udp as udp;
+
 #[cfg(feature = "telemetry")]
 #[doc(inline)]
 pub use ::rama_core::telemetry;
@@ -347,7 +353,7 @@ pub use ::rama_net as net;
 #[cfg(feature = "http")]
 pub mod http;
 
-#[cfg(any(feature = "proxy", feature = "haproxy"))]
+#[cfg(any(feature = "proxy", feature = "haproxy", feature = "socks5"))]
 pub mod proxy {
     //! rama proxy support
 
@@ -358,6 +364,10 @@ pub mod proxy {
     #[cfg(feature = "haproxy")]
     #[doc(inline)]
     pub use ::rama_haproxy as haproxy;
+
+    #[cfg(feature = "socks5")]
+    #[doc(inline)]
+    pub use ::rama_socks5 as socks5;
 }
 
 #[cfg(feature = "ua")]
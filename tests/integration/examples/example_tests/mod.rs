@@ -26,6 +26,9 @@ mod mtls_tunnel_and_service;
 #[cfg(feature = "rustls")]
 mod tls_termination;
 
+#[cfg(feature = "tls")]
+mod tls_sni_router;
+
 // TODO: enable again in future,
 // does not work for now, not sure why...
 // Running example manually does work via curl,
@@ -0,0 +1,68 @@
+use super::utils;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// A TLS record containing a minimal ClientHello with the given server name.
+fn client_hello_record(server_name: &str) -> Vec<u8> {
+    let name = server_name.as_bytes();
+    let mut extensions = vec![0x00, 0x00];
+    extensions.extend_from_slice(&(name.len() as u16 + 5).to_be_bytes());
+    extensions.extend_from_slice(&(name.len() as u16 + 3).to_be_bytes());
+    extensions.push(0x00);
+    extensions.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    extensions.extend_from_slice(name);
+
+    let mut body = vec![0x03, 0x03];
+    body.extend_from_slice(&[0x42; 32]);
+    body.extend_from_slice(&[0x00, 0x00, 0x02, 0x13, 0x01, 0x01, 0x00]);
+    body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+    body.extend_from_slice(&extensions);
+
+    let mut handshake = vec![0x01];
+    handshake.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+    handshake.extend_from_slice(&body);
+
+    let mut record = vec![0x16, 0x03, 0x01];
+    record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+    record.extend_from_slice(&handshake);
+    record
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_tls_sni_router() {
+    utils::init_tracing();
+
+    let runner = utils::ExampleRunner::interactive("tls_sni_router", Some("tls"));
+
+    let mut stream = None;
+    for i in 0..5 {
+        match runner.connect_tcp("127.0.0.1:62040").await {
+            Ok(s) => {
+                stream = Some(s);
+                break;
+            }
+            Err(e) => {
+                eprintln!("connect_tcp error: {}", e);
+                tokio::time::sleep(std::time::Duration::from_millis(500 + 250 * i)).await;
+            }
+        }
+    }
+    let mut stream = stream.expect("connect to sni router");
+
+    // routed to the echo upstream, ClientHello included
+    let record = client_hello_record("echo.localhost");
+    stream.write_all(&record).await.unwrap();
+    let mut buf = vec![0; record.len()];
+    stream.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, record);
+
+    // unknown server name: closed by the router
+    let mut stream = runner.connect_tcp("127.0.0.1:62040").await.unwrap();
+    stream
+        .write_all(&client_hello_record("example.com"))
+        .await
+        .unwrap();
+    let mut buf = Vec::new();
+    stream.read_to_end(&mut buf).await.unwrap();
+    assert!(buf.is_empty());
+}